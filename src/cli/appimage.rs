@@ -0,0 +1,148 @@
+//! AppImage payloads
+//!
+//! A type 2 AppImage is an ELF runtime followed by a squashfs image of the
+//! app. The image is unpacked with `unsquashfs` at the offset where the
+//! runtime ends, so the AppImage itself never runs outside the sandbox and
+//! no FUSE is needed inside it. The unpacked app starts through `AppRun`.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Entry point of an unpacked AppImage
+pub const APPIMAGE_ENTRY: &str = "AppRun";
+
+/// Magic bytes at offset 8 of a type 2 AppImage
+const APPIMAGE_MAGIC: &[u8] = b"AI\x02";
+
+/// Whether a file is a type 2 AppImage
+pub fn is_appimage(path: &Path) -> bool {
+    let mut header = [0u8; 11];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok_and(|()| header.starts_with(b"\x7fELF") && &header[8..] == APPIMAGE_MAGIC)
+}
+
+/// Offset of the squashfs image: the end of the runtime's section headers
+fn squashfs_offset(path: &Path) -> std::io::Result<u64> {
+    let mut header = [0u8; 64];
+    File::open(path)?.read_exact(&mut header)?;
+    let big_endian = header[5] == 2;
+    let read = |range: std::ops::Range<usize>| {
+        let bytes = &header[range];
+        let mut value = 0u64;
+        if big_endian {
+            for b in bytes {
+                value = (value << 8) | *b as u64;
+            }
+        } else {
+            for b in bytes.iter().rev() {
+                value = (value << 8) | *b as u64;
+            }
+        }
+        value
+    };
+    let (shoff, shentsize, shnum) = if header[4] == 2 {
+        (read(0x28..0x30), read(0x3A..0x3C), read(0x3C..0x3E))
+    } else {
+        (read(0x20..0x24), read(0x2E..0x30), read(0x30..0x32))
+    };
+    Ok(shoff + shentsize * shnum)
+}
+
+fn unsquashfs(appimage: &Path) -> std::io::Result<Command> {
+    if which::which("unsquashfs").is_err() {
+        return Err(std::io::Error::other(
+            "unsquashfs is needed to unpack AppImages (install squashfs-tools)",
+        ));
+    }
+    let mut command = Command::new("unsquashfs");
+    command
+        .arg("-o")
+        .arg(squashfs_offset(appimage)?.to_string());
+    Ok(command)
+}
+
+/// Paths of the files at the top level of an AppImage
+pub fn appimage_top_level(appimage: &Path) -> std::io::Result<Vec<String>> {
+    let output = unsquashfs(appimage)?
+        .arg("-l")
+        .arg(appimage)
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "unsquashfs could not read {}",
+            appimage.display()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        // "squashfs-root/AppRun"
+        .filter_map(|line| line.split_once('/').map(|(_, path)| path))
+        .filter(|path| !path.is_empty() && !path.contains('/'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Read one top-level file of an AppImage
+pub fn read_appimage_file(appimage: &Path, name: &str) -> std::io::Result<String> {
+    let scratch = std::env::temp_dir().join(format!("voidbox-appimage-{}", std::process::id()));
+    if scratch.exists() {
+        fs::remove_dir_all(&scratch)?;
+    }
+    let status = unsquashfs(appimage)?
+        .args(["-q", "-n", "-d"])
+        .arg(&scratch)
+        .arg(appimage)
+        .arg(name)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    let content = fs::read_to_string(scratch.join(name));
+    fs::remove_dir_all(&scratch).ok();
+    if !status.success() {
+        return Err(std::io::Error::other(format!(
+            "unsquashfs could not extract {}",
+            name
+        )));
+    }
+    content
+}
+
+/// Unpack an AppImage's contents into `target`
+pub fn extract_appimage(appimage: &Path, target: &Path) -> std::io::Result<()> {
+    // unsquashfs wants to create its destination itself
+    let unpacked: PathBuf = target.with_extension("unpack");
+    if unpacked.exists() {
+        fs::remove_dir_all(&unpacked)?;
+    }
+    let status = unsquashfs(appimage)?
+        .args(["-q", "-n", "-d"])
+        .arg(&unpacked)
+        .arg(appimage)
+        .stdout(Stdio::null())
+        .status()?;
+    if !status.success() {
+        fs::remove_dir_all(&unpacked).ok();
+        return Err(std::io::Error::other(format!(
+            "unsquashfs could not unpack {}",
+            appimage.display()
+        )));
+    }
+
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(&unpacked)? {
+        let entry = entry?;
+        let destination = target.join(entry.file_name());
+        if destination.is_dir() {
+            fs::remove_dir_all(&destination)?;
+        } else if fs::symlink_metadata(&destination).is_ok() {
+            fs::remove_file(&destination)?;
+        }
+        fs::rename(entry.path(), destination)?;
+    }
+    fs::remove_dir(&unpacked)?;
+    Ok(())
+}
@@ -0,0 +1,287 @@
+//! Backup command implementation
+//!
+//! Backs up the data of apps marked for backup to a directory of archives
+//! or a restic repository. Backups are consistent: a running app's data is
+//! read from an instant btrfs snapshot, or the app is stopped for the
+//! backup and started again when asked to; otherwise it is skipped.
+
+use crate::runtime::{is_app_running, running_instances, stop_instance};
+use crate::settings::{BackupConfig, load_config, save_config};
+use crate::storage::{
+    DataSnapshot, archive_dir, delete_snapshot, instant_snapshots, paths, snapshot_app_data,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error("No backup destination: pass --to or set [backup] to in config")]
+    NoDestination,
+
+    #[error("{0} is running; pass --stop-running to stop it during the backup")]
+    Running(String),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Settings error: {0}")]
+    SettingsError(#[from] crate::settings::SettingsError),
+
+    #[error("Snapshot error: {0}")]
+    SnapshotError(#[from] crate::storage::SnapshotError),
+
+    #[error("Backup failed: {0}")]
+    Failed(String),
+}
+
+/// Prefix of restic repository destinations
+const RESTIC_PREFIX: &str = "restic:";
+
+/// How long a stopped app gets to exit before the backup gives up
+const STOP_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Where backups are written
+enum Destination {
+    /// Directory receiving one `<app>-<time>.tar.gz` per backup
+    Directory(PathBuf),
+    /// restic repository, as passed to `restic -r`
+    Restic(String),
+}
+
+impl Destination {
+    fn parse(to: &str) -> Self {
+        match to.strip_prefix(RESTIC_PREFIX) {
+            Some(repository) => Destination::Restic(repository.to_string()),
+            None => Destination::Directory(PathBuf::from(to)),
+        }
+    }
+}
+
+/// Mark or unmark an app's data for backup
+pub fn backup_mark(app_name: &str, marked: bool) -> Result<(), BackupError> {
+    if marked && !paths::manifest_path(app_name).exists() {
+        return Err(BackupError::NotInstalled(app_name.to_string()));
+    }
+
+    let mut config = load_config()?;
+    let listed = config
+        .backup
+        .apps
+        .iter()
+        .any(|a| paths::same_app(a, app_name));
+    if marked && !listed {
+        config.backup.apps.push(app_name.to_string());
+    } else if !marked && listed {
+        config.backup.apps.retain(|a| !paths::same_app(a, app_name));
+    }
+    save_config(&config)?;
+
+    if marked {
+        say!("[voidbox] The data of {} will be backed up", app_name);
+    } else {
+        say!(
+            "[voidbox] The data of {} will no longer be backed up",
+            app_name
+        );
+    }
+    Ok(())
+}
+
+/// Back up the data of `apps`, or of the apps marked for backup
+pub fn backup_data(
+    to: Option<&str>,
+    apps: &[String],
+    stop_running: bool,
+) -> Result<(), BackupError> {
+    let config = load_config()?.backup;
+    let apps = if apps.is_empty() {
+        config.apps.clone()
+    } else {
+        apps.to_vec()
+    };
+    if apps.is_empty() {
+        say!("No apps are marked for backup.");
+        say!("Mark one with: voidbox backup mark <app>");
+        return Ok(());
+    }
+
+    let mut failed = Vec::new();
+    for app_name in &apps {
+        match back_up_app(app_name, to, stop_running, &config) {
+            Ok(true) => {}
+            Ok(false) => say!("[voidbox] {} has no data yet", app_name),
+            Err(e @ BackupError::NoDestination) => return Err(e),
+            Err(e) => {
+                say!("[voidbox] Could not back up {}: {}", app_name, e);
+                failed.push(app_name.clone());
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(BackupError::Failed(format!(
+            "{} of {} apps not backed up ({})",
+            failed.len(),
+            apps.len(),
+            failed.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Back up one app's data; returns false if it has none
+pub(crate) fn back_up_app(
+    app_name: &str,
+    to: Option<&str>,
+    stop_running: bool,
+    config: &BackupConfig,
+) -> Result<bool, BackupError> {
+    let destination = to
+        .or(config.to.as_deref())
+        .map(Destination::parse)
+        .ok_or(BackupError::NoDestination)?;
+    if !paths::app_dir(app_name).exists() {
+        return Err(BackupError::NotInstalled(app_name.to_string()));
+    }
+    let data_dir = paths::app_data_dir(app_name);
+    if !data_dir.is_dir() {
+        return Ok(false);
+    }
+
+    say!("[voidbox] Backing up the data of {}...", app_name);
+
+    // A running app's files may be mid-write: back up a frozen copy, or
+    // stop the app for the duration
+    let mut snapshot: Option<DataSnapshot> = None;
+    let mut stopped = false;
+    if is_app_running(app_name) {
+        if instant_snapshots(app_name) {
+            snapshot = snapshot_app_data(app_name)?;
+        } else if stop_running {
+            stop_app(app_name)?;
+            stopped = true;
+        } else {
+            return Err(BackupError::Running(app_name.to_string()));
+        }
+    }
+
+    let source = snapshot
+        .as_ref()
+        .map(|s| s.path.clone())
+        .unwrap_or(data_dir);
+    let result = match &destination {
+        Destination::Directory(dir) => back_up_to_directory(app_name, &source, dir),
+        Destination::Restic(repository) => back_up_to_restic(
+            app_name,
+            &source,
+            repository,
+            config.restic_password_file.as_deref(),
+        ),
+    };
+
+    if let Some(snapshot) = &snapshot
+        && let Err(e) = delete_snapshot(snapshot)
+    {
+        say!(
+            "[voidbox] Warning: Could not remove temporary snapshot {}: {}",
+            snapshot.path.display(),
+            e
+        );
+    }
+    if stopped {
+        restart_app(app_name);
+    }
+
+    result.map(|()| true)
+}
+
+/// Stop all instances of an app and wait for them to exit
+fn stop_app(app_name: &str) -> Result<(), BackupError> {
+    say!("[voidbox] Stopping {} for the backup...", app_name);
+    for instance in running_instances()
+        .iter()
+        .filter(|i| paths::same_app(&i.app, app_name))
+    {
+        stop_instance(instance);
+    }
+
+    let started = Instant::now();
+    while is_app_running(app_name) {
+        if started.elapsed() > STOP_TIMEOUT {
+            return Err(BackupError::Failed(format!(
+                "{} did not exit in time",
+                app_name
+            )));
+        }
+        std::thread::sleep(Duration::from_millis(250));
+    }
+    Ok(())
+}
+
+/// Start an app stopped for a backup again, detached from this process
+fn restart_app(app_name: &str) {
+    let started = std::env::current_exe().and_then(|exe| {
+        Command::new(exe)
+            .args(["run", app_name])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    });
+    match started {
+        Ok(_) => say!("[voidbox] Started {} again", app_name),
+        Err(e) => say!("[voidbox] Warning: Could not restart {}: {}", app_name, e),
+    }
+}
+
+fn back_up_to_directory(app_name: &str, source: &Path, dir: &Path) -> Result<(), BackupError> {
+    fs::create_dir_all(dir)?;
+    let archive = dir.join(format!(
+        "{}-{}.tar.gz",
+        paths::app_slug(app_name),
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    archive_dir(source, &archive)?;
+    say!("[voidbox] Wrote {}", archive.display());
+    Ok(())
+}
+
+fn back_up_to_restic(
+    app_name: &str,
+    source: &Path,
+    repository: &str,
+    password_file: Option<&str>,
+) -> Result<(), BackupError> {
+    if which::which("restic").is_err() {
+        return Err(BackupError::Failed("restic is not installed".to_string()));
+    }
+
+    let mut command = Command::new("restic");
+    command.args(["-r", repository]);
+    if let Some(file) = password_file {
+        command.args(["--password-file", file]);
+    }
+    let status = command
+        .args([
+            "backup",
+            "--tag",
+            "voidbox",
+            "--tag",
+            &paths::app_slug(app_name),
+        ])
+        .arg(source)
+        .status()?;
+    if !status.success() {
+        return Err(BackupError::Failed(format!(
+            "restic exited with {}",
+            status
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Cache command implementation
+
+use crate::settings::load_config;
+use crate::storage::{
+    CacheError, metadata_cache_usage, parse_size, prune_cache, prune_metadata_cache, serve_cache,
+    verify_cache,
+};
+use indicatif::HumanBytes;
+
+/// Re-hash every cached download and evict corrupt entries
+pub fn cache_verify() -> Result<(), CacheError> {
+    say!("[voidbox] Verifying download cache...");
+
+    let report = verify_cache()?;
+
+    for url in &report.evicted {
+        say!("  evicted: {}", url);
+    }
+
+    if report.orphans_removed > 0 {
+        say!(
+            "[voidbox] Removed {} orphaned file(s)",
+            report.orphans_removed
+        );
+    }
+
+    say!(
+        "[voidbox] Checked {} entries: {} ok, {} evicted",
+        report.checked,
+        report.ok,
+        report.evicted.len()
+    );
+
+    Ok(())
+}
+
+/// Evict least-recently-used downloads until the cache fits in `max_size`
+/// (the configured limit if not given)
+pub fn cache_prune(max_size: Option<&str>) -> Result<(), CacheError> {
+    let max_size = match max_size {
+        Some(size) => size.to_string(),
+        None => load_config()?.cache.max_size,
+    };
+
+    let limit = parse_size(&max_size).ok_or_else(|| CacheError::InvalidSize(max_size.clone()))?;
+
+    let report = prune_cache(limit)?;
+
+    for url in &report.evicted {
+        say!("  evicted: {}", url);
+    }
+
+    say!(
+        "[voidbox] Freed {}, cache is now {} (limit {})",
+        HumanBytes(report.freed),
+        HumanBytes(report.remaining),
+        HumanBytes(limit)
+    );
+
+    Ok(())
+}
+
+/// Share the download cache with LAN peers until interrupted
+pub fn cache_serve(port: Option<u16>) -> Result<(), CacheError> {
+    let port = match port {
+        Some(port) => port,
+        None => load_config()?.lan_cache.port,
+    };
+
+    serve_cache(port)?;
+    Ok(())
+}
+
+/// Drop expired (or, with `clear`, all) cached icons and metadata and show
+/// what remains
+pub fn cache_metadata(clear: bool) -> Result<(), CacheError> {
+    let (removed, freed) = prune_metadata_cache(clear)?;
+    if removed > 0 {
+        say!(
+            "[voidbox] Removed {} cached file(s), freed {}",
+            removed,
+            HumanBytes(freed)
+        );
+    }
+
+    let (count, size) = metadata_cache_usage()?;
+    say!(
+        "[voidbox] Metadata cache: {} file(s), {}",
+        count,
+        HumanBytes(size)
+    );
+    Ok(())
+}
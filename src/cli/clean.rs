@@ -0,0 +1,187 @@
+//! Clean command implementation
+//!
+//! Removes what installs, updates and restores leave behind when they are
+//! interrupted or simply never cleaned up: downloads that haven't been used
+//! for `[cache] max_age`, interrupted downloads as old, incomplete staged
+//! updates that can no longer be continued, and temporary extraction and
+//! swap directories. A temporary `<dir>.old` or `<dir>.restore` is only
+//! removed while `<dir>` itself exists, so an interrupted swap never loses
+//! the only copy.
+
+use crate::cli::maintain::ABANDONED_INSTALL_AGE;
+use crate::cli::removed::{prune_removed, removal_keep};
+use crate::runtime::is_app_running;
+use crate::settings::load_config;
+use crate::storage::{CacheError, clean_cache, parse_age, paths, prune_metadata_cache};
+use indicatif::HumanBytes;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CleanError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Cache error: {0}")]
+    CacheError(#[from] CacheError),
+
+    #[error("Settings error: {0}")]
+    SettingsError(#[from] crate::settings::SettingsError),
+
+    #[error("Invalid age: {0} (expected e.g. 30d, 2w, 12h)")]
+    InvalidAge(String),
+}
+
+/// Temporary directories younger than this may belong to a running operation
+const IN_PROGRESS_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Extensions of temporary siblings in an app's directory, made while
+/// swapping in a staged rootfs or restored data
+const SWAP_EXTENSIONS: &[&str] = &["old", "restore"];
+
+/// Extensions of temporary directories next to an app's payload, made while
+/// unpacking an AppImage or restoring optimized originals
+const PAYLOAD_EXTENSIONS: &[&str] = &["unpack", "originals"];
+
+/// Total size of the files under a path
+pub(crate) fn tree_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn older_than(path: &Path, age: Duration) -> bool {
+    fs::symlink_metadata(path)
+        .and_then(|m| m.modified())
+        .is_ok_and(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .is_ok_and(|a| a >= age)
+        })
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .is_some_and(|e| extensions.iter().any(|x| e == *x))
+}
+
+/// Leftover temporary paths in one app's directory
+fn app_leftovers(app_dir: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+
+    // An incomplete staged update is continued from its journal for a
+    // while, like an interrupted install
+    let staged = app_dir.join("staged");
+    let journal = staged.join("install-journal.json");
+    if staged.is_dir()
+        && !staged.join("staged.json").exists()
+        && older_than(&staged, IN_PROGRESS_AGE)
+        && (!journal.exists() || older_than(&journal, ABANDONED_INSTALL_AGE))
+    {
+        found.push(staged);
+    }
+
+    if let Ok(entries) = fs::read_dir(app_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if has_extension(&path, SWAP_EXTENSIONS) && path.with_extension("").exists() {
+                found.push(path);
+            }
+        }
+    }
+
+    if let Ok(entries) = fs::read_dir(app_dir.join("snapshots")) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if has_extension(&path, &["partial"]) && older_than(&path, IN_PROGRESS_AGE) {
+                found.push(path);
+            }
+        }
+    }
+
+    if let Ok(entries) = fs::read_dir(app_dir.join("rootfs/opt")) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if has_extension(&path, PAYLOAD_EXTENSIONS) && older_than(&path, IN_PROGRESS_AGE) {
+                found.push(path);
+            }
+        }
+    }
+
+    found
+}
+
+fn remove_path(path: &Path) -> std::io::Result<u64> {
+    let size = tree_size(path);
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(size)
+}
+
+/// Remove stale downloads and temporary directories, reporting the space
+/// reclaimed
+pub fn clean(max_age: Option<&str>) -> Result<(), CleanError> {
+    let max_age = match max_age {
+        Some(age) => age.to_string(),
+        None => load_config()?.cache.max_age,
+    };
+    let age = parse_age(&max_age).ok_or_else(|| CleanError::InvalidAge(max_age.clone()))?;
+    let mut freed = 0;
+
+    say!(
+        "[voidbox] Removing downloads not used in the last {}...",
+        max_age
+    );
+    let report = clean_cache(age)?;
+    for url in &report.evicted {
+        say!("  removed: {}", url);
+    }
+    if report.partials > 0 {
+        say!("  removed {} interrupted download(s)", report.partials);
+    }
+    freed += report.freed;
+
+    say!("[voidbox] Removing temporary directories...");
+    if let Ok(entries) = fs::read_dir(paths::apps_dir()) {
+        for entry in entries.flatten() {
+            let app_dir = entry.path();
+            let app_name = entry.file_name().to_string_lossy().into_owned();
+            if !app_dir.is_dir() || is_app_running(&app_name) {
+                continue;
+            }
+            for path in app_leftovers(&app_dir) {
+                match remove_path(&path) {
+                    Ok(size) => {
+                        say!("  removed: {} ({})", path.display(), HumanBytes(size));
+                        freed += size;
+                    }
+                    Err(e) => say!(
+                        "[voidbox] Warning: Could not remove {}: {}",
+                        path.display(),
+                        e
+                    ),
+                }
+            }
+        }
+    }
+
+    say!("[voidbox] Deleting expired removals...");
+    for app in prune_removed(removal_keep())? {
+        say!("  deleted: removed {}", app);
+    }
+
+    let (_, metadata_freed) = prune_metadata_cache(false)?;
+    freed += metadata_freed;
+
+    say!("[voidbox] Reclaimed {}", HumanBytes(freed));
+    Ok(())
+}
@@ -0,0 +1,216 @@
+//! Install conflict detection and resolution
+//!
+//! Before installing, voidbox checks for things it would otherwise replace
+//! silently: the app already being installed, a command wrapper in
+//! ~/.local/bin that voidbox didn't write, and a desktop entry that doesn't
+//! launch this app. How to resolve them comes from `--overwrite`, `--skip` or
+//! `--abort`, or is asked interactively (terminal prompt or GUI chooser).
+
+use crate::cli::install::InstallError;
+use crate::cli::pipeline::has_interrupted_install;
+use crate::manifest::AppManifest;
+use crate::storage::paths;
+use std::fs;
+use std::path::PathBuf;
+
+/// Something an install would replace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// The app is already installed
+    AlreadyInstalled {
+        installed: Option<String>,
+        requested: Option<String>,
+    },
+    /// The name is the alias of a different app (by app ID)
+    OtherApp { name: String, id: String },
+    /// A command wrapper not written by voidbox
+    Wrapper(PathBuf),
+    /// A desktop entry that doesn't launch this app
+    DesktopEntry(PathBuf),
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Conflict::AlreadyInstalled {
+                installed,
+                requested,
+            } => {
+                let installed = installed.as_deref().unwrap_or("unknown version");
+                match requested {
+                    Some(requested) => write!(
+                        f,
+                        "already installed (v{}, installing v{})",
+                        installed, requested
+                    ),
+                    None => write!(f, "already installed (v{})", installed),
+                }
+            }
+            Conflict::OtherApp { name, id } => {
+                write!(f, "'{}' is already used by a different app ({})", name, id)
+            }
+            Conflict::Wrapper(path) => {
+                write!(
+                    f,
+                    "{} exists and was not created by voidbox",
+                    path.display()
+                )
+            }
+            Conflict::DesktopEntry(path) => {
+                write!(f, "{} exists and launches something else", path.display())
+            }
+        }
+    }
+}
+
+/// How conflicts should be resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Ask the user (abort if nobody can be asked)
+    #[default]
+    Ask,
+    /// Replace whatever is in the way
+    Overwrite,
+    /// Keep existing files; an already installed app is left alone
+    Skip,
+    /// Fail the install
+    Abort,
+}
+
+impl ConflictPolicy {
+    /// Policy from the `--overwrite`/`--skip`/`--abort` command line flags
+    pub fn from_flags(overwrite: bool, skip: bool, abort: bool) -> Self {
+        if overwrite {
+            ConflictPolicy::Overwrite
+        } else if skip {
+            ConflictPolicy::Skip
+        } else if abort {
+            ConflictPolicy::Abort
+        } else {
+            ConflictPolicy::Ask
+        }
+    }
+}
+
+/// Find everything installing this manifest would replace
+pub fn detect_conflicts(manifest: &AppManifest) -> Vec<Conflict> {
+    let app_name = &manifest.app.name;
+    let slug = paths::app_slug(app_name);
+    let mut conflicts = Vec::new();
+
+    if paths::app_dir(app_name).exists() {
+        let record = crate::cli::get_installed_apps().ok().and_then(|apps| {
+            apps.into_iter()
+                .find(|a| paths::same_app(&a.name, app_name))
+        });
+        match record {
+            // An interrupted first install isn't installed yet; rerunning resumes it
+            None if has_interrupted_install(app_name) => {}
+            // Installs from before app IDs have none; adopt the new one
+            Some(record) if record.id.is_some() && record.id != manifest.app.id => {
+                conflicts.push(Conflict::OtherApp {
+                    name: record.name,
+                    id: record.id.unwrap_or_default(),
+                });
+            }
+            record => conflicts.push(Conflict::AlreadyInstalled {
+                installed: record.and_then(|a| a.version),
+                requested: manifest.app.version.clone(),
+            }),
+        }
+    }
+
+    let wrapper = paths::app_wrapper_path(app_name);
+    if let Ok(content) = fs::read_to_string(&wrapper)
+        && !content.contains(&format!("exec voidbox run {} ", slug))
+    {
+        conflicts.push(Conflict::Wrapper(wrapper));
+    } else if !wrapper.exists() && wrapper.symlink_metadata().is_ok() {
+        // Dangling symlink or unreadable file in the way
+        conflicts.push(Conflict::Wrapper(wrapper));
+    }
+
+    let desktop = paths::desktop_entry_path(&manifest.desktop_id());
+    if let Ok(content) = fs::read_to_string(&desktop)
+        && !content
+            .lines()
+            .any(|l| l == format!("Exec=voidbox run {}", slug))
+    {
+        conflicts.push(Conflict::DesktopEntry(desktop));
+    }
+
+    conflicts
+}
+
+/// Decide how to handle conflicts, asking the user if the policy says so
+///
+/// Returns `Overwrite` or `Skip`; choosing to abort is an error.
+pub fn resolve_conflicts(
+    manifest: &AppManifest,
+    conflicts: &[Conflict],
+    policy: ConflictPolicy,
+) -> Result<ConflictPolicy, InstallError> {
+    if conflicts.is_empty() {
+        return Ok(ConflictPolicy::Overwrite);
+    }
+
+    let policy = match policy {
+        ConflictPolicy::Ask => ask(manifest, conflicts),
+        policy => policy,
+    };
+
+    match policy {
+        ConflictPolicy::Overwrite | ConflictPolicy::Skip => Ok(policy),
+        ConflictPolicy::Ask | ConflictPolicy::Abort => Err(abort_error(manifest, conflicts)),
+    }
+}
+
+fn abort_error(manifest: &AppManifest, conflicts: &[Conflict]) -> InstallError {
+    match conflicts {
+        [Conflict::AlreadyInstalled { .. }] => {
+            InstallError::AlreadyInstalled(manifest.app.name.clone())
+        }
+        _ => InstallError::Conflict(
+            conflicts
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join("; "),
+        ),
+    }
+}
+
+fn ask(manifest: &AppManifest, conflicts: &[Conflict]) -> ConflictPolicy {
+    let mut message = format!("Installing {} would replace:", manifest.app.display_name);
+    for conflict in conflicts {
+        message.push_str(&format!("\n  - {}", conflict));
+    }
+
+    let choices = [
+        ConflictPolicy::Overwrite,
+        ConflictPolicy::Skip,
+        ConflictPolicy::Abort,
+    ];
+
+    if crate::can_prompt() {
+        say!("[voidbox] {}", message);
+        let answer = crate::ask("[voidbox] [o]verwrite, [s]kip, [a]bort?").unwrap_or_default();
+        match answer.to_lowercase().as_str() {
+            "o" | "overwrite" => ConflictPolicy::Overwrite,
+            "s" | "skip" => ConflictPolicy::Skip,
+            _ => ConflictPolicy::Abort,
+        }
+    } else if crate::gui::can_ask() {
+        crate::gui::choose(
+            "Voidbox - Install Conflict",
+            &message,
+            &["Overwrite", "Skip (keep existing files)", "Abort install"],
+        )
+        .map(|i| choices[i])
+        .unwrap_or(ConflictPolicy::Abort)
+    } else {
+        say!("[voidbox] {}", message);
+        say!("[voidbox] Not interactive; pass --overwrite or --skip to proceed");
+        ConflictPolicy::Abort
+    }
+}
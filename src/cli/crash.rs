@@ -0,0 +1,86 @@
+//! Reporting apps that fail right after a desktop launch
+//!
+//! An app started from the desktop has its output in the launch log, so
+//! one that crashes on startup seems to do nothing at all. When it exits
+//! with an error within `QUICK_EXIT` of starting, a dialog shows the end
+//! of what it printed and offers to run `voidbox doctor <app>`; without a
+//! dialog tool a notification points there instead.
+
+use crate::cli::logs::tail;
+use crate::gui::{ask_action, can_ask, notify, show_text};
+use crate::manifest::AppManifest;
+use crate::storage::{AppLog, app_log_path, paths};
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+/// Exits sooner than this after starting count as failed launches
+pub const QUICK_EXIT: Duration = Duration::from_secs(3);
+
+/// Lines of the app's output shown
+const OUTPUT_LINES: usize = 12;
+
+/// Where the launch log currently ends, so a failed launch can show only
+/// its own output
+pub fn launch_log_position(app_name: &str) -> u64 {
+    fs::metadata(app_log_path(app_name, AppLog::Launch)).map_or(0, |m| m.len())
+}
+
+/// The launch log's content from `position` on
+fn launch_output(app_name: &str, position: u64) -> String {
+    let mut output = Vec::new();
+    if let Ok(mut file) = fs::File::open(app_log_path(app_name, AppLog::Launch))
+        && file.seek(SeekFrom::Start(position)).is_ok()
+    {
+        file.read_to_end(&mut output).ok();
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+/// Tell the user an app failed right after starting, with its last output
+/// and a way to diagnose it
+pub fn report_failed_launch(manifest: &AppManifest, log_position: u64, status: ExitStatus) {
+    let app_name = &manifest.app.name;
+    let display_name = &manifest.app.display_name;
+    let output = launch_output(app_name, log_position);
+    let output = tail(&output, OUTPUT_LINES).join("\n");
+
+    let mut message = format!("{} quit right after starting ({}).", display_name, status);
+    if !output.trim().is_empty() {
+        message.push_str(&format!("\n\nLast output:\n{}", output));
+    }
+
+    if !can_ask() {
+        notify(
+            &format!("{} failed to start", display_name),
+            &format!(
+                "Run 'voidbox doctor {}' or 'voidbox logs {}' to find out why",
+                paths::app_slug(app_name),
+                paths::app_slug(app_name)
+            ),
+        );
+        return;
+    }
+    if !ask_action(
+        &format!("{} failed to start", display_name),
+        &message,
+        "Run diagnostics",
+    ) {
+        return;
+    }
+
+    let exe = std::env::current_exe().unwrap_or_else(|_| paths::install_path());
+    let report = match Command::new(exe)
+        .args(["doctor", &paths::app_slug(app_name)])
+        .output()
+    {
+        Ok(out) => format!(
+            "{}{}",
+            String::from_utf8_lossy(&out.stdout),
+            String::from_utf8_lossy(&out.stderr)
+        ),
+        Err(e) => format!("Could not run voidbox doctor: {}", e),
+    };
+    show_text(&format!("Diagnostics for {}", display_name), &report);
+}
@@ -0,0 +1,119 @@
+//! Data command implementation
+//!
+//! Snapshots of an app's data directory, taken on request or before
+//! updates (`[snapshots] before_update` in config), and restoring them.
+
+use crate::cli::install::InstallError;
+use crate::cli::table::Table;
+use crate::runtime::is_app_running;
+use crate::settings::load_config;
+use crate::storage::{
+    SnapshotKind, list_snapshots, paths, prune_snapshots, restore_snapshot, snapshot_app_data,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DataError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error("{0} is running; close it first")]
+    Running(String),
+
+    #[error("Snapshot error: {0}")]
+    SnapshotError(#[from] crate::storage::SnapshotError),
+}
+
+fn check_installed(app_name: &str) -> Result<(), DataError> {
+    if !paths::app_dir(app_name).exists() {
+        return Err(DataError::NotInstalled(app_name.to_string()));
+    }
+    Ok(())
+}
+
+/// Snapshot an app's data now
+pub fn data_snapshot(app_name: &str) -> Result<(), DataError> {
+    check_installed(app_name)?;
+    if is_app_running(app_name) {
+        say!(
+            "[voidbox] Warning: {} is running; files it is writing may be inconsistent",
+            app_name
+        );
+    }
+
+    match snapshot_app_data(app_name)? {
+        Some(snapshot) => say!("[voidbox] Snapshot {} of {} taken", snapshot.id, app_name),
+        None => say!("[voidbox] {} has no data yet", app_name),
+    }
+    Ok(())
+}
+
+/// List an app's data snapshots
+pub fn data_list(app_name: &str) -> Result<(), DataError> {
+    check_installed(app_name)?;
+    let snapshots = list_snapshots(app_name);
+    if snapshots.is_empty() {
+        say!("No snapshots of {}.", app_name);
+        return Ok(());
+    }
+
+    say!("Snapshots of {}:", app_name);
+    let mut table = Table::new(&["SNAPSHOT", "KIND", "SIZE"]).right(2);
+    for snapshot in &snapshots {
+        let (kind, size) = match snapshot.kind {
+            SnapshotKind::Btrfs => ("btrfs", String::new()),
+            SnapshotKind::Archive => {
+                let size = std::fs::metadata(&snapshot.path)
+                    .map(|m| m.len())
+                    .unwrap_or(0);
+                ("archive", indicatif::HumanBytes(size).to_string())
+            }
+        };
+        table.row(vec![snapshot.id.clone(), kind.to_string(), size]);
+    }
+    table.print();
+    say!();
+    say!(
+        "Restore one with: voidbox data restore {} --from <snapshot>",
+        app_name
+    );
+    Ok(())
+}
+
+/// Replace an app's data with a snapshot
+pub fn data_restore(app_name: &str, id: &str) -> Result<(), DataError> {
+    check_installed(app_name)?;
+    if is_app_running(app_name) {
+        return Err(DataError::Running(app_name.to_string()));
+    }
+
+    restore_snapshot(app_name, id)?;
+    say!("[voidbox] Restored the data of {} from {}", app_name, id);
+    Ok(())
+}
+
+/// Snapshot an app's data before an update if the config asks for it
+///
+/// A failed snapshot stops the update so data isn't migrated unprotected.
+pub(crate) fn snapshot_before_update(app_name: &str) -> Result<(), InstallError> {
+    let config = load_config().unwrap_or_default();
+    if !config.snapshots.before_update {
+        return Ok(());
+    }
+
+    let failed = |e: crate::storage::SnapshotError| {
+        InstallError::Failed(format!(
+            "Could not snapshot the data of {}: {}",
+            app_name, e
+        ))
+    };
+    if let Some(snapshot) = snapshot_app_data(app_name).map_err(failed)? {
+        say!(
+            "[voidbox] Snapshot {} of {}'s data taken before updating",
+            snapshot.id,
+            app_name
+        );
+    }
+    prune_snapshots(app_name, config.snapshots.keep.max(1)).map_err(failed)?;
+    Ok(())
+}
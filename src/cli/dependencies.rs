@@ -0,0 +1,90 @@
+//! Dependencies between voidbox apps
+//!
+//! A manifest's `dependencies.apps` lists other voidbox apps it needs, such
+//! as a shared runtime, by any source `voidbox install` takes (usually a
+//! repository name or a manifest URL). Installing the app installs the
+//! missing ones first, each after its own dependencies, and shows them in
+//! the same confirmation. Apps already installed are left as they are; a
+//! dependency cycle is an error.
+
+use crate::cli::install::{InstallError, resolve_request};
+use crate::manifest::AppManifest;
+use crate::storage::paths;
+
+/// An app another one needs that isn't installed yet
+#[derive(Debug, Clone)]
+pub struct AppDependency {
+    /// Install source the dependency is named by
+    pub source: String,
+    pub manifest: AppManifest,
+    /// Display name of the app that needs it
+    pub needed_by: String,
+}
+
+fn is_installed(app_name: &str) -> bool {
+    crate::cli::get_installed_apps()
+        .unwrap_or_default()
+        .iter()
+        .any(|a| paths::same_app(&a.name, app_name))
+}
+
+/// Dependencies of a manifest that aren't installed, in the order to
+/// install them
+pub fn missing_dependencies(manifest: &AppManifest) -> Result<Vec<AppDependency>, InstallError> {
+    let mut missing = Vec::new();
+    collect(manifest, &mut vec![manifest.app.name.clone()], &mut missing)?;
+    Ok(missing)
+}
+
+/// Add a manifest's missing dependencies, theirs first; `chain` holds the
+/// apps whose dependencies are being collected
+fn collect(
+    manifest: &AppManifest,
+    chain: &mut Vec<String>,
+    missing: &mut Vec<AppDependency>,
+) -> Result<(), InstallError> {
+    let cycle = |chain: &[String], app: &str| {
+        InstallError::Failed(format!(
+            "Dependency cycle: {} -> {}",
+            chain.join(" -> "),
+            app
+        ))
+    };
+
+    for source in &manifest.dependencies.apps {
+        if chain.iter().any(|a| paths::same_app(a, source)) {
+            return Err(cycle(chain, source));
+        }
+        if is_installed(source) {
+            continue;
+        }
+
+        let (dependency, _) = resolve_request(source, None, None).map_err(|e| {
+            InstallError::Failed(format!(
+                "{} needs {}, which can't be found: {}",
+                manifest.app.display_name, source, e
+            ))
+        })?;
+        let name = &dependency.app.name;
+        if chain.iter().any(|a| paths::same_app(a, name)) {
+            return Err(cycle(chain, name));
+        }
+        if is_installed(name)
+            || missing
+                .iter()
+                .any(|d| paths::same_app(&d.manifest.app.name, name))
+        {
+            continue;
+        }
+
+        chain.push(name.clone());
+        collect(&dependency, chain, missing)?;
+        chain.pop();
+        missing.push(AppDependency {
+            source: source.clone(),
+            manifest: dependency,
+            needed_by: manifest.app.display_name.clone(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+//! Desktop command implementation
+//!
+//! `voidbox desktop regen` writes installed apps' icons, desktop entries
+//! and command wrappers again: for apps whose integration failed during
+//! install (and was undone), or after the files were deleted or edited.
+
+use crate::cli::install::integrate_installed;
+use crate::desktop::{KeepIntegration, refresh_batch};
+use crate::manifest::parse_manifest_file;
+use crate::storage::paths;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DesktopCommandError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error("Failed to read database: {0}")]
+    ListError(#[from] crate::cli::ListError),
+
+    #[error("Desktop integration failed for {0} app(s)")]
+    Failed(usize),
+}
+
+/// Add installed apps to the desktop again, one app or all of them
+pub fn regen_desktop(app: Option<&str>) -> Result<(), DesktopCommandError> {
+    let mut apps = crate::cli::get_installed_apps()?;
+    if let Some(name) = app {
+        apps.retain(|a| paths::same_app(&a.name, name));
+        if apps.is_empty() {
+            return Err(DesktopCommandError::NotInstalled(name.to_string()));
+        }
+    }
+
+    let _refresh = refresh_batch();
+    let mut failed = 0;
+    for installed in &apps {
+        let manifest = match parse_manifest_file(&paths::manifest_path(&installed.name)) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                say!("[voidbox] Skipping {}: {}", installed.name, e);
+                failed += 1;
+                continue;
+            }
+        };
+        match integrate_installed(&manifest, KeepIntegration::default()) {
+            Ok(true) => say!("  {}: added to the desktop", installed.name),
+            Ok(false) => failed += 1,
+            Err(e) => {
+                say!("[voidbox] Could not record {}: {}", installed.name, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(DesktopCommandError::Failed(failed));
+    }
+    say!(
+        "[voidbox] Regenerated the desktop integration of {} app(s)",
+        apps.len()
+    );
+    Ok(())
+}
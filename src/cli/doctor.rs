@@ -0,0 +1,284 @@
+//! Doctor command implementation
+//!
+//! Checks the environment voidbox runs in rather than individual apps:
+//! whether installed commands can be found on PATH, whether its directories
+//! exist and are usable, whether the GUI has a display to open on, and
+//! whether the configured download sources can be reached. Given an app,
+//! it also checks that the class its windows were last seen with matches
+//! its desktop entry, so taskbars show its icon. Every problem comes with
+//! the fix to try.
+
+use crate::cli::health::{HealthError, HealthIssue, check_system, print_section};
+use crate::cli::list::get_installed_apps;
+use crate::desktop::window_class;
+use crate::manifest::{SourceConfig, parse_manifest_file, tap_url};
+use crate::settings::load_config;
+use crate::storage::{http_head, paths};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Time allowed for each reachability check
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Run the environment checks, and those of an app's windows if one is
+/// given, and print the results
+pub fn doctor(app_name: Option<&str>) -> Result<(), HealthError> {
+    let mut sections = vec![
+        ("sandbox", check_system()),
+        ("PATH", check_path()),
+        ("directories", check_directories()),
+        ("display", check_display()),
+        ("network", check_network()),
+    ];
+    if let Some(app_name) = app_name {
+        sections.push(("windows", check_windows(app_name)?));
+    }
+
+    let mut problems = 0;
+    for (name, issues) in &sections {
+        problems += issues.len();
+        print_section(name, issues);
+    }
+
+    if problems > 0 {
+        return Err(HealthError::Unhealthy(problems));
+    }
+
+    say!("[voidbox] No problems found");
+    Ok(())
+}
+
+/// Check that app commands in ~/.local/bin can be run by name
+fn check_path() -> Vec<HealthIssue> {
+    let bin_dir = paths::bin_dir();
+    let on_path = std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir == bin_dir));
+    if on_path {
+        return Vec::new();
+    }
+
+    vec![HealthIssue::new(
+        format!(
+            "{} is not on PATH; app commands can only be run by full path",
+            bin_dir.display()
+        ),
+        format!(
+            "Add 'export PATH=\"{}:$PATH\"' to ~/.profile and log in again",
+            bin_dir.display()
+        ),
+    )]
+}
+
+/// Directories voidbox reads and writes
+fn required_dirs() -> Vec<PathBuf> {
+    vec![
+        paths::data_dir(),
+        paths::bases_dir(),
+        paths::apps_dir(),
+        paths::manifests_dir(),
+        paths::settings_dir(),
+        paths::icons_dir(),
+        paths::cache_dir(),
+        paths::state_dir(),
+        paths::desktop_dir(),
+        paths::bin_dir(),
+    ]
+}
+
+/// Check that voidbox's directories exist, belong to the user and aren't
+/// writable by others
+fn check_directories() -> Vec<HealthIssue> {
+    let uid = nix::unistd::getuid().as_raw();
+    let mut issues = Vec::new();
+
+    for dir in required_dirs() {
+        let metadata = match std::fs::metadata(&dir) {
+            Ok(metadata) if metadata.is_dir() => metadata,
+            Ok(_) => {
+                issues.push(HealthIssue::new(
+                    format!("{} is not a directory", dir.display()),
+                    format!("Move {} aside; voidbox recreates it", dir.display()),
+                ));
+                continue;
+            }
+            // The cache and state directories appear once something is stored
+            Err(_) if dir == paths::state_dir() || dir == paths::cache_dir() => continue,
+            Err(e) => {
+                issues.push(HealthIssue::new(
+                    format!("{} is missing: {}", dir.display(), e),
+                    format!("mkdir -p {}", dir.display()),
+                ));
+                continue;
+            }
+        };
+
+        if metadata.uid() != uid {
+            issues.push(HealthIssue::new(
+                format!("{} is owned by another user", dir.display()),
+                format!("sudo chown -R $USER {}", dir.display()),
+            ));
+        } else if !writable(&dir) {
+            issues.push(HealthIssue::new(
+                format!("{} is not writable", dir.display()),
+                format!("chmod u+rwx {}", dir.display()),
+            ));
+        }
+        if metadata.permissions().mode() & 0o002 != 0 {
+            issues.push(HealthIssue::new(
+                format!("{} is writable by every user", dir.display()),
+                format!("chmod o-w {}", dir.display()),
+            ));
+        }
+    }
+
+    issues
+}
+
+fn writable(dir: &Path) -> bool {
+    nix::unistd::access(dir, nix::unistd::AccessFlags::W_OK).is_ok()
+}
+
+/// Check that the GUI installer and editor have a display to open on
+fn check_display() -> Vec<HealthIssue> {
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some_and(|display| {
+        let socket = PathBuf::from(&display);
+        if socket.is_absolute() {
+            socket.exists()
+        } else {
+            runtime_dir
+                .as_ref()
+                .is_some_and(|d| d.join(socket).exists())
+        }
+    });
+    let x11 = std::env::var("DISPLAY").is_ok_and(|display| {
+        // Remote displays (host:0) can't be checked locally
+        let Some(number) = display.strip_prefix(':') else {
+            return !display.is_empty();
+        };
+        let number = number.split('.').next().unwrap_or_default();
+        Path::new("/tmp/.X11-unix")
+            .join(format!("X{}", number))
+            .exists()
+    });
+
+    let mut issues = Vec::new();
+    if !wayland && !x11 {
+        issues.push(HealthIssue::new(
+            "No Wayland or X11 display found; the GUI installer and apps can't open windows",
+            "Run voidbox from a graphical session, or set WAYLAND_DISPLAY or DISPLAY",
+        ));
+    }
+    if !crate::gui::has_gui_support() {
+        issues.push(HealthIssue::new(
+            "Neither zenity nor kdialog is installed; dialogs fall back to the terminal",
+            "Install zenity (GNOME) or kdialog (KDE) with your package manager",
+        ));
+    }
+    issues
+}
+
+/// Check that the class an app's windows were last seen with leads
+/// taskbars to its desktop entry
+fn check_windows(app_name: &str) -> Result<Vec<HealthIssue>, HealthError> {
+    let manifest = parse_manifest_file(&paths::manifest_path(app_name))
+        .map_err(|_| HealthError::NotInstalled(app_name.to_string()))?;
+    let name = &manifest.app.name;
+
+    // Recorded by launches, when the compositor lists windows
+    let Ok(seen) = std::fs::read_to_string(paths::app_window_class_path(name)) else {
+        say!(
+            "[voidbox] No window of {} seen yet; launch it in a Wayland session whose compositor lists windows, then check again",
+            name
+        );
+        return Ok(Vec::new());
+    };
+    let seen = seen.trim();
+    let class = window_class(&manifest);
+    let desktop_id = manifest.desktop_id();
+    if seen.eq_ignore_ascii_case(&class) || seen.eq_ignore_ascii_case(&desktop_id) {
+        return Ok(Vec::new());
+    }
+
+    Ok(vec![HealthIssue::new(
+        format!(
+            "{}'s windows have class '{}', but its desktop entry ({}) expects '{}'; taskbars may show a generic icon",
+            name, seen, desktop_id, class
+        ),
+        format!(
+            "Set wm_class = \"{}\" under [desktop] in {}, adding window_rules = true if the icon is still wrong, then run voidbox update {}",
+            seen,
+            paths::manifest_path(name).display(),
+            name
+        ),
+    )])
+}
+
+/// URLs of the sources installs and updates download from, with a label
+fn network_sources() -> Vec<(String, String)> {
+    let mut sources = vec![
+        (
+            "Ubuntu base images".to_string(),
+            crate::UBUNTU_RELEASES_URL.to_string(),
+        ),
+        (
+            "GitHub releases".to_string(),
+            "https://api.github.com".to_string(),
+        ),
+    ];
+
+    for location in load_config().unwrap_or_default().repositories {
+        let url = tap_url(&location).or_else(|| {
+            (location.starts_with("http://") || location.starts_with("https://"))
+                .then(|| location.clone())
+        });
+        if let Some(url) = url {
+            sources.push((format!("repository {}", location), url));
+        }
+    }
+
+    for app in get_installed_apps().unwrap_or_default() {
+        if let Ok(manifest) = parse_manifest_file(&paths::manifest_path(&app.name))
+            && let SourceConfig::Direct { url, .. } = &manifest.source
+        {
+            sources.push((format!("{} downloads", app.name), url.clone()));
+        }
+    }
+
+    // One check per host is enough
+    let mut hosts = Vec::new();
+    sources.retain(|(_, url)| {
+        let host = url
+            .split("://")
+            .nth(1)
+            .and_then(|rest| rest.split('/').next())
+            .unwrap_or(url)
+            .to_string();
+        let first = !hosts.contains(&host);
+        hosts.push(host);
+        first
+    });
+    sources
+}
+
+/// Check that each download source answers
+fn check_network() -> Vec<HealthIssue> {
+    let mut issues = Vec::new();
+    for (label, url) in network_sources() {
+        let result = http_head(&url)
+            .config()
+            .timeout_global(Some(NETWORK_TIMEOUT))
+            .build()
+            .call();
+        match result {
+            // Any HTTP response means the host is reachable
+            Ok(_) | Err(ureq::Error::StatusCode(_)) => {}
+            Err(e) => issues.push(HealthIssue::new(
+                format!("Can't reach {} ({}): {}", label, url, e),
+                "Check your connection, or configure a proxy or mirror in config.toml",
+            )),
+        }
+    }
+    issues
+}
@@ -0,0 +1,161 @@
+//! Env command implementation
+//!
+//! Shows the environment variables an app is started with and changes them
+//! through its user override. `--locale` and `--timezone` are shorthands for
+//! the variables that select them, so software can be tried in another
+//! locale without changing the session.
+
+use crate::manifest::parse_manifest_file;
+use crate::settings::{load_env_overrides, remove_env_overrides, save_env_overrides};
+use crate::storage::paths;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum EnvError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error("Invalid variable '{0}': expected NAME=VALUE")]
+    InvalidVariable(String),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] crate::manifest::ManifestError),
+
+    #[error("Settings error: {0}")]
+    SettingsError(#[from] crate::settings::SettingsError),
+}
+
+/// Variables set by `--locale`; LC_ALL takes precedence over the session's
+/// LC_* variables
+const LOCALE_VARS: &[&str] = &["LANG", "LC_ALL"];
+
+/// Variable set by `--timezone`
+const TIMEZONE_VAR: &str = "TZ";
+
+/// Locales glibc provides without generated locale data
+const BUILTIN_LOCALES: &[&str] = &["C", "POSIX", "C.UTF-8", "C.utf8"];
+
+/// Show an app's environment, setting or unsetting variables first
+pub fn env(
+    app_name: &str,
+    locale: Option<&str>,
+    timezone: Option<&str>,
+    set: &[String],
+    unset: &[String],
+    reset: bool,
+) -> Result<(), EnvError> {
+    let manifest_path = paths::manifest_path(app_name);
+    if !manifest_path.exists() {
+        return Err(EnvError::NotInstalled(app_name.to_string()));
+    }
+    let manifest = parse_manifest_file(&manifest_path)?;
+    let rootfs = paths::app_rootfs_dir(app_name);
+
+    if reset {
+        remove_env_overrides(app_name)?;
+        say!("[voidbox] Reset environment of {}", app_name);
+    }
+
+    let mut overrides = load_env_overrides(app_name)?;
+    let edits = locale.is_some() || timezone.is_some() || !set.is_empty() || !unset.is_empty();
+    if edits {
+        for name in unset {
+            overrides.remove(name);
+        }
+        for assignment in set {
+            let (name, value) = parse_assignment(assignment)?;
+            overrides.insert(name.to_string(), value.to_string());
+        }
+        if let Some(locale) = locale {
+            if !locale_available(&rootfs, locale) {
+                say!(
+                    "[voidbox] Warning: Locale {} isn't generated in the app's rootfs; \
+                     add the base image's locales package to its dependencies",
+                    locale
+                );
+            }
+            for name in LOCALE_VARS {
+                overrides.insert(name.to_string(), locale.to_string());
+            }
+        }
+        if let Some(timezone) = timezone {
+            if !timezone_available(&rootfs, timezone) {
+                say!(
+                    "[voidbox] Warning: Time zone {} isn't in the app's rootfs (is tzdata installed?)",
+                    timezone
+                );
+            }
+            overrides.insert(TIMEZONE_VAR.to_string(), timezone.to_string());
+        }
+        save_env_overrides(app_name, &overrides)?;
+        say!("[voidbox] Changes apply the next time {} starts", app_name);
+    }
+
+    say!("Environment of {}:", app_name);
+    let mut shown = false;
+    for (name, value) in &manifest.binary.env {
+        if !overrides.contains_key(name) {
+            say!("  {}={}", name, value);
+            shown = true;
+        }
+    }
+    for (name, value) in &overrides {
+        say!("  {}={} (user override)", name, value);
+        shown = true;
+    }
+    if !shown {
+        say!("  (inherited from the session)");
+    }
+
+    Ok(())
+}
+
+/// Split NAME=VALUE, checking NAME is a valid variable name
+fn parse_assignment(assignment: &str) -> Result<(&str, &str), EnvError> {
+    let invalid = || EnvError::InvalidVariable(assignment.to_string());
+    let (name, value) = assignment.split_once('=').ok_or_else(invalid)?;
+    let valid_name = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_name {
+        return Err(invalid());
+    }
+    Ok((name, value))
+}
+
+/// Whether locale data for `locale` exists in the rootfs
+///
+/// glibc looks up "de_DE.UTF-8" as "de_DE.utf8"; with a locale archive the
+/// locale can't be checked without parsing it, so it's assumed present.
+fn locale_available(rootfs: &Path, locale: &str) -> bool {
+    if BUILTIN_LOCALES.contains(&locale) {
+        return true;
+    }
+    let locale_dir = rootfs.join("usr/lib/locale");
+    if locale_dir.join("locale-archive").exists() {
+        return true;
+    }
+    let normalized = match locale.split_once('.') {
+        Some((language, codeset)) => format!(
+            "{}.{}",
+            language,
+            codeset.to_ascii_lowercase().replace('-', "")
+        ),
+        None => locale.to_string(),
+    };
+    locale_dir.join(&normalized).is_dir() || locale_dir.join(locale).is_dir()
+}
+
+/// Whether `timezone` exists in the rootfs's zoneinfo
+fn timezone_available(rootfs: &Path, timezone: &str) -> bool {
+    // POSIX TZ strings ("UTC0", "EST5EDT") and plain UTC work without tzdata
+    timezone == "UTC"
+        || timezone.chars().any(|c| c.is_ascii_digit())
+        || rootfs
+            .join("usr/share/zoneinfo")
+            .join(timezone.trim_start_matches(':'))
+            .is_file()
+}
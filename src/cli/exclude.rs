@@ -0,0 +1,204 @@
+//! Size-optimized installs
+//!
+//! Files matching exclude patterns (from the manifest's `[binary] exclude`
+//! and the config's `[install] exclude`) are skipped while the app payload
+//! is extracted: debug symbols, unused locales, duplicate libraries.
+//! Patterns are globs relative to the payload directory; `*` and `?` stay
+//! within a path component, `**` spans components, patterns without a `/`
+//! match file names anywhere, and a leading `!` keeps files an earlier
+//! pattern excluded. What was skipped is recorded next to the rootfs, so
+//! the files aren't mistaken for missing ones later.
+
+use crate::manifest::AppManifest;
+use crate::settings::load_config;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One compiled pattern
+struct Rule {
+    regex: Regex,
+    /// Keeps matching files instead of excluding them
+    keep: bool,
+    /// Pattern without a `/`, matched against file names
+    name_only: bool,
+}
+
+/// Exclude patterns of an install
+pub(crate) struct ExcludeRules {
+    patterns: Vec<String>,
+    rules: Vec<Rule>,
+    /// File name of the app's binary, which is never excluded
+    binary: String,
+}
+
+/// Files skipped by an install
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExcludedFiles {
+    pub patterns: Vec<String>,
+    /// Paths relative to the payload directory
+    pub files: Vec<String>,
+    /// Bytes not written
+    pub bytes: u64,
+}
+
+/// Translate a glob to an anchored regex
+fn glob_regex(glob: &str) -> Option<Regex> {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).ok()
+}
+
+impl ExcludeRules {
+    /// Patterns from the config followed by the manifest's
+    pub(crate) fn for_manifest(manifest: &AppManifest) -> Self {
+        let mut patterns = load_config().map(|c| c.install.exclude).unwrap_or_default();
+        patterns.extend(manifest.binary.exclude.iter().cloned());
+
+        let rules = patterns
+            .iter()
+            .filter_map(|pattern| {
+                let (keep, glob) = match pattern.strip_prefix('!') {
+                    Some(glob) => (true, glob),
+                    None => (false, pattern.as_str()),
+                };
+                // "dir/" excludes everything below dir
+                let glob = glob.trim_start_matches('/').trim_end_matches('/');
+                let regex = glob_regex(glob);
+                if regex.is_none() {
+                    say!(
+                        "[voidbox] Warning: Ignoring invalid exclude pattern '{}'",
+                        pattern
+                    );
+                }
+                Some(Rule {
+                    regex: regex?,
+                    keep,
+                    name_only: !glob.contains('/'),
+                })
+            })
+            .collect();
+
+        Self {
+            patterns,
+            rules,
+            binary: manifest.binary.name.clone(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether a payload-relative path is excluded
+    ///
+    /// A pattern matching a directory applies to everything in it; the last
+    /// matching pattern decides.
+    pub(crate) fn is_excluded(&self, path: &Path) -> bool {
+        if path.file_name().is_some_and(|name| *name == *self.binary) {
+            return false;
+        }
+
+        let ancestors: Vec<&Path> = path
+            .ancestors()
+            .filter(|a| !a.as_os_str().is_empty())
+            .collect();
+        let mut excluded = false;
+        for rule in &self.rules {
+            let matched = ancestors.iter().any(|ancestor| {
+                let subject = if rule.name_only {
+                    ancestor.file_name().map(|n| n.to_string_lossy())
+                } else {
+                    Some(ancestor.to_string_lossy())
+                };
+                subject.is_some_and(|s| rule.regex.is_match(&s))
+            });
+            if matched {
+                excluded = !rule.keep;
+            }
+        }
+        excluded
+    }
+
+    /// Start a record of skipped files
+    pub(crate) fn record(&self) -> ExcludedFiles {
+        ExcludedFiles {
+            patterns: self.patterns.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl ExcludedFiles {
+    pub(crate) fn add(&mut self, path: &Path, size: u64) {
+        self.files.push(path.to_string_lossy().into_owned());
+        self.bytes += size;
+    }
+}
+
+/// Record of skipped files, stored next to the rootfs
+fn excluded_path(rootfs: &Path) -> PathBuf {
+    rootfs.with_file_name("excluded.json")
+}
+
+/// Save (or clear) the record of files an install skipped
+pub(crate) fn save_excluded(rootfs: &Path, excluded: &ExcludedFiles) -> std::io::Result<()> {
+    let path = excluded_path(rootfs);
+    if excluded.files.is_empty() {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+    let json = serde_json::to_string_pretty(excluded).map_err(std::io::Error::other)?;
+    fs::write(path, json)
+}
+
+/// Files the install of an app's rootfs skipped
+pub fn load_excluded(rootfs: &Path) -> Option<ExcludedFiles> {
+    let content = fs::read_to_string(excluded_path(rootfs)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Remove excluded files from a copied payload directory
+pub(crate) fn prune_excluded(
+    payload: &Path,
+    rules: &ExcludeRules,
+    excluded: &mut ExcludedFiles,
+) -> std::io::Result<()> {
+    for entry in walkdir::WalkDir::new(payload).contents_first(true) {
+        let entry = entry.map_err(std::io::Error::other)?;
+        let Ok(relative) = entry.path().strip_prefix(payload) else {
+            continue;
+        };
+        if relative.as_os_str().is_empty() || !rules.is_excluded(relative) {
+            continue;
+        }
+        if entry.file_type().is_dir() {
+            // Emptied by the files below it; kept if something was re-included
+            fs::remove_dir(entry.path()).ok();
+        } else {
+            excluded.add(relative, entry.metadata().map(|m| m.len()).unwrap_or(0));
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
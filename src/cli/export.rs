@@ -0,0 +1,233 @@
+//! Export and import commands
+//!
+//! `voidbox export` writes a portable description of everything installed:
+//! each app's manifest, installed version, whether it is pinned and the URL
+//! its manifest came from. `voidbox import` replays such a file on another
+//! machine, installing the apps that aren't there yet. Pinned apps are
+//! installed at their pinned version, others at the latest unless
+//! `--exact`, which installs (and pins) every app at its exported version.
+//! Apps installed from local files are skipped unless the same files exist
+//! on the other machine.
+
+use crate::cli::conflict::ConflictPolicy;
+use crate::cli::install::{
+    install_app_from_manifest, installed_at_requested_version, update_record,
+};
+use crate::cli::json::{is_json, print_json};
+use crate::cli::versions::manifest_at_version;
+use crate::desktop::refresh_batch;
+use crate::manifest::{AppManifest, SourceConfig, for_host_arch, parse_manifest_file};
+use crate::storage::paths;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] crate::manifest::ManifestError),
+
+    #[error("Install error: {0}")]
+    InstallError(#[from] crate::cli::InstallError),
+
+    #[error("Failed to read database: {0}")]
+    ListError(#[from] crate::cli::ListError),
+
+    #[error("Invalid export file: {0}")]
+    InvalidFile(String),
+
+    #[error("Import failed: {0}")]
+    Failed(String),
+}
+
+/// Everything installed, as written by `voidbox export`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetupExport {
+    /// voidbox version that wrote the file
+    pub voidbox_version: String,
+    pub exported: String,
+    #[serde(default)]
+    pub apps: Vec<ExportedApp>,
+}
+
+/// One installed app in an export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedApp {
+    pub name: String,
+    pub version: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    /// URL the manifest was installed from, for updates to re-fetch
+    pub source_url: Option<String>,
+    pub manifest: AppManifest,
+}
+
+/// Describe every installed app
+pub fn export_setup() -> Result<SetupExport, ExportError> {
+    let mut apps = Vec::new();
+    for app in crate::cli::get_installed_apps()? {
+        let manifest = match parse_manifest_file(&paths::manifest_path(&app.name)) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                eprintln!("[voidbox] Warning: Skipping {}: {}", app.name, e);
+                continue;
+            }
+        };
+        if matches!(manifest.source, SourceConfig::Local { .. }) {
+            eprintln!(
+                "[voidbox] Warning: {} was installed from local files; importing it needs the \
+                 same files on the other machine",
+                app.name
+            );
+        }
+        apps.push(ExportedApp {
+            name: app.name,
+            version: app.version,
+            pinned: app.pinned,
+            source_url: app.source_url,
+            manifest,
+        });
+    }
+
+    Ok(SetupExport {
+        voidbox_version: env!("CARGO_PKG_VERSION").to_string(),
+        exported: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        apps,
+    })
+}
+
+/// Write the export to a file, or print it
+pub fn export_to(output: Option<&Path>) -> Result<(), ExportError> {
+    let export = export_setup()?;
+    if output.is_none() && is_json() {
+        print_json(&export);
+        return Ok(());
+    }
+
+    let content = toml::to_string_pretty(&export)
+        .map_err(|e| ExportError::Failed(format!("Failed to serialize: {}", e)))?;
+    match output {
+        Some(path) => {
+            fs::write(path, content)?;
+            say!(
+                "[voidbox] Exported {} app(s) to {}",
+                export.apps.len(),
+                path.display()
+            );
+            say!("[voidbox] Replay it elsewhere with: voidbox import <file>");
+        }
+        None if !crate::is_quiet() => print!("{}", content),
+        None => {}
+    }
+    Ok(())
+}
+
+/// Read an export written as TOML (or JSON, with `--json`)
+fn read_export(path: &Path) -> Result<SetupExport, ExportError> {
+    let content = fs::read_to_string(path)?;
+    toml::from_str(&content)
+        .or_else(|e| serde_json::from_str(&content).map_err(|_| e))
+        .map_err(|e| ExportError::InvalidFile(e.to_string()))
+}
+
+/// Why an exported app can't or needn't be installed here
+fn skip_reason(app: &ExportedApp) -> Option<String> {
+    if let SourceConfig::Local { path } = &app.manifest.source
+        && !path.exists()
+    {
+        return Some(format!("local files {} not found", path.display()));
+    }
+    installed_at_requested_version(&app.manifest).map(|v| format!("v{} is already installed", v))
+}
+
+/// Install the apps an export lists that aren't installed yet
+///
+/// With `exact`, every app is installed and pinned at its exported version;
+/// otherwise only pinned apps are. With `dry_run` nothing is installed.
+pub fn import_setup(file: &Path, exact: bool, dry_run: bool) -> Result<(), ExportError> {
+    let export = read_export(file)?;
+    if export.apps.is_empty() {
+        say!("[voidbox] {} lists no apps", file.display());
+        return Ok(());
+    }
+    say!(
+        "[voidbox] Importing {} app(s) exported on {}...",
+        export.apps.len(),
+        export.exported
+    );
+
+    // One desktop database refresh for the whole import
+    let _refresh = (!dry_run).then(refresh_batch);
+
+    let mut installed = 0;
+    let mut failed = Vec::new();
+    for app in &export.apps {
+        if let Some(reason) = skip_reason(app) {
+            say!("[voidbox] Skipping {}: {}", app.name, reason);
+            continue;
+        }
+
+        let pin = app.pinned || exact;
+        let version = app.version.as_deref().filter(|_| pin);
+        if dry_run {
+            match version {
+                Some(version) => say!("  install {} v{} (pinned)", app.name, version),
+                None => say!("  install {} (latest)", app.name),
+            }
+            continue;
+        }
+
+        say!("[voidbox] Installing {}...", app.name);
+        match import_app(app, version) {
+            Ok(()) => installed += 1,
+            Err(e) => {
+                say!("[voidbox] Could not install {}: {}", app.name, e);
+                failed.push(app.name.clone());
+            }
+        }
+    }
+
+    if dry_run {
+        say!();
+        say!("Install them with: voidbox import {}", file.display());
+        return Ok(());
+    }
+
+    say!("[voidbox] Installed {} app(s)", installed);
+    if !failed.is_empty() {
+        return Err(ExportError::Failed(format!(
+            "{} app(s) not installed ({})",
+            failed.len(),
+            failed.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Install one exported app, at `version` and pinned if given
+fn import_app(app: &ExportedApp, version: Option<&str>) -> Result<(), ExportError> {
+    // The export may come from a machine of another architecture
+    let manifest = for_host_arch(&app.manifest)?;
+    let manifest = match version {
+        Some(version) => manifest_at_version(&app.name, &manifest, version)
+            .map_err(|e| ExportError::Failed(e.to_string()))?,
+        None => manifest,
+    };
+
+    // Apps already on this machine are left alone
+    install_app_from_manifest(&manifest, ConflictPolicy::Skip)?;
+    if installed_at_requested_version(&manifest).is_none() {
+        return Ok(());
+    }
+    update_record(&app.name, |record| {
+        if record.source_url.is_none() {
+            record.source_url = app.source_url.clone();
+        }
+        record.pinned |= version.is_some();
+    })?;
+    Ok(())
+}
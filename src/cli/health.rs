@@ -0,0 +1,245 @@
+//! Health command implementation
+//!
+//! Combines the individual checks (install integrity, desktop integration,
+//! base runtime, nightly staleness, the sandbox backend and entries of apps
+//! that are gone) into one report
+//! with a suggested fix for each problem.
+
+use crate::cli::leftovers::{LeftoverKind, find_leftovers};
+use crate::cli::list::get_installed_apps;
+use crate::cli::payload::find_entrypoint;
+use crate::manifest::{InstalledApp, manifest_hash, parse_manifest_file};
+use crate::runtime::user_namespace_blocker;
+use crate::storage::paths;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+/// Nightly builds older than this are reported as stale
+const NIGHTLY_MAX_AGE_DAYS: i64 = 14;
+
+#[derive(Error, Debug)]
+pub enum HealthError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error("{0} problem(s) found")]
+    Unhealthy(usize),
+
+    #[error("Health check failed: {0}")]
+    Failed(String),
+}
+
+/// A problem found by a health check
+#[derive(Debug, Clone)]
+pub struct HealthIssue {
+    pub problem: String,
+    /// Command or action that fixes it
+    pub fix: String,
+}
+
+impl HealthIssue {
+    pub(crate) fn new(problem: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            problem: problem.into(),
+            fix: fix.into(),
+        }
+    }
+}
+
+/// Check one app, or every installed app, and print the results
+pub fn health(app_name: Option<&str>) -> Result<(), HealthError> {
+    let apps = get_installed_apps().map_err(|e| HealthError::Failed(e.to_string()))?;
+
+    let apps: Vec<InstalledApp> = match app_name {
+        Some(name) => {
+            let app = apps
+                .into_iter()
+                .find(|a| paths::same_app(&a.name, name))
+                .ok_or_else(|| HealthError::NotInstalled(name.to_string()))?;
+            vec![app]
+        }
+        None => apps,
+    };
+
+    let mut problems = 0;
+
+    let system = check_system();
+    problems += system.len();
+    print_section("voidbox", &system);
+
+    for app in &apps {
+        let issues = check_app(app);
+        problems += issues.len();
+        print_section(&app.name, &issues);
+    }
+
+    if problems > 0 {
+        return Err(HealthError::Unhealthy(problems));
+    }
+
+    say!("[voidbox] Everything looks healthy");
+    Ok(())
+}
+
+pub(crate) fn print_section(name: &str, issues: &[HealthIssue]) {
+    if issues.is_empty() {
+        say!("{}: ok", name);
+        return;
+    }
+
+    say!("{}: {} problem(s)", name, issues.len());
+    for issue in issues {
+        say!("  - {}", issue.problem);
+        say!("    fix: {}", issue.fix);
+    }
+}
+
+/// Checks that affect every app
+pub fn check_system() -> Vec<HealthIssue> {
+    let mut issues = Vec::new();
+
+    if let Some(blocker) = user_namespace_blocker() {
+        issues.push(HealthIssue::new(
+            format!("Sandbox unavailable: {}", blocker),
+            "Re-enable unprivileged user namespaces (sysctl) or add an AppArmor profile for voidbox",
+        ));
+    }
+
+    if !paths::install_path().exists() {
+        issues.push(HealthIssue::new(
+            format!(
+                "voidbox is not installed at {}; desktop entries can't launch apps",
+                paths::install_path().display()
+            ),
+            "Run voidbox once from its download location to reinstall it",
+        ));
+    }
+
+    // Entries left behind by apps that are gone still show up in menus
+    let orphans: Vec<_> = find_leftovers()
+        .into_iter()
+        .filter(|l| matches!(l.kind, LeftoverKind::DesktopEntry | LeftoverKind::Autostart))
+        .collect();
+    if !orphans.is_empty() {
+        let apps: BTreeSet<&str> = orphans.iter().map(|l| l.app.as_str()).collect();
+        issues.push(HealthIssue::new(
+            format!(
+                "{} desktop entr{} for apps that are no longer installed ({})",
+                orphans.len(),
+                if orphans.len() == 1 { "y" } else { "ies" },
+                apps.into_iter().collect::<Vec<_>>().join(", ")
+            ),
+            "voidbox scan-leftovers",
+        ));
+    }
+
+    issues
+}
+
+/// Checks for a single installed app
+pub fn check_app(app: &InstalledApp) -> Vec<HealthIssue> {
+    let mut issues = Vec::new();
+    let name = &app.name;
+    let repair = format!("voidbox repair {}", name);
+
+    let manifest_path = paths::manifest_path(name);
+    let manifest = match parse_manifest_file(&manifest_path) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            issues.push(HealthIssue::new(
+                format!("Saved manifest is unreadable: {}", e),
+                format!("voidbox install <manifest> --force (for {})", name),
+            ));
+            return issues;
+        }
+    };
+
+    // Install integrity
+    if let Some(saved) = &app.manifest_hash
+        && manifest_hash(&manifest).is_ok_and(|h| &h != saved)
+    {
+        issues.push(HealthIssue::new(
+            "Manifest changed since install",
+            format!("voidbox update {}", name),
+        ));
+    }
+
+    let rootfs = paths::app_rootfs_dir(name);
+    if !rootfs.exists() {
+        issues.push(HealthIssue::new("Rootfs is missing", repair));
+        return issues;
+    }
+
+    // Where `voidbox run` looks for it
+    if find_entrypoint(&rootfs, &manifest).is_none() {
+        issues.push(HealthIssue::new(
+            format!(
+                "Binary '{}' is missing from the rootfs",
+                manifest.binary.name
+            ),
+            repair.clone(),
+        ));
+    }
+
+    // Shared base runtime
+    if !rootfs.join("etc/os-release").exists() || !rootfs.join("usr/lib").is_dir() {
+        issues.push(HealthIssue::new(
+            format!(
+                "Base runtime ({}) is missing or incomplete",
+                manifest.runtime.base
+            ),
+            repair.clone(),
+        ));
+    }
+
+    // Desktop integration
+    let desktop_path = paths::desktop_entry_path(&manifest.desktop_id());
+    match fs::read_to_string(&desktop_path) {
+        Ok(entry) => {
+            let icon = entry
+                .lines()
+                .find_map(|l| l.strip_prefix("Icon="))
+                .unwrap_or_default();
+            if icon.starts_with('/') && !Path::new(icon).exists() {
+                issues.push(HealthIssue::new(
+                    format!("Desktop entry points to a missing icon ({})", icon),
+                    repair.clone(),
+                ));
+            }
+        }
+        Err(_) => issues.push(HealthIssue::new(
+            format!("Desktop entry is missing ({})", desktop_path.display()),
+            repair.clone(),
+        )),
+    }
+
+    if !paths::app_wrapper_path(name).exists() {
+        issues.push(HealthIssue::new(
+            "Command wrapper in ~/.local/bin is missing",
+            repair.clone(),
+        ));
+    }
+
+    // Stale nightly builds
+    let is_nightly = app
+        .version
+        .as_deref()
+        .is_some_and(|v| v.to_lowercase().contains("nightly"));
+    if is_nightly
+        && let Some(age) = app
+            .installed_date
+            .as_deref()
+            .and_then(|d| chrono::NaiveDateTime::parse_from_str(d, "%Y-%m-%d %H:%M:%S").ok())
+            .map(|d| (chrono::Local::now().naive_local() - d).num_days())
+        && age > NIGHTLY_MAX_AGE_DAYS
+    {
+        issues.push(HealthIssue::new(
+            format!("Nightly build is {} days old", age),
+            format!("voidbox update {}", name),
+        ));
+    }
+
+    issues
+}
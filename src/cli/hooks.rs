@@ -0,0 +1,245 @@
+//! Manifest hooks
+//!
+//! `[[hooks.post_install]]` steps run once an app's files are unpacked, on
+//! installs, updates and repairs; `[[hooks.pre_remove]]` steps run before
+//! its files are removed. A step either runs a command in the app's
+//! container, where its rootfs is `/`, or writes a file into the rootfs.
+//! Command output is printed, so it lands in the install log, and emitted
+//! as `HookOutput` events for progress frontends. A failing post-install
+//! step fails the install; a failing pre-remove step only warns, so an app
+//! can always be removed.
+
+use crate::events::{Event, emit};
+use crate::manifest::{AppManifest, HookStep};
+use crate::storage::paths;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HookError {
+    #[error("{hook} hook '{step}' failed: {reason}")]
+    Failed {
+        hook: &'static str,
+        step: String,
+        reason: String,
+    },
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// When a manifest's hook steps run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PostInstall,
+    PreRemove,
+}
+
+impl Hook {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Hook::PostInstall => "post_install",
+            Hook::PreRemove => "pre_remove",
+        }
+    }
+
+    fn steps(self, manifest: &AppManifest) -> &[HookStep] {
+        match self {
+            Hook::PostInstall => &manifest.hooks.post_install,
+            Hook::PreRemove => &manifest.hooks.pre_remove,
+        }
+    }
+}
+
+/// Short description of a step for messages
+fn describe(step: &HookStep) -> String {
+    match &step.file {
+        Some(file) => format!("write {}", file),
+        None => step.run.join(" "),
+    }
+}
+
+/// Run a manifest's steps for a hook in order, stopping at the first that
+/// fails
+pub fn run_hooks(manifest: &AppManifest, rootfs: &Path, hook: Hook) -> Result<(), HookError> {
+    let steps = hook.steps(manifest);
+    if steps.is_empty() {
+        return Ok(());
+    }
+
+    say!("[voidbox] Running {} hooks...", hook.as_str());
+    for step in steps {
+        let failed = |reason: String| HookError::Failed {
+            hook: hook.as_str(),
+            step: describe(step),
+            reason,
+        };
+        match &step.file {
+            Some(file) => {
+                say!("[voidbox]   Writing {}", file);
+                write_file(rootfs, file, &step.content).map_err(|e| failed(e.to_string()))?;
+            }
+            None => {
+                say!("[voidbox]   Running {}", describe(step));
+                run_command(manifest, rootfs, hook, &step.run).map_err(failed)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write a file inside the rootfs, refusing paths that lead out of it
+///
+/// A symlink in the unpacked files could point anywhere on the host, so the
+/// path is walked one directory at a time from the rootfs without following
+/// symlinks (or `..`), creating missing directories inside the one already
+/// opened.
+fn write_file(rootfs: &Path, file: &str, content: &str) -> std::io::Result<()> {
+    use nix::errno::Errno;
+    use nix::fcntl::{OFlag, openat};
+    use nix::sys::stat::{Mode, mkdirat};
+    use std::os::fd::OwnedFd;
+    use std::path::Component;
+
+    let outside = || std::io::Error::other("the path leads out of the rootfs");
+    let not_followed = |e: Errno| match e {
+        Errno::ELOOP | Errno::ENOTDIR => outside(),
+        e => e.into(),
+    };
+
+    let mut names = Vec::new();
+    for component in Path::new(file.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(name) => names.push(name),
+            Component::CurDir => {}
+            _ => return Err(outside()),
+        }
+    }
+    let Some((name, dirs)) = names.split_last() else {
+        return Err(std::io::Error::other("no file name"));
+    };
+
+    let mut dir: OwnedFd = fs::File::open(rootfs)?.into();
+    for dir_name in dirs {
+        match mkdirat(&dir, *dir_name, Mode::from_bits_truncate(0o755)) {
+            Ok(()) | Err(Errno::EEXIST) => {}
+            Err(e) => return Err(e.into()),
+        }
+        dir = openat(
+            &dir,
+            *dir_name,
+            OFlag::O_DIRECTORY | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC,
+            Mode::empty(),
+        )
+        .map_err(not_followed)?;
+    }
+    let file = openat(
+        &dir,
+        *name,
+        OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_TRUNC | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC,
+        Mode::from_bits_truncate(0o644),
+    )
+    .map_err(not_followed)?;
+    fs::File::from(file).write_all(content.as_bytes())
+}
+
+/// The voidbox binary to run container commands with; the installed one,
+/// since this may run as an app-specific launcher
+fn voidbox_exe() -> std::io::Result<PathBuf> {
+    let installed = paths::install_path();
+    if installed.exists() {
+        Ok(installed)
+    } else {
+        std::env::current_exe()
+    }
+}
+
+/// Run a command in the app's container, passing on its output line by
+/// line
+fn run_command(
+    manifest: &AppManifest,
+    rootfs: &Path,
+    hook: Hook,
+    command: &[String],
+) -> Result<(), String> {
+    let (reader, writer) = std::io::pipe().map_err(|e| e.to_string())?;
+    let mut child = {
+        // Dropped once started so the pipe closes when the command exits
+        let mut cmd = Command::new(voidbox_exe().map_err(|e| e.to_string())?);
+        cmd.arg("internal-run")
+            .arg(rootfs)
+            .arg(&command[0])
+            .arg("--")
+            .args(&command[1..])
+            .stdout(writer.try_clone().map_err(|e| e.to_string())?)
+            .stderr(writer);
+        cmd.spawn().map_err(|e| e.to_string())?
+    };
+
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else { break };
+        say!("  {}", line);
+        emit(Event::HookOutput {
+            app: manifest.app.name.clone(),
+            hook: hook.as_str().to_string(),
+            line,
+        });
+    }
+
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("exited with {}", status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dirs(name: &str) -> (PathBuf, PathBuf) {
+        let base =
+            std::env::temp_dir().join(format!("voidbox-hooks-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let (rootfs, host) = (base.join("rootfs"), base.join("host"));
+        fs::create_dir_all(&rootfs).unwrap();
+        fs::create_dir_all(&host).unwrap();
+        (rootfs, host)
+    }
+
+    #[test]
+    fn writes_inside_rootfs_creating_directories() {
+        let (rootfs, _) = temp_dirs("write");
+        write_file(&rootfs, "/etc/profile.d/app.sh", "export A=1\n").unwrap();
+        let written = fs::read_to_string(rootfs.join("etc/profile.d/app.sh")).unwrap();
+        assert_eq!(written, "export A=1\n");
+    }
+
+    #[test]
+    fn refuses_parent_components() {
+        let (rootfs, host) = temp_dirs("parent");
+        assert!(write_file(&rootfs, "../host/escaped", "x").is_err());
+        assert!(!host.join("escaped").exists());
+    }
+
+    #[test]
+    fn refuses_symlinked_directories_without_creating_outside() {
+        let (rootfs, host) = temp_dirs("dirlink");
+        std::os::unix::fs::symlink(&host, rootfs.join("etc")).unwrap();
+        assert!(write_file(&rootfs, "etc/new/app.conf", "x").is_err());
+        assert!(!host.join("new").exists());
+    }
+
+    #[test]
+    fn refuses_symlinked_files() {
+        let (rootfs, host) = temp_dirs("filelink");
+        fs::write(host.join("target"), "original").unwrap();
+        std::os::unix::fs::symlink(host.join("target"), rootfs.join("app.conf")).unwrap();
+        assert!(write_file(&rootfs, "app.conf", "x").is_err());
+        assert_eq!(fs::read_to_string(host.join("target")).unwrap(), "original");
+    }
+}
@@ -1,8 +1,17 @@
 //! Info command implementation
 
-use crate::manifest::{InstalledApp, parse_manifest_file};
+use crate::cli::clean::tree_size;
+use crate::cli::exclude::load_excluded;
+use crate::cli::json::{is_json, print_json};
+use crate::cli::optimize::load_optimized;
+use crate::manifest::{
+    AppManifest, InstalledApp, PermissionConfig, SourceConfig, manifest_hash, parse_manifest_file,
+};
+use crate::settings::{load_overrides, manifest_permissions, permission_preset};
 use crate::storage::paths;
+use serde::Serialize;
 use std::fs;
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -17,38 +26,94 @@ pub enum InfoError {
     ManifestError(#[from] crate::manifest::ManifestError),
 }
 
+/// `voidbox info --json`
+#[derive(Debug, Serialize)]
+struct VoidboxInfo {
+    version: &'static str,
+    data_dir: PathBuf,
+    install_path: PathBuf,
+    installed_apps: usize,
+    /// None if the check failed
+    latest_version: Option<String>,
+}
+
+/// `voidbox info <app> --json`
+#[derive(Debug, Serialize)]
+struct AppInfo {
+    name: String,
+    display_name: String,
+    description: String,
+    /// Installed version, or the manifest's when voidbox has no record
+    version: Option<String>,
+    installed_date: Option<String>,
+    license: Option<String>,
+    /// Where the app's files were downloaded from
+    source: String,
+    /// URL the manifest was installed from, if any
+    source_url: Option<String>,
+    install_path: PathBuf,
+    /// Bytes the app's directory takes up, data included
+    size: u64,
+    rootfs: PathBuf,
+    rootfs_exists: bool,
+    manifest: PathBuf,
+    manifest_hash: Option<String>,
+    desktop_entry: PathBuf,
+    /// "present", "missing" or "not integrated"
+    desktop_entry_status: &'static str,
+    /// Where the permissions come from: "override", "preset" or "manifest"
+    permissions_source: &'static str,
+    permissions: PermissionConfig,
+}
+
 /// Show info about voidbox itself
 pub fn show_voidbox_info() -> Result<(), InfoError> {
-    println!("voidbox v{}", crate::VERSION);
-    println!("Universal Linux App Platform");
-    println!();
-    println!("Data directory: {}", paths::data_dir().display());
-    println!("Install path:   {}", paths::install_path().display());
-    println!();
+    if is_json() {
+        let installed_apps = crate::cli::get_installed_apps()
+            .map(|apps| apps.len())
+            .unwrap_or(0);
+        print_json(&VoidboxInfo {
+            version: crate::VERSION,
+            data_dir: paths::data_dir(),
+            install_path: paths::install_path(),
+            installed_apps,
+            latest_version: check_latest_version().ok(),
+        });
+        return Ok(());
+    }
+
+    say!("voidbox v{}", crate::VERSION);
+    say!("Universal Linux App Platform");
+    say!();
+    say!("Data directory: {}", paths::data_dir().display());
+    say!("Install path:   {}", paths::install_path().display());
+    say!();
 
     // Count installed apps
     let db_path = paths::database_path();
     if db_path.exists() {
         let content = fs::read_to_string(&db_path)?;
         let apps: Vec<InstalledApp> = serde_json::from_str(&content).unwrap_or_default();
-        println!("Installed apps: {}", apps.len());
+        say!("Installed apps: {}", apps.len());
     } else {
-        println!("Installed apps: 0");
+        say!("Installed apps: 0");
     }
 
-    println!();
+    say!();
 
     // Check for self-updates
-    print!("Checking for updates... ");
+    if !crate::is_quiet() {
+        print!("Checking for updates... ");
+    }
     match check_latest_version() {
         Ok(latest) => {
             if latest == crate::VERSION {
-                println!("Up to date (v{})", latest);
+                say!("Up to date (v{})", latest);
             } else {
-                println!("Update available: v{}", latest);
+                say!("Update available: v{}", latest);
             }
         }
-        Err(e) => println!("Failed ({})", e),
+        Err(e) => say!("Failed ({})", e),
     }
 
     Ok(())
@@ -63,50 +128,168 @@ pub fn show_app_info(app_name: &str) -> Result<(), InfoError> {
 
     let manifest = parse_manifest_file(&manifest_path)?;
     let rootfs = paths::app_rootfs_dir(app_name);
+    let info = app_info(app_name, &manifest);
 
-    println!("{}", manifest.app.display_name);
-    println!("{}", "=".repeat(manifest.app.display_name.len()));
-    println!();
-    println!("Name:        {}", manifest.app.name);
-    println!("Description: {}", manifest.app.description);
+    if is_json() {
+        print_json(&info);
+        return Ok(());
+    }
 
-    if let Some(version) = &manifest.app.version {
-        println!("Version:     {}", version);
+    say!("{}", manifest.app.display_name);
+    say!("{}", "=".repeat(manifest.app.display_name.len()));
+    say!();
+    say!("Name:        {}", manifest.app.name);
+    say!("Description: {}", manifest.app.description);
+
+    if let Some(version) = &info.version {
+        say!("Version:     {}", version);
+    }
+    if let Some(date) = &info.installed_date {
+        say!("Installed:   {}", date);
     }
 
     if let Some(license) = &manifest.app.license {
-        println!("License:     {}", license);
+        say!("License:     {}", license);
     }
 
-    println!();
-    println!(
+    say!();
+    say!("Source:      {}", info.source);
+    if let Some(url) = &info.source_url {
+        say!("From URL:    {}", url);
+    }
+    say!(
+        "Install dir: {} ({})",
+        info.install_path.display(),
+        indicatif::HumanBytes(info.size)
+    );
+    say!(
         "Rootfs:      {} ({})",
         rootfs.display(),
         if rootfs.exists() { "exists" } else { "missing" }
     );
-    println!("Manifest:    {}", manifest_path.display());
+    if let Some(excluded) = load_excluded(&rootfs) {
+        say!(
+            "Excluded:    {} files ({} saved)",
+            excluded.files.len(),
+            indicatif::HumanBytes(excluded.bytes)
+        );
+    }
+    if let Some(optimized) = load_optimized(&rootfs) {
+        say!(
+            "Optimized:   {} files ({} saved)",
+            optimized.files.len(),
+            indicatif::HumanBytes(optimized.saved())
+        );
+    }
+    say!("Manifest:    {}", manifest_path.display());
+    if let Ok(hash) = manifest_hash(&manifest) {
+        say!("Hash:        {}", hash);
+    }
+    say!(
+        "Desktop:     {} ({})",
+        info.desktop_entry.display(),
+        info.desktop_entry_status
+    );
 
     // Show permissions
-    println!();
-    println!("Permissions:");
-    let perms = &manifest.permissions;
-    println!("  Network:    {}", if perms.network { "yes" } else { "no" });
-    println!("  Audio:      {}", if perms.audio { "yes" } else { "no" });
-    println!(
+    say!();
+    let overrides = load_overrides(app_name).ok().flatten();
+    match (&overrides, permission_preset(&manifest)) {
+        (Some(_), _) => say!("Permissions (user override):"),
+        (None, Some(preset)) => say!("Permissions ({} preset):", preset),
+        (None, None) => say!("Permissions:"),
+    }
+    let perms = &overrides.unwrap_or_else(|| manifest_permissions(&manifest));
+    say!("  Network:    {}", if perms.network { "yes" } else { "no" });
+    say!("  Audio:      {}", if perms.audio { "yes" } else { "no" });
+    say!(
         "  Microphone: {}",
         if perms.microphone { "yes" } else { "no" }
     );
-    println!("  GPU:        {}", if perms.gpu { "yes" } else { "no" });
-    println!("  Camera:     {}", if perms.camera { "yes" } else { "no" });
-    println!("  Home:       {}", if perms.home { "yes" } else { "no" });
-    println!(
+    say!("  GPU:        {}", if perms.gpu { "yes" } else { "no" });
+    say!("  Camera:     {}", if perms.camera { "yes" } else { "no" });
+    say!("  Home:       {}", if perms.home { "yes" } else { "no" });
+    say!(
         "  Dev Mode:   {}",
         if perms.dev_mode { "yes" } else { "no" }
     );
+    say!("  Clipboard:  {}", perms.clipboard);
+    if !manifest.dbus.is_default() {
+        say!(
+            "  Session bus: {}",
+            if perms.session_bus {
+                "as the manifest asks"
+            } else {
+                "default names only"
+            }
+        );
+    }
 
     Ok(())
 }
 
+fn app_info(app_name: &str, manifest: &AppManifest) -> AppInfo {
+    let rootfs = paths::app_rootfs_dir(app_name);
+    let overrides = load_overrides(app_name).ok().flatten();
+    let permissions_source = match (&overrides, permission_preset(manifest)) {
+        (Some(_), _) => "override",
+        (None, Some(_)) => "preset",
+        (None, None) => "manifest",
+    };
+    let record = crate::cli::get_installed_apps()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|a| paths::same_app(&a.name, app_name));
+    let desktop_entry = paths::desktop_entry_path(&manifest.desktop_id());
+    let desktop_entry_status = if record.as_ref().is_some_and(|r| r.not_integrated) {
+        "not integrated"
+    } else if desktop_entry.exists() {
+        "present"
+    } else {
+        "missing"
+    };
+
+    AppInfo {
+        name: manifest.app.name.clone(),
+        display_name: manifest.app.display_name.clone(),
+        description: manifest.app.description.clone(),
+        version: record
+            .as_ref()
+            .and_then(|r| r.version.clone())
+            .or_else(|| manifest.app.version.clone()),
+        installed_date: record.as_ref().and_then(|r| r.installed_date.clone()),
+        license: manifest.app.license.clone(),
+        source: source_description(&manifest.source),
+        source_url: record.and_then(|r| r.source_url),
+        install_path: paths::app_dir(app_name),
+        size: tree_size(&paths::app_dir(app_name)),
+        rootfs_exists: rootfs.exists(),
+        rootfs,
+        manifest: paths::manifest_path(app_name),
+        manifest_hash: manifest_hash(manifest).ok(),
+        desktop_entry,
+        desktop_entry_status,
+        permissions_source,
+        permissions: overrides.unwrap_or_else(|| manifest_permissions(manifest)),
+    }
+}
+
+/// Where an app's files are downloaded from, for display
+fn source_description(source: &SourceConfig) -> String {
+    match source {
+        SourceConfig::Github {
+            owner, repo, tag, ..
+        } => format!(
+            "https://github.com/{}/{} ({})",
+            owner,
+            repo,
+            tag.as_deref().unwrap_or("latest release")
+        ),
+        SourceConfig::Direct { url, .. } => url.clone(),
+        SourceConfig::Local { path } => path.display().to_string(),
+    }
+}
+
 fn check_latest_version() -> Result<String, String> {
     let status = self_update::backends::github::Update::configure()
         .repo_owner(crate::SELF_UPDATE_OWNER)
@@ -1,18 +1,41 @@
 //! Install command implementation
 
-use crate::desktop::{create_app_wrapper, create_desktop_entry, extract_icon};
+use crate::cli::appimage::{extract_appimage, is_appimage};
+use crate::cli::conflict::{Conflict, ConflictPolicy, detect_conflicts, resolve_conflicts};
+use crate::cli::dependencies::missing_dependencies;
+use crate::cli::exclude::{ExcludeRules, ExcludedFiles, prune_excluded, save_excluded};
+use crate::cli::hooks::{Hook, run_hooks};
+use crate::cli::integrity::record_installed_files;
+use crate::cli::json::{is_json, print_json};
+use crate::cli::optimize::{optimize_payload, payload_originals};
+use crate::cli::payload::find_entrypoint;
+use crate::cli::pipeline::{Artifact, InstallJournal, InstallStep, run_step};
+use crate::cli::plan::plan_install;
+use crate::cli::repo::repository_index;
+use crate::cli::versions::{
+    SlotData, manifest_at_version, share_slot_data, slot_app, slot_manifest, split_version,
+};
+use crate::desktop::{KeepIntegration, integrate_app, refresh_batch};
+use crate::events::{Event, EventKind, emit, fire_event, subscribe};
+use crate::gui::{Frontend, InstallType, frontend, run_installer};
 use crate::manifest::{
-    AppManifest, ArchiveType, InstalledApp, SourceConfig, parse_manifest_file, parse_manifest_url,
-    validate_manifest,
+    AppManifest, ArchiveType, ChecksumCheck, InstalledApp, MANIFEST_HASH_PREFIX, SignatureStatus,
+    SourceConfig, for_host_arch, host_debian_arch, load_checksum_db, manifest_hash, manifest_text,
+    parse_manifest_file, parse_manifest_url, resolve_index_app, split_tap_app, validate_manifest,
+    verify_signature,
+};
+use crate::runtime::is_app_running;
+use crate::settings::load_config;
+use crate::storage::{
+    AppLog, append_app_log, cached_download, capture_stdout, enforce_cache_limit, http_get, paths,
+    remove_cached, scan_artifact, sha256_file,
 };
-use crate::storage::{download_file, paths};
 use flate2::read::GzDecoder;
 use serde::Deserialize;
 use std::fs::{self, File};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use thiserror::Error;
-use walkdir::WalkDir;
 
 #[derive(Error, Debug)]
 pub enum InstallError {
@@ -25,11 +48,20 @@ pub enum InstallError {
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
+    #[error("Scan error: {0}")]
+    ScanError(#[from] crate::storage::ScanError),
+
+    #[error("Hook error: {0}")]
+    HookError(#[from] crate::cli::HookError),
+
     #[error("Installation failed: {0}")]
     Failed(String),
 
     #[error("App already installed: {0}")]
     AlreadyInstalled(String),
+
+    #[error("Install conflict: {0}")]
+    Conflict(String),
 }
 
 #[derive(Deserialize)]
@@ -45,12 +77,184 @@ struct GitHubAsset {
 }
 
 /// Install an app from a manifest source
-pub fn install_app(source: &str, force: bool) -> Result<(), InstallError> {
-    println!("[voidbox] Installing from {}...", source);
+///
+/// In an interactive session the install plan is shown and confirmed first,
+/// unless `yes`. With `dry_run` the plan is only printed (as JSON in JSON
+/// output mode) and nothing is changed. A source of the form
+/// `<name>@<version>` installs that version and pins the app to it, or with
+/// `slot` installs it side by side with the app's main install.
+///
+/// Started from the desktop rather than a terminal, the installer window
+/// confirms the install and shows its progress instead.
+pub fn install_app(
+    source: &str,
+    policy: ConflictPolicy,
+    yes: bool,
+    dry_run: bool,
+    slot: Option<SlotData>,
+) -> Result<(), InstallError> {
+    let (source, version) = split_version(source);
+    if slot.is_some() && version.is_none() {
+        return Err(InstallError::Failed(format!(
+            "Side-by-side installs need a version: voidbox install {}@<version>",
+            source
+        )));
+    }
+    let resolve = || resolve_request(source, version, slot);
+
+    if dry_run {
+        return dry_run_install(&resolve()?.0, policy);
+    }
+
+    if frontend() == Frontend::Gui {
+        let (manifest, signature) = resolve()?;
+        install_in_gui(&manifest, signature)?;
+        if installed_at_requested_version(&manifest).is_none() {
+            // Cancelled in the window
+            return Ok(());
+        }
+        return record_install(&manifest, source, version.is_some(), slot);
+    }
+
+    // Progress events are the install's JSON output
+    let _events = is_json().then(|| subscribe(print_json::<Event>));
+
+    match version {
+        Some(version) => say!("[voidbox] Installing {} {}...", source, version),
+        None => say!("[voidbox] Installing from {}...", source),
+    }
+    let (manifest, _) = resolve()?;
+
+    let ask = !yes && !is_json() && crate::can_prompt();
+    if ask
+        && (policy == ConflictPolicy::Overwrite
+            || installed_at_requested_version(&manifest).is_none())
+    {
+        for dependency in missing_dependencies(&manifest)? {
+            let plan = plan_install(&dependency.manifest)?;
+            say!(
+                "[voidbox] {} {} (needed by {}):",
+                dependency.manifest.app.display_name,
+                plan.version,
+                dependency.needed_by
+            );
+            plan.print();
+        }
+        let plan = plan_install(&manifest)?;
+        say!("[voidbox] {} {}:", manifest.app.display_name, plan.version);
+        plan.print();
+        if !plan.confirm(yes) {
+            say!("[voidbox] Cancelled");
+            return Ok(());
+        }
+    }
+
+    install_app_from_manifest(&manifest, policy)?;
+    record_install(&manifest, source, version.is_some(), slot)
+}
+
+/// Find the manifest for a source at a version, made a side-by-side install
+/// with `slot`
+pub(crate) fn resolve_request(
+    source: &str,
+    version: Option<&str>,
+    slot: Option<SlotData>,
+) -> Result<(AppManifest, SignatureStatus), InstallError> {
+    let manifest = resolve_source(source)?;
+    // Checked before a version or slot changes the manifest, or the
+    // host's source is picked
+    let signature = check_signature(&manifest)?;
+    validate_manifest(&manifest)?;
+    let manifest = for_host_arch(&manifest)?;
+    let manifest = match version {
+        Some(version) => manifest_at_version(source, &manifest, version)
+            .map_err(|e| InstallError::Failed(e.to_string()))?,
+        None => manifest,
+    };
+    let manifest = match slot {
+        Some(_) => slot_manifest(&manifest),
+        None => manifest,
+    };
+    Ok((manifest, signature))
+}
+
+/// Check a manifest's signature before it is installed, refusing one that
+/// doesn't match or, with `signatures.require`, isn't trusted
+pub(crate) fn check_signature(manifest: &AppManifest) -> Result<SignatureStatus, InstallError> {
+    let config = load_config().unwrap_or_default().signatures;
+    let status = verify_signature(manifest, &config.trusted_keys)?;
+    match &status {
+        SignatureStatus::Trusted { .. } => say!("[voidbox] {}", status),
+        SignatureStatus::UnknownKey { key_id } => say!(
+            "[voidbox] Warning: {} is signed with key {}, which isn't trusted",
+            manifest.app.name,
+            key_id
+        ),
+        SignatureStatus::Unsigned => {}
+    }
+    if config.require && !matches!(status, SignatureStatus::Trusted { .. }) {
+        return Err(InstallError::Failed(format!(
+            "{} isn't signed with a trusted key (signatures.require is set)",
+            manifest.app.name
+        )));
+    }
+    Ok(status)
+}
+
+/// Confirm and install a manifest in the installer window
+fn install_in_gui(manifest: &AppManifest, signature: SignatureStatus) -> Result<(), InstallError> {
+    let manifest_content = toml::to_string_pretty(manifest)
+        .map_err(|e| InstallError::Failed(format!("Failed to serialize manifest: {}", e)))?;
+    run_installer(InstallType::AppInstall {
+        name: manifest.app.name.clone(),
+        display_name: manifest.app.display_name.clone(),
+        manifest_content,
+        signature,
+    })
+    .map_err(|e| InstallError::Failed(format!("Could not open the installer: {}", e)))
+}
+
+/// Record where an install came from and whether it's pinned, and share a
+/// side-by-side install's data when asked
+pub(crate) fn record_install(
+    manifest: &AppManifest,
+    source: &str,
+    pinned: bool,
+    slot: Option<SlotData>,
+) -> Result<(), InstallError> {
+    let is_url = source.starts_with("http://") || source.starts_with("https://");
+    if is_url {
+        update_record(&manifest.app.name, |app| {
+            app.source_url = Some(source.to_string())
+        })?;
+    }
+    if pinned {
+        update_record(&manifest.app.name, |app| app.pinned = true)?;
+    }
+    if slot == Some(SlotData::Shared)
+        && paths::app_rootfs_dir(&manifest.app.name).exists()
+        && let Some((app, _)) = manifest.app.name.rsplit_once('@')
+    {
+        share_slot_data(&manifest.app.name, app)?;
+    }
+    Ok(())
+}
 
-    // Parse manifest based on source type
-    let manifest = if source.starts_with("http://") || source.starts_with("https://") {
-        parse_manifest_url(source)?
+/// Find the manifest a source names: a file, URL, manifest hash, tap app,
+/// installed app or repository app
+pub(crate) fn resolve_source(source: &str) -> Result<AppManifest, InstallError> {
+    let is_url = source.starts_with("http://") || source.starts_with("https://");
+    Ok(if is_url {
+        let manifest = parse_manifest_url(source)?;
+        show_manifest_origin(source, &manifest);
+        manifest
+    } else if source.starts_with(MANIFEST_HASH_PREFIX) {
+        find_manifest_by_hash(source)?
+    } else if let Some((tap, app)) = split_tap_app(source) {
+        let index = repository_index(tap).map_err(|e| InstallError::Failed(e.to_string()))?;
+        let manifest = resolve_index_app(tap, &index, app)?;
+        say!("[voidbox] Found {} in {}", app, tap);
+        manifest
     } else if Path::new(source).exists() {
         parse_manifest_file(Path::new(source))?
     } else {
@@ -58,21 +262,329 @@ pub fn install_app(source: &str, force: bool) -> Result<(), InstallError> {
         let manifest_path = paths::manifest_path(source);
         if manifest_path.exists() {
             parse_manifest_file(&manifest_path)?
+        } else if let Some(manifest) = find_in_repositories(source)? {
+            manifest
         } else {
-            // TODO: Try registry lookup
             return Err(InstallError::Failed(format!(
                 "Manifest not found: {}. Try 'voidbox install ./manifest.toml' or a URL.",
                 source
             )));
         }
+    })
+}
+
+/// Report what installing a manifest would do without changing anything
+pub(crate) fn dry_run_install(
+    manifest: &AppManifest,
+    policy: ConflictPolicy,
+) -> Result<(), InstallError> {
+    let display_name = &manifest.app.display_name;
+    if policy != ConflictPolicy::Overwrite
+        && let Some(version) = installed_at_requested_version(manifest)
+    {
+        say!(
+            "[voidbox] {} v{} is already installed; nothing would change",
+            display_name,
+            version
+        );
+        return Ok(());
+    }
+
+    let plan = plan_install(manifest)?;
+    if is_json() {
+        print_json(&plan);
+        return Ok(());
+    }
+
+    for dependency in missing_dependencies(manifest)? {
+        say!(
+            "[voidbox] {} needs {}, which would be installed first",
+            display_name,
+            dependency.manifest.app.display_name
+        );
+    }
+
+    say!(
+        "[voidbox] Installing {} {} would make these changes:",
+        display_name,
+        plan.version
+    );
+    plan.print_details();
+    for conflict in detect_conflicts(manifest) {
+        say!("[voidbox] Conflict: {}", conflict);
+    }
+    say!("[voidbox] Dry run; nothing was changed");
+    Ok(())
+}
+
+/// Print where a manifest fetched from a URL comes from
+fn show_manifest_origin(url: &str, manifest: &AppManifest) {
+    let host = url
+        .split("://")
+        .nth(1)
+        .and_then(|rest| rest.split('/').next())
+        .unwrap_or(url);
+    say!(
+        "[voidbox] Manifest for {} from {}",
+        manifest.app.display_name,
+        host
+    );
+    if let Some(publisher) = &manifest.app.publisher {
+        say!("[voidbox]   Publisher: {}", publisher);
+    }
+    match &manifest.source {
+        SourceConfig::Github { owner, repo, .. } => {
+            say!("[voidbox]   Downloads from: github.com/{}/{}", owner, repo)
+        }
+        SourceConfig::Direct { url, .. } => say!("[voidbox]   Downloads from: {}", url),
+        SourceConfig::Local { path } => {
+            say!("[voidbox]   Local files: {}", path.display())
+        }
+    }
+    if url.starts_with("http://") {
+        say!("[voidbox] Warning: Manifest was fetched over plain HTTP");
+    }
+}
+
+/// Change an installed app's database record, if it has one
+pub(crate) fn update_record(
+    app_name: &str,
+    change: impl FnOnce(&mut InstalledApp),
+) -> Result<(), InstallError> {
+    let db_path = paths::database_path();
+    if !db_path.exists() {
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(&db_path)?;
+    let mut apps: Vec<InstalledApp> = serde_json::from_str(&content).unwrap_or_default();
+    let Some(app) = apps.iter_mut().find(|a| paths::same_app(&a.name, app_name)) else {
+        return Ok(());
+    };
+    change(app);
+
+    let content = serde_json::to_string_pretty(&apps)
+        .map_err(|e| InstallError::Failed(format!("Failed to serialize: {}", e)))?;
+    fs::write(&db_path, content)?;
+    Ok(())
+}
+
+/// Look an app up in the configured repository indexes
+fn find_in_repositories(app: &str) -> Result<Option<AppManifest>, InstallError> {
+    let config = load_config().map_err(|e| InstallError::Failed(e.to_string()))?;
+
+    for location in &config.repositories {
+        let index = match repository_index(location) {
+            Ok(index) => index,
+            Err(e) => {
+                say!(
+                    "[voidbox] Warning: Could not load repository {}: {}",
+                    location,
+                    e
+                );
+                continue;
+            }
+        };
+        if index.apps.contains_key(app) {
+            say!("[voidbox] Found {} in {}", app, location);
+            return Ok(Some(resolve_index_app(location, &index, app)?));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Find a saved manifest by its content hash
+fn find_manifest_by_hash(hash: &str) -> Result<AppManifest, InstallError> {
+    let dir = paths::manifests_dir();
+    if dir.exists() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if let Ok(manifest) = parse_manifest_file(&path)
+                && manifest_hash(&manifest).is_ok_and(|h| h == hash)
+            {
+                return Ok(manifest);
+            }
+        }
+    }
+
+    Err(InstallError::Failed(format!(
+        "No manifest with hash {} found in {}",
+        hash,
+        paths::manifests_dir().display()
+    )))
+}
+
+/// Install an app from an already-parsed manifest, after the apps it
+/// depends on
+///
+/// Conflicts with existing files are resolved according to `policy`.
+pub fn install_app_from_manifest(
+    manifest: &AppManifest,
+    policy: ConflictPolicy,
+) -> Result<(), InstallError> {
+    install_app_dependencies(manifest, policy)?;
+    install_without_dependencies(manifest, policy)
+}
+
+/// Install the apps a manifest depends on that aren't installed yet
+pub(crate) fn install_app_dependencies(
+    manifest: &AppManifest,
+    policy: ConflictPolicy,
+) -> Result<(), InstallError> {
+    for dependency in missing_dependencies(manifest)? {
+        say!(
+            "[voidbox] Installing {}, which {} needs...",
+            dependency.manifest.app.display_name,
+            dependency.needed_by
+        );
+        install_without_dependencies(&dependency.manifest, policy)?;
+        record_install(&dependency.manifest, &dependency.source, false, None)?;
+    }
+    Ok(())
+}
+
+fn install_without_dependencies(
+    manifest: &AppManifest,
+    policy: ConflictPolicy,
+) -> Result<(), InstallError> {
+    if policy != ConflictPolicy::Overwrite
+        && let Some(version) = installed_at_requested_version(manifest)
+    {
+        say!(
+            "[voidbox] {} v{} is already installed. Use --reinstall to install it again.",
+            manifest.app.display_name,
+            version
+        );
+        emit(Event::Skipped {
+            app: manifest.app.name.clone(),
+            reason: format!("v{} is already installed", version),
+        });
+        return Ok(());
+    }
+
+    let conflicts = detect_conflicts(manifest);
+    let result = match resolve_conflicts(manifest, &conflicts, policy) {
+        Ok(ConflictPolicy::Skip)
+            if conflicts
+                .iter()
+                .any(|c| matches!(c, Conflict::AlreadyInstalled { .. })) =>
+        {
+            say!(
+                "[voidbox] {} is already installed, skipping",
+                manifest.app.display_name
+            );
+            emit(Event::Skipped {
+                app: manifest.app.name.clone(),
+                reason: "already installed".to_string(),
+            });
+            return Ok(());
+        }
+        Ok(ConflictPolicy::Skip)
+            if let Some(other) = conflicts
+                .iter()
+                .find(|c| matches!(c, Conflict::OtherApp { .. })) =>
+        {
+            say!("[voidbox] {}, skipping", other);
+            emit(Event::Skipped {
+                app: manifest.app.name.clone(),
+                reason: other.to_string(),
+            });
+            return Ok(());
+        }
+        Ok(ConflictPolicy::Skip) => install_manifest_keeping(manifest, true, &conflicts),
+        Ok(_) => install_manifest(manifest, true),
+        Err(e) => Err(e),
+    };
+    report_result(EventKind::Install, manifest, &result);
+    result
+}
+
+/// Fire the webhook event for a finished install or update
+pub(crate) fn report_result(
+    kind: EventKind,
+    manifest: &AppManifest,
+    result: &Result<(), InstallError>,
+) {
+    let app_name = manifest.app.name.as_str();
+    match result {
+        Ok(()) => {
+            let version = installed_version(app_name);
+            fire_event(
+                kind,
+                Some(app_name),
+                version.as_deref(),
+                &format!("{} installed", manifest.app.display_name),
+            );
+        }
+        // Nothing happened, so there is nothing to report
+        Err(InstallError::AlreadyInstalled(_)) => {}
+        Err(e) => fire_event(EventKind::Failure, Some(app_name), None, &e.to_string()),
+    }
+}
+
+/// Installed version, if the app is already installed at the version this
+/// manifest asks for (or from this exact manifest when it doesn't pin one)
+pub fn installed_at_requested_version(manifest: &AppManifest) -> Option<String> {
+    let app_name = &manifest.app.name;
+    if !paths::app_rootfs_dir(app_name).exists() {
+        return None;
+    }
+
+    let record = crate::cli::get_installed_apps()
+        .ok()?
+        .into_iter()
+        .find(|a| paths::same_app(&a.name, app_name))?;
+    if record.id.is_some() && record.id != manifest.app.id {
+        return None;
+    }
+    let installed = record
+        .version
+        .clone()
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let same = match &manifest.app.version {
+        Some(requested) => {
+            record.version.as_deref().map(|v| v.trim_start_matches('v'))
+                == Some(requested.trim_start_matches('v'))
+        }
+        None => {
+            record.manifest_hash.is_some() && record.manifest_hash == manifest_hash(manifest).ok()
+        }
     };
 
-    install_app_from_manifest(&manifest, force)
+    same.then_some(installed)
+}
+
+fn installed_version(app_name: &str) -> Option<String> {
+    crate::cli::get_installed_apps()
+        .ok()?
+        .into_iter()
+        .find(|a| paths::same_app(&a.name, app_name))
+        .and_then(|a| a.version)
+}
+
+/// Run the install pipeline for a manifest without firing events
+pub(crate) fn install_manifest(manifest: &AppManifest, force: bool) -> Result<(), InstallError> {
+    install_manifest_keeping(manifest, force, &[])
 }
 
-/// Install an app from an already-parsed manifest
-pub fn install_app_from_manifest(manifest: &AppManifest, force: bool) -> Result<(), InstallError> {
-    validate_manifest(manifest)?;
+/// Run the install pipeline, leaving the conflicting files in `keep` alone
+fn install_manifest_keeping(
+    manifest: &AppManifest,
+    force: bool,
+    keep: &[Conflict],
+) -> Result<(), InstallError> {
+    // Side-by-side installs carry their version in the name, which
+    // manifests can't; the rest is checked as written
+    match slot_app(manifest) {
+        Some(app) => {
+            let mut unslotted = manifest.clone();
+            unslotted.app.name = app.to_string();
+            validate_manifest(&unslotted)?;
+        }
+        None => validate_manifest(manifest)?,
+    }
 
     let app_name = &manifest.app.name;
     let app_dir = paths::app_dir(app_name);
@@ -82,83 +594,330 @@ pub fn install_app_from_manifest(manifest: &AppManifest, force: bool) -> Result<
         return Err(InstallError::AlreadyInstalled(app_name.clone()));
     }
 
+    // Replacing files under a running app corrupts it
+    if is_app_running(app_name) {
+        return Err(InstallError::Failed(format!(
+            "{} is running; close it first or use 'voidbox update {} --stage'",
+            app_name, app_name
+        )));
+    }
+
+    // Desktop databases are refreshed once, when the outermost batch ends
+    let _refresh = refresh_batch();
+
+    let fresh_install = !app_dir.exists();
+
     // Create directories
     paths::ensure_dirs()?;
     fs::create_dir_all(&app_dir)?;
 
+    with_install_log(app_name, "install", || {
+        install_files(manifest, keep, fresh_install)
+    })
+}
+
+/// Run part of an install or update with its output also going to the
+/// app's install log, followed by how it ended
+pub(crate) fn with_install_log<T>(
+    app_name: &str,
+    action: &str,
+    run: impl FnOnce() -> Result<T, InstallError>,
+) -> Result<T, InstallError> {
+    append_app_log(app_name, AppLog::Install, &format!("{} started", action));
+    let capture = capture_stdout(app_name, AppLog::Install);
+    let result = run();
+    drop(capture);
+    let outcome = match &result {
+        Ok(_) => format!("{} finished", action),
+        Err(e) => format!("{} failed: {}", action, e),
+    };
+    append_app_log(app_name, AppLog::Install, &outcome);
+    result
+}
+
+/// Save the manifest, fill the rootfs and add the app to the desktop
+fn install_files(
+    manifest: &AppManifest,
+    keep: &[Conflict],
+    fresh_install: bool,
+) -> Result<(), InstallError> {
+    let app_name = &manifest.app.name;
+
     // Save manifest locally
     let manifest_path = paths::manifest_path(app_name);
-    let manifest_content = toml::to_string_pretty(&manifest)
-        .map_err(|e| InstallError::Failed(format!("Failed to serialize manifest: {}", e)))?;
-    fs::write(&manifest_path, manifest_content)?;
+    fs::write(&manifest_path, manifest_text(manifest)?)?;
 
-    // Download and setup base image
+    // Download base image, dependencies and the app itself
     let rootfs = paths::app_rootfs_dir(app_name);
-    setup_base_image(&rootfs, &manifest)?;
+    let mut journal = InstallJournal::open(&rootfs, manifest);
+    let actual_version = prepare_rootfs(&rootfs, manifest, &mut journal)?;
+
+    run_step(app_name, InstallStep::Integrate, || {
+        // Save installed app info with actual version
+        save_installed_app(manifest, actual_version.as_deref())?;
+
+        let keep = KeepIntegration {
+            desktop_entry: keep.iter().any(|c| matches!(c, Conflict::DesktopEntry(_))),
+            wrapper: keep.iter().any(|c| matches!(c, Conflict::Wrapper(_))),
+        };
+        if keep.desktop_entry {
+            say!("[voidbox] Keeping existing desktop entry");
+        }
+        if keep.wrapper {
+            say!("[voidbox] Keeping existing command wrapper");
+        }
+        integrate_installed(manifest, keep)?;
+        Ok(())
+    })?;
+    journal.finish();
+    emit(Event::Integrated {
+        app: app_name.clone(),
+    });
+
+    // Only fresh installs are quarantined, not reinstalls or updates
+    if fresh_install {
+        crate::cli::quarantine_if_enabled(app_name);
+    }
 
-    // Install dependencies
-    install_dependencies(&rootfs, &manifest)?;
+    say!(
+        "[voidbox] Successfully installed {}!",
+        manifest.app.display_name
+    );
+    say!("[voidbox] Run with: voidbox run {}", app_name);
+    emit(Event::Completed {
+        app: app_name.clone(),
+        version: actual_version,
+    });
+
+    Ok(())
+}
 
-    // Download and install the app (returns actual version downloaded)
-    let actual_version = install_app_binary(&rootfs, &manifest)?;
+/// Populate a rootfs with the base image, dependencies and app binary
+/// Returns the actual version downloaded (if available)
+///
+/// Runs the resolve, download, verify and extract steps, skipping any the
+/// journal records as completed by an earlier attempt.
+pub(crate) fn prepare_rootfs(
+    rootfs: &Path,
+    manifest: &AppManifest,
+    journal: &mut InstallJournal,
+) -> Result<Option<String>, InstallError> {
+    let app_name = manifest.app.name.as_str();
 
-    // Extract icon
-    let icon_filename = manifest.desktop.icon.as_deref();
-    if let Err(e) = extract_icon(app_name, icon_filename) {
-        println!("[voidbox] Warning: Could not extract icon: {}", e);
+    emit(Event::ResolveStarted {
+        app: app_name.to_string(),
+        download_size: manifest.hints.download_bytes(),
+        extract_seconds: manifest.hints.extract_seconds(),
+    });
+    if !journal.is_done(InstallStep::Resolve) {
+        let (base, app) = run_step(app_name, InstallStep::Resolve, || {
+            Ok((resolve_base_image(rootfs)?, resolve_app(manifest)?))
+        })?;
+        journal.base = base;
+        journal.app = app;
+        journal.complete(InstallStep::Resolve)?;
     }
 
-    // Create desktop entry
-    if let Err(e) = create_desktop_entry(&manifest) {
-        println!("[voidbox] Warning: Could not create desktop entry: {}", e);
+    if !journal.is_done(InstallStep::Download) {
+        let (base_archive, app_archive) = run_step(app_name, InstallStep::Download, || {
+            Ok((
+                download_artifact("Ubuntu base", journal.base.as_ref())?,
+                download_artifact(&manifest.app.display_name, journal.app.as_ref())?,
+            ))
+        })?;
+        journal.base_archive = base_archive;
+        journal.app_archive = app_archive;
+        journal.complete(InstallStep::Download)?;
     }
 
-    // Create wrapper script
-    if let Err(e) = create_app_wrapper(app_name) {
-        println!("[voidbox] Warning: Could not create wrapper script: {}", e);
+    if !journal.is_done(InstallStep::Verify) {
+        let (base_archive, app_archive) = run_step(app_name, InstallStep::Verify, || {
+            Ok((
+                verify_artifact(
+                    app_name,
+                    journal.base.as_ref(),
+                    journal.base_archive.as_deref(),
+                )?,
+                verify_artifact(
+                    app_name,
+                    journal.app.as_ref(),
+                    journal.app_archive.as_deref(),
+                )?,
+            ))
+        })?;
+        journal.base_archive = base_archive;
+        journal.app_archive = app_archive;
+        journal.complete(InstallStep::Verify)?;
     }
 
-    // Save installed app info with actual version
-    save_installed_app(&manifest, actual_version.as_deref())?;
+    if !journal.is_done(InstallStep::Extract) {
+        emit(Event::ExtractStarted {
+            app: app_name.to_string(),
+        });
+        run_step(app_name, InstallStep::Extract, || {
+            if let Some(archive) = &journal.base_archive {
+                extract_base_image(rootfs, archive)?;
+            }
+            install_dependencies(rootfs, manifest)?;
+            install_app_files(
+                rootfs,
+                manifest,
+                journal.app.as_ref(),
+                journal.app_archive.as_deref(),
+            )
+        })?;
+        journal.complete(InstallStep::Extract)?;
+
+        // Keep the download cache within its configured size
+        enforce_cache_limit();
+    }
 
-    println!(
-        "[voidbox] Successfully installed {}!",
-        manifest.app.display_name
-    );
-    println!("[voidbox] Run with: voidbox run {}", app_name);
+    if !journal.is_done(InstallStep::Hooks) {
+        run_step(app_name, InstallStep::Hooks, || {
+            Ok(run_hooks(manifest, rootfs, Hook::PostInstall)?)
+        })?;
+        journal.complete(InstallStep::Hooks)?;
+    }
+    record_installed_files(rootfs, manifest)?;
+    emit(Event::Extracted {
+        app: app_name.to_string(),
+    });
 
-    Ok(())
+    Ok(journal
+        .app
+        .as_ref()
+        .map(|a| a.version.clone())
+        .filter(|v| v != "latest"))
 }
 
-/// Setup base image (Ubuntu) for an app
-fn setup_base_image(rootfs: &Path, _manifest: &AppManifest) -> Result<(), InstallError> {
-    if rootfs.exists() {
-        // Check if base is already setup
-        if rootfs.join("etc/os-release").exists() {
-            println!("[voidbox] Base image already exists, skipping...");
-            return Ok(());
-        }
-        fs::remove_dir_all(rootfs)?;
+/// Resolve the Ubuntu base image, unless the rootfs already has one
+fn resolve_base_image(rootfs: &Path) -> Result<Option<Artifact>, InstallError> {
+    if rootfs.join("etc/os-release").exists() {
+        say!("[voidbox] Base image already exists, skipping...");
+        return Ok(None);
     }
 
-    fs::create_dir_all(rootfs)?;
+    say!("[voidbox] Fetching Ubuntu base image...");
+    let (version, url) = fetch_latest_ubuntu_base()?;
+    Ok(Some(Artifact {
+        version,
+        url,
+        sha256: None,
+        repository: None,
+    }))
+}
+
+/// Resolve the app artifact (None for local sources)
+fn resolve_app(manifest: &AppManifest) -> Result<Option<Artifact>, InstallError> {
+    Ok(resolve_download(manifest)?.map(|(version, url)| Artifact {
+        version,
+        url,
+        sha256: manifest.source.sha256().map(str::to_string),
+        repository: manifest.repository.clone(),
+    }))
+}
 
-    println!("[voidbox] Fetching Ubuntu base image...");
+/// Download an artifact into the cache
+fn download_artifact(
+    name: &str,
+    artifact: Option<&Artifact>,
+) -> Result<Option<PathBuf>, InstallError> {
+    let Some(artifact) = artifact else {
+        return Ok(None);
+    };
+    say!("[voidbox] Downloading {} {}...", name, artifact.version);
+    let sha256 = artifact.trusted_sha256();
+    Ok(Some(cached_download(
+        &artifact.url,
+        sha256.as_deref(),
+        true,
+    )?))
+}
 
-    // Fetch latest Ubuntu base
-    let (version, url) = fetch_latest_ubuntu_base()?;
-    println!("[voidbox] Downloading Ubuntu {} base...", version);
+/// Scan a downloaded artifact, fetching it again if it left the cache
+fn verify_artifact(
+    app_name: &str,
+    artifact: Option<&Artifact>,
+    archive: Option<&Path>,
+) -> Result<Option<PathBuf>, InstallError> {
+    let Some(artifact) = artifact else {
+        return Ok(None);
+    };
+    let archive = match archive {
+        Some(path) if path.exists() => path.to_path_buf(),
+        _ => cached_download(&artifact.url, artifact.trusted_sha256().as_deref(), true)?,
+    };
+    scan_artifact(app_name, &artifact.url, &archive)?;
+    check_known_checksum(artifact, &archive)?;
+    Ok(Some(archive))
+}
+
+/// Compare a download with the checksum its manifest gives, or else with
+/// the known-good checksum database
+fn check_known_checksum(artifact: &Artifact, archive: &Path) -> Result<(), InstallError> {
+    let require = load_config().unwrap_or_default().checksums.require;
+    let sha256 = sha256_file(archive)?;
+
+    if let Some(expected) = &artifact.sha256 {
+        if !expected.eq_ignore_ascii_case(&sha256) {
+            remove_cached(&artifact.url);
+            return Err(InstallError::Failed(format!(
+                "Checksum mismatch for {}: the manifest expects {}, the download has {}",
+                artifact.url,
+                expected.to_ascii_lowercase(),
+                sha256
+            )));
+        }
+        say!("[voidbox] Checksum matches the manifest");
+        return Ok(());
+    }
+
+    match load_checksum_db().check(
+        &artifact.url,
+        &artifact.version,
+        artifact.repository.as_deref(),
+        &sha256,
+    ) {
+        ChecksumCheck::Verified => {
+            say!("[voidbox] Checksum matches the known-good database");
+            Ok(())
+        }
+        ChecksumCheck::Mismatch { expected } => {
+            // Fetch it again on retry rather than trusting the cached copy
+            remove_cached(&artifact.url);
+            Err(InstallError::Failed(format!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                artifact.url, expected, sha256
+            )))
+        }
+        ChecksumCheck::Unknown if require => Err(InstallError::Failed(format!(
+            "No known checksum for {} (checksums.require is set)",
+            artifact.url
+        ))),
+        ChecksumCheck::Unknown => {
+            say!(
+                "[voidbox] Warning: No known checksum for {}; its integrity can't be verified",
+                artifact.url
+            );
+            Ok(())
+        }
+    }
+}
 
-    let archive_path = rootfs.join("ubuntu_base.tar.gz");
-    download_file(&url, &archive_path, true)?;
+/// Extract the Ubuntu base image into a fresh rootfs
+fn extract_base_image(rootfs: &Path, archive_path: &Path) -> Result<(), InstallError> {
+    // Anything already here is left over from an interrupted extraction
+    if rootfs.exists() {
+        fs::remove_dir_all(rootfs)?;
+    }
+    fs::create_dir_all(rootfs)?;
 
-    println!("[voidbox] Extracting base image...");
-    let tar_gz = File::open(&archive_path)?;
+    say!("[voidbox] Extracting base image...");
+    let tar_gz = File::open(archive_path)?;
     let decoder = GzDecoder::new(tar_gz);
     let mut archive = tar::Archive::new(decoder);
     archive.set_ignore_zeros(true);
     archive.unpack(rootfs)?;
-    fs::remove_file(archive_path)?;
 
     // Setup network
     if Path::new("/etc/resolv.conf").exists() {
@@ -172,9 +931,8 @@ fn setup_base_image(rootfs: &Path, _manifest: &AppManifest) -> Result<(), Instal
 }
 
 /// Fetch latest Ubuntu base image URL
-fn fetch_latest_ubuntu_base() -> Result<(String, String), InstallError> {
-    let mut resp = ureq::get(crate::UBUNTU_RELEASES_URL)
-        .header("User-Agent", crate::APP_NAME)
+pub(crate) fn fetch_latest_ubuntu_base() -> Result<(String, String), InstallError> {
+    let mut resp = http_get(crate::UBUNTU_RELEASES_URL)
         .call()
         .map_err(|e| InstallError::Failed(format!("Failed to fetch Ubuntu releases: {}", e)))?;
 
@@ -213,27 +971,25 @@ fn fetch_latest_ubuntu_base() -> Result<(String, String), InstallError> {
     });
 
     // Try versions from newest to oldest
+    let arch = host_debian_arch();
     for version in versions.iter().rev() {
         let release_url = format!("{}{}/release/", crate::UBUNTU_RELEASES_URL, version);
 
-        if let Ok(mut resp) = ureq::get(&release_url)
-            .header("User-Agent", crate::APP_NAME)
-            .call()
+        if let Ok(mut resp) = http_get(&release_url).call()
+            && let Ok(body) = resp.body_mut().read_to_string()
         {
-            if let Ok(body) = resp.body_mut().read_to_string() {
-                let pattern = format!("ubuntu-base-{}-base-amd64.tar.gz", version);
-                if body.contains(&pattern) {
-                    let download_url = format!("{}{}", release_url, pattern);
-                    return Ok((version.clone(), download_url));
-                }
+            let pattern = format!("ubuntu-base-{}-base-{}.tar.gz", version, arch);
+            if body.contains(&pattern) {
+                let download_url = format!("{}{}", release_url, pattern);
+                return Ok((version.clone(), download_url));
+            }
 
-                // Try base version for point releases
-                let base_version: String = version.split('.').take(2).collect::<Vec<_>>().join(".");
-                let alt_pattern = format!("ubuntu-base-{}-base-amd64.tar.gz", base_version);
-                if body.contains(&alt_pattern) {
-                    let download_url = format!("{}{}", release_url, alt_pattern);
-                    return Ok((version.clone(), download_url));
-                }
+            // Try base version for point releases
+            let base_version: String = version.split('.').take(2).collect::<Vec<_>>().join(".");
+            let alt_pattern = format!("ubuntu-base-{}-base-{}.tar.gz", base_version, arch);
+            if body.contains(&alt_pattern) {
+                let download_url = format!("{}{}", release_url, alt_pattern);
+                return Ok((version.clone(), download_url));
             }
         }
     }
@@ -247,7 +1003,7 @@ fn install_dependencies(rootfs: &Path, manifest: &AppManifest) -> Result<(), Ins
         return Ok(());
     }
 
-    println!("[voidbox] Installing dependencies...");
+    say!("[voidbox] Installing dependencies...");
 
     // Get Ubuntu codename
     let _codename = get_ubuntu_codename(rootfs);
@@ -342,12 +1098,12 @@ echo "Setup complete!"
 
     match status {
         Ok(s) if !s.success() => {
-            println!(
+            say!(
                 "[voidbox] Note: Some packages couldn't be fully configured (expected in container)"
             );
         }
         Err(e) => {
-            println!("[voidbox] Warning: Setup script failed: {}", e);
+            say!("[voidbox] Warning: Setup script failed: {}", e);
         }
         _ => {}
     }
@@ -370,80 +1126,56 @@ fn get_ubuntu_codename(rootfs: &Path) -> String {
     "noble".to_string()
 }
 
-/// Download and install the app binary
-/// Returns the actual version downloaded (if available)
-fn install_app_binary(
+/// Extract the app artifact (or copy local files) into the rootfs
+fn install_app_files(
     rootfs: &Path,
     manifest: &AppManifest,
-) -> Result<Option<String>, InstallError> {
-    let (version, download_url, override_ext) = match &manifest.source {
-        SourceConfig::Github {
-            owner,
-            repo,
-            asset_os,
-            asset_arch,
-            asset_extension,
-            ..
-        } => (
-            fetch_github_release(
-                owner,
-                repo,
-                asset_os,
-                asset_arch,
-                asset_extension.as_deref(),
-            )?
-            .0,
-            fetch_github_release(
-                owner,
-                repo,
-                asset_os,
-                asset_arch,
-                asset_extension.as_deref(),
-            )?
-            .1,
-            None,
-        ),
-        SourceConfig::Direct {
-            url, archive_type, ..
-        } => ("latest".to_string(), url.clone(), archive_type.clone()),
-        SourceConfig::Local { path } => {
+    artifact: Option<&Artifact>,
+    archive_path: Option<&Path>,
+) -> Result<(), InstallError> {
+    let install_dir = manifest
+        .binary
+        .install_dir
+        .as_deref()
+        .unwrap_or(&manifest.app.name);
+    let rules = ExcludeRules::for_manifest(manifest);
+    let mut excluded = rules.record();
+
+    let (Some(artifact), Some(archive_path)) = (artifact, archive_path) else {
+        if let SourceConfig::Local { path } = &manifest.source {
             // Just copy from local path
-            let install_dir = manifest
-                .binary
-                .install_dir
-                .as_deref()
-                .unwrap_or(&manifest.app.name);
             let target_dir = rootfs.join(format!("opt/{}", install_dir));
             fs::create_dir_all(&target_dir)?;
 
-            if path.is_dir() {
-                copy_dir_all(path, &target_dir)?;
+            if path.is_dir() || is_appimage(path) {
+                if path.is_dir() {
+                    copy_dir_all(path, &target_dir)?;
+                } else {
+                    extract_appimage(path, &target_dir)?;
+                }
+                if !rules.is_empty() {
+                    prune_excluded(&target_dir, &rules, &mut excluded)?;
+                }
             } else {
                 fs::copy(path, target_dir.join(path.file_name().unwrap()))?;
             }
 
             create_binary_symlink(rootfs, manifest)?;
-            return Ok(None);
+            finish_excluded(rootfs, &excluded)?;
+            optimize_payload(
+                rootfs,
+                &target_dir,
+                manifest,
+                payload_originals(manifest, None, None),
+            )?;
         }
+        return Ok(());
     };
 
-    let actual_version = if version != "latest" {
-        Some(version.clone())
-    } else {
-        None
+    let override_ext = match &manifest.source {
+        SourceConfig::Direct { archive_type, .. } => archive_type.clone(),
+        _ => None,
     };
-
-    println!(
-        "[voidbox] Downloading {} v{}...",
-        manifest.app.display_name, version
-    );
-
-    let install_dir = manifest
-        .binary
-        .install_dir
-        .as_deref()
-        .unwrap_or(&manifest.app.name);
-
     let extension = if let Some(ext) = override_ext {
         if ext.starts_with('.') {
             ext
@@ -451,14 +1183,10 @@ fn install_app_binary(
             format!(".{}", ext)
         }
     } else {
-        get_extension_from_url(&download_url)
+        get_extension_from_url(&artifact.url)
     };
 
-    let archive_path = rootfs.join(format!("{}_download{}", install_dir, extension));
-
-    download_file(&download_url, &archive_path, true)?;
-
-    println!("[voidbox] Extracting...");
+    say!("[voidbox] Extracting...");
     let target_dir = rootfs.join(format!("opt/{}", install_dir));
     fs::create_dir_all(&target_dir)?;
 
@@ -468,7 +1196,7 @@ fn install_app_binary(
 
     match archive_type {
         ArchiveType::Zip => {
-            let file = File::open(&archive_path)?;
+            let file = File::open(archive_path)?;
             let mut archive = zip::ZipArchive::new(file)
                 .map_err(|e| InstallError::Failed(format!("Failed to open zip: {}", e)))?;
 
@@ -478,6 +1206,12 @@ fn install_app_binary(
                 })?;
 
                 let outpath = match file.enclosed_name() {
+                    Some(path) if rules.is_excluded(&path) => {
+                        if !file.is_dir() {
+                            excluded.add(&path, file.size());
+                        }
+                        continue;
+                    }
                     Some(path) => target_dir.join(path),
                     None => continue,
                 };
@@ -504,10 +1238,25 @@ fn install_app_binary(
             }
         }
         ArchiveType::TarGz => {
-            let file = File::open(&archive_path)?;
+            let file = File::open(archive_path)?;
             let decoder = GzDecoder::new(file);
             let mut archive = tar::Archive::new(decoder);
-            archive.unpack(&target_dir)?;
+            if rules.is_empty() {
+                archive.unpack(&target_dir)?;
+            } else {
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    let path = entry.path()?.into_owned();
+                    let path = path.strip_prefix(".").unwrap_or(&path);
+                    if rules.is_excluded(path) {
+                        if entry.header().entry_type().is_file() {
+                            excluded.add(path, entry.size());
+                        }
+                        continue;
+                    }
+                    entry.unpack_in(&target_dir)?;
+                }
+            }
         }
         _ => {
             return Err(InstallError::Failed(format!(
@@ -517,12 +1266,56 @@ fn install_app_binary(
         }
     }
 
-    fs::remove_file(archive_path)?;
-
     // Create symlink to binary
     create_binary_symlink(rootfs, manifest)?;
+    finish_excluded(rootfs, &excluded)?;
+    optimize_payload(
+        rootfs,
+        &target_dir,
+        manifest,
+        payload_originals(manifest, Some(artifact), Some(archive_type)),
+    )?;
 
-    Ok(actual_version)
+    Ok(())
+}
+
+/// Report and record the files an install skipped
+fn finish_excluded(rootfs: &Path, excluded: &ExcludedFiles) -> Result<(), InstallError> {
+    if !excluded.files.is_empty() {
+        say!(
+            "[voidbox] Excluded {} file(s), saving {}",
+            excluded.files.len(),
+            indicatif::HumanBytes(excluded.bytes)
+        );
+    }
+    save_excluded(rootfs, excluded)?;
+    Ok(())
+}
+
+/// Resolve the version and download URL of a manifest's artifact
+/// (None for local sources)
+pub fn resolve_download(manifest: &AppManifest) -> Result<Option<(String, String)>, InstallError> {
+    match &manifest.source {
+        SourceConfig::Github {
+            owner,
+            repo,
+            asset_os,
+            asset_arch,
+            asset_extension,
+            tag,
+            ..
+        } => fetch_github_release(
+            owner,
+            repo,
+            asset_os,
+            asset_arch,
+            asset_extension.as_deref(),
+            tag.as_deref(),
+        )
+        .map(Some),
+        SourceConfig::Direct { url, .. } => Ok(Some(("latest".to_string(), url.clone()))),
+        SourceConfig::Local { .. } => Ok(None),
+    }
 }
 
 fn fetch_github_release(
@@ -531,14 +1324,20 @@ fn fetch_github_release(
     asset_os: &str,
     asset_arch: &str,
     asset_extension: Option<&str>,
+    tag: Option<&str>,
 ) -> Result<(String, String), InstallError> {
-    let api_url = format!(
-        "https://api.github.com/repos/{}/{}/releases/latest",
-        owner, repo
-    );
+    let api_url = match tag {
+        Some(tag) => format!(
+            "https://api.github.com/repos/{}/{}/releases/tags/{}",
+            owner, repo, tag
+        ),
+        None => format!(
+            "https://api.github.com/repos/{}/{}/releases/latest",
+            owner, repo
+        ),
+    };
 
-    let mut resp = ureq::get(&api_url)
-        .header("User-Agent", crate::APP_NAME)
+    let mut resp = http_get(&api_url)
         .call()
         .map_err(|e| InstallError::Failed(format!("GitHub API error: {}", e)))?;
 
@@ -587,45 +1386,12 @@ fn get_extension_from_url(url: &str) -> String {
     }
 }
 
-fn create_binary_symlink(rootfs: &Path, manifest: &AppManifest) -> Result<(), InstallError> {
-    let install_dir = manifest
-        .binary
-        .install_dir
-        .as_deref()
-        .unwrap_or(&manifest.app.name);
-    let target_dir = rootfs.join(format!("opt/{}", install_dir));
-
-    // Find the binary
+pub(crate) fn create_binary_symlink(
+    rootfs: &Path,
+    manifest: &AppManifest,
+) -> Result<(), InstallError> {
     let binary_name = &manifest.binary.name;
-    let mut binary_path = None;
-
-    // Priority 1: Check manifest path (suffix match for flexibility)
-    if let Some(explicit_path) = &manifest.binary.path {
-        for entry in WalkDir::new(&target_dir).max_depth(3) {
-            if let Ok(entry) = entry {
-                if entry.path().ends_with(explicit_path) && entry.path().is_file() {
-                    binary_path = Some(entry.path().to_path_buf());
-                    break;
-                }
-            }
-        }
-    }
-
-    // Priority 2: Name match (existing logic)
-    if binary_path.is_none() {
-        for entry in WalkDir::new(&target_dir).max_depth(3) {
-            if let Ok(entry) = entry {
-                if entry.file_name().to_string_lossy() == binary_name.as_str()
-                    && entry.path().is_file()
-                {
-                    binary_path = Some(entry.path().to_path_buf());
-                    break;
-                }
-            }
-        }
-    }
-
-    let binary_path = binary_path.ok_or_else(|| {
+    let binary_path = find_entrypoint(rootfs, manifest).ok_or_else(|| {
         InstallError::Failed(format!("Binary '{}' not found in archive", binary_name))
     })?;
 
@@ -661,7 +1427,33 @@ fn copy_dir_all(src: &Path, dst: &Path) -> Result<(), InstallError> {
     Ok(())
 }
 
-fn save_installed_app(
+/// Add an installed app to the desktop
+///
+/// A failure is undone and only warned about: the app stays installed,
+/// recorded as not integrated until `voidbox desktop regen` succeeds.
+/// Returns whether it was added.
+pub(crate) fn integrate_installed(
+    manifest: &AppManifest,
+    keep: KeepIntegration,
+) -> Result<bool, InstallError> {
+    let app_name = &manifest.app.name;
+    let result = integrate_app(manifest, keep);
+    if let Err(e) = &result {
+        say!(
+            "[voidbox] Warning: Could not add {} to the desktop: {}",
+            manifest.app.display_name,
+            e
+        );
+        say!(
+            "[voidbox] It is installed but not integrated; retry with: voidbox desktop regen {}",
+            app_name
+        );
+    }
+    update_record(app_name, |app| app.not_integrated = result.is_err())?;
+    Ok(result.is_ok())
+}
+
+pub(crate) fn save_installed_app(
     manifest: &AppManifest,
     actual_version: Option<&str>,
 ) -> Result<(), InstallError> {
@@ -674,8 +1466,15 @@ fn save_installed_app(
         Vec::new()
     };
 
-    // Remove existing entry if any
-    apps.retain(|a| a.name != manifest.app.name);
+    // Remove existing entry if any, keeping where its manifest came from
+    // and whether it is pinned or protected
+    let existing = apps
+        .iter()
+        .find(|a| paths::same_app(&a.name, &manifest.app.name));
+    let source_url = existing.and_then(|a| a.source_url.clone());
+    let pinned = existing.is_some_and(|a| a.pinned);
+    let protected = existing.is_some_and(|a| a.protected);
+    apps.retain(|a| !paths::same_app(&a.name, &manifest.app.name));
 
     // Use actual downloaded version if available, otherwise manifest version
     let version = actual_version
@@ -685,11 +1484,17 @@ fn save_installed_app(
     // Add new entry
     apps.push(InstalledApp {
         name: manifest.app.name.clone(),
+        id: manifest.app.id.clone(),
         display_name: manifest.app.display_name.clone(),
         version,
         base_version: None,
         installed_date: Some(chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()),
         manifest_path: Some(paths::manifest_path(&manifest.app.name)),
+        manifest_hash: manifest_hash(manifest).ok(),
+        source_url,
+        pinned,
+        protected,
+        not_integrated: false,
     });
 
     let content = serde_json::to_string_pretty(&apps)
@@ -0,0 +1,263 @@
+//! Installed file records and `voidbox verify <app>`
+//!
+//! Once an install, update or repair has put an app's payload in place,
+//! post-install hooks included, the size and SHA-256 of every file under it
+//! (and the target of every symlink) is recorded next to the rootfs.
+//! `voidbox verify <app>` hashes the payload again and reports files that
+//! are missing, modified or extra. Only the payload is recorded: the rest
+//! of the rootfs is the shared base image, and the app's data lives outside
+//! it.
+
+use crate::cli::json::{is_json, print_json};
+use crate::cli::payload::payload_dir;
+use crate::cli::table::Table;
+use crate::manifest::{AppManifest, parse_manifest_file};
+use crate::storage::paths;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum IntegrityError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error(
+        "{0} was installed before voidbox recorded its files; run 'voidbox repair {0}' to record them"
+    )]
+    NoRecord(String),
+
+    #[error("{0} problem(s) found; run 'voidbox repair {1}' to reinstall its files")]
+    Corrupted(usize, String),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] io::Error),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] crate::manifest::ManifestError),
+}
+
+/// What was installed at one path of the payload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordedFile {
+    File { size: u64, sha256: String },
+    Symlink { target: PathBuf },
+}
+
+/// Files of an installed payload
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InstalledFiles {
+    /// Payload directory, relative to the rootfs
+    pub payload: String,
+    /// Keyed by path relative to the payload directory
+    pub files: BTreeMap<String, RecordedFile>,
+}
+
+/// Differences between an installed payload and its record
+#[derive(Debug, Default, Serialize)]
+pub struct IntegrityReport {
+    pub app: String,
+    pub checked: usize,
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+    pub extra: Vec<String>,
+}
+
+impl IntegrityReport {
+    pub fn problems(&self) -> usize {
+        self.missing.len() + self.modified.len() + self.extra.len()
+    }
+}
+
+/// Record of installed files, stored next to the rootfs
+fn installed_files_path(rootfs: &Path) -> PathBuf {
+    rootfs.with_file_name("files.json")
+}
+
+/// Save (or clear) the record of an installed payload
+pub(crate) fn save_installed_files(
+    rootfs: &Path,
+    files: Option<&InstalledFiles>,
+) -> io::Result<()> {
+    let path = installed_files_path(rootfs);
+    match files {
+        Some(files) => {
+            let json = serde_json::to_string_pretty(files).map_err(io::Error::other)?;
+            fs::write(path, json)
+        }
+        None => {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Files recorded for the payload in a rootfs
+pub fn load_installed_files(rootfs: &Path) -> Option<InstalledFiles> {
+    let content = fs::read_to_string(installed_files_path(rootfs)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Hash the payload in a rootfs and record it
+pub(crate) fn record_installed_files(rootfs: &Path, manifest: &AppManifest) -> io::Result<()> {
+    let payload = payload_dir(manifest);
+    let files = scan_payload(&rootfs.join(&payload))?;
+    save_installed_files(rootfs, Some(&InstalledFiles { payload, files }))
+}
+
+/// Every file and symlink under a payload directory, without following
+/// symlinks
+fn scan_payload(payload: &Path) -> io::Result<BTreeMap<String, RecordedFile>> {
+    let mut files = BTreeMap::new();
+    if !payload.exists() {
+        return Ok(files);
+    }
+    for entry in walkdir::WalkDir::new(payload) {
+        let entry = entry.map_err(io::Error::other)?;
+        let path = entry.path();
+        let Ok(relative) = path.strip_prefix(payload) else {
+            continue;
+        };
+        let relative = relative.to_string_lossy().into_owned();
+        let file_type = entry.file_type();
+        if file_type.is_symlink() {
+            let target = fs::read_link(path)?;
+            files.insert(relative, RecordedFile::Symlink { target });
+        } else if file_type.is_file() {
+            let mut hasher = Sha256::new();
+            let size = io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+            files.insert(
+                relative,
+                RecordedFile::File {
+                    size,
+                    sha256: hex::encode(hasher.finalize()),
+                },
+            );
+        }
+    }
+    Ok(files)
+}
+
+/// Compare a payload with its record
+fn compare(
+    app: &str,
+    recorded: &InstalledFiles,
+    current: &BTreeMap<String, RecordedFile>,
+) -> IntegrityReport {
+    let mut report = IntegrityReport {
+        app: app.to_string(),
+        checked: recorded.files.len(),
+        ..Default::default()
+    };
+    for (path, file) in &recorded.files {
+        match current.get(path) {
+            None => report.missing.push(path.clone()),
+            Some(now) if now != file => report.modified.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    report.extra = current
+        .keys()
+        .filter(|path| !recorded.files.contains_key(*path))
+        .cloned()
+        .collect();
+    report
+}
+
+/// Check an installed app's payload against the record made at install
+pub fn verify_installed_app(app_name: &str) -> Result<IntegrityReport, IntegrityError> {
+    let manifest_path = paths::manifest_path(app_name);
+    if !manifest_path.exists() {
+        return Err(IntegrityError::NotInstalled(app_name.to_string()));
+    }
+    let manifest = parse_manifest_file(&manifest_path)?;
+    let rootfs = paths::app_rootfs_dir(app_name);
+    let recorded = load_installed_files(&rootfs)
+        .ok_or_else(|| IntegrityError::NoRecord(manifest.app.name.clone()))?;
+    let current = scan_payload(&rootfs.join(&recorded.payload))?;
+    Ok(compare(&manifest.app.name, &recorded, &current))
+}
+
+/// Verify an installed app and print what differs
+pub fn verify_app(app_name: &str) -> Result<(), IntegrityError> {
+    say!("[voidbox] Verifying {}...", app_name);
+    let report = verify_installed_app(app_name)?;
+
+    if is_json() {
+        print_json(&report);
+    } else if report.problems() == 0 {
+        say!(
+            "[voidbox] All {} file(s) of {} match the install",
+            report.checked,
+            report.app
+        );
+    } else {
+        let mut table = Table::new(&["STATUS", "FILE"]);
+        for (status, files) in [
+            ("missing", &report.missing),
+            ("modified", &report.modified),
+            ("extra", &report.extra),
+        ] {
+            for file in files {
+                table.row(vec![status.to_string(), file.clone()]);
+            }
+        }
+        table.print();
+        say!();
+        say!(
+            "{} file(s) checked: {} missing, {} modified, {} extra",
+            report.checked,
+            report.missing.len(),
+            report.modified.len(),
+            report.extra.len()
+        );
+    }
+
+    match report.problems() {
+        0 => Ok(()),
+        problems => Err(IntegrityError::Corrupted(problems, report.app)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_missing_modified_and_extra_files() {
+        let payload =
+            std::env::temp_dir().join(format!("voidbox-integrity-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&payload);
+        fs::create_dir_all(payload.join("lib")).unwrap();
+        fs::write(payload.join("app"), "binary").unwrap();
+        fs::write(payload.join("lib/libfoo.so"), "library").unwrap();
+        fs::write(payload.join("data.pak"), "resources").unwrap();
+        std::os::unix::fs::symlink("app", payload.join("app-link")).unwrap();
+
+        let recorded = InstalledFiles {
+            payload: "opt/app".into(),
+            files: scan_payload(&payload).unwrap(),
+        };
+        assert_eq!(recorded.files.len(), 4);
+        assert_eq!(compare("app", &recorded, &recorded.files).problems(), 0);
+
+        fs::write(payload.join("app"), "tampered").unwrap();
+        fs::remove_file(payload.join("lib/libfoo.so")).unwrap();
+        fs::remove_file(payload.join("app-link")).unwrap();
+        std::os::unix::fs::symlink("/etc/passwd", payload.join("app-link")).unwrap();
+        fs::write(payload.join("lib/evil.so"), "extra").unwrap();
+
+        let report = compare("app", &recorded, &scan_payload(&payload).unwrap());
+        assert_eq!(report.missing, ["lib/libfoo.so"]);
+        assert_eq!(report.modified, ["app", "app-link"]);
+        assert_eq!(report.extra, ["lib/evil.so"]);
+        fs::remove_dir_all(&payload).unwrap();
+    }
+}
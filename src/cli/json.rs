@@ -0,0 +1,67 @@
+//! JSON and quiet output modes
+//!
+//! With the global `--json` flag, stdout carries only JSON, one document
+//! per line, for scripts to parse: `list`, `search`, `verify` and `info`
+//! print one document, `install` streams its progress events, and a
+//! failing command prints `{"error": {"message": ...}}` before exiting
+//! non-zero. Everything voidbox would otherwise print goes to stderr.
+//!
+//! With `--quiet`, that other output isn't printed at all (see `say!`),
+//! leaving only errors on stderr (and JSON on stdout when both flags are
+//! given).
+
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::FromRawFd;
+use std::sync::{Mutex, OnceLock};
+
+/// The original stdout, reserved for JSON once JSON output is enabled
+static SINK: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Reserve stdout for JSON and send all other output to stderr
+///
+/// Must be called before anything is printed.
+pub fn enable_json_output() -> std::io::Result<()> {
+    std::io::stdout().flush()?;
+    // SAFETY: dup only duplicates the stdout descriptor; the File below
+    // takes ownership of the new one
+    let fd = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: File owns the descriptor dup just returned
+    let sink = unsafe { File::from_raw_fd(fd) };
+    // SAFETY: dup2 points stdout at stderr; both stay open
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    SINK.set(Mutex::new(sink)).ok();
+    Ok(())
+}
+
+/// Check whether JSON output is enabled
+pub fn is_json() -> bool {
+    SINK.get().is_some()
+}
+
+/// Write a value as one line of JSON (only in JSON output mode)
+pub fn print_json<T: Serialize + ?Sized>(value: &T) {
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+    if let Ok(line) = serde_json::to_string(value)
+        && let Ok(mut out) = sink.lock()
+    {
+        writeln!(out, "{}", line).ok();
+    }
+}
+
+/// Write a failed command's error
+pub fn print_json_error(error: &dyn std::error::Error) {
+    print_json(&serde_json::json!({
+        "error": {
+            "message": error.to_string(),
+        },
+    }));
+}
@@ -8,7 +8,7 @@
 
 use crate::cli;
 use crate::gui;
-use crate::manifest::parse_manifest;
+use crate::manifest::{SignatureStatus, for_host_arch, parse_manifest};
 use crate::storage::paths;
 use std::fs;
 use std::os::unix::fs::symlink;
@@ -68,7 +68,7 @@ fn ensure_runtime_installed(app_name: &str, gui_mode: bool) -> Result<(), Launch
     // Install voidbox if not present
     if !voidbox_installed {
         if !gui_mode {
-            println!(
+            say!(
                 "[voidbox] Installing voidbox to {}...",
                 voidbox_path.display()
             );
@@ -85,7 +85,7 @@ fn ensure_runtime_installed(app_name: &str, gui_mode: bool) -> Result<(), Launch
     // Create symlink for this app if not present
     if !symlink_exists {
         if !gui_mode {
-            println!("[voidbox] Creating {} symlink...", symlink_path.display());
+            say!("[voidbox] Creating {} symlink...", symlink_path.display());
         }
         // Remove broken symlink if it exists
         let _ = fs::remove_file(&symlink_path);
@@ -129,14 +129,45 @@ pub fn should_run_as_launcher() -> Option<String> {
             "internal-init",
             "install",
             "remove",
+            "undo-remove",
             "run",
             "update",
             "list",
+            "versions",
+            "pin",
+            "unpin",
+            "protect",
+            "unprotect",
+            "rollback",
+            "repair",
+            "logs",
+            "export",
+            "import",
             "info",
+            "permissions",
+            "env",
+            "data",
+            "backup",
+            "optimize",
+            "scan-leftovers",
+            "migrate",
             "shell",
             "search",
             "settings",
             "self-update",
+            "webhook",
+            "cache",
+            "clean",
+            "doctor",
+            "recover",
+            "manifest",
+            "validate",
+            "health",
+            "maintain",
+            "monitor",
+            "remote",
+            "repo",
+            "desktop",
             "--help",
             "-h",
             "--version",
@@ -156,8 +187,8 @@ pub fn run_launcher(app_name: &str) -> Result<(), LauncherError> {
     let manifest_content = get_embedded_manifest(app_name)
         .ok_or_else(|| LauncherError::UnknownApp(app_name.to_string()))?;
 
-    // Parse the manifest
-    let manifest = parse_manifest(manifest_content)?;
+    // Parse the manifest and pick the host's source
+    let manifest = for_host_arch(&parse_manifest(manifest_content)?)?;
     let display_name = &manifest.app.display_name;
 
     // Check if we're in GUI mode
@@ -182,21 +213,22 @@ pub fn run_launcher(app_name: &str) -> Result<(), LauncherError> {
                 name: app_name.to_string(),
                 display_name: display_name.clone(),
                 manifest_content: manifest_content.to_string(),
+                signature: SignatureStatus::Unsigned,
             }) {
                 eprintln!("GUI Error: {}", e);
                 std::process::exit(1);
             }
         } else {
-            println!("[voidbox] Installing {}...", display_name);
+            say!("[voidbox] Installing {}...", display_name);
             std::fs::write(&manifest_path, manifest_content)?;
-            cli::install_app_from_manifest(&manifest, false)?;
-            println!("[voidbox] {} installed.", display_name);
+            cli::install_app_from_manifest(&manifest, cli::ConflictPolicy::Ask)?;
+            say!("[voidbox] {} installed.", display_name);
         }
     }
 
     // Run the app
     if !gui_mode {
-        println!("[voidbox] Starting {}...", display_name);
+        say!("[voidbox] Starting {}...", display_name);
     }
 
     // Get command line args to pass through (skip argv[0])
@@ -204,7 +236,7 @@ pub fn run_launcher(app_name: &str) -> Result<(), LauncherError> {
 
     // Run the app directly using our own run logic
     // This avoids the need to spawn a separate process
-    cli::run_app(app_name, &args, None, false)?;
+    cli::run_app(app_name, &args, None, false, false)?;
 
     Ok(())
 }
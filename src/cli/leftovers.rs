@@ -0,0 +1,271 @@
+//! Leftovers scanner
+//!
+//! Finds desktop integration files in the user's XDG directories that point
+//! at voidbox apps which are no longer installed: desktop entries, autostart
+//! entries, command wrappers and icons. Files written by older voidbox
+//! versions are recognized too - `voidbox-<app>.desktop` entries, entries
+//! that run voidbox by its full path, `void_<app>` launcher links and
+//! `voidbox-<app>` icons in the hicolor theme.
+
+use crate::cli::table::Table;
+use crate::desktop::{mark_desktop_changed, mark_icons_changed, refresh_batch};
+use crate::storage::paths;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LeftoversError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Kind of leftover file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeftoverKind {
+    DesktopEntry,
+    Autostart,
+    Wrapper,
+    Icon,
+}
+
+impl LeftoverKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LeftoverKind::DesktopEntry => "desktop entry",
+            LeftoverKind::Autostart => "autostart entry",
+            LeftoverKind::Wrapper => "command wrapper",
+            LeftoverKind::Icon => "icon",
+        }
+    }
+}
+
+/// A file belonging to an app that is no longer installed
+#[derive(Debug)]
+pub struct Leftover {
+    pub kind: LeftoverKind,
+    pub path: PathBuf,
+    /// App the file refers to
+    pub app: String,
+}
+
+/// Prefix of launcher links older versions created in the bin directory
+const LAUNCHER_PREFIX: &str = "void_";
+
+/// Prefix of desktop entries and icons named after the app's slug
+const LEGACY_PREFIX: &str = "voidbox-";
+
+fn is_installed(app: &str) -> bool {
+    paths::manifest_path(app).exists() || paths::app_dir(app).exists()
+}
+
+/// App an Exec line starts through voidbox, if any
+///
+/// Matches `voidbox run <app>` (with voidbox given by name or full path)
+/// and `void_<app>` launchers.
+fn exec_app(exec: &str) -> Option<String> {
+    let mut words = exec.split_whitespace();
+    let program = Path::new(words.next()?).file_name()?.to_str()?;
+    if let Some(app) = program.strip_prefix(LAUNCHER_PREFIX) {
+        return Some(app.to_string());
+    }
+    if program != crate::APP_NAME || words.next()? != "run" {
+        return None;
+    }
+    words
+        .find(|w| !w.starts_with('-'))
+        .map(|app| app.trim_matches('"').to_string())
+}
+
+/// App a desktop entry starts through voidbox, if any
+fn desktop_entry_app(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("Exec="))
+        .and_then(exec_app)
+}
+
+/// Desktop entries in `dir` for apps that are no longer installed
+fn scan_desktop_entries(dir: &Path, kind: LeftoverKind, found: &mut Vec<Leftover>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|e| e != "desktop") {
+            continue;
+        }
+        if let Some(app) = desktop_entry_app(&path)
+            && !is_installed(&app)
+        {
+            found.push(Leftover { kind, path, app });
+        }
+    }
+}
+
+/// Command wrappers and launcher links for apps that are no longer installed
+fn scan_wrappers(found: &mut Vec<Leftover>) {
+    let Ok(entries) = fs::read_dir(paths::bin_dir()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let app = if let Some(app) = name.strip_prefix(LAUNCHER_PREFIX) {
+            Some(app.to_string())
+        } else {
+            // Wrapper scripts are small; anything big is someone's binary
+            let small = fs::symlink_metadata(&path).is_ok_and(|m| m.is_file() && m.len() < 4096);
+            small
+                .then(|| fs::read_to_string(&path).ok())
+                .flatten()
+                .and_then(|script| {
+                    script
+                        .lines()
+                        .find_map(|line| line.trim().strip_prefix("exec "))
+                        .and_then(exec_app)
+                })
+        };
+        if let Some(app) = app
+            && !is_installed(&app)
+        {
+            found.push(Leftover {
+                kind: LeftoverKind::Wrapper,
+                path,
+                app,
+            });
+        }
+    }
+}
+
+/// Icons for apps that are no longer installed
+fn scan_icons(found: &mut Vec<Leftover>) {
+    // voidbox's own icon directory holds `<slug>.png`
+    if let Ok(entries) = fs::read_dir(paths::icons_dir()) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if let Some(app) = path.file_stem().map(|s| s.to_string_lossy().into_owned())
+                && !is_installed(&app)
+            {
+                found.push(Leftover {
+                    kind: LeftoverKind::Icon,
+                    path,
+                    app,
+                });
+            }
+        }
+    }
+
+    // Older versions installed `voidbox-<slug>` icons into the user's theme
+    let Some(theme) = dirs::data_local_dir().map(|d| d.join("icons/hicolor")) else {
+        return;
+    };
+    for entry in walkdir::WalkDir::new(theme)
+        .max_depth(3)
+        .into_iter()
+        .flatten()
+    {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let in_apps = entry.path().parent().is_some_and(|p| p.ends_with("apps"));
+        if !in_apps {
+            continue;
+        }
+        let Some(app) = name
+            .strip_prefix(LEGACY_PREFIX)
+            .and_then(|rest| Path::new(rest).file_stem())
+            .map(|s| s.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+        if !is_installed(&app) {
+            found.push(Leftover {
+                kind: LeftoverKind::Icon,
+                path: entry.path().to_path_buf(),
+                app,
+            });
+        }
+    }
+}
+
+/// Find desktop integration files of apps that are no longer installed
+pub fn find_leftovers() -> Vec<Leftover> {
+    let mut found = Vec::new();
+    scan_desktop_entries(
+        &paths::desktop_dir(),
+        LeftoverKind::DesktopEntry,
+        &mut found,
+    );
+    if let Some(config) = dirs::config_dir() {
+        scan_desktop_entries(
+            &config.join("autostart"),
+            LeftoverKind::Autostart,
+            &mut found,
+        );
+    }
+    scan_wrappers(&mut found);
+    scan_icons(&mut found);
+    found.sort_by(|a, b| a.app.cmp(&b.app).then_with(|| a.path.cmp(&b.path)));
+    found
+}
+
+/// List leftovers of uninstalled apps and offer to delete them
+pub fn scan_leftovers(yes: bool) -> Result<(), LeftoversError> {
+    let leftovers = find_leftovers();
+    if leftovers.is_empty() {
+        say!("No leftovers of uninstalled apps found.");
+        return Ok(());
+    }
+
+    say!("Leftovers of uninstalled apps:");
+    let mut table = Table::new(&["APP", "KIND", "PATH"]);
+    for leftover in &leftovers {
+        table.row(vec![
+            leftover.app.clone(),
+            leftover.kind.as_str().to_string(),
+            leftover.path.display().to_string(),
+        ]);
+    }
+    table.print();
+    say!();
+
+    if !confirm_delete(yes, leftovers.len()) {
+        say!("[voidbox] Nothing deleted.");
+        return Ok(());
+    }
+
+    let _refresh = refresh_batch();
+    let mut deleted = 0;
+    for leftover in &leftovers {
+        match fs::remove_file(&leftover.path) {
+            Ok(()) => deleted += 1,
+            Err(e) => {
+                say!(
+                    "[voidbox] Warning: Could not delete {}: {}",
+                    leftover.path.display(),
+                    e
+                );
+                continue;
+            }
+        }
+        match leftover.kind {
+            LeftoverKind::DesktopEntry => mark_desktop_changed(true),
+            LeftoverKind::Icon => mark_icons_changed(),
+            LeftoverKind::Autostart | LeftoverKind::Wrapper => {}
+        }
+    }
+    say!("[voidbox] Deleted {} file(s)", deleted);
+    Ok(())
+}
+
+/// Ask whether to delete; `yes` skips the question
+fn confirm_delete(yes: bool, count: usize) -> bool {
+    if yes {
+        return true;
+    }
+    if !crate::can_prompt() {
+        say!("[voidbox] Not interactive; pass --yes to delete these files");
+        return false;
+    }
+    crate::confirm(&format!("[voidbox] Delete these {} file(s)?", count), false)
+}
@@ -0,0 +1,256 @@
+//! Preferring system libraries over bundled copies
+//!
+//! Apps often bundle libraries that go stale: an old libstdc++ that mesa's
+//! drivers can't load against, an old libssl, their own libGL or libvulkan
+//! loader. With `[runtime] system_libraries = true` (or `[libraries]
+//! prefer_system` in config) the bundled copies of a whitelist of such
+//! libraries are replaced at launch by the rootfs's own, unless the rootfs
+//! copy is older. Only the whitelisted libraries are redirected: they are
+//! linked into a directory placed first on `LD_LIBRARY_PATH`, so putting
+//! the system library directories ahead of the app's doesn't override
+//! everything else it bundles.
+
+use crate::manifest::AppManifest;
+use crate::settings::load_config;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Libraries whose system copy is safe to use instead of a bundled one:
+/// ABI-stable runtimes and the graphics stack, which must match the
+/// system's drivers
+const SYSTEM_LIBRARIES: &[&str] = &[
+    "libstdc++",
+    "libgcc_s",
+    "libssl",
+    "libcrypto",
+    "libGL",
+    "libGLX",
+    "libEGL",
+    "libGLESv2",
+    "libgbm",
+    "libdrm",
+    "libvulkan",
+    "libva",
+    "libva-drm",
+    "libva-x11",
+];
+
+/// Directory in the container linking the preferred system libraries
+const SYSTEM_LIBS_DIR: &str = "usr/lib/voidbox/system-libs";
+
+/// Symlinks followed when resolving a library's real file
+const MAX_LINKS: usize = 8;
+
+/// What happens to one bundled library
+#[derive(Debug)]
+pub struct LibraryChoice {
+    /// File name the app loads (e.g. "libstdc++.so.6")
+    pub name: String,
+    /// Bundled copy, relative to the payload directory
+    pub bundled: PathBuf,
+    /// System copy used instead, as a container path
+    pub system: Option<String>,
+    /// Why the bundled copy is kept
+    pub reason: Option<&'static str>,
+}
+
+/// Libraries redirected to system copies for an app
+#[derive(Debug, Default)]
+pub struct SystemLibraries {
+    pub choices: Vec<LibraryChoice>,
+}
+
+impl SystemLibraries {
+    pub fn redirected(&self) -> impl Iterator<Item = &LibraryChoice> {
+        self.choices.iter().filter(|c| c.system.is_some())
+    }
+}
+
+/// Whether an app prefers system libraries over its bundled ones
+pub fn system_libraries_enabled(manifest: &AppManifest) -> bool {
+    manifest.runtime.system_libraries.unwrap_or_else(|| {
+        load_config()
+            .map(|c| c.libraries.prefer_system)
+            .unwrap_or(false)
+    })
+}
+
+/// Whitelisted library a file name is a copy of ("libGL.so.1" -> "libGL")
+fn whitelisted(name: &str) -> Option<&'static str> {
+    let (stem, rest) = name.split_once(".so")?;
+    if !rest.is_empty() && !rest.starts_with('.') {
+        return None;
+    }
+    SYSTEM_LIBRARIES.iter().copied().find(|l| *l == stem)
+}
+
+/// Library directories of the rootfs, most specific first
+fn system_lib_dirs() -> Vec<String> {
+    vec![
+        format!("usr/lib/{}-linux-gnu", std::env::consts::ARCH),
+        format!("lib/{}-linux-gnu", std::env::consts::ARCH),
+        "usr/lib64".to_string(),
+        "usr/lib".to_string(),
+    ]
+}
+
+/// Follow relative symlinks (within `path`'s directory) to the real file
+fn real_file(path: &Path) -> Option<PathBuf> {
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_LINKS {
+        match fs::read_link(&path) {
+            // Absolute targets point into the container, not the host
+            Ok(target) if target.is_relative() => path = path.parent()?.join(target),
+            Ok(_) => return None,
+            Err(_) => return path.is_file().then_some(path),
+        }
+    }
+    None
+}
+
+/// Version numbers after ".so." in a library's file name
+fn library_version(path: &Path) -> Vec<u64> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    name.split_once(".so.")
+        .map(|(_, version)| {
+            version
+                .split('.')
+                .map_while(|part| part.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Decide, for each whitelisted library the payload bundles, which copy to
+/// use, and link the system copies into the container's system-libs
+/// directory
+///
+/// Returns `None` if the app doesn't prefer system libraries.
+pub(crate) fn prefer_system_libraries(
+    manifest: &AppManifest,
+    rootfs: &Path,
+    payload: &Path,
+) -> std::io::Result<Option<SystemLibraries>> {
+    if !system_libraries_enabled(manifest) {
+        return Ok(None);
+    }
+
+    let shim = rootfs.join(SYSTEM_LIBS_DIR);
+    if shim.exists() {
+        fs::remove_dir_all(&shim)?;
+    }
+    fs::create_dir_all(&shim)?;
+
+    let mut libraries = SystemLibraries::default();
+    let lib_dirs = system_lib_dirs();
+    let mut seen = HashSet::new();
+    for entry in walkdir::WalkDir::new(payload).into_iter().flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some(library) = whitelisted(&name) else {
+            continue;
+        };
+        // The first copy found decides; the loader stops at it too
+        if entry.file_type().is_dir() || !seen.insert(name.clone()) {
+            continue;
+        }
+        let bundled = entry
+            .path()
+            .strip_prefix(payload)
+            .unwrap_or(entry.path())
+            .to_path_buf();
+
+        let mut choice = LibraryChoice {
+            name: name.clone(),
+            bundled,
+            system: None,
+            reason: None,
+        };
+        if manifest
+            .runtime
+            .bundled_libraries
+            .iter()
+            .any(|l| l == library || *l == name)
+        {
+            choice.reason = Some("kept by the manifest");
+        } else {
+            let system = lib_dirs
+                .iter()
+                .map(|dir| Path::new(dir).join(&name))
+                .find(|path| real_file(&rootfs.join(path)).is_some());
+            match system {
+                None => choice.reason = Some("no system copy"),
+                Some(system) => {
+                    let system_version =
+                        library_version(&real_file(&rootfs.join(&system)).unwrap_or_default());
+                    let bundled_version = real_file(entry.path())
+                        .map(|p| library_version(&p))
+                        .unwrap_or_default();
+                    if system_version < bundled_version {
+                        choice.reason = Some("system copy is older");
+                    } else {
+                        let target = format!("/{}", system.display());
+                        std::os::unix::fs::symlink(&target, shim.join(&name))?;
+                        choice.system = Some(target);
+                    }
+                }
+            }
+        }
+        libraries.choices.push(choice);
+    }
+
+    libraries.choices.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Some(libraries))
+}
+
+/// `LD_LIBRARY_PATH` putting the redirected libraries ahead of `existing`
+pub(crate) fn library_path(existing: Option<&str>) -> String {
+    let shim = format!("/{}", SYSTEM_LIBS_DIR);
+    match existing.filter(|p| !p.is_empty()) {
+        Some(existing) => format!("{}:{}", shim, existing),
+        None => shim,
+    }
+}
+
+/// Print which copy of each bundled library an app uses
+pub(crate) fn print_library_diagnostics(
+    manifest: &AppManifest,
+    libraries: Option<&SystemLibraries>,
+) {
+    let Some(libraries) = libraries else {
+        say!(
+            "[voidbox] Bundled libraries are used as shipped (system_libraries is off for {})",
+            manifest.app.name
+        );
+        return;
+    };
+
+    if libraries.choices.is_empty() {
+        say!(
+            "[voidbox] {} bundles no libraries voidbox can replace",
+            manifest.app.name
+        );
+        return;
+    }
+    say!("[voidbox] Libraries:");
+    for choice in &libraries.choices {
+        match (&choice.system, choice.reason) {
+            (Some(system), _) => say!("  {}  system ({})", choice.name, system),
+            (None, reason) => say!(
+                "  {}  bundled (/{}/{}; {})",
+                choice.name,
+                crate::cli::payload::payload_dir(manifest),
+                choice.bundled.display(),
+                reason.unwrap_or("kept")
+            ),
+        }
+    }
+    say!(
+        "[voidbox] Libraries the binary's RPATH points at still win over LD_LIBRARY_PATH; \
+         check with: ldd /usr/bin/{}",
+        manifest.binary.name
+    );
+}
@@ -1,7 +1,11 @@
 //! List command implementation
 
+use crate::cli::clean::tree_size;
+use crate::cli::table::Table;
 use crate::manifest::InstalledApp;
 use crate::storage::paths;
+use indicatif::HumanBytes;
+use serde::Serialize;
 use std::fs;
 use thiserror::Error;
 
@@ -14,14 +18,35 @@ pub enum ListError {
     DatabaseError(String),
 }
 
+/// An installed app as `voidbox list --json` prints it
+#[derive(Serialize)]
+struct ListedApp {
+    #[serde(flatten)]
+    app: InstalledApp,
+    /// Bytes the app's directory takes up, data included
+    size: u64,
+}
+
 /// List all installed apps
 pub fn list_apps() -> Result<(), ListError> {
+    if crate::cli::is_json() {
+        let apps: Vec<ListedApp> = get_installed_apps()?
+            .into_iter()
+            .map(|app| ListedApp {
+                size: tree_size(&paths::app_dir(&app.name)),
+                app,
+            })
+            .collect();
+        crate::cli::print_json(&apps);
+        return Ok(());
+    }
+
     let db_path = paths::database_path();
 
     if !db_path.exists() {
-        println!("No apps installed.");
-        println!();
-        println!("Install an app with: voidbox install <manifest.toml>");
+        say!("No apps installed.");
+        say!();
+        say!("Install an app with: voidbox install <manifest.toml>");
         return Ok(());
     }
 
@@ -30,28 +55,60 @@ pub fn list_apps() -> Result<(), ListError> {
         serde_json::from_str(&content).map_err(|e| ListError::DatabaseError(e.to_string()))?;
 
     if apps.is_empty() {
-        println!("No apps installed.");
-        println!();
-        println!("Install an app with: voidbox install <manifest.toml>");
+        say!("No apps installed.");
+        say!();
+        say!("Install an app with: voidbox install <manifest.toml>");
+        return Ok(());
+    }
+
+    if crate::is_plain() {
+        let mut table = Table::new(&["NAME", "DISPLAY NAME", "VERSION", "SIZE", "INSTALLED"]);
+        for app in &apps {
+            table.row(vec![
+                app.name.clone(),
+                app.display_name.clone(),
+                app.version.clone().unwrap_or_else(|| "unknown".into()),
+                HumanBytes(tree_size(&paths::app_dir(&app.name))).to_string(),
+                app.installed_date.clone().unwrap_or_default(),
+            ]);
+        }
+        table.print();
         return Ok(());
     }
 
-    println!("Installed apps:");
-    println!();
+    say!("Installed apps:");
+    say!();
 
     for app in &apps {
         let version = app.version.as_deref().unwrap_or("unknown");
         let date = app.installed_date.as_deref().unwrap_or("");
 
-        println!("  {} ({})", app.display_name, app.name);
-        println!("    Version:   {}", version);
+        say!("  {} ({})", app.display_name, app.name);
+        if app.pinned {
+            say!("    Version:   {} (pinned)", version);
+        } else {
+            say!("    Version:   {}", version);
+        }
+        if app.protected {
+            say!("    Protected: yes (remove with --force)");
+        }
         if !date.is_empty() {
-            println!("    Installed: {}", date);
+            say!("    Installed: {}", date);
+        }
+        say!(
+            "    Size:      {}",
+            HumanBytes(tree_size(&paths::app_dir(&app.name)))
+        );
+        if app.not_integrated {
+            say!(
+                "    Desktop:   not integrated (fix with 'voidbox desktop regen {}')",
+                app.name
+            );
         }
-        println!();
+        say!();
     }
 
-    println!("Run an app with: voidbox run <app-name>");
+    say!("Run an app with: voidbox run <app-name>");
 
     Ok(())
 }
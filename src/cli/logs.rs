@@ -0,0 +1,87 @@
+//! Logs command implementation
+//!
+//! Prints the end of an app's launch log, or with `--install` its install
+//! log, and with `--follow` keeps printing what is appended until
+//! interrupted.
+
+use crate::storage::{AppLog, app_log_path, paths};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LogsError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// How often a followed log is checked for new output
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The last `lines` lines of a text
+pub(crate) fn tail(content: &str, lines: usize) -> Vec<&str> {
+    let all: Vec<&str> = content.lines().collect();
+    all[all.len().saturating_sub(lines)..].to_vec()
+}
+
+/// Print the end of an app's log, and follow it if asked
+pub fn show_logs(
+    app_name: &str,
+    install: bool,
+    lines: usize,
+    follow: bool,
+) -> Result<(), LogsError> {
+    if !paths::app_dir(app_name).exists() {
+        return Err(LogsError::NotInstalled(app_name.to_string()));
+    }
+    let log = if install {
+        AppLog::Install
+    } else {
+        AppLog::Launch
+    };
+    let path = app_log_path(app_name, log);
+
+    let mut position = 0;
+    match fs::read(&path) {
+        Ok(content) => {
+            position = content.len() as u64;
+            for line in tail(&String::from_utf8_lossy(&content), lines) {
+                say!("{}", line);
+            }
+        }
+        Err(_) if follow => {}
+        Err(_) => {
+            say!("[voidbox] No {} for {} yet", log.file_name(), app_name);
+            return Ok(());
+        }
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    let mut stdout = std::io::stdout();
+    loop {
+        stdout.flush()?;
+        std::thread::sleep(FOLLOW_INTERVAL);
+        let Ok(len) = fs::metadata(&path).map(|m| m.len()) else {
+            continue;
+        };
+        // A rotated log starts over
+        if len < position {
+            position = 0;
+        }
+        if len == position {
+            continue;
+        }
+        let mut file = File::open(&path)?;
+        file.seek(SeekFrom::Start(position))?;
+        let mut new = Vec::new();
+        file.read_to_end(&mut new)?;
+        position += new.len() as u64;
+        stdout.write_all(&new)?;
+    }
+}
@@ -0,0 +1,430 @@
+//! Maintain command implementation
+//!
+//! `voidbox maintain` runs the periodic housekeeping in one pass: garbage
+//! collection of abandoned app directories, cache pruning and verification,
+//! app ID migration, repository index refreshes that are due, data backups,
+//! continuing background updates cut short by a reboot, and update checks.
+//! A summary is written to the state directory so the last run can be
+//! inspected when it ran unattended from the systemd timer.
+
+use crate::cli::backup::back_up_app;
+use crate::cli::list::get_installed_apps;
+use crate::cli::removed::{prune_removed, removal_keep};
+use crate::cli::repo::{refresh_checksums, refresh_repositories};
+use crate::cli::update::{check_update, continue_pending_updates};
+use crate::desktop::{create_desktop_entry, refresh_batch};
+use crate::manifest::{InstalledApp, parse_manifest_file};
+use crate::runtime::is_app_running;
+use crate::settings::load_config;
+use crate::storage::{parse_size, paths, prune_cache, verify_cache};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+/// Interrupted installs are kept this long so they can still be resumed
+pub(crate) const ABANDONED_INSTALL_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+const TIMER_NAME: &str = "voidbox-maintain";
+
+#[derive(Error, Debug)]
+pub enum MaintainError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Maintenance failed: {0}")]
+    Failed(String),
+}
+
+/// Summary of a maintenance run
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MaintenanceReport {
+    pub started: String,
+    pub finished: String,
+    /// App directories removed by garbage collection
+    pub collected: Vec<String>,
+    /// Removed apps deleted after their time for undo-remove ran out
+    #[serde(default)]
+    pub expired_removals: Vec<String>,
+    /// Bytes freed by cache pruning
+    pub cache_freed: u64,
+    pub cache_evicted: usize,
+    /// Cache entries evicted because their content no longer matched
+    pub cache_corrupt: Vec<String>,
+    /// Apps moved to the desktop entry ID from their manifest's app ID
+    #[serde(default)]
+    pub migrated: Vec<String>,
+    /// Apps whose data was backed up
+    #[serde(default)]
+    pub backed_up: Vec<String>,
+    /// Interrupted background updates that were continued
+    #[serde(default)]
+    pub continued_updates: Vec<String>,
+    /// Apps with a newer upstream version, as (app, version)
+    pub updates_available: Vec<(String, String)>,
+    /// Steps that failed, with the error
+    pub errors: Vec<String>,
+}
+
+/// Run gc, cache pruning, cache verification and update checks
+pub fn maintain() -> Result<(), MaintainError> {
+    let mut report = MaintenanceReport {
+        started: chrono::Local::now().to_rfc3339(),
+        ..Default::default()
+    };
+
+    say!("[voidbox] Collecting abandoned app directories...");
+    match collect_garbage() {
+        Ok(collected) => report.collected = collected,
+        Err(e) => report.errors.push(format!("gc: {}", e)),
+    }
+    match prune_removed(removal_keep()) {
+        Ok(expired) => report.expired_removals = expired,
+        Err(e) => report.errors.push(format!("removed apps: {}", e)),
+    }
+
+    say!("[voidbox] Pruning download cache...");
+    match prune() {
+        Ok((evicted, freed)) => {
+            report.cache_evicted = evicted;
+            report.cache_freed = freed;
+        }
+        Err(e) => report.errors.push(format!("cache prune: {}", e)),
+    }
+
+    say!("[voidbox] Verifying download cache...");
+    match verify_cache() {
+        Ok(verify) => report.cache_corrupt = verify.evicted,
+        Err(e) => report.errors.push(format!("cache verify: {}", e)),
+    }
+
+    say!("[voidbox] Refreshing repository indexes...");
+    match refresh_repositories(false) {
+        Ok(0) => {}
+        Ok(failed) => report.errors.push(format!(
+            "repositories: {} index(es) could not be fetched",
+            failed
+        )),
+        Err(e) => report.errors.push(format!("repositories: {}", e)),
+    }
+
+    say!("[voidbox] Refreshing checksum database...");
+    if let Err(e) = refresh_checksums() {
+        report.errors.push(format!("checksums: {}", e));
+    }
+
+    say!("[voidbox] Migrating app IDs...");
+    match migrate_app_ids() {
+        Ok(migrated) => report.migrated = migrated,
+        Err(e) => report.errors.push(format!("app id migration: {}", e)),
+    }
+
+    let backup = load_config().unwrap_or_default().backup;
+    if backup.to.is_some() && !backup.apps.is_empty() {
+        say!("[voidbox] Backing up app data...");
+        for app_name in &backup.apps {
+            match back_up_app(app_name, None, false, &backup) {
+                Ok(true) => report.backed_up.push(app_name.clone()),
+                Ok(false) => {}
+                Err(e) => report.errors.push(format!("backup {}: {}", app_name, e)),
+            }
+        }
+    }
+
+    let (continued, failed) = continue_pending_updates();
+    report.continued_updates = continued;
+    for (app_name, e) in failed {
+        report.errors.push(format!("update {}: {}", app_name, e));
+    }
+
+    say!("[voidbox] Checking for updates...");
+    match get_installed_apps() {
+        Ok(apps) => {
+            for app in apps {
+                match check_update(&app.name) {
+                    Ok(Some(latest)) => report.updates_available.push((app.name, latest)),
+                    Ok(None) => {}
+                    Err(e) => report
+                        .errors
+                        .push(format!("update check {}: {}", app.name, e)),
+                }
+            }
+        }
+        Err(e) => report.errors.push(format!("update check: {}", e)),
+    }
+
+    report.finished = chrono::Local::now().to_rfc3339();
+    print_report(&report);
+
+    let path = paths::maintenance_report_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string_pretty(&report)
+        .map_err(|e| MaintainError::Failed(format!("Failed to serialize report: {}", e)))?;
+    fs::write(&path, content)?;
+    say!("[voidbox] Report written to {}", path.display());
+
+    Ok(())
+}
+
+/// Remove app directories with no install record (failed or abandoned
+/// installs), keeping recent interrupted installs that can still resume
+fn collect_garbage() -> Result<Vec<String>, MaintainError> {
+    let installed: Vec<String> = get_installed_apps()
+        .map_err(|e| MaintainError::Failed(e.to_string()))?
+        .iter()
+        .map(|a| paths::app_slug(&a.name))
+        .collect();
+    Ok(collect_garbage_in(&paths::apps_dir(), &installed)?)
+}
+
+/// Garbage collection of `apps_dir`, whose directories are named by app
+/// slug; `installed` holds the slugs of installed apps
+///
+/// A directory holding app data, kept by `remove` without --purge, only
+/// loses its rootfs.
+fn collect_garbage_in(apps_dir: &Path, installed: &[String]) -> std::io::Result<Vec<String>> {
+    if !apps_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut collected = Vec::new();
+    for entry in fs::read_dir(apps_dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        if !path.is_dir() || installed.contains(&name) || is_app_running(&name) {
+            continue;
+        }
+
+        let resumable = fs::metadata(path.join("install-journal.json"))
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| {
+                SystemTime::now()
+                    .duration_since(modified)
+                    .is_ok_and(|age| age < ABANDONED_INSTALL_AGE)
+            });
+        if resumable {
+            continue;
+        }
+
+        if path.join("data").exists() || path.join("snapshots").exists() {
+            let rootfs = path.join("rootfs");
+            if rootfs.exists() {
+                fs::remove_dir_all(&rootfs)?;
+                collected.push(format!("{} (rootfs)", name));
+            }
+            continue;
+        }
+        fs::remove_dir_all(&path)?;
+        collected.push(name);
+    }
+
+    Ok(collected)
+}
+
+/// Record app IDs for apps installed before their manifest had one, and move
+/// their desktop entries from the slug-based ID to the app ID
+pub fn migrate_app_ids() -> Result<Vec<String>, MaintainError> {
+    let db_path = paths::database_path();
+    if !db_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&db_path)?;
+    let mut apps: Vec<InstalledApp> = serde_json::from_str(&content)
+        .map_err(|e| MaintainError::Failed(format!("Invalid database: {}", e)))?;
+
+    let _refresh = refresh_batch();
+    let mut migrated = Vec::new();
+    for app in apps.iter_mut().filter(|a| a.id.is_none()) {
+        let Ok(manifest) = parse_manifest_file(&paths::manifest_path(&app.name)) else {
+            continue;
+        };
+        let Some(id) = manifest.app.id.clone() else {
+            continue;
+        };
+
+        create_desktop_entry(&manifest).map_err(|e| MaintainError::Failed(e.to_string()))?;
+        app.id = Some(id);
+        migrated.push(app.name.clone());
+    }
+
+    if !migrated.is_empty() {
+        let content = serde_json::to_string_pretty(&apps)
+            .map_err(|e| MaintainError::Failed(format!("Failed to serialize: {}", e)))?;
+        fs::write(&db_path, content)?;
+    }
+
+    Ok(migrated)
+}
+
+fn prune() -> Result<(usize, u64), MaintainError> {
+    let config = load_config().map_err(|e| MaintainError::Failed(e.to_string()))?;
+    let max_size = parse_size(&config.cache.max_size).ok_or_else(|| {
+        MaintainError::Failed(format!(
+            "Invalid cache max_size '{}'",
+            config.cache.max_size
+        ))
+    })?;
+    let report = prune_cache(max_size).map_err(|e| MaintainError::Failed(e.to_string()))?;
+    Ok((report.evicted.len(), report.freed))
+}
+
+fn print_report(report: &MaintenanceReport) {
+    say!("[voidbox] Maintenance complete:");
+    say!(
+        "  gc:      {} abandoned app dir(s) removed",
+        report.collected.len()
+    );
+    if !report.expired_removals.is_empty() {
+        say!(
+            "  removed: {} expired removal(s) deleted",
+            report.expired_removals.len()
+        );
+    }
+    say!(
+        "  cache:   {} evicted ({}), {} corrupt",
+        report.cache_evicted,
+        indicatif::HumanBytes(report.cache_freed),
+        report.cache_corrupt.len()
+    );
+    say!("  app ids: {} migrated", report.migrated.len());
+    if !report.backed_up.is_empty() {
+        say!("  backup:  {} app(s) backed up", report.backed_up.len());
+    }
+    if !report.continued_updates.is_empty() {
+        say!(
+            "  resumed: {} interrupted update(s) continued",
+            report.continued_updates.len()
+        );
+    }
+    say!("  updates: {} available", report.updates_available.len());
+    for (app, version) in &report.updates_available {
+        say!("    {} -> v{}", app, version);
+    }
+    if !report.errors.is_empty() {
+        say!("  errors:");
+        for error in &report.errors {
+            say!("    {}", error);
+        }
+    }
+}
+
+fn systemd_user_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("systemd/user")
+}
+
+/// Install and enable a weekly systemd user timer running `voidbox maintain`
+pub fn install_maintenance_timer() -> Result<(), MaintainError> {
+    let exe = if paths::install_path().exists() {
+        paths::install_path()
+    } else {
+        std::env::current_exe()?
+    };
+
+    let dir = systemd_user_dir();
+    fs::create_dir_all(&dir)?;
+
+    let service = format!(
+        "[Unit]\n\
+         Description=Voidbox maintenance\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={} maintain\n\
+         Nice=19\n\
+         IOSchedulingClass=idle\n",
+        exe.display()
+    );
+    let timer = "[Unit]\n\
+                 Description=Weekly voidbox maintenance\n\
+                 \n\
+                 [Timer]\n\
+                 OnCalendar=weekly\n\
+                 Persistent=true\n\
+                 RandomizedDelaySec=1h\n\
+                 \n\
+                 [Install]\n\
+                 WantedBy=timers.target\n";
+
+    fs::write(dir.join(format!("{}.service", TIMER_NAME)), service)?;
+    fs::write(dir.join(format!("{}.timer", TIMER_NAME)), timer)?;
+
+    let enabled = Command::new("systemctl")
+        .args(["--user", "daemon-reload"])
+        .status()
+        .is_ok_and(|s| s.success())
+        && Command::new("systemctl")
+            .args([
+                "--user",
+                "enable",
+                "--now",
+                &format!("{}.timer", TIMER_NAME),
+            ])
+            .status()
+            .is_ok_and(|s| s.success());
+
+    if enabled {
+        say!("[voidbox] Weekly maintenance timer enabled");
+    } else {
+        say!(
+            "[voidbox] Timer written to {}; enable it with: systemctl --user enable --now {}.timer",
+            dir.display(),
+            TIMER_NAME
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_app_dir(apps_dir: &Path, name: &str, parts: &[&str]) -> PathBuf {
+        let dir = apps_dir.join(name);
+        for part in parts {
+            fs::create_dir_all(dir.join(part)).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn garbage_collection_keeps_installed_apps_by_slug() {
+        let apps_dir = std::env::temp_dir().join(format!("voidbox-gc-{}", std::process::id()));
+        fs::remove_dir_all(&apps_dir).ok();
+
+        // Names that aren't slugs themselves: Unicode and a side-by-side slot
+        let names = ["Música Player", "app@1.2"];
+        let installed: Vec<String> = names.iter().map(|n| paths::app_slug(n)).collect();
+        assert!(
+            names
+                .iter()
+                .zip(&installed)
+                .all(|(name, slug)| name != slug)
+        );
+        for slug in &installed {
+            make_app_dir(&apps_dir, slug, &["rootfs", "data", "snapshots"]);
+        }
+        let stray = make_app_dir(&apps_dir, "stray", &["rootfs"]);
+        let kept = make_app_dir(&apps_dir, "removed-keeping-data", &["rootfs", "data"]);
+
+        let collected = collect_garbage_in(&apps_dir, &installed).unwrap();
+
+        for slug in &installed {
+            assert!(apps_dir.join(slug).join("data").exists());
+            assert!(apps_dir.join(slug).join("rootfs").exists());
+        }
+        assert!(!stray.exists());
+        assert!(kept.join("data").exists());
+        assert!(!kept.join("rootfs").exists());
+        assert_eq!(collected.len(), 2);
+        fs::remove_dir_all(&apps_dir).ok();
+    }
+}
@@ -0,0 +1,150 @@
+//! Manifest command implementation
+
+use crate::manifest::{
+    ManifestBuilder, ManifestError, ManifestOrigin, SourceConfig, build_index, canonical_manifest,
+    flatpak_manifest, manifest_hash, parse_manifest_file, resolve_manifest_chain, signed_text,
+};
+use crate::settings::manifest_permissions;
+use crate::storage::paths;
+use std::path::{Path, PathBuf};
+
+/// Print the canonical content hash of a manifest file, or with
+/// `canonical` the canonical form itself
+pub fn hash_manifest(file: &Path, canonical: bool) -> Result<(), ManifestError> {
+    let manifest = parse_manifest_file(file)?;
+    if canonical {
+        let canonical = canonical_manifest(&manifest)?;
+        if !crate::is_quiet() {
+            print!("{}", canonical);
+        }
+    } else {
+        say!("{}", manifest_hash(&manifest)?);
+    }
+    Ok(())
+}
+
+/// Print the text a manifest's signature covers: the file up to its
+/// `[signature]` table, then the bases it extends
+pub fn print_signed_text(file: &Path) -> Result<(), ManifestError> {
+    let content = std::fs::read_to_string(file)?;
+    let (_, bases) = resolve_manifest_chain(&content, &ManifestOrigin::File(file.to_path_buf()))?;
+    let own = match signed_text(&content) {
+        Some(own) => own,
+        None if toml::from_str::<toml::Table>(&content)?.contains_key("signature") => {
+            return Err(ManifestError::SignatureError(
+                "the signature must be a [signature] table at the end of the manifest".into(),
+            ));
+        }
+        None => &content,
+    };
+    // Exactly as it is signed, without a newline added
+    if !crate::is_quiet() {
+        print!("{}{}", own, bases.concat());
+    }
+    Ok(())
+}
+
+/// Scaffold a new manifest from a GitHub repository or download URL
+pub fn new_manifest(
+    name: &str,
+    github: Option<&str>,
+    url: Option<&str>,
+    binary: Option<&str>,
+    display_name: Option<&str>,
+    output: Option<&Path>,
+) -> Result<(), ManifestError> {
+    let source = match (github, url) {
+        (Some(repo), _) => {
+            let (owner, repo) = repo.split_once('/').ok_or_else(|| {
+                ManifestError::ValidationError(format!("Expected owner/repo, got '{}'", repo))
+            })?;
+            SourceConfig::github(owner, repo)
+        }
+        (None, Some(url)) => SourceConfig::direct(url),
+        (None, None) => {
+            return Err(ManifestError::ValidationError(
+                "Either --github or --url is required".into(),
+            ));
+        }
+    };
+
+    let content = ManifestBuilder::new(
+        name,
+        display_name.unwrap_or(name),
+        source,
+        binary.unwrap_or(name),
+    )
+    .category("Utility")
+    .to_toml()?;
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, content)?;
+            say!("[voidbox] Wrote manifest to {}", path.display());
+        }
+        None if !crate::is_quiet() => print!("{}", content),
+        None => {}
+    }
+    Ok(())
+}
+
+/// Manifest file to open in the editor: a path, or an installed app's
+/// saved manifest
+pub fn manifest_edit_path(target: &str) -> Result<PathBuf, ManifestError> {
+    let path = PathBuf::from(target);
+    if path.exists() || target.ends_with(".toml") {
+        return Ok(path);
+    }
+
+    let saved = paths::manifest_path(target);
+    if saved.exists() {
+        Ok(saved)
+    } else {
+        Err(ManifestError::NotFound(target.to_string()))
+    }
+}
+
+/// Export a manifest (file or installed app) as a Flatpak manifest
+pub fn export_flatpak(target: &str, output: Option<&Path>) -> Result<(), ManifestError> {
+    let manifest = parse_manifest_file(&manifest_edit_path(target)?)?;
+    let export = flatpak_manifest(&manifest, &manifest_permissions(&manifest));
+    let content = serde_json::to_string_pretty(&export.manifest)
+        .map_err(|e| ManifestError::SerializeError(e.to_string()))?;
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, format!("{}\n", content))?;
+            say!("[voidbox] Wrote Flatpak manifest to {}", path.display());
+        }
+        None => say!("{}", content),
+    }
+    // Notes go to stderr so stdout stays valid JSON
+    for note in &export.notes {
+        eprintln!("[voidbox] Note: {}", note);
+    }
+    Ok(())
+}
+
+/// Build (or rebuild) the index of a repository directory
+pub fn build_repo_index(dir: &Path) -> Result<(), ManifestError> {
+    say!("[voidbox] Building index for {}...", dir.display());
+
+    let report = build_index(dir)?;
+
+    for (file, error) in &report.failed {
+        say!("  {}: {}", file, error);
+    }
+    if !report.failed.is_empty() {
+        return Err(ManifestError::ValidationError(format!(
+            "{} manifest(s) failed, index not written",
+            report.failed.len()
+        )));
+    }
+
+    say!(
+        "[voidbox] Indexed {} app(s) with {} variable(s)",
+        report.index.apps.len(),
+        report.index.vars.len()
+    );
+    Ok(())
+}
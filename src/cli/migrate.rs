@@ -0,0 +1,400 @@
+//! Migrate command implementation
+//!
+//! Imports the AppImages registered by other AppImage managers -
+//! AppImageLauncher, Gear Lever and AM/AppMan - as voidbox apps. Each
+//! AppImage is copied into voidbox's `adopted/` directory and installed
+//! from there, with its name, description, categories and icon taken from
+//! the desktop entry inside it; the other tool's desktop entries and icons
+//! are removed so the menu doesn't show the app twice. `--dry-run` only
+//! reports what would be imported.
+
+use crate::cli::appimage::{APPIMAGE_ENTRY, appimage_top_level, is_appimage, read_appimage_file};
+use crate::cli::install::install_manifest;
+use crate::desktop::{mark_desktop_changed, mark_icons_changed, refresh_batch};
+use crate::manifest::{AppManifest, ManifestBuilder, SourceConfig};
+use crate::storage::paths;
+use indicatif::HumanBytes;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MigrateError {
+    #[error("Unknown tool '{0}': expected appimagelauncher, gearlever or am")]
+    UnknownTool(String),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] crate::manifest::ManifestError),
+
+    #[error("Install error: {0}")]
+    InstallError(#[from] crate::cli::InstallError),
+
+    #[error("Migration failed: {0}")]
+    Failed(String),
+}
+
+/// An AppImage manager voidbox can import from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrateSource {
+    AppImageLauncher,
+    GearLever,
+    Am,
+}
+
+impl MigrateSource {
+    pub fn parse(tool: &str) -> Result<Self, MigrateError> {
+        match tool.to_ascii_lowercase().as_str() {
+            "appimagelauncher" => Ok(MigrateSource::AppImageLauncher),
+            "gearlever" | "gear-lever" => Ok(MigrateSource::GearLever),
+            "am" | "appman" => Ok(MigrateSource::Am),
+            _ => Err(MigrateError::UnknownTool(tool.to_string())),
+        }
+    }
+
+    fn display_name(&self) -> &'static str {
+        match self {
+            MigrateSource::AppImageLauncher => "AppImageLauncher",
+            MigrateSource::GearLever => "Gear Lever",
+            MigrateSource::Am => "AM",
+        }
+    }
+}
+
+/// An app registered with another tool
+#[derive(Debug)]
+struct ForeignApp {
+    /// Name the other tool shows for it
+    name: String,
+    appimage: PathBuf,
+    /// The other tool's desktop entries and icons for it
+    integration: Vec<PathBuf>,
+}
+
+/// Keys of a desktop file's `[Desktop Entry]` group
+fn desktop_entry_fields(content: &str) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    let mut in_entry = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+        } else if in_entry && let Some((key, value)) = line.split_once('=') {
+            fields.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    fields
+}
+
+/// Program an Exec line runs, with quotes removed
+fn exec_program(exec: &str) -> Option<PathBuf> {
+    let exec = exec.trim();
+    let program = match exec.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next()?,
+        None => exec.split_whitespace().next()?,
+    };
+    Some(PathBuf::from(program))
+}
+
+/// Icons named `icon` in the user's hicolor theme
+fn theme_icons(icon: &str) -> Vec<PathBuf> {
+    let Some(theme) = dirs::data_local_dir().map(|d| d.join("icons/hicolor")) else {
+        return Vec::new();
+    };
+    walkdir::WalkDir::new(theme)
+        .max_depth(3)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| e.path().file_stem().is_some_and(|s| *s == *icon))
+        .map(|e| e.path().to_path_buf())
+        .collect()
+}
+
+/// Apps whose desktop entries in the user's applications directory start
+/// with `prefix` (AppImageLauncher's `appimagekit_`, Gear Lever's
+/// `gearlever_`)
+fn find_by_desktop_prefix(prefix: &str) -> Vec<ForeignApp> {
+    let Ok(entries) = fs::read_dir(paths::desktop_dir()) else {
+        return Vec::new();
+    };
+    let mut apps = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if !file_name.starts_with(prefix) || !file_name.ends_with(".desktop") {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let fields = desktop_entry_fields(&content);
+        let Some(appimage) = fields.get("Exec").and_then(|e| exec_program(e)) else {
+            continue;
+        };
+        let mut integration = vec![path];
+        if let Some(icon) = fields.get("Icon").filter(|i| !i.contains('/')) {
+            integration.extend(theme_icons(icon));
+        }
+        apps.push(ForeignApp {
+            name: fields.get("Name").cloned().unwrap_or_else(|| {
+                appimage
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            }),
+            appimage,
+            integration,
+        });
+    }
+    apps
+}
+
+/// Apps installed by AM (system-wide in /opt) or AppMan (in
+/// ~/Applications): a directory per app holding the AppImage under the
+/// app's name next to a `remove` script
+fn find_am_apps() -> Vec<ForeignApp> {
+    let mut roots = vec![PathBuf::from("/opt")];
+    roots.extend(dirs::home_dir().map(|h| h.join("Applications")));
+    let entry_dirs = [
+        paths::desktop_dir(),
+        PathBuf::from("/usr/local/share/applications"),
+    ];
+
+    let mut apps = Vec::new();
+    for root in roots {
+        let Ok(entries) = fs::read_dir(&root) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !dir.join("remove").is_file() || !dir.join(&name).is_file() {
+                continue;
+            }
+            let integration = entry_dirs
+                .iter()
+                .map(|d| d.join(format!("{}-AM.desktop", name)))
+                .filter(|p| p.exists())
+                .collect();
+            apps.push(ForeignApp {
+                appimage: dir.join(&name),
+                name,
+                integration,
+            });
+        }
+    }
+    apps
+}
+
+fn find_apps(source: MigrateSource) -> Vec<ForeignApp> {
+    let mut apps = match source {
+        MigrateSource::AppImageLauncher => find_by_desktop_prefix("appimagekit_"),
+        MigrateSource::GearLever => find_by_desktop_prefix("gearlever_"),
+        MigrateSource::Am => find_am_apps(),
+    };
+    apps.sort_by(|a, b| a.name.cmp(&b.name));
+    apps
+}
+
+/// voidbox app name for a name shown by another tool ("Foo Bar" -> "foo-bar")
+fn app_name_for(name: &str) -> String {
+    let mut app_name = String::new();
+    for c in name.trim().chars() {
+        if c.is_alphanumeric() {
+            app_name.extend(c.to_lowercase());
+        } else if !app_name.ends_with('-') {
+            app_name.push('-');
+        }
+    }
+    let app_name = app_name.trim_matches('-');
+    if app_name.is_empty() {
+        "appimage".to_string()
+    } else {
+        app_name.to_string()
+    }
+}
+
+/// Import the apps another AppImage manager has registered
+pub fn migrate(tool: &str, dry_run: bool) -> Result<(), MigrateError> {
+    let source = MigrateSource::parse(tool)?;
+    let apps = find_apps(source);
+    if apps.is_empty() {
+        say!("No apps registered with {} found.", source.display_name());
+        return Ok(());
+    }
+
+    if dry_run {
+        say!("Apps registered with {}:", source.display_name());
+    }
+    let mut imported = 0;
+    let mut failed = Vec::new();
+    for app in &apps {
+        let app_name = app_name_for(&app.name);
+        let skip = if !app.appimage.is_file() {
+            Some("AppImage missing".to_string())
+        } else if !is_appimage(&app.appimage) {
+            Some("not a type 2 AppImage".to_string())
+        } else if paths::manifest_path(&app_name).exists() {
+            Some(format!("already installed as {}", app_name))
+        } else {
+            None
+        };
+
+        if dry_run {
+            say!("  {} ({})", app.name, app.appimage.display());
+            match &skip {
+                Some(reason) => say!("    skip: {}", reason),
+                None => {
+                    let size = fs::metadata(&app.appimage).map(|m| m.len()).unwrap_or(0);
+                    say!("    import as {} ({} AppImage)", app_name, HumanBytes(size));
+                    for path in &app.integration {
+                        say!("    remove {}", path.display());
+                    }
+                }
+            }
+            continue;
+        }
+
+        if let Some(reason) = skip {
+            say!("[voidbox] Skipping {}: {}", app.name, reason);
+            continue;
+        }
+        say!("[voidbox] Importing {} as {}...", app.name, app_name);
+        match import_app(app, &app_name) {
+            Ok(()) => imported += 1,
+            Err(e) => {
+                say!("[voidbox] Could not import {}: {}", app.name, e);
+                failed.push(app.name.clone());
+            }
+        }
+    }
+
+    if dry_run {
+        say!();
+        say!("Import them with: voidbox migrate --from {}", tool);
+        return Ok(());
+    }
+
+    say!(
+        "[voidbox] Imported {} app(s) from {}",
+        imported,
+        source.display_name()
+    );
+    if imported > 0 {
+        say!(
+            "[voidbox] The original AppImages were left in place; remove them with {} once \
+             the imported apps work",
+            source.display_name()
+        );
+    }
+    if !failed.is_empty() {
+        return Err(MigrateError::Failed(format!(
+            "{} app(s) not imported ({})",
+            failed.len(),
+            failed.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Adopt one AppImage and install it, then drop the other tool's
+/// integration files
+fn import_app(app: &ForeignApp, app_name: &str) -> Result<(), MigrateError> {
+    let adopted_dir = paths::adopted_dir();
+    fs::create_dir_all(&adopted_dir)?;
+    let adopted = adopted_dir.join(format!("{}.AppImage", paths::app_slug(app_name)));
+    fs::copy(&app.appimage, &adopted)?;
+
+    let result = appimage_manifest(app, app_name, &adopted)
+        .and_then(|manifest| Ok(install_manifest(&manifest, false)?));
+    if let Err(e) = result {
+        fs::remove_file(&adopted).ok();
+        return Err(e);
+    }
+
+    let _refresh = refresh_batch();
+    for path in &app.integration {
+        match fs::remove_file(path) {
+            Ok(()) if path.extension().is_some_and(|e| e == "desktop") => {
+                mark_desktop_changed(true)
+            }
+            Ok(()) => mark_icons_changed(),
+            Err(e) => say!(
+                "[voidbox] Warning: Could not remove {}: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Manifest for an adopted AppImage, from the desktop entry inside it
+fn appimage_manifest(
+    app: &ForeignApp,
+    app_name: &str,
+    adopted: &Path,
+) -> Result<AppManifest, MigrateError> {
+    let top_level = appimage_top_level(adopted)?;
+    let fields = top_level
+        .iter()
+        .find(|f| f.ends_with(".desktop"))
+        .and_then(|f| read_appimage_file(adopted, f).ok())
+        .map(|content| desktop_entry_fields(&content))
+        .unwrap_or_default();
+
+    let display_name = fields.get("Name").unwrap_or(&app.name);
+    let mut builder = ManifestBuilder::new(
+        app_name,
+        display_name.as_str(),
+        SourceConfig::Local {
+            path: adopted.to_path_buf(),
+        },
+        APPIMAGE_ENTRY,
+    );
+    if let Some(comment) = fields.get("Comment") {
+        builder = builder.description(comment.as_str());
+    }
+    let list = |key: &str| -> Vec<String> {
+        fields
+            .get(key)
+            .map(|v| {
+                v.split(';')
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    for category in list("Categories") {
+        builder = builder.category(category);
+    }
+    for mime_type in list("MimeType") {
+        builder = builder.mime_type(mime_type);
+    }
+    for keyword in list("Keywords") {
+        builder = builder.keyword(keyword);
+    }
+    if let Some(wm_class) = fields.get("StartupWMClass") {
+        builder = builder.wm_class(wm_class.as_str());
+    }
+    // Generated entries notify by default
+    if fields.get("StartupNotify").is_some_and(|v| v == "false") {
+        builder = builder.startup_notify(false);
+    }
+    // The icon the entry names sits next to it at the top level
+    if let Some(icon) = fields.get("Icon").and_then(|icon| {
+        ["png", "svg"]
+            .iter()
+            .map(|ext| format!("{}.{}", icon, ext))
+            .find(|file| top_level.contains(file))
+    }) {
+        builder = builder.icon(icon);
+    }
+
+    Ok(builder.build()?)
+}
@@ -1,19 +1,111 @@
 //! CLI command handlers
 
+mod appimage;
+mod backup;
+mod cache;
+mod clean;
+mod conflict;
+mod crash;
+mod data;
+mod dependencies;
+mod desktop;
+mod doctor;
+mod env;
+mod exclude;
+mod export;
+mod health;
+mod hooks;
 mod info;
 mod install;
+mod integrity;
+mod json;
 mod launcher;
+mod leftovers;
+mod libraries;
 mod list;
+mod logs;
+mod maintain;
+mod manifest;
+mod migrate;
+mod monitor;
+mod optimize;
+mod payload;
+mod permissions;
+mod pin;
+mod pipeline;
+mod plan;
+mod protect;
+mod quarantine;
+mod recover;
+mod remote;
 mod remove;
+mod removed;
+mod repair;
+mod repo;
+mod rollback;
 mod run;
+mod search;
 mod shell;
+mod stage;
+mod table;
+mod transaction;
 mod update;
+mod validate;
+mod verify;
+mod versions;
+mod webhook;
 
+pub use appimage::*;
+pub use backup::*;
+pub use cache::*;
+pub use clean::*;
+pub use conflict::*;
+pub use crash::*;
+pub use data::*;
+pub use dependencies::*;
+pub use desktop::*;
+pub use doctor::*;
+pub use env::*;
+pub use exclude::*;
+pub use export::*;
+pub use health::*;
+pub use hooks::*;
 pub use info::*;
 pub use install::*;
+pub use integrity::*;
+pub use json::*;
 pub use launcher::*;
+pub use leftovers::*;
+pub use libraries::*;
 pub use list::*;
+pub use logs::*;
+pub use maintain::*;
+pub use manifest::*;
+pub use migrate::*;
+pub use monitor::*;
+pub use optimize::*;
+pub use payload::*;
+pub use permissions::*;
+pub use pin::*;
+pub use pipeline::*;
+pub use plan::*;
+pub use protect::*;
+pub use quarantine::*;
+pub use recover::*;
+pub use remote::*;
 pub use remove::*;
+pub use removed::*;
+pub use repair::*;
+pub use repo::*;
+pub use rollback::*;
 pub use run::*;
+pub use search::*;
 pub use shell::*;
+pub use stage::*;
+pub use table::*;
+pub use transaction::*;
 pub use update::*;
+pub use validate::*;
+pub use verify::*;
+pub use versions::*;
+pub use webhook::*;
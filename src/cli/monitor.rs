@@ -0,0 +1,48 @@
+//! Monitor command implementation
+
+use crate::cli::table::Table;
+use crate::runtime::{instance_usage, running_instances};
+use indicatif::HumanBytes;
+use std::time::{Duration, Instant};
+
+/// Interval CPU usage is averaged over in the printed snapshot
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Whether a graphical session is available for the monitor window
+pub fn has_display() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some() || std::env::var_os("DISPLAY").is_some()
+}
+
+/// Print resource usage and sandbox status of running apps
+pub fn print_running_apps() {
+    let instances = running_instances();
+    if instances.is_empty() {
+        say!("No apps are running.");
+        return;
+    }
+
+    let before: Vec<u64> = instances
+        .iter()
+        .map(|info| instance_usage(info).cpu_usec)
+        .collect();
+    let started = Instant::now();
+    std::thread::sleep(SAMPLE_INTERVAL);
+    let elapsed = started.elapsed().as_micros().max(1) as f64;
+
+    let mut table = Table::new(&["APP", "PID", "CPU", "MEMORY", "SANDBOX"])
+        .right(1)
+        .right(2)
+        .right(3);
+    for (info, before) in instances.iter().zip(before) {
+        let usage = instance_usage(info);
+        let cpu = usage.cpu_usec.saturating_sub(before) as f64 * 100.0 / elapsed;
+        table.row(vec![
+            info.app.clone(),
+            info.pid.to_string(),
+            format!("{:.1}%", cpu),
+            HumanBytes(usage.memory).to_string(),
+            info.sandbox.clone(),
+        ]);
+    }
+    table.print();
+}
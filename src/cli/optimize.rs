@@ -0,0 +1,456 @@
+//! Strip-and-compress post-processing of installed payloads
+//!
+//! Apps with `[binary] optimize = true` (or every app, with `[install]
+//! optimize` in config) get their ELF files stripped of debug info and
+//! their large compressed resources recompressed after the payload is
+//! extracted. A file is only replaced when the result is smaller. The
+//! originals aren't kept next to the rootfs - that would undo the savings -
+//! but are recovered from the payload in the download cache (fetched again
+//! if it has been evicted) by `voidbox optimize <app> --restore`.
+
+use crate::cli::appimage::{extract_appimage, is_appimage};
+use crate::cli::install::InstallError;
+use crate::cli::integrity::record_installed_files;
+use crate::cli::pipeline::Artifact;
+use crate::cli::table::Table;
+use crate::manifest::{AppManifest, ArchiveType, parse_manifest_file};
+use crate::runtime::is_app_running;
+use crate::settings::load_config;
+use crate::storage::{cached_download, paths};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use indicatif::HumanBytes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum OptimizeError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error("{0} is running; close it first")]
+    Running(String),
+
+    #[error("{0} has no optimized files")]
+    NotOptimized(String),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] crate::manifest::ManifestError),
+
+    #[error("Restore failed: {0}")]
+    Failed(String),
+}
+
+/// Resources at least this large are recompressed
+const LARGE_RESOURCE: u64 = 1024 * 1024;
+
+/// Where the original files of an optimized payload can be recovered from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", tag = "kind")]
+pub enum Originals {
+    /// The downloaded archive, kept in the download cache
+    Archive {
+        url: String,
+        archive_type: ArchiveType,
+    },
+    /// A local source directory or AppImage
+    Local { path: PathBuf },
+}
+
+/// A file replaced by a smaller version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptimizedFile {
+    /// Path relative to the payload directory
+    pub path: String,
+    pub original_size: u64,
+    pub size: u64,
+}
+
+/// Record of the files an install optimized
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OptimizedFiles {
+    pub originals: Originals,
+    pub files: Vec<OptimizedFile>,
+}
+
+impl OptimizedFiles {
+    /// Bytes saved over the original files
+    pub fn saved(&self) -> u64 {
+        self.files
+            .iter()
+            .map(|f| f.original_size.saturating_sub(f.size))
+            .sum()
+    }
+}
+
+/// Whether an app's payload is stripped and recompressed after install
+pub fn optimize_enabled(manifest: &AppManifest) -> bool {
+    manifest
+        .binary
+        .optimize
+        .unwrap_or_else(|| load_config().map(|c| c.install.optimize).unwrap_or(false))
+}
+
+/// Record of optimized files, stored next to the rootfs
+fn optimized_path(rootfs: &Path) -> PathBuf {
+    rootfs.with_file_name("optimized.json")
+}
+
+/// Save (or clear) the record of files an install optimized
+pub(crate) fn save_optimized(
+    rootfs: &Path,
+    optimized: Option<&OptimizedFiles>,
+) -> std::io::Result<()> {
+    let path = optimized_path(rootfs);
+    match optimized {
+        Some(optimized) if !optimized.files.is_empty() => {
+            let json = serde_json::to_string_pretty(optimized).map_err(std::io::Error::other)?;
+            fs::write(path, json)
+        }
+        _ => {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Files the install of an app's rootfs optimized
+pub fn load_optimized(rootfs: &Path) -> Option<OptimizedFiles> {
+    let content = fs::read_to_string(optimized_path(rootfs)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Where an installed payload's originals come from
+pub(crate) fn payload_originals(
+    manifest: &AppManifest,
+    artifact: Option<&Artifact>,
+    archive_type: Option<ArchiveType>,
+) -> Option<Originals> {
+    match (artifact, archive_type) {
+        (Some(artifact), Some(archive_type)) => Some(Originals::Archive {
+            url: artifact.url.clone(),
+            archive_type,
+        }),
+        _ => match &manifest.source {
+            crate::manifest::SourceConfig::Local { path } if path.is_dir() || is_appimage(path) => {
+                Some(Originals::Local { path: path.clone() })
+            }
+            _ => None,
+        },
+    }
+}
+
+/// Strip and recompress a freshly extracted payload if the app asks for it
+///
+/// Failures to optimize single files are reported and leave them as they
+/// were; the install goes on either way.
+pub(crate) fn optimize_payload(
+    rootfs: &Path,
+    payload: &Path,
+    manifest: &AppManifest,
+    originals: Option<Originals>,
+) -> Result<(), InstallError> {
+    let Some(originals) = originals.filter(|_| optimize_enabled(manifest)) else {
+        save_optimized(rootfs, None)?;
+        return Ok(());
+    };
+
+    say!("[voidbox] Stripping and recompressing...");
+    let strip = which::which("strip").is_ok();
+    if !strip {
+        say!("[voidbox] Warning: strip (binutils) not found; ELF files are left as they are");
+    }
+
+    let mut optimized = OptimizedFiles {
+        originals,
+        files: Vec::new(),
+    };
+    for entry in walkdir::WalkDir::new(payload).into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.path();
+        let original_size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        let result = if strip && is_elf(path) {
+            strip_debug_info(path)
+        } else if original_size >= LARGE_RESOURCE {
+            recompress(path)
+        } else {
+            Ok(None)
+        };
+
+        match result {
+            Ok(Some(size)) => {
+                let relative = path.strip_prefix(payload).unwrap_or(path);
+                optimized.files.push(OptimizedFile {
+                    path: relative.to_string_lossy().into_owned(),
+                    original_size,
+                    size,
+                });
+            }
+            Ok(None) => {}
+            Err(e) => say!(
+                "[voidbox] Warning: Could not optimize {}: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+
+    if !optimized.files.is_empty() {
+        say!(
+            "[voidbox] Optimized {} file(s), saving {}",
+            optimized.files.len(),
+            HumanBytes(optimized.saved())
+        );
+    }
+    save_optimized(rootfs, Some(&optimized))?;
+    Ok(())
+}
+
+fn is_elf(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok_and(|()| magic == *b"\x7fELF")
+}
+
+/// Replace `path` with `candidate` if it is smaller, returning the new size
+fn replace_if_smaller(path: &Path, candidate: &Path) -> std::io::Result<Option<u64>> {
+    let original = fs::metadata(path)?;
+    let size = fs::metadata(candidate)?.len();
+    if size >= original.len() {
+        fs::remove_file(candidate)?;
+        return Ok(None);
+    }
+    fs::set_permissions(candidate, original.permissions())?;
+    fs::rename(candidate, path)?;
+    Ok(Some(size))
+}
+
+/// Temporary output path next to a file being optimized
+fn candidate_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".voidbox-optimize");
+    path.with_file_name(name)
+}
+
+/// Remove debug sections from an ELF file
+fn strip_debug_info(path: &Path) -> std::io::Result<Option<u64>> {
+    let candidate = candidate_path(path);
+    let status = Command::new("strip")
+        .args(["--strip-debug", "--preserve-dates", "-o"])
+        .arg(&candidate)
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        fs::remove_file(&candidate).ok();
+        // Not every ELF file is one strip understands (e.g. other
+        // architectures' prebuilt helpers)
+        return Ok(None);
+    }
+    replace_if_smaller(path, &candidate)
+}
+
+/// Recompress a large resource losslessly at a higher level
+///
+/// PNG images go through oxipng or optipng when installed; gzip files
+/// (including `.svgz`) are recompressed in-process.
+fn recompress(path: &Path) -> std::io::Result<Option<u64>> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let candidate = candidate_path(path);
+
+    let produced = match extension.as_str() {
+        "png" => {
+            let command = if which::which("oxipng").is_ok() {
+                Some(("oxipng", "--out"))
+            } else if which::which("optipng").is_ok() {
+                Some(("optipng", "-out"))
+            } else {
+                None
+            };
+            match command {
+                Some((program, out)) => Command::new(program)
+                    .arg(out)
+                    .arg(&candidate)
+                    .arg(path)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()?
+                    .success(),
+                None => false,
+            }
+        }
+        "gz" | "svgz" => {
+            let mut decoder = GzDecoder::new(File::open(path)?);
+            let mut encoder = GzEncoder::new(File::create(&candidate)?, Compression::best());
+            std::io::copy(&mut decoder, &mut encoder)?;
+            encoder.finish()?;
+            true
+        }
+        _ => false,
+    };
+
+    if !produced || !candidate.exists() {
+        fs::remove_file(&candidate).ok();
+        return Ok(None);
+    }
+    replace_if_smaller(path, &candidate)
+}
+
+/// Show an app's optimized files, or put the originals back
+pub fn optimize(app_name: &str, restore: bool) -> Result<(), OptimizeError> {
+    let manifest_path = paths::manifest_path(app_name);
+    if !manifest_path.exists() {
+        return Err(OptimizeError::NotInstalled(app_name.to_string()));
+    }
+    let manifest = parse_manifest_file(&manifest_path)?;
+    let rootfs = paths::app_rootfs_dir(app_name);
+
+    let Some(optimized) = load_optimized(&rootfs) else {
+        if restore {
+            return Err(OptimizeError::NotOptimized(app_name.to_string()));
+        }
+        say!("{} has no optimized files.", app_name);
+        if !optimize_enabled(&manifest) {
+            say!("Enable with `optimize = true` in the manifest's [binary] table.");
+        }
+        return Ok(());
+    };
+
+    if !restore {
+        say!(
+            "{} optimized file(s) of {}, saving {}:",
+            optimized.files.len(),
+            app_name,
+            HumanBytes(optimized.saved())
+        );
+        let mut table = Table::new(&["FILE", "ORIGINAL", "NOW"]).right(1).right(2);
+        for file in &optimized.files {
+            table.row(vec![
+                file.path.clone(),
+                HumanBytes(file.original_size).to_string(),
+                HumanBytes(file.size).to_string(),
+            ]);
+        }
+        table.print();
+        say!();
+        say!(
+            "Restore the originals with: voidbox optimize {} --restore",
+            app_name
+        );
+        return Ok(());
+    }
+
+    if is_app_running(app_name) {
+        return Err(OptimizeError::Running(app_name.to_string()));
+    }
+
+    let install_dir = manifest
+        .binary
+        .install_dir
+        .as_deref()
+        .unwrap_or(&manifest.app.name);
+    let payload = rootfs.join("opt").join(install_dir);
+    let wanted: HashSet<&str> = optimized.files.iter().map(|f| f.path.as_str()).collect();
+    let restored = restore_originals(&optimized.originals, &payload, &wanted)?;
+    if restored < wanted.len() {
+        return Err(OptimizeError::Failed(format!(
+            "only {} of {} originals found in the payload",
+            restored,
+            wanted.len()
+        )));
+    }
+
+    save_optimized(&rootfs, None)?;
+    record_installed_files(&rootfs, &manifest)?;
+    say!(
+        "[voidbox] Restored {} original file(s) of {}",
+        restored,
+        app_name
+    );
+    Ok(())
+}
+
+/// Copy the `wanted` payload files from their source, returning how many
+/// were restored
+fn restore_originals(
+    originals: &Originals,
+    payload: &Path,
+    wanted: &HashSet<&str>,
+) -> Result<usize, OptimizeError> {
+    let mut restored = 0;
+    match originals {
+        Originals::Local { path } if is_appimage(path) => {
+            let unpacked = payload.with_extension("originals");
+            extract_appimage(path, &unpacked)?;
+            for file in wanted {
+                fs::copy(unpacked.join(file), payload.join(file))?;
+                restored += 1;
+            }
+            fs::remove_dir_all(&unpacked)?;
+        }
+        Originals::Local { path } => {
+            for file in wanted {
+                let target = payload.join(file);
+                fs::copy(path.join(file), &target)?;
+                restored += 1;
+            }
+        }
+        Originals::Archive { url, archive_type } => {
+            say!("[voidbox] Recovering originals from the download cache...");
+            let archive_path = cached_download(url, None, true)
+                .map_err(|e| OptimizeError::Failed(format!("could not fetch {}: {}", url, e)))?;
+            match archive_type {
+                ArchiveType::Zip => {
+                    let mut archive = zip::ZipArchive::new(File::open(&archive_path)?)
+                        .map_err(|e| OptimizeError::Failed(e.to_string()))?;
+                    for file in wanted {
+                        let mut entry = archive
+                            .by_name(file)
+                            .map_err(|e| OptimizeError::Failed(format!("{}: {}", file, e)))?;
+                        let mut target = File::create(payload.join(file))?;
+                        std::io::copy(&mut entry, &mut target)?;
+                        restored += 1;
+                    }
+                }
+                ArchiveType::TarGz => {
+                    let mut archive = tar::Archive::new(GzDecoder::new(File::open(&archive_path)?));
+                    archive.set_preserve_permissions(true);
+                    for entry in archive.entries()? {
+                        let mut entry = entry?;
+                        let path = entry.path()?.into_owned();
+                        let path = path.strip_prefix(".").unwrap_or(&path);
+                        if wanted.contains(path.to_string_lossy().as_ref()) {
+                            entry.unpack(payload.join(path))?;
+                            restored += 1;
+                        }
+                    }
+                }
+                other => {
+                    return Err(OptimizeError::Failed(format!(
+                        "unsupported archive type {}",
+                        other.extension()
+                    )));
+                }
+            }
+        }
+    }
+    Ok(restored)
+}
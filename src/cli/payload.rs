@@ -0,0 +1,343 @@
+//! Read-only app payloads
+//!
+//! An app's installed files are mounted read-only at runtime so it can't
+//! modify its own binaries; updates come from `voidbox update`. The
+//! container's home is a per-app data directory outside the rootfs. Apps
+//! that try to update themselves anyway (Electron auto-updaters) are
+//! reported after they exit, with an offer to block their updater: common
+//! updater switches are set and the helpers updaters run to install an
+//! update are replaced by stubs that refuse.
+
+use crate::cli::libraries::{library_path, prefer_system_libraries};
+use crate::manifest::{AppManifest, PermissionConfig, manifest_to_toml, parse_manifest_file};
+use crate::runtime::ContainerSetup;
+use crate::settings::{TmpConfig, load_config, load_env_overrides};
+use crate::storage::{create_data_dir, parse_size, paths};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// Environment variables honoured by common built-in updaters
+pub const UPDATER_BLOCK_ENV: &[(&str, &str)] =
+    &[("DISABLE_AUTO_UPDATE", "1"), ("DISABLE_AUTOUPDATER", "1")];
+
+/// Programs updaters run to install an update: privilege helpers used by
+/// electron-updater's deb/rpm installers and the AppImage updater
+const UPDATER_STUBS: &[&str] = &[
+    "pkexec",
+    "gksudo",
+    "kdesudo",
+    "beesu",
+    "appimageupdatetool",
+    "AppImageUpdate",
+];
+
+/// Log the stubs append to, relative to the container's /root
+const BLOCKED_LOG: &str = ".cache/voidbox-blocked-updates.log";
+
+/// Payload directory of an app, relative to its rootfs
+pub(crate) fn payload_dir(manifest: &AppManifest) -> String {
+    let install_dir = manifest
+        .binary
+        .install_dir
+        .as_deref()
+        .unwrap_or(&manifest.app.name);
+    format!("opt/{}", install_dir)
+}
+
+/// Find the app's binary in its payload
+///
+/// The manifest's `path` is matched as a suffix first, then the binary's
+/// name.
+pub(crate) fn find_entrypoint(rootfs: &Path, manifest: &AppManifest) -> Option<PathBuf> {
+    let payload = rootfs.join(payload_dir(manifest));
+    let find = |matches: &dyn Fn(&walkdir::DirEntry) -> bool| {
+        WalkDir::new(&payload)
+            .max_depth(3)
+            .into_iter()
+            .flatten()
+            .find(|entry| matches(entry) && entry.path().is_file())
+            .map(|entry| entry.into_path())
+    };
+
+    manifest
+        .binary
+        .path
+        .as_ref()
+        .and_then(|path| find(&|entry| entry.path().ends_with(path)))
+        .or_else(|| {
+            find(&|entry| entry.file_name().to_string_lossy() == manifest.binary.name.as_str())
+        })
+}
+
+/// Create the app's data directory if needed and return it
+///
+/// On first use the rootfs's `/root` is moved there, so whatever the app
+/// stored in its home before is kept.
+pub(crate) fn prepare_app_data(app_name: &str, rootfs: &Path) -> std::io::Result<PathBuf> {
+    let data_dir = paths::app_data_dir(app_name);
+    if data_dir.exists() {
+        return Ok(data_dir);
+    }
+
+    let old_home = rootfs.join("root");
+    if old_home.is_dir() {
+        fs::rename(&old_home, &data_dir)?;
+        fs::create_dir(&old_home)?;
+    } else {
+        create_data_dir(&data_dir)?;
+    }
+    Ok(data_dir)
+}
+
+/// Container setup for running an app
+pub(crate) fn container_setup(
+    manifest: &AppManifest,
+    rootfs: &Path,
+) -> std::io::Result<ContainerSetup> {
+    let mut setup = ContainerSetup {
+        payload: Some(payload_dir(manifest)),
+        data_dir: Some(prepare_app_data(&manifest.app.name, rootfs)?),
+        env: manifest.binary.env.clone(),
+        updater_stubs: None,
+        dbus_proxy: None,
+        host_bus: false,
+        live_dir: None,
+        wayland_socket: None,
+        tmp_size: private_tmp_size(manifest),
+        privileged: false,
+    };
+    match load_env_overrides(&manifest.app.name) {
+        Ok(overrides) => setup.env.extend(overrides),
+        Err(e) => say!(
+            "[voidbox] Warning: Could not load environment overrides: {}",
+            e
+        ),
+    }
+    let payload = rootfs.join(payload_dir(manifest));
+    match prefer_system_libraries(manifest, rootfs, &payload) {
+        Ok(Some(libraries)) if libraries.redirected().next().is_some() => {
+            let path = library_path(setup.env.get("LD_LIBRARY_PATH").map(String::as_str));
+            setup.env.insert("LD_LIBRARY_PATH".to_string(), path);
+        }
+        Ok(_) => {}
+        Err(e) => say!(
+            "[voidbox] Warning: Could not set up system libraries: {}",
+            e
+        ),
+    }
+    if updater_blocked(manifest) {
+        for (name, value) in UPDATER_BLOCK_ENV {
+            setup
+                .env
+                .entry(name.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+        setup.updater_stubs = Some(write_updater_stubs()?);
+    }
+    Ok(setup)
+}
+
+/// Size of an app's private /tmp, or `None` to share the host's
+fn private_tmp_size(manifest: &AppManifest) -> Option<u64> {
+    let config = load_config().unwrap_or_default();
+    if !config.tmp.private {
+        return None;
+    }
+    let size = manifest
+        .runtime
+        .tmp_size
+        .as_deref()
+        .unwrap_or(&config.tmp.size);
+    match parse_size(size) {
+        Some(bytes) if bytes > 0 => Some(bytes),
+        _ => {
+            say!(
+                "[voidbox] Warning: Invalid /tmp size '{}', using {}",
+                size,
+                TmpConfig::default().size
+            );
+            parse_size(&TmpConfig::default().size)
+        }
+    }
+}
+
+/// Whether an app's built-in updater is blocked
+pub fn updater_blocked(manifest: &AppManifest) -> bool {
+    manifest
+        .binary
+        .block_updater
+        .unwrap_or_else(|| load_config().map(|c| c.updaters.block).unwrap_or(false))
+}
+
+/// Write the updater stubs (shared by all apps) and return their directory
+fn write_updater_stubs() -> std::io::Result<PathBuf> {
+    let dir = paths::updater_stubs_dir();
+    fs::create_dir_all(&dir)?;
+    let script = format!(
+        "#!/bin/sh\n\
+         # Installed by voidbox: updates are handled by voidbox\n\
+         echo \"[voidbox] $(basename \"$0\") blocked: update this app with voidbox\" >&2\n\
+         mkdir -p \"$(dirname /root/{log})\" 2>/dev/null\n\
+         echo \"$(basename \"$0\") $*\" >> /root/{log} 2>/dev/null\n\
+         exit 1\n",
+        log = BLOCKED_LOG
+    );
+    for name in UPDATER_STUBS {
+        let path = dir.join(name);
+        if fs::read_to_string(&path).ok().as_deref() != Some(script.as_str()) {
+            fs::write(&path, &script)?;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+        }
+    }
+    Ok(dir)
+}
+
+/// Host directory the app sees as its home
+pub(crate) fn app_home(app_name: &str, permissions: &PermissionConfig) -> Option<PathBuf> {
+    if permissions.home || permissions.native_mode {
+        std::env::var_os("HOME").map(PathBuf::from)
+    } else {
+        Some(paths::app_data_dir(app_name))
+    }
+}
+
+/// Updater download directories written since `since`
+///
+/// electron-updater stages downloads in `~/.cache/<app>-updater`.
+fn updater_attempts(home: &Path, since: SystemTime) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(home.join(".cache")) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with("-updater"))
+        .filter(|entry| {
+            entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|mtime| mtime >= since)
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Stub invocations logged during the run; the log is cleared
+fn blocked_attempts(app_name: &str) -> Vec<String> {
+    let log = paths::app_data_dir(app_name).join(BLOCKED_LOG);
+    let Ok(content) = fs::read_to_string(&log) else {
+        return Vec::new();
+    };
+    fs::remove_file(&log).ok();
+    content.lines().map(String::from).collect()
+}
+
+/// Report an app that tried to update itself and offer to block its updater
+pub(crate) fn review_self_update(manifest: &AppManifest, home: &Path, since: SystemTime) {
+    let mut attempts: Vec<String> = updater_attempts(home, since)
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    attempts.extend(blocked_attempts(&manifest.app.name));
+    if attempts.is_empty() {
+        return;
+    }
+
+    let blocked = updater_blocked(manifest);
+    let message = if blocked {
+        format!(
+            "{} still tried to update itself; its updater is blocked. \
+             Use `voidbox update {}` to update it.",
+            manifest.app.display_name, manifest.app.name
+        )
+    } else {
+        format!(
+            "{} tried to update itself, but its files are read-only. \
+             Use `voidbox update {}` to update it instead.",
+            manifest.app.display_name, manifest.app.name
+        )
+    };
+    say!("[voidbox] {}", message);
+    for attempt in &attempts {
+        say!("[voidbox]   {}", attempt);
+    }
+    if blocked {
+        return;
+    }
+
+    let block = if crate::can_prompt() {
+        crate::confirm("[voidbox] Block its built-in updater?", false)
+    } else if crate::gui::can_ask() {
+        crate::gui::ask_yes_no(
+            "Voidbox - App Updater",
+            &format!("{}\n\nBlock its built-in updater?", message),
+        )
+    } else {
+        false
+    };
+    if !block {
+        return;
+    }
+
+    match block_updater(&manifest.app.name) {
+        Ok(()) => say!(
+            "[voidbox] Blocked the built-in updater of {}",
+            manifest.app.display_name
+        ),
+        Err(e) => say!("[voidbox] Warning: Could not update the manifest: {}", e),
+    }
+}
+
+/// Set `block_updater` in the installed manifest
+fn block_updater(app_name: &str) -> Result<(), String> {
+    let manifest_path = paths::manifest_path(app_name);
+    let mut manifest = parse_manifest_file(&manifest_path).map_err(|e| e.to_string())?;
+    manifest.binary.block_updater = Some(true);
+    let content = manifest_to_toml(&manifest).map_err(|e| e.to_string())?;
+    fs::write(&manifest_path, content).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::parse_manifest;
+
+    #[test]
+    fn entrypoint_comes_from_the_payload_not_the_link() {
+        let rootfs = std::env::temp_dir().join(format!("voidbox-payload-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&rootfs);
+        fs::create_dir_all(rootfs.join("opt/demo/bin")).unwrap();
+        fs::create_dir_all(rootfs.join("usr/bin")).unwrap();
+        fs::write(rootfs.join("opt/demo/bin/demo"), "binary").unwrap();
+        // As if the app had repointed its link at a script it wrote
+        std::os::unix::fs::symlink("/root/evil.sh", rootfs.join("usr/bin/demo")).unwrap();
+
+        let manifest = parse_manifest(
+            r#"
+[app]
+name = "demo"
+display_name = "Demo"
+
+[source]
+type = "direct"
+url = "https://example.com/demo.tar.gz"
+
+[runtime]
+
+[binary]
+name = "demo"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            find_entrypoint(&rootfs, &manifest),
+            Some(rootfs.join("opt/demo/bin/demo"))
+        );
+
+        fs::remove_file(rootfs.join("opt/demo/bin/demo")).unwrap();
+        assert_eq!(find_entrypoint(&rootfs, &manifest), None);
+        fs::remove_dir_all(&rootfs).unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+//! Permissions command implementation
+//!
+//! Shows an app's effective permissions and changes them through its user
+//! override. Camera and microphone changes reach a running app right away;
+//! the rest apply the next time it starts.
+
+use crate::cli::table::Table;
+use crate::manifest::{ClipboardAccess, PermissionConfig, parse_manifest_file};
+use crate::runtime::is_app_running;
+use crate::settings::{
+    load_overrides, manifest_permissions, permission_preset, remove_overrides, save_overrides,
+    write_live_permissions,
+};
+use crate::storage::paths;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PermissionsError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error("Unknown permission: {0}")]
+    UnknownPermission(String),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] crate::manifest::ManifestError),
+
+    #[error("Settings error: {0}")]
+    SettingsError(#[from] crate::settings::SettingsError),
+}
+
+/// Permissions applied live to a running app
+const LIVE_PERMISSIONS: &[&str] = &["camera", "microphone"];
+
+/// Show an app's permissions, granting, revoking or resetting them first
+pub fn permissions(
+    app_name: &str,
+    grant: &[String],
+    revoke: &[String],
+    clipboard: Option<ClipboardAccess>,
+    reset: bool,
+) -> Result<(), PermissionsError> {
+    let manifest_path = paths::manifest_path(app_name);
+    if !manifest_path.exists() {
+        return Err(PermissionsError::NotInstalled(app_name.to_string()));
+    }
+    let manifest = parse_manifest_file(&manifest_path)?;
+
+    if reset {
+        remove_overrides(app_name)?;
+        say!("[voidbox] Reset permissions of {}", app_name);
+    }
+
+    let mut overrides = load_overrides(app_name)?;
+    let edits = !grant.is_empty() || !revoke.is_empty() || clipboard.is_some();
+    if edits {
+        let mut permissions = overrides.unwrap_or_else(|| manifest_permissions(&manifest));
+        for (names, enabled) in [(grant, true), (revoke, false)] {
+            for name in names {
+                if !permissions.set(name, enabled) {
+                    return Err(PermissionsError::UnknownPermission(name.clone()));
+                }
+            }
+        }
+        if let Some(clipboard) = clipboard {
+            permissions.clipboard = clipboard;
+        }
+        save_overrides(app_name, &permissions)?;
+        overrides = Some(permissions);
+    }
+
+    let changed = reset || edits;
+    let permissions = overrides
+        .clone()
+        .unwrap_or_else(|| manifest_permissions(&manifest));
+    if changed && is_app_running(app_name) {
+        write_live_permissions(app_name, &permissions)?;
+        say!(
+            "[voidbox] {} is running: {} changes apply now, others on its next start",
+            manifest.app.display_name,
+            LIVE_PERMISSIONS.join(" and ")
+        );
+    }
+
+    match (&overrides, permission_preset(&manifest)) {
+        (Some(_), _) => say!("Permissions of {} (user override):", app_name),
+        (None, Some(preset)) => say!("Permissions of {} ({} preset):", app_name, preset),
+        (None, None) => say!("Permissions of {}:", app_name),
+    }
+    let enabled = permissions.enabled_names();
+    let mut table = Table::new(&["PERMISSION", "STATE"]);
+    for name in PermissionConfig::NAMES {
+        let state = if enabled.contains(&name) { "on" } else { "off" };
+        table.row(vec![name.to_string(), state.to_string()]);
+    }
+    table.row(vec!["clipboard".into(), permissions.clipboard.to_string()]);
+    table.print();
+
+    Ok(())
+}
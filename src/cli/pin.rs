@@ -0,0 +1,114 @@
+//! Pin command implementation
+//!
+//! A pinned app stays at its installed version: `voidbox update` skips it,
+//! and a forced update reinstalls the same version from the saved manifest.
+//! The pin is kept in the installed apps database. For a GitHub source the
+//! release is also recorded in the saved manifest; unpinning drops it again
+//! so the next update moves to the latest release.
+
+use crate::cli::install::{InstallError, update_record};
+use crate::cli::versions::manifest_at_version;
+use crate::manifest::{
+    AppManifest, InstalledApp, SourceConfig, manifest_hash, manifest_text, parse_manifest_file,
+};
+use crate::storage::paths;
+use std::fs;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PinError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] crate::manifest::ManifestError),
+
+    #[error("Install error: {0}")]
+    InstallError(#[from] InstallError),
+}
+
+fn installed_record(app_name: &str) -> Result<InstalledApp, PinError> {
+    crate::cli::get_installed_apps()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|a| paths::same_app(&a.name, app_name))
+        .ok_or_else(|| PinError::NotInstalled(app_name.to_string()))
+}
+
+/// Replace an app's saved manifest, keeping its recorded hash in step so
+/// the change isn't taken for an edit that needs a reinstall
+fn save_manifest(manifest: &AppManifest) -> Result<(), PinError> {
+    fs::write(
+        paths::manifest_path(&manifest.app.name),
+        manifest_text(manifest)?,
+    )?;
+    let hash = manifest_hash(manifest).ok();
+    update_record(&manifest.app.name, |app| app.manifest_hash = hash)?;
+    Ok(())
+}
+
+/// Pin an app to its installed version
+pub fn pin_app(app_name: &str) -> Result<(), PinError> {
+    let record = installed_record(app_name)?;
+    let version = record.version.as_deref().unwrap_or("unknown");
+    if record.pinned {
+        say!(
+            "[voidbox] {} is already pinned at v{}",
+            record.display_name,
+            version
+        );
+        return Ok(());
+    }
+
+    // Record the release so a forced update reinstalls it
+    let manifest = parse_manifest_file(&paths::manifest_path(&record.name))?;
+    if let SourceConfig::Github { tag: None, .. } = &manifest.source
+        && let Some(version) = &record.version
+    {
+        match manifest_at_version(&record.name, &manifest, version) {
+            Ok(pinned) => save_manifest(&pinned)?,
+            Err(e) => say!(
+                "[voidbox] Warning: Could not look up the release of v{}: {}; \
+                 'update --force' would install the latest release",
+                version,
+                e
+            ),
+        }
+    }
+
+    update_record(&record.name, |app| app.pinned = true)?;
+    say!(
+        "[voidbox] Pinned {} at v{}; updates will skip it",
+        record.display_name,
+        version
+    );
+    Ok(())
+}
+
+/// Let updates move an app to newer versions again
+pub fn unpin_app(app_name: &str) -> Result<(), PinError> {
+    let record = installed_record(app_name)?;
+    if !record.pinned {
+        say!("[voidbox] {} is not pinned", record.display_name);
+        return Ok(());
+    }
+
+    let mut manifest = parse_manifest_file(&paths::manifest_path(&record.name))?;
+    if let SourceConfig::Github { tag, .. } = &mut manifest.source
+        && tag.is_some()
+    {
+        *tag = None;
+        manifest.app.version = None;
+        save_manifest(&manifest)?;
+    }
+
+    update_record(&record.name, |app| app.pinned = false)?;
+    say!(
+        "[voidbox] Unpinned {}; 'voidbox update' will update it again",
+        record.display_name
+    );
+    Ok(())
+}
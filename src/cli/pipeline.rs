@@ -0,0 +1,322 @@
+//! Install pipeline steps, per-step retry and the resume journal
+//!
+//! An install runs as discrete steps - resolve, download, verify, extract,
+//! integrate. Each step is retried on failure, and completed steps are
+//! recorded in a journal next to the rootfs so a rerun resumes where the
+//! last attempt stopped instead of starting over; an interrupted download
+//! continues from the bytes already fetched. A full disk or read-only
+//! file system pauses the step and asks the user to free space instead of
+//! failing the install.
+
+use crate::cli::install::InstallError;
+use crate::events::{Event, emit};
+use crate::manifest::{AppManifest, load_checksum_db, manifest_hash};
+use crate::settings::load_config;
+use crate::storage::{ScanError, log_transaction, partial_cached_download, paths};
+use indicatif::HumanBytes;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A discrete step of the install pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallStep {
+    Resolve,
+    Download,
+    Verify,
+    Extract,
+    /// The manifest's post-install hooks
+    Hooks,
+    Integrate,
+}
+
+impl InstallStep {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstallStep::Resolve => "resolve",
+            InstallStep::Download => "download",
+            InstallStep::Verify => "verify",
+            InstallStep::Extract => "extract",
+            InstallStep::Hooks => "hooks",
+            InstallStep::Integrate => "integrate",
+        }
+    }
+}
+
+/// A resolved artifact to download
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Artifact {
+    pub version: String,
+    pub url: String,
+    /// SHA-256 the manifest expects of the download
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Repository of the manifest, whose checksum database applies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+}
+
+impl Artifact {
+    /// SHA-256 the download must have, from the manifest or else the
+    /// known-good checksum database
+    pub fn trusted_sha256(&self) -> Option<String> {
+        self.sha256.clone().or_else(|| {
+            load_checksum_db()
+                .expected(&self.url, &self.version, self.repository.as_deref())
+                .map(str::to_string)
+        })
+    }
+}
+
+/// Progress of an install, persisted between attempts
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InstallJournal {
+    /// Hash of the manifest being installed; a different manifest restarts
+    pub manifest_hash: String,
+    pub completed: Vec<InstallStep>,
+    /// Base image to set up (None if the rootfs already has one)
+    pub base: Option<Artifact>,
+    /// App artifact (None for local sources)
+    pub app: Option<Artifact>,
+    pub base_archive: Option<PathBuf>,
+    pub app_archive: Option<PathBuf>,
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    app_name: String,
+}
+
+impl InstallJournal {
+    /// Load the saved journal for a rootfs if it is for this manifest
+    pub fn load(rootfs: &Path, manifest: &AppManifest) -> Option<Self> {
+        let hash = manifest_hash(manifest).unwrap_or_default();
+        fs::read_to_string(journal_path(rootfs))
+            .ok()
+            .and_then(|c| serde_json::from_str::<InstallJournal>(&c).ok())
+            .filter(|j| j.manifest_hash == hash)
+    }
+
+    /// Load the journal for a rootfs, or start a new one
+    pub fn open(rootfs: &Path, manifest: &AppManifest) -> Self {
+        let path = journal_path(rootfs);
+        let hash = manifest_hash(manifest).unwrap_or_default();
+
+        let mut journal = match Self::load(rootfs, manifest) {
+            Some(journal) => {
+                if !journal.completed.is_empty() {
+                    let done: Vec<&str> = journal.completed.iter().map(|s| s.as_str()).collect();
+                    say!(
+                        "[voidbox] Resuming install (completed: {})",
+                        done.join(", ")
+                    );
+                }
+                journal
+            }
+            None => InstallJournal {
+                manifest_hash: hash,
+                ..Default::default()
+            },
+        };
+        journal.path = path;
+        journal.app_name = manifest.app.name.clone();
+        journal
+    }
+
+    pub fn is_done(&self, step: InstallStep) -> bool {
+        self.completed.contains(&step)
+    }
+
+    /// Record a completed step
+    pub fn complete(&mut self, step: InstallStep) -> Result<(), InstallError> {
+        if !self.is_done(step) {
+            self.completed.push(step);
+        }
+        log_transaction(&self.app_name, "step", &format!("{} done", step.as_str()));
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| InstallError::Failed(format!("Failed to serialize journal: {}", e)))?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, content)?;
+        Ok(())
+    }
+
+    /// Remove the journal once the install has finished
+    pub fn finish(self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+fn journal_path(rootfs: &Path) -> PathBuf {
+    rootfs.with_file_name("install-journal.json")
+}
+
+/// Whether an install of this app was interrupted and left a journal
+pub fn has_interrupted_install(app_name: &str) -> bool {
+    journal_path(&paths::app_rootfs_dir(app_name)).exists()
+}
+
+/// Forget an interrupted install of this app, so it isn't continued
+pub(crate) fn discard_interrupted_install(app_name: &str) -> std::io::Result<()> {
+    let path = journal_path(&paths::app_rootfs_dir(app_name));
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Describe an interrupted install of this manifest that rerunning the
+/// install would continue, including any partially downloaded artifact
+pub fn interrupted_install(manifest: &AppManifest) -> Option<String> {
+    let journal = InstallJournal::load(&paths::app_rootfs_dir(&manifest.app.name), manifest)?;
+
+    let partial = [&journal.base, &journal.app]
+        .into_iter()
+        .flatten()
+        .find_map(|artifact| partial_cached_download(&artifact.url));
+    if let Some((downloaded, total)) = partial {
+        return Some(match total {
+            Some(total) => format!(
+                "{} of {} already downloaded",
+                HumanBytes(downloaded),
+                HumanBytes(total)
+            ),
+            None => format!("{} already downloaded", HumanBytes(downloaded)),
+        });
+    }
+
+    let last = journal.completed.last()?;
+    Some(format!("interrupted after the {} step", last.as_str()))
+}
+
+/// Run a pipeline step, retrying with backoff on failure
+pub fn run_step<T>(
+    app_name: &str,
+    step: InstallStep,
+    mut f: impl FnMut() -> Result<T, InstallError>,
+) -> Result<T, InstallError> {
+    let retries = load_config().map(|c| c.install.step_retries).unwrap_or(2);
+    let mut attempt = 0;
+
+    loop {
+        match f() {
+            Ok(value) => return Ok(value),
+            // Retrying can't change a scanner's verdict
+            Err(e @ InstallError::ScanError(ScanError::Detected { .. })) => {
+                emit_failed(app_name, step, &e);
+                return Err(e);
+            }
+            // Retrying won't help until the user frees space; ask instead
+            Err(e) if storage_blocker(&e).is_some() => {
+                let reason = storage_blocker(&e).unwrap_or_default();
+                log_transaction(
+                    app_name,
+                    "step",
+                    &format!("{} paused: {}", step.as_str(), reason),
+                );
+                if !wait_for_space(app_name, step, reason) {
+                    log_transaction(
+                        app_name,
+                        "step",
+                        &format!("{} aborted: {}", step.as_str(), e),
+                    );
+                    emit_failed(app_name, step, &e);
+                    return Err(e);
+                }
+            }
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                let delay = Duration::from_secs(2 * attempt as u64);
+                say!(
+                    "[voidbox] {} step failed: {} (retry {}/{} in {}s)",
+                    step.as_str(),
+                    e,
+                    attempt,
+                    retries,
+                    delay.as_secs()
+                );
+                log_transaction(
+                    app_name,
+                    "step",
+                    &format!("{} failed, retrying: {}", step.as_str(), e),
+                );
+                std::thread::sleep(delay);
+            }
+            Err(e) => {
+                log_transaction(
+                    app_name,
+                    "step",
+                    &format!("{} failed: {}", step.as_str(), e),
+                );
+                emit_failed(app_name, step, &e);
+                return Err(e);
+            }
+        }
+    }
+}
+
+fn emit_failed(app_name: &str, step: InstallStep, error: &InstallError) {
+    emit(Event::Failed {
+        app: app_name.to_string(),
+        stage: step.as_str().to_string(),
+        error: error.to_string(),
+    });
+}
+
+/// Detect a full disk or read-only file system anywhere in an error's chain
+fn storage_blocker(err: &InstallError) -> Option<&'static str> {
+    let mut source: Option<&dyn Error> = Some(err);
+    while let Some(e) = source {
+        if let Some(io) = e.downcast_ref::<std::io::Error>() {
+            match io.kind() {
+                ErrorKind::StorageFull | ErrorKind::QuotaExceeded => {
+                    return Some("the disk is full");
+                }
+                ErrorKind::ReadOnlyFilesystem => return Some("the file system is read-only"),
+                _ => {}
+            }
+        }
+        source = e.source();
+    }
+
+    // Some steps (e.g. extraction) only keep the message
+    let message = err.to_string();
+    if message.contains("No space left on device") || message.contains("Disk quota exceeded") {
+        Some("the disk is full")
+    } else if message.contains("Read-only file system") {
+        Some("the file system is read-only")
+    } else {
+        None
+    }
+}
+
+/// Pause a step until the user has freed space; returns false to abort
+///
+/// Completed steps stay in the journal, so resuming only redoes this one.
+fn wait_for_space(app_name: &str, step: InstallStep, reason: &str) -> bool {
+    let message = format!(
+        "Installing {} paused during the {} step because {} ({}).\n\n\
+         Free some space (or make it writable) and resume, or abort the install.",
+        app_name,
+        step.as_str(),
+        reason,
+        paths::data_dir().display()
+    );
+    say!("[voidbox] {}", message.replace("\n\n", " "));
+
+    if crate::can_prompt() {
+        crate::confirm("[voidbox] Resume?", true)
+    } else if crate::gui::can_ask() {
+        crate::gui::ask_yes_no(
+            "Voidbox - Not Enough Space",
+            &format!("{}\n\nResume the install?", message),
+        )
+    } else {
+        false
+    }
+}
@@ -0,0 +1,374 @@
+//! Change plans
+//!
+//! Multi-app operations first work out what they would change, print that as
+//! a diff (like a package manager's transaction summary) and ask for
+//! confirmation before touching anything. Single installs get an
+//! `InstallPlan` instead: what will be downloaded, roughly how much disk it
+//! takes, and which files outside the app's directory are created.
+
+use crate::cli::Artifact;
+use crate::cli::install::{InstallError, fetch_latest_ubuntu_base, resolve_download};
+use crate::cli::interrupted_install;
+use crate::manifest::{AppManifest, SourceConfig};
+use crate::storage::{cached_size, paths, remote_size};
+use indicatif::HumanBytes;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Unpacked size of a download relative to its compressed size, for
+/// estimates before anything is extracted
+const UNPACKED_RATIO: u64 = 3;
+
+/// Name of the base image download in a plan
+const BASE_NAME: &str = "Ubuntu base";
+
+/// One change to an installed app
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlannedChange {
+    /// Install an app that isn't installed yet
+    Add {
+        app: String,
+        version: Option<String>,
+    },
+    /// Move an app to a newer version
+    Upgrade {
+        app: String,
+        from: Option<String>,
+        to: String,
+    },
+    /// Reinstall an app at the same version
+    Reinstall { app: String, reason: String },
+    /// Remove an installed app
+    Remove { app: String },
+}
+
+impl PlannedChange {
+    pub fn app(&self) -> &str {
+        match self {
+            PlannedChange::Add { app, .. }
+            | PlannedChange::Upgrade { app, .. }
+            | PlannedChange::Reinstall { app, .. }
+            | PlannedChange::Remove { app } => app,
+        }
+    }
+}
+
+impl std::fmt::Display for PlannedChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlannedChange::Add { app, version } => match version {
+                Some(version) => write!(f, "+ add       {} {}", app, version),
+                None => write!(f, "+ add       {}", app),
+            },
+            PlannedChange::Upgrade { app, from, to } => write!(
+                f,
+                "~ upgrade   {} {} → {}",
+                app,
+                from.as_deref().unwrap_or("?"),
+                to
+            ),
+            PlannedChange::Reinstall { app, reason } => {
+                write!(f, "~ reinstall {} ({})", app, reason)
+            }
+            PlannedChange::Remove { app } => write!(f, "- remove    {}", app),
+        }
+    }
+}
+
+/// The changes an operation would make
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub changes: Vec<PlannedChange>,
+    /// Apps that were checked and need no change
+    pub unchanged: Vec<String>,
+    /// Apps that couldn't be planned, with the error
+    pub errors: Vec<(String, String)>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Print the plan as a diff
+    pub fn print(&self) {
+        for change in &self.changes {
+            say!("  {}", change);
+        }
+        for (app, error) in &self.errors {
+            say!("  ! {}: {}", app, error);
+        }
+
+        let count = |f: fn(&PlannedChange) -> bool| self.changes.iter().filter(|c| f(c)).count();
+        say!(
+            "[voidbox] {} to add, {} to upgrade, {} to reinstall, {} to remove, {} unchanged",
+            count(|c| matches!(c, PlannedChange::Add { .. })),
+            count(|c| matches!(c, PlannedChange::Upgrade { .. })),
+            count(|c| matches!(c, PlannedChange::Reinstall { .. })),
+            count(|c| matches!(c, PlannedChange::Remove { .. })),
+            self.unchanged.len()
+        );
+    }
+
+    /// Ask whether to go ahead; `yes` skips the question
+    ///
+    /// Without a terminal to ask on, the plan is not applied.
+    pub fn confirm(&self, yes: bool) -> bool {
+        if yes {
+            return true;
+        }
+
+        if !crate::can_prompt() {
+            say!("[voidbox] Not interactive; pass --yes to apply these changes");
+            return false;
+        }
+        crate::confirm("[voidbox] Proceed?", false)
+    }
+}
+
+/// One download an install needs
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedDownload {
+    pub name: String,
+    pub version: String,
+    pub url: String,
+    /// SHA-256 the download must have, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Size reported by the server, or of the cached copy
+    pub size: Option<u64>,
+    /// Already in the download cache
+    pub cached: bool,
+}
+
+/// What installing an app will do, worked out before anything changes
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallPlan {
+    /// Version that will be installed ("local" for local sources)
+    pub version: String,
+    /// Where the app is downloaded from (None for local sources)
+    pub origin: Option<String>,
+    pub downloads: Vec<PlannedDownload>,
+    /// The Ubuntu base image has to be set up before the app
+    pub needs_base: bool,
+    /// Packages installed into the base image
+    pub packages: Vec<String>,
+    /// Size of local source files copied in
+    pub local_size: Option<u64>,
+    /// Directories created for the app
+    pub directories: Vec<PathBuf>,
+    /// Files created outside the app's directory for desktop integration
+    pub integration: Vec<PathBuf>,
+    /// Progress of an interrupted earlier install that will be continued
+    pub resume: Option<String>,
+}
+
+impl InstallPlan {
+    /// Bytes still to download, and whether every size was known
+    pub fn download_size(&self) -> (u64, bool) {
+        let pending: Vec<_> = self.downloads.iter().filter(|d| !d.cached).collect();
+        let size = pending.iter().filter_map(|d| d.size).sum();
+        (size, pending.iter().all(|d| d.size.is_some()))
+    }
+
+    /// Estimated disk space used once installed
+    pub fn installed_size(&self) -> Option<u64> {
+        if self.downloads.iter().any(|d| d.size.is_none()) {
+            return None;
+        }
+        let unpacked: u64 = self
+            .downloads
+            .iter()
+            .filter_map(|d| d.size)
+            .map(|s| s * UNPACKED_RATIO)
+            .sum();
+        Some(unpacked + self.local_size.unwrap_or(0))
+    }
+
+    /// The plan as label and value pairs, for the CLI prompt and the GUI
+    pub fn summary(&self) -> Vec<(&'static str, String)> {
+        let (size, known) = self.download_size();
+        let download = match (size, known) {
+            (0, true) => "Nothing (all cached)".to_string(),
+            (size, true) => HumanBytes(size).to_string(),
+            (0, false) => "Unknown".to_string(),
+            (size, false) => format!("at least {}", HumanBytes(size)),
+        };
+        let installed = self
+            .installed_size()
+            .map(|s| format!("about {}", HumanBytes(s)))
+            .unwrap_or_else(|| "Unknown".to_string());
+        let runtime = if !self.needs_base {
+            "Already set up".to_string()
+        } else if self
+            .downloads
+            .iter()
+            .any(|d| d.name == BASE_NAME && d.cached)
+        {
+            "Ubuntu base image is set up first (cached)".to_string()
+        } else {
+            "Ubuntu base image is downloaded and set up first".to_string()
+        };
+
+        let mut rows = vec![
+            ("Download size:", download),
+            ("Installed size:", installed),
+            ("Runtime:", runtime),
+        ];
+        if !self.packages.is_empty() {
+            rows.push((
+                "Packages:",
+                format!("{} ({})", self.packages.len(), self.packages.join(", ")),
+            ));
+        }
+        rows.push((
+            "Desktop integration:",
+            format!(
+                "{} file(s): desktop entry, icon, command wrapper",
+                self.integration.len()
+            ),
+        ));
+        rows
+    }
+
+    /// Print the plan before asking to install
+    pub fn print(&self) {
+        self.print_changes(false);
+    }
+
+    /// Print the plan in full, with download URLs and the directories
+    /// created, for `voidbox install --dry-run`
+    pub fn print_details(&self) {
+        self.print_changes(true);
+    }
+
+    fn print_changes(&self, details: bool) {
+        for download in &self.downloads {
+            let size = download
+                .size
+                .map(|s| HumanBytes(s).to_string())
+                .unwrap_or_else(|| "size unknown".to_string());
+            let cached = if download.cached { ", cached" } else { "" };
+            say!(
+                "  + download  {} {} ({}{})",
+                download.name,
+                download.version,
+                size,
+                cached
+            );
+            if details {
+                say!("              {}", download.url);
+            }
+        }
+        if details {
+            for path in &self.directories {
+                say!("  + mkdir     {}", path.display());
+            }
+        }
+        for path in &self.integration {
+            say!("  + create    {}", path.display());
+        }
+        for (label, value) in self.summary() {
+            say!("[voidbox] {} {}", label, value);
+        }
+        if let Some(resume) = &self.resume {
+            say!(
+                "[voidbox] Previous install: {}; it will continue from there",
+                resume
+            );
+        }
+    }
+
+    /// Ask whether to install; `yes` or a non-interactive session skips
+    /// the question
+    pub fn confirm(&self, yes: bool) -> bool {
+        yes || crate::confirm("[voidbox] Install?", true)
+    }
+}
+
+fn planned_download(name: &str, artifact: Artifact) -> PlannedDownload {
+    let cached = cached_size(&artifact.url);
+    PlannedDownload {
+        name: name.to_string(),
+        sha256: artifact.trusted_sha256(),
+        size: cached.or_else(|| remote_size(&artifact.url)),
+        cached: cached.is_some(),
+        version: artifact.version,
+        url: artifact.url,
+    }
+}
+
+/// Work out what installing an app would do (network access, no changes)
+pub fn plan_install(manifest: &AppManifest) -> Result<InstallPlan, InstallError> {
+    let app_name = &manifest.app.name;
+    let needs_base = !paths::app_rootfs_dir(app_name)
+        .join("etc/os-release")
+        .exists();
+
+    let mut downloads = Vec::new();
+    if needs_base {
+        let (version, url) = fetch_latest_ubuntu_base()?;
+        downloads.push(planned_download(
+            BASE_NAME,
+            Artifact {
+                version,
+                url,
+                sha256: None,
+                repository: None,
+            },
+        ));
+    }
+    let (version, origin) = match resolve_download(manifest)? {
+        Some((version, url)) => {
+            downloads.push(planned_download(
+                &manifest.app.display_name,
+                Artifact {
+                    version: version.clone(),
+                    url: url.clone(),
+                    sha256: manifest.source.sha256().map(str::to_string),
+                    repository: manifest.repository.clone(),
+                },
+            ));
+            (version, Some(url))
+        }
+        None => ("local".to_string(), None),
+    };
+
+    let local_size = match &manifest.source {
+        SourceConfig::Local { path } => Some(
+            walkdir::WalkDir::new(path)
+                .into_iter()
+                .flatten()
+                .filter_map(|e| e.metadata().ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .sum(),
+        ),
+        _ => None,
+    };
+
+    let integration = vec![
+        paths::desktop_entry_path(&manifest.desktop_id()),
+        paths::app_icon_path(app_name),
+        paths::app_wrapper_path(app_name),
+    ];
+    let mut directories = vec![paths::app_dir(app_name), paths::app_rootfs_dir(app_name)];
+    for parent in integration.iter().filter_map(|path| path.parent()) {
+        if !directories.iter().any(|dir| dir == parent) {
+            directories.push(parent.to_path_buf());
+        }
+    }
+    directories.retain(|dir| !dir.exists());
+
+    Ok(InstallPlan {
+        version,
+        origin,
+        downloads,
+        needs_base,
+        packages: manifest.dependencies.packages.clone(),
+        local_size,
+        directories,
+        integration,
+        resume: interrupted_install(manifest),
+    })
+}
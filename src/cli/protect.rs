@@ -0,0 +1,62 @@
+//! Protect command implementation
+//!
+//! A protected app can't be removed by accident: `voidbox remove` and
+//! `voidbox uninstall` refuse it without `--force`, and uninstalling
+//! voidbox with `--purge` refuses while any app is protected. The flag is
+//! kept in the installed apps database and survives updates and reinstalls.
+
+use crate::cli::install::{InstallError, update_record};
+use crate::manifest::InstalledApp;
+use crate::storage::paths;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ProtectError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error("Install error: {0}")]
+    InstallError(#[from] InstallError),
+}
+
+fn installed_record(app_name: &str) -> Result<InstalledApp, ProtectError> {
+    crate::cli::get_installed_apps()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|a| paths::same_app(&a.name, app_name))
+        .ok_or_else(|| ProtectError::NotInstalled(app_name.to_string()))
+}
+
+/// Whether an installed app is protected from removal
+pub fn is_protected(app_name: &str) -> bool {
+    installed_record(app_name).is_ok_and(|a| a.protected)
+}
+
+/// Protect an app from removal
+pub fn protect_app(app_name: &str) -> Result<(), ProtectError> {
+    let record = installed_record(app_name)?;
+    if record.protected {
+        say!("[voidbox] {} is already protected", record.display_name);
+        return Ok(());
+    }
+
+    update_record(&record.name, |app| app.protected = true)?;
+    say!(
+        "[voidbox] Protected {}; removing it now needs --force",
+        record.display_name
+    );
+    Ok(())
+}
+
+/// Let an app be removed without `--force` again
+pub fn unprotect_app(app_name: &str) -> Result<(), ProtectError> {
+    let record = installed_record(app_name)?;
+    if !record.protected {
+        say!("[voidbox] {} is not protected", record.display_name);
+        return Ok(());
+    }
+
+    update_record(&record.name, |app| app.protected = false)?;
+    say!("[voidbox] {} is no longer protected", record.display_name);
+    Ok(())
+}
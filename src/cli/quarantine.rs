@@ -0,0 +1,169 @@
+//! Quarantine for the first run of newly installed apps
+//!
+//! When enabled in config, a fresh install is marked as quarantined. Its
+//! first run uses the strictest sandbox profile regardless of the manifest,
+//! and afterwards the user sees what the app tried to write and which
+//! permissions it asked for before deciding whether to grant them.
+
+use crate::manifest::{AppManifest, PermissionConfig};
+use crate::settings::{load_config, save_overrides, strict_permissions};
+use crate::storage::paths;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// Files the app could write to while quarantined (container paths without
+/// the leading `/`), with their mtimes
+pub type AuditSnapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+/// Mark a freshly installed app as quarantined if enabled in config
+pub(crate) fn quarantine_if_enabled(app_name: &str) {
+    let enabled = load_config().map(|c| c.quarantine.enabled).unwrap_or(false);
+    if !enabled {
+        return;
+    }
+
+    if let Err(e) = fs::write(paths::app_quarantine_path(app_name), "") {
+        say!(
+            "[voidbox] Warning: Could not quarantine {}: {}",
+            app_name,
+            e
+        );
+        return;
+    }
+    say!("[voidbox] First run will be quarantined (strict sandbox)");
+}
+
+/// Check whether an app's next run is quarantined
+pub fn is_quarantined(app_name: &str) -> bool {
+    paths::app_quarantine_path(app_name).exists()
+}
+
+/// Record the files under the container's home directories
+///
+/// With the home permission withheld, anything the app writes to `$HOME`
+/// lands in the rootfs or the app's data directory (its `/root`), which is
+/// what makes its attempts visible.
+pub(crate) fn snapshot_rootfs(rootfs: &Path, data_dir: &Path) -> AuditSnapshot {
+    let mut snapshot = AuditSnapshot::new();
+    for (dir, container_dir) in [
+        (rootfs.join("home"), "home"),
+        (data_dir.to_path_buf(), "root"),
+    ] {
+        for entry in WalkDir::new(&dir).max_depth(6).into_iter().flatten() {
+            if entry.file_type().is_file() {
+                let mtime = entry.metadata().ok().and_then(|m| m.modified().ok());
+                let relative = entry.path().strip_prefix(&dir).unwrap_or(entry.path());
+                snapshot.insert(Path::new(container_dir).join(relative), mtime);
+            }
+        }
+    }
+    snapshot
+}
+
+/// Permissions the manifest requests that the strict profile withheld
+fn withheld_permissions(manifest: &AppManifest, requested: &PermissionConfig) -> Vec<&'static str> {
+    let granted = strict_permissions().enabled_names();
+    requested
+        .enabled_names()
+        .into_iter()
+        .filter(|name| !granted.contains(name))
+        // Only a [dbus] table asks for more than the default names
+        .filter(|name| *name != "session_bus" || !manifest.dbus.is_default())
+        .collect()
+}
+
+/// Show what the quarantined run did and ask whether to grant permissions
+pub(crate) fn review_quarantine(
+    manifest: &AppManifest,
+    requested: &PermissionConfig,
+    rootfs: &Path,
+    before: &AuditSnapshot,
+) {
+    let app_name = &manifest.app.name;
+    let after = snapshot_rootfs(rootfs, &paths::app_data_dir(app_name));
+
+    let touched: Vec<&Path> = after
+        .iter()
+        .filter(|(path, mtime)| before.get(*path) != Some(mtime))
+        .map(|(path, _)| path.as_path())
+        .collect();
+
+    let mut report = format!(
+        "{} ran in quarantine (strict sandbox).\n",
+        manifest.app.display_name
+    );
+
+    if touched.is_empty() {
+        report.push_str("\nIt did not write to its home directory.\n");
+    } else {
+        report.push_str(&format!(
+            "\nIt wrote {} file(s) where it expected your home directory:\n",
+            touched.len()
+        ));
+        for path in touched.iter().take(15) {
+            report.push_str(&format!("  /{}\n", path.display()));
+        }
+        if touched.len() > 15 {
+            report.push_str(&format!("  ... and {} more\n", touched.len() - 15));
+        }
+    }
+
+    let withheld = withheld_permissions(manifest, requested);
+    if withheld.is_empty() {
+        report.push_str("\nIt requests no permissions beyond the strict profile.\n");
+    } else {
+        report.push_str(&format!(
+            "\nRequested permissions: {}\n",
+            withheld.join(", ")
+        ));
+    }
+    if withheld.contains(&"session_bus") {
+        let dbus = &manifest.dbus;
+        if !dbus.filter {
+            report.push_str("Session bus: unfiltered\n");
+        }
+        if !dbus.talk.is_empty() {
+            report.push_str(&format!(
+                "Session bus names to talk to: {}\n",
+                dbus.talk.join(", ")
+            ));
+        }
+        if !dbus.own.is_empty() {
+            report.push_str(&format!(
+                "Session bus names to own: {}\n",
+                dbus.own.join(", ")
+            ));
+        }
+    }
+
+    say!();
+    for line in report.lines() {
+        say!("[voidbox] {}", line);
+    }
+
+    let granted = crate::gui::can_ask()
+        && crate::gui::ask_yes_no(
+            "Voidbox Quarantine",
+            &format!("{}\nGrant the requested permissions?", report),
+        );
+
+    if granted {
+        say!("[voidbox] Permissions granted for {}", app_name);
+    } else {
+        // Keep the strict profile as a user override; it can be relaxed later
+        if let Err(e) = save_overrides(app_name, &strict_permissions()) {
+            say!("[voidbox] Warning: Could not save permissions: {}", e);
+            return;
+        }
+        say!(
+            "[voidbox] {} keeps the strict profile. Edit {} to change it.",
+            app_name,
+            paths::app_settings_path(app_name).display()
+        );
+    }
+
+    fs::remove_file(paths::app_quarantine_path(app_name)).ok();
+}
@@ -0,0 +1,144 @@
+//! Recover command implementation
+//!
+//! A self-update that is interrupted or downloads a bad release can leave
+//! `~/.local/bin/voidbox` truncated or unrunnable, which breaks every
+//! desktop entry at once. Self-update keeps the previous, verified binary
+//! as `voidbox.bak` next to it; `voidbox recover` checks the installed
+//! binary and puts the backup back when it is broken. Run from a freshly
+//! downloaded binary, it installs itself when there is no usable backup.
+
+use crate::storage::paths;
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RecoverError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("No working voidbox binary to recover from: {0}")]
+    NoBackup(String),
+
+    #[error("Restored binary failed verification: {0}")]
+    VerifyFailed(String),
+}
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+
+/// Where the known-good copy of a binary is kept
+pub fn backup_path(binary: &Path) -> PathBuf {
+    binary.with_extension("bak")
+}
+
+/// Check that a file is a runnable voidbox binary
+///
+/// The ELF header is checked before anything is run, so a truncated or
+/// garbled download is never executed.
+pub fn verify_binary(binary: &Path) -> Result<(), String> {
+    binary_version(binary).map(drop)
+}
+
+/// Check that a file is a runnable voidbox binary and return the version
+/// it reports
+pub fn binary_version(binary: &Path) -> Result<String, String> {
+    let mut magic = [0u8; 4];
+    fs::File::open(binary)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .map_err(|e| format!("{}: {}", binary.display(), e))?;
+    if &magic != ELF_MAGIC {
+        return Err(format!("{} is not an executable", binary.display()));
+    }
+
+    let output = Command::new(binary)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("{} can't run: {}", binary.display(), e))?;
+    let version = String::from_utf8_lossy(&output.stdout);
+    match version.trim().strip_prefix(crate::APP_NAME) {
+        Some(version) if output.status.success() => Ok(version.trim().to_string()),
+        _ => Err(format!(
+            "{} does not report a voidbox version",
+            binary.display()
+        )),
+    }
+}
+
+/// Copy `from` over `to` atomically, as an executable
+fn replace_binary(from: &Path, to: &Path) -> std::io::Result<()> {
+    let tmp = to.with_extension("tmp");
+    fs::copy(from, &tmp)?;
+    fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755))?;
+    fs::rename(&tmp, to)
+}
+
+/// Keep a verified copy of `binary` as its backup before replacing it
+///
+/// A binary that fails verification is not backed up, so an existing good
+/// backup is never overwritten with a broken one.
+pub fn keep_backup(binary: &Path) -> Result<(), RecoverError> {
+    verify_binary(binary).map_err(RecoverError::VerifyFailed)?;
+    let backup = backup_path(binary);
+    replace_binary(binary, &backup)?;
+    verify_binary(&backup).map_err(RecoverError::VerifyFailed)
+}
+
+/// Put the backup of `binary` back in place and verify the result
+pub fn restore_backup(binary: &Path) -> Result<(), RecoverError> {
+    let backup = backup_path(binary);
+    verify_binary(&backup).map_err(RecoverError::NoBackup)?;
+    replace_binary(&backup, binary)?;
+    verify_binary(binary).map_err(RecoverError::VerifyFailed)
+}
+
+/// Check the installed binary and restore it if it is broken
+pub fn recover() -> Result<(), RecoverError> {
+    let installed = paths::install_path();
+
+    match verify_binary(&installed) {
+        Ok(()) => {
+            say!(
+                "[voidbox] {} is working; nothing to recover",
+                installed.display()
+            );
+            return Ok(());
+        }
+        Err(e) => say!("[voidbox] Installed binary is broken: {}", e),
+    }
+
+    let backup_error = match restore_backup(&installed) {
+        Ok(()) => {
+            say!(
+                "[voidbox] Restored the previous binary from {}",
+                backup_path(&installed).display()
+            );
+            return Ok(());
+        }
+        Err(e) => e.to_string(),
+    };
+
+    // Run from a fresh download, this binary can replace the broken one
+    let current = std::env::current_exe()?;
+    if current != installed && verify_binary(&current).is_ok() {
+        if let Some(parent) = installed.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        replace_binary(&current, &installed)?;
+        verify_binary(&installed).map_err(RecoverError::VerifyFailed)?;
+        say!(
+            "[voidbox] Installed v{} from {}",
+            crate::VERSION,
+            current.display()
+        );
+        return Ok(());
+    }
+
+    Err(RecoverError::NoBackup(format!(
+        "{}; download voidbox again and run it with 'recover'",
+        backup_error
+    )))
+}
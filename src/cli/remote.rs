@@ -0,0 +1,224 @@
+//! Remote command implementation - drive voidbox on another machine over SSH
+
+use crate::manifest::{MAX_EXTENDS_DEPTH, ManifestOrigin, host_arch};
+use std::fs::File;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RemoteError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("SSH to {host} failed: {message}")]
+    SshFailed { host: String, message: String },
+
+    #[error("Remote install failed with exit code {0}")]
+    InstallFailed(i32),
+
+    #[error("{host} is {remote}, this voidbox is built for {local}; install voidbox there first")]
+    ArchMismatch {
+        host: String,
+        remote: String,
+        local: String,
+    },
+
+    #[error("Can't copy the manifest: {0}")]
+    ManifestError(String),
+}
+
+/// Where voidbox lives on the remote machine (relative to the remote $HOME)
+const REMOTE_BIN: &str = ".local/bin/voidbox";
+
+/// Install an app on a remote machine, copying voidbox there if needed
+pub fn remote_install(host: &str, source: &str, force: bool) -> Result<(), RemoteError> {
+    say!("[voidbox] Connecting to {}...", host);
+
+    let remote_bin = ensure_remote_voidbox(host)?;
+
+    // Local manifest files are copied over with the local bases they
+    // extend, at the same paths under a temporary directory so relative
+    // `extends` resolve there as they do here; URLs and app names pass through
+    let local_manifest = Path::new(source);
+    let remote_source = if !source.contains("://") && local_manifest.is_file() {
+        let files = local_manifest_chain(local_manifest)?;
+        let remote_dir = ssh_output(host, "mktemp -d")?;
+        say!("[voidbox] Copying {} to {}...", source, host);
+        for file in &files {
+            let remote_path = format!("{}{}", remote_dir, file.display());
+            ssh_with_stdin(
+                host,
+                &format!(
+                    "mkdir -p \"$(dirname {0})\" && cat > {0}",
+                    shell_quote(&remote_path)
+                ),
+                file,
+            )?;
+        }
+        let remote_path = format!("{}{}", remote_dir, files[0].display());
+        Some((remote_dir, remote_path))
+    } else {
+        None
+    };
+
+    let install_source = remote_source
+        .as_ref()
+        .map_or(source, |(_, path)| path.as_str());
+    let mut command = format!("{} install {}", remote_bin, shell_quote(install_source));
+    if force {
+        command.push_str(" --force");
+    }
+    if let Some((dir, _)) = &remote_source {
+        command.push_str(&format!(
+            "; status=$?; rm -rf {}; exit $status",
+            shell_quote(dir)
+        ));
+    }
+
+    say!("[voidbox] Installing {} on {}...", source, host);
+
+    // A TTY keeps the remote progress bars working
+    let mut ssh = Command::new("ssh");
+    if std::io::stdout().is_terminal() {
+        ssh.arg("-t");
+    }
+    let status = ssh
+        .arg("--")
+        .arg(host)
+        .arg(&command)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()?;
+
+    if !status.success() {
+        return Err(RemoteError::InstallFailed(status.code().unwrap_or(1)));
+    }
+
+    say!("[voidbox] Installed {} on {}", source, host);
+    Ok(())
+}
+
+/// Find voidbox on the remote machine, copying this binary if it's missing
+fn ensure_remote_voidbox(host: &str) -> Result<String, RemoteError> {
+    let found = ssh_output(
+        host,
+        &format!(
+            "command -v voidbox || {{ test -x \"$HOME/{0}\" && echo \"$HOME/{0}\"; }} || true",
+            REMOTE_BIN
+        ),
+    )?;
+    if !found.is_empty() {
+        return Ok(shell_quote(&found));
+    }
+
+    // This binary only runs on a machine of the same architecture
+    let remote_arch = match ssh_output(host, "uname -m")?.as_str() {
+        "arm64" => "aarch64".to_string(),
+        arch if arch.starts_with("armv7") => "armv7".to_string(),
+        arch => arch.to_string(),
+    };
+    if remote_arch != host_arch() {
+        return Err(RemoteError::ArchMismatch {
+            host: host.to_string(),
+            remote: remote_arch,
+            local: host_arch().to_string(),
+        });
+    }
+
+    say!("[voidbox] voidbox not found on {}, copying it...", host);
+    let self_exe = std::env::current_exe()?;
+    ssh_with_stdin(
+        host,
+        &format!(
+            "mkdir -p \"$HOME/.local/bin\" && cat > \"$HOME/{0}\" && chmod +x \"$HOME/{0}\"",
+            REMOTE_BIN
+        ),
+        &self_exe,
+    )?;
+
+    Ok(format!("\"$HOME/{}\"", REMOTE_BIN))
+}
+
+/// A local manifest followed by the local bases in its `extends` chain, as
+/// absolute paths
+///
+/// A base at a URL ends the chain, since the remote machine fetches it
+/// itself. An absolute local path can't be recreated under the remote
+/// directory, so it's refused.
+fn local_manifest_chain(manifest: &Path) -> Result<Vec<PathBuf>, RemoteError> {
+    let mut files = vec![std::path::absolute(manifest)?];
+    loop {
+        let current = &files[files.len() - 1];
+        let content = std::fs::read_to_string(current)?;
+        let table: toml::Table = toml::from_str(&content)
+            .map_err(|e| RemoteError::ManifestError(format!("{}: {}", current.display(), e)))?;
+        let Some(reference) = table.get("extends").and_then(|value| value.as_str()) else {
+            return Ok(files);
+        };
+        if Path::new(reference).is_absolute() {
+            return Err(RemoteError::ManifestError(format!(
+                "{} extends the absolute path {}; use a relative path or URL",
+                current.display(),
+                reference
+            )));
+        }
+        match ManifestOrigin::File(current.clone()).resolve(reference) {
+            ManifestOrigin::File(base) if files.len() <= MAX_EXTENDS_DEPTH => files.push(base),
+            ManifestOrigin::File(_) => {
+                return Err(RemoteError::ManifestError(format!(
+                    "extends chain is deeper than {} levels",
+                    MAX_EXTENDS_DEPTH
+                )));
+            }
+            _ => return Ok(files),
+        }
+    }
+}
+
+/// Run a remote command and return its trimmed stdout
+fn ssh_output(host: &str, command: &str) -> Result<String, RemoteError> {
+    let output = Command::new("ssh")
+        .arg("--")
+        .arg(host)
+        .arg(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(RemoteError::SshFailed {
+            host: host.to_string(),
+            message: format!("'{}' exited with {}", command, output.status),
+        });
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Run a remote command with a local file as its stdin
+fn ssh_with_stdin(host: &str, command: &str, file: &Path) -> Result<(), RemoteError> {
+    let status = Command::new("ssh")
+        .arg("--")
+        .arg(host)
+        .arg(command)
+        .stdin(File::open(file)?)
+        .stderr(Stdio::inherit())
+        .status()?;
+
+    if !status.success() {
+        return Err(RemoteError::SshFailed {
+            host: host.to_string(),
+            message: format!("'{}' exited with {}", command, status),
+        });
+    }
+
+    Ok(())
+}
+
+/// Quote a string for a POSIX shell
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
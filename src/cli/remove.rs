@@ -1,10 +1,20 @@
 //! Remove command implementation
 
-use crate::desktop::{remove_app_wrapper, remove_desktop_entry, remove_icon};
-use crate::manifest::InstalledApp;
-use crate::settings::remove_overrides;
+use crate::cli::exclude::{ExcludedFiles, save_excluded};
+use crate::cli::hooks::{Hook, run_hooks};
+use crate::cli::integrity::save_installed_files;
+use crate::cli::optimize::save_optimized;
+use crate::cli::protect::is_protected;
+use crate::cli::removed::{keep_removed, prune_removed, removal_keep};
+use crate::desktop::{
+    refresh_batch, remove_app_wrapper, remove_desktop_entry, remove_icon, remove_window_rules,
+};
+use crate::events::{EventKind, fire_event};
+use crate::manifest::{InstalledApp, parse_manifest_file};
+use crate::settings::{remove_env_overrides, remove_overrides};
 use crate::storage::paths;
 use std::fs;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -12,6 +22,9 @@ pub enum RemoveError {
     #[error("App not installed: {0}")]
     NotInstalled(String),
 
+    #[error("{0} is protected; remove it with --force or run 'voidbox unprotect {0}' first")]
+    Protected(String),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -19,8 +32,67 @@ pub enum RemoveError {
     Failed(String),
 }
 
-/// Remove an installed app
-pub fn remove_app(app_name: &str, purge: bool) -> Result<(), RemoveError> {
+/// Remove an installed app; a protected one only with `force`
+///
+/// What is removed is kept for `voidbox undo-remove` for `[removal] keep`.
+pub fn remove_app(app_name: &str, purge: bool, force: bool) -> Result<(), RemoveError> {
+    if !force && is_protected(app_name) {
+        return Err(RemoveError::Protected(app_name.to_string()));
+    }
+    let keep = removal_keep();
+    remove_and_report(app_name, purge, (!keep.is_zero()).then_some(keep))
+}
+
+/// Remove an app for good, without keeping it for `voidbox undo-remove`
+pub(crate) fn discard_app(app_name: &str, purge: bool) -> Result<(), RemoveError> {
+    remove_and_report(app_name, purge, None)
+}
+
+fn remove_and_report(
+    app_name: &str,
+    purge: bool,
+    keep: Option<Duration>,
+) -> Result<(), RemoveError> {
+    let result = remove_app_files(app_name, purge, keep);
+    match &result {
+        Ok(()) => fire_event(
+            EventKind::Remove,
+            Some(app_name),
+            None,
+            &format!("{} removed", app_name),
+        ),
+        Err(RemoveError::NotInstalled(_)) => {}
+        Err(e) => fire_event(EventKind::Failure, Some(app_name), None, &e.to_string()),
+    }
+    result
+}
+
+/// Uninstall an app: like `remove`, but its data can go too
+///
+/// The data is deleted with `purge`, kept with `keep_data`, and otherwise
+/// only if the user says so; it's kept when there's no one to ask.
+pub fn uninstall_app(
+    app_name: &str,
+    purge: bool,
+    keep_data: bool,
+    force: bool,
+) -> Result<(), RemoveError> {
+    if !force && is_protected(app_name) {
+        return Err(RemoveError::Protected(app_name.to_string()));
+    }
+    if !paths::app_dir(app_name).exists() && !paths::manifest_path(app_name).exists() {
+        return Err(RemoveError::NotInstalled(app_name.to_string()));
+    }
+    let purge =
+        purge || (!keep_data && crate::confirm(&format!("Delete {}'s data too?", app_name), false));
+    remove_app(app_name, purge, force)
+}
+
+fn remove_app_files(
+    app_name: &str,
+    purge: bool,
+    keep: Option<Duration>,
+) -> Result<(), RemoveError> {
     let app_dir = paths::app_dir(app_name);
     let manifest_path = paths::manifest_path(app_name);
 
@@ -28,54 +100,100 @@ pub fn remove_app(app_name: &str, purge: bool) -> Result<(), RemoveError> {
         return Err(RemoveError::NotInstalled(app_name.to_string()));
     }
 
-    println!("[voidbox] Removing {}...", app_name);
+    say!("[voidbox] Removing {}...", app_name);
+
+    // Hooks see the app as it is; a failing one mustn't keep it installed
+    let rootfs = paths::app_rootfs_dir(app_name);
+    if let Ok(manifest) = parse_manifest_file(&manifest_path)
+        && rootfs.exists()
+        && let Err(e) = run_hooks(&manifest, &rootfs, Hook::PreRemove)
+    {
+        say!("[voidbox] Warning: {}", e);
+    }
+
+    // The files removed are moved aside for undo-remove first
+    if let Some(keep) = keep {
+        if let Err(e) = prune_removed(keep) {
+            say!(
+                "[voidbox] Warning: Could not delete expired removals: {}",
+                e
+            );
+        }
+        keep_removed(app_name, purge).map_err(|e| {
+            RemoveError::Failed(format!(
+                "Could not keep {} for undo-remove: {}",
+                app_name, e
+            ))
+        })?;
+    }
+
+    let _refresh = refresh_batch();
 
     // Remove desktop entry
-    if let Err(e) = remove_desktop_entry(app_name) {
-        println!("[voidbox] Warning: Could not remove desktop entry: {}", e);
+    let app_id = parse_manifest_file(&manifest_path)
+        .ok()
+        .and_then(|m| m.app.id);
+    if let Err(e) = remove_desktop_entry(app_name, app_id.as_deref()) {
+        say!("[voidbox] Warning: Could not remove desktop entry: {}", e);
     }
 
     // Remove wrapper script
     if let Err(e) = remove_app_wrapper(app_name) {
-        println!("[voidbox] Warning: Could not remove wrapper script: {}", e);
+        say!("[voidbox] Warning: Could not remove wrapper script: {}", e);
     }
 
     // Remove icon
     if let Err(e) = remove_icon(app_name) {
-        println!("[voidbox] Warning: Could not remove icon: {}", e);
+        say!("[voidbox] Warning: Could not remove icon: {}", e);
     }
 
+    // Remove window-rule hints and the window class last seen
+    if let Err(e) = remove_window_rules(app_name) {
+        say!(
+            "[voidbox] Warning: Could not remove window-rule hints: {}",
+            e
+        );
+    }
+    fs::remove_file(paths::app_window_class_path(app_name)).ok();
+
     // Remove manifest
     if manifest_path.exists() {
         fs::remove_file(&manifest_path)?;
     }
 
     // Remove settings
-    if let Err(e) = remove_overrides(app_name) {
-        println!("[voidbox] Warning: Could not remove settings: {}", e);
+    if let Err(e) = remove_overrides(app_name).and_then(|()| remove_env_overrides(app_name)) {
+        say!("[voidbox] Warning: Could not remove settings: {}", e);
     }
 
     if purge {
         // Remove entire app directory (including data)
         if app_dir.exists() {
-            println!("[voidbox] Removing app data (this may take a moment)...");
+            say!("[voidbox] Removing app data (this may take a moment)...");
             fs::remove_dir_all(&app_dir)?;
         }
     } else {
         // Just remove rootfs but keep any app data
         let rootfs = paths::app_rootfs_dir(app_name);
         if rootfs.exists() {
-            println!("[voidbox] Removing rootfs...");
+            say!("[voidbox] Removing rootfs...");
             fs::remove_dir_all(&rootfs)?;
         }
-        println!("[voidbox] Note: App data kept in {}", app_dir.display());
-        println!("[voidbox] Use --purge to remove everything.");
+        // Records describing the removed rootfs
+        save_excluded(&rootfs, &ExcludedFiles::default())?;
+        save_optimized(&rootfs, None)?;
+        save_installed_files(&rootfs, None)?;
+        say!("[voidbox] Note: App data kept in {}", app_dir.display());
+        say!("[voidbox] Use --purge to remove everything.");
     }
 
     // Update installed apps database
     remove_from_database(app_name)?;
 
-    println!("[voidbox] {} removed successfully!", app_name);
+    say!("[voidbox] {} removed successfully!", app_name);
+    if keep.is_some() {
+        say!("[voidbox] Undo with: voidbox undo-remove {}", app_name);
+    }
 
     Ok(())
 }
@@ -91,7 +209,7 @@ fn remove_from_database(app_name: &str) -> Result<(), RemoveError> {
     let mut apps: Vec<InstalledApp> = serde_json::from_str(&content)
         .map_err(|e| RemoveError::Failed(format!("Failed to parse database: {}", e)))?;
 
-    apps.retain(|a| a.name != app_name);
+    apps.retain(|a| !paths::same_app(&a.name, app_name));
 
     let content = serde_json::to_string_pretty(&apps)
         .map_err(|e| RemoveError::Failed(format!("Failed to serialize: {}", e)))?;
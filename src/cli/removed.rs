@@ -0,0 +1,281 @@
+//! Recently removed apps
+//!
+//! `voidbox remove` doesn't delete an app's files right away: its rootfs
+//! (or with `--purge` its whole directory), manifest, settings and install
+//! record are moved to `removed/` in the data directory and kept for
+//! `[removal] keep` (7 days by default). `voidbox undo-remove <app>` puts
+//! the latest removal of an app back and adds it to the desktop again;
+//! without an app it lists what can be restored. Expired removals are
+//! deleted by the next removal, `voidbox clean` and `voidbox maintain`.
+
+use crate::cli::install::integrate_installed;
+use crate::cli::remove::RemoveError;
+use crate::desktop::{KeepIntegration, refresh_batch};
+use crate::events::{EventKind, fire_event};
+use crate::manifest::{InstalledApp, parse_manifest_file};
+use crate::settings::load_config;
+use crate::storage::{parse_age, paths};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What a removal kept, saved as `removed.json` in its directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovedApp {
+    pub app: String,
+    pub display_name: String,
+    /// When it was removed (RFC 3339)
+    pub removed: String,
+    /// Whether the app's data was removed too
+    pub purged: bool,
+    /// Install record, put back on undo
+    pub record: Option<InstalledApp>,
+}
+
+const INFO_FILE: &str = "removed.json";
+const MANIFEST_FILE: &str = "manifest.toml";
+const SETTINGS_FILE: &str = "settings.toml";
+const ENV_FILE: &str = "env.toml";
+
+/// Records kept next to an app's rootfs that describe it
+const ROOTFS_RECORDS: &[&str] = &["excluded.json", "optimized.json"];
+
+/// How long removed apps are kept; zero keeps none
+pub fn removal_keep() -> Duration {
+    let keep = load_config().unwrap_or_default().removal.keep;
+    parse_age(&keep).unwrap_or_else(|| {
+        say!(
+            "[voidbox] Warning: Invalid removal.keep '{}' (expected e.g. 7d); keeping removed apps 7d",
+            keep
+        );
+        Duration::from_secs(7 * 24 * 60 * 60)
+    })
+}
+
+/// Move what removing an app deletes into a new entry of the removed
+/// apps, returning its directory
+///
+/// The manifest and settings are copied and left for the removal to
+/// delete; the rootfs, or with `purge` the app's directory, is moved.
+pub(crate) fn keep_removed(app_name: &str, purge: bool) -> io::Result<PathBuf> {
+    let record = crate::cli::get_installed_apps()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|a| paths::same_app(&a.name, app_name));
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let dir = paths::removed_dir().join(format!("{}-{}", paths::app_slug(app_name), stamp));
+    fs::create_dir_all(&dir)?;
+
+    let result = (|| {
+        let info = RemovedApp {
+            app: app_name.to_string(),
+            display_name: record
+                .as_ref()
+                .map(|r| r.display_name.clone())
+                .unwrap_or_else(|| app_name.to_string()),
+            removed: chrono::Local::now().to_rfc3339(),
+            purged: purge,
+            record,
+        };
+        let json = serde_json::to_string_pretty(&info).map_err(io::Error::other)?;
+        fs::write(dir.join(INFO_FILE), json)?;
+
+        for (from, to) in [
+            (paths::manifest_path(app_name), MANIFEST_FILE),
+            (paths::app_settings_path(app_name), SETTINGS_FILE),
+            (paths::app_env_path(app_name), ENV_FILE),
+        ] {
+            if from.exists() {
+                fs::copy(&from, dir.join(to))?;
+            }
+        }
+
+        let app_dir = paths::app_dir(app_name);
+        if purge {
+            if app_dir.exists() {
+                fs::rename(&app_dir, dir.join("app"))?;
+            }
+        } else {
+            for record in ROOTFS_RECORDS {
+                let path = app_dir.join(record);
+                if path.exists() {
+                    fs::rename(&path, dir.join(record))?;
+                }
+            }
+            let rootfs = paths::app_rootfs_dir(app_name);
+            if rootfs.exists() {
+                fs::rename(&rootfs, dir.join("rootfs"))?;
+            }
+        }
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        restore_files(app_name, &dir).ok();
+        fs::remove_dir_all(&dir).ok();
+        return Err(e);
+    }
+    Ok(dir)
+}
+
+/// Read the removed apps, newest first
+pub fn removed_apps() -> Vec<(PathBuf, RemovedApp)> {
+    let mut removed: Vec<(PathBuf, RemovedApp)> = fs::read_dir(paths::removed_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let dir = entry.path();
+            let content = fs::read_to_string(dir.join(INFO_FILE)).ok()?;
+            let info = serde_json::from_str(&content).ok()?;
+            Some((dir, info))
+        })
+        .collect();
+    removed.sort_by(|a, b| b.1.removed.cmp(&a.1.removed));
+    removed
+}
+
+/// Delete removed apps kept longer than `keep`, returning their names
+pub fn prune_removed(keep: Duration) -> io::Result<Vec<String>> {
+    let mut pruned = Vec::new();
+    for (dir, info) in removed_apps() {
+        let expired = chrono::DateTime::parse_from_rfc3339(&info.removed)
+            .ok()
+            .and_then(|removed| {
+                (chrono::Local::now().fixed_offset() - removed)
+                    .to_std()
+                    .ok()
+            })
+            .is_none_or(|age| age >= keep);
+        if expired {
+            fs::remove_dir_all(&dir)?;
+            pruned.push(info.app);
+        }
+    }
+    Ok(pruned)
+}
+
+/// Put an app's latest removal back, or list the removed apps without one
+pub fn undo_remove(app_name: Option<&str>) -> Result<(), RemoveError> {
+    let removed = removed_apps();
+    let Some(app_name) = app_name else {
+        if removed.is_empty() {
+            say!("[voidbox] No recently removed apps");
+        }
+        for (_, info) in &removed {
+            say!(
+                "  {} ({}), removed {}{}",
+                info.display_name,
+                info.app,
+                info.removed,
+                if info.purged { " with its data" } else { "" }
+            );
+        }
+        return Ok(());
+    };
+
+    let Some((dir, info)) = removed
+        .into_iter()
+        .find(|(_, info)| paths::same_app(&info.app, app_name))
+    else {
+        return Err(RemoveError::Failed(format!(
+            "{} wasn't removed recently, or was kept too long ago",
+            app_name
+        )));
+    };
+    let app_name = info.app.as_str();
+    if paths::manifest_path(app_name).exists() || paths::app_rootfs_dir(app_name).exists() {
+        return Err(RemoveError::Failed(format!(
+            "{} is installed again, so its removal can't be undone",
+            app_name
+        )));
+    }
+    if info.purged && paths::app_dir(app_name).exists() {
+        return Err(RemoveError::Failed(format!(
+            "{} has new data in {}; move it away to restore the removed app",
+            app_name,
+            paths::app_dir(app_name).display()
+        )));
+    }
+
+    say!("[voidbox] Restoring {}...", info.display_name);
+    restore_files(app_name, &dir)?;
+    if let Some(record) = info.record {
+        restore_record(record)?;
+    }
+
+    let manifest_path = paths::manifest_path(app_name);
+    if manifest_path.exists() {
+        let manifest =
+            parse_manifest_file(&manifest_path).map_err(|e| RemoveError::Failed(e.to_string()))?;
+        let _refresh = refresh_batch();
+        integrate_installed(&manifest, KeepIntegration::default())
+            .map_err(|e| RemoveError::Failed(e.to_string()))?;
+    }
+    fs::remove_dir_all(&dir)?;
+
+    fire_event(
+        EventKind::Install,
+        Some(app_name),
+        None,
+        &format!("{} restored", info.display_name),
+    );
+    say!("[voidbox] Restored {}", info.display_name);
+    Ok(())
+}
+
+/// Move a removal's files back where they came from
+fn restore_files(app_name: &str, dir: &Path) -> io::Result<()> {
+    let app_dir = paths::app_dir(app_name);
+    if dir.join("app").exists() {
+        if let Some(parent) = app_dir.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(dir.join("app"), &app_dir)?;
+    }
+    if dir.join("rootfs").exists() {
+        fs::create_dir_all(&app_dir)?;
+        fs::rename(dir.join("rootfs"), paths::app_rootfs_dir(app_name))?;
+    }
+    for record in ROOTFS_RECORDS {
+        if dir.join(record).exists() {
+            fs::rename(dir.join(record), app_dir.join(record))?;
+        }
+    }
+
+    for (from, to) in [
+        (MANIFEST_FILE, paths::manifest_path(app_name)),
+        (SETTINGS_FILE, paths::app_settings_path(app_name)),
+        (ENV_FILE, paths::app_env_path(app_name)),
+    ] {
+        let from = dir.join(from);
+        if from.exists() && !to.exists() {
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(&from, &to)?;
+        }
+    }
+    Ok(())
+}
+
+/// Add a removed app's install record back to the database
+fn restore_record(record: InstalledApp) -> Result<(), RemoveError> {
+    let db_path = paths::database_path();
+    let mut apps: Vec<InstalledApp> = fs::read_to_string(&db_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    apps.retain(|a| !paths::same_app(&a.name, &record.name));
+    apps.push(record);
+
+    let content = serde_json::to_string_pretty(&apps)
+        .map_err(|e| RemoveError::Failed(format!("Failed to serialize: {}", e)))?;
+    fs::write(&db_path, content)?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! Repair command implementation
+//!
+//! `voidbox repair <app>` rebuilds an app's rootfs from its saved manifest,
+//! for installs whose files were partly deleted or corrupted. The base
+//! image, packages and app are downloaded again (cached copies are dropped
+//! first, in case the damage came from them) and extracted into a fresh
+//! rootfs, which replaces the old one only once complete. The app's data,
+//! settings, records and kept versions are left alone, except the record of
+//! installed files, which then describes the new payload. An interrupted
+//! repair continues where it stopped the next time it is run.
+
+use crate::cli::install::{
+    InstallError, fetch_latest_ubuntu_base, integrate_installed, prepare_rootfs, resolve_download,
+    with_install_log,
+};
+use crate::cli::integrity::{load_installed_files, save_installed_files};
+use crate::cli::pipeline::InstallJournal;
+use crate::cli::stage::swap_dirs;
+use crate::cli::versions::manifest_at_version;
+use crate::desktop::{KeepIntegration, refresh_batch};
+use crate::events::{EventKind, fire_event};
+use crate::manifest::{AppManifest, SourceConfig, parse_manifest_file};
+use crate::runtime::is_app_running;
+use crate::storage::{paths, remove_cached};
+use std::fs;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RepairError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error("{0} is running; close it first")]
+    Running(String),
+
+    #[error("Could not find v{version} of {app} again: {reason}")]
+    VersionNotFound {
+        app: String,
+        version: String,
+        reason: String,
+    },
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] crate::manifest::ManifestError),
+
+    #[error("Install error: {0}")]
+    InstallError(#[from] InstallError),
+}
+
+/// Rebuild an app's rootfs from its saved manifest, keeping its data
+pub fn repair_app(app_name: &str) -> Result<(), RepairError> {
+    let manifest_path = paths::manifest_path(app_name);
+    let record = crate::cli::get_installed_apps()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|a| paths::same_app(&a.name, app_name));
+    let (Some(record), true) = (record, manifest_path.exists()) else {
+        return Err(RepairError::NotInstalled(app_name.to_string()));
+    };
+    if is_app_running(&record.name) {
+        return Err(RepairError::Running(record.name));
+    }
+
+    let saved = parse_manifest_file(&manifest_path)?;
+    let manifest = installed_release(&saved, record.version.as_deref())?;
+    let version = record.version.as_deref().unwrap_or("unknown");
+    say!(
+        "[voidbox] Repairing {} v{}...",
+        saved.app.display_name,
+        version
+    );
+
+    let repair_dir = paths::app_repair_dir(&record.name);
+    let rootfs = repair_dir.join("rootfs");
+    if repair_dir.exists() && InstallJournal::load(&rootfs, &manifest).is_none() {
+        fs::remove_dir_all(&repair_dir)?;
+    }
+    if !repair_dir.exists() {
+        drop_cached_downloads(&manifest);
+    }
+    fs::create_dir_all(&repair_dir)?;
+
+    let mut journal = InstallJournal::open(&rootfs, &manifest);
+    with_install_log(&record.name, "repair", || {
+        prepare_rootfs(&rootfs, &manifest, &mut journal)
+    })?;
+    journal.finish();
+
+    // The damaged rootfs ends up in the repair directory and is discarded
+    let live_rootfs = paths::app_rootfs_dir(&record.name);
+    if live_rootfs.exists() {
+        swap_dirs(&rootfs, &live_rootfs)?;
+    } else {
+        fs::rename(&rootfs, &live_rootfs)?;
+    }
+    // The record of installed files describes the rebuilt payload
+    save_installed_files(&live_rootfs, load_installed_files(&rootfs).as_ref())?;
+    fs::remove_dir_all(&repair_dir)?;
+
+    let _refresh = refresh_batch();
+    integrate_installed(&saved, KeepIntegration::default())?;
+
+    fire_event(
+        EventKind::Install,
+        Some(&record.name),
+        record.version.as_deref(),
+        &format!("{} repaired", saved.app.display_name),
+    );
+    say!(
+        "[voidbox] Repaired {} v{}; its data was kept",
+        saved.app.display_name,
+        version
+    );
+    Ok(())
+}
+
+/// The manifest pointed at the installed release, so a repair doesn't
+/// install a newer one
+fn installed_release(
+    manifest: &AppManifest,
+    version: Option<&str>,
+) -> Result<AppManifest, RepairError> {
+    match (&manifest.source, version) {
+        (SourceConfig::Github { tag: None, .. }, Some(version)) => {
+            manifest_at_version(&manifest.app.name, manifest, version).map_err(|e| {
+                RepairError::VersionNotFound {
+                    app: manifest.app.name.clone(),
+                    version: version.to_string(),
+                    reason: e.to_string(),
+                }
+            })
+        }
+        _ => Ok(manifest.clone()),
+    }
+}
+
+/// Drop the cached archives a repair would otherwise reuse
+fn drop_cached_downloads(manifest: &AppManifest) {
+    if let Ok((_, url)) = fetch_latest_ubuntu_base() {
+        remove_cached(&url);
+    }
+    if let Ok(Some((_, url))) = resolve_download(manifest) {
+        remove_cached(&url);
+    }
+}
@@ -0,0 +1,132 @@
+//! Repo command implementation
+//!
+//! Adds and removes the repository indexes `voidbox install <name>`
+//! searches. A location is a directory, an index file, an index URL or a
+//! GitHub tap (`gh:user/repo`). Remote indexes are fetched again once
+//! their `[repository_refresh]` interval has passed, or on `voidbox repo
+//! refresh`. Also refreshes the checksum database that repositories and
+//! checksum sources publish.
+
+use crate::manifest::{
+    RepoIndex, index_is_fresh, load_index_cached, refresh_checksum_db, refresh_index, tap_url,
+};
+use crate::settings::{load_config, save_config};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RepoError {
+    #[error("Repository already added: {0}")]
+    AlreadyAdded(String),
+
+    #[error("Repository not configured: {0}")]
+    NotConfigured(String),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] crate::manifest::ManifestError),
+
+    #[error("Settings error: {0}")]
+    SettingsError(#[from] crate::settings::SettingsError),
+}
+
+/// Location as stored in config: local paths are made absolute
+fn normalize_location(location: &str) -> String {
+    let is_remote = tap_url(location).is_some()
+        || location.starts_with("http://")
+        || location.starts_with("https://");
+    if is_remote {
+        return location.trim_end_matches('/').to_string();
+    }
+    Path::new(location)
+        .canonicalize()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| location.to_string())
+}
+
+/// Add a repository after checking its index loads
+pub fn add_repository(location: &str) -> Result<(), RepoError> {
+    let location = normalize_location(location);
+    let mut config = load_config()?;
+    if config.repositories.contains(&location) {
+        return Err(RepoError::AlreadyAdded(location));
+    }
+
+    let index = refresh_index(&location)?;
+    config.repositories.push(location.clone());
+    save_config(&config)?;
+
+    say!("[voidbox] Added {} ({} app(s))", location, index.apps.len());
+    if location.starts_with(crate::manifest::TAP_PREFIX) {
+        say!(
+            "[voidbox] Install its apps with: voidbox install {}/<app>",
+            location
+        );
+    }
+    Ok(())
+}
+
+/// A repository's index, fetched again once its refresh interval has passed
+pub fn repository_index(location: &str) -> Result<RepoIndex, RepoError> {
+    let interval = load_config()?.repository_refresh.interval(location)?;
+    Ok(load_index_cached(location, interval)?)
+}
+
+/// Fetch the indexes of configured repositories, all of them or only those
+/// due for a refresh; returns the number that failed
+pub fn refresh_repositories(force: bool) -> Result<usize, RepoError> {
+    let config = load_config()?;
+    let mut failed = 0;
+    for location in &config.repositories {
+        let interval = config.repository_refresh.interval(location)?;
+        if !force && index_is_fresh(location, interval) {
+            continue;
+        }
+        match refresh_index(location) {
+            Ok(index) => say!("  {}: {} app(s)", location, index.apps.len()),
+            Err(e) => {
+                say!("[voidbox] Warning: Could not refresh {}: {}", location, e);
+                failed += 1;
+            }
+        }
+    }
+    Ok(failed)
+}
+
+/// Refresh the known-good checksum database; returns the number of entries
+pub fn refresh_checksums() -> Result<usize, RepoError> {
+    let config = load_config()?;
+    let (db, failed) = refresh_checksum_db(
+        &config.checksums.sources,
+        &config.repositories,
+        &config.checksums.keys,
+    )?;
+    for (location, error) in &failed {
+        say!(
+            "[voidbox] Warning: Could not fetch checksums from {}: {}",
+            location,
+            error
+        );
+    }
+    say!(
+        "[voidbox] Checksum database has {} artifact(s)",
+        db.artifacts.len()
+    );
+    Ok(db.artifacts.len())
+}
+
+/// Remove a repository from config
+pub fn remove_repository(location: &str) -> Result<(), RepoError> {
+    let normalized = normalize_location(location);
+    let mut config = load_config()?;
+    let before = config.repositories.len();
+    config
+        .repositories
+        .retain(|r| r != location && *r != normalized);
+    if config.repositories.len() == before {
+        return Err(RepoError::NotConfigured(location.to_string()));
+    }
+
+    save_config(&config)?;
+    say!("[voidbox] Removed {}", normalized);
+    Ok(())
+}
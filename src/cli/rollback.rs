@@ -0,0 +1,289 @@
+//! Rollback command implementation
+//!
+//! Updates keep the version they replace in the app's `previous`
+//! directory: its payload (the `/opt` directory the app's files are in),
+//! manifest and the records of the files its install excluded and
+//! optimized. `voidbox rollback` puts that version back and pins the app
+//! there, so the next update doesn't bring back the version that broke it.
+//! The version rolled back from is kept in turn, so a second rollback
+//! undoes the first. Only the most recently replaced version is kept.
+
+use crate::cli::exclude::{load_excluded, save_excluded};
+use crate::cli::install::{
+    InstallError, create_binary_symlink, integrate_installed, save_installed_app, update_record,
+};
+use crate::cli::integrity::{load_installed_files, save_installed_files};
+use crate::cli::optimize::{load_optimized, save_optimized};
+use crate::cli::payload::payload_dir;
+use crate::cli::pipeline::{discard_interrupted_install, has_interrupted_install};
+use crate::desktop::{KeepIntegration, refresh_batch};
+use crate::events::{EventKind, fire_event};
+use crate::manifest::{AppManifest, parse_manifest_file};
+use crate::runtime::is_app_running;
+use crate::storage::paths;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RollbackError {
+    #[error("App not installed: {0}")]
+    NotInstalled(String),
+
+    #[error("{0} has no previous version to roll back to")]
+    NoPrevious(String),
+
+    #[error("{0} is running; close it first")]
+    Running(String),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] crate::manifest::ManifestError),
+
+    #[error("Install error: {0}")]
+    InstallError(#[from] InstallError),
+}
+
+/// Metadata written once a replaced version has been kept
+#[derive(Debug, Serialize, Deserialize)]
+struct PreviousInfo {
+    version: Option<String>,
+    replaced_date: String,
+}
+
+fn info_path(previous_dir: &Path) -> std::path::PathBuf {
+    previous_dir.join("previous.json")
+}
+
+fn load_info(previous_dir: &Path) -> Option<PreviousInfo> {
+    let content = fs::read_to_string(info_path(previous_dir)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Version the installed apps database records for an app
+fn installed_version(app_name: &str) -> Option<String> {
+    crate::cli::get_installed_apps()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|a| paths::same_app(&a.name, app_name))
+        .and_then(|a| a.version)
+}
+
+/// Copy a directory tree, keeping symlinks and permissions
+fn copy_tree(src: &Path, dst: &Path) -> io::Result<()> {
+    // Directories come after their contents, so read-only ones are still
+    // writable while being filled
+    for entry in walkdir::WalkDir::new(src).contents_first(true) {
+        let entry = entry.map_err(io::Error::other)?;
+        let relative = entry.path().strip_prefix(src).map_err(io::Error::other)?;
+        let target = dst.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            fs::create_dir_all(&target)?;
+            fs::set_permissions(&target, entry.metadata()?.permissions())?;
+        } else if file_type.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Keep an installed version's payload, manifest and records in `dest`
+///
+/// The payload is taken from `rootfs`: moved out of it when `take`, copied
+/// otherwise. The records always describe the live install.
+fn keep_version(
+    app_name: &str,
+    manifest: &AppManifest,
+    rootfs: &Path,
+    dest: &Path,
+    take: bool,
+) -> io::Result<()> {
+    let payload = payload_dir(manifest);
+    let source = rootfs.join(&payload);
+    if !source.exists() {
+        return Ok(());
+    }
+
+    if dest.exists() {
+        fs::remove_dir_all(dest)?;
+    }
+    let kept_rootfs = dest.join("rootfs");
+    let kept = kept_rootfs.join(&payload);
+    if let Some(parent) = kept.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if take {
+        fs::rename(&source, &kept)?;
+    } else {
+        copy_tree(&source, &kept)?;
+    }
+
+    let live_rootfs = paths::app_rootfs_dir(app_name);
+    save_excluded(
+        &kept_rootfs,
+        &load_excluded(&live_rootfs).unwrap_or_default(),
+    )?;
+    save_optimized(&kept_rootfs, load_optimized(&live_rootfs).as_ref())?;
+    save_installed_files(&kept_rootfs, load_installed_files(&live_rootfs).as_ref())?;
+    fs::copy(paths::manifest_path(app_name), dest.join("manifest.toml"))?;
+
+    // Written last: its presence marks the kept version as complete
+    let info = PreviousInfo {
+        version: installed_version(app_name).or_else(|| manifest.app.version.clone()),
+        replaced_date: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    let content = serde_json::to_string_pretty(&info).map_err(io::Error::other)?;
+    fs::write(info_path(dest), content)
+}
+
+/// The installed version, unless its files are from an interrupted install
+/// (whose first attempt already kept the version before it)
+fn complete_install(app_name: &str) -> Option<AppManifest> {
+    if has_interrupted_install(app_name) {
+        return None;
+    }
+    parse_manifest_file(&paths::manifest_path(app_name)).ok()
+}
+
+/// Keep a copy of the installed version before an update overwrites it
+pub(crate) fn keep_previous(app_name: &str) -> io::Result<()> {
+    let Some(manifest) = complete_install(app_name) else {
+        return Ok(());
+    };
+    keep_version(
+        app_name,
+        &manifest,
+        &paths::app_rootfs_dir(app_name),
+        &paths::app_previous_dir(app_name),
+        false,
+    )
+}
+
+/// Keep the installed version from a rootfs swapped out by a staged update
+///
+/// Call before the new version's manifest and records replace the old.
+pub(crate) fn keep_replaced(app_name: &str, old_rootfs: &Path) -> io::Result<()> {
+    let Some(manifest) = complete_install(app_name) else {
+        return Ok(());
+    };
+    keep_version(
+        app_name,
+        &manifest,
+        old_rootfs,
+        &paths::app_previous_dir(app_name),
+        true,
+    )
+}
+
+/// Version an app would roll back to
+pub fn previous_version(app_name: &str) -> Option<Option<String>> {
+    load_info(&paths::app_previous_dir(app_name)).map(|info| info.version)
+}
+
+/// Put back the version the last update replaced
+pub fn rollback_app(app_name: &str) -> Result<(), RollbackError> {
+    let current_version = installed_version(app_name);
+    let (previous, version) = restore_previous(app_name, true)?;
+    update_record(app_name, |app| app.pinned = true)?;
+
+    let version = version.as_deref().unwrap_or("unknown");
+    fire_event(
+        EventKind::Update,
+        Some(app_name),
+        Some(version),
+        &format!("{} rolled back", previous.app.display_name),
+    );
+    say!(
+        "[voidbox] Rolled back {} from v{} to v{}",
+        previous.app.display_name,
+        current_version.as_deref().unwrap_or("unknown"),
+        version
+    );
+    say!(
+        "[voidbox] {} is pinned at v{}; updates skip it until 'voidbox unpin {}'",
+        previous.app.display_name,
+        version,
+        app_name
+    );
+    Ok(())
+}
+
+/// Put the kept previous version in place of the installed one, returning
+/// its manifest and version
+///
+/// With `keep_current` the installed version is kept in turn, so that
+/// rolling back again undoes this; otherwise it is discarded.
+pub(crate) fn restore_previous(
+    app_name: &str,
+    keep_current: bool,
+) -> Result<(AppManifest, Option<String>), RollbackError> {
+    let manifest_path = paths::manifest_path(app_name);
+    if !manifest_path.exists() {
+        return Err(RollbackError::NotInstalled(app_name.to_string()));
+    }
+    let previous_dir = paths::app_previous_dir(app_name);
+    let info =
+        load_info(&previous_dir).ok_or_else(|| RollbackError::NoPrevious(app_name.to_string()))?;
+    if is_app_running(app_name) {
+        return Err(RollbackError::Running(app_name.to_string()));
+    }
+
+    let previous = parse_manifest_file(&previous_dir.join("manifest.toml"))?;
+    let live_rootfs = paths::app_rootfs_dir(app_name);
+
+    // The version rolled back from is kept in turn when asked; otherwise,
+    // and when it is what an interrupted update left behind, it is removed
+    // (and the update isn't continued)
+    let next_dir = previous_dir.with_extension("next");
+    match complete_install(app_name).filter(|_| keep_current) {
+        Some(current) => keep_version(app_name, &current, &live_rootfs, &next_dir, true)?,
+        None => {
+            if let Ok(current) = parse_manifest_file(&manifest_path) {
+                let partial = live_rootfs.join(payload_dir(&current));
+                if partial.exists() {
+                    fs::remove_dir_all(partial)?;
+                }
+            }
+            discard_interrupted_install(app_name)?;
+        }
+    }
+
+    let kept_rootfs = previous_dir.join("rootfs");
+    let payload = payload_dir(&previous);
+    let target = live_rootfs.join(&payload);
+    if target.exists() {
+        fs::remove_dir_all(&target)?;
+    }
+    fs::rename(kept_rootfs.join(&payload), &target)?;
+    save_excluded(
+        &live_rootfs,
+        &load_excluded(&kept_rootfs).unwrap_or_default(),
+    )?;
+    save_optimized(&live_rootfs, load_optimized(&kept_rootfs).as_ref())?;
+    save_installed_files(&live_rootfs, load_installed_files(&kept_rootfs).as_ref())?;
+    create_binary_symlink(&live_rootfs, &previous)?;
+
+    fs::copy(previous_dir.join("manifest.toml"), &manifest_path)?;
+    save_installed_app(&previous, info.version.as_deref())?;
+
+    fs::remove_dir_all(&previous_dir)?;
+    if next_dir.exists() {
+        fs::rename(&next_dir, &previous_dir)?;
+    }
+
+    // Refresh desktop integration for the restored version
+    let _refresh = refresh_batch();
+    integrate_installed(&previous, KeepIntegration::default())?;
+
+    Ok((previous, info.version))
+}
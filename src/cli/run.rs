@@ -1,12 +1,30 @@
 //! Run command implementation
 
-use crate::manifest::{AppManifest, PermissionConfig, parse_manifest_file};
-use crate::runtime::{setup_container_namespaces, setup_user_namespace, spawn_container_init};
-use crate::settings::{load_overrides, merge_permissions};
-use crate::storage::paths;
+use crate::cli::crash::{QUICK_EXIT, launch_log_position, report_failed_launch};
+use crate::cli::payload::{app_home, container_setup, find_entrypoint, review_self_update};
+use crate::cli::quarantine::{is_quarantined, review_quarantine, snapshot_rootfs};
+use crate::cli::stage::{apply_staged_update, has_staged_update};
+use crate::desktop::activate_running_instance;
+use crate::gui::{Frontend, frontend};
+use crate::manifest::{AppManifest, ClipboardAccess, PermissionConfig, parse_manifest_file};
+use crate::runtime::{
+    ContainerSetup, FirstWindow, acquire_instance_lock, is_app_running, register_instance,
+    setup_container_namespaces, setup_user_namespace, start_container_init, start_dbus_proxy,
+    start_wayland_context, watch_windows,
+};
+use crate::settings::{
+    load_overrides, manifest_permissions, merge_permissions, strict_permissions,
+    write_live_permissions,
+};
+use crate::storage::{AppLog, append_app_log, paths, redirect_output};
+use std::fs;
 use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 
+/// How long a launch watches for the app's first window
+const WINDOW_WAIT: Duration = Duration::from_secs(120);
+
 #[derive(Error, Debug)]
 pub enum RunError {
     #[error("App not installed: {0}")]
@@ -24,6 +42,9 @@ pub enum RunError {
     #[error("Settings error: {0}")]
     SettingsError(#[from] crate::settings::SettingsError),
 
+    #[error("Lock error: {0}")]
+    LockError(#[from] crate::runtime::LockError),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -37,13 +58,24 @@ pub fn run_app(
     args: &[String],
     url: Option<&str>,
     dev_mode: bool,
+    profile_startup: bool,
 ) -> Result<(), RunError> {
+    let launched = Instant::now();
+
     // Check if app is installed
     let manifest_path = paths::manifest_path(app_name);
     if !manifest_path.exists() {
         return Err(RunError::NotInstalled(app_name.to_string()));
     }
 
+    // Switch to a staged update if no other instance is using the current one
+    if has_staged_update(app_name)
+        && !is_app_running(app_name)
+        && let Err(e) = apply_staged_update(app_name)
+    {
+        say!("[voidbox] Warning: Could not apply staged update: {}", e);
+    }
+
     let rootfs = paths::app_rootfs_dir(app_name);
     if !rootfs.exists() {
         return Err(RunError::NotInstalled(app_name.to_string()));
@@ -52,66 +84,318 @@ pub fn run_app(
     // Load manifest
     let manifest = parse_manifest_file(&manifest_path)?;
 
+    // Hand off to the running instance for single-instance apps
+    if manifest.binary.single_instance && is_app_running(app_name) {
+        let uris = instance_uris(url, args);
+        if activate_running_instance(&manifest, &uris) {
+            say!(
+                "[voidbox] {} is already running, activated it.",
+                manifest.app.display_name
+            );
+        } else {
+            say!(
+                "[voidbox] {} is already running.",
+                manifest.app.display_name
+            );
+        }
+        return Ok(());
+    }
+
+    // Held until the app exits so updates can detect the running instance
+    let _instance = acquire_instance_lock(app_name)?;
+
+    // Started from the desktop, nothing would see the app's output, so it
+    // goes to the app's launch log instead
+    let desktop_launch = frontend() == Frontend::Gui;
+    let log_position = launch_log_position(app_name);
+    if frontend() == Frontend::Terminal {
+        append_app_log(
+            app_name,
+            AppLog::Launch,
+            "started from a terminal (output not captured)",
+        );
+    } else {
+        append_app_log(app_name, AppLog::Launch, "started");
+        if let Err(e) = redirect_output(app_name, AppLog::Launch) {
+            eprintln!("[voidbox] Warning: Could not write the launch log: {}", e);
+        }
+    }
+
     // Get permissions (manifest defaults + user overrides)
-    let mut permissions = manifest.permissions.clone();
+    let mut permissions = manifest_permissions(&manifest);
     if let Some(overrides) = load_overrides(app_name)? {
-        permissions = merge_permissions(&manifest.permissions, Some(&overrides));
+        permissions = merge_permissions(&permissions, Some(&overrides));
     }
 
     // Override dev_mode if specified on command line
     if dev_mode {
         permissions.dev_mode = true;
     }
+    let resolved = Instant::now();
+
+    // Payload read-only, home in the app's data directory
+    let mut setup = container_setup(&manifest, &rootfs)?;
+
+    // First run of a quarantined app: strict profile, audited afterwards
+    let requested = permissions.clone();
+    let audit = is_quarantined(app_name).then(|| {
+        say!(
+            "[voidbox] {} is quarantined: running with the strict sandbox profile",
+            manifest.app.display_name
+        );
+        permissions = strict_permissions();
+        snapshot_rootfs(&rootfs, &paths::app_data_dir(app_name))
+    });
+
+    // Session bus filtered to the names the app's permissions allow; the
+    // proxy runs until the app exits
+    let dbus_proxy = manifest
+        .dbus_proxy_names(&permissions)
+        .map(|(talk, own)| start_dbus_proxy(app_name, &talk, &own));
+    match &dbus_proxy {
+        Some(Some(proxy)) => setup.dbus_proxy = Some(proxy.socket().to_path_buf()),
+        // A filtered bus never falls back to the full one
+        Some(None) if std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some() => {
+            return Err(RunError::Failed(format!(
+                "xdg-dbus-proxy could not be started to filter the session bus \
+                 of {} (is it installed?)",
+                manifest.app.display_name
+            )));
+        }
+        Some(None) => {}
+        None => setup.host_bus = true,
+    }
+
+    // Limited clipboard: no X11, and a compositor connection marked as
+    // sandboxed, which withholds the background clipboard protocol
+    let wayland_context = (permissions.clipboard < ClipboardAccess::Write)
+        .then(|| start_wayland_context(app_name, &manifest.desktop_id()))
+        .flatten();
+    match &wayland_context {
+        Some(context) => setup.wayland_socket = Some(context.socket().to_path_buf()),
+        None if std::env::var_os("WAYLAND_DISPLAY").is_none() => {}
+        // No clipboard at all can't be promised over a plain connection
+        None if permissions.clipboard == ClipboardAccess::None => {
+            return Err(RunError::Failed(format!(
+                "{} has no clipboard access, but the compositor doesn't support \
+                 sandboxed connections",
+                manifest.app.display_name
+            )));
+        }
+        None if permissions.clipboard == ClipboardAccess::Read => say!(
+            "[voidbox] Warning: The compositor doesn't support sandboxed connections; \
+             clipboard access of {} is not restricted",
+            manifest.app.display_name
+        ),
+        None => {}
+    }
+
+    // Camera and microphone can be toggled while the app runs
+    match write_live_permissions(app_name, &permissions) {
+        Ok(()) => setup.live_dir = Some(paths::app_live_dir(app_name)),
+        Err(e) => say!(
+            "[voidbox] Warning: Could not publish live permissions: {}",
+            e
+        ),
+    }
 
     // Build command and args
     let (cmd, cmd_args) = build_command(&manifest, args, url, &rootfs)?;
 
+    // Shown in the task monitor; the launcher moves into the instance's
+    // cgroup here so the container is created in it
+    let _registration = match register_instance(
+        app_name,
+        &sandbox_status(&permissions, audit.is_some(), &setup),
+    ) {
+        Ok(guard) => Some(guard),
+        Err(e) => {
+            say!("[voidbox] Warning: Could not register the instance: {}", e);
+            None
+        }
+    };
+
     // Setup namespaces
     setup_user_namespace()?;
     setup_container_namespaces()?;
+    let sandboxed = Instant::now();
+
+    // Connected before the app starts so its first window isn't missed;
+    // not counted in the profile
+    let watcher = watch_windows();
+    let watched = watcher.is_some();
 
     // Spawn container init process with permissions
     let self_exe = std::env::current_exe()?;
-    let status = spawn_container_init(&self_exe, &rootfs, &cmd, &cmd_args, &permissions)?;
+    let started = SystemTime::now();
+    let spawned = Instant::now();
+    let mut child =
+        start_container_init(&self_exe, &rootfs, &cmd, &cmd_args, &permissions, &setup)?;
+
+    // The first window's class is kept for `voidbox doctor <app>`, which
+    // checks it against the desktop entry
+    let timeout = (!profile_startup).then_some(WINDOW_WAIT);
+    let window = watcher.and_then(|watcher| watcher.wait_for_window(&mut child, timeout));
+    if let Some(class) = window.as_ref().and_then(|w| w.app_id.as_deref()) {
+        record_window_class(app_name, class);
+    }
+    if profile_startup {
+        let window = match window {
+            Some(window) => Ok(window),
+            None if watched => Err("no window seen before the app exited"),
+            None => Err("not measured (needs a Wayland compositor that lists windows)"),
+        };
+        print_startup_profile(
+            &manifest.app.display_name,
+            &[
+                ("Spec resolution", resolved - launched),
+                ("Sandbox setup", sandboxed - resolved),
+            ],
+            spawned,
+            window,
+        );
+    }
+    let status = child.wait()?;
+
+    if let Some(before) = audit {
+        review_quarantine(&manifest, &requested, &rootfs, &before);
+    }
+    if let Some(home) = app_home(app_name, &permissions) {
+        review_self_update(&manifest, &home, started);
+    }
 
+    append_app_log(app_name, AppLog::Launch, &format!("exited ({})", status));
     if !status.success() {
+        // A desktop launch that fails at once would otherwise look like
+        // nothing happened
+        if desktop_launch && spawned.elapsed() < QUICK_EXIT {
+            drop(_registration);
+            drop(_instance);
+            report_failed_launch(&manifest, log_position, status);
+        }
         std::process::exit(status.code().unwrap_or(1));
     }
 
     Ok(())
 }
 
-/// Build the command and arguments to run
-fn build_command(
-    manifest: &AppManifest,
-    args: &[String],
-    url: Option<&str>,
-    rootfs: &Path,
-) -> Result<(String, Vec<String>), RunError> {
-    if !args.is_empty() {
-        // Custom command specified
-        return Ok((args[0].clone(), args[1..].to_vec()));
+/// Note the class of an app's first window, if it changed
+fn record_window_class(app_name: &str, class: &str) {
+    let path = paths::app_window_class_path(app_name);
+    if fs::read_to_string(&path).ok().as_deref() != Some(class)
+        && let Err(e) = fs::write(&path, class)
+    {
+        say!(
+            "[voidbox] Warning: Could not record the window class: {}",
+            e
+        );
     }
+}
 
-    // Default app command
-    let binary_name = &manifest.binary.name;
-
-    // Resolve the actual binary path by reading the symlink created during install
-    // This is required for native_mode where /usr/bin is masked by the host
-    let symlink_path = rootfs.join("usr/bin").join(binary_name);
+/// Print how long each phase of a launch took
+fn print_startup_profile(
+    display_name: &str,
+    phases: &[(&str, Duration)],
+    spawned: Instant,
+    window: Result<FirstWindow, &str>,
+) {
+    let ms = |d: Duration| format!("{:>6} ms", d.as_millis());
+    let overhead: Duration = phases.iter().map(|(_, d)| *d).sum();
 
-    // Use symlink_metadata to check existence of the link itself, not the target
-    // (since target is absolute path inside container, it won't exist on host)
-    let cmd = if std::fs::symlink_metadata(&symlink_path).is_ok() {
-        match std::fs::read_link(&symlink_path) {
-            Ok(target) => target.to_string_lossy().into_owned(),
-            Err(_) => format!("/usr/bin/{}", binary_name),
+    say!("[voidbox] Startup profile of {}:", display_name);
+    for (phase, duration) in phases {
+        say!("  {:<18} {}", phase, ms(*duration));
+    }
+    say!("  {:<18} {}", "Wrapper overhead", ms(overhead));
+    match window {
+        Ok(window) => {
+            let until_window = window.opened.saturating_duration_since(spawned);
+            say!(
+                "  {:<18} {}  (app ID {})",
+                "Until first window",
+                ms(until_window),
+                window.app_id.as_deref().unwrap_or("unknown")
+            );
+            say!("  {:<18} {}", "Total", ms(overhead + until_window));
         }
+        Err(reason) => say!("  {:<18} {}", "First window", reason),
+    }
+}
+
+/// Sandbox status of an instance, as shown in the task monitor
+fn sandbox_status(
+    permissions: &PermissionConfig,
+    quarantined: bool,
+    setup: &ContainerSetup,
+) -> String {
+    let mut status = if quarantined {
+        "quarantined".to_string()
+    } else if permissions.native_mode {
+        "native mode".to_string()
     } else {
-        format!("/usr/bin/{}", binary_name)
+        "sandboxed".to_string()
     };
+    if setup.dbus_proxy.is_some() {
+        status.push_str(", bus filtered");
+    }
+    if setup.wayland_socket.is_some() {
+        status.push_str(", clipboard restricted");
+    }
+    status
+}
+
+/// What a running instance is asked to open: the URL and any arguments
+/// that are URLs or existing files, as URIs
+///
+/// Other arguments can't be handed to a running instance and are dropped
+/// with a warning.
+fn instance_uris(url: Option<&str>, args: &[String]) -> Vec<String> {
+    let mut uris: Vec<String> = url.map(String::from).into_iter().collect();
+    for arg in args {
+        if arg.contains("://") {
+            uris.push(arg.clone());
+        } else if let Some(uri) = Path::new(arg)
+            .canonicalize()
+            .ok()
+            .and_then(|path| url::Url::from_file_path(path).ok())
+        {
+            // Percent-encoded, so spaces, '#', '?' and non-UTF-8 bytes
+            // survive
+            uris.push(uri.into());
+        } else {
+            say!(
+                "[voidbox] Warning: '{}' can't be passed to the running instance",
+                arg
+            );
+        }
+    }
+    uris
+}
+
+/// Build the command and arguments to run: the app's binary with the
+/// manifest's arguments, then the ones given at launch
+fn build_command(
+    manifest: &AppManifest,
+    args: &[String],
+    url: Option<&str>,
+    rootfs: &Path,
+) -> Result<(String, Vec<String>), RunError> {
+    // Found in the read-only payload rather than through the /usr/bin link
+    // install creates, which lies outside it and which the app could point
+    // elsewhere; the host's /usr/bin also hides the link in native mode
+    let binary = find_entrypoint(rootfs, manifest).ok_or_else(|| {
+        RunError::Failed(format!(
+            "'{}' is missing from the installed files; run 'voidbox repair {}'",
+            manifest.binary.name, manifest.app.name
+        ))
+    })?;
+    let cmd = Path::new("/")
+        .join(binary.strip_prefix(rootfs).unwrap_or(&binary))
+        .to_string_lossy()
+        .into_owned();
 
     let mut cmd_args: Vec<String> = manifest.binary.args.clone();
+    cmd_args.extend_from_slice(args);
 
     // Add URL if specified (for browsers)
     if let Some(u) = url {
@@ -130,7 +414,46 @@ pub fn internal_init(
 ) -> Result<(), RunError> {
     use crate::runtime::init_and_exec;
 
-    init_and_exec(rootfs, cmd, args, permissions)?;
+    init_and_exec(rootfs, cmd, args, permissions, &ContainerSetup::default())?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn instance_uris_percent_encode_paths() {
+        let dir = std::env::temp_dir().join(format!("voidbox-uris-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        // Reached through a link, so only the resolved path isn't UTF-8
+        let raw = dir.join(OsStr::from_bytes(b"caf\xe9"));
+        fs::create_dir_all(&raw).unwrap();
+        std::os::unix::fs::symlink(&raw, dir.join("cafe")).unwrap();
+        fs::write(dir.join("a file #1?.txt"), "").unwrap();
+        fs::write(raw.join("100%.txt"), "").unwrap();
+
+        let arg = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        let uris = instance_uris(
+            Some("https://example.com/?q=1"),
+            &[
+                arg("a file #1?.txt"),
+                arg("cafe/100%.txt"),
+                arg("missing.txt"),
+            ],
+        );
+        let base = url::Url::from_directory_path(dir.canonicalize().unwrap()).unwrap();
+        assert_eq!(
+            uris,
+            [
+                "https://example.com/?q=1".to_string(),
+                format!("{}a%20file%20%231%3F.txt", base),
+                format!("{}caf%E9/100%25.txt", base),
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+//! Search command implementation
+//!
+//! Looks for apps in the indexes of the configured repositories (see
+//! `voidbox repo`) by name, display name or description, so an app can be
+//! installed by name without finding its manifest first. Indexes are read
+//! from the cache until their refresh interval has passed, as installing by
+//! name does.
+
+use crate::cli::repo::repository_index;
+use crate::cli::table::Table;
+use crate::settings::load_config;
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SearchError {
+    #[error("Settings error: {0}")]
+    SettingsError(#[from] crate::settings::SettingsError),
+}
+
+/// An app a repository lists that matches the query
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    pub name: String,
+    pub display_name: String,
+    pub description: String,
+    pub version: Option<String>,
+    /// Repository whose index lists the app
+    pub repository: String,
+}
+
+/// Apps in the configured repositories matching `query`, ignoring case
+///
+/// A repository whose index can't be loaded is skipped with a warning.
+pub fn search_repositories(query: &str) -> Result<Vec<SearchResult>, SearchError> {
+    let query = query.to_lowercase();
+    let mut results = Vec::new();
+
+    for location in load_config()?.repositories {
+        let index = match repository_index(&location) {
+            Ok(index) => index,
+            Err(e) => {
+                say!("[voidbox] Warning: Could not load {}: {}", location, e);
+                continue;
+            }
+        };
+        for (name, entry) in index.apps {
+            let matches = [&name, &entry.display_name, &entry.description]
+                .iter()
+                .any(|field| field.to_lowercase().contains(&query));
+            if matches {
+                results.push(SearchResult {
+                    display_name: if entry.display_name.is_empty() {
+                        name.clone()
+                    } else {
+                        entry.display_name
+                    },
+                    name,
+                    description: entry.description,
+                    version: entry.version,
+                    repository: location.clone(),
+                });
+            }
+        }
+    }
+
+    // Exact name matches first
+    results.sort_by(|a, b| {
+        (a.name.to_lowercase() != query, &a.name, &a.repository).cmp(&(
+            b.name.to_lowercase() != query,
+            &b.name,
+            &b.repository,
+        ))
+    });
+    Ok(results)
+}
+
+/// Search the configured repositories and print the matching apps
+pub fn search(query: &str) -> Result<(), SearchError> {
+    let no_repositories = load_config()?.repositories.is_empty();
+    let results = search_repositories(query)?;
+
+    if crate::cli::is_json() {
+        crate::cli::print_json(&results);
+        return Ok(());
+    }
+
+    if no_repositories {
+        say!("No repositories configured.");
+        say!();
+        say!("Add one with: voidbox repo add <location>");
+        return Ok(());
+    }
+
+    if results.is_empty() {
+        say!("No apps match '{}'.", query);
+        return Ok(());
+    }
+
+    let mut table = Table::new(&["NAME", "VERSION", "DESCRIPTION", "REPOSITORY"]);
+    for result in &results {
+        let description = if result.description.is_empty() {
+            result.display_name.clone()
+        } else {
+            format!("{} - {}", result.display_name, result.description)
+        };
+        table.row(vec![
+            result.name.clone(),
+            result.version.clone().unwrap_or_else(|| "-".into()),
+            description,
+            result.repository.clone(),
+        ]);
+    }
+    table.print();
+
+    if !crate::is_plain() {
+        say!();
+        say!("Install with: voidbox install <name>");
+    }
+    Ok(())
+}
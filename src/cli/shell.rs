@@ -1,7 +1,12 @@
 //! Shell command implementation
 
+use crate::cli::libraries::{library_path, prefer_system_libraries, print_library_diagnostics};
+use crate::cli::payload::{payload_dir, prepare_app_data};
 use crate::manifest::parse_manifest_file;
-use crate::runtime::{setup_container_namespaces, setup_user_namespace, spawn_container_init};
+use crate::runtime::{
+    ContainerSetup, setup_container_namespaces, setup_user_namespace, spawn_container_init,
+};
+use crate::settings::manifest_permissions;
 use crate::storage::paths;
 use thiserror::Error;
 
@@ -37,14 +42,38 @@ pub fn shell(app_name: &str, dev_mode: bool) -> Result<(), ShellError> {
 
     // Load manifest for permissions
     let manifest = parse_manifest_file(&manifest_path)?;
-    let mut permissions = manifest.permissions.clone();
+    let mut permissions = manifest_permissions(&manifest);
 
     // Always enable dev_mode for shell access (or if explicitly requested)
     permissions.dev_mode = dev_mode || true;
 
-    println!("[voidbox] Opening shell in {} container...", app_name);
-    println!("[voidbox] Type 'exit' to leave the container.");
-    println!();
+    say!("[voidbox] Opening shell in {} container...", app_name);
+    say!("[voidbox] Type 'exit' to leave the container.");
+    say!();
+
+    // Same home as the app; the payload stays writable for debugging
+    let mut setup = ContainerSetup {
+        data_dir: Some(prepare_app_data(app_name, &rootfs)?),
+        privileged: true,
+        ..Default::default()
+    };
+
+    // Same library search order as the app, so ldd shows what it loads
+    let libraries =
+        prefer_system_libraries(&manifest, &rootfs, &rootfs.join(payload_dir(&manifest)))?;
+    print_library_diagnostics(&manifest, libraries.as_ref());
+    if libraries.is_some_and(|l| l.redirected().next().is_some()) {
+        let path = library_path(
+            manifest
+                .binary
+                .env
+                .get("LD_LIBRARY_PATH")
+                .map(String::as_str),
+        );
+        say!("[voidbox] LD_LIBRARY_PATH={}", path);
+        setup.env.insert("LD_LIBRARY_PATH".to_string(), path);
+    }
+    say!();
 
     // Setup namespaces
     setup_user_namespace()?;
@@ -55,7 +84,7 @@ pub fn shell(app_name: &str, dev_mode: bool) -> Result<(), ShellError> {
     let shell = "/bin/bash".to_string();
     let args: Vec<String> = vec![];
 
-    let status = spawn_container_init(&self_exe, &rootfs, &shell, &args, &permissions)
+    let status = spawn_container_init(&self_exe, &rootfs, &shell, &args, &permissions, &setup)
         .map_err(|e| ShellError::Failed(e.to_string()))?;
 
     if !status.success() {
@@ -0,0 +1,175 @@
+//! Staged updates
+//!
+//! An update can be downloaded into the app's staging directory while the
+//! app is running, then swapped in atomically the next time it starts.
+//! This avoids "text file busy" errors and half-replaced files. Staging
+//! that is interrupted (by a suspend that outlasts the connection, or a
+//! reboot) continues where it stopped the next time the update is staged.
+
+use crate::cli::data::snapshot_before_update;
+use crate::cli::exclude::{load_excluded, save_excluded};
+use crate::cli::install::{
+    InstallError, integrate_installed, prepare_rootfs, save_installed_app, with_install_log,
+};
+use crate::cli::integrity::{load_installed_files, save_installed_files};
+use crate::cli::optimize::{load_optimized, save_optimized};
+use crate::cli::pipeline::InstallJournal;
+use crate::cli::rollback::keep_replaced;
+use crate::desktop::{KeepIntegration, refresh_batch};
+use crate::events::{EventKind, fire_event};
+use crate::manifest::{AppManifest, manifest_text, parse_manifest_file};
+use crate::runtime::is_app_running;
+use crate::storage::paths;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Metadata written once a staged update is complete
+#[derive(Debug, Serialize, Deserialize)]
+struct StagedInfo {
+    version: Option<String>,
+    staged_date: String,
+}
+
+/// Download and prepare an update without touching the live install
+pub fn stage_update(manifest: &AppManifest) -> Result<(), InstallError> {
+    let app_name = &manifest.app.name;
+    let staging = paths::app_staging_dir(app_name);
+    let rootfs = staging.join("rootfs");
+
+    // An interrupted attempt at staging this manifest is continued (its
+    // journal says how far it got); anything else starts from scratch
+    if staging.exists() && InstallJournal::load(&rootfs, manifest).is_none() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+
+    let mut journal = InstallJournal::open(&rootfs, manifest);
+    let version = with_install_log(app_name, "staging an update", || {
+        prepare_rootfs(&rootfs, manifest, &mut journal)
+    })?;
+    journal.finish();
+
+    fs::write(staging.join("manifest.toml"), manifest_text(manifest)?)?;
+
+    // Written last: its presence marks the staged update as complete
+    let info = StagedInfo {
+        version,
+        staged_date: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    let content = serde_json::to_string_pretty(&info)
+        .map_err(|e| InstallError::Failed(format!("Failed to serialize: {}", e)))?;
+    fs::write(staging.join("staged.json"), content)?;
+
+    Ok(())
+}
+
+/// Check whether an app has a complete staged update waiting
+pub fn has_staged_update(app_name: &str) -> bool {
+    paths::app_staging_dir(app_name)
+        .join("staged.json")
+        .exists()
+}
+
+/// Swap the staged rootfs into place
+pub fn apply_staged_update(app_name: &str) -> Result<(), InstallError> {
+    if !has_staged_update(app_name) {
+        return Err(InstallError::Failed(format!(
+            "No staged update for {}",
+            app_name
+        )));
+    }
+
+    if is_app_running(app_name) {
+        return Err(InstallError::Failed(format!(
+            "{} is running; close it to apply the staged update",
+            app_name
+        )));
+    }
+
+    let staging = paths::app_staging_dir(app_name);
+    let content = fs::read_to_string(staging.join("staged.json"))?;
+    let info: StagedInfo = serde_json::from_str(&content)
+        .map_err(|e| InstallError::Failed(format!("Invalid staged update: {}", e)))?;
+    let manifest = parse_manifest_file(&staging.join("manifest.toml"))?;
+
+    snapshot_before_update(app_name)?;
+
+    let live_rootfs = paths::app_rootfs_dir(app_name);
+    let staged_rootfs = staging.join("rootfs");
+
+    if live_rootfs.exists() {
+        // The old rootfs ends up in the staging dir and is discarded below,
+        // all but the payload kept for rolling back
+        swap_dirs(&staged_rootfs, &live_rootfs)?;
+        keep_replaced(app_name, &staged_rootfs)?;
+    } else {
+        fs::rename(&staged_rootfs, &live_rootfs)?;
+    }
+
+    fs::copy(
+        staging.join("manifest.toml"),
+        paths::manifest_path(app_name),
+    )?;
+    // The record of excluded files belongs to the rootfs swapped in
+    let excluded = load_excluded(&staged_rootfs).unwrap_or_default();
+    save_excluded(&live_rootfs, &excluded)?;
+    save_optimized(&live_rootfs, load_optimized(&staged_rootfs).as_ref())?;
+    save_installed_files(&live_rootfs, load_installed_files(&staged_rootfs).as_ref())?;
+    save_installed_app(&manifest, info.version.as_deref())?;
+    fs::remove_dir_all(&staging)?;
+
+    // Refresh desktop integration for the new version
+    let _refresh = refresh_batch();
+    integrate_installed(&manifest, KeepIntegration::default())?;
+
+    let version = info.version.as_deref().or(manifest.app.version.as_deref());
+    fire_event(
+        EventKind::Update,
+        Some(app_name),
+        version,
+        &format!("{} updated (staged)", manifest.app.display_name),
+    );
+
+    say!(
+        "[voidbox] Applied staged update for {} (v{})",
+        manifest.app.display_name,
+        version.unwrap_or("unknown")
+    );
+
+    Ok(())
+}
+
+/// Atomically exchange two directories (falls back to three renames on
+/// filesystems without RENAME_EXCHANGE)
+pub(crate) fn swap_dirs(a: &Path, b: &Path) -> io::Result<()> {
+    let a_c = CString::new(a.as_os_str().as_bytes())?;
+    let b_c = CString::new(b.as_os_str().as_bytes())?;
+
+    let ret = unsafe {
+        libc::renameat2(
+            libc::AT_FDCWD,
+            a_c.as_ptr(),
+            libc::AT_FDCWD,
+            b_c.as_ptr(),
+            libc::RENAME_EXCHANGE,
+        )
+    };
+    if ret == 0 {
+        return Ok(());
+    }
+
+    let err = io::Error::last_os_error();
+    if matches!(err.raw_os_error(), Some(libc::EINVAL) | Some(libc::ENOSYS)) {
+        let tmp = b.with_extension("old");
+        fs::rename(b, &tmp)?;
+        fs::rename(a, b)?;
+        fs::rename(&tmp, a)?;
+        return Ok(());
+    }
+
+    Err(err)
+}
@@ -0,0 +1,138 @@
+//! Tabular command output
+//!
+//! Tables are aligned in columns and fit to the terminal: when a row is
+//! too wide, the widest column is truncated with an ellipsis. With
+//! `--plain` they print one tab-separated record per line instead, without
+//! header padding or truncation, which reads well in screen readers and
+//! splits cleanly in scripts.
+
+use std::io::IsTerminal;
+
+/// Width assumed when stdout is not a terminal and COLUMNS is unset
+const DEFAULT_WIDTH: usize = 100;
+
+/// Narrowest a truncated column gets
+const MIN_COLUMN_WIDTH: usize = 8;
+
+/// Space between columns
+const GAP: &str = "  ";
+
+/// Width of the terminal in columns: COLUMNS, then the terminal's size
+pub fn terminal_width() -> usize {
+    if let Some(columns) = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse::<usize>().ok())
+        .filter(|c| *c > 0)
+    {
+        return columns;
+    }
+    if std::io::stdout().is_terminal() {
+        let mut size = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: TIOCGWINSZ only writes into the winsize struct
+        let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+        if ok && size.ws_col > 0 {
+            return size.ws_col as usize;
+        }
+    }
+    DEFAULT_WIDTH
+}
+
+/// Shorten `text` to `width` characters, ending in an ellipsis if cut
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// A table printed aligned, or tab-separated with `--plain`
+pub struct Table {
+    headers: Vec<&'static str>,
+    /// Columns aligned to the right (numbers)
+    right: Vec<bool>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&'static str]) -> Self {
+        Self {
+            headers: headers.to_vec(),
+            right: vec![false; headers.len()],
+            rows: Vec::new(),
+        }
+    }
+
+    /// Align a column to the right
+    pub fn right(mut self, column: usize) -> Self {
+        self.right[column] = true;
+        self
+    }
+
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    pub fn print(&self) {
+        if crate::is_plain() {
+            say!("{}", self.headers.join("\t"));
+            for row in &self.rows {
+                say!("{}", row.join("\t"));
+            }
+            return;
+        }
+
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        // Shave the widest column until the table fits, so wide columns
+        // shrink together
+        let available = terminal_width();
+        let total =
+            |widths: &[usize]| widths.iter().sum::<usize>() + GAP.len() * (widths.len() - 1);
+        while total(&widths) > available {
+            let (widest, width) = widths
+                .iter()
+                .copied()
+                .enumerate()
+                .max_by_key(|(_, w)| *w)
+                .unwrap_or_default();
+            if width <= MIN_COLUMN_WIDTH {
+                break;
+            }
+            widths[widest] -= 1;
+        }
+
+        let headers: Vec<String> = self.headers.iter().map(|h| h.to_string()).collect();
+        for row in std::iter::once(&headers).chain(&self.rows) {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .zip(&self.right)
+                .enumerate()
+                .map(|(i, ((cell, width), right))| {
+                    let cell = truncate(cell, *width);
+                    if *right {
+                        format!("{:>width$}", cell, width = width)
+                    } else if i + 1 == row.len() {
+                        // No trailing padding on the last column
+                        cell
+                    } else {
+                        format!("{:<width$}", cell, width = width)
+                    }
+                })
+                .collect();
+            say!("{}", cells.join(GAP));
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Installing several apps as one transaction
+//!
+//! `voidbox install a.toml b.toml c.toml` resolves every manifest and
+//! downloads everything the installs need before changing anything, so a
+//! missing manifest or a failed download leaves the system as it was. The
+//! installs are then made one after another; when one fails, the ones
+//! already made are undone in reverse order: new apps are removed again and
+//! reinstalled ones go back to the version they replaced. Apps the requested
+//! ones depend on join the transaction ahead of them.
+
+use crate::cli::conflict::ConflictPolicy;
+use crate::cli::dependencies::missing_dependencies;
+use crate::cli::install::{
+    InstallError, dry_run_install, install_app_from_manifest, installed_at_requested_version,
+    record_install, resolve_request, update_record,
+};
+use crate::cli::json::{is_json, print_json};
+use crate::cli::plan::plan_install;
+use crate::cli::remove::{RemoveError, discard_app};
+use crate::cli::rollback::{keep_previous, restore_previous};
+use crate::cli::versions::{SlotData, split_version};
+use crate::desktop::refresh_batch;
+use crate::events::{Event, emit, subscribe};
+use crate::manifest::AppManifest;
+use crate::runtime::is_app_running;
+use crate::storage::{cached_download, paths};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// One app of the transaction
+struct Request<'a> {
+    source: String,
+    version: Option<&'a str>,
+    manifest: AppManifest,
+}
+
+/// What undoing an app's install has to put back
+enum Before {
+    /// The app wasn't installed; its directory may still have held data
+    NotInstalled { had_dir: bool },
+    /// The app was installed and has been kept for restoring, with what
+    /// its record said about it. The version an earlier update replaced is
+    /// moved aside meanwhile.
+    Installed {
+        pinned: bool,
+        source_url: Option<String>,
+        earlier_previous: Option<PathBuf>,
+    },
+}
+
+/// Install several apps, all of them or none
+///
+/// Every source is resolved and every download made before the first
+/// install; if an install fails, those made before it are undone. Apps
+/// already installed at the requested version are left out.
+pub fn install_apps(
+    sources: &[String],
+    policy: ConflictPolicy,
+    yes: bool,
+    dry_run: bool,
+    slot: Option<SlotData>,
+) -> Result<(), InstallError> {
+    let mut requests: Vec<Request> = Vec::new();
+    for source in sources {
+        let (source, version) = split_version(source);
+        if slot.is_some() && version.is_none() {
+            return Err(InstallError::Failed(format!(
+                "Side-by-side installs need a version: voidbox install {}@<version>",
+                source
+            )));
+        }
+        say!("[voidbox] Resolving {}...", source);
+        let (manifest, _) = resolve_request(source, version, slot)?;
+        if requests
+            .iter()
+            .any(|r| paths::same_app(&r.manifest.app.name, &manifest.app.name))
+        {
+            return Err(InstallError::Failed(format!(
+                "{} is requested more than once",
+                manifest.app.name
+            )));
+        }
+        requests.push(Request {
+            source: source.to_string(),
+            version,
+            manifest,
+        });
+    }
+
+    // Dependencies go first; one also requested keeps its request's place
+    let mut ordered: Vec<Request> = Vec::new();
+    for request in requests {
+        for dependency in missing_dependencies(&request.manifest)? {
+            if !ordered
+                .iter()
+                .any(|r| paths::same_app(&r.manifest.app.name, &dependency.manifest.app.name))
+            {
+                ordered.push(Request {
+                    source: dependency.source,
+                    version: None,
+                    manifest: dependency.manifest,
+                });
+            }
+        }
+        match ordered
+            .iter_mut()
+            .find(|r| paths::same_app(&r.manifest.app.name, &request.manifest.app.name))
+        {
+            Some(listed) => *listed = request,
+            None => ordered.push(request),
+        }
+    }
+    let mut requests = ordered;
+
+    if dry_run {
+        for request in &requests {
+            dry_run_install(&request.manifest, policy)?;
+        }
+        return Ok(());
+    }
+
+    // Progress events are the install's JSON output
+    let _events = is_json().then(|| subscribe(print_json::<Event>));
+
+    requests.retain(|request| {
+        let manifest = &request.manifest;
+        let installed = (policy != ConflictPolicy::Overwrite)
+            .then(|| installed_at_requested_version(manifest))
+            .flatten();
+        if let Some(version) = &installed {
+            say!(
+                "[voidbox] {} v{} is already installed, leaving it out",
+                manifest.app.display_name,
+                version
+            );
+            emit(Event::Skipped {
+                app: manifest.app.name.clone(),
+                reason: format!("v{} is already installed", version),
+            });
+        }
+        installed.is_none()
+    });
+    if requests.is_empty() {
+        return Ok(());
+    }
+    for request in &requests {
+        let app_name = &request.manifest.app.name;
+        if is_app_running(app_name) {
+            return Err(InstallError::Failed(format!(
+                "{} is running; close it first",
+                app_name
+            )));
+        }
+    }
+
+    let mut plans = Vec::new();
+    for request in &requests {
+        plans.push(plan_install(&request.manifest)?);
+    }
+    let ask = !yes && !is_json() && crate::can_prompt();
+    if ask {
+        for (request, plan) in requests.iter().zip(&plans) {
+            say!(
+                "[voidbox] {} {}:",
+                request.manifest.app.display_name,
+                plan.version
+            );
+            plan.print();
+        }
+        if plans.last().is_some_and(|plan| !plan.confirm(yes)) {
+            say!("[voidbox] Cancelled");
+            return Ok(());
+        }
+    }
+
+    // Everything is downloaded into the cache, where the installs find it
+    let mut downloaded = HashSet::new();
+    for download in plans.iter().flat_map(|plan| &plan.downloads) {
+        if download.cached || !downloaded.insert(&download.url) {
+            continue;
+        }
+        say!(
+            "[voidbox] Downloading {} {}...",
+            download.name,
+            download.version
+        );
+        cached_download(&download.url, download.sha256.as_deref(), true)?;
+    }
+
+    commit(&requests, policy, slot)?;
+    say!(
+        "[voidbox] Installed {} apps: {}",
+        requests.len(),
+        requests
+            .iter()
+            .map(|r| r.manifest.app.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    Ok(())
+}
+
+/// Install the apps in order, undoing them all if one fails
+fn commit(
+    requests: &[Request],
+    policy: ConflictPolicy,
+    slot: Option<SlotData>,
+) -> Result<(), InstallError> {
+    let _refresh = refresh_batch();
+
+    // Apps skipped over a conflict weren't changed, so aren't undone
+    let skipped = Arc::new(Mutex::new(Vec::new()));
+    let _skipped = {
+        let skipped = Arc::clone(&skipped);
+        subscribe(move |event| {
+            if let Event::Skipped { app, .. } = event
+                && let Ok(mut skipped) = skipped.lock()
+            {
+                skipped.push(app.clone());
+            }
+        })
+    };
+
+    let mut made: Vec<(&str, Before)> = Vec::new();
+    for request in requests {
+        let manifest = &request.manifest;
+        let app_name = manifest.app.name.as_str();
+        let result = keep_for_undo(app_name)
+            .map_err(InstallError::from)
+            .and_then(|before| {
+                made.push((app_name, before));
+                install_app_from_manifest(manifest, policy)?;
+                record_install(manifest, &request.source, request.version.is_some(), slot)
+            });
+
+        if let Err(e) = result {
+            say!(
+                "[voidbox] Installing {} failed; undoing the installs made so far",
+                manifest.app.display_name
+            );
+            for (app_name, before) in made.into_iter().rev() {
+                if let Err(e) = undo(app_name, before) {
+                    say!(
+                        "[voidbox] Warning: Could not undo the install of {}: {}",
+                        app_name,
+                        e
+                    );
+                }
+            }
+            return Err(e);
+        }
+        if skipped
+            .lock()
+            .is_ok_and(|s| s.iter().any(|a| a == app_name))
+            && let Some((_, before)) = made.pop()
+        {
+            release(app_name, before);
+        }
+    }
+
+    for (_, before) in made {
+        if let Before::Installed {
+            earlier_previous: Some(earlier),
+            ..
+        } = before
+        {
+            fs::remove_dir_all(earlier).ok();
+        }
+    }
+    Ok(())
+}
+
+/// Note what undoing an app's install has to restore, keeping a copy of
+/// the installed version
+fn keep_for_undo(app_name: &str) -> io::Result<Before> {
+    if !paths::manifest_path(app_name).exists() || !paths::app_rootfs_dir(app_name).exists() {
+        return Ok(Before::NotInstalled {
+            had_dir: paths::app_dir(app_name).exists(),
+        });
+    }
+
+    let previous = paths::app_previous_dir(app_name);
+    let earlier = previous.with_extension("earlier");
+    if earlier.exists() {
+        fs::remove_dir_all(&earlier)?;
+    }
+    let earlier_previous = if previous.exists() {
+        fs::rename(&previous, &earlier)?;
+        Some(earlier)
+    } else {
+        None
+    };
+    keep_previous(app_name)?;
+
+    let record = crate::cli::get_installed_apps()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|a| paths::same_app(&a.name, app_name));
+    Ok(Before::Installed {
+        pinned: record.as_ref().is_some_and(|r| r.pinned),
+        source_url: record.and_then(|r| r.source_url),
+        earlier_previous,
+    })
+}
+
+/// Put back the version an earlier update replaced, for an app the
+/// transaction left unchanged
+fn release(app_name: &str, before: Before) {
+    if let Before::Installed {
+        earlier_previous, ..
+    } = before
+    {
+        let previous = paths::app_previous_dir(app_name);
+        fs::remove_dir_all(&previous).ok();
+        if let Some(earlier) = earlier_previous {
+            fs::rename(earlier, previous).ok();
+        }
+    }
+}
+
+/// Put an app back as it was before the transaction
+fn undo(app_name: &str, before: Before) -> Result<(), String> {
+    match before {
+        Before::NotInstalled { had_dir } => match discard_app(app_name, !had_dir) {
+            Ok(()) | Err(RemoveError::NotInstalled(_)) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        },
+        Before::Installed {
+            pinned,
+            source_url,
+            earlier_previous,
+        } => {
+            let (manifest, version) =
+                restore_previous(app_name, false).map_err(|e| e.to_string())?;
+            update_record(app_name, |app| {
+                app.pinned = pinned;
+                app.source_url = source_url.clone();
+            })
+            .map_err(|e| e.to_string())?;
+            if let Some(earlier) = earlier_previous {
+                fs::rename(earlier, paths::app_previous_dir(app_name))
+                    .map_err(|e| e.to_string())?;
+            }
+            say!(
+                "[voidbox] Restored {} v{}",
+                manifest.app.display_name,
+                version.as_deref().unwrap_or("unknown")
+            );
+            Ok(())
+        }
+    }
+}
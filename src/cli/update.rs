@@ -1,10 +1,28 @@
 //! Update command implementation
 
-use crate::cli::install::install_app;
-use crate::manifest::{InstalledApp, SourceConfig, parse_manifest_file};
-use crate::storage::paths;
-use serde::Deserialize;
+use crate::cli::conflict::ConflictPolicy;
+use crate::cli::data::snapshot_before_update;
+use crate::cli::install::{
+    check_signature, install_app_dependencies, install_manifest, report_result,
+};
+use crate::cli::plan::{Plan, PlannedChange, plan_install};
+use crate::cli::recover::{backup_path, binary_version, keep_backup, verify_binary};
+use crate::cli::rollback::{keep_previous, previous_version};
+use crate::cli::stage::{apply_staged_update, has_staged_update, stage_update};
+use crate::desktop::refresh_batch;
+use crate::events::EventKind;
+use crate::manifest::{
+    AppManifest, InstalledApp, SourceConfig, for_host_arch, manifest_hash, parse_manifest_file,
+    parse_manifest_url,
+};
+use crate::runtime::{in_background, is_app_running};
+use crate::settings::{load_config, load_overrides, manifest_permissions};
+use crate::storage::{http_get, is_metered, parse_size, paths, sha256_file};
+use indicatif::HumanBytes;
+use serde::{Deserialize, Serialize};
 use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -21,6 +39,9 @@ pub enum UpdateError {
     #[error("Manifest error: {0}")]
     ManifestError(#[from] crate::manifest::ManifestError),
 
+    #[error("Update of {0} declined: it asks for new permissions")]
+    PermissionsDeclined(String),
+
     #[error("Update failed: {0}")]
     Failed(String),
 }
@@ -37,8 +58,7 @@ fn get_latest_github_version(owner: &str, repo: &str) -> Result<String, UpdateEr
         owner, repo
     );
 
-    let mut resp = ureq::get(&api_url)
-        .header("User-Agent", crate::APP_NAME)
+    let mut resp = http_get(&api_url)
         .call()
         .map_err(|e| UpdateError::Failed(format!("GitHub API error: {}", e)))?;
 
@@ -53,8 +73,8 @@ fn get_latest_github_version(owner: &str, repo: &str) -> Result<String, UpdateEr
     Ok(release.tag_name.trim_start_matches('v').to_string())
 }
 
-/// Get installed version of an app
-fn get_installed_version(app_name: &str) -> Option<String> {
+/// Get the database record of an installed app
+fn get_installed_record(app_name: &str) -> Option<InstalledApp> {
     let db_path = paths::database_path();
     if !db_path.exists() {
         return None;
@@ -64,80 +84,522 @@ fn get_installed_version(app_name: &str) -> Option<String> {
     let apps: Vec<InstalledApp> = serde_json::from_str(&content).ok()?;
 
     apps.into_iter()
-        .find(|a| a.name == app_name)
-        .and_then(|a| a.version)
+        .find(|a| paths::same_app(&a.name, app_name))
+}
+
+/// Numeric parts of a version, which order versions when compared
+pub(crate) fn version_key(version: &str) -> Vec<u32> {
+    version
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|p| p.parse().ok())
+        .collect()
 }
 
 /// Compare versions (returns true if latest > installed)
 fn is_newer_version(installed: &str, latest: &str) -> bool {
-    let parse_version = |s: &str| -> Vec<u32> {
-        s.split(|c: char| !c.is_ascii_digit())
-            .filter_map(|p| p.parse().ok())
-            .collect()
+    version_key(latest) > version_key(installed)
+}
+
+/// Latest upstream version of an app, if its source can be checked
+///
+/// Direct and local sources have no release to ask, so the version the
+/// manifest declares is taken as theirs.
+fn latest_version(manifest: &AppManifest) -> Result<Option<String>, UpdateError> {
+    match &manifest.source {
+        SourceConfig::Github { owner, repo, .. } => {
+            Ok(Some(get_latest_github_version(owner, repo)?))
+        }
+        SourceConfig::Direct { .. } | SourceConfig::Local { .. } => {
+            Ok(manifest.app.version.clone())
+        }
+    }
+}
+
+/// Check for a newer upstream version without installing it
+///
+/// Returns the latest version if it is newer than the installed one.
+pub fn check_update(app_name: &str) -> Result<Option<String>, UpdateError> {
+    let manifest_path = paths::manifest_path(app_name);
+    if !manifest_path.exists() {
+        return Err(UpdateError::NotInstalled(app_name.to_string()));
+    }
+
+    let manifest = parse_manifest_file(&manifest_path)?;
+    let Some(record) = get_installed_record(app_name).filter(|a| !a.pinned) else {
+        return Ok(None);
     };
+    let installed = record.version;
 
-    let installed_parts = parse_version(installed);
-    let latest_parts = parse_version(latest);
+    Ok(match (installed, latest_version(&manifest)?) {
+        (Some(installed), Some(latest)) if is_newer_version(&installed, &latest) => Some(latest),
+        _ => None,
+    })
+}
 
-    latest_parts > installed_parts
+/// What updating one app would do
+enum UpdateCheck {
+    Change(PlannedChange),
+    UpToDate(String),
+    /// Pinned to the installed version
+    Pinned(Option<String>),
+    /// The source has no version to compare against
+    Unchecked,
 }
 
-/// Update a specific app
-pub fn update_app(app_name: &str, force: bool) -> Result<(), UpdateError> {
+/// Manifest an update would install; apps installed from a URL re-fetch it
+fn update_manifest(
+    manifest_path: &Path,
+    record: Option<&InstalledApp>,
+) -> Result<AppManifest, UpdateError> {
+    let Some(url) = record.and_then(|r| r.source_url.as_deref()) else {
+        return Ok(parse_manifest_file(manifest_path)?);
+    };
+
+    // A fetched manifest's signature is checked as a new install's is
+    let fetched = parse_manifest_url(url)
+        .map_err(|e| e.to_string())
+        .and_then(|manifest| match check_signature(&manifest) {
+            Ok(_) => for_host_arch(&manifest).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        });
+    match fetched {
+        Ok(manifest) => Ok(manifest),
+        Err(e) => {
+            say!(
+                "[voidbox] Warning: Could not fetch manifest from {}: {}",
+                url,
+                e
+            );
+            Ok(parse_manifest_file(manifest_path)?)
+        }
+    }
+}
+
+/// A newer manifest file for an installed app, checked as a new install's is
+fn manifest_from_file(app_name: &str, file: &Path) -> Result<AppManifest, UpdateError> {
+    let manifest = parse_manifest_file(file)?;
+    if !paths::same_app(&manifest.app.name, app_name) {
+        return Err(UpdateError::Failed(format!(
+            "{} is a manifest for {}, not {}",
+            file.display(),
+            manifest.app.name,
+            app_name
+        )));
+    }
+    check_signature(&manifest)?;
+    Ok(for_host_arch(&manifest)?)
+}
+
+/// Work out what updating an app would do, without changing anything
+///
+/// With `from`, the update comes from that manifest file and is only
+/// installed if its version is newer (or when forced).
+fn plan_app_update(
+    app_name: &str,
+    force: bool,
+    from: Option<&Path>,
+) -> Result<(AppManifest, UpdateCheck), UpdateError> {
     let manifest_path = paths::manifest_path(app_name);
 
     if !manifest_path.exists() {
         return Err(UpdateError::NotInstalled(app_name.to_string()));
     }
 
-    // Load manifest to check source
-    let manifest = parse_manifest_file(&manifest_path)?;
-    let display_name = &manifest.app.display_name;
+    let record = get_installed_record(app_name);
+    let installed_version = record.as_ref().and_then(|a| a.version.clone());
+
+    // A pinned app is only reinstalled, from its saved manifest at the
+    // pinned version, when forced
+    if record.as_ref().is_some_and(|a| a.pinned) {
+        let manifest = match from {
+            Some(file) => manifest_from_file(app_name, file)?,
+            None => parse_manifest_file(&manifest_path)?,
+        };
+        let check = if force {
+            UpdateCheck::Change(PlannedChange::Reinstall {
+                app: app_name.to_string(),
+                reason: "forced".to_string(),
+            })
+        } else {
+            UpdateCheck::Pinned(installed_version)
+        };
+        return Ok((manifest, check));
+    }
 
-    // Get installed version
-    let installed_version = get_installed_version(app_name);
+    let manifest = match from {
+        Some(file) => manifest_from_file(app_name, file)?,
+        None => update_manifest(&manifest_path, record.as_ref())?,
+    };
 
-    // Check for updates based on source type
-    let latest_version = match &manifest.source {
-        SourceConfig::Github { owner, repo, .. } => Some(get_latest_github_version(owner, repo)?),
-        SourceConfig::Direct { .. } => None, // Can't check version for direct URLs
-        SourceConfig::Local { .. } => None,  // Local sources don't have remote versions
+    // A manifest edited since install needs a reinstall even without a new
+    // upstream version; a given file is compared by version instead
+    let installed_hash = record.and_then(|a| a.manifest_hash);
+    let drifted = from.is_none()
+        && installed_hash.is_some_and(|h| manifest_hash(&manifest).is_ok_and(|cur| cur != h));
+    if drifted {
+        let change = PlannedChange::Reinstall {
+            app: app_name.to_string(),
+            reason: "manifest changed since install".to_string(),
+        };
+        return Ok((manifest, UpdateCheck::Change(change)));
+    }
+
+    let check = match (installed_version, latest_version(&manifest)?) {
+        (Some(installed), Some(latest)) if is_newer_version(&installed, &latest) => {
+            UpdateCheck::Change(PlannedChange::Upgrade {
+                app: app_name.to_string(),
+                from: Some(installed),
+                to: latest,
+            })
+        }
+        _ if force => UpdateCheck::Change(PlannedChange::Reinstall {
+            app: app_name.to_string(),
+            reason: "forced".to_string(),
+        }),
+        (Some(installed), Some(_)) => UpdateCheck::UpToDate(installed),
+        (Some(_), None) => UpdateCheck::Unchecked,
+        (None, Some(latest)) => UpdateCheck::Change(PlannedChange::Upgrade {
+            app: app_name.to_string(),
+            from: None,
+            to: latest,
+        }),
+        (None, None) => UpdateCheck::Change(PlannedChange::Reinstall {
+            app: app_name.to_string(),
+            reason: "installed version unknown".to_string(),
+        }),
     };
 
-    // Compare versions
-    if !force {
-        if let (Some(installed), Some(latest)) = (&installed_version, &latest_version) {
-            if !is_newer_version(installed, latest) {
-                println!("[voidbox] {} is up to date (v{})", display_name, installed);
-                return Ok(());
-            }
-            println!(
-                "[voidbox] {} update available: v{} -> v{}",
-                display_name, installed, latest
+    Ok((manifest, check))
+}
+
+/// Update a specific app
+///
+/// With `stage`, or when the app is currently running, the update is only
+/// downloaded and staged; it is applied on the next launch. An update that
+/// asks for new permissions needs approval unless `accept_permissions`.
+/// With `from`, the update is the app's manifest in that file.
+pub fn update_app(
+    app_name: &str,
+    force: bool,
+    stage: bool,
+    accept_permissions: bool,
+    from: Option<&Path>,
+) -> Result<(), UpdateError> {
+    let (manifest, check) = plan_app_update(app_name, force, from)?;
+    let display_name = &manifest.app.display_name;
+
+    match check {
+        UpdateCheck::UpToDate(installed) => {
+            say!("[voidbox] {} is up to date (v{})", display_name, installed);
+            return Ok(());
+        }
+        UpdateCheck::Pinned(installed) => {
+            say!(
+                "[voidbox] {} is pinned at v{}, skipping (unpin with 'voidbox unpin {}')",
+                display_name,
+                installed.as_deref().unwrap_or("unknown"),
+                app_name
             );
-        } else if installed_version.is_some() && latest_version.is_none() {
-            println!(
-                "[voidbox] {} - cannot check for updates (non-GitHub source)",
-                display_name
+            return Ok(());
+        }
+        UpdateCheck::Unchecked => {
+            say!(
+                "[voidbox] {} - cannot check for updates (its manifest has no version); \
+                 update from a newer manifest with 'voidbox update {} --from <file>'",
+                display_name,
+                app_name
             );
             return Ok(());
         }
+        UpdateCheck::Change(PlannedChange::Upgrade {
+            from: Some(installed),
+            to: latest,
+            ..
+        }) => say!(
+            "[voidbox] {} update available: v{} -> v{}",
+            display_name,
+            installed,
+            latest
+        ),
+        UpdateCheck::Change(PlannedChange::Reinstall { reason, .. }) if reason != "forced" => {
+            say!("[voidbox] {} {}, reinstalling", display_name, reason)
+        }
+        UpdateCheck::Change(_) => {}
+    }
+
+    apply_app_update(app_name, &manifest, stage, accept_permissions)
+}
+
+/// Install a planned update, staging it if asked to or if the app is running
+fn apply_app_update(
+    app_name: &str,
+    manifest: &AppManifest,
+    stage: bool,
+    accept_permissions: bool,
+) -> Result<(), UpdateError> {
+    let display_name = &manifest.app.display_name;
+
+    if let Some(size) = deferred_download(manifest)? {
+        say!(
+            "[voidbox] Deferring the update of {} ({}) until the connection isn't metered",
+            display_name,
+            size
+        );
+        return Ok(());
+    }
+
+    let added = added_permissions(app_name, manifest);
+    if !added.is_empty() && !accept_permissions && !approve_permissions(display_name, &added) {
+        return Err(UpdateError::PermissionsDeclined(app_name.to_string()));
     }
 
-    println!("[voidbox] Updating {}...", display_name);
+    // Remember background updates until they finish, so one cut short by
+    // a reboot is continued by the next maintenance run
+    if in_background() {
+        set_pending(app_name, Some(stage));
+    }
+    let result = install_app_update(app_name, manifest, stage);
+    if in_background() {
+        set_pending(app_name, None);
+    }
+    result
+}
+
+/// Stage or install an update
+fn install_app_update(
+    app_name: &str,
+    manifest: &AppManifest,
+    stage: bool,
+) -> Result<(), UpdateError> {
+    let display_name = &manifest.app.display_name;
+
+    // Apps the new version depends on are installed first
+    install_app_dependencies(manifest, ConflictPolicy::Skip)?;
+
+    if stage || is_app_running(app_name) {
+        if !stage {
+            say!(
+                "[voidbox] {} is running, staging the update instead...",
+                display_name
+            );
+        }
+        say!("[voidbox] Staging update for {}...", display_name);
+        stage_update(manifest)?;
+        say!(
+            "[voidbox] Update staged. It will be applied the next time {} starts.",
+            display_name
+        );
+        say!(
+            "[voidbox] Apply now with: voidbox update {} --apply",
+            app_name
+        );
+        return Ok(());
+    }
+
+    say!("[voidbox] Updating {}...", display_name);
+    snapshot_before_update(app_name)?;
+    keep_previous(app_name)?;
 
     // Reinstall the app (force=true to overwrite)
-    install_app(manifest_path.to_str().unwrap(), true)?;
+    let result = install_manifest(manifest, true);
+    report_result(EventKind::Update, manifest, &result);
+    if result.is_err() && previous_version(app_name).is_some() {
+        say!(
+            "[voidbox] Go back to the version before with: voidbox rollback {}",
+            app_name
+        );
+    }
+    result?;
+
+    Ok(())
+}
+
+/// A background update that was started but not finished
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingUpdate {
+    app: String,
+    stage: bool,
+}
+
+fn load_pending() -> Vec<PendingUpdate> {
+    fs::read_to_string(paths::pending_updates_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Record an app's update as started (with whether it is staged), or
+/// finished with `None`
+fn set_pending(app_name: &str, stage: Option<bool>) {
+    let mut pending = load_pending();
+    pending.retain(|p| p.app != app_name);
+    if let Some(stage) = stage {
+        pending.push(PendingUpdate {
+            app: app_name.to_string(),
+            stage,
+        });
+    }
+
+    let path = paths::pending_updates_path();
+    if pending.is_empty() {
+        fs::remove_file(&path).ok();
+        return;
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+    if let Ok(content) = serde_json::to_string_pretty(&pending) {
+        fs::write(&path, content).ok();
+    }
+}
+
+/// Continue background updates that were cut short, e.g. by a reboot;
+/// returns the apps continued and the errors of those that failed
+///
+/// Downloads continue from the bytes already fetched and staging from its
+/// journal. Updates no longer needed are forgotten.
+pub fn continue_pending_updates() -> (Vec<String>, Vec<(String, UpdateError)>) {
+    let mut continued = Vec::new();
+    let mut failed = Vec::new();
+    for pending in load_pending() {
+        let app_name = &pending.app;
+        match plan_app_update(app_name, false, None) {
+            Ok((manifest, UpdateCheck::Change(_))) => {
+                say!(
+                    "[voidbox] Continuing the interrupted update of {}...",
+                    manifest.app.display_name
+                );
+                match apply_app_update(app_name, &manifest, pending.stage, false) {
+                    Ok(()) => continued.push(app_name.clone()),
+                    Err(e) => {
+                        // e.g. declined permissions: not worth retrying each run
+                        set_pending(app_name, None);
+                        failed.push((app_name.clone(), e));
+                    }
+                }
+            }
+            Ok(_) => set_pending(app_name, None),
+            Err(e) => {
+                set_pending(app_name, None);
+                failed.push((app_name.clone(), e));
+            }
+        }
+    }
+    (continued, failed)
+}
+
+/// Size of an update's download when it should wait for an unmetered
+/// connection: in background runs on a metered connection, downloads over
+/// `[network] metered_max_download` or of unknown size
+fn deferred_download(manifest: &AppManifest) -> Result<Option<String>, UpdateError> {
+    if !in_background() || !is_metered() {
+        return Ok(None);
+    }
+
+    let limit = load_config()
+        .map_err(|e| UpdateError::Failed(e.to_string()))?
+        .network
+        .metered_max_download;
+    let limit = parse_size(&limit).ok_or_else(|| {
+        UpdateError::Failed(format!("Invalid network metered_max_download '{}'", limit))
+    })?;
+    let (size, known) = plan_install(manifest)?.download_size();
+    Ok(if !known {
+        Some("size unknown".to_string())
+    } else if size > limit {
+        Some(HumanBytes(size).to_string())
+    } else {
+        None
+    })
+}
+
+/// Permissions the updated manifest enables that the installed app doesn't
+/// have; none when the user has set the app's permissions explicitly
+fn added_permissions(app_name: &str, manifest: &AppManifest) -> Vec<&'static str> {
+    if load_overrides(app_name).ok().flatten().is_some() {
+        return Vec::new();
+    }
+    let Ok(installed) = parse_manifest_file(&paths::manifest_path(app_name)) else {
+        return Vec::new();
+    };
+
+    let granted = manifest_permissions(&installed).enabled_names();
+    manifest_permissions(manifest)
+        .enabled_names()
+        .into_iter()
+        .filter(|p| !granted.contains(p))
+        .collect()
+}
+
+/// Ask whether an update may grant new permissions
+///
+/// Without a terminal or dialog tool to ask with, the update is declined.
+fn approve_permissions(display_name: &str, added: &[&str]) -> bool {
+    let message = format!(
+        "The update for {} asks for new permissions: {}",
+        display_name,
+        added.join(", ")
+    );
+    say!("[voidbox] {}", message);
+
+    if crate::can_prompt() {
+        crate::confirm("[voidbox] Allow and update?", false)
+    } else if crate::gui::can_ask() {
+        crate::gui::ask_yes_no(
+            "Voidbox - New Permissions",
+            &format!("{}\n\nAllow them and update?", message),
+        )
+    } else {
+        say!("[voidbox] Not interactive; pass --accept-permissions to allow them");
+        false
+    }
+}
+
+/// Apply a previously staged update now
+pub fn apply_update(app_name: &str) -> Result<(), UpdateError> {
+    if !paths::manifest_path(app_name).exists() {
+        return Err(UpdateError::NotInstalled(app_name.to_string()));
+    }
+
+    apply_staged_update(app_name)?;
+
+    Ok(())
+}
+
+/// Apply all staged updates for apps that aren't running
+pub fn apply_all_updates() -> Result<(), UpdateError> {
+    let apps = crate::cli::get_installed_apps().map_err(|e| UpdateError::Failed(e.to_string()))?;
+
+    let staged: Vec<_> = apps.iter().filter(|a| has_staged_update(&a.name)).collect();
+
+    if staged.is_empty() {
+        say!("[voidbox] No staged updates.");
+        return Ok(());
+    }
+
+    let _refresh = refresh_batch();
+    for app in staged {
+        if let Err(e) = apply_staged_update(&app.name) {
+            say!("[voidbox] Could not apply update for {}: {}", app.name, e);
+        }
+    }
 
     Ok(())
 }
 
 /// Update all installed apps
-pub fn update_all(force: bool) -> Result<(), UpdateError> {
+///
+/// Prints the planned changes first and asks for confirmation unless `yes`.
+pub fn update_all(
+    force: bool,
+    stage: bool,
+    yes: bool,
+    accept_permissions: bool,
+) -> Result<(), UpdateError> {
     let db_path = paths::database_path();
 
     if !db_path.exists() {
-        println!("[voidbox] No apps installed.");
+        say!("[voidbox] No apps installed.");
         return Ok(());
     }
 
@@ -146,47 +608,139 @@ pub fn update_all(force: bool) -> Result<(), UpdateError> {
         .map_err(|e| UpdateError::Failed(format!("Failed to parse database: {}", e)))?;
 
     if apps.is_empty() {
-        println!("[voidbox] No apps installed.");
+        say!("[voidbox] No apps installed.");
         return Ok(());
     }
 
-    println!("[voidbox] Checking {} app(s) for updates...", apps.len());
-
-    let mut updated = 0;
-    let mut up_to_date = 0;
-    let mut failed = 0;
+    say!("[voidbox] Checking {} app(s) for updates...", apps.len());
 
+    let mut plan = Plan::default();
+    let mut manifests = Vec::new();
     for app in &apps {
-        match update_app(&app.name, force) {
-            Ok(()) => {
-                // Check if it was actually updated or already up to date
-                // by looking at the output (the function prints its status)
-                updated += 1;
-            }
-            Err(UpdateError::Failed(msg)) if msg.contains("up to date") => {
-                up_to_date += 1;
-            }
-            Err(e) => {
-                println!("[voidbox] Failed to update {}: {}", app.name, e);
-                failed += 1;
+        match plan_app_update(&app.name, force, None) {
+            Ok((manifest, UpdateCheck::Change(change))) => {
+                plan.changes.push(change);
+                manifests.push((app.name.clone(), manifest));
             }
+            Ok(_) => plan.unchanged.push(app.name.clone()),
+            Err(e) => plan.errors.push((app.name.clone(), e.to_string())),
         }
     }
 
-    println!("[voidbox] Update check complete!");
-    if updated > 0 || up_to_date > 0 || failed > 0 {
-        if failed > 0 {
-            println!("  {} failed", failed);
+    plan.print();
+    if plan.is_empty() {
+        say!("[voidbox] Nothing to update.");
+        return Ok(());
+    }
+    if !plan.confirm(yes) {
+        say!("[voidbox] Update cancelled.");
+        return Ok(());
+    }
+
+    let mut failed = 0;
+
+    // One desktop database refresh for the whole run
+    let _refresh = refresh_batch();
+    for (app_name, manifest) in &manifests {
+        if let Err(e) = apply_app_update(app_name, manifest, stage, accept_permissions) {
+            say!("[voidbox] Failed to update {}: {}", app_name, e);
+            failed += 1;
         }
     }
 
+    say!("[voidbox] Update complete!");
+    if failed > 0 {
+        say!("  {} failed", failed);
+    }
+
+    Ok(())
+}
+
+/// Download this host's build of a release into `dir`, check it against
+/// the SHA-256 published with it, and unpack the binary
+///
+/// Each archive is published with a `<archive>.sha256` file holding its
+/// checksum; a release without one isn't installed.
+fn download_release(
+    release: &self_update::update::Release,
+    dir: &Path,
+) -> Result<PathBuf, UpdateError> {
+    let target = self_update::get_target();
+    let asset = release
+        .assets
+        .iter()
+        .find(|asset| {
+            asset.name.contains(target)
+                && asset.name.contains(crate::APP_NAME)
+                && !asset.name.ends_with(".sha256")
+        })
+        .ok_or_else(|| {
+            UpdateError::Failed(format!("v{} has no build for {}", release.version, target))
+        })?;
+    let checksum_name = format!("{}.sha256", asset.name);
+    let checksum = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == checksum_name)
+        .ok_or_else(|| {
+            UpdateError::Failed(format!(
+                "v{} publishes no checksum for {}; not updating",
+                release.version, asset.name
+            ))
+        })?;
+
+    let archive = dir.join(&asset.name);
+    download_release_asset(&asset.download_url, &archive)?;
+    let checksum_file = dir.join(&checksum_name);
+    download_release_asset(&checksum.download_url, &checksum_file)?;
+
+    verify_checksum(&archive, &fs::read_to_string(&checksum_file)?)
+        .map_err(|e| UpdateError::Failed(format!("{}: {}", asset.name, e)))?;
+
+    self_update::Extract::from_source(&archive)
+        .extract_file(dir, crate::APP_NAME)
+        .map_err(|e| UpdateError::Failed(format!("Failed to unpack {}: {}", asset.name, e)))?;
+    let staged = dir.join(crate::APP_NAME);
+    fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    Ok(staged)
+}
+
+/// Check a file against a published checksum in `sha256sum` format (the
+/// hash, then optionally the file name)
+fn verify_checksum(file: &Path, published: &str) -> Result<(), String> {
+    let expected = published
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    if expected.len() != 64 {
+        return Err("the published checksum is not a SHA-256".into());
+    }
+    let actual = sha256_file(file).map_err(|e| e.to_string())?;
+    if actual != expected {
+        return Err(format!(
+            "does not match its published checksum (expected {}, got {})",
+            expected, actual
+        ));
+    }
+    Ok(())
+}
+
+/// Download a GitHub release asset to a file
+fn download_release_asset(url: &str, dest: &Path) -> Result<(), UpdateError> {
+    let mut response = http_get(url)
+        .header("Accept", "application/octet-stream")
+        .call()
+        .map_err(|e| UpdateError::Failed(format!("Download failed: {}", e)))?;
+    let mut file = fs::File::create(dest)?;
+    std::io::copy(&mut response.body_mut().as_reader(), &mut file)?;
     Ok(())
 }
 
 /// Self-update voidbox
 pub fn self_update(force: bool) -> Result<(), UpdateError> {
-    println!("[voidbox] Checking for updates...");
-    println!("  Installed: v{}", crate::VERSION);
+    say!("[voidbox] Checking for updates...");
+    say!("  Installed: v{}", crate::VERSION);
 
     let status = self_update::backends::github::Update::configure()
         .repo_owner(crate::SELF_UPDATE_OWNER)
@@ -202,7 +756,7 @@ pub fn self_update(force: bool) -> Result<(), UpdateError> {
         .map_err(|e| UpdateError::Failed(format!("Failed to check for updates: {}", e)))?;
 
     let latest_version = latest.version.trim_start_matches('v');
-    println!("  Latest:    v{}", latest_version);
+    say!("  Latest:    v{}", latest_version);
 
     let current = semver::Version::parse(crate::VERSION).ok();
     let latest_parsed = semver::Version::parse(latest_version).ok();
@@ -213,27 +767,83 @@ pub fn self_update(force: bool) -> Result<(), UpdateError> {
     };
 
     if !force && !is_newer {
-        println!("[voidbox] Already running latest version.");
+        say!("[voidbox] Already running latest version.");
         return Ok(());
     }
 
-    println!("[voidbox] Updating to v{}...", latest_version);
+    say!("[voidbox] Updating to v{}...", latest_version);
 
-    self_update::backends::github::Update::configure()
-        .repo_owner(crate::SELF_UPDATE_OWNER)
-        .repo_name(crate::SELF_UPDATE_REPO)
-        .bin_name(crate::APP_NAME)
-        .identifier(crate::APP_NAME)
-        .current_version(crate::VERSION)
-        .build()
-        .map_err(|e| UpdateError::Failed(format!("Failed to configure update: {}", e)))?
-        .update()
-        .map_err(|e| UpdateError::Failed(format!("Update failed: {}", e)))?;
+    // The copy voidbox installed itself as is replaced, not whichever one
+    // happens to be running; keep it so a bad update can be rolled back
+    let installed = paths::install_path();
+    let binary = if installed.exists() {
+        installed
+    } else {
+        std::env::current_exe()?
+    };
+    if let Err(e) = keep_backup(&binary) {
+        // A backup kept by an earlier update can still be rolled back to
+        let backup = backup_path(&binary);
+        if verify_binary(&backup).is_err() {
+            return Err(UpdateError::Failed(format!(
+                "Could not keep a backup of {} ({}); not updating without one",
+                binary.display(),
+                e
+            )));
+        }
+        say!(
+            "[voidbox] Warning: Could not refresh the backup ({}); keeping {}",
+            e,
+            backup.display()
+        );
+    }
 
-    println!(
+    // Staged next to the binary, so the swap is a rename
+    let staging = self_update::TempDir::new_in(binary.parent().unwrap_or(Path::new(".")))?;
+    let staged = download_release(&latest, staging.path())?;
+
+    // It must run and be the release it was downloaded as
+    let version = binary_version(&staged).map_err(|e| {
+        UpdateError::Failed(format!("v{} failed verification: {}", latest_version, e))
+    })?;
+    if version.trim_start_matches('v') != latest_version {
+        return Err(UpdateError::Failed(format!(
+            "v{} failed verification: it reports v{} instead",
+            latest_version, version
+        )));
+    }
+    fs::rename(&staged, &binary)?;
+
+    say!(
         "[voidbox] Updated to v{}! Please restart voidbox.",
         latest_version
     );
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_archive_must_match_published_checksum() {
+        let archive =
+            std::env::temp_dir().join(format!("voidbox-release-{}.tar.gz", std::process::id()));
+        fs::write(&archive, "release").unwrap();
+        let sha256 = sha256_file(&archive).unwrap();
+
+        assert!(verify_checksum(&archive, &sha256).is_ok());
+        assert!(
+            verify_checksum(
+                &archive,
+                &format!("{}  voidbox.tar.gz\n", sha256.to_uppercase())
+            )
+            .is_ok()
+        );
+        assert!(verify_checksum(&archive, &"0".repeat(64)).is_err());
+        assert!(verify_checksum(&archive, "").is_err());
+        assert!(verify_checksum(&archive, "not-a-checksum").is_err());
+        fs::remove_file(&archive).unwrap();
+    }
+}
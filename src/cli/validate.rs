@@ -0,0 +1,248 @@
+//! Validate command implementation
+//!
+//! `voidbox validate <file>` checks a manifest the way installs would,
+//! without installing or downloading anything, and reports every problem
+//! with the line and field it's in, for manifest authors. Bases named by
+//! `extends` are read when they're local files; a base at a URL isn't
+//! fetched, so only the manifest's own syntax and schema version are
+//! checked then.
+
+use crate::cli::json::{is_json, print_json};
+use crate::manifest::{
+    AppManifest, ManifestError, ManifestOrigin, check_schema_version, manifest_problems,
+    parse_manifest_from,
+};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+use toml::Table;
+use toml_edit::{ImDocument, TableLike};
+
+#[derive(Error, Debug)]
+pub enum ValidateError {
+    #[error("{0} problem(s) found")]
+    Invalid(usize),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// A problem found in a manifest file
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    /// Line of the file it's on, when known
+    pub line: Option<usize>,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+/// Check a manifest file and report what's wrong with it
+pub fn validate_file(path: &Path) -> Result<(), ValidateError> {
+    let content = fs::read_to_string(path)?;
+    let mut findings = check_manifest(path, &content);
+    findings.sort_by_key(|f| f.line.unwrap_or(usize::MAX));
+
+    if is_json() {
+        print_json(&findings);
+    } else if findings.is_empty() {
+        match remote_base(path, &content) {
+            Some(url) => say!(
+                "[voidbox] No problems in {}, but its base {} wasn't fetched, so only its syntax was checked",
+                path.display(),
+                url
+            ),
+            None => say!("[voidbox] {} is valid", path.display()),
+        }
+    } else {
+        for finding in &findings {
+            let location = match finding.line {
+                Some(line) => format!("{}:{}", path.display(), line),
+                None => path.display().to_string(),
+            };
+            match &finding.field {
+                Some(field) => say!("{}: {}: {}", location, field, finding.message),
+                None => say!("{}: {}", location, finding.message),
+            }
+        }
+    }
+
+    match findings.len() {
+        0 => Ok(()),
+        count => Err(ValidateError::Invalid(count)),
+    }
+}
+
+/// Every problem with a manifest's content
+fn check_manifest(path: &Path, content: &str) -> Vec<Finding> {
+    // Nothing else can be checked without valid TOML
+    let table: Table = match toml::from_str(content) {
+        Ok(table) => table,
+        Err(e) => return vec![parse_finding(content, &e)],
+    };
+    if let Err(e) = check_schema_version(&table) {
+        return vec![Finding {
+            line: field_line(content, "schema_version"),
+            field: Some("schema_version".into()),
+            message: e.to_string(),
+        }];
+    }
+    if remote_base(path, content).is_some() {
+        return Vec::new();
+    }
+
+    // Without a base, parse the text itself so errors have a position
+    let manifest = if table.contains_key("extends") {
+        parse_manifest_from(content, &ManifestOrigin::File(path.to_path_buf()))
+    } else {
+        toml::from_str::<AppManifest>(content).map_err(ManifestError::from)
+    };
+    let manifest = match manifest {
+        Ok(manifest) => manifest,
+        Err(ManifestError::ParseError(e)) => return vec![parse_finding(content, &e)],
+        Err(e) => {
+            return vec![Finding {
+                line: None,
+                field: None,
+                message: e.to_string(),
+            }];
+        }
+    };
+
+    manifest_problems(&manifest)
+        .into_iter()
+        .map(|problem| Finding {
+            line: field_line(content, &problem.field),
+            field: Some(problem.field),
+            message: problem.message,
+        })
+        .collect()
+}
+
+/// A TOML or type error, at the line it points to
+fn parse_finding(content: &str, error: &toml::de::Error) -> Finding {
+    let line = error.span().map(|span| {
+        content[..span.start.min(content.len())]
+            .matches('\n')
+            .count()
+            + 1
+    });
+    Finding {
+        line,
+        field: None,
+        message: error
+            .message()
+            .trim()
+            .lines()
+            .collect::<Vec<_>>()
+            .join("; "),
+    }
+}
+
+/// The URL of a base in the manifest's `extends` chain, if any
+fn remote_base(path: &Path, content: &str) -> Option<String> {
+    let mut origin = ManifestOrigin::File(path.to_path_buf());
+    let mut table: Table = toml::from_str(content).ok()?;
+    for _ in 0..=crate::manifest::MAX_EXTENDS_DEPTH {
+        let reference = table.get("extends")?.as_str()?;
+        origin = origin.resolve(reference);
+        if let ManifestOrigin::Url(url) = &origin {
+            return Some(url.clone());
+        }
+        table = toml::from_str(&origin.load().ok()?).ok()?;
+    }
+    None
+}
+
+/// Line a field is set on, or failing that its closest parent, from the
+/// positions the TOML parser keeps
+///
+/// `field` is a dotted path such as "binary.env.PATH"; "hooks.post_install[1]"
+/// is the second `[[hooks.post_install]]` table. Keys may contain dots
+/// themselves, so the longest key that exists is taken at each level.
+fn field_line(content: &str, field: &str) -> Option<usize> {
+    let document = ImDocument::parse(content).ok()?;
+    let segments: Vec<&str> = field.split('.').collect();
+    let mut table: &dyn TableLike = document.as_table();
+    let mut span = None;
+    let mut start = 0;
+
+    while start < segments.len() {
+        let Some((end, key, item, index)) = (start + 1..=segments.len()).rev().find_map(|end| {
+            let segment = segments[start..end].join(".");
+            let (name, index) = match segment.strip_suffix(']').and_then(|s| s.rsplit_once('[')) {
+                Some((name, index)) => (name.to_string(), index.parse::<usize>().ok()),
+                None => (segment, None),
+            };
+            let (key, item) = table.get_key_value(&name)?;
+            Some((end, key, item, index))
+        }) else {
+            break;
+        };
+        span = key.span().or_else(|| item.span()).or(span);
+        start = end;
+
+        let next: Option<&dyn TableLike> = match index {
+            None => item.as_table_like(),
+            Some(index) => {
+                if let Some(tables) = item.as_array_of_tables() {
+                    let element = tables.get(index)?;
+                    span = element.span().or(span);
+                    Some(element)
+                } else if let Some(array) = item.as_array() {
+                    let element = array.get(index)?;
+                    span = element.span().or(span);
+                    element.as_inline_table().map(|t| t as &dyn TableLike)
+                } else {
+                    None
+                }
+            }
+        };
+        match next {
+            Some(next) => table = next,
+            None => break,
+        }
+    }
+
+    span.map(|span| {
+        content[..span.start.min(content.len())]
+            .matches('\n')
+            .count()
+            + 1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+[app]
+name = "demo"
+
+[binary]
+name = "demo"
+env = { PATH = "/bin", "MY.VAR" = "1" }
+
+[desktop.localized.de]
+name = "Demo"
+
+[[hooks.post_install]]
+run = "true"
+
+[[hooks.post_install]]
+write = "etc/x"
+"#;
+
+    #[test]
+    fn fields_are_found_by_their_position() {
+        assert_eq!(field_line(MANIFEST, "app.name"), Some(3));
+        assert_eq!(field_line(MANIFEST, "binary.env.PATH"), Some(7));
+        assert_eq!(field_line(MANIFEST, "binary.env.MY.VAR"), Some(7));
+        assert_eq!(field_line(MANIFEST, "desktop.localized.de"), Some(9));
+        assert_eq!(field_line(MANIFEST, "hooks.post_install[1]"), Some(15));
+        // Unset fields point at their closest parent
+        assert_eq!(field_line(MANIFEST, "binary.args"), Some(5));
+        assert_eq!(field_line(MANIFEST, "hints.download_size"), None);
+    }
+}
@@ -0,0 +1,299 @@
+//! Repo verify command implementation
+//!
+//! `voidbox repo verify <repo>` checks every manifest a repository lists,
+//! current and earlier versions alike, the way installs would use them:
+//! each is interpolated, parsed and validated, its source is resolved to a
+//! download URL, and the download is fetched and hashed against the
+//! manifest's `sha256` and the known-good checksum database; a manifest
+//! with sources for several architectures has each of them checked. With
+//! `--headers-only` only the URL's headers are requested, which is quicker
+//! but can't check hashes. Meant for a repository's CI: the report is
+//! printed as JSON with `--json` or written with `--report`, and the
+//! command fails if any entry does.
+
+use crate::cli::install::resolve_download;
+use crate::cli::json::{is_json, print_json};
+use crate::cli::table::Table;
+use crate::manifest::{
+    AppManifest, ChecksumCheck, ManifestError, arch_sources, for_arch, load_checksum_db,
+    load_index, resolve_index_app, resolve_index_app_version, validate_manifest,
+};
+use crate::storage::{http_get, http_head};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Time allowed for each header request
+const HEAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Error, Debug)]
+pub enum VerifyError {
+    #[error("{failed} of {checked} manifest(s) failed verification")]
+    Failed { failed: usize, checked: usize },
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] crate::manifest::ManifestError),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Outcome of one manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyStatus {
+    Ok,
+    /// Usable, but something couldn't be checked
+    Warning,
+    Failed,
+}
+
+/// Verification of one manifest a repository lists
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifiedManifest {
+    pub app: String,
+    /// Version the index lists the manifest under
+    pub version: Option<String>,
+    /// Architecture checked, for manifests with sources for several
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+    /// Manifest path relative to the index
+    pub manifest: String,
+    /// Download the source resolved to
+    pub url: Option<String>,
+    /// Size of the download, from its headers or as fetched
+    pub size: Option<u64>,
+    /// SHA-256 of the download (not with `--headers-only`)
+    pub sha256: Option<String>,
+    pub status: VerifyStatus,
+    pub problems: Vec<String>,
+}
+
+impl VerifiedManifest {
+    fn fail(&mut self, problem: impl Into<String>) {
+        self.status = VerifyStatus::Failed;
+        self.problems.push(problem.into());
+    }
+
+    fn warn(&mut self, problem: impl Into<String>) {
+        if self.status == VerifyStatus::Ok {
+            self.status = VerifyStatus::Warning;
+        }
+        self.problems.push(problem.into());
+    }
+}
+
+/// Report of `voidbox repo verify`
+#[derive(Debug, Serialize)]
+pub struct VerifyReport {
+    pub repository: String,
+    pub headers_only: bool,
+    pub checked: usize,
+    pub failed: usize,
+    pub manifests: Vec<VerifiedManifest>,
+}
+
+/// Verify every manifest of a repository and report the results
+pub fn verify_repository(
+    location: &str,
+    headers_only: bool,
+    report_path: Option<&Path>,
+) -> Result<(), VerifyError> {
+    say!("[voidbox] Verifying {}...", location);
+    let index = load_index(location)?;
+
+    let mut manifests = Vec::new();
+    for (app, entry) in &index.apps {
+        let mut check = |version: Option<&String>, file: &String, current: bool| {
+            say!(
+                "[voidbox] Checking {} {}...",
+                app,
+                version.map(String::as_str).unwrap_or("(current)")
+            );
+            let mut result = VerifiedManifest {
+                app: app.clone(),
+                version: version.cloned(),
+                arch: None,
+                manifest: file.clone(),
+                url: None,
+                size: None,
+                sha256: None,
+                status: VerifyStatus::Ok,
+                problems: Vec::new(),
+            };
+            let manifest = match version {
+                Some(version) if !current => {
+                    resolve_index_app_version(location, &index, app, version).and_then(|m| {
+                        m.ok_or_else(|| ManifestError::NotFound(format!("{} {}", app, version)))
+                    })
+                }
+                _ => resolve_index_app(location, &index, app),
+            };
+            match manifest.and_then(|m| validate_manifest(&m).map(|_| m)) {
+                Ok(manifest) if !manifest.sources.is_empty() => {
+                    for arch in arch_sources(&manifest).into_keys() {
+                        let mut result = result.clone();
+                        match for_arch(&manifest, &arch) {
+                            Ok(manifest) => verify_source(&manifest, headers_only, &mut result),
+                            Err(e) => result.fail(e.to_string()),
+                        }
+                        result.arch = Some(arch);
+                        manifests.push(result);
+                    }
+                    return;
+                }
+                Ok(manifest) => verify_source(&manifest, headers_only, &mut result),
+                Err(e) => result.fail(e.to_string()),
+            }
+            manifests.push(result);
+        };
+        check(entry.version.as_ref(), &entry.manifest, true);
+        for (version, file) in &entry.versions {
+            check(Some(version), file, false);
+        }
+    }
+
+    let report = VerifyReport {
+        repository: location.to_string(),
+        headers_only,
+        checked: manifests.len(),
+        failed: manifests
+            .iter()
+            .filter(|m| m.status == VerifyStatus::Failed)
+            .count(),
+        manifests,
+    };
+
+    if let Some(path) = report_path {
+        let content = serde_json::to_string_pretty(&report).map_err(std::io::Error::other)?;
+        std::fs::write(path, content + "\n")?;
+        say!("[voidbox] Report written to {}", path.display());
+    }
+    if is_json() {
+        print_json(&report);
+    } else {
+        print_report(&report);
+    }
+
+    if report.failed > 0 {
+        return Err(VerifyError::Failed {
+            failed: report.failed,
+            checked: report.checked,
+        });
+    }
+    Ok(())
+}
+
+/// Resolve a manifest's download and check it
+fn verify_source(manifest: &AppManifest, headers_only: bool, result: &mut VerifiedManifest) {
+    let (version, url) = match resolve_download(manifest) {
+        Ok(Some(download)) => download,
+        Ok(None) => {
+            result.warn("local source; nothing to download");
+            return;
+        }
+        Err(e) => {
+            result.fail(format!("source can't be resolved: {}", e));
+            return;
+        }
+    };
+    result.url = Some(url.clone());
+
+    if headers_only {
+        match http_head(&url)
+            .config()
+            .timeout_global(Some(HEAD_TIMEOUT))
+            .build()
+            .call()
+        {
+            Ok(response) => {
+                result.size = response
+                    .headers()
+                    .get("content-length")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse().ok());
+            }
+            Err(e) => result.fail(format!("{} can't be fetched: {}", url, e)),
+        }
+        return;
+    }
+
+    let (sha256, size) = match hash_download(&url) {
+        Ok(hashed) => hashed,
+        Err(e) => {
+            result.fail(format!("{} can't be fetched: {}", url, e));
+            return;
+        }
+    };
+    result.sha256 = Some(sha256.clone());
+    result.size = Some(size);
+
+    if let Some(expected) = manifest.source.sha256() {
+        if !expected.eq_ignore_ascii_case(&sha256) {
+            result.fail(format!(
+                "checksum mismatch: the manifest expects {}",
+                expected.to_ascii_lowercase()
+            ));
+        }
+        return;
+    }
+    match load_checksum_db().check(&url, &version, manifest.repository.as_deref(), &sha256) {
+        ChecksumCheck::Verified => {}
+        ChecksumCheck::Mismatch { expected } => result.fail(format!(
+            "checksum mismatch: the known-good database expects {}",
+            expected
+        )),
+        ChecksumCheck::Unknown => {
+            result.warn("no checksum in the manifest or the known-good database")
+        }
+    }
+}
+
+/// Fetch a download, hashing it on the way without storing it
+fn hash_download(url: &str) -> Result<(String, u64), String> {
+    let mut response = http_get(url).call().map_err(|e| e.to_string())?;
+    let mut reader = response.body_mut().as_reader();
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 65536];
+    let mut size = 0;
+    loop {
+        let read = reader.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok((hex::encode(hasher.finalize()), size))
+}
+
+/// Print a report as a table, with each manifest's problems below it
+fn print_report(report: &VerifyReport) {
+    let mut table = Table::new(&["APP", "VERSION", "STATUS", "PROBLEMS"]);
+    for manifest in &report.manifests {
+        let status = match manifest.status {
+            VerifyStatus::Ok => "ok",
+            VerifyStatus::Warning => "warning",
+            VerifyStatus::Failed => "failed",
+        };
+        let mut version = manifest.version.clone().unwrap_or_else(|| "-".into());
+        if let Some(arch) = &manifest.arch {
+            version = format!("{} ({})", version, arch);
+        }
+        table.row(vec![
+            manifest.app.clone(),
+            version,
+            status.to_string(),
+            manifest.problems.join("; "),
+        ]);
+    }
+    table.print();
+    say!(
+        "[voidbox] Checked {} manifest(s): {} failed",
+        report.checked,
+        report.failed
+    );
+}
@@ -0,0 +1,393 @@
+//! Versions command implementation
+//!
+//! Lists the versions an app's sources offer: the releases of a GitHub
+//! source, and the current and earlier versions a repository lists for the
+//! app. `voidbox install <name>@<version>` installs one of them and pins the
+//! app there, so updates leave it alone.
+//!
+//! With `--side-by-side` the version is installed next to the main install
+//! instead, as its own app named `<name>@<version>` with a desktop entry
+//! labelled by the version. Its data is kept separately unless
+//! `--shared-data`, which points it at the main install's data directory.
+
+use crate::cli::install::resolve_source;
+use crate::cli::json::{is_json, print_json};
+use crate::cli::payload::prepare_app_data;
+use crate::cli::repo::repository_index;
+use crate::cli::rollback::previous_version;
+use crate::cli::table::Table;
+use crate::cli::update::version_key;
+use crate::manifest::{AppManifest, SourceConfig, resolve_index_app_version, split_tap_app};
+use crate::settings::load_config;
+use crate::storage::{create_data_dir, http_get, paths};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VersionsError {
+    #[error("Install error: {0}")]
+    InstallError(#[from] crate::cli::InstallError),
+
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] crate::manifest::ManifestError),
+
+    #[error("Settings error: {0}")]
+    SettingsError(#[from] crate::settings::SettingsError),
+
+    #[error("{app} has no version {version}; see 'voidbox versions {app}'")]
+    NotFound { app: String, version: String },
+
+    #[error("{0}")]
+    Failed(String),
+}
+
+/// Where a side-by-side install keeps its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotData {
+    /// Its own data directory
+    Separate,
+    /// The main install's data directory
+    Shared,
+}
+
+/// Most releases the GitHub API returns in one page
+const RELEASES_PER_PAGE: usize = 100;
+
+/// A version an app's sources offer
+#[derive(Debug, Clone, Serialize)]
+pub struct AvailableVersion {
+    pub version: String,
+    /// Release date (YYYY-MM-DD), when the source has one
+    pub released: Option<String>,
+    pub prerelease: bool,
+    /// Where it comes from: a GitHub repository, a voidbox repository or
+    /// the manifest itself
+    pub source: String,
+}
+
+#[derive(Deserialize)]
+struct GitHubRelease {
+    tag_name: String,
+    #[serde(default)]
+    published_at: Option<String>,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    draft: bool,
+}
+
+/// Published releases of a GitHub repository, newest first
+fn github_releases(owner: &str, repo: &str) -> Result<Vec<GitHubRelease>, VersionsError> {
+    let api_url = format!(
+        "https://api.github.com/repos/{}/{}/releases?per_page={}",
+        owner, repo, RELEASES_PER_PAGE
+    );
+
+    let mut resp = http_get(&api_url)
+        .call()
+        .map_err(|e| VersionsError::Failed(format!("GitHub API error: {}", e)))?;
+
+    let body = resp
+        .body_mut()
+        .read_to_string()
+        .map_err(|e| VersionsError::Failed(format!("Failed to read response: {}", e)))?;
+
+    let releases: Vec<GitHubRelease> = serde_json::from_str(&body)
+        .map_err(|e| VersionsError::Failed(format!("Failed to parse GitHub response: {}", e)))?;
+
+    Ok(releases.into_iter().filter(|r| !r.draft).collect())
+}
+
+fn same_version(a: &str, b: &str) -> bool {
+    a.trim_start_matches('v') == b.trim_start_matches('v')
+}
+
+/// Split `<name>@<version>` into the source and the version asked for
+///
+/// URLs and existing paths are taken as they are, since they may contain
+/// an `@` of their own.
+pub fn split_version(source: &str) -> (&str, Option<&str>) {
+    let is_url = source.starts_with("http://") || source.starts_with("https://");
+    if is_url || Path::new(source).exists() {
+        return (source, None);
+    }
+    match source.rsplit_once('@') {
+        Some((name, version))
+            if !name.is_empty() && !version.is_empty() && !version.contains('/') =>
+        {
+            (name, Some(version))
+        }
+        _ => (source, None),
+    }
+}
+
+/// Repositories that may list an app: the tap it was named through, or
+/// the configured repositories
+fn candidate_repositories(source: &str) -> Result<Vec<String>, VersionsError> {
+    Ok(match split_tap_app(source) {
+        Some((tap, _)) => vec![tap.to_string()],
+        None => load_config()?.repositories,
+    })
+}
+
+/// Versions the repositories list for an app
+fn repository_versions(source: &str, app: &str) -> Result<Vec<AvailableVersion>, VersionsError> {
+    let mut versions = Vec::new();
+    for location in candidate_repositories(source)? {
+        let index = match repository_index(&location) {
+            Ok(index) => index,
+            Err(e) => {
+                say!(
+                    "[voidbox] Warning: Could not load repository {}: {}",
+                    location,
+                    e
+                );
+                continue;
+            }
+        };
+        let Some(entry) = index.apps.get(app) else {
+            continue;
+        };
+        for version in entry.version.iter().chain(entry.versions.keys()) {
+            versions.push(AvailableVersion {
+                version: version.trim_start_matches('v').to_string(),
+                released: None,
+                prerelease: false,
+                source: location.clone(),
+            });
+        }
+    }
+    Ok(versions)
+}
+
+/// Versions an app's sources offer, newest first
+pub fn available_versions(
+    source: &str,
+    manifest: &AppManifest,
+) -> Result<Vec<AvailableVersion>, VersionsError> {
+    let mut versions = Vec::new();
+    if let SourceConfig::Github { owner, repo, .. } = &manifest.source {
+        for release in github_releases(owner, repo)? {
+            versions.push(AvailableVersion {
+                version: release.tag_name.trim_start_matches('v').to_string(),
+                released: release.published_at.map(|d| d.chars().take(10).collect()),
+                prerelease: release.prerelease,
+                source: format!("github.com/{}/{}", owner, repo),
+            });
+        }
+    }
+
+    let mut listed = repository_versions(source, &manifest.app.name)?;
+    // Other sources only offer the version their manifest names
+    if !matches!(manifest.source, SourceConfig::Github { .. })
+        && let Some(version) = &manifest.app.version
+    {
+        listed.push(AvailableVersion {
+            version: version.trim_start_matches('v').to_string(),
+            released: None,
+            prerelease: false,
+            source: "manifest".to_string(),
+        });
+    }
+    for available in listed {
+        if !versions.iter().any(|v| v.version == available.version) {
+            versions.push(available);
+        }
+    }
+
+    versions.sort_by_key(|v| std::cmp::Reverse(version_key(&v.version)));
+    Ok(versions)
+}
+
+/// The manifest that installs one version of an app
+///
+/// A repository's manifest for that version is used when one lists it, and
+/// a GitHub source is pointed at the matching release. The version is
+/// recorded in the manifest so the install is checked against it.
+pub fn manifest_at_version(
+    source: &str,
+    manifest: &AppManifest,
+    version: &str,
+) -> Result<AppManifest, VersionsError> {
+    let wanted = version.trim_start_matches('v');
+    let not_found = || VersionsError::NotFound {
+        app: manifest.app.name.clone(),
+        version: wanted.to_string(),
+    };
+
+    let mut listed = None;
+    for location in candidate_repositories(source)? {
+        if let Ok(index) = repository_index(&location)
+            && let Some(found) =
+                resolve_index_app_version(&location, &index, &manifest.app.name, wanted)?
+        {
+            listed = Some(found);
+            break;
+        }
+    }
+
+    let from_repository = listed.is_some();
+    let mut pinned = listed.unwrap_or_else(|| manifest.clone());
+    if let SourceConfig::Github {
+        owner,
+        repo,
+        tag,
+        sha256,
+        ..
+    } = &mut pinned.source
+    {
+        let release = github_releases(owner, repo)?
+            .into_iter()
+            .find(|r| same_version(&r.tag_name, wanted))
+            .ok_or_else(not_found)?;
+        // The manifest's checksum is of the release it names, not this one
+        if tag.as_deref() != Some(release.tag_name.as_str()) {
+            *sha256 = None;
+        }
+        *tag = Some(release.tag_name);
+    } else if !from_repository
+        && !pinned
+            .app
+            .version
+            .as_deref()
+            .is_some_and(|v| same_version(v, wanted))
+    {
+        return Err(not_found());
+    }
+
+    pinned.app.version = Some(wanted.to_string());
+    Ok(pinned)
+}
+
+/// Name of the side-by-side install of one version of an app
+pub fn slot_name(app: &str, version: &str) -> String {
+    format!("{}@{}", app, version)
+}
+
+/// Name of the app a side-by-side install was made from, if the manifest is
+/// one
+pub fn slot_app(manifest: &AppManifest) -> Option<&str> {
+    let (app, version) = manifest.app.name.rsplit_once('@')?;
+    (!app.is_empty() && manifest.app.version.as_deref() == Some(version)).then_some(app)
+}
+
+/// A manifest installing its version next to the app's main install, under
+/// the slot name and with names and IDs that don't clash with it
+pub fn slot_manifest(manifest: &AppManifest) -> AppManifest {
+    let version = manifest.app.version.clone().unwrap_or_default();
+    let mut slot = manifest.clone();
+    slot.app.name = slot_name(&manifest.app.name, &version);
+    slot.app.display_name = format!("{} {}", manifest.app.display_name, version);
+    for strings in slot.desktop.localized.values_mut() {
+        if let Some(name) = &mut strings.name {
+            *name = format!("{} {}", name, version);
+        }
+    }
+
+    // IDs must stay valid reverse-DNS names
+    let id_suffix: String = version
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    slot.app.id = manifest
+        .app
+        .id
+        .as_ref()
+        .map(|id| format!("{}.v{}", id, id_suffix));
+    slot.desktop.dbus_name = None;
+    slot
+}
+
+/// Point a side-by-side install's data directory at the main install's,
+/// unless it already has data of its own
+pub fn share_slot_data(slot: &str, app: &str) -> std::io::Result<()> {
+    let link = paths::app_data_dir(slot);
+    if link.symlink_metadata().is_ok() {
+        return Ok(());
+    }
+
+    let rootfs = paths::app_rootfs_dir(app);
+    let target = if rootfs.exists() {
+        prepare_app_data(app, &rootfs)?
+    } else {
+        let data_dir = paths::app_data_dir(app);
+        std::fs::create_dir_all(paths::app_dir(app))?;
+        if !data_dir.exists() {
+            create_data_dir(&data_dir)?;
+        }
+        data_dir
+    };
+    std::fs::create_dir_all(paths::app_dir(slot))?;
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// Print the versions available for an app
+pub fn show_versions(source: &str) -> Result<(), VersionsError> {
+    let manifest = resolve_source(source)?;
+    let versions = available_versions(source, &manifest)?;
+    if is_json() {
+        print_json(&versions);
+        return Ok(());
+    }
+
+    if versions.is_empty() {
+        say!(
+            "[voidbox] {} has no versions to choose from",
+            manifest.app.display_name
+        );
+        return Ok(());
+    }
+
+    let apps = crate::cli::get_installed_apps().unwrap_or_default();
+    let installed = apps
+        .iter()
+        .find(|a| paths::same_app(&a.name, &manifest.app.name));
+    let previous = previous_version(&manifest.app.name).flatten();
+
+    let mut table = Table::new(&["VERSION", "RELEASED", "FROM", "NOTE"]);
+    for available in &versions {
+        let mut notes = Vec::new();
+        if let Some(app) = installed
+            && app
+                .version
+                .as_deref()
+                .is_some_and(|v| same_version(v, &available.version))
+        {
+            notes.push(if app.pinned {
+                "installed, pinned"
+            } else {
+                "installed"
+            });
+        }
+        let slot = slot_name(&manifest.app.name, &available.version);
+        if apps.iter().any(|a| paths::same_app(&a.name, &slot)) {
+            notes.push("side by side");
+        }
+        if previous
+            .as_deref()
+            .is_some_and(|v| same_version(v, &available.version))
+        {
+            notes.push("previous");
+        }
+        if available.prerelease {
+            notes.push("pre-release");
+        }
+        table.row(vec![
+            available.version.clone(),
+            available.released.clone().unwrap_or_default(),
+            available.source.clone(),
+            notes.join(", "),
+        ]);
+    }
+    table.print();
+
+    if !crate::is_plain() {
+        say!();
+        say!(
+            "Install one with: voidbox install {}@<version>",
+            manifest.app.name
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,55 @@
+//! Webhook command implementation
+
+use crate::events::{EventKind, EventPayload, WebhookError, send_webhook};
+use crate::settings::load_config;
+
+/// Fire a test event at every configured webhook and report the results
+pub fn test_webhooks() -> Result<(), WebhookError> {
+    let config = load_config()?;
+
+    if config.webhooks.is_empty() {
+        say!("[voidbox] No webhooks configured.");
+        say!();
+        say!(
+            "Add a [[webhooks]] entry with a url or command to {}",
+            crate::paths::config_path().display()
+        );
+        return Ok(());
+    }
+
+    let payload = EventPayload::new(
+        EventKind::Test,
+        None,
+        None,
+        "Test event from voidbox webhook test",
+    );
+
+    let mut failed = 0;
+    for hook in &config.webhooks {
+        if !crate::is_quiet() {
+            print!("  {} ... ", hook.target());
+            std::io::Write::flush(&mut std::io::stdout()).ok();
+        }
+
+        match send_webhook(hook, &payload) {
+            Ok(()) => say!("ok"),
+            Err(e) => {
+                say!("failed ({})", e);
+                failed += 1;
+            }
+        }
+    }
+
+    say!();
+    if failed > 0 {
+        say!(
+            "[voidbox] {} of {} webhook(s) failed.",
+            failed,
+            config.webhooks.len()
+        );
+    } else {
+        say!("[voidbox] All webhooks delivered.");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+//! Activation of an already-running app instance
+
+use crate::manifest::AppManifest;
+use std::process::{Command, Stdio};
+
+/// Try to bring an already-running instance to the front
+///
+/// Uses the manifest's D-Bus activation name (explicit or derived from the
+/// app ID) with org.freedesktop.Application, otherwise falls back to focusing the
+/// window by its WM class. Returns true if activation succeeded.
+pub fn activate_running_instance(manifest: &AppManifest, uris: &[String]) -> bool {
+    if let Some(dbus_name) = manifest.dbus_name()
+        && activate_dbus(&dbus_name, uris)
+    {
+        return true;
+    }
+
+    focus_window(&window_class(manifest))
+}
+
+/// WM class of an app's windows
+pub fn window_class(manifest: &AppManifest) -> String {
+    manifest
+        .desktop
+        .wm_class
+        .clone()
+        .unwrap_or_else(|| manifest.app.name.clone())
+}
+
+/// Count an app's open windows by WM class
+///
+/// Returns `None` when neither wmctrl nor xdotool is available; windows of
+/// native Wayland clients aren't visible to either.
+pub fn window_count(manifest: &AppManifest) -> Option<usize> {
+    let wm_class = window_class(manifest);
+
+    if which::which("wmctrl").is_ok() {
+        let output = Command::new("wmctrl")
+            .arg("-lx")
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        // Columns: window ID, desktop, instance.class, host, title
+        let count = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_whitespace().nth(2))
+            .filter(|class| {
+                class
+                    .split('.')
+                    .any(|part| part.eq_ignore_ascii_case(&wm_class))
+            })
+            .count();
+        return Some(count);
+    }
+
+    if which::which("xdotool").is_ok() {
+        let output = Command::new("xdotool")
+            .args(["search", "--onlyvisible", "--class", &wm_class])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        return Some(String::from_utf8_lossy(&output.stdout).lines().count());
+    }
+
+    None
+}
+
+/// Call org.freedesktop.Application.Activate (or Open when URIs are given)
+fn activate_dbus(dbus_name: &str, uris: &[String]) -> bool {
+    let object_path = format!("/{}", dbus_name.replace(['.', '-'], "/"));
+
+    let mut cmd = Command::new("gdbus");
+    cmd.args([
+        "call",
+        "--session",
+        "--dest",
+        dbus_name,
+        "--object-path",
+        &object_path,
+    ]);
+
+    let platform_data = platform_data();
+    if uris.is_empty() {
+        cmd.args([
+            "--method",
+            "org.freedesktop.Application.Activate",
+            &platform_data,
+        ]);
+    } else {
+        let uri_list = uris
+            .iter()
+            .map(|u| format!("'{}'", u.replace('\'', "\\'")))
+            .collect::<Vec<_>>()
+            .join(", ");
+        cmd.args([
+            "--method",
+            "org.freedesktop.Application.Open",
+            &format!("[{}]", uri_list),
+            &platform_data,
+        ]);
+    }
+
+    cmd.stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Platform data for an org.freedesktop.Application call: the activation
+/// token (Wayland) and startup ID (X11) the launcher gave voidbox, which
+/// let the running instance raise its window past focus-stealing prevention
+fn platform_data() -> String {
+    let entries: Vec<String> = [
+        ("activation-token", "XDG_ACTIVATION_TOKEN"),
+        ("desktop-startup-id", "DESKTOP_STARTUP_ID"),
+    ]
+    .into_iter()
+    .filter_map(|(key, var)| {
+        let value = std::env::var(var).ok()?;
+        Some(format!("'{}': <'{}'>", key, value.replace('\'', "\\'")))
+    })
+    .collect();
+    format!("{{{}}}", entries.join(", "))
+}
+
+/// Focus a window by WM class using whichever tool is available
+fn focus_window(wm_class: &str) -> bool {
+    let attempts: [(&str, Vec<&str>); 2] = [
+        ("wmctrl", vec!["-x", "-a", wm_class]),
+        (
+            "xdotool",
+            vec!["search", "--class", wm_class, "windowactivate"],
+        ),
+    ];
+
+    for (tool, args) in attempts {
+        if which::which(tool).is_err() {
+            continue;
+        }
+        let ok = Command::new(tool)
+            .args(&args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+        if ok {
+            return true;
+        }
+    }
+
+    false
+}
@@ -1,8 +1,11 @@
 //! Desktop entry (.desktop file) generation
 
+use super::mark_desktop_changed;
 use crate::manifest::AppManifest;
 use crate::storage::paths;
 use std::fs;
+use std::path::Path;
+use std::process::Command;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,7 +16,7 @@ pub enum DesktopError {
 
 /// Generate a .desktop file for an app
 pub fn create_desktop_entry(manifest: &AppManifest) -> Result<(), DesktopError> {
-    let desktop_path = paths::app_desktop_path(&manifest.app.name);
+    let desktop_path = paths::desktop_entry_path(&manifest.desktop_id());
 
     if let Some(parent) = desktop_path.parent() {
         fs::create_dir_all(parent)?;
@@ -27,8 +30,9 @@ pub fn create_desktop_entry(manifest: &AppManifest) -> Result<(), DesktopError>
         "application-x-executable".to_string()
     };
 
+    // "Application" isn't a registered category, so validators reject it
     let categories = if manifest.desktop.categories.is_empty() {
-        "Application;".to_string()
+        "Utility;".to_string()
     } else {
         format!("{};", manifest.desktop.categories.join(";"))
     };
@@ -39,6 +43,10 @@ pub fn create_desktop_entry(manifest: &AppManifest) -> Result<(), DesktopError>
         .clone()
         .unwrap_or_else(|| manifest.app.name.clone());
 
+    // Launchers show a busy cursor and hand the app an activation token,
+    // which lets its first window take focus
+    let startup_notify = manifest.desktop.startup_notify.unwrap_or(true);
+
     let keywords = if manifest.desktop.keywords.is_empty() {
         String::new()
     } else {
@@ -51,38 +59,123 @@ pub fn create_desktop_entry(manifest: &AppManifest) -> Result<(), DesktopError>
         format!("MimeType={}\n", manifest.desktop.mime_types.join(";"))
     };
 
+    // Untranslated value first, then one line per locale
+    let mut names = format!("Name={}\n", escape_value(&manifest.app.display_name));
+    let mut comments = format!("Comment={}\n", escape_value(&manifest.app.description));
+    let mut generic_names = manifest
+        .desktop
+        .generic_name
+        .as_deref()
+        .map(|g| format!("GenericName={}\n", escape_value(g)))
+        .unwrap_or_default();
+
+    for (locale, strings) in &manifest.desktop.localized {
+        if let Some(name) = &strings.name {
+            names.push_str(&format!("Name[{}]={}\n", locale, escape_value(name)));
+        }
+        if let Some(comment) = &strings.comment {
+            comments.push_str(&format!("Comment[{}]={}\n", locale, escape_value(comment)));
+        }
+        if let Some(generic_name) = &strings.generic_name {
+            generic_names.push_str(&format!(
+                "GenericName[{}]={}\n",
+                locale,
+                escape_value(generic_name)
+            ));
+        }
+    }
+
     let content = format!(
         r#"[Desktop Entry]
-Name={}
-Comment={}
+{}{}{}TryExec={}
 Exec=voidbox run {}
 Icon={}
 Terminal=false
 Type=Application
 Categories={}
+StartupNotify={}
 StartupWMClass={}
 {}{}
 "#,
-        manifest.app.display_name,
-        manifest.app.description,
-        manifest.app.name,
+        names,
+        generic_names,
+        comments,
+        paths::install_path().display(),
+        paths::app_slug(&manifest.app.name),
         icon_value,
         categories,
+        startup_notify,
         wm_class,
         keywords,
         mime_types,
     );
 
-    fs::write(&desktop_path, content)?;
+    // Skip the write (and the desktop database refresh) if nothing changed
+    let previous = fs::read_to_string(&desktop_path).ok();
+    if previous.as_deref() == Some(content.as_str()) {
+        return Ok(());
+    }
+
+    fs::write(&desktop_path, &content)?;
+    validate_desktop_entry(&desktop_path);
+
+    // Apps that gained an app ID drop the entry under their old slug-based ID
+    let legacy_path = paths::app_desktop_path(&manifest.app.name);
+    if legacy_path != desktop_path && legacy_path.exists() {
+        fs::remove_file(&legacy_path)?;
+    }
+
+    let had_mime = previous.is_some_and(|p| p.contains("\nMimeType="));
+    mark_desktop_changed(had_mime || !manifest.desktop.mime_types.is_empty());
 
     Ok(())
 }
 
-/// Remove a .desktop file for an app
-pub fn remove_desktop_entry(app_name: &str) -> Result<(), DesktopError> {
-    let desktop_path = paths::app_desktop_path(app_name);
-    if desktop_path.exists() {
-        fs::remove_file(desktop_path)?;
+/// Check a written entry with desktop-file-validate, if it's installed
+///
+/// Problems are only reported: desktop environments still show most
+/// entries the validator complains about.
+fn validate_desktop_entry(path: &Path) {
+    let Ok(output) = Command::new("desktop-file-validate").arg(path).output() else {
+        return;
+    };
+    if output.status.success() {
+        return;
+    }
+    say!(
+        "[voidbox] Warning: desktop-file-validate rejected {}:",
+        path.display()
+    );
+    for line in String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .chain(String::from_utf8_lossy(&output.stderr).lines())
+    {
+        say!("  {}", line.trim());
+    }
+}
+
+/// Escape a string value for a desktop entry (newlines, tabs, backslashes)
+fn escape_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+}
+
+/// Remove the .desktop file(s) for an app, under its app ID and its slug
+pub fn remove_desktop_entry(app_name: &str, app_id: Option<&str>) -> Result<(), DesktopError> {
+    let mut desktop_paths = vec![paths::app_desktop_path(app_name)];
+    desktop_paths.extend(app_id.map(paths::desktop_entry_path));
+
+    for desktop_path in desktop_paths {
+        if desktop_path.exists() {
+            let had_mime = fs::read_to_string(&desktop_path)
+                .map(|c| c.contains("\nMimeType="))
+                .unwrap_or(false);
+            fs::remove_file(desktop_path)?;
+            mark_desktop_changed(had_mime);
+        }
     }
     Ok(())
 }
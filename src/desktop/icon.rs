@@ -1,7 +1,9 @@
 //! Icon extraction and management
 
+use super::mark_icons_changed;
 use crate::storage::paths;
 use std::fs;
+use std::path::Path;
 use thiserror::Error;
 use walkdir::WalkDir;
 
@@ -28,7 +30,7 @@ pub fn extract_icon(app_name: &str, icon_filename: Option<&str>) -> Result<(), I
         for entry in WalkDir::new(&app_rootfs).max_depth(5) {
             if let Ok(entry) = entry {
                 if entry.file_name().to_string_lossy() == filename {
-                    fs::copy(entry.path(), &icon_dest)?;
+                    install_icon(entry.path(), &icon_dest)?;
                     return Ok(());
                 }
             }
@@ -51,7 +53,7 @@ pub fn extract_icon(app_name: &str, icon_filename: Option<&str>) -> Result<(), I
             let name = entry.file_name().to_string_lossy().to_lowercase();
             for pattern in &patterns {
                 if name == pattern.to_lowercase() {
-                    fs::copy(entry.path(), &icon_dest)?;
+                    install_icon(entry.path(), &icon_dest)?;
                     return Ok(());
                 }
             }
@@ -62,11 +64,23 @@ pub fn extract_icon(app_name: &str, icon_filename: Option<&str>) -> Result<(), I
     Ok(())
 }
 
+/// Copy an icon into place unless the existing one is identical
+fn install_icon(src: &Path, dest: &Path) -> Result<(), IconError> {
+    let new = fs::read(src)?;
+    if fs::read(dest).ok().as_deref() == Some(new.as_slice()) {
+        return Ok(());
+    }
+    fs::write(dest, new)?;
+    mark_icons_changed();
+    Ok(())
+}
+
 /// Remove icon for an app
 pub fn remove_icon(app_name: &str) -> Result<(), IconError> {
     let icon_path = paths::app_icon_path(app_name);
     if icon_path.exists() {
         fs::remove_file(icon_path)?;
+        mark_icons_changed();
     }
     Ok(())
 }
@@ -0,0 +1,115 @@
+//! Desktop integration as a whole
+//!
+//! An app's icon, desktop entry (with its MIME associations) and command
+//! wrapper are written together. When one of them fails, the ones already
+//! written are put back as they were, so an app is either on the desktop
+//! or not at all, never half-registered.
+
+use super::{
+    DesktopError, IconError, SymlinkError, create_app_wrapper, create_desktop_entry, extract_icon,
+    mark_desktop_changed, mark_icons_changed, write_window_rules,
+};
+use crate::manifest::AppManifest;
+use crate::storage::paths;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum IntegrationError {
+    #[error(transparent)]
+    Icon(#[from] IconError),
+
+    #[error(transparent)]
+    DesktopEntry(#[from] DesktopError),
+
+    #[error(transparent)]
+    Wrapper(#[from] SymlinkError),
+}
+
+/// Pieces of an app's integration to leave alone, e.g. kept from a
+/// conflicting install
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeepIntegration {
+    pub desktop_entry: bool,
+    pub wrapper: bool,
+}
+
+/// A file as it was before integration wrote it
+struct SavedFile {
+    path: PathBuf,
+    content: Option<Vec<u8>>,
+}
+
+impl SavedFile {
+    fn save(path: PathBuf) -> Self {
+        let content = fs::read(&path).ok();
+        Self { path, content }
+    }
+
+    fn restore(&self) -> io::Result<()> {
+        match &self.content {
+            Some(content) => fs::write(&self.path, content),
+            None if fs::symlink_metadata(&self.path).is_ok() => fs::remove_file(&self.path),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Put an app on the desktop: icon, desktop entry and command wrapper
+///
+/// If a piece fails, those already written are restored before the error
+/// is returned.
+pub fn integrate_app(
+    manifest: &AppManifest,
+    keep: KeepIntegration,
+) -> Result<(), IntegrationError> {
+    let app_name = &manifest.app.name;
+    let mut saved = vec![SavedFile::save(paths::app_icon_path(app_name))];
+    if !keep.desktop_entry {
+        saved.push(SavedFile::save(paths::desktop_entry_path(
+            &manifest.desktop_id(),
+        )));
+        // Removed when the app gained an app ID
+        saved.push(SavedFile::save(paths::app_desktop_path(app_name)));
+    }
+    if !keep.wrapper {
+        saved.push(SavedFile::save(paths::app_wrapper_path(app_name)));
+    }
+
+    let result = (|| -> Result<(), IntegrationError> {
+        extract_icon(app_name, manifest.desktop.icon.as_deref())?;
+        if !keep.desktop_entry {
+            create_desktop_entry(manifest)?;
+        }
+        if !keep.wrapper {
+            create_app_wrapper(app_name)?;
+        }
+        Ok(())
+    })();
+
+    if result.is_ok() {
+        match write_window_rules(manifest) {
+            Ok(Some(dir)) => say!("[voidbox] Window-rule hints written to {}", dir.display()),
+            Ok(None) => {}
+            Err(e) => say!(
+                "[voidbox] Warning: Could not write window-rule hints: {}",
+                e
+            ),
+        }
+    } else {
+        for file in saved.iter().rev() {
+            if let Err(e) = file.restore() {
+                say!(
+                    "[voidbox] Warning: Could not restore {}: {}",
+                    file.path.display(),
+                    e
+                );
+            }
+        }
+        mark_icons_changed();
+        mark_desktop_changed(true);
+    }
+    result
+}
@@ -0,0 +1,82 @@
+//! App list for third-party launchers
+//!
+//! Launchers such as KRunner or Ulauncher plugins can read installed apps
+//! from a JSON index instead of desktop entries, which portable setups may
+//! not have. The index is rewritten whenever desktop entries or icons change.
+
+use crate::manifest::{AppManifest, InstalledApp, parse_manifest_file};
+use crate::storage::paths;
+use serde::Serialize;
+use std::fs;
+
+/// Format version of the index, bumped on incompatible changes
+const LAUNCHER_INDEX_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct LauncherIndex {
+    version: u32,
+    apps: Vec<LauncherApp>,
+}
+
+/// An installed app as seen by launchers
+#[derive(Serialize)]
+struct LauncherApp {
+    name: String,
+    /// Desktop file ID, matching the app's window app ID
+    id: String,
+    display_name: String,
+    generic_name: Option<String>,
+    description: String,
+    /// Absolute icon path, if the app has an icon
+    icon: Option<String>,
+    keywords: Vec<String>,
+    categories: Vec<String>,
+    /// Command line that launches the app
+    exec: Vec<String>,
+}
+
+impl LauncherApp {
+    fn new(manifest: &AppManifest) -> Self {
+        let icon = paths::app_icon_path(&manifest.app.name);
+        Self {
+            name: manifest.app.name.clone(),
+            id: manifest.desktop_id(),
+            display_name: manifest.app.display_name.clone(),
+            generic_name: manifest.desktop.generic_name.clone(),
+            description: manifest.app.description.clone(),
+            icon: icon.exists().then(|| icon.to_string_lossy().into_owned()),
+            keywords: manifest.desktop.keywords.clone(),
+            categories: manifest.desktop.categories.clone(),
+            exec: vec![
+                "voidbox".to_string(),
+                "run".to_string(),
+                paths::app_slug(&manifest.app.name),
+            ],
+        }
+    }
+}
+
+/// Rewrite the launcher index from the app database
+pub fn write_launcher_index() -> std::io::Result<()> {
+    let installed: Vec<InstalledApp> = fs::read_to_string(paths::database_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+
+    let apps = installed
+        .iter()
+        .filter_map(|app| parse_manifest_file(&paths::manifest_path(&app.name)).ok())
+        .map(|manifest| LauncherApp::new(&manifest))
+        .collect();
+    let index = LauncherIndex {
+        version: LAUNCHER_INDEX_VERSION,
+        apps,
+    };
+
+    let path = paths::launcher_index_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(&index).map_err(std::io::Error::other)?;
+    fs::write(path, json)
+}
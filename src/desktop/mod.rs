@@ -1,9 +1,19 @@
 //! Desktop integration - .desktop files, icons, symlinks
 
+mod activate;
 mod entry;
 mod icon;
+mod integrate;
+mod launchers;
+mod refresh;
 mod symlink;
+mod window_rules;
 
+pub use activate::*;
 pub use entry::*;
 pub use icon::*;
+pub use integrate::*;
+pub use launchers::*;
+pub use refresh::*;
 pub use symlink::*;
+pub use window_rules::*;
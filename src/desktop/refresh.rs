@@ -0,0 +1,111 @@
+//! Batched desktop database refreshes
+//!
+//! Changes to desktop entries, icons and MIME associations are recorded as
+//! they happen; the matching refresh tools run once when the outermost
+//! [`RefreshBatch`] is dropped. Updating ten apps therefore triggers one
+//! refresh pass, and none at all if no file actually changed.
+
+use crate::storage::paths;
+use std::process::Command;
+use std::sync::Mutex;
+
+#[derive(Default, Clone, Copy)]
+struct PendingRefresh {
+    desktop: bool,
+    icons: bool,
+    mime: bool,
+}
+
+struct RefreshState {
+    depth: usize,
+    pending: PendingRefresh,
+}
+
+static STATE: Mutex<RefreshState> = Mutex::new(RefreshState {
+    depth: 0,
+    pending: PendingRefresh {
+        desktop: false,
+        icons: false,
+        mime: false,
+    },
+});
+
+/// Defers desktop refreshes until dropped (batches may be nested)
+pub struct RefreshBatch {
+    _private: (),
+}
+
+/// Start a refresh batch
+pub fn refresh_batch() -> RefreshBatch {
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    state.depth += 1;
+    RefreshBatch { _private: () }
+}
+
+impl Drop for RefreshBatch {
+    fn drop(&mut self) {
+        let pending = {
+            let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+            state.depth -= 1;
+            if state.depth > 0 {
+                return;
+            }
+            std::mem::take(&mut state.pending)
+        };
+        run_refresh(pending);
+    }
+}
+
+fn mark(change: impl FnOnce(&mut PendingRefresh)) {
+    let pending = {
+        let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+        change(&mut state.pending);
+        if state.depth > 0 {
+            return;
+        }
+        std::mem::take(&mut state.pending)
+    };
+    run_refresh(pending);
+}
+
+/// Record that a desktop entry changed (and whether it declares MIME types)
+pub(crate) fn mark_desktop_changed(mime: bool) {
+    mark(|p| {
+        p.desktop = true;
+        p.mime |= mime;
+    });
+}
+
+/// Record that an app icon changed
+pub(crate) fn mark_icons_changed() {
+    mark(|p| p.icons = true);
+}
+
+fn run_refresh(pending: PendingRefresh) {
+    if pending.desktop || pending.mime {
+        // Also rebuilds mimeinfo.cache from MimeType= lines
+        super::update_desktop_database();
+    }
+
+    if pending.mime
+        && let Some(mime_dir) = dirs::data_local_dir().map(|d| d.join("mime"))
+        && mime_dir.exists()
+    {
+        let _ = Command::new("update-mime-database").arg(mime_dir).output();
+    }
+
+    if pending.icons {
+        let _ = Command::new("xdg-icon-resource")
+            .args(["forceupdate", "--mode", "user"])
+            .output();
+    }
+
+    if (pending.desktop || pending.icons || !paths::launcher_index_path().exists())
+        && let Err(e) = super::write_launcher_index()
+    {
+        say!(
+            "[voidbox] Warning: Could not write the launcher index: {}",
+            e
+        );
+    }
+}
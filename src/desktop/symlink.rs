@@ -12,7 +12,7 @@ pub enum SymlinkError {
 
 /// Create a wrapper script for an app in ~/.local/bin
 pub fn create_app_wrapper(app_name: &str) -> Result<(), SymlinkError> {
-    let wrapper_path = paths::bin_dir().join(app_name);
+    let wrapper_path = paths::app_wrapper_path(app_name);
 
     if let Some(parent) = wrapper_path.parent() {
         fs::create_dir_all(parent)?;
@@ -23,7 +23,7 @@ pub fn create_app_wrapper(app_name: &str) -> Result<(), SymlinkError> {
         r#"#!/bin/sh
 exec voidbox run {} -- "$@"
 "#,
-        app_name
+        paths::app_slug(app_name)
     );
 
     fs::write(&wrapper_path, script)?;
@@ -39,7 +39,7 @@ exec voidbox run {} -- "$@"
 
 /// Remove wrapper script for an app
 pub fn remove_app_wrapper(app_name: &str) -> Result<(), SymlinkError> {
-    let wrapper_path = paths::bin_dir().join(app_name);
+    let wrapper_path = paths::app_wrapper_path(app_name);
     if wrapper_path.exists() {
         fs::remove_file(wrapper_path)?;
     }
@@ -60,7 +60,7 @@ pub fn install_self() -> Result<(), SymlinkError> {
         return Ok(());
     }
 
-    println!(
+    say!(
         "[{}] Installing to {}...",
         crate::APP_NAME,
         install_path.display()
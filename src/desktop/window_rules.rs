@@ -0,0 +1,86 @@
+//! Window-rule hints for apps whose windows don't match their desktop entry
+//!
+//! Taskbars find a window's icon by looking for a desktop entry named after
+//! its Wayland app ID (or with a matching StartupWMClass). Apps that report
+//! another class, common with Electron, show a generic icon on those that
+//! don't go by StartupWMClass. For them, `[desktop] window_rules` writes
+//! hints into the app's `window-rules/` directory: a KWin rule to import
+//! in System Settings, and an `app_ids-mapping` for Waybar's taskbar (used
+//! with sway, Hyprland and other wlroots compositors).
+
+use super::window_class;
+use crate::manifest::AppManifest;
+use crate::storage::paths;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// KWin rule file, imported under System Settings > Window Rules
+const KWIN_RULE_FILE: &str = "kwin.kwinrule";
+
+/// Waybar taskbar snippet, merged into the `wlr/taskbar` module's config
+const WAYBAR_FILE: &str = "waybar.json";
+
+/// Write or remove an app's window-rule hints as its manifest asks
+///
+/// Returns the directory holding them when they were written.
+pub fn write_window_rules(manifest: &AppManifest) -> io::Result<Option<PathBuf>> {
+    let dir = paths::app_window_rules_dir(&manifest.app.name);
+    if !manifest.desktop.window_rules {
+        remove_window_rules(&manifest.app.name)?;
+        return Ok(None);
+    }
+    fs::create_dir_all(&dir)?;
+
+    let class = window_class(manifest);
+    let desktop_id = manifest.desktop_id();
+
+    // KWin matches the X11 class and the Wayland app ID alike; the desktop
+    // file rule is forced so it also holds for windows already open
+    let kwin_rule = format!(
+        "[{id}]\n\
+         Description={name} (voidbox)\n\
+         wmclass=(?i)^{class}$\n\
+         wmclassmatch=3\n\
+         wmclasscomplete=false\n\
+         desktopfile={id}\n\
+         desktopfilerule=2\n",
+        id = desktop_id,
+        name = manifest.app.display_name,
+        class = escape_regex(&class),
+    );
+    fs::write(dir.join(KWIN_RULE_FILE), kwin_rule)?;
+
+    let waybar = serde_json::json!({
+        "wlr/taskbar": {
+            "app_ids-mapping": { class: desktop_id }
+        }
+    });
+    fs::write(
+        dir.join(WAYBAR_FILE),
+        serde_json::to_string_pretty(&waybar).unwrap_or_default() + "\n",
+    )?;
+
+    Ok(Some(dir))
+}
+
+/// Remove an app's window-rule hints
+pub fn remove_window_rules(app_name: &str) -> io::Result<()> {
+    let dir = paths::app_window_rules_dir(app_name);
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+/// Escape a class name for use in a KWin regular expression
+fn escape_regex(class: &str) -> String {
+    let mut escaped = String::new();
+    for c in class.chars() {
+        if "\\.^$|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
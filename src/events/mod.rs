@@ -0,0 +1,8 @@
+//! Event notifications - webhooks for install/update/remove/failure events,
+//! and structured progress events for frontends
+
+mod progress;
+mod webhook;
+
+pub use progress::*;
+pub use webhook::*;
@@ -0,0 +1,97 @@
+//! Structured progress events for long-running operations
+//!
+//! Install, update and download code emits `Event`s instead of printing
+//! progress for a particular frontend. Frontends subscribe for as long as
+//! they're interested: the GUI installer maps events to its progress bar,
+//! and JSON output modes serialize them as they arrive.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Something that happened during an operation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Looking up what to download for an app, with the manifest's hints
+    /// of how big and slow its install is
+    ResolveStarted {
+        app: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        download_size: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        extract_seconds: Option<u64>,
+    },
+    /// Bytes of a download received so far
+    DownloadProgress {
+        url: String,
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    /// Unpacking the downloads and installing packages started
+    ExtractStarted { app: String },
+    /// A line printed by one of the manifest's hook commands
+    HookOutput {
+        app: String,
+        hook: String,
+        line: String,
+    },
+    /// The app's files are unpacked into its rootfs
+    Extracted { app: String },
+    /// Desktop entry, icon and command wrapper are in place
+    Integrated { app: String },
+    /// The operation finished
+    Completed {
+        app: String,
+        version: Option<String>,
+    },
+    /// Nothing was done, e.g. because the app is already installed
+    Skipped { app: String, reason: String },
+    /// A stage failed for good (after any retries)
+    Failed {
+        app: String,
+        stage: String,
+        error: String,
+    },
+}
+
+type Callback = Arc<dyn Fn(&Event) + Send + Sync>;
+
+static SUBSCRIBERS: Mutex<Vec<(u64, Callback)>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps a subscription alive; dropping it unsubscribes
+#[must_use = "dropping the subscription unsubscribes immediately"]
+pub struct Subscription(u64);
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Ok(mut subscribers) = SUBSCRIBERS.lock() {
+            subscribers.retain(|(id, _)| *id != self.0);
+        }
+    }
+}
+
+/// Call `callback` for every event until the subscription is dropped
+///
+/// Callbacks run on the thread emitting the event, so they should be quick;
+/// send to a channel to handle events elsewhere.
+pub fn subscribe(callback: impl Fn(&Event) + Send + Sync + 'static) -> Subscription {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    if let Ok(mut subscribers) = SUBSCRIBERS.lock() {
+        subscribers.push((id, Arc::new(callback)));
+    }
+    Subscription(id)
+}
+
+/// Deliver an event to all subscribers
+pub fn emit(event: Event) {
+    // Call outside the lock so a callback may subscribe or unsubscribe
+    let callbacks: Vec<Callback> = match SUBSCRIBERS.lock() {
+        Ok(subscribers) => subscribers.iter().map(|(_, c)| Arc::clone(c)).collect(),
+        Err(_) => return,
+    };
+    for callback in callbacks {
+        callback(&event);
+    }
+}
@@ -0,0 +1,210 @@
+//! Webhook delivery for app events
+//!
+//! Webhooks are declared in the global config and receive a JSON payload,
+//! either as an HTTP POST body or on stdin of a shell command. Events are
+//! delivered on background threads so a slow endpoint doesn't hold up the
+//! operation; voidbox waits a little for them before it exits.
+
+use crate::settings::{WebhookConfig, load_config};
+use crate::storage::http_post;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Time allowed for connecting to a webhook URL, and for the whole request
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long voidbox waits for deliveries still running when it exits
+const EXIT_WAIT: Duration = Duration::from_secs(10);
+
+/// Deliveries started by `fire_event`
+static PENDING: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Settings error: {0}")]
+    SettingsError(#[from] crate::settings::SettingsError),
+
+    #[error("HTTP request failed: {0}")]
+    HttpError(String),
+
+    #[error("Command failed: {0}")]
+    CommandError(String),
+
+    #[error("Webhook has neither url nor command")]
+    NoTarget,
+
+    #[error("Failed to serialize payload: {0}")]
+    SerializeError(#[from] serde_json::Error),
+}
+
+/// Kinds of events delivered to webhooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Install,
+    Update,
+    Remove,
+    Failure,
+    Test,
+}
+
+/// JSON payload sent to webhooks
+#[derive(Debug, Clone, Serialize)]
+pub struct EventPayload {
+    pub event: EventKind,
+    pub app: Option<String>,
+    pub version: Option<String>,
+    pub message: String,
+    pub timestamp: String,
+    pub host: String,
+    pub voidbox_version: String,
+}
+
+impl EventPayload {
+    pub fn new(event: EventKind, app: Option<&str>, version: Option<&str>, message: &str) -> Self {
+        let host = nix::unistd::gethostname()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_default();
+
+        Self {
+            event,
+            app: app.map(String::from),
+            version: version.map(String::from),
+            message: message.to_string(),
+            timestamp: chrono::Local::now().to_rfc3339(),
+            host,
+            voidbox_version: crate::VERSION.to_string(),
+        }
+    }
+}
+
+/// Fire an event to all configured webhooks that want it
+///
+/// Delivery happens in the background; failures are reported as warnings
+/// and never fail the operation that triggered the event.
+pub fn fire_event(event: EventKind, app: Option<&str>, version: Option<&str>, message: &str) {
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!(
+                "[voidbox] Warning: Could not load config for webhooks: {}",
+                e
+            );
+            return;
+        }
+    };
+
+    let payload = EventPayload::new(event, app, version, message);
+
+    for hook in config.webhooks.into_iter().filter(|h| h.wants(event)) {
+        let payload = payload.clone();
+        let delivery = thread::spawn(move || {
+            if let Err(e) = send_webhook(&hook, &payload) {
+                eprintln!("[voidbox] Warning: Webhook {} failed: {}", hook.target(), e);
+            }
+        });
+        if let Ok(mut pending) = PENDING.lock() {
+            pending.push(delivery);
+        }
+    }
+}
+
+/// Give deliveries still running a chance to finish before voidbox exits
+pub fn wait_for_webhooks() {
+    let pending = match PENDING.lock() {
+        Ok(mut pending) => std::mem::take(&mut *pending),
+        Err(_) => return,
+    };
+    let deadline = Instant::now() + EXIT_WAIT;
+    while Instant::now() < deadline && pending.iter().any(|d| !d.is_finished()) {
+        thread::sleep(Duration::from_millis(50));
+    }
+    let unfinished = pending.iter().filter(|d| !d.is_finished()).count();
+    if unfinished > 0 {
+        eprintln!(
+            "[voidbox] Warning: Gave up on {} webhook delivery(s) still running",
+            unfinished
+        );
+    }
+}
+
+/// Deliver a payload to a single webhook, retrying with backoff
+pub fn send_webhook(hook: &WebhookConfig, payload: &EventPayload) -> Result<(), WebhookError> {
+    let body = serde_json::to_string(payload)?;
+
+    let mut attempt = 0;
+    loop {
+        let result = if let Some(url) = &hook.url {
+            post_json(url, &body)
+        } else if let Some(cmd) = &hook.command {
+            run_command(cmd, &body, payload.event)
+        } else {
+            return Err(WebhookError::NoTarget);
+        };
+
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt >= hook.retries => return Err(e),
+            Err(_) => {
+                // Exponential backoff: 1s, 2s, 4s, ... capped at 30s
+                let delay = Duration::from_secs((1u64 << attempt.min(5)).min(30));
+                thread::sleep(delay);
+                attempt += 1;
+            }
+        }
+    }
+}
+
+fn post_json(url: &str, body: &str) -> Result<(), WebhookError> {
+    http_post(url)
+        .config()
+        .timeout_connect(Some(CONNECT_TIMEOUT))
+        .timeout_global(Some(REQUEST_TIMEOUT))
+        .build()
+        .header("Content-Type", "application/json")
+        .send(body)
+        .map_err(|e| WebhookError::HttpError(e.to_string()))?;
+
+    Ok(())
+}
+
+fn run_command(cmd: &str, body: &str, event: EventKind) -> Result<(), WebhookError> {
+    let event_name = serde_json::to_value(event)?
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+
+    let mut child = Command::new("sh")
+        .args(["-c", cmd])
+        .env("VOIDBOX_EVENT", event_name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| WebhookError::CommandError(e.to_string()))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(body.as_bytes())
+            .map_err(|e| WebhookError::CommandError(e.to_string()))?;
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| WebhookError::CommandError(e.to_string()))?;
+
+    if !status.success() {
+        return Err(WebhookError::CommandError(format!(
+            "exited with {}",
+            status.code().unwrap_or(-1)
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,525 @@
+//! Form-based manifest editor
+//!
+//! Edits the most common manifest fields (names, source, binary, icon,
+//! categories and permissions) and writes the result back through
+//! [`ManifestBuilder`], so it is validated like any other manifest. Fields
+//! the form doesn't show are carried over from the manifest being edited.
+
+use eframe::egui;
+use std::fs;
+use std::path::PathBuf;
+
+use super::window::run_window;
+use crate::manifest::{
+    AppManifest, ClipboardAccess, ManifestBuilder, ManifestError, PermissionConfig, RuntimeConfig,
+    SourceConfig, manifest_to_toml, parse_manifest_file,
+};
+use crate::settings::{PermissionPreset, manifest_permissions};
+
+/// Wayland app_id / X11 WM_CLASS of the editor window
+const EDITOR_APP_ID: &str = "voidbox-manifest-editor";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SourceKind {
+    Github,
+    Direct,
+    Local,
+}
+
+/// Editable copy of the form fields
+struct ManifestForm {
+    name: String,
+    display_name: String,
+    description: String,
+    source: SourceKind,
+    /// owner/repo for GitHub sources
+    github: String,
+    url: String,
+    local_path: String,
+    binary: String,
+    /// Space-separated
+    args: String,
+    icon: String,
+    /// Semicolon-separated, as in desktop entries
+    categories: String,
+    /// Whether the manifest declares permissions or leaves them to the
+    /// category preset
+    declare_permissions: bool,
+    permissions: PermissionConfig,
+}
+
+impl Default for ManifestForm {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            display_name: String::new(),
+            description: String::new(),
+            source: SourceKind::Github,
+            github: String::new(),
+            url: String::new(),
+            local_path: String::new(),
+            binary: String::new(),
+            args: String::new(),
+            icon: String::new(),
+            categories: "Utility".to_string(),
+            declare_permissions: false,
+            permissions: PermissionPreset::Minimal.permissions(),
+        }
+    }
+}
+
+impl ManifestForm {
+    fn from_manifest(manifest: &AppManifest) -> Self {
+        let mut form = Self {
+            name: manifest.app.name.clone(),
+            display_name: manifest.app.display_name.clone(),
+            description: manifest.app.description.clone(),
+            binary: manifest.binary.name.clone(),
+            args: manifest.binary.args.join(" "),
+            icon: manifest.desktop.icon.clone().unwrap_or_default(),
+            categories: manifest.desktop.categories.join(";"),
+            declare_permissions: manifest.permissions.is_some(),
+            permissions: manifest_permissions(manifest),
+            ..Default::default()
+        };
+        match &manifest.source {
+            SourceConfig::Github { owner, repo, .. } => {
+                form.source = SourceKind::Github;
+                form.github = format!("{}/{}", owner, repo);
+            }
+            SourceConfig::Direct { url, .. } => {
+                form.source = SourceKind::Direct;
+                form.url = url.clone();
+            }
+            SourceConfig::Local { path } => {
+                form.source = SourceKind::Local;
+                form.local_path = path.display().to_string();
+            }
+        }
+        form
+    }
+
+    /// Source from the form, keeping the asset/archive settings of `base`
+    fn source(&self, base: Option<&AppManifest>) -> Result<SourceConfig, ManifestError> {
+        let base = base.map(|m| &m.source);
+        Ok(match self.source {
+            SourceKind::Github => {
+                let (owner, repo) = self
+                    .github
+                    .trim()
+                    .split_once('/')
+                    .filter(|(o, r)| !o.is_empty() && !r.is_empty())
+                    .ok_or_else(|| {
+                        ManifestError::ValidationError(
+                            "GitHub repository must be owner/repo".into(),
+                        )
+                    })?;
+                match base {
+                    Some(SourceConfig::Github {
+                        owner: base_owner,
+                        repo: base_repo,
+                        asset_pattern,
+                        asset_os,
+                        asset_arch,
+                        asset_extension,
+                        tag,
+                        sha256,
+                    }) => SourceConfig::Github {
+                        owner: owner.to_string(),
+                        repo: repo.to_string(),
+                        asset_pattern: asset_pattern.clone(),
+                        asset_os: asset_os.clone(),
+                        asset_arch: asset_arch.clone(),
+                        asset_extension: asset_extension.clone(),
+                        // A release tag only means something in its own repo
+                        tag: tag
+                            .clone()
+                            .filter(|_| base_owner == owner && base_repo == repo),
+                        sha256: sha256
+                            .clone()
+                            .filter(|_| base_owner == owner && base_repo == repo),
+                    },
+                    _ => SourceConfig::github(owner, repo),
+                }
+            }
+            SourceKind::Direct => {
+                let url = self.url.trim();
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    return Err(ManifestError::ValidationError(
+                        "Download URL must start with https://".into(),
+                    ));
+                }
+                match base {
+                    Some(SourceConfig::Direct {
+                        url: base_url,
+                        version_url,
+                        archive_type,
+                        sha256,
+                    }) => SourceConfig::Direct {
+                        url: url.to_string(),
+                        version_url: version_url.clone(),
+                        archive_type: archive_type.clone(),
+                        // A checksum only holds for the download it was made of
+                        sha256: sha256.clone().filter(|_| base_url == url),
+                    },
+                    _ => SourceConfig::direct(url),
+                }
+            }
+            SourceKind::Local => SourceConfig::Local {
+                path: PathBuf::from(self.local_path.trim()),
+            },
+        })
+    }
+
+    /// Build and validate the manifest
+    fn build(&self, base: Option<&AppManifest>) -> Result<AppManifest, ManifestError> {
+        let mut builder = ManifestBuilder::new(
+            self.name.trim(),
+            self.display_name.trim(),
+            self.source(base)?,
+            self.binary.trim(),
+        )
+        .description(self.description.trim());
+
+        if self.declare_permissions {
+            builder = builder.permissions(self.permissions.clone());
+        }
+
+        for arg in self.args.split_whitespace() {
+            builder = builder.arg(arg);
+        }
+        for category in self.categories.split(';').map(str::trim) {
+            if !category.is_empty() {
+                builder = builder.category(category);
+            }
+        }
+        if !self.icon.trim().is_empty() {
+            builder = builder.icon(self.icon.trim());
+        }
+        if let Some(base) = base {
+            builder = carry_over(builder, base);
+        }
+
+        builder.build()
+    }
+}
+
+/// Copy the fields the form doesn't edit from the original manifest
+fn carry_over(mut builder: ManifestBuilder, base: &AppManifest) -> ManifestBuilder {
+    let app = &base.app;
+    if let Some(id) = &app.id {
+        builder = builder.id(id);
+    }
+    if let Some(version) = &app.version {
+        builder = builder.version(version);
+    }
+    if let Some(license) = &app.license {
+        builder = builder.license(license);
+    }
+    if let Some(publisher) = &app.publisher {
+        builder = builder.publisher(publisher);
+    }
+
+    // The builder starts from the default architecture list
+    builder = builder.base(&base.runtime.base);
+    let default_arch = RuntimeConfig::default().arch;
+    for arch in base
+        .runtime
+        .arch
+        .iter()
+        .filter(|a| !default_arch.contains(a))
+    {
+        builder = builder.arch(arch);
+    }
+    for (arch, source) in &base.sources {
+        builder = builder.arch_source(arch, source.clone());
+    }
+    if let Some(size) = &base.runtime.tmp_size {
+        builder = builder.tmp_size(size);
+    }
+    if let Some(prefer) = base.runtime.system_libraries {
+        builder = builder.system_libraries(prefer);
+    }
+    for library in &base.runtime.bundled_libraries {
+        builder = builder.bundled_library(library);
+    }
+    for package in &base.dependencies.packages {
+        builder = builder.package(package);
+    }
+    for app in &base.dependencies.apps {
+        builder = builder.app_dependency(app);
+    }
+
+    let binary = &base.binary;
+    if let Some(path) = &binary.path {
+        builder = builder.binary_path(path);
+    }
+    if let Some(dir) = &binary.install_dir {
+        builder = builder.install_dir(dir);
+    }
+    builder = builder.single_instance(binary.single_instance);
+    for (name, value) in &binary.env {
+        builder = builder.env(name, value);
+    }
+    if let Some(block) = binary.block_updater {
+        builder = builder.block_updater(block);
+    }
+    for pattern in &binary.exclude {
+        builder = builder.exclude(pattern);
+    }
+    if let Some(optimize) = binary.optimize {
+        builder = builder.optimize(optimize);
+    }
+
+    let desktop = &base.desktop;
+    for mime_type in &desktop.mime_types {
+        builder = builder.mime_type(mime_type);
+    }
+    for keyword in &desktop.keywords {
+        builder = builder.keyword(keyword);
+    }
+    if let Some(wm_class) = &desktop.wm_class {
+        builder = builder.wm_class(wm_class);
+    }
+    if let Some(startup_notify) = desktop.startup_notify {
+        builder = builder.startup_notify(startup_notify);
+    }
+    builder = builder.window_rules(desktop.window_rules);
+    if let Some(dbus_name) = &desktop.dbus_name {
+        builder = builder.dbus_name(dbus_name);
+    }
+    if let Some(generic_name) = &desktop.generic_name {
+        builder = builder.generic_name(generic_name);
+    }
+    for (locale, strings) in &desktop.localized {
+        builder = builder.localized(locale, strings.clone());
+    }
+    for step in &base.hooks.post_install {
+        builder = builder.post_install(step.clone());
+    }
+    for step in &base.hooks.pre_remove {
+        builder = builder.pre_remove(step.clone());
+    }
+    builder.dbus(base.dbus.clone()).hints(base.hints.clone())
+}
+
+pub struct ManifestEditor {
+    path: PathBuf,
+    /// The manifest being edited, if the file existed
+    base: Option<AppManifest>,
+    form: ManifestForm,
+    /// Result of the last save
+    status: Option<Result<String, String>>,
+}
+
+impl ManifestEditor {
+    pub fn new(path: PathBuf) -> Self {
+        let mut status = None;
+        let base = if path.exists() {
+            // Fall back to the raw file so a manifest failing validation
+            // can still be opened and fixed
+            match parse_manifest_file(&path) {
+                Ok(manifest) => Some(manifest),
+                Err(e) => {
+                    status = Some(Err(e.to_string()));
+                    fs::read_to_string(&path)
+                        .ok()
+                        .and_then(|c| toml::from_str::<AppManifest>(&c).ok())
+                }
+            }
+        } else {
+            None
+        };
+
+        let form = base
+            .as_ref()
+            .map(ManifestForm::from_manifest)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            base,
+            form,
+            status,
+        }
+    }
+
+    fn save(&mut self, manifest: &AppManifest) {
+        let result = manifest_to_toml(manifest)
+            .and_then(|content| Ok(fs::write(&self.path, content)?))
+            .map(|()| format!("Saved to {}", self.path.display()))
+            .map_err(|e| e.to_string());
+        if result.is_ok() {
+            self.base = Some(manifest.clone());
+        }
+        self.status = Some(result);
+    }
+}
+
+impl eframe::App for ManifestEditor {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let built = self.form.build(self.base.as_ref());
+
+        egui::TopBottomPanel::bottom("actions").show(ctx, |ui| {
+            ui.add_space(6.0);
+            match &built {
+                Ok(_) => match &self.status {
+                    Some(Ok(message)) => {
+                        ui.label(message);
+                    }
+                    Some(Err(message)) => {
+                        ui.colored_label(egui::Color32::RED, message);
+                    }
+                    None => {
+                        ui.label("Manifest is valid");
+                    }
+                },
+                Err(e) => {
+                    ui.colored_label(egui::Color32::RED, e.to_string());
+                }
+            }
+            ui.horizontal(|ui| {
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let save = ui.add_enabled(built.is_ok(), egui::Button::new("Save"));
+                    if save.clicked()
+                        && let Ok(manifest) = &built
+                    {
+                        self.save(manifest);
+                    }
+                    if ui.button("Close").clicked() {
+                        std::process::exit(0);
+                    }
+                });
+            });
+            ui.add_space(6.0);
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.heading(self.path.display().to_string());
+                ui.add_space(10.0);
+                show_form(ui, &mut self.form);
+            });
+        });
+    }
+}
+
+fn show_form(ui: &mut egui::Ui, form: &mut ManifestForm) {
+    egui::Grid::new("manifest_form")
+        .num_columns(2)
+        .spacing([12.0, 6.0])
+        .show(ui, |ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut form.name);
+            ui.end_row();
+            ui.label("Display name:");
+            ui.text_edit_singleline(&mut form.display_name);
+            ui.end_row();
+            ui.label("Description:");
+            ui.text_edit_singleline(&mut form.description);
+            ui.end_row();
+
+            ui.label("Source:");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut form.source, SourceKind::Github, "GitHub releases");
+                ui.radio_value(&mut form.source, SourceKind::Direct, "Download URL");
+                ui.radio_value(&mut form.source, SourceKind::Local, "Local files");
+            });
+            ui.end_row();
+            match form.source {
+                SourceKind::Github => {
+                    ui.label("Repository:");
+                    ui.add(egui::TextEdit::singleline(&mut form.github).hint_text("owner/repo"));
+                }
+                SourceKind::Direct => {
+                    ui.label("URL:");
+                    ui.add(egui::TextEdit::singleline(&mut form.url).hint_text("https://"));
+                }
+                SourceKind::Local => {
+                    ui.label("Path:");
+                    ui.text_edit_singleline(&mut form.local_path);
+                }
+            }
+            ui.end_row();
+
+            ui.label("Executable:");
+            ui.text_edit_singleline(&mut form.binary);
+            ui.end_row();
+            ui.label("Arguments:");
+            ui.add(egui::TextEdit::singleline(&mut form.args).hint_text("separated by spaces"));
+            ui.end_row();
+            ui.label("Icon:");
+            ui.add(egui::TextEdit::singleline(&mut form.icon).hint_text("file in the app archive"));
+            ui.end_row();
+            ui.label("Categories:");
+            ui.add(
+                egui::TextEdit::singleline(&mut form.categories).hint_text("Network;WebBrowser"),
+            );
+            ui.end_row();
+        });
+
+    ui.add_space(10.0);
+    let categories: Vec<String> = form
+        .categories
+        .split(';')
+        .map(|c| c.trim().to_string())
+        .collect();
+    let preset = PermissionPreset::for_categories(&categories);
+    let declare = ui.checkbox(&mut form.declare_permissions, "Declare permissions");
+    if declare.changed() && form.declare_permissions {
+        form.permissions = preset.permissions();
+    }
+    if !form.declare_permissions {
+        ui.label(format!("The {} preset applies.", preset));
+        return;
+    }
+    let permissions = &mut form.permissions;
+    egui::Grid::new("manifest_permissions")
+        .num_columns(3)
+        .show(ui, |ui| {
+            let toggles = [
+                ("Network", &mut permissions.network),
+                ("Audio", &mut permissions.audio),
+                ("Microphone", &mut permissions.microphone),
+                ("GPU", &mut permissions.gpu),
+                ("Camera", &mut permissions.camera),
+                ("Home", &mut permissions.home),
+                ("Downloads", &mut permissions.downloads),
+                ("Removable media", &mut permissions.removable_media),
+                ("Developer mode", &mut permissions.dev_mode),
+                ("Fonts", &mut permissions.fonts),
+                ("Themes", &mut permissions.themes),
+                ("Native mode", &mut permissions.native_mode),
+            ];
+            for (i, (label, value)) in toggles.into_iter().enumerate() {
+                ui.checkbox(value, label);
+                if i % 3 == 2 {
+                    ui.end_row();
+                }
+            }
+        });
+    ui.horizontal(|ui| {
+        ui.label("Clipboard:");
+        egui::ComboBox::from_id_salt("manifest_clipboard")
+            .selected_text(permissions.clipboard.description())
+            .show_ui(ui, |ui| {
+                for level in [
+                    ClipboardAccess::None,
+                    ClipboardAccess::Read,
+                    ClipboardAccess::Write,
+                ] {
+                    ui.selectable_value(&mut permissions.clipboard, level, level.description());
+                }
+            });
+    });
+}
+
+/// Open the editor for a manifest file (created on save if it doesn't exist)
+pub fn run_manifest_editor(path: PathBuf) -> Result<(), eframe::Error> {
+    run_window(
+        "Voidbox Manifest Editor",
+        EDITOR_APP_ID,
+        [520.0, 560.0],
+        || ManifestEditor::new(path),
+    )
+}
@@ -0,0 +1,83 @@
+//! Frontend selection
+//!
+//! voidbox is started from terminals, but also by file managers, URL scheme
+//! handlers and file associations, where nothing reads stdout. Every entry
+//! point asks `frontend()` where progress, questions and errors go: the
+//! terminal, dialogs and the installer window, or nowhere but the exit
+//! status when running unattended.
+
+use super::ProgressDialog;
+use super::progress::InstallProgress;
+use crate::events::{Subscription, subscribe};
+use std::sync::{Arc, Mutex};
+
+/// Where an operation reports to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frontend {
+    /// Started from a terminal: print and prompt there
+    Terminal,
+    /// Started from the desktop: show windows and dialogs
+    Gui,
+    /// Neither, e.g. from a script, timer or `--json`: only report errors
+    Headless,
+}
+
+/// Pick the frontend for this process
+pub fn frontend() -> Frontend {
+    let has_terminal = atty::is(atty::Stream::Stdin)
+        || atty::is(atty::Stream::Stdout)
+        || atty::is(atty::Stream::Stderr);
+    if has_terminal {
+        return Frontend::Terminal;
+    }
+
+    // Unattended runs must never wait on a window
+    let unattended = crate::assume_yes()
+        || crate::is_quiet()
+        || crate::cli::is_json()
+        || crate::runtime::in_background();
+    let has_display =
+        std::env::var_os("WAYLAND_DISPLAY").is_some() || std::env::var_os("DISPLAY").is_some();
+    if unattended || !has_display {
+        Frontend::Headless
+    } else {
+        Frontend::Gui
+    }
+}
+
+/// A progress dialog following an operation's progress events, closed when
+/// dropped
+pub struct GuiProgress {
+    // Unsubscribe before the dialog goes away
+    _subscription: Subscription,
+    _dialog: Arc<Mutex<ProgressDialog>>,
+}
+
+impl GuiProgress {
+    /// Show a progress dialog for an operation
+    pub fn new(title: &str, text: &str) -> Self {
+        let dialog = Arc::new(Mutex::new(ProgressDialog::new_determinate(title, text)));
+        let events = Arc::clone(&dialog);
+        let progress = Mutex::new(InstallProgress::default());
+        let subscription = subscribe(move |event| {
+            if let Some((progress, message)) =
+                progress.lock().ok().and_then(|mut p| p.update(event))
+                && let Ok(mut dialog) = events.lock()
+            {
+                dialog.set_text(&message);
+                // Reaching 100% closes the dialog; one app finishing isn't
+                // the end of an operation covering several
+                dialog.set_progress(((progress * 100.0) as u32).min(99));
+            }
+        });
+        Self {
+            _subscription: subscription,
+            _dialog: dialog,
+        }
+    }
+}
+
+/// Show a progress dialog for an operation when running from the desktop
+pub fn gui_progress(title: &str, text: &str) -> Option<GuiProgress> {
+    (frontend() == Frontend::Gui).then(|| GuiProgress::new(title, text))
+}
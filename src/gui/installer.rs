@@ -2,9 +2,15 @@ use eframe::egui;
 use std::sync::mpsc::{Receiver, Sender, channel};
 use std::thread;
 
+use super::progress::InstallProgress;
+use super::window::run_window;
 use crate::cli;
 use crate::desktop::install_self;
-use crate::manifest::parse_manifest;
+use crate::events::{Event, subscribe};
+use crate::manifest::{
+    AppManifest, ClipboardAccess, SignatureStatus, SourceConfig, for_host_arch, parse_manifest,
+};
+use crate::settings::manifest_permissions;
 use crate::storage::paths;
 
 pub enum InstallType {
@@ -13,41 +19,140 @@ pub enum InstallType {
         name: String,
         display_name: String,
         manifest_content: String,
+        /// Checked before any version or slot change to the manifest
+        signature: SignatureStatus,
     },
 }
 
 pub struct InstallerApp {
     install_type: InstallType,
+    details: Option<AppDetails>,
     state: InstallerState,
+    /// Follows the install pipeline's events
+    progress: InstallProgress,
     recv: Receiver<InstallStatus>,
     sender: Sender<InstallStatus>, // Kept to clone for the thread
 }
 
 enum InstallerState {
     Confirmation,
+    AlreadyInstalled { version: String },
     Installing { progress: f32, message: String },
     Done { message: String },
     Error { message: String },
 }
 
 enum InstallStatus {
+    /// Progress of the installer's own steps
     Progress(f32, String),
+    /// Progress reported by the install pipeline
+    Event(Event),
     Success(String),
     Error(String),
+    Planned(Result<cli::InstallPlan, String>),
+}
+
+/// Manifest details shown before the user confirms an app install
+struct AppDetails {
+    publisher: String,
+    signature: SignatureStatus,
+    version: String,
+    permissions: Vec<&'static str>,
+    clipboard: ClipboardAccess,
+    /// Apps the app depends on that will be installed with it
+    dependencies: Vec<String>,
+    /// Progress of an interrupted earlier install that will be continued
+    resume: Option<String>,
+    /// None while the install is still being planned
+    plan: Option<Result<cli::InstallPlan, String>>,
+}
+
+impl AppDetails {
+    fn from_manifest(manifest: &AppManifest, signature: SignatureStatus) -> Self {
+        let publisher = manifest
+            .app
+            .publisher
+            .clone()
+            .or_else(|| match &manifest.source {
+                SourceConfig::Github { owner, .. } => Some(owner.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| "Unknown".to_string());
+        let permissions = manifest_permissions(manifest);
+        let installed = cli::get_installed_apps().unwrap_or_default();
+        let dependencies = manifest
+            .dependencies
+            .apps
+            .iter()
+            .filter(|a| !installed.iter().any(|i| paths::same_app(&i.name, a)))
+            .cloned()
+            .collect();
+
+        Self {
+            publisher,
+            signature,
+            version: manifest
+                .app
+                .version
+                .clone()
+                .unwrap_or_else(|| "latest".to_string()),
+            permissions: permissions
+                .enabled_names()
+                .into_iter()
+                .filter(|p| !p.starts_with("clipboard"))
+                .collect(),
+            clipboard: permissions.clipboard,
+            dependencies,
+            resume: cli::interrupted_install(manifest),
+            plan: None,
+        }
+    }
 }
 
 impl InstallerApp {
     pub fn new(install_type: InstallType) -> Self {
         let (sender, recv) = channel();
+        let mut state = InstallerState::Confirmation;
+        let mut details = None;
+
+        // Parse up front so a bad manifest fails before the user clicks Install
+        if let InstallType::AppInstall {
+            manifest_content,
+            signature,
+            ..
+        } = &install_type
+        {
+            match parse_manifest(manifest_content) {
+                Ok(manifest) => {
+                    if let Some(version) = cli::installed_at_requested_version(&manifest) {
+                        state = InstallerState::AlreadyInstalled { version };
+                    }
+                    details = Some(AppDetails::from_manifest(&manifest, signature.clone()));
+                    let sender = sender.clone();
+                    thread::spawn(move || {
+                        let plan = cli::plan_install(&manifest).map_err(|e| e.to_string());
+                        let _ = sender.send(InstallStatus::Planned(plan));
+                    });
+                }
+                Err(e) => {
+                    state = InstallerState::Error {
+                        message: format!("Invalid manifest: {}", e),
+                    };
+                }
+            }
+        }
+
         Self {
             install_type,
-            state: InstallerState::Confirmation,
+            details,
+            state,
+            progress: InstallProgress::default(),
             recv,
             sender,
         }
     }
 
-    fn start_installation(&mut self) {
+    fn start_installation(&mut self, policy: cli::ConflictPolicy) {
         let sender = self.sender.clone();
         let install_type = match &self.install_type {
             InstallType::SelfInstall => InstallType::SelfInstall,
@@ -55,10 +160,12 @@ impl InstallerApp {
                 name,
                 display_name,
                 manifest_content,
+                signature,
             } => InstallType::AppInstall {
                 name: name.clone(),
                 display_name: display_name.clone(),
                 manifest_content: manifest_content.clone(),
+                signature: signature.clone(),
             },
         };
 
@@ -68,7 +175,7 @@ impl InstallerApp {
         };
 
         thread::spawn(
-            move || match perform_installation(install_type, sender.clone()) {
+            move || match perform_installation(install_type, policy, sender.clone()) {
                 Ok(msg) => {
                     let _ = sender.send(InstallStatus::Success(msg));
                 }
@@ -82,6 +189,7 @@ impl InstallerApp {
 
 fn perform_installation(
     install_type: InstallType,
+    policy: cli::ConflictPolicy,
     sender: Sender<InstallStatus>,
 ) -> Result<String, Box<dyn std::error::Error>> {
     match install_type {
@@ -108,43 +216,43 @@ fn perform_installation(
             name,
             display_name,
             manifest_content,
+            ..
         } => {
             let _ = sender.send(InstallStatus::Progress(
-                0.1,
+                0.0,
                 format!("Preparing to install {}...", display_name),
             ));
 
             // Ensure runtime is installed first
             if !paths::install_path().exists() {
                 let _ = sender.send(InstallStatus::Progress(
-                    0.2,
+                    0.0,
                     "Installing Voidbox runtime...".to_string(),
                 ));
                 paths::ensure_dirs()?;
                 install_self()?;
             }
 
-            let _ = sender.send(InstallStatus::Progress(
-                0.3,
-                "Parsing manifest...".to_string(),
-            ));
-            let manifest = parse_manifest(&manifest_content)?;
+            let manifest = for_host_arch(&parse_manifest(&manifest_content)?)?;
             let manifest_path = paths::manifest_path(&name);
 
             // Save manifest
             paths::ensure_dirs()?;
             std::fs::write(&manifest_path, manifest_content)?;
 
-            // We can't easily get granular progress from the CLI functions yet without refactoring,
-            // so we'll just show indeterminate progress or "Installing..."
-            let _ = sender.send(InstallStatus::Progress(
-                0.5,
-                "Downloading and extracting...".to_string(),
-            ));
+            if let Some(resume) = cli::interrupted_install(&manifest) {
+                let _ = sender.send(InstallStatus::Progress(
+                    0.0,
+                    format!("Resuming previous install ({})...", resume),
+                ));
+            }
 
-            // Install the app
-            // Note: This blocks until done
-            cli::install_app_from_manifest(&manifest, false)?;
+            // Forward the pipeline's events until the install returns
+            let events = sender.clone();
+            let _subscription = subscribe(move |event| {
+                let _ = events.send(InstallStatus::Event(event.clone()));
+            });
+            cli::install_app_from_manifest(&manifest, policy)?;
 
             let _ = sender.send(InstallStatus::Progress(1.0, "Done!".to_string()));
             Ok(format!("{} has been installed successfully!", display_name))
@@ -163,88 +271,227 @@ impl eframe::App for InstallerApp {
                         message: msg,
                     };
                 }
+                InstallStatus::Event(event) => {
+                    if let Some((progress, message)) = self.progress.update(&event) {
+                        self.state = InstallerState::Installing { progress, message };
+                    }
+                }
                 InstallStatus::Success(msg) => {
                     self.state = InstallerState::Done { message: msg };
                 }
                 InstallStatus::Error(msg) => {
                     self.state = InstallerState::Error { message: msg };
                 }
+                InstallStatus::Planned(plan) => {
+                    if let Some(details) = self.details.as_mut() {
+                        details.plan = Some(plan);
+                    }
+                }
             }
         }
 
+        // Unpacking reports no progress; the bar follows its expected time
+        if let InstallerState::Installing { progress, message } = &mut self.state
+            && self.progress.is_estimating()
+        {
+            (*progress, *message) = self.progress.current();
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.vertical_centered(|ui| {
-                ui.add_space(20.0);
-                ui.heading("Voidbox Installer");
-                ui.add_space(20.0);
-
-                match &self.state {
-                    InstallerState::Confirmation => {
-                        match &self.install_type {
-                            InstallType::SelfInstall => {
-                                ui.label(format!("Install Voidbox v{}?", crate::VERSION));
-                                ui.label("This will install voidbox to ~/.local/bin/voidbox");
+            // Scroll rather than clip when the window is small for the scale
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(20.0);
+                    ui.heading("Voidbox Installer");
+                    ui.add_space(20.0);
+
+                    match &self.state {
+                        InstallerState::Confirmation => {
+                            match &self.install_type {
+                                InstallType::SelfInstall => {
+                                    ui.label(format!("Install Voidbox v{}?", crate::VERSION));
+                                    ui.label("This will install voidbox to ~/.local/bin/voidbox");
+                                }
+                                InstallType::AppInstall { display_name, .. } => {
+                                    ui.label(format!("Install {}?", display_name));
+                                    ui.add_space(10.0);
+                                    if let Some(details) = &self.details {
+                                        show_details(ui, details);
+                                    }
+                                }
                             }
-                            InstallType::AppInstall { display_name, .. } => {
-                                ui.label(format!("Install {}?", display_name));
-                                ui.label(
-                                    "This will download and install the application container.",
+                            ui.add_space(20.0);
+
+                            ui.horizontal(|ui| {
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        let resuming = self
+                                            .details
+                                            .as_ref()
+                                            .is_some_and(|d| d.resume.is_some());
+                                        let label = if resuming { "Resume" } else { "Install" };
+                                        if ui.button(label).clicked() {
+                                            self.start_installation(cli::ConflictPolicy::Ask);
+                                        }
+                                        if ui.button("Cancel").clicked() {
+                                            std::process::exit(0);
+                                        }
+                                    },
                                 );
+                            });
+                        }
+                        InstallerState::AlreadyInstalled { version } => {
+                            if let InstallType::AppInstall {
+                                name, display_name, ..
+                            } = &self.install_type
+                            {
+                                let name = name.clone();
+                                ui.label(format!(
+                                    "{} is already installed (v{})",
+                                    display_name, version
+                                ));
+                                ui.add_space(20.0);
+
+                                ui.horizontal(|ui| {
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::Center),
+                                        |ui| {
+                                            if ui.button("Open").clicked() {
+                                                open_app(&name);
+                                                std::process::exit(0);
+                                            }
+                                            if ui.button("Reinstall").clicked() {
+                                                self.start_installation(
+                                                    cli::ConflictPolicy::Overwrite,
+                                                );
+                                            }
+                                            if ui.button("Cancel").clicked() {
+                                                std::process::exit(0);
+                                            }
+                                        },
+                                    );
+                                });
                             }
                         }
-                        ui.add_space(30.0);
-
-                        ui.horizontal(|ui| {
-                            ui.with_layout(
-                                egui::Layout::right_to_left(egui::Align::Center),
-                                |ui| {
-                                    if ui.button("Install").clicked() {
-                                        self.start_installation();
-                                    }
-                                    if ui.button("Cancel").clicked() {
-                                        std::process::exit(0);
-                                    }
-                                },
-                            );
-                        });
-                    }
-                    InstallerState::Installing { progress, message } => {
-                        ui.label(message);
-                        ui.add_space(10.0);
-                        ui.add(egui::ProgressBar::new(*progress).animate(true));
-                    }
-                    InstallerState::Done { message } => {
-                        ui.label(message);
-                        ui.add_space(20.0);
-                        if ui.button("Close").clicked() {
-                            std::process::exit(0);
+                        InstallerState::Installing { progress, message } => {
+                            ui.label(message);
+                            ui.add_space(10.0);
+                            ui.add(egui::ProgressBar::new(*progress).animate(true));
                         }
-                    }
-                    InstallerState::Error { message } => {
-                        ui.colored_label(egui::Color32::RED, "Installation Failed");
-                        ui.label(message);
-                        ui.add_space(20.0);
-                        if ui.button("Close").clicked() {
-                            std::process::exit(1);
+                        InstallerState::Done { message } => {
+                            ui.label(message);
+                            ui.add_space(20.0);
+                            if ui.button("Close").clicked() {
+                                std::process::exit(0);
+                            }
+                        }
+                        InstallerState::Error { message } => {
+                            ui.colored_label(egui::Color32::RED, "Installation Failed");
+                            ui.label(message);
+                            ui.add_space(20.0);
+                            if ui.button("Close").clicked() {
+                                std::process::exit(1);
+                            }
                         }
                     }
-                }
+                });
             });
         });
     }
 }
 
-pub fn run_installer(install_type: InstallType) -> Result<(), eframe::Error> {
-    let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([400.0, 300.0])
-            .with_resizable(false),
-        ..Default::default()
+/// Launch an installed app in the background
+fn open_app(name: &str) {
+    let exe = std::env::current_exe().unwrap_or_else(|_| paths::install_path());
+    let _ = std::process::Command::new(exe)
+        .args(["run", &paths::app_slug(name)])
+        .spawn();
+}
+
+/// Render publisher, version, size, origin, permissions and clipboard access
+fn show_details(ui: &mut egui::Ui, details: &AppDetails) {
+    let (version, origin) = match &details.plan {
+        None => (details.version.clone(), "Checking...".to_string()),
+        Some(Ok(plan)) => (
+            plan.version.clone(),
+            plan.origin
+                .clone()
+                .unwrap_or_else(|| "Local files".to_string()),
+        ),
+        Some(Err(e)) => (details.version.clone(), format!("Could not resolve: {}", e)),
+    };
+
+    let permissions = if details.permissions.is_empty() {
+        "None".to_string()
+    } else {
+        details.permissions.join(", ")
     };
 
-    eframe::run_native(
+    egui::Grid::new("app_details")
+        .num_columns(2)
+        .spacing([12.0, 6.0])
+        .show(ui, |ui| {
+            ui.label("Publisher:");
+            ui.label(&details.publisher);
+            ui.end_row();
+            ui.label("Signature:");
+            match &details.signature {
+                SignatureStatus::UnknownKey { .. } => {
+                    ui.colored_label(ui.visuals().warn_fg_color, details.signature.to_string())
+                }
+                signature => ui.label(signature.to_string()),
+            };
+            ui.end_row();
+            ui.label("Version:");
+            ui.label(version);
+            ui.end_row();
+            ui.label("Origin:");
+            ui.add(egui::Label::new(origin).wrap());
+            ui.end_row();
+            match &details.plan {
+                Some(Ok(plan)) => {
+                    for (label, value) in plan.summary() {
+                        ui.label(label);
+                        ui.add(egui::Label::new(value).wrap());
+                        ui.end_row();
+                    }
+                }
+                Some(Err(_)) => {}
+                None => {
+                    ui.label("Download size:");
+                    ui.label("Checking...");
+                    ui.end_row();
+                }
+            }
+            if !details.dependencies.is_empty() {
+                ui.label("Also installs:");
+                ui.add(egui::Label::new(details.dependencies.join(", ")).wrap());
+                ui.end_row();
+            }
+            ui.label("Permissions:");
+            ui.add(egui::Label::new(permissions).wrap());
+            ui.end_row();
+            ui.label("Clipboard:");
+            ui.label(details.clipboard.description());
+            ui.end_row();
+            if let Some(resume) = &details.resume {
+                ui.label("Previous install:");
+                ui.add(egui::Label::new(format!("{}; it will continue from there", resume)).wrap());
+                ui.end_row();
+            }
+        });
+}
+
+/// Wayland app_id / X11 WM_CLASS of the installer window, for window rules
+const INSTALLER_APP_ID: &str = "voidbox-installer";
+
+pub fn run_installer(install_type: InstallType) -> Result<(), eframe::Error> {
+    run_window(
         "Voidbox Installer",
-        options,
-        Box::new(|_cc| Ok(Box::new(InstallerApp::new(install_type)))),
+        INSTALLER_APP_ID,
+        [460.0, 400.0],
+        || InstallerApp::new(install_type),
     )
 }
@@ -5,9 +5,20 @@
 //! - Progress bars
 //! - Error/info messages
 //! - Yes/No questions
+//!
+//! The installer, manifest editor and task monitor windows are native egui
+//! apps.
 
+pub mod editor;
+pub mod frontend;
 pub mod installer;
+pub mod monitor;
+pub mod progress;
+mod window;
+pub use editor::run_manifest_editor;
+pub use frontend::{Frontend, GuiProgress, frontend, gui_progress};
 pub use installer::{InstallType, run_installer};
+pub use monitor::run_task_monitor;
 
 use std::io::Write;
 
@@ -15,8 +26,7 @@ use std::process::{Child, Command, Stdio};
 
 /// Check if we're running in a GUI environment (not a TTY)
 pub fn is_gui_mode() -> bool {
-    // Check if stdin is NOT a TTY (double-clicked from file manager)
-    !atty::is(atty::Stream::Stdin)
+    frontend() == Frontend::Gui
 }
 
 /// Check if any GUI dialog tool is available
@@ -24,6 +34,11 @@ pub fn has_gui_support() -> bool {
     which_dialog().is_some()
 }
 
+/// Check if questions may be asked with a dialog (never when unattended)
+pub fn can_ask() -> bool {
+    !crate::assume_yes() && has_gui_support()
+}
+
 /// Detect which dialog tool is available
 fn which_dialog() -> Option<DialogTool> {
     // Prefer zenity (GTK/GNOME), fall back to kdialog (KDE)
@@ -60,7 +75,7 @@ pub fn show_info(title: &str, message: &str) {
                 .ok();
         }
         None => {
-            println!("{}: {}", title, message);
+            say!("{}: {}", title, message);
         }
     }
 }
@@ -109,12 +124,142 @@ pub fn ask_yes_no(title: &str, message: &str) -> bool {
             .status()
             .map(|s| s.success())
             .unwrap_or(false),
+        None => crate::confirm(message, false),
+    }
+}
+
+/// Show an error with a button for `action` next to Close, returns true
+/// if the user chose the action
+pub fn ask_action(title: &str, message: &str, action: &str) -> bool {
+    match which_dialog() {
+        Some(DialogTool::Zenity) => Command::new("zenity")
+            .args([
+                "--question",
+                "--icon-name",
+                "dialog-error",
+                "--no-markup",
+                "--title",
+                title,
+                "--text",
+                message,
+                "--ok-label",
+                action,
+                "--cancel-label",
+                "Close",
+                "--width",
+                "500",
+            ])
+            .status()
+            .is_ok_and(|s| s.success()),
+        Some(DialogTool::Kdialog) => Command::new("kdialog")
+            .args([
+                "--title",
+                title,
+                "--yes-label",
+                action,
+                "--no-label",
+                "Close",
+                "--warningyesno",
+                message,
+            ])
+            .status()
+            .is_ok_and(|s| s.success()),
         None => {
-            print!("{} [y/N] ", message);
-            std::io::stdout().flush().ok();
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input).ok();
-            input.trim().to_lowercase() == "y"
+            eprintln!("Error - {}: {}", title, message);
+            false
+        }
+    }
+}
+
+/// Show a long text, such as a report, in a scrollable dialog
+pub fn show_text(title: &str, text: &str) {
+    match which_dialog() {
+        Some(DialogTool::Zenity) => {
+            if let Ok(mut child) = Command::new("zenity")
+                .args([
+                    "--text-info",
+                    "--title",
+                    title,
+                    "--width",
+                    "700",
+                    "--height",
+                    "500",
+                ])
+                .stdin(Stdio::piped())
+                .spawn()
+            {
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(text.as_bytes()).ok();
+                }
+                child.wait().ok();
+            }
+        }
+        Some(DialogTool::Kdialog) => {
+            Command::new("kdialog")
+                .args(["--title", title, "--msgbox", text])
+                .status()
+                .ok();
+        }
+        None => {
+            say!("{}\n{}", title, text);
+        }
+    }
+}
+
+/// Show a single-choice dialog, returns the index of the chosen option
+/// (None if cancelled)
+pub fn choose(title: &str, message: &str, options: &[&str]) -> Option<usize> {
+    match which_dialog() {
+        Some(DialogTool::Zenity) => {
+            let mut args = vec![
+                "--list".to_string(),
+                "--radiolist".to_string(),
+                "--title".to_string(),
+                title.to_string(),
+                "--text".to_string(),
+                message.to_string(),
+                "--column".to_string(),
+                "".to_string(),
+                "--column".to_string(),
+                "Action".to_string(),
+                "--width".to_string(),
+                "400".to_string(),
+                "--height".to_string(),
+                "300".to_string(),
+            ];
+            for (i, option) in options.iter().enumerate() {
+                args.push(if i == 0 { "TRUE" } else { "FALSE" }.to_string());
+                args.push(option.to_string());
+            }
+            let output = Command::new("zenity").args(&args).output().ok()?;
+            let choice = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            options.iter().position(|o| *o == choice)
+        }
+        Some(DialogTool::Kdialog) => {
+            let mut args = vec![
+                "--title".to_string(),
+                title.to_string(),
+                "--radiolist".to_string(),
+                message.to_string(),
+            ];
+            for (i, option) in options.iter().enumerate() {
+                args.push(i.to_string());
+                args.push(option.to_string());
+                args.push(if i == 0 { "on" } else { "off" }.to_string());
+            }
+            let output = Command::new("kdialog").args(&args).output().ok()?;
+            String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+        }
+        None => {
+            say!("{}", message);
+            for (i, option) in options.iter().enumerate() {
+                say!("  {}) {}", i + 1, option);
+            }
+            match crate::ask("Choice [1]:")?.as_str() {
+                "" => Some(0),
+                n => n.parse::<usize>().ok()?.checked_sub(1),
+            }
+            .filter(|&i| i < options.len())
         }
     }
 }
@@ -164,7 +309,7 @@ impl ProgressDialog {
                 }
             }
             None => {
-                println!("{}: {}", title, text);
+                say!("{}: {}", title, text);
                 Self {
                     child: None,
                     tool: None,
@@ -272,7 +417,7 @@ pub fn notify(title: &str, message: &str) {
                 .ok();
         }
         None => {
-            println!("{}: {}", title, message);
+            say!("{}: {}", title, message);
         }
     }
 }
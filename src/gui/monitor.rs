@@ -0,0 +1,164 @@
+//! Task monitor for running apps
+//!
+//! Lists every running app instance with its CPU and memory usage, open
+//! windows and sandbox status, refreshed every second, and stops apps on
+//! request.
+
+use eframe::egui;
+use indicatif::HumanBytes;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::window::run_window;
+use crate::desktop::window_count;
+use crate::manifest::parse_manifest_file;
+use crate::runtime::{InstanceInfo, instance_usage, running_instances, stop_instance};
+use crate::storage::paths;
+
+/// Wayland app_id / X11 WM_CLASS of the monitor window
+const MONITOR_APP_ID: &str = "voidbox-monitor";
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// One running instance as shown in the list
+struct Row {
+    info: InstanceInfo,
+    display_name: String,
+    /// Percent of one CPU; `None` until two samples were taken
+    cpu: Option<f64>,
+    memory: u64,
+    processes: usize,
+    windows: Option<usize>,
+}
+
+/// CPU sample of an instance, keyed by launcher PID
+struct Sample {
+    at: Instant,
+    cpu_usec: u64,
+}
+
+struct TaskMonitor {
+    rows: Vec<Row>,
+    samples: HashMap<u32, Sample>,
+    last_refresh: Option<Instant>,
+    /// Launcher PIDs of instances asked to stop
+    stopping: Vec<u32>,
+}
+
+impl TaskMonitor {
+    fn new() -> Self {
+        Self {
+            rows: Vec::new(),
+            samples: HashMap::new(),
+            last_refresh: None,
+            stopping: Vec::new(),
+        }
+    }
+
+    fn refresh(&mut self) {
+        let now = Instant::now();
+        let mut samples = HashMap::new();
+        self.rows = running_instances()
+            .into_iter()
+            .map(|info| {
+                let usage = instance_usage(&info);
+                let cpu = self.samples.get(&info.pid).map(|previous| {
+                    let elapsed = now.duration_since(previous.at).as_micros().max(1) as f64;
+                    usage.cpu_usec.saturating_sub(previous.cpu_usec) as f64 * 100.0 / elapsed
+                });
+                samples.insert(
+                    info.pid,
+                    Sample {
+                        at: now,
+                        cpu_usec: usage.cpu_usec,
+                    },
+                );
+
+                let manifest = parse_manifest_file(&paths::manifest_path(&info.app)).ok();
+                Row {
+                    display_name: manifest
+                        .as_ref()
+                        .map(|m| m.app.display_name.clone())
+                        .unwrap_or_else(|| info.app.clone()),
+                    windows: manifest.as_ref().and_then(window_count),
+                    cpu,
+                    memory: usage.memory,
+                    processes: usage.processes.len(),
+                    info,
+                }
+            })
+            .collect();
+        self.samples = samples;
+        self.stopping
+            .retain(|pid| self.rows.iter().any(|row| row.info.pid == *pid));
+        self.last_refresh = Some(now);
+    }
+}
+
+impl eframe::App for TaskMonitor {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self
+            .last_refresh
+            .is_none_or(|at| at.elapsed() >= REFRESH_INTERVAL)
+        {
+            self.refresh();
+        }
+        ctx.request_repaint_after(REFRESH_INTERVAL);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.rows.is_empty() {
+                ui.label("No apps are running.");
+                return;
+            }
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("instances")
+                    .num_columns(6)
+                    .striped(true)
+                    .spacing([16.0, 6.0])
+                    .show(ui, |ui| {
+                        for heading in ["App", "CPU", "Memory", "Windows", "Sandbox", ""] {
+                            ui.strong(heading);
+                        }
+                        ui.end_row();
+
+                        for row in &self.rows {
+                            ui.label(&row.display_name).on_hover_text(format!(
+                                "{} (PID {}, {} processes)",
+                                row.info.app, row.info.pid, row.processes
+                            ));
+                            ui.label(
+                                row.cpu
+                                    .map(|cpu| format!("{:.1}%", cpu))
+                                    .unwrap_or_else(|| "…".to_string()),
+                            );
+                            ui.label(HumanBytes(row.memory).to_string());
+                            ui.label(
+                                row.windows
+                                    .map(|n| n.to_string())
+                                    .unwrap_or_else(|| "–".to_string()),
+                            );
+                            ui.label(&row.info.sandbox);
+                            if self.stopping.contains(&row.info.pid) {
+                                ui.label("Stopping…");
+                            } else if ui.button("Stop").clicked() {
+                                stop_instance(&row.info);
+                                self.stopping.push(row.info.pid);
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+    }
+}
+
+/// Open the task monitor window
+pub fn run_task_monitor() -> Result<(), eframe::Error> {
+    run_window(
+        "Voidbox Task Monitor",
+        MONITOR_APP_ID,
+        [640.0, 320.0],
+        TaskMonitor::new,
+    )
+}
@@ -0,0 +1,189 @@
+//! Install progress bar
+//!
+//! Each phase of an install gets a share of the bar in proportion to how
+//! long it's expected to take. Downloads are weighed by their size: the
+//! manifest's `hints.download_size`, or the Content-Length of the downloads
+//! once they start. Unpacking is weighed by `hints.extract_time`, or
+//! estimated from the download size; as it reports no progress of its own,
+//! the bar moves on with the time spent against that estimate.
+
+use crate::events::Event;
+use indicatif::HumanBytes;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// Assumed download speed, for weighing downloads against other phases
+const DOWNLOAD_BYTES_PER_SECOND: f32 = 5.0 * 1024.0 * 1024.0;
+
+/// Assumed unpacking speed when the manifest gives no extract time
+const EXTRACT_BYTES_PER_SECOND: f32 = 40.0 * 1024.0 * 1024.0;
+
+/// Expected seconds of the phases nothing hints at
+const RESOLVE_SECONDS: f32 = 2.0;
+const DOWNLOAD_SECONDS: f32 = 20.0;
+const EXTRACT_SECONDS: f32 = 10.0;
+const INTEGRATE_SECONDS: f32 = 2.0;
+
+/// How far unpacking may move the bar before it reports being done
+const EXTRACT_ESTIMATE_LIMIT: f32 = 0.95;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Resolve,
+    Download,
+    Extract,
+    Integrate,
+    Finishing,
+    Done,
+}
+
+/// Progress of one app's install, following its pipeline events
+pub struct InstallProgress {
+    phase: Phase,
+    /// Hinted size of the app's download
+    download_size: Option<u64>,
+    /// Hinted time to unpack it
+    extract_seconds: Option<u64>,
+    /// Bytes received and expected of each download, by URL
+    downloads: BTreeMap<String, (u64, Option<u64>)>,
+    extract_started: Option<Instant>,
+    message: String,
+    /// The bar never moves back while an app installs
+    shown: f32,
+}
+
+impl Default for InstallProgress {
+    fn default() -> Self {
+        Self {
+            phase: Phase::Resolve,
+            download_size: None,
+            extract_seconds: None,
+            downloads: BTreeMap::new(),
+            extract_started: None,
+            message: "Preparing...".to_string(),
+            shown: 0.0,
+        }
+    }
+}
+
+impl InstallProgress {
+    /// Follow a pipeline event, returning the bar's new position and message
+    pub fn update(&mut self, event: &Event) -> Option<(f32, String)> {
+        match event {
+            Event::ResolveStarted {
+                download_size,
+                extract_seconds,
+                ..
+            } => {
+                // Installs of an app's dependencies start over
+                *self = Self {
+                    download_size: *download_size,
+                    extract_seconds: *extract_seconds,
+                    message: "Looking up the download...".to_string(),
+                    ..Self::default()
+                };
+            }
+            Event::DownloadProgress {
+                url,
+                downloaded,
+                total,
+            } => {
+                self.phase = Phase::Download;
+                self.downloads.insert(url.clone(), (*downloaded, *total));
+                let size = HumanBytes(*downloaded);
+                self.message = match total {
+                    Some(total) if *total > 0 => {
+                        format!("Downloading... {} of {}", size, HumanBytes(*total))
+                    }
+                    _ => format!("Downloading... {}", size),
+                };
+            }
+            Event::ExtractStarted { .. } => {
+                self.phase = Phase::Extract;
+                self.extract_started = Some(Instant::now());
+                self.message = "Unpacking...".to_string();
+            }
+            Event::HookOutput { line, .. } => {
+                self.message = line.clone();
+            }
+            Event::Extracted { .. } => {
+                self.phase = Phase::Integrate;
+                self.message = "Adding to the desktop...".to_string();
+            }
+            Event::Integrated { .. } => {
+                self.phase = Phase::Finishing;
+                self.message = "Finishing...".to_string();
+            }
+            Event::Completed { .. } | Event::Skipped { .. } => {
+                self.phase = Phase::Done;
+                self.message = "Done!".to_string();
+            }
+            // The install's error is reported when it returns
+            Event::Failed { .. } => return None,
+        }
+        Some(self.current())
+    }
+
+    /// The bar's position and message now; moves on while unpacking
+    pub fn current(&mut self) -> (f32, String) {
+        self.shown = self.shown.max(self.position());
+        (self.shown, self.message.clone())
+    }
+
+    /// Whether the position changes without events
+    pub fn is_estimating(&self) -> bool {
+        self.phase == Phase::Extract
+    }
+
+    fn position(&self) -> f32 {
+        let weights = self.weights();
+        let (index, fraction) = match self.phase {
+            Phase::Resolve => (0, 0.0),
+            Phase::Download => (1, self.download_fraction()),
+            Phase::Extract => (2, self.extract_fraction(weights[2])),
+            Phase::Integrate => (3, 0.0),
+            Phase::Finishing => (3, 0.5),
+            Phase::Done => return 1.0,
+        };
+        let total: f32 = weights.iter().sum();
+        let done: f32 = weights[..index].iter().sum();
+        (done + weights[index] * fraction) / total
+    }
+
+    /// Expected seconds of resolving, downloading, unpacking and
+    /// integrating
+    fn weights(&self) -> [f32; 4] {
+        let size = self.download_size.or_else(|| self.measured_size());
+        let download = size.map_or(DOWNLOAD_SECONDS, |s| s as f32 / DOWNLOAD_BYTES_PER_SECOND);
+        let extract = match (self.extract_seconds, size) {
+            (Some(seconds), _) => seconds as f32,
+            (None, Some(size)) => (size as f32 / EXTRACT_BYTES_PER_SECOND).max(1.0),
+            (None, None) => EXTRACT_SECONDS,
+        };
+        [RESOLVE_SECONDS, download, extract, INTEGRATE_SECONDS]
+    }
+
+    /// Total size of the downloads, once every one has a Content-Length
+    fn measured_size(&self) -> Option<u64> {
+        self.downloads
+            .values()
+            .map(|(_, total)| total.filter(|t| *t > 0))
+            .sum::<Option<u64>>()
+            .filter(|s| *s > 0)
+    }
+
+    fn download_fraction(&self) -> f32 {
+        let received: u64 = self.downloads.values().map(|(d, _)| d).sum();
+        match self.measured_size().or(self.download_size) {
+            Some(size) => (received as f32 / size as f32).min(1.0),
+            None => 0.0,
+        }
+    }
+
+    fn extract_fraction(&self, expected_seconds: f32) -> f32 {
+        let elapsed = self
+            .extract_started
+            .map_or(0.0, |started| started.elapsed().as_secs_f32());
+        (elapsed / expected_seconds).min(EXTRACT_ESTIMATE_LIMIT)
+    }
+}
@@ -0,0 +1,88 @@
+//! Native window setup shared by the egui windows
+//!
+//! Windows get a stable app ID for window manager rules, follow the
+//! display's scale factor (overridable with `VOIDBOX_GUI_SCALE`), and are
+//! retried with Mesa's software renderer when OpenGL can't be initialized.
+
+use eframe::egui;
+
+/// Environment variable overriding the UI scale (e.g. "1.5")
+const SCALE_ENV: &str = "VOIDBOX_GUI_SCALE";
+
+/// Set for a window relaunched with software rendering, so it doesn't
+/// relaunch again
+const SOFTWARE_RENDERING_ENV: &str = "VOIDBOX_SOFTWARE_RENDERING";
+
+/// Open a resizable window of `size` logical points and run `app` in it
+pub fn run_window<A: eframe::App + 'static>(
+    title: &str,
+    app_id: &str,
+    size: [f32; 2],
+    app: impl FnOnce() -> A + 'static,
+) -> Result<(), eframe::Error> {
+    let options = eframe::NativeOptions {
+        // Sizes are in logical points; eframe applies the output's scale factor
+        viewport: egui::ViewportBuilder::default()
+            .with_app_id(app_id)
+            .with_inner_size(size)
+            .with_min_inner_size([size[0] * 0.75, size[1] * 0.75])
+            .with_max_inner_size([size[0] * 2.0, size[1] * 2.0])
+            .with_resizable(true),
+        ..Default::default()
+    };
+
+    let result = eframe::run_native(
+        title,
+        options,
+        Box::new(|cc| {
+            if let Some(scale) = std::env::var(SCALE_ENV)
+                .ok()
+                .and_then(|s| s.parse::<f32>().ok())
+                .filter(|s| (0.5..=4.0).contains(s))
+            {
+                cc.egui_ctx.set_zoom_factor(scale);
+            }
+            Ok(Box::new(app()))
+        }),
+    );
+
+    match result {
+        Err(e) if is_gl_error(&e) && std::env::var_os(SOFTWARE_RENDERING_ENV).is_none() => {
+            eprintln!(
+                "[voidbox] OpenGL initialization failed ({}), retrying with software rendering",
+                e
+            );
+            relaunch_with_software_rendering();
+            Err(e)
+        }
+        result => result,
+    }
+}
+
+/// Whether the window failed to set up OpenGL (no GPU or drivers)
+fn is_gl_error(error: &eframe::Error) -> bool {
+    matches!(
+        error,
+        eframe::Error::Glutin(_) | eframe::Error::NoGlutinConfigs(..) | eframe::Error::OpenGL(_)
+    )
+}
+
+/// Run this process again with Mesa's software renderer and exit with its
+/// status; returns only if it couldn't be started
+///
+/// A winit event loop can't be created twice in one process, so the retry
+/// has to be a new process.
+fn relaunch_with_software_rendering() {
+    let Ok(exe) = std::env::current_exe() else {
+        return;
+    };
+    let status = std::process::Command::new(exe)
+        .args(std::env::args_os().skip(1))
+        .env("LIBGL_ALWAYS_SOFTWARE", "1")
+        .env("GALLIUM_DRIVER", "llvmpipe")
+        .env(SOFTWARE_RENDERING_ENV, "1")
+        .status();
+    if let Ok(status) = status {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
@@ -2,8 +2,21 @@
 //!
 //! A portable, isolated application environment using Linux user namespaces.
 
+/// Print a line of output, unless `--quiet` asked for nothing but errors
+///
+/// Only voidbox's own output is affected; apps it runs keep their stdout.
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !$crate::is_quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
 pub mod cli;
 pub mod desktop;
+pub mod events;
 pub mod gui;
 pub mod manifest;
 pub mod runtime;
@@ -31,5 +44,116 @@ pub const SELF_UPDATE_REPO: &str = "voidbox";
 /// Container hostname
 pub const CONTAINER_HOSTNAME: &str = "voidbox";
 
+static VERBOSE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Enable verbose output (set from the global `--verbose` flag)
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Check whether verbose output is enabled
+pub fn is_verbose() -> bool {
+    VERBOSE.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+static PLAIN: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Enable plain output (set from the global `--plain` flag)
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Check whether plain output is enabled: tab-separated tables, no
+/// progress bars, no color
+pub fn is_plain() -> bool {
+    PLAIN.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+static ASSUME_YES: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Run unattended (set from the global `--yes` flag): confirmations are
+/// accepted, other questions get their non-interactive answer, and no
+/// dialogs are opened
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Check whether voidbox runs unattended
+pub fn assume_yes() -> bool {
+    ASSUME_YES.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Whether questions may be asked on the terminal; not with `--quiet`,
+/// which would leave them unseen
+pub fn can_prompt() -> bool {
+    use std::io::IsTerminal;
+    !assume_yes() && !is_quiet() && std::io::stdin().is_terminal()
+}
+
+/// Ask a question on the terminal and read the answer
+///
+/// Returns `None` when questions can't be asked (see `can_prompt`) or
+/// stdin has ended.
+pub fn ask(prompt: &str) -> Option<String> {
+    use std::io::Write;
+    if !can_prompt() {
+        return None;
+    }
+    print!("{} ", prompt);
+    std::io::stdout().flush().ok();
+    let mut input = String::new();
+    match std::io::stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(input.trim().to_string()),
+    }
+}
+
+/// Ask a yes/no question on the terminal
+///
+/// An empty answer, or a question that can't be asked, takes `default`;
+/// otherwise only "y" or "yes" is yes, and stdin ending is no.
+pub fn confirm(prompt: &str, default: bool) -> bool {
+    if !can_prompt() {
+        return default;
+    }
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+    match ask(&format!("{} {}", prompt, hint)) {
+        Some(answer) if answer.is_empty() => default,
+        Some(answer) => answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"),
+        None => false,
+    }
+}
+
+static QUIET: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Suppress all output but errors (set from the global `--quiet` flag)
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Check whether output is suppressed
+pub fn is_quiet() -> bool {
+    QUIET.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Whether output may be colored
+///
+/// Follows the NO_COLOR and CLICOLOR/CLICOLOR_FORCE conventions; otherwise
+/// colors are used only when stdout is a terminal.
+pub fn color_enabled() -> bool {
+    use std::io::IsTerminal;
+    let set = |name: &str| std::env::var(name).is_ok_and(|v| !v.is_empty());
+    if is_plain() || set("NO_COLOR") {
+        return false;
+    }
+    if std::env::var("CLICOLOR_FORCE").is_ok_and(|v| !v.is_empty() && v != "0") {
+        return true;
+    }
+    if std::env::var("CLICOLOR").is_ok_and(|v| v == "0") {
+        return false;
+    }
+    std::io::stdout().is_terminal()
+}
+
 /// Ubuntu releases URL for fetching base images
 pub const UBUNTU_RELEASES_URL: &str = "https://cdimage.ubuntu.com/ubuntu-base/releases/";
@@ -11,12 +11,14 @@ use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 use voidbox::cli;
-use voidbox::desktop::install_self;
+use voidbox::desktop::{self, install_self};
 use voidbox::gui;
-use voidbox::manifest::PermissionConfig;
+use voidbox::manifest::{ClipboardAccess, PermissionConfig};
 use voidbox::runtime::{
-    init_and_exec, setup_container_namespaces, setup_user_namespace, spawn_container_init,
+    ContainerSetup, init_and_exec, setup_container_namespaces, setup_user_namespace,
+    spawn_container_init,
 };
+use voidbox::say;
 use voidbox::storage::paths;
 
 #[derive(Parser)]
@@ -24,6 +26,32 @@ use voidbox::storage::paths;
 #[command(version = voidbox::VERSION)]
 #[command(about = "Universal Linux App Platform - portable, isolated application environments")]
 struct Cli {
+    /// Show more detail about what voidbox is doing
+    #[arg(long, short, global = true)]
+    verbose: bool,
+
+    /// Plain output: tab-separated tables, no progress bars or colors
+    #[arg(long, global = true)]
+    plain: bool,
+
+    /// Print JSON on stdout (list, search, verify, info, install and errors);
+    /// other output goes to stderr
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Run at low CPU and IO priority, for scripted and scheduled use
+    #[arg(long, global = true)]
+    background: bool,
+
+    /// Run unattended: accept confirmations, answer other questions the
+    /// non-interactive way, and never open dialogs or the GUI installer
+    #[arg(long, short, global = true)]
+    yes: bool,
+
+    /// Print nothing but errors (JSON output is still printed with --json)
+    #[arg(long, short, global = true)]
+    quiet: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -32,12 +60,40 @@ struct Cli {
 enum Commands {
     /// Install an app from a manifest
     Install {
-        /// Manifest source (file path, URL, or app name from registry)
-        source: String,
+        /// Manifest sources (file path, URL, or app name from registry);
+        /// `<name>@<version>` installs and pins a specific version. Several
+        /// are installed together: all of them or none
+        #[arg(required = true)]
+        sources: Vec<String>,
 
-        /// Force reinstall even if already installed
-        #[arg(long, short)]
+        /// Force reinstall even if already installed (same as --overwrite)
+        #[arg(long, short, visible_alias = "reinstall")]
         force: bool,
+
+        /// Replace conflicting files and existing installs without asking
+        #[arg(long, conflicts_with_all = ["skip", "abort"])]
+        overwrite: bool,
+
+        /// Keep conflicting files; skip apps that are already installed
+        #[arg(long, conflicts_with = "abort")]
+        skip: bool,
+
+        /// Fail on any conflict without asking
+        #[arg(long)]
+        abort: bool,
+
+        /// Show what would be downloaded and created without changing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Install `<name>@<version>` next to the main install instead of
+        /// replacing it
+        #[arg(long)]
+        side_by_side: bool,
+
+        /// Let a side-by-side install use the main install's data
+        #[arg(long, requires = "side_by_side")]
+        shared_data: bool,
     },
 
     /// Remove an installed app
@@ -48,6 +104,16 @@ enum Commands {
         /// Also remove all app data
         #[arg(long)]
         purge: bool,
+
+        /// Remove the app even if it is protected
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Put back a recently removed app, or list them without one
+    UndoRemove {
+        /// App name
+        app: Option<String>,
     },
 
     /// Run an installed app
@@ -63,14 +129,102 @@ enum Commands {
         #[arg(long)]
         dev: bool,
 
-        /// Additional arguments to pass to the app
-        #[arg(last = true)]
+        /// Print how long spec resolution, sandbox setup and the app's
+        /// first window took
+        #[arg(long)]
+        profile_startup: bool,
+
+        /// Arguments passed on to the app; everything after the first one
+        /// (or after `--`) is the app's, even if it looks like an option
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
     },
 
     /// List installed apps
     List,
 
+    /// Write a portable description of every installed app
+    Export {
+        /// File to write (prints to stdout if not specified)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Install the apps listed by `voidbox export` that aren't installed
+    Import {
+        /// File written by `voidbox export`
+        file: PathBuf,
+
+        /// Install every app at its exported version and pin it
+        #[arg(long)]
+        exact: bool,
+
+        /// Only report what would be installed
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Keep an app at its installed version; updates skip it
+    Pin {
+        /// App name
+        app: String,
+    },
+
+    /// Let updates move a pinned app to newer versions again
+    Unpin {
+        /// App name
+        app: String,
+    },
+
+    /// Protect an app from removal; removing it then needs --force
+    Protect {
+        /// App name
+        app: String,
+    },
+
+    /// Let a protected app be removed without --force again
+    Unprotect {
+        /// App name
+        app: String,
+    },
+
+    /// Go back to the version an app's last update replaced
+    Rollback {
+        /// App name
+        app: String,
+    },
+
+    /// Download and extract an app again from its saved manifest, keeping
+    /// its data
+    Repair {
+        /// App name
+        app: String,
+    },
+
+    /// Show an app's launch output, or its install output
+    Logs {
+        /// App name
+        app: String,
+
+        /// Show the install log instead of the launch log
+        #[arg(long)]
+        install: bool,
+
+        /// Number of lines to show
+        #[arg(long, short = 'n', default_value_t = 50)]
+        lines: usize,
+
+        /// Keep printing new output until interrupted
+        #[arg(long, short)]
+        follow: bool,
+    },
+
+    /// List the versions available for an app
+    Versions {
+        /// App name, manifest file or URL
+        app: String,
+    },
+
     /// Update apps
     Update {
         /// App name to update (updates all if not specified)
@@ -79,6 +233,27 @@ enum Commands {
         /// Force update even if already on latest
         #[arg(long, short)]
         force: bool,
+
+        /// Download the update now but apply it on the next launch
+        #[arg(long, conflicts_with = "apply")]
+        stage: bool,
+
+        /// Apply a previously staged update now
+        #[arg(long)]
+        apply: bool,
+
+        /// Allow updates that ask for new permissions without asking
+        #[arg(long)]
+        accept_permissions: bool,
+
+        /// Update from this manifest file if its version is newer
+        #[arg(
+            long,
+            value_name = "MANIFEST",
+            requires = "app",
+            conflicts_with = "apply"
+        )]
+        from: Option<PathBuf>,
     },
 
     /// Update voidbox itself
@@ -98,17 +273,203 @@ enum Commands {
         dev: bool,
     },
 
+    /// Show or change an app's permissions
+    Permissions {
+        /// App name
+        app: String,
+
+        /// Turn permissions on (e.g. --grant camera)
+        #[arg(long, value_name = "PERMISSION")]
+        grant: Vec<String>,
+
+        /// Turn permissions off (e.g. --revoke microphone)
+        #[arg(long, value_name = "PERMISSION")]
+        revoke: Vec<String>,
+
+        /// Clipboard access: none, read (paste only) or write
+        #[arg(long, value_name = "LEVEL")]
+        clipboard: Option<ClipboardAccess>,
+
+        /// Drop the user override and go back to the manifest's permissions
+        #[arg(long)]
+        reset: bool,
+    },
+
+    /// Show or change an app's environment variables
+    Env {
+        /// App name
+        app: String,
+
+        /// Run the app in a locale (sets LANG and LC_ALL, e.g. de_DE.UTF-8)
+        #[arg(long)]
+        locale: Option<String>,
+
+        /// Run the app in a time zone (sets TZ, e.g. Europe/Berlin)
+        #[arg(long)]
+        timezone: Option<String>,
+
+        /// Set a variable (e.g. --set GDK_SCALE=2)
+        #[arg(long, value_name = "NAME=VALUE")]
+        set: Vec<String>,
+
+        /// Remove a variable set with --set, --locale or --timezone
+        #[arg(long, value_name = "NAME")]
+        unset: Vec<String>,
+
+        /// Drop all user-set variables
+        #[arg(long)]
+        reset: bool,
+    },
+
+    /// Snapshot and restore app data
+    Data {
+        #[command(subcommand)]
+        action: DataCommands,
+    },
+
+    /// Back up app data
+    Backup {
+        #[command(subcommand)]
+        action: BackupCommands,
+    },
+
+    /// Show or undo the stripping and recompression of an app's files
+    Optimize {
+        /// App name
+        app: String,
+
+        /// Put the original files back
+        #[arg(long)]
+        restore: bool,
+    },
+
+    /// Import the apps another AppImage manager has registered
+    Migrate {
+        /// Tool to import from: appimagelauncher, gearlever or am
+        #[arg(long)]
+        from: String,
+
+        /// Only report what would be imported
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Find files left behind by uninstalled apps and offer to delete them
+    ScanLeftovers,
+
+    /// Check an installed app's files against the record made at install
+    Verify {
+        /// App name
+        app: String,
+    },
+
+    /// Search the configured repositories for apps
+    Search {
+        /// Text to look for in app names and descriptions
+        query: String,
+    },
+
     /// Show information about voidbox or a specific app
     Info {
         /// App name (shows voidbox info if not specified)
         app: Option<String>,
     },
 
-    /// Uninstall voidbox completely
+    /// Uninstall an app (asking whether to delete its data), or voidbox completely
     Uninstall {
-        /// Also remove all app data
-        #[arg(long)]
+        /// App to uninstall (uninstalls voidbox if not specified)
+        app: Option<String>,
+
+        /// Also delete the app's data, or all app data when uninstalling voidbox
+        #[arg(long, conflicts_with = "keep_data")]
         purge: bool,
+
+        /// Keep the app's data without asking
+        #[arg(long, requires = "app")]
+        keep_data: bool,
+
+        /// Uninstall even if the app, or with --purge any app, is protected
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Manage event webhooks
+    Webhook {
+        #[command(subcommand)]
+        action: WebhookCommands,
+    },
+
+    /// Manage the download cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommands,
+    },
+
+    /// Check a manifest file offline and report every problem in it
+    Validate {
+        /// Manifest file
+        file: PathBuf,
+    },
+
+    /// Work with manifest files
+    Manifest {
+        #[command(subcommand)]
+        action: ManifestCommands,
+    },
+
+    /// Show resource usage of running apps and stop them
+    Monitor {
+        /// Print a snapshot instead of opening the monitor window
+        #[arg(long)]
+        print: bool,
+    },
+
+    /// Check installed apps and the sandbox for problems
+    Health {
+        /// App to check (all apps if omitted)
+        app: Option<String>,
+    },
+
+    /// Check PATH, directories, the display and network access for problems
+    Doctor {
+        /// Also check that this app's windows match its desktop entry
+        app: Option<String>,
+    },
+
+    /// Restore a broken voidbox binary from the backup kept by self-update
+    Recover,
+
+    /// Remove stale downloads and leftover temporary directories
+    Clean {
+        /// Remove downloads unused for this long, e.g. "30d" or "2w"
+        /// (defaults to config)
+        #[arg(long)]
+        max_age: Option<String>,
+    },
+
+    /// Run housekeeping: gc, cache pruning and verification, update checks
+    Maintain {
+        /// Install a weekly systemd user timer that runs this command
+        #[arg(long)]
+        install_timer: bool,
+    },
+
+    /// Maintain manifest repositories
+    Repo {
+        #[command(subcommand)]
+        action: RepoCommands,
+    },
+
+    /// Manage installed apps' desktop entries, icons and command wrappers
+    Desktop {
+        #[command(subcommand)]
+        action: DesktopCommands,
+    },
+
+    /// Manage apps on other machines over SSH
+    Remote {
+        #[command(subcommand)]
+        action: RemoteCommands,
     },
 
     /// Internal initialization command (do not use manually)
@@ -119,6 +480,9 @@ enum Commands {
         /// Serialized permissions JSON
         #[arg(long)]
         permissions: Option<String>,
+        /// Serialized container setup JSON
+        #[arg(long)]
+        setup: Option<String>,
         #[arg(last = true)]
         args: Vec<String>,
     },
@@ -133,6 +497,211 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum DataCommands {
+    /// Snapshot an app's data now
+    Snapshot {
+        /// App name
+        app: String,
+    },
+    /// List an app's data snapshots
+    List {
+        /// App name
+        app: String,
+    },
+    /// Replace an app's data with a snapshot
+    Restore {
+        /// App name
+        app: String,
+
+        /// Snapshot to restore (see `voidbox data list`)
+        #[arg(long = "from", value_name = "SNAPSHOT")]
+        snapshot: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupCommands {
+    /// Back up the data of apps marked for backup (or of the given apps)
+    Data {
+        /// Apps to back up (defaults to the marked ones)
+        apps: Vec<String>,
+
+        /// Destination directory, or restic:<repository> (defaults to config)
+        #[arg(long)]
+        to: Option<String>,
+
+        /// Stop running apps during the backup if their data can't be
+        /// snapshotted, and start them again afterwards
+        #[arg(long)]
+        stop_running: bool,
+    },
+    /// Mark an app's data for backup
+    Mark {
+        /// App name
+        app: String,
+    },
+    /// Stop backing up an app's data
+    Unmark {
+        /// App name
+        app: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum WebhookCommands {
+    /// Fire a test event at all configured webhooks
+    Test,
+}
+
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Re-hash cached downloads and evict corrupt entries
+    Verify,
+    /// Evict least-recently-used downloads to fit a size limit
+    Prune {
+        /// Maximum cache size, e.g. "500M" or "5G" (defaults to config)
+        #[arg(long)]
+        max_size: Option<String>,
+    },
+    /// Share the download cache with machines on the LAN
+    Serve {
+        /// Port to listen on (defaults to config)
+        #[arg(long)]
+        port: Option<u16>,
+    },
+    /// Show the icon and metadata cache, dropping expired entries
+    Metadata {
+        /// Delete every cached entry
+        #[arg(long)]
+        clear: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ManifestCommands {
+    /// Print the canonical content hash of a manifest
+    Hash {
+        /// Manifest file
+        file: PathBuf,
+        /// Print the canonical form instead
+        #[arg(long)]
+        canonical: bool,
+    },
+
+    /// Print the text a manifest's signature covers, for minisign -S -l
+    SignedText {
+        /// Manifest file
+        file: PathBuf,
+    },
+
+    /// Edit a manifest (file or installed app) in a form
+    Edit {
+        /// Manifest file (created on save) or installed app name
+        target: String,
+    },
+
+    /// Scaffold a new manifest
+    New {
+        /// App name (lowercase, hyphens)
+        name: String,
+
+        /// GitHub repository to download releases from (owner/repo)
+        #[arg(long, conflicts_with = "url", required_unless_present = "url")]
+        github: Option<String>,
+
+        /// Direct download URL
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Binary name inside the archive (defaults to the app name)
+        #[arg(long)]
+        binary: Option<String>,
+
+        /// Display name (defaults to the app name)
+        #[arg(long)]
+        display_name: Option<String>,
+
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Generate a best-effort Flatpak manifest from a manifest
+    ExportFlatpak {
+        /// Manifest file or installed app name
+        name: String,
+
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RepoCommands {
+    /// Validate a repository's manifests and write its index.toml
+    Build {
+        /// Repository directory
+        dir: PathBuf,
+    },
+
+    /// Add a repository to search (directory, index file, URL or gh:user/repo)
+    Add {
+        /// Repository location
+        location: String,
+    },
+
+    /// Stop searching a repository
+    Remove {
+        /// Repository location
+        location: String,
+    },
+
+    /// Fetch the indexes of all repositories now
+    Refresh,
+
+    /// Refresh the known-good checksum database from sources and repositories
+    Checksums,
+
+    /// Check every manifest of a repository and its downloads, for CI
+    Verify {
+        /// Repository location (directory, index file, URL or gh:user/repo)
+        location: String,
+        /// Request only the downloads' headers, without checking hashes
+        #[arg(long)]
+        headers_only: bool,
+        /// Also write the report as JSON to this file
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DesktopCommands {
+    /// Write desktop entries, icons and command wrappers again
+    Regen {
+        /// App to regenerate (all installed apps if not specified)
+        app: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RemoteCommands {
+    /// Install an app on a remote machine (copies voidbox there if missing)
+    Install {
+        /// SSH destination (user@host)
+        host: String,
+
+        /// Manifest source (file path, URL, or app name from registry)
+        source: String,
+
+        /// Force reinstall even if already installed
+        #[arg(long, short)]
+        force: bool,
+    },
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Check if we're running as a launcher (void_brave, void_discord, etc.)
     // This uses argv[0] detection similar to busybox
@@ -143,21 +712,51 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Check if we're being double-clicked (no args, not a TTY)
     let args: Vec<String> = std::env::args().collect();
     if args.len() == 1 && gui::is_gui_mode() {
-        return gui_install_mode();
+        let result = gui_install_mode();
+        voidbox::events::wait_for_webhooks();
+        return result;
     }
 
     let cli = Cli::parse();
+    voidbox::set_verbose(cli.verbose);
+    voidbox::set_assume_yes(cli.yes);
+    voidbox::set_quiet(cli.quiet);
+    // JSON and quiet output have no use for progress bars or colors either
+    voidbox::set_plain(cli.plain || cli.json || cli.quiet);
+    if cli.json {
+        cli::enable_json_output()?;
+    }
+
+    let command = cli.command.unwrap_or(Commands::List);
+
+    // A dry run must not leave anything behind, not even voidbox's own
+    // directories
+    let dry_run = matches!(command, Commands::Install { dry_run: true, .. });
 
     // Ensure data directories exist
-    paths::ensure_dirs()?;
+    if !dry_run {
+        paths::ensure_dirs()?;
+    }
 
-    let command = cli.command.unwrap_or(Commands::List);
+    // Maintenance is always background work; other commands run at normal
+    // priority unless asked
+    let background = cli.background
+        || matches!(
+            command,
+            Commands::Maintain {
+                install_timer: false
+            }
+        );
+    let _background = background.then(voidbox::runtime::enter_background);
 
-    // Self-install on first run (skip for internal commands)
-    if !matches!(
-        command,
-        Commands::InternalInit { .. } | Commands::InternalRun { .. }
-    ) {
+    // Self-install on first run (skip for internal commands, for recover,
+    // which checks what it replaces, and for dry runs)
+    if !dry_run
+        && !matches!(
+            command,
+            Commands::InternalInit { .. } | Commands::InternalRun { .. } | Commands::Recover
+        )
+    {
         if !voidbox::desktop::is_installed() {
             if let Err(e) = install_self() {
                 eprintln!("[voidbox] Warning: Self-installation failed: {}", e);
@@ -165,31 +764,148 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    // Started from the desktop, nothing reads stdout: long operations show a
+    // progress dialog and errors an error dialog
+    let frontend = gui::frontend();
+    let internal = matches!(
+        command,
+        Commands::InternalInit { .. } | Commands::InternalRun { .. }
+    );
+    let progress = progress_text(&command)
+        .filter(|_| frontend == gui::Frontend::Gui)
+        .map(|text| gui::GuiProgress::new("Voidbox", text));
+
+    let result = run_command(command);
+    voidbox::events::wait_for_webhooks();
+    if let Err(e) = &result {
+        if cli::is_json() {
+            cli::print_json_error(e.as_ref());
+            std::process::exit(1);
+        }
+        if frontend == gui::Frontend::Gui && !internal {
+            drop(progress);
+            gui::show_error("Voidbox Error", &e.to_string());
+            std::process::exit(1);
+        }
+    }
+    result
+}
+
+/// Progress dialog message for commands worth one when run from the desktop
+///
+/// Installs open the installer window instead, and commands that run apps
+/// or only print show nothing.
+fn progress_text(command: &Commands) -> Option<&'static str> {
+    Some(match command {
+        Commands::Update { apply: true, .. } => "Applying updates...",
+        Commands::Update { .. } => "Updating apps...",
+        Commands::SelfUpdate { .. } => "Updating Voidbox...",
+        Commands::Remove { .. } | Commands::Uninstall { app: Some(_), .. } => "Removing app...",
+        Commands::Uninstall { app: None, .. } => "Uninstalling Voidbox...",
+        Commands::Import { dry_run: false, .. } => "Installing apps...",
+        Commands::Install {
+            sources,
+            dry_run: false,
+            ..
+        } if sources.len() > 1 => "Installing apps...",
+        Commands::Rollback { .. } => "Rolling back...",
+        Commands::Repair { .. } => "Repairing app...",
+        _ => return None,
+    })
+}
+
+/// Run a parsed subcommand
+fn run_command(command: Commands) -> Result<(), Box<dyn std::error::Error>> {
     match command {
-        Commands::Install { source, force } => {
-            cli::install_app(&source, force)?;
+        Commands::Install {
+            sources,
+            force,
+            overwrite,
+            skip,
+            abort,
+            dry_run,
+            side_by_side,
+            shared_data,
+        } => {
+            let policy = cli::ConflictPolicy::from_flags(force || overwrite, skip, abort);
+            let slot = side_by_side.then_some(if shared_data {
+                cli::SlotData::Shared
+            } else {
+                cli::SlotData::Separate
+            });
+            match sources.as_slice() {
+                [source] => cli::install_app(source, policy, voidbox::assume_yes(), dry_run, slot)?,
+                _ => cli::install_apps(&sources, policy, voidbox::assume_yes(), dry_run, slot)?,
+            }
+        }
+
+        Commands::Export { output } => cli::export_to(output.as_deref())?,
+
+        Commands::Import {
+            file,
+            exact,
+            dry_run,
+        } => cli::import_setup(&file, exact, dry_run)?,
+
+        Commands::Pin { app } => cli::pin_app(&app)?,
+
+        Commands::Unpin { app } => cli::unpin_app(&app)?,
+
+        Commands::Protect { app } => cli::protect_app(&app)?,
+
+        Commands::Unprotect { app } => cli::unprotect_app(&app)?,
+
+        Commands::Rollback { app } => cli::rollback_app(&app)?,
+
+        Commands::Repair { app } => cli::repair_app(&app)?,
+
+        Commands::Logs {
+            app,
+            install,
+            lines,
+            follow,
+        } => cli::show_logs(&app, install, lines, follow)?,
+
+        Commands::Versions { app } => {
+            cli::show_versions(&app)?;
         }
 
-        Commands::Remove { app, purge } => {
-            cli::remove_app(&app, purge)?;
+        Commands::Remove { app, purge, force } => {
+            cli::remove_app(&app, purge, force)?;
         }
 
+        Commands::UndoRemove { app } => cli::undo_remove(app.as_deref())?,
+
         Commands::Run {
             app,
             url,
             dev,
+            profile_startup,
             args,
         } => {
-            cli::run_app(&app, &args, url.as_deref(), dev)?;
+            cli::run_app(&app, &args, url.as_deref(), dev, profile_startup)?;
         }
 
         Commands::List => {
             cli::list_apps()?;
         }
 
-        Commands::Update { app, force } => match app {
-            Some(app_name) => cli::update_app(&app_name, force)?,
-            None => cli::update_all(force)?,
+        Commands::Update {
+            app,
+            force,
+            stage,
+            apply,
+            accept_permissions,
+            from,
+        } => match (app, apply) {
+            (Some(app_name), true) => cli::apply_update(&app_name)?,
+            (None, true) => cli::apply_all_updates()?,
+            (Some(app_name), false) => {
+                cli::update_app(&app_name, force, stage, accept_permissions, from.as_deref())?
+            }
+            (None, false) => {
+                cli::update_all(force, stage, voidbox::assume_yes(), accept_permissions)?
+            }
         },
 
         Commands::SelfUpdate { force } => {
@@ -200,19 +916,182 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             cli::shell(&app, dev)?;
         }
 
+        Commands::Permissions {
+            app,
+            grant,
+            revoke,
+            clipboard,
+            reset,
+        } => cli::permissions(&app, &grant, &revoke, clipboard, reset)?,
+
+        Commands::Env {
+            app,
+            locale,
+            timezone,
+            set,
+            unset,
+            reset,
+        } => cli::env(
+            &app,
+            locale.as_deref(),
+            timezone.as_deref(),
+            &set,
+            &unset,
+            reset,
+        )?,
+
+        Commands::Data { action } => match action {
+            DataCommands::Snapshot { app } => cli::data_snapshot(&app)?,
+            DataCommands::List { app } => cli::data_list(&app)?,
+            DataCommands::Restore { app, snapshot } => cli::data_restore(&app, &snapshot)?,
+        },
+
+        Commands::Optimize { app, restore } => cli::optimize(&app, restore)?,
+
+        Commands::ScanLeftovers => cli::scan_leftovers(voidbox::assume_yes())?,
+
+        Commands::Migrate { from, dry_run } => cli::migrate(&from, dry_run)?,
+
+        Commands::Backup { action } => match action {
+            BackupCommands::Data {
+                apps,
+                to,
+                stop_running,
+            } => cli::backup_data(to.as_deref(), &apps, stop_running)?,
+            BackupCommands::Mark { app } => cli::backup_mark(&app, true)?,
+            BackupCommands::Unmark { app } => cli::backup_mark(&app, false)?,
+        },
+
+        Commands::Search { query } => cli::search(&query)?,
+
+        Commands::Verify { app } => cli::verify_app(&app)?,
+
         Commands::Info { app } => match app {
             Some(app_name) => cli::show_app_info(&app_name)?,
             None => cli::show_voidbox_info()?,
         },
 
-        Commands::Uninstall { purge } => {
-            uninstall_voidbox(purge)?;
+        Commands::Uninstall {
+            app: Some(app),
+            purge,
+            keep_data,
+            force,
+        } => {
+            cli::uninstall_app(&app, purge, keep_data, force)?;
+        }
+
+        Commands::Uninstall {
+            app: None,
+            purge,
+            force,
+            ..
+        } => {
+            uninstall_voidbox(purge, force)?;
+        }
+
+        Commands::Webhook { action } => match action {
+            WebhookCommands::Test => cli::test_webhooks()?,
+        },
+
+        Commands::Cache { action } => match action {
+            CacheCommands::Verify => cli::cache_verify()?,
+            CacheCommands::Prune { max_size } => cli::cache_prune(max_size.as_deref())?,
+            CacheCommands::Serve { port } => cli::cache_serve(port)?,
+            CacheCommands::Metadata { clear } => cli::cache_metadata(clear)?,
+        },
+
+        Commands::Clean { max_age } => cli::clean(max_age.as_deref())?,
+
+        Commands::Monitor { print } => {
+            if print || !cli::has_display() {
+                cli::print_running_apps();
+            } else {
+                gui::run_task_monitor()?;
+            }
         }
 
+        Commands::Health { app } => cli::health(app.as_deref())?,
+
+        Commands::Doctor { app } => cli::doctor(app.as_deref())?,
+
+        Commands::Recover => cli::recover()?,
+
+        Commands::Maintain { install_timer } => {
+            if install_timer {
+                cli::install_maintenance_timer()?;
+            } else {
+                cli::maintain()?;
+            }
+        }
+
+        Commands::Desktop { action } => match action {
+            DesktopCommands::Regen { app } => cli::regen_desktop(app.as_deref())?,
+        },
+
+        Commands::Repo { action } => match action {
+            RepoCommands::Build { dir } => cli::build_repo_index(&dir)?,
+            RepoCommands::Add { location } => cli::add_repository(&location)?,
+            RepoCommands::Remove { location } => cli::remove_repository(&location)?,
+            RepoCommands::Refresh => {
+                say!("[voidbox] Refreshing repository indexes...");
+                let failed = cli::refresh_repositories(true)?;
+                if failed > 0 {
+                    return Err(
+                        format!("{} repository index(es) could not be fetched", failed).into(),
+                    );
+                }
+            }
+            RepoCommands::Checksums => {
+                cli::refresh_checksums()?;
+            }
+            RepoCommands::Verify {
+                location,
+                headers_only,
+                report,
+            } => cli::verify_repository(&location, headers_only, report.as_deref())?,
+        },
+
+        Commands::Remote { action } => match action {
+            RemoteCommands::Install {
+                host,
+                source,
+                force,
+            } => cli::remote_install(&host, &source, force)?,
+        },
+
+        Commands::Validate { file } => cli::validate_file(&file)?,
+
+        Commands::Manifest { action } => match action {
+            ManifestCommands::Hash { file, canonical } => cli::hash_manifest(&file, canonical)?,
+            ManifestCommands::SignedText { file } => cli::print_signed_text(&file)?,
+            ManifestCommands::Edit { target } => {
+                gui::run_manifest_editor(cli::manifest_edit_path(&target)?)?
+            }
+            ManifestCommands::New {
+                name,
+                github,
+                url,
+                binary,
+                display_name,
+                output,
+            } => cli::new_manifest(
+                &name,
+                github.as_deref(),
+                url.as_deref(),
+                binary.as_deref(),
+                display_name.as_deref(),
+                output.as_deref(),
+            )?,
+            ManifestCommands::ExportFlatpak { name, output } => {
+                cli::export_flatpak(&name, output.as_deref())?
+            }
+        },
+
         Commands::InternalInit {
             rootfs,
             cmd,
             permissions,
+            setup,
             args,
         } => {
             // This runs inside the new namespace after fork
@@ -221,7 +1100,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 Some(json) => serde_json::from_str(&json).unwrap_or_default(),
                 None => PermissionConfig::default(),
             };
-            init_and_exec(&rootfs, &cmd, &args, &perms)?;
+            let setup = match setup {
+                Some(json) => serde_json::from_str(&json).unwrap_or_default(),
+                None => ContainerSetup::default(),
+            };
+            init_and_exec(&rootfs, &cmd, &args, &perms, &setup)?;
         }
 
         Commands::InternalRun { rootfs, cmd, args } => {
@@ -240,12 +1123,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 fonts: false,  // Don't mount fonts - let packages install
                 themes: false, // Don't mount themes/icons - let packages install
                 native_mode: false,
+                clipboard: ClipboardAccess::None,
+                session_bus: false,
             };
             setup_user_namespace()?;
             setup_container_namespaces()?;
 
             let self_exe = std::env::current_exe()?;
-            let status = spawn_container_init(&self_exe, &rootfs, &cmd, &args, &permissions)?;
+            let status = spawn_container_init(
+                &self_exe,
+                &rootfs,
+                &cmd,
+                &args,
+                &permissions,
+                &ContainerSetup {
+                    privileged: true,
+                    ..Default::default()
+                },
+            )?;
 
             if !status.success() {
                 std::process::exit(status.code().unwrap_or(1));
@@ -256,33 +1151,57 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn uninstall_voidbox(purge: bool) -> Result<(), Box<dyn std::error::Error>> {
+fn uninstall_voidbox(purge: bool, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let protected: Vec<String> = cli::get_installed_apps()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|a| a.protected)
+        .map(|a| a.name)
+        .collect();
+    if purge && !force && !protected.is_empty() {
+        return Err(format!(
+            "Protected apps would lose their data: {}; uninstall with --force to remove them too",
+            protected.join(", ")
+        )
+        .into());
+    }
+
     if purge {
-        println!("[voidbox] This will remove voidbox and ALL app data.");
+        say!("[voidbox] This will remove voidbox and ALL app data.");
     } else {
-        println!("[voidbox] This will remove voidbox but keep app data.");
+        say!("[voidbox] This will remove voidbox but keep app data.");
     }
-    print!("Continue? [y/N] ");
-    std::io::Write::flush(&mut std::io::stdout())?;
-
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)?;
-
-    if input.trim().to_lowercase() != "y" {
-        println!("[voidbox] Uninstall cancelled.");
+    if !voidbox::assume_yes() && !voidbox::confirm("[voidbox] Continue?", false) {
+        say!("[voidbox] Uninstall cancelled.");
         return Ok(());
     }
 
-    println!("[voidbox] Uninstalling...");
+    say!("[voidbox] Uninstalling...");
 
     // Remove install binary
     let install_path = paths::install_path();
     if install_path.exists() {
         std::fs::remove_file(&install_path)?;
-        println!("  Removed {}", install_path.display());
+        say!("  Removed {}", install_path.display());
+    }
+
+    // Remove each app's desktop integration; entries named by app ID
+    // don't share the voidbox- prefix swept below
+    let _refresh = desktop::refresh_batch();
+    for app in cli::get_installed_apps().unwrap_or_default() {
+        if let Err(e) = desktop::remove_desktop_entry(&app.name, app.id.as_deref()) {
+            say!(
+                "  Warning: Could not remove {}'s desktop entry: {}",
+                app.name,
+                e
+            );
+        }
+        desktop::remove_icon(&app.name).ok();
+        desktop::remove_app_wrapper(&app.name).ok();
+        desktop::remove_window_rules(&app.name).ok();
     }
 
-    // Remove all desktop entries
+    // Remove any other voidbox desktop entries
     let desktop_dir = paths::desktop_dir();
     if desktop_dir.exists() {
         for entry in std::fs::read_dir(&desktop_dir)? {
@@ -290,7 +1209,7 @@ fn uninstall_voidbox(purge: bool) -> Result<(), Box<dyn std::error::Error>> {
                 let name = entry.file_name().to_string_lossy().to_string();
                 if name.starts_with("voidbox-") && name.ends_with(".desktop") {
                     std::fs::remove_file(entry.path())?;
-                    println!("  Removed {}", entry.path().display());
+                    say!("  Removed {}", entry.path().display());
                 }
             }
         }
@@ -300,18 +1219,18 @@ fn uninstall_voidbox(purge: bool) -> Result<(), Box<dyn std::error::Error>> {
         // Remove entire data directory
         let data_dir = paths::data_dir();
         if data_dir.exists() {
-            println!("  Removing data directory (this may take a moment)...");
+            say!("  Removing data directory (this may take a moment)...");
             std::fs::remove_dir_all(&data_dir)?;
-            println!("  Removed {}", data_dir.display());
+            say!("  Removed {}", data_dir.display());
         }
     } else {
-        println!();
-        println!("  Note: App data kept at {}", paths::data_dir().display());
-        println!("  Use --purge to remove everything.");
+        say!();
+        say!("  Note: App data kept at {}", paths::data_dir().display());
+        say!("  Use --purge to remove everything.");
     }
 
-    println!();
-    println!("[voidbox] Uninstall complete!");
+    say!();
+    say!("[voidbox] Uninstall complete!");
 
     Ok(())
 }
@@ -358,7 +1277,7 @@ fn gui_install_mode() -> Result<(), Box<dyn std::error::Error>> {
     if let Err(e) = run_installer(InstallType::SelfInstall) {
         eprintln!("GUI Error: {}", e);
         // Fallback to text mode if GUI fails (unlikely with egui)
-        println!("Falling back to terminal mode...");
+        say!("Falling back to terminal mode...");
     }
 
     Ok(())
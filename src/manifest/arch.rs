@@ -0,0 +1,77 @@
+//! Per-architecture sources
+//!
+//! A manifest's `[source]` is the download for the architectures listed in
+//! `runtime.arch`, x86_64 when it lists none. Other architectures get a
+//! source of their own under `[sources.<arch>]`, which also takes the place
+//! of `[source]` for an architecture listed in both. Installs pick the
+//! host's source with `for_host_arch`; the manifest is signed and hashed
+//! with all of them.
+
+use super::{AppManifest, ManifestError, SourceConfig};
+use std::collections::BTreeMap;
+
+/// Architectures manifests can give sources for
+pub const SUPPORTED_ARCHES: &[&str] = &["x86_64", "aarch64", "armv7"];
+
+/// Architecture `[source]` is for when `runtime.arch` lists none
+const DEFAULT_ARCH: &str = "x86_64";
+
+/// The host's architecture, as manifests name it
+pub fn host_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "arm" => "armv7",
+        arch => arch,
+    }
+}
+
+/// The host's architecture as Debian and Ubuntu name it
+pub fn host_debian_arch() -> &'static str {
+    match host_arch() {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "armv7" => "armhf",
+        arch => arch,
+    }
+}
+
+/// Every architecture a manifest has a source for, with that source
+pub fn arch_sources(manifest: &AppManifest) -> BTreeMap<String, SourceConfig> {
+    let mut sources = manifest.sources.clone();
+    let default_arches = if manifest.runtime.arch.is_empty() {
+        vec![DEFAULT_ARCH.to_string()]
+    } else {
+        manifest.runtime.arch.clone()
+    };
+    for arch in default_arches {
+        sources
+            .entry(arch)
+            .or_insert_with(|| manifest.source.clone());
+    }
+    sources
+}
+
+/// The manifest with its source for an architecture as `[source]`
+///
+/// Every architecture's source is kept under `[sources]`, so the result
+/// can be picked from again on another machine.
+pub fn for_arch(manifest: &AppManifest, arch: &str) -> Result<AppManifest, ManifestError> {
+    let sources = arch_sources(manifest);
+    let Some(source) = sources.get(arch) else {
+        return Err(ManifestError::UnsupportedArch {
+            app: manifest.app.display_name.clone(),
+            arch: arch.to_string(),
+            supported: sources.keys().cloned().collect::<Vec<_>>().join(", "),
+        });
+    };
+    let mut selected = manifest.clone();
+    selected.source = source.clone();
+    if !manifest.sources.is_empty() {
+        selected.sources = sources;
+    }
+    Ok(selected)
+}
+
+/// The manifest with the host's source as `[source]`
+pub fn for_host_arch(manifest: &AppManifest) -> Result<AppManifest, ManifestError> {
+    for_arch(manifest, host_arch())
+}
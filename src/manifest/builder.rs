@@ -0,0 +1,303 @@
+//! Typed manifest construction
+//!
+//! [`ManifestBuilder`] takes every required field up front, so a manifest
+//! built in Rust can't be missing one; optional fields are set with chained
+//! methods and the result is validated by [`ManifestBuilder::build`].
+
+use super::ManifestError;
+use super::schema::*;
+use super::validate::validate_manifest;
+use std::collections::BTreeMap;
+
+impl SourceConfig {
+    /// GitHub releases source with default asset matching
+    pub fn github(owner: impl Into<String>, repo: impl Into<String>) -> Self {
+        SourceConfig::Github {
+            owner: owner.into(),
+            repo: repo.into(),
+            asset_pattern: None,
+            asset_os: "linux".to_string(),
+            asset_arch: "amd64".to_string(),
+            asset_extension: None,
+            tag: None,
+            sha256: None,
+        }
+    }
+
+    /// Direct download source
+    pub fn direct(url: impl Into<String>) -> Self {
+        SourceConfig::Direct {
+            url: url.into(),
+            version_url: None,
+            archive_type: None,
+            sha256: None,
+        }
+    }
+}
+
+/// Builder for [`AppManifest`]
+#[derive(Debug, Clone)]
+pub struct ManifestBuilder {
+    manifest: AppManifest,
+}
+
+impl ManifestBuilder {
+    /// Start a manifest from its required fields
+    pub fn new(
+        name: impl Into<String>,
+        display_name: impl Into<String>,
+        source: SourceConfig,
+        binary: impl Into<String>,
+    ) -> Self {
+        Self {
+            manifest: AppManifest {
+                schema_version: Some(SCHEMA_VERSION),
+                app: AppInfo {
+                    name: name.into(),
+                    id: None,
+                    display_name: display_name.into(),
+                    description: String::new(),
+                    version: None,
+                    license: None,
+                    publisher: None,
+                },
+                source,
+                sources: BTreeMap::new(),
+                runtime: RuntimeConfig::default(),
+                dependencies: DependencyConfig::default(),
+                binary: BinaryConfig {
+                    name: binary.into(),
+                    path: None,
+                    args: Vec::new(),
+                    install_dir: None,
+                    single_instance: false,
+                    env: BTreeMap::new(),
+                    block_updater: None,
+                    exclude: Vec::new(),
+                    optimize: None,
+                },
+                desktop: DesktopConfig::default(),
+                permissions: None,
+                dbus: DbusConfig::default(),
+                hints: ProgressHints::default(),
+                hooks: HooksConfig::default(),
+                signature: None,
+                repository: None,
+            },
+        }
+    }
+
+    /// Reverse-DNS app ID (e.g. "io.github.foo.Player")
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.manifest.app.id = Some(id.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.manifest.app.description = description.into();
+        self
+    }
+
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.manifest.app.version = Some(version.into());
+        self
+    }
+
+    pub fn license(mut self, license: impl Into<String>) -> Self {
+        self.manifest.app.license = Some(license.into());
+        self
+    }
+
+    pub fn publisher(mut self, publisher: impl Into<String>) -> Self {
+        self.manifest.app.publisher = Some(publisher.into());
+        self
+    }
+
+    /// Base image (e.g. "ubuntu:24.04")
+    pub fn base(mut self, base: impl Into<String>) -> Self {
+        self.manifest.runtime.base = base.into();
+        self
+    }
+
+    pub fn arch(mut self, arch: impl Into<String>) -> Self {
+        self.manifest.runtime.arch.push(arch.into());
+        self
+    }
+
+    /// Source for an architecture, used there instead of `source`
+    pub fn arch_source(mut self, arch: impl Into<String>, source: SourceConfig) -> Self {
+        self.manifest.sources.insert(arch.into(), source);
+        self
+    }
+
+    pub fn tmp_size(mut self, size: impl Into<String>) -> Self {
+        self.manifest.runtime.tmp_size = Some(size.into());
+        self
+    }
+
+    pub fn system_libraries(mut self, prefer: bool) -> Self {
+        self.manifest.runtime.system_libraries = Some(prefer);
+        self
+    }
+
+    /// Library kept bundled when system libraries are preferred
+    pub fn bundled_library(mut self, library: impl Into<String>) -> Self {
+        self.manifest.runtime.bundled_libraries.push(library.into());
+        self
+    }
+
+    /// Extra package to install into the rootfs
+    pub fn package(mut self, package: impl Into<String>) -> Self {
+        self.manifest.dependencies.packages.push(package.into());
+        self
+    }
+
+    /// Other voidbox app to install first
+    pub fn app_dependency(mut self, source: impl Into<String>) -> Self {
+        self.manifest.dependencies.apps.push(source.into());
+        self
+    }
+
+    /// Path of the binary inside the extracted archive
+    pub fn binary_path(mut self, path: impl Into<String>) -> Self {
+        self.manifest.binary.path = Some(path.into());
+        self
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.manifest.binary.args.push(arg.into());
+        self
+    }
+
+    pub fn install_dir(mut self, dir: impl Into<String>) -> Self {
+        self.manifest.binary.install_dir = Some(dir.into());
+        self
+    }
+
+    pub fn single_instance(mut self, single_instance: bool) -> Self {
+        self.manifest.binary.single_instance = single_instance;
+        self
+    }
+
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.manifest.binary.env.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn block_updater(mut self, block: bool) -> Self {
+        self.manifest.binary.block_updater = Some(block);
+        self
+    }
+
+    /// Glob of payload files not to install
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.manifest.binary.exclude.push(pattern.into());
+        self
+    }
+
+    pub fn optimize(mut self, optimize: bool) -> Self {
+        self.manifest.binary.optimize = Some(optimize);
+        self
+    }
+
+    pub fn category(mut self, category: impl Into<String>) -> Self {
+        self.manifest.desktop.categories.push(category.into());
+        self
+    }
+
+    pub fn mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.manifest.desktop.mime_types.push(mime_type.into());
+        self
+    }
+
+    pub fn keyword(mut self, keyword: impl Into<String>) -> Self {
+        self.manifest.desktop.keywords.push(keyword.into());
+        self
+    }
+
+    pub fn icon(mut self, icon: impl Into<String>) -> Self {
+        self.manifest.desktop.icon = Some(icon.into());
+        self
+    }
+
+    pub fn wm_class(mut self, wm_class: impl Into<String>) -> Self {
+        self.manifest.desktop.wm_class = Some(wm_class.into());
+        self
+    }
+
+    /// Whether the app completes startup notification
+    pub fn startup_notify(mut self, startup_notify: bool) -> Self {
+        self.manifest.desktop.startup_notify = Some(startup_notify);
+        self
+    }
+
+    /// Whether to write window-rule hints for the app's `wm_class`
+    pub fn window_rules(mut self, window_rules: bool) -> Self {
+        self.manifest.desktop.window_rules = window_rules;
+        self
+    }
+
+    /// D-Bus name used to activate a running instance
+    pub fn dbus_name(mut self, dbus_name: impl Into<String>) -> Self {
+        self.manifest.desktop.dbus_name = Some(dbus_name.into());
+        self
+    }
+
+    pub fn generic_name(mut self, generic_name: impl Into<String>) -> Self {
+        self.manifest.desktop.generic_name = Some(generic_name.into());
+        self
+    }
+
+    /// Add translated desktop strings for a locale
+    pub fn localized(mut self, locale: impl Into<String>, strings: LocalizedStrings) -> Self {
+        self.manifest
+            .desktop
+            .localized
+            .insert(locale.into(), strings);
+        self
+    }
+
+    pub fn permissions(mut self, permissions: PermissionConfig) -> Self {
+        self.manifest.permissions = Some(permissions);
+        self
+    }
+
+    pub fn dbus(mut self, dbus: DbusConfig) -> Self {
+        self.manifest.dbus = dbus;
+        self
+    }
+
+    /// Size and unpack time hints for the install's progress bar
+    pub fn hints(mut self, hints: ProgressHints) -> Self {
+        self.manifest.hints = hints;
+        self
+    }
+
+    /// Step to run once the app's files are unpacked
+    pub fn post_install(mut self, step: HookStep) -> Self {
+        self.manifest.hooks.post_install.push(step);
+        self
+    }
+
+    /// Step to run before the app's files are removed
+    pub fn pre_remove(mut self, step: HookStep) -> Self {
+        self.manifest.hooks.pre_remove.push(step);
+        self
+    }
+
+    /// Validate and return the manifest
+    pub fn build(self) -> Result<AppManifest, ManifestError> {
+        validate_manifest(&self.manifest)?;
+        Ok(self.manifest)
+    }
+
+    /// Validate and serialize the manifest to TOML
+    pub fn to_toml(self) -> Result<String, ManifestError> {
+        manifest_to_toml(&self.build()?)
+    }
+}
+
+/// Serialize a manifest to TOML
+pub fn manifest_to_toml(manifest: &AppManifest) -> Result<String, ManifestError> {
+    toml::to_string_pretty(manifest).map_err(|e| ManifestError::SerializeError(e.to_string()))
+}
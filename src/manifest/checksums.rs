@@ -0,0 +1,378 @@
+//! Database of known-good artifact checksums
+//!
+//! Most manifests point at upstream downloads without recording a hash, so
+//! a tampered or swapped artifact would install unnoticed. A checksum
+//! database maps artifact URLs (with `*` wildcards) and versions to the
+//! SHA-256 upstream published, so such downloads can still be verified.
+//! Databases are `checksums.toml` files published next to a repository's
+//! `index.toml`; `voidbox repo checksums` merges the ones from the
+//! configured sources and repositories into a local copy.
+//!
+//! A database is only used when it is signed, like a manifest, with the
+//! minisign key configured for its location. Entries from a repository's
+//! database only vouch for downloads of that repository's manifests;
+//! entries from the dedicated sources apply to every manifest.
+//!
+//! ```toml
+//! [[artifact]]
+//! url = "https://github.com/example/app/releases/download/*/app-x86_64.AppImage"
+//! version = "1.4.2"
+//! sha256 = "9f86d08..."
+//!
+//! [signature]
+//! public_key = "RWQ..."
+//! signature = "RWQ..."
+//! ```
+
+use super::index::repo_file_origin;
+use super::signature::{signed_text, verify_text};
+use super::{ManifestError, ManifestSignature};
+use crate::storage::paths;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// File name of a repository's checksum database
+pub const CHECKSUMS_FILE: &str = "checksums.toml";
+
+/// Known checksum of one artifact
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownChecksum {
+    /// Artifact URL; `*` matches any run of characters
+    pub url: String,
+    /// Version the checksum applies to (any version when unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub sha256: String,
+    /// Repository whose manifests the entry applies to; entries from the
+    /// dedicated sources apply to all
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+}
+
+/// A checksum database
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChecksumDb {
+    #[serde(default, rename = "artifact")]
+    pub artifacts: Vec<KnownChecksum>,
+}
+
+/// Result of checking a downloaded artifact against the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChecksumCheck {
+    Verified,
+    Mismatch { expected: String },
+    Unknown,
+}
+
+impl ChecksumDb {
+    /// Entries for an artifact of a manifest from `repository` (None for
+    /// manifests from elsewhere), exact version matches first
+    fn candidates<'a>(
+        &'a self,
+        url: &str,
+        version: &str,
+        repository: Option<&str>,
+    ) -> Vec<&'a KnownChecksum> {
+        let version = version.trim_start_matches('v');
+        let mut found: Vec<&KnownChecksum> = self
+            .artifacts
+            .iter()
+            .filter(|a| a.repository.is_none() || a.repository.as_deref() == repository)
+            .filter(|a| wildcard_match(&a.url, url))
+            .filter(|a| {
+                a.version
+                    .as_deref()
+                    .is_none_or(|v| v.trim_start_matches('v') == version)
+            })
+            .collect();
+        found.sort_by_key(|a| a.version.is_none());
+        found
+    }
+
+    /// Check an artifact's hash against the database
+    ///
+    /// Any matching entry with the same hash verifies it; entries exist but
+    /// none match is a mismatch.
+    pub fn check(
+        &self,
+        url: &str,
+        version: &str,
+        repository: Option<&str>,
+        sha256: &str,
+    ) -> ChecksumCheck {
+        let candidates = self.candidates(url, version, repository);
+        if candidates
+            .iter()
+            .any(|a| a.sha256.eq_ignore_ascii_case(sha256))
+        {
+            return ChecksumCheck::Verified;
+        }
+        match candidates.first() {
+            Some(expected) => ChecksumCheck::Mismatch {
+                expected: expected.sha256.clone(),
+            },
+            None => ChecksumCheck::Unknown,
+        }
+    }
+
+    /// The checksum the database expects of an artifact, if it knows one
+    pub fn expected(&self, url: &str, version: &str, repository: Option<&str>) -> Option<&str> {
+        self.candidates(url, version, repository)
+            .first()
+            .map(|a| a.sha256.as_str())
+    }
+
+    /// Add another database's entries, scoped to `repository`, skipping
+    /// duplicates
+    fn merge(&mut self, other: ChecksumDb, repository: Option<&str>) {
+        for mut artifact in other.artifacts {
+            artifact.repository = repository.map(str::to_string);
+            if !self.artifacts.contains(&artifact) {
+                self.artifacts.push(artifact);
+            }
+        }
+    }
+}
+
+/// Match `*` wildcards against the whole of `text`
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == text;
+    };
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = rest.split('*').collect();
+    let (last, middle) = parts.split_last().unwrap_or((&"", &[]));
+    for part in middle {
+        match remaining.find(part) {
+            Some(at) => remaining = &remaining[at + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.ends_with(last)
+}
+
+/// The local copy of the database (empty until refreshed)
+pub fn load_checksum_db() -> ChecksumDb {
+    fs::read_to_string(paths::checksum_db_path())
+        .ok()
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Parse a published database, checking its signature against the key
+/// configured for its location
+fn verify_database(content: &str, key: Option<&String>) -> Result<ChecksumDb, ManifestError> {
+    let Some(key) = key else {
+        return Err(ManifestError::SignatureError(
+            "no key is configured for its checksum database".into(),
+        ));
+    };
+    let Some(signed) = signed_text(content) else {
+        return Err(ManifestError::SignatureError(
+            "the checksum database isn't signed (it needs a [signature] table at the end)".into(),
+        ));
+    };
+    let table: toml::Table = toml::from_str(content)?;
+    let signature: ManifestSignature = table
+        .get("signature")
+        .cloned()
+        .ok_or_else(|| ManifestError::SignatureError("missing [signature] table".into()))?
+        .try_into()?;
+    verify_text(signed, key, &signature.signature, "checksum database")?;
+    Ok(toml::from_str(signed)?)
+}
+
+/// Fetch the databases published by `sources` and `repositories`, signed
+/// with their configured `keys` (public keys by location), and replace the
+/// local copy
+///
+/// Most repositories don't publish a database, so only failures of the
+/// dedicated sources and of repositories with a key are reported; they are
+/// returned with the error. The local copy is kept when no database could
+/// be fetched.
+pub fn refresh_checksum_db(
+    sources: &[String],
+    repositories: &[String],
+    keys: &BTreeMap<String, String>,
+) -> Result<(ChecksumDb, Vec<(String, String)>), ManifestError> {
+    let mut db = ChecksumDb::default();
+    let mut failed = Vec::new();
+    let mut fetched = 0;
+
+    let locations = sources
+        .iter()
+        .map(|s| (s, None))
+        .chain(repositories.iter().map(|r| (r, Some(r.as_str()))));
+    for (location, repository) in locations {
+        let key = keys.get(location);
+        if repository.is_some() && key.is_none() {
+            continue;
+        }
+        let parsed = repo_file_origin(location, CHECKSUMS_FILE)
+            .load()
+            .and_then(|content| verify_database(&content, key));
+        match parsed {
+            Ok(other) => {
+                db.merge(other, repository);
+                fetched += 1;
+            }
+            Err(e) => failed.push((location.clone(), e.to_string())),
+        }
+    }
+
+    if fetched == 0 {
+        return Ok((load_checksum_db(), failed));
+    }
+
+    let path = paths::checksum_db_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content =
+        toml::to_string_pretty(&db).map_err(|e| ManifestError::SerializeError(e.to_string()))?;
+    let tmp = path.with_extension("toml.tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, &path)?;
+    Ok((db, failed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const URL: &str = "https://example.com/releases/download/v1.2/app.AppImage";
+    const GOOD: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    const OTHER: &str = "60303ae22b998861bce3b28f33eec1be758a213c86c93c076dbe9f558c11c752";
+
+    const DATABASE: &str = r#"[[artifact]]
+url = "https://example.com/releases/download/*/app.AppImage"
+version = "1.2"
+sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+"#;
+
+    fn entry(repository: Option<&str>) -> KnownChecksum {
+        KnownChecksum {
+            url: "https://example.com/releases/download/*/app.AppImage".into(),
+            version: Some("1.2".into()),
+            sha256: GOOD.into(),
+            repository: repository.map(str::to_string),
+        }
+    }
+
+    /// A minisign public key line for a test key
+    fn public_key(seed: u8) -> String {
+        let mut bytes = b"Ed".to_vec();
+        bytes.extend([seed; 8]);
+        bytes.extend(
+            SigningKey::from_bytes(&[seed; 32])
+                .verifying_key()
+                .as_bytes(),
+        );
+        STANDARD.encode(bytes)
+    }
+
+    /// `content` with a `[signature]` table signed by a test key
+    fn signed(content: &str, seed: u8) -> String {
+        let mut bytes = b"Ed".to_vec();
+        bytes.extend([seed; 8]);
+        bytes.extend(
+            SigningKey::from_bytes(&[seed; 32])
+                .sign(content.as_bytes())
+                .to_bytes(),
+        );
+        format!(
+            "{}[signature]\npublic_key = \"{}\"\nsignature = \"{}\"\n",
+            content,
+            public_key(seed),
+            STANDARD.encode(bytes)
+        )
+    }
+
+    #[test]
+    fn wildcards_match_the_whole_url() {
+        assert!(wildcard_match(
+            "https://example.com/*/app",
+            "https://example.com/v1/app"
+        ));
+        assert!(wildcard_match("*", "anything"));
+        assert!(!wildcard_match(
+            "https://example.com/*/app",
+            "https://example.com/v1/app.sig"
+        ));
+        assert!(!wildcard_match(
+            "https://example.com/app",
+            "https://evil.example/app"
+        ));
+    }
+
+    #[test]
+    fn matching_entry_verifies_and_other_hash_mismatches() {
+        let db = ChecksumDb {
+            artifacts: vec![entry(None)],
+        };
+        assert_eq!(db.check(URL, "v1.2", None, GOOD), ChecksumCheck::Verified);
+        assert_eq!(
+            db.check(URL, "1.2", None, OTHER),
+            ChecksumCheck::Mismatch {
+                expected: GOOD.into()
+            }
+        );
+        assert_eq!(db.check(URL, "1.3", None, GOOD), ChecksumCheck::Unknown);
+    }
+
+    #[test]
+    fn repository_entries_only_apply_to_its_manifests() {
+        let db = ChecksumDb {
+            artifacts: vec![entry(Some("gh:a/repo"))],
+        };
+        assert_eq!(
+            db.check(URL, "1.2", Some("gh:a/repo"), GOOD),
+            ChecksumCheck::Verified
+        );
+        assert_eq!(
+            db.check(URL, "1.2", Some("gh:b/repo"), OTHER),
+            ChecksumCheck::Unknown
+        );
+        assert_eq!(db.check(URL, "1.2", None, OTHER), ChecksumCheck::Unknown);
+        assert_eq!(db.expected(URL, "1.2", Some("gh:b/repo")), None);
+    }
+
+    #[test]
+    fn merge_scopes_entries_to_the_publishing_repository() {
+        let mut published: ChecksumDb = toml::from_str(DATABASE).unwrap();
+        // A database can't widen its own scope
+        published.artifacts[0].repository = None;
+        let mut db = ChecksumDb::default();
+        db.merge(published, Some("gh:a/repo"));
+        assert_eq!(db.artifacts, vec![entry(Some("gh:a/repo"))]);
+    }
+
+    #[test]
+    fn database_signed_with_the_configured_key_is_accepted() {
+        let db = verify_database(&signed(DATABASE, 3), Some(&public_key(3))).unwrap();
+        assert_eq!(db.artifacts, vec![entry(None)]);
+    }
+
+    #[test]
+    fn unsigned_or_unkeyed_databases_are_rejected() {
+        assert!(verify_database(DATABASE, Some(&public_key(3))).is_err());
+        assert!(verify_database(&signed(DATABASE, 3), None).is_err());
+    }
+
+    #[test]
+    fn database_signed_with_another_key_is_rejected() {
+        assert!(verify_database(&signed(DATABASE, 4), Some(&public_key(3))).is_err());
+    }
+
+    #[test]
+    fn changed_database_is_rejected() {
+        let content = signed(DATABASE, 3).replace(GOOD, OTHER);
+        assert!(verify_database(&content, Some(&public_key(3))).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! Manifest inheritance via `extends`
+//!
+//! A manifest may name a base manifest (local path or URL) in a top-level
+//! `extends` field. The base is loaded first and the manifest's own fields
+//! are merged over it: tables merge key by key, everything else (including
+//! arrays) is replaced. Bases can extend further bases, up to
+//! [`MAX_EXTENDS_DEPTH`] levels; cycles are rejected.
+
+use super::parser::fetch_manifest_text;
+use super::{ManifestError, SCHEMA_VERSION};
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Maximum length of an `extends` chain
+pub const MAX_EXTENDS_DEPTH: usize = 8;
+
+/// Where a manifest was loaded from, used to resolve relative `extends`
+#[derive(Debug, Clone)]
+pub enum ManifestOrigin {
+    File(PathBuf),
+    Url(String),
+    /// Inline content; relative paths resolve against the working directory
+    Inline,
+}
+
+impl ManifestOrigin {
+    /// Resolve an `extends` reference relative to this origin
+    pub(crate) fn resolve(&self, reference: &str) -> ManifestOrigin {
+        if reference.starts_with("http://") || reference.starts_with("https://") {
+            return ManifestOrigin::Url(reference.to_string());
+        }
+
+        match self {
+            ManifestOrigin::Url(url) => {
+                // Scheme and host end at the first '/' after "://"
+                let host_end = url
+                    .find("://")
+                    .and_then(|i| url[i + 3..].find('/').map(|j| i + 3 + j))
+                    .unwrap_or(url.len());
+                if reference.starts_with('/') {
+                    ManifestOrigin::Url(format!("{}{}", &url[..host_end], reference))
+                } else {
+                    let dir_end = url
+                        .rfind('/')
+                        .filter(|&i| i >= host_end)
+                        .unwrap_or(host_end);
+                    ManifestOrigin::Url(format!("{}/{}", &url[..dir_end], reference))
+                }
+            }
+            ManifestOrigin::File(path) => {
+                let dir = path.parent().unwrap_or(Path::new("."));
+                ManifestOrigin::File(dir.join(reference))
+            }
+            ManifestOrigin::Inline => ManifestOrigin::File(PathBuf::from(reference)),
+        }
+    }
+
+    /// Identity used for cycle detection
+    fn key(&self) -> String {
+        match self {
+            ManifestOrigin::File(path) => path
+                .canonicalize()
+                .unwrap_or_else(|_| path.clone())
+                .display()
+                .to_string(),
+            ManifestOrigin::Url(url) => url.clone(),
+            ManifestOrigin::Inline => String::new(),
+        }
+    }
+
+    /// Read the content at this origin
+    pub(crate) fn load(&self) -> Result<String, ManifestError> {
+        match self {
+            ManifestOrigin::File(path) => std::fs::read_to_string(path)
+                .map_err(|e| ManifestError::ExtendsError(format!("{}: {}", path.display(), e))),
+            ManifestOrigin::Url(url) => fetch_manifest_text(url)
+                .map_err(|e| ManifestError::ExtendsError(format!("{}: {}", url, e))),
+            ManifestOrigin::Inline => Err(ManifestError::ExtendsError(
+                "inline manifest has no content to load".into(),
+            )),
+        }
+    }
+}
+
+/// Parse manifest TOML and apply its `extends` chain
+pub fn resolve_manifest_table(
+    content: &str,
+    origin: &ManifestOrigin,
+) -> Result<Table, ManifestError> {
+    resolve_manifest_chain(content, origin).map(|(table, _)| table)
+}
+
+/// Parse manifest TOML and apply its `extends` chain, also returning the
+/// text of each base in the chain, nearest first
+pub(crate) fn resolve_manifest_chain(
+    content: &str,
+    origin: &ManifestOrigin,
+) -> Result<(Table, Vec<String>), ManifestError> {
+    let mut chain = vec![origin.key()];
+    let mut bases = Vec::new();
+    let table = resolve(content, origin, &mut chain, &mut bases)?;
+    Ok((table, bases))
+}
+
+fn resolve(
+    content: &str,
+    origin: &ManifestOrigin,
+    chain: &mut Vec<String>,
+    bases: &mut Vec<String>,
+) -> Result<Table, ManifestError> {
+    let mut table: Table = toml::from_str(content)?;
+    check_schema_version(&table)?;
+
+    let Some(extends) = table.remove("extends") else {
+        return Ok(table);
+    };
+    let reference = extends.as_str().ok_or_else(|| {
+        ManifestError::ExtendsError("extends must be a path or URL string".into())
+    })?;
+
+    if chain.len() > MAX_EXTENDS_DEPTH {
+        return Err(ManifestError::ExtendsError(format!(
+            "extends chain is deeper than {} levels",
+            MAX_EXTENDS_DEPTH
+        )));
+    }
+
+    let base_origin = origin.resolve(reference);
+    let key = base_origin.key();
+    if chain.contains(&key) {
+        return Err(ManifestError::ExtendsError(format!(
+            "extends cycle through {}",
+            reference
+        )));
+    }
+
+    chain.push(key);
+    let base_content = base_origin.load()?;
+    bases.push(base_content.clone());
+    let mut base = resolve(&base_content, &base_origin, chain, bases)?;
+    chain.pop();
+
+    merge_tables(&mut base, table);
+    Ok(base)
+}
+
+/// Merge `overlay` into `base`, recursing into tables
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Reject a manifest written for a newer voidbox, whose fields this one
+/// would misread
+pub(crate) fn check_schema_version(table: &Table) -> Result<(), ManifestError> {
+    match table.get("schema_version").and_then(Value::as_integer) {
+        Some(version) if version > i64::from(SCHEMA_VERSION) => {
+            Err(ManifestError::UnsupportedSchema {
+                version,
+                supported: SCHEMA_VERSION,
+            })
+        }
+        _ => Ok(()),
+    }
+}
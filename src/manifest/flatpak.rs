@@ -0,0 +1,384 @@
+//! Flatpak manifest export
+//!
+//! Turns a voidbox manifest into a flatpak-builder manifest: the download
+//! becomes the module's source, the payload is copied to `/app`, and the
+//! app's permissions become `finish-args`. The result is a starting point
+//! for packaging, not a finished manifest - checksums the manifest doesn't
+//! give are left as `FIXME` and anything without a Flatpak equivalent is reported as a note.
+
+use super::{AppManifest, ClipboardAccess, PermissionConfig, SourceConfig};
+use serde_json::{Value, json};
+
+/// Runtime the exported manifest builds against
+const FLATPAK_RUNTIME: &str = "org.freedesktop.Platform";
+const FLATPAK_SDK: &str = "org.freedesktop.Sdk";
+const FLATPAK_RUNTIME_VERSION: &str = "24.08";
+
+/// Placeholder for values the packager has to fill in
+const FIXME: &str = "FIXME";
+
+/// An exported Flatpak manifest
+#[derive(Debug)]
+pub struct FlatpakExport {
+    /// flatpak-builder manifest (JSON)
+    pub manifest: Value,
+    /// What couldn't be translated and needs the packager's attention
+    pub notes: Vec<String>,
+}
+
+/// Flatpak app ID: the manifest's, or one derived from its GitHub source
+fn flatpak_app_id(manifest: &AppManifest, notes: &mut Vec<String>) -> String {
+    if let Some(id) = &manifest.app.id {
+        return id.clone();
+    }
+    let clean = |s: &str| s.replace(|c: char| !c.is_ascii_alphanumeric() && c != '_', "_");
+    match &manifest.source {
+        SourceConfig::Github { owner, repo, .. } => {
+            let id = format!("io.github.{}.{}", clean(owner), clean(repo));
+            notes.push(format!(
+                "No app ID in the manifest; using {} (set [app] id)",
+                id
+            ));
+            id
+        }
+        _ => {
+            let id = format!("org.example.{}", clean(&manifest.app.name));
+            notes.push(format!(
+                "No app ID in the manifest; {} is a placeholder (set [app] id)",
+                id
+            ));
+            id
+        }
+    }
+}
+
+/// finish-args granting what the app's permissions allow
+fn finish_args(
+    manifest: &AppManifest,
+    permissions: &PermissionConfig,
+    notes: &mut Vec<String>,
+) -> Vec<String> {
+    let mut args: Vec<String> = ["--share=ipc", "--socket=wayland", "--socket=fallback-x11"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+    if permissions.network {
+        args.push("--share=network".into());
+    }
+    if permissions.audio || permissions.microphone {
+        args.push("--socket=pulseaudio".into());
+    }
+    if permissions.gpu {
+        args.push("--device=dri".into());
+    }
+    if permissions.camera {
+        // Flatpak has no narrower device permission for cameras
+        args.push("--device=all".into());
+    }
+    if permissions.home {
+        args.push("--filesystem=home".into());
+    } else if permissions.downloads {
+        args.push("--filesystem=xdg-download".into());
+    }
+    if permissions.removable_media {
+        args.push("--filesystem=/media".into());
+        args.push("--filesystem=/run/media".into());
+    }
+    if permissions.fonts {
+        args.push("--filesystem=xdg-data/fonts:ro".into());
+    }
+    if permissions.themes {
+        args.push("--filesystem=xdg-data/themes:ro".into());
+        args.push("--filesystem=xdg-data/icons:ro".into());
+    }
+
+    if manifest.dbus.filter {
+        // Portals are always reachable inside a Flatpak sandbox
+        for name in manifest
+            .dbus_talk_names()
+            .into_iter()
+            .filter(|n| !n.starts_with("org.freedesktop.portal."))
+        {
+            args.push(format!("--talk-name={}", name));
+        }
+        for name in manifest.dbus_own_names() {
+            args.push(format!("--own-name={}", name));
+        }
+    } else {
+        args.push("--socket=session-bus".into());
+    }
+
+    for (key, value) in &manifest.binary.env {
+        args.push(format!("--env={}={}", key, value));
+    }
+
+    if permissions.dev_mode {
+        notes.push("dev_mode has no Flatpak equivalent; it was left out".into());
+    }
+    if permissions.native_mode {
+        notes.push("native_mode runs the app unsandboxed; the Flatpak stays sandboxed".into());
+    }
+    if permissions.clipboard != ClipboardAccess::Write {
+        notes.push(format!(
+            "Flatpak apps always have full clipboard access (clipboard = \"{}\")",
+            permissions.clipboard.name()
+        ));
+    }
+    args
+}
+
+/// The module's main source: the download voidbox installs from
+fn payload_source(manifest: &AppManifest, notes: &mut Vec<String>) -> Value {
+    if !manifest.sources.is_empty() {
+        notes.push(format!(
+            "Only [source] was exported; add the sources for {} with only-arches",
+            manifest
+                .sources
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    match &manifest.source {
+        SourceConfig::Direct {
+            url,
+            archive_type,
+            sha256,
+            ..
+        } => {
+            let sha256 = match sha256 {
+                Some(sha256) => json!(sha256),
+                None => {
+                    notes.push("Fill in the sha256 of the download".into());
+                    json!(FIXME)
+                }
+            };
+            let is_archive = archive_type
+                .as_deref()
+                .map(|t| t != "binary")
+                .unwrap_or_else(|| {
+                    [".tar.gz", ".tgz", ".tar.xz", ".tar.bz2", ".zip"]
+                        .iter()
+                        .any(|ext| url.ends_with(ext))
+                });
+            if is_archive {
+                json!({
+                    "type": "archive",
+                    "url": url,
+                    "sha256": sha256,
+                    "strip-components": 0,
+                })
+            } else {
+                json!({
+                    "type": "file",
+                    "url": url,
+                    "sha256": sha256,
+                })
+            }
+        }
+        SourceConfig::Github {
+            owner,
+            repo,
+            asset_pattern,
+            ..
+        } => {
+            notes.push(format!(
+                "Fill in the url and sha256 of a release asset of {}/{}; \
+                 x-checker-data lets flatpak-external-data-checker keep them current",
+                owner, repo
+            ));
+            let mut checker = json!({
+                "type": "json",
+                "url": format!("https://api.github.com/repos/{}/{}/releases/latest", owner, repo),
+                "version-query": ".tag_name",
+            });
+            if let Some(pattern) = asset_pattern {
+                checker["url-query"] = json!(format!(
+                    ".assets[] | select(.name | test(\"{}\")) | .browser_download_url",
+                    pattern.replace('\\', "\\\\").replace('"', "\\\"")
+                ));
+            }
+            json!({
+                "type": "archive",
+                "url": format!("https://github.com/{}/{}/releases/download/{}", owner, repo, FIXME),
+                "sha256": FIXME,
+                "strip-components": 0,
+                "x-checker-data": checker,
+            })
+        }
+        SourceConfig::Local { path } => {
+            notes.push("The source is a local path; publish it somewhere first".into());
+            if path.is_dir() {
+                json!({ "type": "dir", "path": path })
+            } else {
+                json!({ "type": "file", "path": path })
+            }
+        }
+    }
+}
+
+/// Desktop entry for the Flatpak, named after the app ID
+fn desktop_entry(manifest: &AppManifest, app_id: &str) -> String {
+    let mut entry = format!(
+        "[Desktop Entry]\nType=Application\nName={}\nExec={}\nIcon={}\n",
+        manifest.app.display_name, manifest.binary.name, app_id
+    );
+    if !manifest.app.description.is_empty() {
+        entry.push_str(&format!("Comment={}\n", manifest.app.description));
+    }
+    let desktop = &manifest.desktop;
+    if let Some(generic_name) = &desktop.generic_name {
+        entry.push_str(&format!("GenericName={}\n", generic_name));
+    }
+    if !desktop.categories.is_empty() {
+        entry.push_str(&format!("Categories={};\n", desktop.categories.join(";")));
+    }
+    if !desktop.mime_types.is_empty() {
+        entry.push_str(&format!("MimeType={};\n", desktop.mime_types.join(";")));
+    }
+    if !desktop.keywords.is_empty() {
+        entry.push_str(&format!("Keywords={};\n", desktop.keywords.join(";")));
+    }
+    entry.push_str(&format!(
+        "StartupNotify={}\n",
+        desktop.startup_notify.unwrap_or(true)
+    ));
+    if let Some(wm_class) = &desktop.wm_class {
+        entry.push_str(&format!("StartupWMClass={}\n", wm_class));
+    }
+    entry
+}
+
+/// Shell-quote a word for the build commands and wrapper script
+fn quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// Build a best-effort Flatpak manifest from a voidbox manifest
+pub fn flatpak_manifest(manifest: &AppManifest, permissions: &PermissionConfig) -> FlatpakExport {
+    let mut notes = Vec::new();
+    let app_id = flatpak_app_id(manifest, &mut notes);
+    let install_dir = manifest
+        .binary
+        .install_dir
+        .as_deref()
+        .unwrap_or(&manifest.app.name);
+    let binary_path = manifest
+        .binary
+        .path
+        .as_deref()
+        .unwrap_or(&manifest.binary.name);
+
+    let mut wrapper = format!(
+        "#!/bin/sh\nexec /app/{}/{}",
+        install_dir,
+        binary_path.trim_start_matches('/')
+    );
+    for arg in &manifest.binary.args {
+        wrapper.push(' ');
+        wrapper.push_str(&quote(arg));
+    }
+    wrapper.push_str(" \"$@\"\n");
+
+    let icon_name = manifest
+        .desktop
+        .icon
+        .clone()
+        .unwrap_or_else(|| format!("{}.png", manifest.app.name));
+    let icon_dir = if icon_name.ends_with(".svg") {
+        "scalable"
+    } else {
+        "512x512"
+    };
+    let icon_ext = if icon_name.ends_with(".svg") {
+        "svg"
+    } else {
+        "png"
+    };
+    notes.push(format!(
+        "The icon is looked up as {} and installed as {}; check its size",
+        icon_name, icon_dir
+    ));
+
+    let build_commands = vec![
+        format!("mkdir -p /app/{}", quote(install_dir)),
+        format!(
+            "cp -a $(ls -A | grep -v -e '^{id}.desktop$' -e '^voidbox-wrapper.sh$') /app/{dir}/",
+            id = app_id,
+            dir = quote(install_dir)
+        ),
+        format!(
+            "install -Dm755 voidbox-wrapper.sh /app/bin/{}",
+            quote(&manifest.binary.name)
+        ),
+        format!(
+            "install -Dm644 {id}.desktop /app/share/applications/{id}.desktop",
+            id = app_id
+        ),
+        format!(
+            "icon=$(find /app/{dir} -name {icon} | head -n 1); \
+             if [ -n \"$icon\" ]; then install -Dm644 \"$icon\" \
+             /app/share/icons/hicolor/{size}/apps/{id}.{ext}; fi",
+            dir = quote(install_dir),
+            icon = quote(&icon_name),
+            size = icon_dir,
+            id = app_id,
+            ext = icon_ext
+        ),
+    ];
+
+    let sources = vec![
+        payload_source(manifest, &mut notes),
+        json!({
+            "type": "inline",
+            "dest-filename": format!("{}.desktop", app_id),
+            "contents": desktop_entry(manifest, &app_id),
+        }),
+        json!({
+            "type": "inline",
+            "dest-filename": "voidbox-wrapper.sh",
+            "contents": wrapper,
+        }),
+    ];
+
+    if !manifest.dependencies.packages.is_empty() {
+        notes.push(format!(
+            "Debian packages are not available in the Flatpak runtime; \
+             add modules for what's missing: {}",
+            manifest.dependencies.packages.join(", ")
+        ));
+    }
+    if !manifest.dependencies.apps.is_empty() {
+        notes.push(format!(
+            "The app needs other voidbox apps, which the Flatpak doesn't include: {}",
+            manifest.dependencies.apps.join(", ")
+        ));
+    }
+    if !manifest.hooks.is_empty() {
+        notes.push(
+            "Install and remove hooks aren't exported; add post-install steps to the module's build-commands by hand".to_string(),
+        );
+    }
+
+    let flatpak = json!({
+        "id": app_id,
+        "runtime": FLATPAK_RUNTIME,
+        "runtime-version": FLATPAK_RUNTIME_VERSION,
+        "sdk": FLATPAK_SDK,
+        "command": manifest.binary.name,
+        "finish-args": finish_args(manifest, permissions, &mut notes),
+        "modules": [{
+            "name": manifest.app.name,
+            "buildsystem": "simple",
+            "build-commands": build_commands,
+            "sources": sources,
+        }],
+    });
+
+    FlatpakExport {
+        manifest: flatpak,
+        notes,
+    }
+}
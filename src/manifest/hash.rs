@@ -0,0 +1,132 @@
+//! Canonical manifest serialization and content hashing
+//!
+//! The canonical form is the parsed manifest (defaults filled in) written as
+//! compact JSON with object keys sorted and no insignificant whitespace, so
+//! formatting, comments, key order and spelled-out defaults don't change the
+//! hash. The signature is left out, as signing a manifest doesn't change its
+//! content.
+
+use super::{AppManifest, ManifestError};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Prefix used when referencing a manifest by hash
+pub const MANIFEST_HASH_PREFIX: &str = "sha256:";
+
+/// Serialize a manifest to its canonical form
+pub fn canonical_manifest(manifest: &AppManifest) -> Result<String, ManifestError> {
+    let mut value =
+        serde_json::to_value(manifest).map_err(|e| ManifestError::HashError(e.to_string()))?;
+    if let Value::Object(map) = &mut value {
+        map.remove("signature");
+    }
+    let mut out = String::new();
+    write_canonical(&value, &mut out);
+    Ok(out)
+}
+
+/// Compute the content hash of a manifest ("sha256:<hex>")
+pub fn manifest_hash(manifest: &AppManifest) -> Result<String, ManifestError> {
+    let canonical = canonical_manifest(manifest)?;
+    let digest = Sha256::digest(canonical.as_bytes());
+    Ok(format!("{}{}", MANIFEST_HASH_PREFIX, hex::encode(digest)))
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(&map[key], out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A manifest using only fields that existed when hashing was added
+    const MANIFEST: &str = r#"
+[app]
+name = "demo"
+display_name = "Demo"
+description = "A demo app"
+
+[source]
+type = "direct"
+url = "https://example.com/demo.tar.gz"
+
+[runtime]
+base = "ubuntu:24.04"
+
+[binary]
+name = "demo"
+args = ["--flag"]
+
+[desktop]
+categories = ["Utility"]
+
+[permissions]
+network = true
+home = false
+"#;
+
+    /// Its hash as the first hashing voidbox computed it; fields added
+    /// since must stay out of the canonical form while unset
+    const MANIFEST_HASH: &str =
+        "sha256:8e9e2804644cce7db72cdd68f6b74f0b152cd23d4605d40ca0f0d1f7c647e1df";
+
+    fn hash_of(content: &str) -> String {
+        manifest_hash(&toml::from_str(content).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn hash_is_stable_across_field_additions() {
+        assert_eq!(hash_of(MANIFEST), MANIFEST_HASH);
+    }
+
+    #[test]
+    fn hash_ignores_formatting_and_spelled_out_defaults() {
+        let reformatted = MANIFEST
+            .replace("[binary]", "# the binary\n[binary]")
+            .replace(
+                "network = true",
+                "network    =   true\nclipboard = \"write\"",
+            );
+        assert_eq!(hash_of(&reformatted), MANIFEST_HASH);
+    }
+
+    #[test]
+    fn hash_changes_with_content() {
+        assert_ne!(
+            hash_of(&MANIFEST.replace("--flag", "--other")),
+            MANIFEST_HASH
+        );
+        let with_id = MANIFEST.replace(
+            "name = \"demo\"",
+            "name = \"demo\"\nid = \"org.example.Demo\"",
+        );
+        assert_ne!(hash_of(&with_id), MANIFEST_HASH);
+    }
+}
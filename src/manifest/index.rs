@@ -0,0 +1,359 @@
+//! Repository indexes
+//!
+//! A repository is a directory (or URL prefix) with an `index.toml` listing
+//! its member manifests. The index may define shared `[vars]` that members
+//! reference as `${name}`; they are substituted into the member's TOML when
+//! it is resolved, so one `version` bump updates every artifact URL that
+//! uses it. Write `$${` for a literal `${`.
+//!
+//! A repository hosted on GitHub can be named with the tap shorthand
+//! `gh:user/repo`, which reads the `index.toml` at the root of the repo's
+//! default branch.
+//!
+//! An app's entry may also list manifests of earlier versions under
+//! `versions`, which `voidbox install <name>@<version>` installs.
+//!
+//! Remote indexes are cached under `cache/indexes` and fetched again once
+//! their repository's refresh interval has passed; a stale copy is used
+//! when the refetch fails.
+//!
+//! ```toml
+//! [vars]
+//! base_url = "https://downloads.example.com"
+//! version = "1.4.2"
+//!
+//! [apps.foo]
+//! manifest = "foo.toml"
+//! ```
+
+use super::ManifestError;
+use super::extends::ManifestOrigin;
+use super::parser::parse_manifest_from;
+use super::schema::AppManifest;
+use super::validate::validate_manifest;
+use crate::storage::paths;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// File name of a repository index
+pub const INDEX_FILE: &str = "index.toml";
+
+/// Prefix of the GitHub tap shorthand (`gh:user/repo`)
+pub const TAP_PREFIX: &str = "gh:";
+
+/// A repository index
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoIndex {
+    /// Variables interpolated into member manifests
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    /// Member apps keyed by app name
+    #[serde(default)]
+    pub apps: BTreeMap<String, IndexEntry>,
+}
+
+/// An app listed in a repository index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Manifest path relative to the index
+    pub manifest: String,
+    #[serde(default)]
+    pub display_name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Manifests of earlier versions, relative to the index, by version
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub versions: BTreeMap<String, String>,
+}
+
+/// Substitute `${name}` references with values from `vars`
+///
+/// Unknown or unterminated references are errors rather than being left in
+/// place, so a typo can't end up in a download URL.
+pub fn interpolate(
+    template: &str,
+    vars: &BTreeMap<String, String>,
+) -> Result<String, ManifestError> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start..];
+
+        if let Some(escaped) = after.strip_prefix("$${") {
+            output.push_str("${");
+            rest = escaped;
+        } else if let Some(reference) = after.strip_prefix("${") {
+            let end = reference.find('}').ok_or_else(|| {
+                ManifestError::ValidationError("unterminated ${ in manifest".into())
+            })?;
+            let name = &reference[..end];
+            let value = vars.get(name).ok_or_else(|| {
+                ManifestError::ValidationError(format!("undefined variable ${{{}}}", name))
+            })?;
+            output.push_str(value);
+            rest = &reference[end + 1..];
+        } else {
+            output.push('$');
+            rest = &after[1..];
+        }
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Raw content URL of a `gh:user/repo` tap's root
+pub fn tap_url(location: &str) -> Option<String> {
+    let (user, repo) = location.strip_prefix(TAP_PREFIX)?.split_once('/')?;
+    if user.is_empty() || repo.is_empty() || repo.contains('/') {
+        return None;
+    }
+    Some(format!(
+        "https://raw.githubusercontent.com/{}/{}/HEAD",
+        user, repo
+    ))
+}
+
+/// Split `gh:user/repo/app` into its tap (`gh:user/repo`) and app name
+pub fn split_tap_app(source: &str) -> Option<(&str, &str)> {
+    let (tap, app) = source.rsplit_once('/')?;
+    (tap_url(tap).is_some() && !app.is_empty()).then_some((tap, app))
+}
+
+/// Where the index lives: a directory, an index file, an index URL or a tap
+fn index_origin(location: &str) -> ManifestOrigin {
+    if let Some(url) = tap_url(location) {
+        ManifestOrigin::Url(format!("{}/{}", url, INDEX_FILE))
+    } else if location.starts_with("http://") || location.starts_with("https://") {
+        if location.ends_with(".toml") {
+            ManifestOrigin::Url(location.to_string())
+        } else {
+            ManifestOrigin::Url(format!("{}/{}", location.trim_end_matches('/'), INDEX_FILE))
+        }
+    } else {
+        let path = Path::new(location);
+        if path.is_dir() {
+            ManifestOrigin::File(path.join(INDEX_FILE))
+        } else {
+            ManifestOrigin::File(path.to_path_buf())
+        }
+    }
+}
+
+/// Where a file published in a repository lives, next to its index
+pub(crate) fn repo_file_origin(location: &str, file: &str) -> ManifestOrigin {
+    index_origin(location).resolve(file)
+}
+
+/// Load a repository index
+pub fn load_index(location: &str) -> Result<RepoIndex, ManifestError> {
+    let content = index_origin(location).load()?;
+    Ok(toml::from_str(&content)?)
+}
+
+/// Cached copy of a remote index
+fn index_cache_path(url: &str) -> PathBuf {
+    let digest = Sha256::digest(url.as_bytes());
+    paths::index_cache_dir().join(format!("{}.toml", hex::encode(digest)))
+}
+
+/// Whether an index needs no fetching: local, or a remote index fetched
+/// within `max_age`
+pub fn index_is_fresh(location: &str, max_age: Duration) -> bool {
+    let ManifestOrigin::Url(url) = index_origin(location) else {
+        return true;
+    };
+    fs::metadata(index_cache_path(&url))
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < max_age)
+}
+
+/// Load a repository index, reusing a remote index fetched within `max_age`
+pub fn load_index_cached(location: &str, max_age: Duration) -> Result<RepoIndex, ManifestError> {
+    let ManifestOrigin::Url(url) = index_origin(location) else {
+        return load_index(location);
+    };
+
+    let path = index_cache_path(&url);
+    if index_is_fresh(location, max_age)
+        && let Ok(content) = fs::read_to_string(&path)
+        && let Ok(index) = toml::from_str(&content)
+    {
+        return Ok(index);
+    }
+
+    match refresh_index(location) {
+        Ok(index) => Ok(index),
+        // A stale copy beats nothing
+        Err(e) => fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .ok_or(e),
+    }
+}
+
+/// Fetch a repository index, updating the cached copy of a remote index
+pub fn refresh_index(location: &str) -> Result<RepoIndex, ManifestError> {
+    let origin = index_origin(location);
+    let content = origin.load()?;
+    let index: RepoIndex = toml::from_str(&content)?;
+
+    if let ManifestOrigin::Url(url) = &origin {
+        let path = index_cache_path(url);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).ok();
+        }
+        // Write then rename so a reader never sees a partial file
+        let partial = path.with_extension("part");
+        if fs::write(&partial, &content).is_ok() {
+            fs::rename(&partial, &path).ok();
+        }
+    }
+    Ok(index)
+}
+
+/// Resolve a member manifest of a repository, interpolating index variables
+pub fn resolve_index_app(
+    location: &str,
+    index: &RepoIndex,
+    app: &str,
+) -> Result<AppManifest, ManifestError> {
+    let entry = index
+        .apps
+        .get(app)
+        .ok_or_else(|| ManifestError::NotFound(format!("{} in {}", app, location)))?;
+
+    let origin = index_origin(location).resolve(&entry.manifest);
+    let content = interpolate(&origin.load()?, &index.vars)?;
+    let mut manifest = parse_manifest_from(&content, &origin)?;
+    manifest.repository = Some(location.to_string());
+    Ok(manifest)
+}
+
+/// Resolve the manifest a repository lists for one version of an app: an
+/// earlier version's manifest, or the current one
+pub fn resolve_index_app_version(
+    location: &str,
+    index: &RepoIndex,
+    app: &str,
+    version: &str,
+) -> Result<Option<AppManifest>, ManifestError> {
+    let Some(entry) = index.apps.get(app) else {
+        return Ok(None);
+    };
+    let same = |v: &str| v.trim_start_matches('v') == version.trim_start_matches('v');
+
+    if let Some((_, manifest)) = entry.versions.iter().find(|(v, _)| same(v)) {
+        let origin = index_origin(location).resolve(manifest);
+        let content = interpolate(&origin.load()?, &index.vars)?;
+        let mut manifest = parse_manifest_from(&content, &origin)?;
+        manifest.repository = Some(location.to_string());
+        return Ok(Some(manifest));
+    }
+    if entry.version.as_deref().is_some_and(same) {
+        return resolve_index_app(location, index, app).map(Some);
+    }
+    Ok(None)
+}
+
+/// Outcome of building a repository index
+#[derive(Debug, Default)]
+pub struct IndexBuildReport {
+    pub index: RepoIndex,
+    /// Member files that failed interpolation or validation, with the error
+    pub failed: Vec<(String, String)>,
+}
+
+/// Build `index.toml` for a repository directory
+///
+/// Every `*.toml` file other than the index with an `[app]` table is a
+/// member (files without one are shared `extends` bases). Members are
+/// interpolated with the existing index's `[vars]`, parsed and validated;
+/// the index is only written if all of them succeed. The earlier versions
+/// each app lists are kept.
+pub fn build_index(dir: &Path) -> Result<IndexBuildReport, ManifestError> {
+    let index_path = dir.join(INDEX_FILE);
+    let existing = if index_path.exists() {
+        toml::from_str::<RepoIndex>(&fs::read_to_string(&index_path)?)?
+    } else {
+        RepoIndex::default()
+    };
+
+    let mut report = IndexBuildReport::default();
+    report.index.vars = existing.vars;
+
+    let mut files: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+        .filter(|p| p.file_name().is_some_and(|n| n != INDEX_FILE))
+        .collect();
+    files.sort();
+
+    for path in files {
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let content = fs::read_to_string(&path)?;
+        if toml::from_str::<toml::Table>(&content).is_ok_and(|t| !t.contains_key("app")) {
+            continue;
+        }
+
+        let manifest = interpolate(&content, &report.index.vars)
+            .and_then(|content| parse_manifest_from(&content, &ManifestOrigin::File(path.clone())))
+            .and_then(|manifest| validate_manifest(&manifest).map(|_| manifest));
+
+        let manifest = match manifest {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                report.failed.push((file_name, e.to_string()));
+                continue;
+            }
+        };
+
+        if let Some(existing) = report.index.apps.get(&manifest.app.name) {
+            report.failed.push((
+                file_name,
+                format!(
+                    "app '{}' is already defined by {}",
+                    manifest.app.name, existing.manifest
+                ),
+            ));
+            continue;
+        }
+
+        report.index.apps.insert(
+            manifest.app.name.clone(),
+            IndexEntry {
+                manifest: file_name,
+                display_name: manifest.app.display_name,
+                description: manifest.app.description,
+                version: manifest.app.version,
+                versions: existing
+                    .apps
+                    .get(&manifest.app.name)
+                    .map(|e| e.versions.clone())
+                    .unwrap_or_default(),
+            },
+        );
+    }
+
+    if report.failed.is_empty() {
+        let content = toml::to_string_pretty(&report.index)
+            .map_err(|e| ManifestError::SerializeError(e.to_string()))?;
+        fs::write(&index_path, content)?;
+    }
+
+    Ok(report)
+}
@@ -1,9 +1,25 @@
 //! Manifest parsing and validation for Voidbox apps
 
+mod arch;
+mod builder;
+mod checksums;
+mod extends;
+mod flatpak;
+mod hash;
+mod index;
 mod parser;
 mod schema;
+mod signature;
 mod validate;
 
+pub use arch::*;
+pub use builder::*;
+pub use checksums::*;
+pub use extends::*;
+pub use flatpak::*;
+pub use hash::*;
+pub use index::*;
 pub use parser::*;
 pub use schema::*;
+pub use signature::*;
 pub use validate::*;
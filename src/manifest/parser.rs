@@ -1,7 +1,11 @@
 //! Manifest parsing functions
 
+use super::extends::{ManifestOrigin, resolve_manifest_chain};
 use super::schema::AppManifest;
+use super::signature::signed_text;
+use crate::storage::http_get;
 use std::path::Path;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -17,17 +21,59 @@ pub enum ManifestError {
 
     #[error("Manifest not found: {0}")]
     NotFound(String),
+
+    #[error("Failed to hash manifest: {0}")]
+    HashError(String),
+
+    #[error("Failed to serialize manifest: {0}")]
+    SerializeError(String),
+
+    #[error("Failed to resolve extends: {0}")]
+    ExtendsError(String),
+
+    #[error("Bad manifest signature: {0}")]
+    SignatureError(String),
+
+    #[error(
+        "the manifest needs schema version {version}, but this voidbox reads up to {supported}; update voidbox with 'voidbox self-update'"
+    )]
+    UnsupportedSchema { version: i64, supported: u32 },
+
+    #[error("{app} has no download for {arch} (it has one for {supported})")]
+    UnsupportedArch {
+        app: String,
+        arch: String,
+        supported: String,
+    },
 }
 
 /// Parse a manifest from a TOML file
 pub fn parse_manifest_file(path: &Path) -> Result<AppManifest, ManifestError> {
     let content = std::fs::read_to_string(path)?;
-    parse_manifest_str(&content)
+    parse_manifest_from(&content, &ManifestOrigin::File(path.to_path_buf()))
 }
 
 /// Parse a manifest from a TOML string
 pub fn parse_manifest_str(content: &str) -> Result<AppManifest, ManifestError> {
-    let manifest: AppManifest = toml::from_str(content)?;
+    parse_manifest_from(content, &ManifestOrigin::Inline)
+}
+
+/// Parse a manifest, resolving `extends` relative to where it came from
+pub fn parse_manifest_from(
+    content: &str,
+    origin: &ManifestOrigin,
+) -> Result<AppManifest, ManifestError> {
+    let (table, bases) = resolve_manifest_chain(content, origin)?;
+    let mut manifest: AppManifest = table.try_into()?;
+
+    // A signature covers the exact text, which is only known here
+    if let Some(signature) = &mut manifest.signature {
+        signature.signed_content =
+            signed_text(content).map(|own| own.to_string() + &bases.concat());
+        if bases.is_empty() {
+            signature.manifest_text = Some(content.to_string());
+        }
+    }
     Ok(manifest)
 }
 
@@ -36,17 +82,30 @@ pub fn parse_manifest(content: &str) -> Result<AppManifest, ManifestError> {
     parse_manifest_str(content)
 }
 
-/// Parse a manifest from a URL
-pub fn parse_manifest_url(url: &str) -> Result<AppManifest, ManifestError> {
-    let mut resp = ureq::get(url)
-        .header("User-Agent", crate::APP_NAME)
+/// Largest manifest accepted from a URL
+const MAX_MANIFEST_SIZE: u64 = 1024 * 1024;
+
+/// Time allowed for fetching a manifest from a URL
+const MANIFEST_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fetch manifest text from a URL, bounded in size and time
+pub fn fetch_manifest_text(url: &str) -> Result<String, ManifestError> {
+    let mut resp = http_get(url)
+        .config()
+        .timeout_global(Some(MANIFEST_FETCH_TIMEOUT))
+        .build()
         .call()
         .map_err(|e| ManifestError::ValidationError(format!("HTTP error: {}", e)))?;
 
-    let content = resp
-        .body_mut()
+    resp.body_mut()
+        .with_config()
+        .limit(MAX_MANIFEST_SIZE)
         .read_to_string()
-        .map_err(|e| ManifestError::ValidationError(format!("Failed to read response: {}", e)))?;
+        .map_err(|e| ManifestError::ValidationError(format!("Failed to read response: {}", e)))
+}
 
-    parse_manifest_str(&content)
+/// Parse a manifest from a URL
+pub fn parse_manifest_url(url: &str) -> Result<AppManifest, ManifestError> {
+    let content = fetch_manifest_text(url)?;
+    parse_manifest_from(&content, &ManifestOrigin::Url(url.to_string()))
 }
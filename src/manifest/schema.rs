@@ -1,27 +1,141 @@
 //! Manifest schema definitions
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+/// Newest manifest format this voidbox reads; manifests needing a newer
+/// one are rejected before anything else is parsed
+pub const SCHEMA_VERSION: u32 = 1;
+
 /// Complete app manifest structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppManifest {
+    /// Format version of the manifest; 1 when not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<u32>,
     pub app: AppInfo,
     pub source: SourceConfig,
+    /// Sources for other architectures, by architecture (see `arch`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sources: BTreeMap<String, SourceConfig>,
     pub runtime: RuntimeConfig,
     #[serde(default)]
     pub dependencies: DependencyConfig,
     pub binary: BinaryConfig,
     #[serde(default)]
     pub desktop: DesktopConfig,
-    #[serde(default)]
-    pub permissions: PermissionConfig,
+    /// Declared permissions; without them a preset for the app's
+    /// categories applies (see `settings::manifest_permissions`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<PermissionConfig>,
+    #[serde(default, skip_serializing_if = "DbusConfig::is_default")]
+    pub dbus: DbusConfig,
+    #[serde(default, skip_serializing_if = "ProgressHints::is_empty")]
+    pub hints: ProgressHints,
+    #[serde(default, skip_serializing_if = "HooksConfig::is_empty")]
+    pub hooks: HooksConfig,
+    /// Signature over the manifest's canonical form
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ManifestSignature>,
+    /// Repository the manifest was found in, whose checksum database
+    /// vouches for its downloads (not part of the manifest)
+    #[serde(skip)]
+    pub repository: Option<String>,
+}
+
+/// How big an install is and how long it takes, so its progress bar can
+/// give each phase a realistic share
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressHints {
+    /// Size of the app's download (e.g. "120M")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_size: Option<String>,
+    /// Typical time to unpack and set up the app (e.g. "20s" or "2m")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extract_time: Option<String>,
+}
+
+impl ProgressHints {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The download size in bytes
+    pub fn download_bytes(&self) -> Option<u64> {
+        self.download_size
+            .as_deref()
+            .and_then(crate::storage::parse_size)
+    }
+
+    /// The extract time in seconds
+    pub fn extract_seconds(&self) -> Option<u64> {
+        self.extract_time
+            .as_deref()
+            .and_then(crate::storage::parse_age)
+            .map(|d| d.as_secs())
+    }
+}
+
+/// Steps run around an app's install and removal (see `cli::hooks`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HooksConfig {
+    /// Run once the app's files are unpacked, on installs, updates and
+    /// repairs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_install: Vec<HookStep>,
+    /// Run before the app's files are removed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_remove: Vec<HookStep>,
+}
+
+impl HooksConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A hook step: either a command or a file to write
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookStep {
+    /// Command and arguments to run in the app's container
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub run: Vec<String>,
+    /// Absolute path of a file to write in the app's rootfs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Content of `file`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub content: String,
+}
+
+/// A minisign signature of a manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestSignature {
+    /// minisign public key (the last line of its `.pub` file)
+    pub public_key: String,
+    /// minisign signature of `voidbox manifest signed-text` (the second
+    /// line of its `.minisig` file)
+    pub signature: String,
+    /// Text the signature covers, kept when the manifest is parsed; `None`
+    /// when the signature isn't a `[signature]` table ending the manifest
+    #[serde(skip)]
+    pub signed_content: Option<String>,
+    /// Whole text of a signed manifest without `extends`, so it can be
+    /// saved as it was signed
+    #[serde(skip)]
+    pub manifest_text: Option<String>,
 }
 
 /// Basic app information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppInfo {
+    /// Short alias used on the command line and for paths
     pub name: String,
+    /// Reverse-DNS app ID (e.g. "io.github.foo.Player"), used for the
+    /// desktop entry ID and default D-Bus name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub display_name: String,
     #[serde(default)]
     pub description: String,
@@ -29,6 +143,9 @@ pub struct AppInfo {
     pub version: Option<String>,
     #[serde(default)]
     pub license: Option<String>,
+    /// Who publishes the app (defaults to the GitHub owner)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
 }
 
 /// Source configuration for downloading the app
@@ -47,6 +164,12 @@ pub enum SourceConfig {
         asset_arch: String,
         #[serde(default)]
         asset_extension: Option<String>,
+        /// Release tag to install instead of the latest release
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tag: Option<String>,
+        /// SHA-256 the release asset must have (only sensible with `tag`)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sha256: Option<String>,
     },
     /// Direct download URL
     Direct {
@@ -55,12 +178,102 @@ pub enum SourceConfig {
         version_url: Option<String>,
         #[serde(default)]
         archive_type: Option<String>,
+        /// SHA-256 the download must have
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sha256: Option<String>,
     },
 
     /// Local file path (for testing)
     Local { path: PathBuf },
 }
 
+impl AppManifest {
+    /// Desktop entry ID: the app ID, or a voidbox-prefixed slug without one
+    pub fn desktop_id(&self) -> String {
+        match &self.app.id {
+            Some(id) => id.clone(),
+            None => format!(
+                "voidbox-{}",
+                crate::storage::paths::app_slug(&self.app.name)
+            ),
+        }
+    }
+
+    /// D-Bus name used for activation: explicit, or derived from the app ID
+    pub fn dbus_name(&self) -> Option<String> {
+        self.desktop
+            .dbus_name
+            .clone()
+            .or_else(|| self.app.id.as_ref().map(|id| id.replace('-', "_")))
+    }
+
+    /// Bus names the app may talk to through the D-Bus proxy
+    pub fn dbus_talk_names(&self) -> Vec<String> {
+        let mut names = default_dbus_talk_names();
+        names.extend(self.dbus.talk.iter().cloned());
+        names
+    }
+
+    /// Bus names the app may own through the D-Bus proxy
+    pub fn dbus_own_names(&self) -> Vec<String> {
+        let mut names = self.default_dbus_own_names();
+        names.extend(self.dbus.own.iter().cloned());
+        names
+    }
+
+    /// The app's MPRIS name and its own D-Bus name
+    fn default_dbus_own_names(&self) -> Vec<String> {
+        let mut names = vec![format!(
+            "org.mpris.MediaPlayer2.{}.*",
+            crate::storage::paths::app_slug(&self.app.name).replace('-', "_")
+        )];
+        names.extend(self.dbus_name());
+        names
+    }
+
+    /// Names the D-Bus proxy lets the app talk to and own with the given
+    /// permissions, or `None` for the unfiltered bus
+    ///
+    /// The manifest's `[dbus]` table only applies with the `session_bus`
+    /// permission; without it the app gets just the default names.
+    pub fn dbus_proxy_names(
+        &self,
+        permissions: &PermissionConfig,
+    ) -> Option<(Vec<String>, Vec<String>)> {
+        if !permissions.session_bus {
+            return Some((default_dbus_talk_names(), self.default_dbus_own_names()));
+        }
+        self.dbus
+            .filter
+            .then(|| (self.dbus_talk_names(), self.dbus_own_names()))
+    }
+}
+
+/// Desktop services every app may use: notifications, the portals and
+/// media player controls
+fn default_dbus_talk_names() -> Vec<String> {
+    [
+        "org.freedesktop.Notifications",
+        "org.freedesktop.portal.*",
+        "org.mpris.MediaPlayer2.*",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+impl SourceConfig {
+    /// The SHA-256 the manifest expects of the download, if it gives one
+    pub fn sha256(&self) -> Option<&str> {
+        match self {
+            SourceConfig::Github { sha256, .. } | SourceConfig::Direct { sha256, .. } => {
+                sha256.as_deref()
+            }
+            SourceConfig::Local { .. } => None,
+        }
+    }
+}
+
 fn default_linux() -> String {
     "linux".to_string()
 }
@@ -76,6 +289,18 @@ pub struct RuntimeConfig {
     pub base: String,
     #[serde(default)]
     pub arch: Vec<String>,
+    /// Size limit of the app's private /tmp (e.g. "256M"; defaults to
+    /// `tmp.size` in config)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmp_size: Option<String>,
+    /// Use the rootfs's copies of whitelisted libraries (libstdc++, libssl,
+    /// mesa) instead of bundled ones (defaults to `libraries.prefer_system`
+    /// in config)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_libraries: Option<bool>,
+    /// Libraries kept bundled even when system libraries are preferred
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bundled_libraries: Vec<String>,
 }
 
 fn default_base() -> String {
@@ -87,6 +312,9 @@ impl Default for RuntimeConfig {
         Self {
             base: default_base(),
             arch: vec!["x86_64".to_string()],
+            tmp_size: None,
+            system_libraries: None,
+            bundled_libraries: Vec::new(),
         }
     }
 }
@@ -96,6 +324,10 @@ impl Default for RuntimeConfig {
 pub struct DependencyConfig {
     #[serde(default)]
     pub packages: Vec<String>,
+    /// Other voidbox apps the app needs, by install source (a repository
+    /// name or manifest URL)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub apps: Vec<String>,
 }
 
 /// Binary configuration
@@ -108,6 +340,23 @@ pub struct BinaryConfig {
     pub args: Vec<String>,
     #[serde(default)]
     pub install_dir: Option<String>,
+    /// Activate the running instance instead of launching a second copy
+    #[serde(default)]
+    pub single_instance: bool,
+    /// Extra environment variables for the app
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Block the app's built-in updater (defaults to `updaters.block` in
+    /// config)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_updater: Option<bool>,
+    /// Globs of payload files not to install (debug symbols, locales)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Strip debug info and recompress large resources after install
+    /// (defaults to `install.optimize` in config)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optimize: Option<bool>,
 }
 
 /// Desktop entry configuration
@@ -123,6 +372,34 @@ pub struct DesktopConfig {
     pub mime_types: Vec<String>,
     #[serde(default)]
     pub keywords: Vec<String>,
+    /// D-Bus well-known name used to activate a running instance
+    #[serde(default)]
+    pub dbus_name: Option<String>,
+    /// Generic name (e.g. "Web Browser")
+    #[serde(default)]
+    pub generic_name: Option<String>,
+    /// Whether the app completes startup notification, so launchers show
+    /// a busy cursor until its window appears (defaults to true)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub startup_notify: Option<bool>,
+    /// Write window-rule hints that tie windows of class `wm_class` to the
+    /// desktop entry, for taskbars that don't go by StartupWMClass
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub window_rules: bool,
+    /// Translations keyed by locale (e.g. "de", "pt_BR")
+    #[serde(default)]
+    pub localized: BTreeMap<String, LocalizedStrings>,
+}
+
+/// Translated desktop entry strings for one locale
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LocalizedStrings {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub generic_name: Option<String>,
 }
 
 /// Permission configuration - all default to true (open by default)
@@ -154,12 +431,182 @@ pub struct PermissionConfig {
     /// The app runs like a normal process but with its own rootfs overlayed
     #[serde(default)]
     pub native_mode: bool,
+    #[serde(default, skip_serializing_if = "ClipboardAccess::is_default")]
+    pub clipboard: ClipboardAccess,
+    /// Session bus access the manifest's `[dbus]` table asks for beyond
+    /// the default names (more names, or the unfiltered bus)
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub session_bus: bool,
+}
+
+/// Clipboard access level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardAccess {
+    /// No clipboard access
+    None,
+    /// Paste from the clipboard
+    Read,
+    /// Paste and copy (unrestricted)
+    #[default]
+    Write,
+}
+
+impl ClipboardAccess {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClipboardAccess::None => "none",
+            ClipboardAccess::Read => "read",
+            ClipboardAccess::Write => "write",
+        }
+    }
+
+    /// What the level allows, for display
+    pub fn description(&self) -> &'static str {
+        match self {
+            ClipboardAccess::None => "No access",
+            ClipboardAccess::Read => "Paste only",
+            ClipboardAccess::Write => "Copy and paste",
+        }
+    }
+}
+
+impl std::str::FromStr for ClipboardAccess {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(ClipboardAccess::None),
+            "read" => Ok(ClipboardAccess::Read),
+            "write" => Ok(ClipboardAccess::Write),
+            _ => Err(format!(
+                "invalid clipboard access '{}' (expected none, read or write)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ClipboardAccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 fn default_true() -> bool {
     true
 }
 
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+/// Session bus access
+///
+/// The bus is filtered through xdg-dbus-proxy: the app can talk to
+/// notifications and the desktop portals, own its D-Bus name and an MPRIS
+/// name, plus whatever is listed here.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbusConfig {
+    /// Filter the bus; `false` gives the app the full session bus
+    #[serde(default = "default_true")]
+    pub filter: bool,
+    /// Extra names the app may talk to (`org.example.*` matches subnames)
+    #[serde(default)]
+    pub talk: Vec<String>,
+    /// Extra names the app may own
+    #[serde(default)]
+    pub own: Vec<String>,
+}
+
+impl DbusConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for DbusConfig {
+    fn default() -> Self {
+        Self {
+            filter: true,
+            talk: Vec::new(),
+            own: Vec::new(),
+        }
+    }
+}
+
+impl PermissionConfig {
+    /// Names of all permissions
+    pub const NAMES: [&'static str; 13] = [
+        "network",
+        "audio",
+        "microphone",
+        "gpu",
+        "camera",
+        "home",
+        "downloads",
+        "removable_media",
+        "dev_mode",
+        "fonts",
+        "themes",
+        "native_mode",
+        "session_bus",
+    ];
+
+    /// Names of all enabled permissions
+    pub fn enabled_names(&self) -> Vec<&'static str> {
+        [
+            ("network", self.network),
+            ("audio", self.audio),
+            ("microphone", self.microphone),
+            ("gpu", self.gpu),
+            ("camera", self.camera),
+            ("home", self.home),
+            ("downloads", self.downloads),
+            ("removable_media", self.removable_media),
+            ("dev_mode", self.dev_mode),
+            ("fonts", self.fonts),
+            ("themes", self.themes),
+            ("native_mode", self.native_mode),
+            ("session_bus", self.session_bus),
+        ]
+        .into_iter()
+        .chain([
+            ("clipboard_read", self.clipboard == ClipboardAccess::Read),
+            ("clipboard", self.clipboard == ClipboardAccess::Write),
+        ])
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| name)
+        .collect()
+    }
+
+    /// Turn a permission on or off by name; false if there is no such permission
+    pub fn set(&mut self, name: &str, enabled: bool) -> bool {
+        let field = match name {
+            "network" => &mut self.network,
+            "audio" => &mut self.audio,
+            "microphone" => &mut self.microphone,
+            "gpu" => &mut self.gpu,
+            "camera" => &mut self.camera,
+            "home" => &mut self.home,
+            "downloads" => &mut self.downloads,
+            "removable_media" => &mut self.removable_media,
+            "dev_mode" => &mut self.dev_mode,
+            "fonts" => &mut self.fonts,
+            "themes" => &mut self.themes,
+            "native_mode" => &mut self.native_mode,
+            "session_bus" => &mut self.session_bus,
+            _ => return false,
+        };
+        *field = enabled;
+        true
+    }
+}
+
 impl Default for PermissionConfig {
     fn default() -> Self {
         Self {
@@ -175,6 +622,8 @@ impl Default for PermissionConfig {
             fonts: true,
             themes: true,
             native_mode: false,
+            clipboard: ClipboardAccess::Write,
+            session_bus: true,
         }
     }
 }
@@ -215,9 +664,96 @@ impl ArchiveType {
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct InstalledApp {
     pub name: String,
+    /// Reverse-DNS app ID from the manifest, if it has one
+    #[serde(default)]
+    pub id: Option<String>,
     pub display_name: String,
     pub version: Option<String>,
     pub base_version: Option<String>,
     pub installed_date: Option<String>,
     pub manifest_path: Option<PathBuf>,
+    /// Canonical hash of the manifest this version was installed from
+    #[serde(default)]
+    pub manifest_hash: Option<String>,
+    /// URL the manifest was installed from; updates re-fetch it
+    #[serde(default)]
+    pub source_url: Option<String>,
+    /// Kept at its installed version; updates skip it
+    #[serde(default)]
+    pub pinned: bool,
+    /// Protected from removal; removing it needs `--force`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub protected: bool,
+    /// Installed, but adding it to the desktop failed and was undone;
+    /// `voidbox desktop regen` retries
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub not_integrated: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::parse_manifest;
+
+    fn manifest(dbus: &str) -> AppManifest {
+        parse_manifest(&format!(
+            r#"
+[app]
+name = "demo"
+display_name = "Demo"
+
+[source]
+type = "direct"
+url = "https://example.com/demo.tar.gz"
+
+[runtime]
+
+[binary]
+name = "demo"
+
+[dbus]
+{}
+"#,
+            dbus
+        ))
+        .unwrap()
+    }
+
+    fn without_session_bus() -> PermissionConfig {
+        PermissionConfig {
+            session_bus: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn session_bus_permission_grants_the_dbus_table() {
+        let manifest = manifest(r#"talk = ["org.example.Service"]"#);
+        let (talk, _) = manifest
+            .dbus_proxy_names(&PermissionConfig::default())
+            .unwrap();
+        assert!(talk.contains(&"org.example.Service".to_string()));
+    }
+
+    #[test]
+    fn without_session_bus_only_default_names_are_allowed() {
+        let manifest = manifest(
+            r#"talk = ["org.example.Service"]
+own = ["org.example.Owned"]"#,
+        );
+        let (talk, own) = manifest.dbus_proxy_names(&without_session_bus()).unwrap();
+        assert_eq!(talk, default_dbus_talk_names());
+        assert!(!own.contains(&"org.example.Owned".to_string()));
+    }
+
+    #[test]
+    fn unfiltered_bus_needs_session_bus_permission() {
+        let manifest = manifest("filter = false");
+        assert!(
+            manifest
+                .dbus_proxy_names(&PermissionConfig::default())
+                .is_none()
+        );
+        assert!(manifest.dbus_proxy_names(&without_session_bus()).is_some());
+    }
 }
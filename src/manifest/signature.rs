@@ -0,0 +1,353 @@
+//! Manifest signatures
+//!
+//! A manifest's `[signature]` holds a minisign public key and a signature
+//! made with it over the manifest's exact text: everything before the
+//! `[signature]` table, which must come last, followed by the text of each
+//! base in its `extends` chain. Any change to those bytes, reformatting
+//! included, breaks the signature, and fields added to the format later
+//! don't. Only minisign's plain Ed25519 signatures (`minisign -S -l`) are
+//! supported.
+//!
+//! A valid signature only shows the manifest is unchanged since whoever
+//! holds the key signed it. Who that is comes from the keys the user
+//! trusts, each under a name; a manifest signed with another key is
+//! reported as such.
+//!
+//! Checksum databases are signed the same way, with the key configured for
+//! the location they are published at (see `checksums`).
+
+use super::{AppManifest, ManifestError, manifest_hash, parse_manifest_str};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use toml::Table;
+
+/// minisign's identifier of plain Ed25519 keys and signatures
+const ALGORITHM_ED25519: &[u8; 2] = b"Ed";
+
+/// minisign's identifier of signatures over a BLAKE2b hash of the content
+const ALGORITHM_PREHASHED: &[u8; 2] = b"ED";
+
+/// What a manifest's signature says about it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SignatureStatus {
+    Unsigned,
+    /// Signed with a trusted key, known under `signer`
+    Trusted {
+        signer: String,
+        key_id: String,
+    },
+    /// Signed with a key that isn't trusted
+    UnknownKey {
+        key_id: String,
+    },
+}
+
+impl std::fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureStatus::Unsigned => write!(f, "Not signed"),
+            SignatureStatus::Trusted { signer, key_id } => {
+                write!(f, "Signed by {} (key {})", signer, key_id)
+            }
+            SignatureStatus::UnknownKey { key_id } => {
+                write!(f, "Signed with untrusted key {}", key_id)
+            }
+        }
+    }
+}
+
+/// The part of a manifest's own text its signature covers: everything
+/// before the `[signature]` table, or `None` when that isn't the last table
+pub fn signed_text(content: &str) -> Option<&str> {
+    let mut start = 0;
+    for line in content.split_inclusive('\n') {
+        if line.trim() == "[signature]" {
+            let rest: Table = toml::from_str(&content[start..]).ok()?;
+            return (rest.len() == 1).then(|| &content[..start]);
+        }
+        start += line.len();
+    }
+    None
+}
+
+/// Text to save a manifest as: the text it was signed as while its content
+/// is unchanged, so the saved copy still verifies, or else serialized anew
+/// without the signature, which no longer applies
+pub fn manifest_text(manifest: &AppManifest) -> Result<String, ManifestError> {
+    if let Some(text) = manifest
+        .signature
+        .as_ref()
+        .and_then(|s| s.manifest_text.as_ref())
+        && parse_manifest_str(text)
+            .and_then(|signed| manifest_hash(&signed))
+            .is_ok_and(|hash| manifest_hash(manifest).is_ok_and(|h| h == hash))
+    {
+        return Ok(text.clone());
+    }
+
+    let mut unsigned = manifest.clone();
+    unsigned.signature = None;
+    toml::to_string_pretty(&unsigned).map_err(|e| ManifestError::SerializeError(e.to_string()))
+}
+
+/// A minisign key ID as minisign prints it
+fn key_id_string(id: &[u8]) -> String {
+    id.iter().rev().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Decode a minisign public key, or the contents of a `.pub` file, into
+/// its key ID and key
+pub fn parse_public_key(key: &str) -> Result<(String, VerifyingKey), ManifestError> {
+    let line = key
+        .lines()
+        .map(str::trim)
+        .rfind(|l| !l.is_empty() && !l.starts_with("untrusted comment:"))
+        .unwrap_or_default();
+    let bytes = STANDARD
+        .decode(line)
+        .map_err(|e| ManifestError::SignatureError(format!("public key isn't base64: {}", e)))?;
+    if bytes.len() != 42 || &bytes[..2] != ALGORITHM_ED25519 {
+        return Err(ManifestError::SignatureError(
+            "public key isn't a minisign Ed25519 key".into(),
+        ));
+    }
+    let mut public = [0u8; 32];
+    public.copy_from_slice(&bytes[10..]);
+    let key = VerifyingKey::from_bytes(&public)
+        .map_err(|e| ManifestError::SignatureError(format!("invalid public key: {}", e)))?;
+    Ok((key_id_string(&bytes[2..10]), key))
+}
+
+/// Check a manifest's signature against its content and the trusted keys
+/// (public keys by signer name)
+///
+/// A signature that doesn't match the content, or that was made with
+/// another key than the one the manifest gives, is an error.
+pub fn verify_signature(
+    manifest: &AppManifest,
+    trusted: &BTreeMap<String, String>,
+) -> Result<SignatureStatus, ManifestError> {
+    let Some(signed) = &manifest.signature else {
+        return Ok(SignatureStatus::Unsigned);
+    };
+    let Some(content) = &signed.signed_content else {
+        return Err(ManifestError::SignatureError(
+            "the signature must be a [signature] table at the end of the manifest".into(),
+        ));
+    };
+    let (key_id, key) = verify_text(content, &signed.public_key, &signed.signature, "manifest")?;
+
+    let signer = trusted.iter().find_map(|(name, trusted_key)| {
+        parse_public_key(trusted_key)
+            .is_ok_and(|(_, trusted_key)| trusted_key == key)
+            .then(|| name.clone())
+    });
+    Ok(match signer {
+        Some(signer) => SignatureStatus::Trusted { signer, key_id },
+        None => SignatureStatus::UnknownKey { key_id },
+    })
+}
+
+/// Check a minisign signature of `content` made with `public_key`,
+/// returning the key's ID and the key
+///
+/// `what` names the signed text in errors.
+pub(crate) fn verify_text(
+    content: &str,
+    public_key: &str,
+    signature: &str,
+    what: &str,
+) -> Result<(String, VerifyingKey), ManifestError> {
+    let (key_id, key) = parse_public_key(public_key)?;
+
+    let bytes = STANDARD
+        .decode(signature.trim())
+        .map_err(|e| ManifestError::SignatureError(format!("signature isn't base64: {}", e)))?;
+    if bytes.len() != 74 {
+        return Err(ManifestError::SignatureError(
+            "signature isn't a minisign signature".into(),
+        ));
+    }
+    if &bytes[..2] == ALGORITHM_PREHASHED {
+        return Err(ManifestError::SignatureError(
+            "prehashed signatures aren't supported; sign with minisign -S -l".into(),
+        ));
+    }
+    if &bytes[..2] != ALGORITHM_ED25519 {
+        return Err(ManifestError::SignatureError(
+            "signature isn't a minisign Ed25519 signature".into(),
+        ));
+    }
+    if key_id_string(&bytes[2..10]) != key_id {
+        return Err(ManifestError::SignatureError(format!(
+            "signed with key {}, not the {}'s key {}",
+            key_id_string(&bytes[2..10]),
+            what,
+            key_id
+        )));
+    }
+    let signature = Signature::from_slice(&bytes[10..])
+        .map_err(|e| ManifestError::SignatureError(e.to_string()))?;
+
+    key.verify_strict(content.as_bytes(), &signature)
+        .map_err(|_| {
+            ManifestError::SignatureError(format!(
+                "the {} was changed after it was signed with key {}",
+                what, key_id
+            ))
+        })?;
+    Ok((key_id, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use std::path::PathBuf;
+
+    /// A manifest without any field added since signing was introduced
+    const MANIFEST: &str = r#"[app]
+name = "demo"
+display_name = "Demo"
+
+[source]
+type = "direct"
+url = "https://example.com/demo.tar.gz"
+
+[runtime]
+base = "ubuntu:24.04"
+
+[binary]
+name = "demo"
+args = ["--flag"]
+
+[permissions]
+network = true
+"#;
+
+    const KEY_ID: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    /// The key as a minisign public key line
+    fn public_key() -> String {
+        let mut bytes = ALGORITHM_ED25519.to_vec();
+        bytes.extend(KEY_ID);
+        bytes.extend(signing_key().verifying_key().as_bytes());
+        STANDARD.encode(bytes)
+    }
+
+    /// A minisign signature line over `content`
+    fn sign(content: &str) -> String {
+        let mut bytes = ALGORITHM_ED25519.to_vec();
+        bytes.extend(KEY_ID);
+        bytes.extend(signing_key().sign(content.as_bytes()).to_bytes());
+        STANDARD.encode(bytes)
+    }
+
+    fn with_signature(content: &str, signed: &str) -> String {
+        format!(
+            "{}\n[signature]\npublic_key = \"{}\"\nsignature = \"{}\"\n",
+            content,
+            public_key(),
+            sign(signed)
+        )
+    }
+
+    fn trusted() -> BTreeMap<String, String> {
+        BTreeMap::from([("example".to_string(), public_key())])
+    }
+
+    fn verify(content: &str) -> Result<SignatureStatus, ManifestError> {
+        verify_signature(&parse_manifest_str(content)?, &trusted())
+    }
+
+    /// A directory of its own for a test's files
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("voidbox-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn signature_made_before_field_additions_verifies() {
+        // Newer fields (app.id, permissions.clipboard, ...) get defaults
+        // when parsed, which must not matter to the signature
+        let signed = format!("{}\n", MANIFEST);
+        let status = verify(&with_signature(MANIFEST, &signed)).unwrap();
+        assert_eq!(
+            status,
+            SignatureStatus::Trusted {
+                signer: "example".into(),
+                key_id: key_id_string(&KEY_ID),
+            }
+        );
+    }
+
+    #[test]
+    fn untrusted_key_is_reported() {
+        let signed = format!("{}\n", MANIFEST);
+        let manifest = parse_manifest_str(&with_signature(MANIFEST, &signed)).unwrap();
+        let status = verify_signature(&manifest, &BTreeMap::new()).unwrap();
+        assert!(matches!(status, SignatureStatus::UnknownKey { .. }));
+    }
+
+    #[test]
+    fn changed_text_is_rejected() {
+        let signed = format!("{}\n", MANIFEST);
+        let content = with_signature(MANIFEST, &signed).replace("--flag", "--other");
+        assert!(verify(&content).is_err());
+
+        // Even a change that leaves the parsed manifest the same
+        let content = with_signature(MANIFEST, &signed).replace("network = true", "network=true");
+        assert!(verify(&content).is_err());
+    }
+
+    #[test]
+    fn signature_must_be_the_last_table() {
+        let (head, tail) = MANIFEST.split_at(MANIFEST.find("[binary]").unwrap());
+        let signed = format!("{}\n", head);
+        let content = format!("{}\n\n{}", with_signature(head, &signed).trim_end(), tail);
+        assert!(signed_text(&content).is_none());
+        assert!(verify(&content).is_err());
+    }
+
+    #[test]
+    fn changed_base_is_rejected() {
+        let dir = test_dir("signature-base");
+        let base = MANIFEST.replace("--flag", "--base");
+        std::fs::write(dir.join("base.toml"), &base).unwrap();
+        let own = "extends = \"base.toml\"\n\n[app]\nname = \"child\"\ndisplay_name = \"Child\"\n";
+        let app = dir.join("app.toml");
+        std::fs::write(&app, with_signature(own, &format!("{}\n{}", own, base))).unwrap();
+
+        let manifest = crate::manifest::parse_manifest_file(&app).unwrap();
+        assert!(verify_signature(&manifest, &trusted()).is_ok());
+
+        std::fs::write(dir.join("base.toml"), base.replace("--base", "--changed")).unwrap();
+        let manifest = crate::manifest::parse_manifest_file(&app).unwrap();
+        assert!(verify_signature(&manifest, &trusted()).is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn saved_text_keeps_the_signature_only_while_unchanged() {
+        let signed = format!("{}\n", MANIFEST);
+        let content = with_signature(MANIFEST, &signed);
+        let manifest = parse_manifest_str(&content).unwrap();
+        assert_eq!(manifest_text(&manifest).unwrap(), content);
+
+        let mut changed = manifest.clone();
+        changed.app.name = "demo@1.0".into();
+        let saved = parse_manifest_str(&manifest_text(&changed).unwrap()).unwrap();
+        assert!(saved.signature.is_none());
+        assert_eq!(saved.app.name, "demo@1.0");
+    }
+}
@@ -1,40 +1,288 @@
 //! Manifest validation
 
-use super::ManifestError;
-use super::schema::AppManifest;
+use super::schema::{AppManifest, SCHEMA_VERSION};
+use super::{ManifestError, SUPPORTED_ARCHES};
+use crate::storage::paths;
+use serde::Serialize;
 
-/// Validate a manifest for completeness and correctness
+/// Something wrong with a manifest, and the field it's in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ManifestProblem {
+    /// Dotted path of the field (e.g. "binary.env.PATH" or
+    /// "hooks.post_install[1]")
+    pub field: String,
+    pub message: String,
+}
+
+/// Validate a manifest for completeness and correctness, failing with its
+/// first problem
 pub fn validate_manifest(manifest: &AppManifest) -> Result<(), ManifestError> {
+    match manifest_problems(manifest).into_iter().next() {
+        Some(problem) => Err(ManifestError::ValidationError(problem.message)),
+        None => Ok(()),
+    }
+}
+
+/// Every problem with a manifest, in the order of its sections
+pub fn manifest_problems(manifest: &AppManifest) -> Vec<ManifestProblem> {
+    let mut problems = Vec::new();
+    let mut problem = |field: String, message: String| {
+        problems.push(ManifestProblem { field, message });
+    };
+
+    if manifest.schema_version == Some(0) {
+        problem(
+            "schema_version".into(),
+            format!("schema_version must be between 1 and {}", SCHEMA_VERSION),
+        );
+    }
+
     // Check required fields
     if manifest.app.name.is_empty() {
-        return Err(ManifestError::ValidationError(
-            "app.name is required".into(),
-        ));
+        problem("app.name".into(), "app.name is required".into());
     }
 
     if manifest.app.display_name.is_empty() {
-        return Err(ManifestError::ValidationError(
+        problem(
+            "app.display_name".into(),
             "app.display_name is required".into(),
-        ));
+        );
     }
 
     if manifest.binary.name.is_empty() {
-        return Err(ManifestError::ValidationError(
-            "binary.name is required".into(),
-        ));
+        problem("binary.name".into(), "binary.name is required".into());
+    }
+
+    // App names may be any Unicode text; paths use a sanitized slug of it
+    let name = &manifest.app.name;
+    if name.trim() != name
+        || name.chars().any(|c| c.is_control() || c == '/' || c == '@')
+        || name.starts_with('-')
+        || name.chars().count() > 64
+    {
+        problem(
+            "app.name".into(),
+            "app.name must be at most 64 characters without slashes, '@', control characters or surrounding spaces, and must not start with '-'".into(),
+        );
+    } else if paths::is_hashed_slug(name) {
+        // It would share the directories of the app the slug was made for
+        problem(
+            "app.name".into(),
+            format!(
+                "app.name '{}' has the shape of a generated slug (a name, '-' and 16 hex digits)",
+                name
+            ),
+        );
+    }
+
+    if let Some(id) = &manifest.app.id
+        && !is_valid_app_id(id)
+    {
+        problem(
+            "app.id".into(),
+            format!(
+                "app.id '{}' must be a reverse-DNS name with at least three parts (e.g. io.github.foo.Player)",
+                id
+            ),
+        );
+    }
+
+    let sources = std::iter::once(("source".to_string(), &manifest.source)).chain(
+        manifest
+            .sources
+            .iter()
+            .map(|(arch, source)| (format!("sources.{}", arch), source)),
+    );
+    for (key, source) in sources {
+        if let Some(sha256) = source.sha256()
+            && !(sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit()))
+        {
+            problem(
+                format!("{}.sha256", key),
+                format!(
+                    "{}.sha256 '{}' must be 64 hexadecimal characters",
+                    key, sha256
+                ),
+            );
+        }
+    }
+
+    let arches = manifest
+        .runtime
+        .arch
+        .iter()
+        .map(|arch| ("runtime.arch".to_string(), arch))
+        .chain(
+            manifest
+                .sources
+                .keys()
+                .map(|arch| (format!("sources.{}", arch), arch)),
+        );
+    for (field, arch) in arches {
+        if !SUPPORTED_ARCHES.contains(&arch.as_str()) {
+            problem(
+                field.clone(),
+                format!(
+                    "{}: unknown architecture '{}' (expected one of {})",
+                    field,
+                    arch,
+                    SUPPORTED_ARCHES.join(", ")
+                ),
+            );
+        }
+    }
+
+    for app in manifest
+        .dependencies
+        .apps
+        .iter()
+        .filter(|a| a.trim().is_empty() || *a == name)
+    {
+        problem(
+            "dependencies.apps".into(),
+            format!("dependencies.apps: '{}' isn't another app to install", app),
+        );
     }
 
-    // Validate app name (lowercase, alphanumeric, hyphens only)
-    if !manifest
-        .app
-        .name
-        .chars()
-        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    for var in manifest.binary.env.keys().filter(|var| {
+        var.is_empty()
+            || var.starts_with(|c: char| c.is_ascii_digit())
+            || !var.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }) {
+        problem(
+            format!("binary.env.{}", var),
+            format!(
+                "binary.env: '{}' isn't a variable name (letters, digits and '_')",
+                var
+            ),
+        );
+    }
+
+    if manifest
+        .binary
+        .args
+        .iter()
+        .chain(manifest.binary.env.values())
+        .any(|s| s.contains('\0'))
+    {
+        problem(
+            "binary.args".into(),
+            "binary.args and binary.env must not contain NUL characters".into(),
+        );
+    }
+
+    if let Some(size) = &manifest.hints.download_size
+        && manifest.hints.download_bytes().is_none_or(|s| s == 0)
     {
-        return Err(ManifestError::ValidationError(
-            "app.name must be lowercase alphanumeric with hyphens only".into(),
-        ));
+        problem(
+            "hints.download_size".into(),
+            format!("hints.download_size '{}' must be a size such as 120M", size),
+        );
+    }
+    if let Some(time) = &manifest.hints.extract_time
+        && manifest.hints.extract_seconds().is_none_or(|s| s == 0)
+    {
+        problem(
+            "hints.extract_time".into(),
+            format!(
+                "hints.extract_time '{}' must be a duration such as 20s or 2m",
+                time
+            ),
+        );
+    }
+
+    let hooks = [
+        ("hooks.post_install", &manifest.hooks.post_install),
+        ("hooks.pre_remove", &manifest.hooks.pre_remove),
+    ];
+    for (key, steps) in hooks {
+        for (index, step) in steps.iter().enumerate() {
+            let issue = match (&step.file, step.run.is_empty()) {
+                (Some(_), false) => Some("sets both run and file"),
+                (None, true) => Some("needs run or file"),
+                (None, false) if !step.content.is_empty() => Some("has content without a file"),
+                (None, false) if step.run[0].is_empty() => Some("has an empty command"),
+                (Some(file), true)
+                    if !file.starts_with('/')
+                        || file.ends_with('/')
+                        || file.split('/').any(|c| c == "..") =>
+                {
+                    Some("writes a file that isn't an absolute path without '..'")
+                }
+                _ => None,
+            };
+            if let Some(issue) = issue {
+                problem(
+                    format!("{}[{}]", key, index),
+                    format!("{}: a step {}", key, issue),
+                );
+            }
+        }
+    }
+
+    if manifest.desktop.window_rules && manifest.desktop.wm_class.is_none() {
+        problem(
+            "desktop.window_rules".into(),
+            "desktop.window_rules needs desktop.wm_class, the class the app's windows have".into(),
+        );
     }
 
-    Ok(())
+    if let Some(size) = &manifest.runtime.tmp_size
+        && crate::storage::parse_size(size).is_none_or(|s| s == 0)
+    {
+        problem(
+            "runtime.tmp_size".into(),
+            format!(
+                "runtime.tmp_size '{}' must be a size such as 256M or 1G",
+                size
+            ),
+        );
+    }
+
+    // Validate locale keys for desktop entry translations (lang_COUNTRY@MODIFIER)
+    for locale in manifest.desktop.localized.keys() {
+        if !is_valid_locale(locale) {
+            problem(
+                format!("desktop.localized.{}", locale),
+                format!(
+                    "desktop.localized: invalid locale '{}' (expected e.g. 'de' or 'pt_BR')",
+                    locale
+                ),
+            );
+        }
+    }
+
+    problems
+}
+
+/// Check a reverse-DNS app ID as used for desktop entry IDs and D-Bus names:
+/// three or more dot-separated elements of [A-Za-z0-9_-], none starting
+/// with a digit
+fn is_valid_app_id(id: &str) -> bool {
+    let elements: Vec<&str> = id.split('.').collect();
+    id.len() <= 255
+        && elements.len() >= 3
+        && elements.iter().all(|e| {
+            !e.is_empty()
+                && !e.starts_with(|c: char| c.is_ascii_digit())
+                && e.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        })
+}
+
+/// Check a locale in the form used by desktop entries: lang[_COUNTRY][@MODIFIER]
+fn is_valid_locale(locale: &str) -> bool {
+    let (base, modifier) = match locale.split_once('@') {
+        Some((base, modifier)) => (base, Some(modifier)),
+        None => (locale, None),
+    };
+    let (lang, country) = match base.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (base, None),
+    };
+
+    (2..=3).contains(&lang.len())
+        && lang.chars().all(|c| c.is_ascii_lowercase())
+        && country.is_none_or(|c| c.len() == 2 && c.chars().all(|c| c.is_ascii_uppercase()))
+        && modifier.is_none_or(|m| !m.is_empty() && m.chars().all(|c| c.is_ascii_alphanumeric()))
 }
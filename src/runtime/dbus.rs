@@ -0,0 +1,75 @@
+//! Session bus filtering
+//!
+//! Sandboxed apps get the session bus through xdg-dbus-proxy, which only
+//! lets them see the names their manifest permits. The proxy's socket is
+//! mounted over the bus socket in the container's runtime directory.
+
+use crate::storage::paths;
+use std::fs;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the proxy's socket to appear
+const PROXY_STARTUP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A running xdg-dbus-proxy (stopped on drop)
+pub struct DbusProxy {
+    child: Child,
+    socket: PathBuf,
+}
+
+impl DbusProxy {
+    /// Host path of the filtered bus socket
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+}
+
+impl Drop for DbusProxy {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+        fs::remove_file(&self.socket).ok();
+    }
+}
+
+/// Start a filtering proxy for an app's session bus
+///
+/// Returns `None` when there is no session bus or xdg-dbus-proxy isn't
+/// installed; the caller decides what access the app gets then.
+pub fn start_dbus_proxy(app_name: &str, talk: &[String], own: &[String]) -> Option<DbusProxy> {
+    let address = std::env::var("DBUS_SESSION_BUS_ADDRESS").ok()?;
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok()?;
+
+    let socket_dir = Path::new(&runtime_dir).join("voidbox");
+    fs::create_dir_all(&socket_dir).ok()?;
+    let socket = socket_dir.join(format!("{}-bus", paths::app_slug(app_name)));
+    fs::remove_file(&socket).ok();
+
+    let child = Command::new("xdg-dbus-proxy")
+        .arg(&address)
+        .arg(&socket)
+        .arg("--filter")
+        .args(talk.iter().map(|name| format!("--talk={}", name)))
+        .args(own.iter().map(|name| format!("--own={}", name)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut proxy = DbusProxy { child, socket };
+
+    let started = Instant::now();
+    while started.elapsed() < PROXY_STARTUP_TIMEOUT {
+        if fs::metadata(&proxy.socket).is_ok_and(|m| m.file_type().is_socket()) {
+            return Some(proxy);
+        }
+        if !matches!(proxy.child.try_wait(), Ok(None)) {
+            return None;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    None
+}
@@ -0,0 +1,127 @@
+//! Camera and microphone device access
+//!
+//! The container shares the host's /dev, so camera and audio capture devices
+//! the app may not use are hidden by mounting /dev/null over them. The
+//! PipeWire and PulseAudio sockets are hidden the same way without the
+//! microphone permission, since the sound servers let any client record.
+//! The container's init keeps reading the app's live permissions while it
+//! runs, so toggling either permission applies without a restart (a device
+//! or socket the app already has open stays open).
+
+use crate::manifest::PermissionConfig;
+use crate::storage::paths::LIVE_PERMISSIONS_FILE;
+use nix::mount::{MntFlags, MsFlags, mount, umount2};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Where the app's live settings directory is mounted in the container
+pub const LIVE_MOUNT: &str = "/.voidbox/live";
+
+/// How often the live permissions are re-read
+const LIVE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Device nodes in `dir` whose names match
+fn devices(dir: &str, matches: fn(&str) -> bool) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| matches(&entry.file_name().to_string_lossy()))
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// V4L2 video and media controller devices
+fn camera_devices() -> Vec<PathBuf> {
+    devices("/dev", |name| {
+        name.starts_with("video") || name.starts_with("media")
+    })
+}
+
+/// ALSA capture PCMs (pcmC<card>D<device>c)
+fn capture_devices() -> Vec<PathBuf> {
+    devices("/dev/snd", |name| {
+        name.starts_with("pcmC") && name.ends_with('c')
+    })
+}
+
+/// Sound server sockets in the runtime directory
+fn sound_server_sockets() -> Vec<PathBuf> {
+    let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") else {
+        return Vec::new();
+    };
+    ["pipewire-0", "pipewire-0-manager", "pulse/native"]
+        .iter()
+        .map(|socket| Path::new(&runtime_dir).join(socket))
+        .filter(|socket| socket.exists())
+        .collect()
+}
+
+/// Devices currently hidden from the app
+#[derive(Debug, Default)]
+pub struct DeviceMasks {
+    masked: BTreeSet<PathBuf>,
+}
+
+impl DeviceMasks {
+    /// Hide or reveal devices to match the permissions
+    pub fn apply(&mut self, permissions: &PermissionConfig) {
+        for (devices, allowed) in [
+            (camera_devices(), permissions.camera),
+            (capture_devices(), permissions.microphone),
+            (sound_server_sockets(), permissions.microphone),
+        ] {
+            for device in devices {
+                let masked = self.masked.contains(&device);
+                if !allowed && !masked {
+                    if mount(
+                        Some("/dev/null"),
+                        &device,
+                        None::<&str>,
+                        MsFlags::MS_BIND,
+                        None::<&str>,
+                    )
+                    .is_ok()
+                    {
+                        self.masked.insert(device);
+                    }
+                } else if allowed && masked && umount2(&device, MntFlags::MNT_DETACH).is_ok() {
+                    self.masked.remove(&device);
+                }
+            }
+        }
+    }
+}
+
+/// Apply device permissions now and follow live changes in the background
+pub fn watch_device_permissions(permissions: &PermissionConfig) {
+    let mut masks = DeviceMasks::default();
+    masks.apply(permissions);
+
+    let live_file = Path::new(LIVE_MOUNT).join(LIVE_PERMISSIONS_FILE);
+    if !live_file.exists() {
+        return;
+    }
+
+    thread::spawn(move || {
+        let mut last_modified: Option<SystemTime> = None;
+        loop {
+            thread::sleep(LIVE_POLL_INTERVAL);
+            let modified = fs::metadata(&live_file).and_then(|m| m.modified()).ok();
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            if let Ok(content) = fs::read_to_string(&live_file)
+                && let Ok(permissions) = serde_json::from_str::<PermissionConfig>(&content)
+            {
+                masks.apply(&permissions);
+            }
+        }
+    });
+}
@@ -1,5 +1,6 @@
 //! Process execution in container
 
+use super::mount::ContainerSetup;
 use crate::manifest::PermissionConfig;
 use nix::unistd::execvp;
 use std::ffi::CString;
@@ -31,24 +32,42 @@ pub fn exec_replace(cmd: &str, args: &[String]) -> Result<(), ExecError> {
     Ok(())
 }
 
-/// Spawn a child process for container initialization
+/// Spawn a child process for container initialization and wait for it
 pub fn spawn_container_init(
     self_exe: &Path,
     rootfs: &Path,
     cmd: &str,
     args: &[String],
     permissions: &PermissionConfig,
+    setup: &ContainerSetup,
 ) -> Result<std::process::ExitStatus, ExecError> {
+    let mut child = start_container_init(self_exe, rootfs, cmd, args, permissions, setup)?;
+    Ok(child.wait()?)
+}
+
+/// Spawn a child process for container initialization without waiting
+pub fn start_container_init(
+    self_exe: &Path,
+    rootfs: &Path,
+    cmd: &str,
+    args: &[String],
+    permissions: &PermissionConfig,
+    setup: &ContainerSetup,
+) -> Result<std::process::Child, ExecError> {
     // Serialize permissions to JSON for passing via command line
     let permissions_json = serde_json::to_string(permissions)
         .map_err(|e| ExecError::ExecFailed(format!("failed to serialize permissions: {}", e)))?;
+    let setup_json = serde_json::to_string(setup)
+        .map_err(|e| ExecError::ExecFailed(format!("failed to serialize setup: {}", e)))?;
 
-    let mut child = Command::new(self_exe)
+    let child = Command::new(self_exe)
         .arg("internal-init")
         .arg(rootfs)
         .arg(cmd)
         .arg("--permissions")
         .arg(&permissions_json)
+        .arg("--setup")
+        .arg(&setup_json)
         .arg("--")
         .args(args)
         .stdin(Stdio::inherit())
@@ -56,7 +75,7 @@ pub fn spawn_container_init(
         .stderr(Stdio::inherit())
         .spawn()?;
 
-    Ok(child.wait()?)
+    Ok(child)
 }
 
 /// Start dbus daemon inside container
@@ -76,18 +95,76 @@ pub fn start_dbus() -> Result<(), ExecError> {
     Ok(())
 }
 
+/// Give up every capability for good and forbid regaining any through exec
+/// (setuid binaries, file capabilities)
+fn drop_privileges() -> std::io::Result<()> {
+    use std::io::Error;
+
+    // Highest capability number the kernel may know; unknown ones are EINVAL
+    for cap in 0..64 {
+        if unsafe { libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0) } != 0 {
+            let error = Error::last_os_error();
+            if error.raw_os_error() != Some(libc::EINVAL) {
+                return Err(error);
+            }
+        }
+    }
+
+    let no_ambient = unsafe {
+        libc::prctl(
+            libc::PR_CAP_AMBIENT,
+            libc::PR_CAP_AMBIENT_CLEAR_ALL,
+            0,
+            0,
+            0,
+        )
+    };
+    if no_ambient != 0 || unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        return Err(Error::last_os_error());
+    }
+
+    // Clear the effective, permitted and inheritable sets (two 32-bit words
+    // each with _LINUX_CAPABILITY_VERSION_3)
+    #[repr(C)]
+    struct CapHeader {
+        version: u32,
+        pid: libc::c_int,
+    }
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct CapData {
+        effective: u32,
+        permitted: u32,
+        inheritable: u32,
+    }
+    let header = CapHeader {
+        version: 0x2008_0522,
+        pid: 0,
+    };
+    let data = [CapData {
+        effective: 0,
+        permitted: 0,
+        inheritable: 0,
+    }; 2];
+    if unsafe { libc::syscall(libc::SYS_capset, &header, data.as_ptr()) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
 /// Initialize container environment and execute command
 pub fn init_and_exec(
     rootfs: &Path,
     cmd: &str,
     args: &[String],
     permissions: &PermissionConfig,
+    setup: &ContainerSetup,
 ) -> Result<(), ExecError> {
     use super::mount::{pivot_to_container, setup_container_env, setup_container_mounts};
-    use nix::sys::wait::{waitpid, WaitStatus};
+    use nix::sys::wait::{WaitStatus, waitpid};
     use nix::unistd::Pid;
 
-    setup_container_mounts(rootfs, permissions)
+    setup_container_mounts(rootfs, permissions, setup)
         .map_err(|e| ExecError::ExecFailed(format!("mount setup: {}", e)))?;
 
     pivot_to_container(rootfs, permissions)
@@ -95,6 +172,9 @@ pub fn init_and_exec(
 
     setup_container_env(permissions);
 
+    // Camera and microphone devices, kept in step with live permissions
+    super::devices::watch_device_permissions(permissions);
+
     // Only start dbus in non-native mode; native_mode uses host's D-Bus
     if !permissions.native_mode {
         start_dbus()?;
@@ -108,9 +188,48 @@ pub fn init_and_exec(
         libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0);
     }
 
+    // Updater stubs go first on PATH
+    let mut path = std::env::var("PATH").unwrap_or_default();
+    if setup.updater_stubs.is_some() && !permissions.native_mode {
+        path = format!("{}:{}", super::mount::UPDATER_STUBS_MOUNT, path);
+    }
+
+    // The filtered bus sits where the real one was
+    let mut command = Command::new(cmd);
+    if setup.dbus_proxy.is_some()
+        && let Some(bus) = super::mount::session_bus_path()
+    {
+        command.env("DBUS_SESSION_BUS_ADDRESS", format!("unix:path={}", bus));
+    }
+
+    // Manifest and override values may refer to the inherited environment
+    let env: Vec<(&String, String)> = setup
+        .env
+        .iter()
+        .map(|(name, value)| {
+            let value = expand_vars(value, |var| match var {
+                "PATH" => Some(path.clone()),
+                _ => std::env::var(var).ok(),
+            });
+            (name, tidy_path_list(name, value))
+        })
+        .collect();
+
+    // The app is root in its namespace; without dropping that it could
+    // remount its read-only binds or replace the filtered sockets
+    if !setup.privileged {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: drop_privileges only makes prctl/capset system calls
+        unsafe {
+            command.pre_exec(drop_privileges);
+        }
+    }
+
     // Spawn app as child process
-    let mut child = Command::new(cmd)
+    let mut child = command
         .args(args)
+        .env("PATH", &path)
+        .envs(env)
         .stdin(Stdio::inherit())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
@@ -118,7 +237,8 @@ pub fn init_and_exec(
         .map_err(|e| ExecError::ExecFailed(format!("{}: {}", cmd, e)))?;
 
     // Wait for direct child first
-    let status = child.wait()
+    let status = child
+        .wait()
         .map_err(|e| ExecError::ExecFailed(format!("wait: {}", e)))?;
     let exit_code = status.code().unwrap_or(1);
 
@@ -133,5 +253,54 @@ pub fn init_and_exec(
         }
     }
 
+    // Drop the private /tmp now that nothing uses it
+    if setup.tmp_size.is_some() {
+        nix::mount::umount2("/tmp", nix::mount::MntFlags::MNT_DETACH).ok();
+    }
+
     std::process::exit(exit_code);
 }
+
+/// Expand `$NAME` and `${NAME}` in a variable's value; unset variables
+/// expand to nothing and `$$` is a literal `$`
+fn expand_vars(value: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(after) = after.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+        } else if let Some(braced) = after.strip_prefix('{')
+            && let Some(end) = braced.find('}')
+        {
+            expanded.push_str(&lookup(&braced[..end]).unwrap_or_default());
+            rest = &braced[end + 1..];
+        } else {
+            let end = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+            if end == 0 {
+                expanded.push('$');
+            } else {
+                expanded.push_str(&lookup(&after[..end]).unwrap_or_default());
+            }
+            rest = &after[end..];
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Drop the empty entries an unset variable leaves in a `*PATH` list, which
+/// the loader and shells would read as the current directory
+fn tidy_path_list(name: &str, value: String) -> String {
+    if !name.ends_with("PATH") || !value.contains(':') {
+        return value;
+    }
+    value
+        .split(':')
+        .filter(|entry| !entry.is_empty())
+        .collect::<Vec<_>>()
+        .join(":")
+}
@@ -0,0 +1,333 @@
+//! Running instance records and resource usage
+//!
+//! Each launch records its PID and sandbox status in the app's instance
+//! directory, and moves itself into a cgroup of its own when the user's
+//! cgroup tree is writable (as under a systemd user session). The task
+//! monitor reads CPU and memory usage from that cgroup, or from the process
+//! tree below the launcher when no cgroup could be created.
+
+use crate::storage::paths;
+use nix::sys::signal::{Signal, kill};
+use nix::unistd::Pid;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Mount point of the unified cgroup hierarchy
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// How long stopped apps get to exit before they are killed
+const STOP_GRACE: Duration = Duration::from_secs(10);
+
+/// A running instance of an app
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceInfo {
+    pub app: String,
+    /// PID of the launcher (`voidbox run`)
+    pub pid: u32,
+    /// Start time of the launcher in clock ticks after boot, which tells it
+    /// apart from a later process reusing its PID
+    #[serde(default)]
+    pub start_time: Option<u64>,
+    /// Cgroup holding the launcher and the container
+    #[serde(default)]
+    pub cgroup: Option<PathBuf>,
+    /// Human-readable sandbox status, e.g. "sandboxed, bus filtered"
+    pub sandbox: String,
+}
+
+/// Resource usage of an instance
+#[derive(Debug, Clone, Default)]
+pub struct ResourceUsage {
+    /// Total CPU time used, in microseconds
+    pub cpu_usec: u64,
+    /// Resident memory in bytes
+    pub memory: u64,
+    /// PIDs of the instance's processes
+    pub processes: Vec<u32>,
+}
+
+/// Registration of a running instance (removed on drop)
+pub struct InstanceGuard {
+    record: PathBuf,
+    /// Created cgroup and the one the launcher came from
+    cgroup: Option<(PathBuf, PathBuf)>,
+}
+
+impl Drop for InstanceGuard {
+    fn drop(&mut self) {
+        fs::remove_file(&self.record).ok();
+        if let Some((cgroup, original)) = &self.cgroup {
+            fs::write(
+                original.join("cgroup.procs"),
+                std::process::id().to_string(),
+            )
+            .ok();
+            fs::remove_dir(cgroup).ok();
+        }
+    }
+}
+
+/// Record this process as a running instance of an app
+///
+/// Must be called before the container is spawned so it is created in the
+/// instance's cgroup.
+pub fn register_instance(app_name: &str, sandbox: &str) -> std::io::Result<InstanceGuard> {
+    let pid = std::process::id();
+    let cgroup = enter_cgroup(app_name, pid);
+    let info = InstanceInfo {
+        app: app_name.to_string(),
+        pid,
+        start_time: process_start_time(pid),
+        cgroup: cgroup.as_ref().map(|(path, _)| path.clone()),
+        sandbox: sandbox.to_string(),
+    };
+
+    let dir = paths::app_instances_dir(app_name);
+    fs::create_dir_all(&dir)?;
+    let record = dir.join(format!("{}.json", pid));
+    let json = serde_json::to_string(&info).map_err(std::io::Error::other)?;
+    fs::write(&record, json)?;
+
+    Ok(InstanceGuard { record, cgroup })
+}
+
+/// Cgroup of the current process
+pub(crate) fn current_cgroup() -> Option<PathBuf> {
+    let content = fs::read_to_string("/proc/self/cgroup").ok()?;
+    let path = content.lines().find_map(|line| line.strip_prefix("0::"))?;
+    Some(Path::new(CGROUP_ROOT).join(path.trim_start_matches('/')))
+}
+
+/// Move this process into a new cgroup next to its current one
+///
+/// Returns the new cgroup and the original one, or `None` if the cgroup
+/// tree isn't writable.
+fn enter_cgroup(app_name: &str, pid: u32) -> Option<(PathBuf, PathBuf)> {
+    let original = current_cgroup()?;
+    let cgroup = original
+        .parent()?
+        .join(format!("voidbox-{}-{}", paths::app_slug(app_name), pid));
+    fs::create_dir(&cgroup).ok()?;
+    if fs::write(cgroup.join("cgroup.procs"), pid.to_string()).is_err() {
+        fs::remove_dir(&cgroup).ok();
+        return None;
+    }
+    Some((cgroup, original))
+}
+
+/// Whether the instance's launcher is still running (and not some other
+/// process that got its PID)
+fn is_alive(info: &InstanceInfo) -> bool {
+    match info.start_time {
+        Some(start_time) => process_start_time(info.pid) == Some(start_time),
+        None => Path::new("/proc").join(info.pid.to_string()).exists(),
+    }
+}
+
+/// When a process started, in clock ticks after boot (field 22 of
+/// /proc/<pid>/stat)
+fn process_start_time(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // Fields after the command name start with the third, the state
+    stat.rsplit_once(')')?
+        .1
+        .split_whitespace()
+        .nth(19)?
+        .parse()
+        .ok()
+}
+
+/// All running instances of installed apps
+///
+/// Records of instances that died without cleaning up are removed.
+pub fn running_instances() -> Vec<InstanceInfo> {
+    let Ok(apps) = fs::read_dir(paths::apps_dir()) else {
+        return Vec::new();
+    };
+
+    let mut instances = Vec::new();
+    for app in apps.flatten() {
+        let Ok(records) = fs::read_dir(app.path().join("instances")) else {
+            continue;
+        };
+        for record in records.flatten() {
+            let info = fs::read_to_string(record.path())
+                .ok()
+                .and_then(|json| serde_json::from_str::<InstanceInfo>(&json).ok());
+            match info {
+                Some(info) if is_alive(&info) => instances.push(info),
+                Some(info) => {
+                    fs::remove_file(record.path()).ok();
+                    if let Some(cgroup) = &info.cgroup {
+                        fs::remove_dir(cgroup).ok();
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+    instances.sort_by(|a, b| a.app.cmp(&b.app).then(a.pid.cmp(&b.pid)));
+    instances
+}
+
+/// Current resource usage of an instance
+pub fn instance_usage(info: &InstanceInfo) -> ResourceUsage {
+    if let Some(cgroup) = &info.cgroup
+        && let Some(usage) = cgroup_usage(cgroup)
+    {
+        return usage;
+    }
+    process_tree_usage(info.pid)
+}
+
+fn cgroup_usage(cgroup: &Path) -> Option<ResourceUsage> {
+    let processes: Vec<u32> = fs::read_to_string(cgroup.join("cgroup.procs"))
+        .ok()?
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect();
+    let cpu_usec = fs::read_to_string(cgroup.join("cpu.stat"))
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))
+        .and_then(|value| value.trim().parse().ok())?;
+    // memory.current is missing unless the memory controller is enabled
+    let memory = match fs::read_to_string(cgroup.join("memory.current")) {
+        Ok(value) => value.trim().parse().ok()?,
+        Err(_) => processes.iter().filter_map(|&pid| process_rss(pid)).sum(),
+    };
+
+    Some(ResourceUsage {
+        cpu_usec,
+        memory,
+        processes,
+    })
+}
+
+/// Usage of a process and all its descendants
+fn process_tree_usage(root: u32) -> ResourceUsage {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    if let Ok(entries) = fs::read_dir("/proc") {
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+                continue;
+            };
+            if let Some(stat) = process_stat(pid) {
+                children.entry(stat.ppid).or_default().push(pid);
+            }
+        }
+    }
+
+    let mut usage = ResourceUsage::default();
+    let mut pending = vec![root];
+    while let Some(pid) = pending.pop() {
+        let Some(stat) = process_stat(pid) else {
+            continue;
+        };
+        usage.cpu_usec += stat.cpu_usec;
+        usage.memory += process_rss(pid).unwrap_or(0);
+        usage.processes.push(pid);
+        if let Some(kids) = children.get(&pid) {
+            pending.extend(kids);
+        }
+    }
+    usage
+}
+
+struct ProcessStat {
+    ppid: u32,
+    cpu_usec: u64,
+}
+
+fn process_stat(pid: u32) -> Option<ProcessStat> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // Fields after the command name, which may contain spaces
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let ticks = |index: usize| fields.get(index)?.parse::<u64>().ok();
+    // SAFETY: sysconf has no preconditions
+    let ticks_per_sec = match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        n if n > 0 => n as u64,
+        _ => 100,
+    };
+    Some(ProcessStat {
+        ppid: fields.get(1)?.parse().ok()?,
+        cpu_usec: (ticks(11)? + ticks(12)?) * 1_000_000 / ticks_per_sec,
+    })
+}
+
+/// Resident memory of a process in bytes
+fn process_rss(pid: u32) -> Option<u64> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let kb: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// Send a signal to an instance's processes, except the launcher
+///
+/// Nothing is signalled once the launcher is gone, since its PID (and the
+/// process tree below it) may belong to something else by then.
+fn signal_app(info: &InstanceInfo, signal: Signal) {
+    if !is_alive(info) {
+        return;
+    }
+    for pid in instance_usage(info).processes {
+        if pid != info.pid {
+            kill(Pid::from_raw(pid as i32), signal).ok();
+        }
+    }
+}
+
+/// Ask an instance's app to quit, killing it if it's still running after a
+/// grace period
+///
+/// The launcher itself isn't signalled; it exits once the app has, which
+/// removes the instance record.
+pub fn stop_instance(info: &InstanceInfo) {
+    signal_app(info, Signal::SIGTERM);
+
+    let info = info.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(STOP_GRACE);
+        signal_app(&info, Signal::SIGKILL);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(pid: u32, start_time: Option<u64>) -> InstanceInfo {
+        InstanceInfo {
+            app: "demo".into(),
+            pid,
+            start_time,
+            cgroup: None,
+            sandbox: String::new(),
+        }
+    }
+
+    #[test]
+    fn running_launcher_is_alive() {
+        let pid = std::process::id();
+        let start_time = process_start_time(pid);
+        assert!(start_time.is_some());
+        assert!(is_alive(&instance(pid, start_time)));
+    }
+
+    #[test]
+    fn reused_pid_is_not_the_launcher() {
+        let pid = std::process::id();
+        let start_time = process_start_time(pid).unwrap();
+        assert!(!is_alive(&instance(pid, Some(start_time + 1))));
+    }
+}
@@ -0,0 +1,60 @@
+//! Per-app instance lock for running-instance detection
+//!
+//! Every running instance holds a shared flock on a lock file in the app's
+//! data directory. Anything that must not happen while the app is running
+//! (like swapping its rootfs) probes for an exclusive lock.
+
+use crate::storage::paths;
+use nix::fcntl::{Flock, FlockArg};
+use std::fs::{self, File, OpenOptions};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LockError {
+    #[error("Failed to open lock file: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Failed to lock: {0}")]
+    LockFailed(String),
+}
+
+/// Shared lock held for the lifetime of a running instance
+pub struct InstanceLock {
+    _lock: Flock<File>,
+}
+
+fn open_lock_file(app_name: &str) -> Result<File, LockError> {
+    let lock_path = paths::app_lock_path(app_name);
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    Ok(OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)?)
+}
+
+/// Register a running instance of an app (released on drop)
+pub fn acquire_instance_lock(app_name: &str) -> Result<InstanceLock, LockError> {
+    let file = open_lock_file(app_name)?;
+    let lock = Flock::lock(file, FlockArg::LockShared)
+        .map_err(|(_, e)| LockError::LockFailed(e.to_string()))?;
+
+    Ok(InstanceLock { _lock: lock })
+}
+
+/// Check whether any instance of an app is currently running
+pub fn is_app_running(app_name: &str) -> bool {
+    if !paths::app_lock_path(app_name).exists() {
+        return false;
+    }
+
+    let Ok(file) = open_lock_file(app_name) else {
+        return false;
+    };
+
+    // If we can't take an exclusive lock, someone holds a shared one
+    Flock::lock(file, FlockArg::LockExclusiveNonblock).is_err()
+}
@@ -1,9 +1,23 @@
 //! Container runtime - namespaces, mounts, and execution
 
+mod dbus;
+mod devices;
 mod exec;
+mod instance;
+mod lock;
 mod mount;
 mod namespace;
+mod priority;
+mod wayland;
+mod windows;
 
+pub use dbus::*;
+pub use devices::*;
 pub use exec::*;
+pub use instance::*;
+pub use lock::*;
 pub use mount::*;
 pub use namespace::*;
+pub use priority::*;
+pub use wayland::*;
+pub use windows::*;
@@ -1,10 +1,12 @@
 //! Mount operations for container setup
 
-use crate::manifest::PermissionConfig;
+use crate::manifest::{ClipboardAccess, PermissionConfig};
 use nix::mount::{MntFlags, MsFlags, mount, umount2};
 use nix::unistd::{chdir, pivot_root, sethostname};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -47,6 +49,43 @@ impl BindMount {
     }
 }
 
+/// Per-app setup applied inside the container
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContainerSetup {
+    /// Payload directory relative to the rootfs, mounted read-only
+    pub payload: Option<String>,
+    /// Host directory mounted writable as the container's `/root`
+    pub data_dir: Option<PathBuf>,
+    /// Extra environment variables for the app
+    pub env: BTreeMap<String, String>,
+    /// Host directory of stub programs put first on the app's PATH
+    #[serde(default)]
+    pub updater_stubs: Option<PathBuf>,
+    /// Filtered bus socket mounted over the session bus socket
+    #[serde(default)]
+    pub dbus_proxy: Option<PathBuf>,
+    /// Share the unfiltered session bus (the manifest turns filtering off)
+    #[serde(default)]
+    pub host_bus: bool,
+    /// Host directory with the app's live settings
+    #[serde(default)]
+    pub live_dir: Option<PathBuf>,
+    /// Restricted compositor socket mounted over the Wayland socket
+    #[serde(default)]
+    pub wayland_socket: Option<PathBuf>,
+    /// Size of the app's private /tmp in bytes; the host's /tmp is shared
+    /// without one
+    #[serde(default)]
+    pub tmp_size: Option<u64>,
+    /// Keep root's capabilities in the namespace (package setup and the
+    /// debugging shell); apps run without them
+    #[serde(default)]
+    pub privileged: bool,
+}
+
+/// Where the updater stubs are mounted in the container
+pub const UPDATER_STUBS_MOUNT: &str = "/usr/lib/voidbox/updater-stubs";
+
 /// Get bind mounts based on permissions
 pub fn get_bind_mounts(permissions: &PermissionConfig) -> Vec<BindMount> {
     let mut mounts = vec![
@@ -86,12 +125,6 @@ pub fn get_bind_mounts(permissions: &PermissionConfig) -> Vec<BindMount> {
         return mounts;
     }
 
-    // XDG_RUNTIME_DIR for audio/Wayland (standard mode)
-    if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
-        let relative = runtime_dir.trim_start_matches('/');
-        mounts.push(BindMount::new(&runtime_dir, relative, false));
-    }
-
     // Home folder mount
     if permissions.home {
         if let Ok(home) = std::env::var("HOME") {
@@ -229,6 +262,7 @@ pub fn get_bind_mounts(permissions: &PermissionConfig) -> Vec<BindMount> {
 pub fn setup_container_mounts(
     rootfs: &Path,
     permissions: &PermissionConfig,
+    setup: &ContainerSetup,
 ) -> Result<(), MountError> {
     // Make root private
     mount(
@@ -252,8 +286,29 @@ pub fn setup_container_mounts(
 
     chdir(rootfs).map_err(|e| MountError::MountFailed(format!("chdir to rootfs: {}", e)))?;
 
+    // App data as /root, before the theme configs are mounted inside it
+    if let Some(data_dir) = &setup.data_dir
+        && !permissions.native_mode
+    {
+        let target = rootfs.join("root");
+        fs::create_dir_all(&target)?;
+        mount(
+            Some(data_dir.as_path()),
+            &target,
+            None::<&str>,
+            MsFlags::MS_BIND | MsFlags::MS_REC,
+            None::<&str>,
+        )
+        .map_err(|e| MountError::MountFailed(format!("bind app data: {}", e)))?;
+    }
+
     // Apply bind mounts
     for bind_mount in get_bind_mounts(permissions) {
+        // A private /tmp replaces the host's
+        if setup.tmp_size.is_some() && bind_mount.target == "tmp" {
+            continue;
+        }
+
         let source = Path::new(&bind_mount.source);
         let target = rootfs.join(&bind_mount.target);
 
@@ -290,9 +345,237 @@ pub fn setup_container_mounts(
         }
     }
 
+    // Stubs shadowing updater helpers; the host's /usr replaces the
+    // container's in native mode, so they are left out there
+    if let Some(stubs) = &setup.updater_stubs
+        && !permissions.native_mode
+    {
+        let target = rootfs.join(UPDATER_STUBS_MOUNT.trim_start_matches('/'));
+        fs::create_dir_all(&target)?;
+        bind_readonly(stubs, &target)?;
+    }
+
+    // Only the sockets the app may use from the runtime directory
+    if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
+        if permissions.native_mode {
+            hide_voidbox_sockets(rootfs, Path::new(&runtime_dir))?;
+        } else {
+            mount_runtime_dir(rootfs, Path::new(&runtime_dir), permissions, setup)?;
+        }
+    }
+
+    if let Some(size) = setup.tmp_size {
+        mount_private_tmp(rootfs, size, shares_x11(permissions))?;
+    } else if !shares_x11(permissions) {
+        hide_x11_sockets(rootfs)?;
+    }
+
+    // Filtered session bus in place of the real one
+    if let Some(proxy) = &setup.dbus_proxy
+        && let Some(bus) = session_bus_path()
+    {
+        bind_socket(proxy, &rootfs.join(bus.trim_start_matches('/')))?;
+    }
+
+    // Restricted compositor connection in place of the real one
+    if let Some(socket) = &setup.wayland_socket
+        && let Some(display) = super::wayland::wayland_socket_path()
+    {
+        bind_socket(socket, &rootfs.join(display.trim_start_matches('/')))?;
+    }
+
+    // Live settings, re-read while the app runs
+    if let Some(live_dir) = &setup.live_dir {
+        let target = rootfs.join(super::devices::LIVE_MOUNT.trim_start_matches('/'));
+        fs::create_dir_all(&target)?;
+        bind_readonly(live_dir, &target)?;
+    }
+
+    // Keep the app from modifying its own files
+    if let Some(payload) = &setup.payload {
+        let target = rootfs.join(payload);
+        if target.is_dir() {
+            bind_readonly(&target, &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Session bus socket path in the runtime directory
+pub fn session_bus_path() -> Option<String> {
+    std::env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .map(|dir| format!("{}/bus", dir.trim_end_matches('/')))
+}
+
+/// Sound server sockets shared with apps that have the audio permission
+const SOUND_SERVER_SOCKETS: &[&str] = &["pipewire-0", "pulse/native"];
+
+/// Mount an empty runtime directory with the sockets the app may use
+///
+/// Sharing the host's would hand the app every socket in it, the filtered
+/// bus sockets voidbox starts for other apps included. The compositor
+/// socket is bound here unless a restricted one replaces it, and the bus
+/// only when the app gets it unfiltered; the filtered bus and restricted
+/// compositor sockets are bound afterwards.
+fn mount_runtime_dir(
+    rootfs: &Path,
+    runtime_dir: &Path,
+    permissions: &PermissionConfig,
+    setup: &ContainerSetup,
+) -> Result<(), MountError> {
+    let target = rootfs.join(runtime_dir.strip_prefix("/").unwrap_or(runtime_dir));
+    fs::create_dir_all(&target)?;
+    mount(
+        Some("tmpfs"),
+        &target,
+        Some("tmpfs"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some("mode=700"),
+    )
+    .map_err(|e| MountError::MountFailed(format!("mount runtime directory: {}", e)))?;
+
+    let mut sockets: Vec<PathBuf> = Vec::new();
+    if permissions.audio {
+        sockets.extend(SOUND_SERVER_SOCKETS.iter().map(|s| runtime_dir.join(s)));
+    }
+    if setup.wayland_socket.is_none()
+        && let Some(display) = super::wayland::wayland_socket_path()
+    {
+        sockets.push(PathBuf::from(display));
+    }
+    if setup.host_bus
+        && let Some(bus) = session_bus_path()
+    {
+        sockets.push(PathBuf::from(bus));
+    }
+
+    for socket in sockets {
+        // Sockets outside the runtime directory are reached some other way
+        let Ok(relative) = socket.strip_prefix(runtime_dir) else {
+            continue;
+        };
+        if !socket.exists() {
+            continue;
+        }
+        let socket_target = target.join(relative);
+        if let Some(parent) = socket_target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        bind_socket(&socket, &socket_target)?;
+    }
+    Ok(())
+}
+
+/// Cover the sockets voidbox starts for apps in a shared runtime directory
+fn hide_voidbox_sockets(rootfs: &Path, runtime_dir: &Path) -> Result<(), MountError> {
+    let relative = runtime_dir.strip_prefix("/").unwrap_or(runtime_dir);
+    let target = rootfs.join(relative).join("voidbox");
+    if !target.is_dir() {
+        return Ok(());
+    }
+    mount(
+        Some("tmpfs"),
+        &target,
+        Some("tmpfs"),
+        MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some("mode=755"),
+    )
+    .map_err(|e| MountError::MountFailed(format!("hide voidbox sockets: {}", e)))
+}
+
+/// Whether the app may reach the X server
+///
+/// Any X11 client can read the clipboard at any time, so apps with limited
+/// clipboard access only get the (restricted) Wayland connection.
+pub fn shares_x11(permissions: &PermissionConfig) -> bool {
+    permissions.clipboard == ClipboardAccess::Write
+}
+
+/// Cover the host's X11 socket directory with an empty one
+fn hide_x11_sockets(rootfs: &Path) -> Result<(), MountError> {
+    let target = rootfs.join("tmp/.X11-unix");
+    if !target.is_dir() {
+        return Ok(());
+    }
+    mount(
+        Some("tmpfs"),
+        &target,
+        Some("tmpfs"),
+        MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some("mode=755"),
+    )
+    .map_err(|e| MountError::MountFailed(format!("hide X11 sockets: {}", e)))
+}
+
+/// Mount a size-limited tmpfs as the container's /tmp
+///
+/// The X11 socket directory is bound into it (if `x11`) so X11 apps can
+/// still reach the display.
+fn mount_private_tmp(rootfs: &Path, size: u64, x11: bool) -> Result<(), MountError> {
+    let target = rootfs.join("tmp");
+    fs::create_dir_all(&target)?;
+    mount(
+        Some("tmpfs"),
+        &target,
+        Some("tmpfs"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some(format!("size={},mode=1777", size).as_str()),
+    )
+    .map_err(|e| MountError::MountFailed(format!("mount private /tmp: {}", e)))?;
+
+    let x11_sockets = Path::new("/tmp/.X11-unix");
+    if x11 && x11_sockets.is_dir() {
+        let x11_target = target.join(".X11-unix");
+        fs::create_dir_all(&x11_target)?;
+        mount(
+            Some(x11_sockets),
+            &x11_target,
+            None::<&str>,
+            MsFlags::MS_BIND,
+            None::<&str>,
+        )
+        .map_err(|e| MountError::MountFailed(format!("bind X11 sockets: {}", e)))?;
+    }
     Ok(())
 }
 
+/// Bind a socket over another (or where it would be)
+fn bind_socket(source: &Path, target: &Path) -> Result<(), MountError> {
+    if !target.exists() {
+        fs::write(target, "")?;
+    }
+    mount(
+        Some(source),
+        target,
+        None::<&str>,
+        MsFlags::MS_BIND,
+        None::<&str>,
+    )
+    .map_err(|e| MountError::MountFailed(format!("bind {}: {}", source.display(), e)))
+}
+
+/// Bind a directory and make the bind read-only
+fn bind_readonly(source: &Path, target: &Path) -> Result<(), MountError> {
+    mount(
+        Some(source),
+        target,
+        None::<&str>,
+        MsFlags::MS_BIND | MsFlags::MS_REC,
+        None::<&str>,
+    )
+    .map_err(|e| MountError::MountFailed(format!("bind {}: {}", target.display(), e)))?;
+    mount(
+        None::<&str>,
+        target,
+        None::<&str>,
+        MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY,
+        None::<&str>,
+    )
+    .map_err(|e| MountError::MountFailed(format!("remount {} read-only: {}", target.display(), e)))
+}
+
 /// Perform pivot_root to switch to container filesystem
 pub fn pivot_to_container(rootfs: &Path, permissions: &PermissionConfig) -> Result<(), MountError> {
     let old_root = rootfs.join("old_root");
@@ -370,7 +653,10 @@ pub fn setup_container_env(permissions: &PermissionConfig) {
 
         // X11/Wayland display - DISPLAY is inherited from parent, just ensure it's set
         // The /tmp/.X11-unix socket is already mounted via /tmp bind mount
-        if std::env::var("DISPLAY").is_err() {
+        if !shares_x11(permissions) {
+            std::env::remove_var("DISPLAY");
+            std::env::remove_var("XAUTHORITY");
+        } else if std::env::var("DISPLAY").is_err() {
             // Default to :0 if not set
             std::env::set_var("DISPLAY", ":0");
         }
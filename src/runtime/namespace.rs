@@ -46,3 +46,21 @@ pub fn setup_container_namespaces() -> Result<(), NamespaceError> {
 
     Ok(())
 }
+
+/// Check host settings that prevent unprivileged user namespaces
+///
+/// Returns a description of the first blocker found, if any.
+pub fn user_namespace_blocker() -> Option<String> {
+    let read = |path: &str| fs::read_to_string(path).ok().map(|s| s.trim().to_string());
+
+    if read("/proc/sys/kernel/unprivileged_userns_clone").as_deref() == Some("0") {
+        return Some("kernel.unprivileged_userns_clone is 0".to_string());
+    }
+    if read("/proc/sys/user/max_user_namespaces").as_deref() == Some("0") {
+        return Some("user.max_user_namespaces is 0".to_string());
+    }
+    if read("/proc/sys/kernel/apparmor_restrict_unprivileged_userns").as_deref() == Some("1") {
+        return Some("AppArmor restricts unprivileged user namespaces".to_string());
+    }
+    None
+}
@@ -0,0 +1,108 @@
+//! Reduced priority for background operations
+//!
+//! Maintenance runs and scripted `--background` operations lower their CPU
+//! and IO priority so they don't make an interactive session sluggish:
+//! the process is reniced, moved to the idle IO scheduling class, and,
+//! when the user's cgroup tree is writable, moved into a cgroup of its own
+//! with low CPU and IO weights. Everything started by the process inherits
+//! the lowered priority. Operations the user starts interactively keep
+//! normal priority.
+
+use super::instance::current_cgroup;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static BACKGROUND: AtomicBool = AtomicBool::new(false);
+
+/// Nice value for background operations
+const BACKGROUND_NICE: libc::c_int = 10;
+
+/// cgroup `cpu.weight` for background operations (default 100)
+const BACKGROUND_CPU_WEIGHT: u32 = 20;
+
+/// cgroup `io.weight` for background operations (default 100)
+const BACKGROUND_IO_WEIGHT: u32 = 10;
+
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+/// Background priority for this process (the cgroup is left on drop)
+pub struct BackgroundGuard {
+    /// Created cgroup and the one the process came from
+    cgroup: Option<(PathBuf, PathBuf)>,
+}
+
+impl Drop for BackgroundGuard {
+    fn drop(&mut self) {
+        BACKGROUND.store(false, Ordering::Relaxed);
+        if let Some((cgroup, original)) = &self.cgroup {
+            fs::write(
+                original.join("cgroup.procs"),
+                std::process::id().to_string(),
+            )
+            .ok();
+            fs::remove_dir(cgroup).ok();
+        }
+    }
+}
+
+/// Lower this process's CPU and IO priority for a background operation
+///
+/// Must be called before threads or child processes are started so they
+/// inherit it. Each step is best effort: what the system doesn't allow is
+/// skipped and reported in verbose output.
+pub fn enter_background() -> BackgroundGuard {
+    BACKGROUND.store(true, Ordering::Relaxed);
+
+    // SAFETY: setpriority only changes this process's scheduling priority
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, BACKGROUND_NICE) } != 0
+        && crate::is_verbose()
+    {
+        eprintln!(
+            "[voidbox] Could not lower CPU priority: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+
+    let ioprio = IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT;
+    // SAFETY: ioprio_set only changes this process's IO scheduling class
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) } != 0
+        && crate::is_verbose()
+    {
+        eprintln!(
+            "[voidbox] Could not lower IO priority: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+
+    let cgroup = enter_background_cgroup();
+    if cgroup.is_none() && crate::is_verbose() {
+        eprintln!("[voidbox] No writable cgroup; running without cgroup weights");
+    }
+    BackgroundGuard { cgroup }
+}
+
+/// Check whether this process runs a background operation
+pub fn in_background() -> bool {
+    BACKGROUND.load(Ordering::Relaxed)
+}
+
+/// Move this process into a low-weight cgroup next to its current one
+fn enter_background_cgroup() -> Option<(PathBuf, PathBuf)> {
+    let original = current_cgroup()?;
+    let pid = std::process::id();
+    let cgroup = original
+        .parent()?
+        .join(format!("{}-background-{}", crate::APP_NAME, pid));
+    fs::create_dir(&cgroup).ok()?;
+    if fs::write(cgroup.join("cgroup.procs"), pid.to_string()).is_err() {
+        fs::remove_dir(&cgroup).ok();
+        return None;
+    }
+    // Only present when the controllers are enabled for the parent
+    fs::write(cgroup.join("cpu.weight"), BACKGROUND_CPU_WEIGHT.to_string()).ok();
+    fs::write(cgroup.join("io.weight"), BACKGROUND_IO_WEIGHT.to_string()).ok();
+    Some((cgroup, original))
+}
@@ -0,0 +1,127 @@
+//! Restricted Wayland connections
+//!
+//! Apps with limited clipboard access connect to the compositor through a
+//! socket created with the security-context protocol. The compositor knows
+//! those clients are sandboxed and withholds privileged protocols from them,
+//! including data-control, which lets a client read and set the clipboard
+//! without having focus. The socket is mounted over the compositor's socket
+//! in the container's runtime directory.
+
+use crate::storage::paths;
+use nix::unistd::pipe;
+use std::fs;
+use std::os::fd::{AsFd, OwnedFd};
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use wayland_client::globals::{GlobalListContents, registry_queue_init};
+use wayland_client::protocol::wl_registry::WlRegistry;
+use wayland_client::{Connection, Dispatch, QueueHandle};
+use wayland_protocols::wp::security_context::v1::client::wp_security_context_manager_v1::WpSecurityContextManagerV1;
+use wayland_protocols::wp::security_context::v1::client::wp_security_context_v1::WpSecurityContextV1;
+
+/// Sandbox engine name reported to the compositor
+const SANDBOX_ENGINE: &str = "voidbox";
+
+struct State;
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as wayland_client::Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WpSecurityContextManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WpSecurityContextManagerV1,
+        _: <WpSecurityContextManagerV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WpSecurityContextV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WpSecurityContextV1,
+        _: <WpSecurityContextV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+/// A restricted compositor socket (closed on drop)
+pub struct WaylandContext {
+    socket: PathBuf,
+    /// The compositor stops accepting on the socket when this is closed
+    _close: OwnedFd,
+}
+
+impl WaylandContext {
+    /// Host path of the restricted socket
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+}
+
+impl Drop for WaylandContext {
+    fn drop(&mut self) {
+        fs::remove_file(&self.socket).ok();
+    }
+}
+
+/// Create a restricted compositor socket for an app
+///
+/// Returns `None` outside a Wayland session or when the compositor doesn't
+/// support the security-context protocol.
+pub fn start_wayland_context(app_name: &str, app_id: &str) -> Option<WaylandContext> {
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok()?;
+    let connection = Connection::connect_to_env().ok()?;
+    let (globals, mut queue) = registry_queue_init::<State>(&connection).ok()?;
+    let qh = queue.handle();
+    let manager: WpSecurityContextManagerV1 = globals.bind(&qh, 1..=1, ()).ok()?;
+
+    let socket_dir = Path::new(&runtime_dir).join("voidbox");
+    fs::create_dir_all(&socket_dir).ok()?;
+    let socket = socket_dir.join(format!("{}-wayland", paths::app_slug(app_name)));
+    fs::remove_file(&socket).ok();
+    let listener = UnixListener::bind(&socket).ok()?;
+    let (close_read, close_write) = pipe().ok()?;
+    let wayland_context = WaylandContext {
+        socket,
+        _close: close_write,
+    };
+
+    let context = manager.create_listener(listener.as_fd(), close_read.as_fd(), &qh, ());
+    context.set_sandbox_engine(SANDBOX_ENGINE.to_string());
+    context.set_app_id(app_id.to_string());
+    context.set_instance_id(std::process::id().to_string());
+    context.commit();
+    context.destroy();
+    manager.destroy();
+    queue.roundtrip(&mut State).ok()?;
+
+    // The compositor now holds its own copies of the listening socket and
+    // the read end of the pipe
+    Some(wayland_context)
+}
+
+/// Compositor socket path in the runtime directory
+pub fn wayland_socket_path() -> Option<String> {
+    let display = std::env::var("WAYLAND_DISPLAY").ok()?;
+    if display.starts_with('/') {
+        return Some(display);
+    }
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok()?;
+    Some(format!("{}/{}", runtime_dir.trim_end_matches('/'), display))
+}
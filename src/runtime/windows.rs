@@ -0,0 +1,180 @@
+//! Watching for an app's first window
+//!
+//! Startup notification and xdg-activation tokens are consumed by the
+//! compositor without anything reaching the launcher, so the first window
+//! of a launch is seen through the foreign-toplevel-list protocol instead:
+//! the compositor announces every new toplevel window to clients that bind
+//! it, including XWayland ones.
+
+use std::collections::HashMap;
+use std::os::fd::AsRawFd;
+use std::process::Child;
+use std::time::{Duration, Instant};
+use wayland_client::backend::ObjectId;
+use wayland_client::globals::{GlobalListContents, registry_queue_init};
+use wayland_client::protocol::wl_registry::WlRegistry;
+use wayland_client::{Connection, Dispatch, EventQueue, Proxy, QueueHandle, event_created_child};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1::{
+    self, ExtForeignToplevelHandleV1,
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_list_v1::{
+    self, ExtForeignToplevelListV1,
+};
+
+/// How often the launched process is checked while no window appears
+const POLL_INTERVAL_MS: i32 = 100;
+
+/// The first window opened after watching started
+#[derive(Debug, Clone)]
+pub struct FirstWindow {
+    pub opened: Instant,
+    pub app_id: Option<String>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Windows that were open before watching started are ignored
+    watching: bool,
+    /// New windows whose details haven't all arrived yet
+    pending: HashMap<ObjectId, FirstWindow>,
+    first: Option<FirstWindow>,
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtForeignToplevelListV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtForeignToplevelListV1,
+        event: ext_foreign_toplevel_list_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } = event
+            && state.watching
+        {
+            state.pending.insert(
+                toplevel.id(),
+                FirstWindow {
+                    opened: Instant::now(),
+                    app_id: None,
+                },
+            );
+        }
+    }
+
+    event_created_child!(State, ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ExtForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ExtForeignToplevelHandleV1,
+        event: ext_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                if let Some(window) = state.pending.get_mut(&handle.id()) {
+                    window.app_id = Some(app_id);
+                }
+            }
+            ext_foreign_toplevel_handle_v1::Event::Done => {
+                if let Some(window) = state.pending.remove(&handle.id())
+                    && state.first.is_none()
+                {
+                    state.first = Some(window);
+                }
+            }
+            ext_foreign_toplevel_handle_v1::Event::Closed => {
+                state.pending.remove(&handle.id());
+                handle.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A compositor connection told about new windows
+pub struct WindowWatcher {
+    connection: Connection,
+    queue: EventQueue<State>,
+    state: State,
+}
+
+/// Start watching for new windows
+///
+/// Returns `None` outside a Wayland session or when the compositor doesn't
+/// support the foreign-toplevel-list protocol.
+pub fn watch_windows() -> Option<WindowWatcher> {
+    let connection = Connection::connect_to_env().ok()?;
+    let (globals, mut queue) = registry_queue_init::<State>(&connection).ok()?;
+    let qh = queue.handle();
+    let _list: ExtForeignToplevelListV1 = globals.bind(&qh, 1..=1, ()).ok()?;
+
+    // The windows already open are announced first
+    let mut state = State::default();
+    queue.roundtrip(&mut state).ok()?;
+    state.watching = true;
+
+    Some(WindowWatcher {
+        connection,
+        queue,
+        state,
+    })
+}
+
+impl WindowWatcher {
+    /// Wait for a new window to open, for at most `timeout` if given
+    ///
+    /// Returns `None` if `child` exits first, the time runs out or the
+    /// compositor connection fails.
+    pub fn wait_for_window(
+        mut self,
+        child: &mut Child,
+        timeout: Option<Duration>,
+    ) -> Option<FirstWindow> {
+        let started = Instant::now();
+        loop {
+            self.queue.dispatch_pending(&mut self.state).ok()?;
+            if let Some(window) = self.state.first.take() {
+                return Some(window);
+            }
+            if !matches!(child.try_wait(), Ok(None))
+                || timeout.is_some_and(|timeout| started.elapsed() >= timeout)
+            {
+                return None;
+            }
+
+            self.connection.flush().ok()?;
+            let Some(guard) = self.queue.prepare_read() else {
+                continue;
+            };
+            let mut fds = libc::pollfd {
+                fd: guard.connection_fd().as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: poll reads one valid pollfd for at most the interval
+            let ready = unsafe { libc::poll(&mut fds, 1, POLL_INTERVAL_MS) };
+            if ready > 0 {
+                guard.read().ok()?;
+            }
+        }
+    }
+}
@@ -0,0 +1,484 @@
+//! Global voidbox configuration (config.toml in the data directory)
+
+use super::SettingsError;
+use crate::events::EventKind;
+use crate::storage::{parse_age, paths};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::time::Duration;
+
+/// Global configuration shared by all apps
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VoidboxConfig {
+    /// Webhooks fired on install/update/remove/failure events
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    /// Proxy settings for downloads
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    /// Download cache settings
+    #[serde(default)]
+    pub cache: CacheConfig,
+    /// Malware scanning of downloaded artifacts
+    #[serde(default)]
+    pub scan: ScanConfig,
+    /// Verification against the database of known-good checksums
+    #[serde(default)]
+    pub checksums: ChecksumConfig,
+    /// Manifest signature verification
+    #[serde(default)]
+    pub signatures: SignatureConfig,
+    /// First-run quarantine for new installs
+    #[serde(default)]
+    pub quarantine: QuarantineConfig,
+    /// Sharing the download cache with machines on the LAN
+    #[serde(default)]
+    pub lan_cache: LanCacheConfig,
+    /// URL rewrite rules redirecting downloads to internal mirrors
+    #[serde(default)]
+    pub mirrors: Vec<MirrorRule>,
+    /// Install pipeline settings
+    #[serde(default)]
+    pub install: InstallConfig,
+    /// Repository indexes (directories, index files or URLs) searched by app name
+    #[serde(default)]
+    pub repositories: Vec<String>,
+    /// How long fetched repository indexes are used before refetching
+    #[serde(default)]
+    pub repository_refresh: RepositoryRefreshConfig,
+    /// Metered connection handling
+    #[serde(default)]
+    pub network: NetworkConfig,
+    /// Apps' built-in updaters
+    #[serde(default)]
+    pub updaters: UpdaterConfig,
+    /// Apps' private /tmp
+    #[serde(default)]
+    pub tmp: TmpConfig,
+    /// App data snapshots
+    #[serde(default)]
+    pub snapshots: SnapshotConfig,
+    /// App data backups
+    #[serde(default)]
+    pub backup: BackupConfig,
+    /// Bundled libraries
+    #[serde(default)]
+    pub libraries: LibrariesConfig,
+    /// Removed apps kept for `voidbox undo-remove`
+    #[serde(default)]
+    pub removal: RemovalConfig,
+}
+
+/// Install pipeline configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallConfig {
+    /// Retries per pipeline step (resolve, download, verify, extract, integrate)
+    #[serde(default = "default_step_retries")]
+    pub step_retries: u32,
+    /// Globs of payload files not to install for any app
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Strip and recompress payloads of apps whose manifest doesn't say
+    #[serde(default)]
+    pub optimize: bool,
+}
+
+fn default_step_retries() -> u32 {
+    2
+}
+
+impl Default for InstallConfig {
+    fn default() -> Self {
+        Self {
+            step_retries: default_step_retries(),
+            exclude: Vec::new(),
+            optimize: false,
+        }
+    }
+}
+
+/// A URL rewrite rule: URLs matching `from` (a regex) are replaced by `to`,
+/// which may reference capture groups (`$1`, `${name}`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirrorRule {
+    pub from: String,
+    pub to: String,
+}
+
+/// LAN cache sharing configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanCacheConfig {
+    /// Try LAN peers before downloading from the internet
+    #[serde(default)]
+    pub enabled: bool,
+    /// Port used by `voidbox cache serve`
+    #[serde(default = "default_lan_cache_port")]
+    pub port: u16,
+}
+
+fn default_lan_cache_port() -> u16 {
+    7787
+}
+
+impl Default for LanCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: default_lan_cache_port(),
+        }
+    }
+}
+
+/// Private /tmp configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TmpConfig {
+    /// Give each app its own /tmp instead of the host's
+    #[serde(default = "default_true")]
+    pub private: bool,
+    /// Default size limit of an app's /tmp (e.g. "512M", "2G")
+    #[serde(default = "default_tmp_size")]
+    pub size: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_tmp_size() -> String {
+    "1G".to_string()
+}
+
+impl Default for TmpConfig {
+    fn default() -> Self {
+        Self {
+            private: true,
+            size: default_tmp_size(),
+        }
+    }
+}
+
+/// App data backup configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackupConfig {
+    /// Apps whose data is backed up
+    #[serde(default)]
+    pub apps: Vec<String>,
+    /// Default destination: a directory, or "restic:<repository>"
+    #[serde(default)]
+    pub to: Option<String>,
+    /// Password file for restic repositories (otherwise restic's own
+    /// environment variables are used)
+    #[serde(default)]
+    pub restic_password_file: Option<String>,
+}
+
+/// App data snapshot configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotConfig {
+    /// Snapshot an app's data before applying an update
+    #[serde(default)]
+    pub before_update: bool,
+    /// Snapshots kept per app; older ones are removed
+    #[serde(default = "default_snapshot_keep")]
+    pub keep: usize,
+}
+
+fn default_snapshot_keep() -> usize {
+    3
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            before_update: false,
+            keep: default_snapshot_keep(),
+        }
+    }
+}
+
+/// Configuration of removed apps kept for undoing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovalConfig {
+    /// How long removed apps are kept (e.g. "7d", "2w"; "0" deletes them
+    /// right away)
+    #[serde(default = "default_removal_keep")]
+    pub keep: String,
+}
+
+fn default_removal_keep() -> String {
+    "7d".to_string()
+}
+
+impl Default for RemovalConfig {
+    fn default() -> Self {
+        Self {
+            keep: default_removal_keep(),
+        }
+    }
+}
+
+/// Bundled library configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LibrariesConfig {
+    /// Prefer system copies of whitelisted libraries unless an app's
+    /// manifest says otherwise
+    #[serde(default)]
+    pub prefer_system: bool,
+}
+
+/// Built-in updater configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdaterConfig {
+    /// Block apps' built-in updaters unless their manifest says otherwise
+    #[serde(default)]
+    pub block: bool,
+}
+
+/// Quarantine configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuarantineConfig {
+    /// Run newly installed apps under the strict profile the first time
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// Scanner hook configuration (e.g. clamscan)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanConfig {
+    /// Scanner executable; scanning is disabled when unset
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Extra arguments passed before the artifact path
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Exit codes that mean "malware found" (clamscan uses 1)
+    #[serde(default = "default_detected_exit_codes")]
+    pub detected_exit_codes: Vec<i32>,
+}
+
+fn default_detected_exit_codes() -> Vec<i32> {
+    vec![1]
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            args: Vec::new(),
+            detected_exit_codes: default_detected_exit_codes(),
+        }
+    }
+}
+
+/// Checksum database configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChecksumConfig {
+    /// Locations publishing a `checksums.toml` (like repositories) that
+    /// vouches for downloads of any manifest; the databases of configured
+    /// repositories are used for their own manifests
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// minisign public key each location's database must be signed with,
+    /// by location; databases without one are ignored
+    #[serde(default)]
+    pub keys: BTreeMap<String, String>,
+    /// Refuse downloads the database has no checksum for
+    #[serde(default)]
+    pub require: bool,
+}
+
+/// Manifest signature configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SignatureConfig {
+    /// minisign public keys of trusted signers, by the name shown for them
+    #[serde(default)]
+    pub trusted_keys: BTreeMap<String, String>,
+    /// Refuse manifests not signed with a trusted key
+    #[serde(default)]
+    pub require: bool,
+}
+
+/// Repository index refresh configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepositoryRefreshConfig {
+    /// Age after which a remote repository's index is fetched again (e.g.
+    /// "6h", "1d"; "0" fetches it every time)
+    #[serde(default = "default_refresh_interval")]
+    pub interval: String,
+    /// Intervals for particular repositories, by location
+    #[serde(default)]
+    pub repositories: HashMap<String, String>,
+}
+
+fn default_refresh_interval() -> String {
+    "6h".to_string()
+}
+
+impl Default for RepositoryRefreshConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_refresh_interval(),
+            repositories: HashMap::new(),
+        }
+    }
+}
+
+impl RepositoryRefreshConfig {
+    /// Refresh interval of a repository
+    pub fn interval(&self, location: &str) -> Result<Duration, SettingsError> {
+        let interval = self.repositories.get(location).unwrap_or(&self.interval);
+        parse_age(interval).ok_or_else(|| {
+            SettingsError::InvalidValue(format!(
+                "refresh interval '{}' for {} (expected e.g. 6h, 1d)",
+                interval, location
+            ))
+        })
+    }
+}
+
+/// Network configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    /// Whether the connection is metered; when unset, NetworkManager is asked
+    #[serde(default)]
+    pub metered: Option<bool>,
+    /// Background downloads larger than this wait for an unmetered
+    /// connection (e.g. "50M")
+    #[serde(default = "default_metered_max_download")]
+    pub metered_max_download: String,
+}
+
+fn default_metered_max_download() -> String {
+    "50M".to_string()
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            metered: None,
+            metered_max_download: default_metered_max_download(),
+        }
+    }
+}
+
+/// Download cache configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// Maximum total size of cached downloads (e.g. "500M", "5G")
+    #[serde(default = "default_cache_max_size")]
+    pub max_size: String,
+    /// Age after which unused downloads are removed by `voidbox clean`
+    /// (e.g. "30d", "2w")
+    #[serde(default = "default_cache_max_age")]
+    pub max_age: String,
+}
+
+fn default_cache_max_size() -> String {
+    "2G".to_string()
+}
+
+fn default_cache_max_age() -> String {
+    "30d".to_string()
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_size: default_cache_max_size(),
+            max_age: default_cache_max_age(),
+        }
+    }
+}
+
+/// How the proxy for downloads is chosen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyMode {
+    /// Environment variables, then desktop settings (GNOME/KDE, PAC)
+    #[default]
+    Auto,
+    /// Use `url` or `pac_url` from this config
+    Manual,
+    /// Always connect directly
+    None,
+}
+
+/// Proxy configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    #[serde(default)]
+    pub mode: ProxyMode,
+    /// Proxy URL for manual mode (e.g. "http://proxy.corp:3128")
+    #[serde(default)]
+    pub url: Option<String>,
+    /// PAC script URL for manual mode
+    #[serde(default)]
+    pub pac_url: Option<String>,
+}
+
+/// A webhook target - either an HTTP URL (JSON POST) or a shell command
+/// that receives the JSON payload on stdin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Events to fire on (all events if empty)
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+    /// Number of retries after the first failed attempt
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+impl WebhookConfig {
+    /// Check whether this webhook wants a given event
+    pub fn wants(&self, kind: EventKind) -> bool {
+        kind == EventKind::Test || self.events.is_empty() || self.events.contains(&kind)
+    }
+
+    /// Short human-readable description of the target
+    pub fn target(&self) -> String {
+        match (&self.url, &self.command) {
+            (Some(url), _) => url.clone(),
+            (None, Some(cmd)) => format!("command: {}", cmd),
+            (None, None) => "(no url or command)".to_string(),
+        }
+    }
+}
+
+/// Load the global config (defaults if no config file exists)
+pub fn load_config() -> Result<VoidboxConfig, SettingsError> {
+    let config_path = paths::config_path();
+
+    if !config_path.exists() {
+        return Ok(VoidboxConfig::default());
+    }
+
+    let content = fs::read_to_string(config_path)?;
+    let config: VoidboxConfig = toml::from_str(&content)?;
+
+    Ok(config)
+}
+
+/// Save the global config
+pub fn save_config(config: &VoidboxConfig) -> Result<(), SettingsError> {
+    let config_path = paths::config_path();
+
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let content =
+        toml::to_string_pretty(config).map_err(|e| SettingsError::SaveError(e.to_string()))?;
+
+    fs::write(config_path, content)?;
+
+    Ok(())
+}
@@ -1,12 +1,32 @@
 //! Default settings and permission management
 
-use crate::manifest::PermissionConfig;
+use crate::manifest::{AppManifest, ClipboardAccess, PermissionConfig};
 
 /// Get default permissions (all open by default)
 pub fn default_permissions() -> PermissionConfig {
     PermissionConfig::default()
 }
 
+/// Get the strictest sandbox profile (every permission withheld)
+pub fn strict_permissions() -> PermissionConfig {
+    PermissionConfig {
+        network: false,
+        audio: false,
+        microphone: false,
+        gpu: false,
+        camera: false,
+        home: false,
+        downloads: false,
+        removable_media: false,
+        dev_mode: false,
+        fonts: false,
+        themes: false,
+        native_mode: false,
+        clipboard: ClipboardAccess::None,
+        session_bus: false,
+    }
+}
+
 /// Merge manifest permissions with user overrides
 pub fn merge_permissions(
     manifest: &PermissionConfig,
@@ -26,7 +46,116 @@ pub fn merge_permissions(
             fonts: ov.fonts,
             themes: ov.themes,
             native_mode: ov.native_mode,
+            clipboard: ov.clipboard,
+            session_bus: ov.session_bus,
         },
         None => manifest.clone(),
     }
 }
+
+/// Sandbox preset applied when a manifest declares no permissions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionPreset {
+    Browser,
+    Editor,
+    Game,
+    Media,
+    /// Apps whose categories match no other preset
+    Minimal,
+}
+
+impl PermissionPreset {
+    /// Pick the preset for a set of desktop categories
+    pub fn for_categories(categories: &[String]) -> Self {
+        let has = |names: &[&str]| categories.iter().any(|c| names.contains(&c.as_str()));
+        if has(&["WebBrowser", "Network"]) {
+            PermissionPreset::Browser
+        } else if has(&["TextEditor", "Development", "IDE", "Office"]) {
+            PermissionPreset::Editor
+        } else if has(&["Game"]) {
+            PermissionPreset::Game
+        } else if has(&["AudioVideo", "Audio", "Video", "Player", "Graphics"]) {
+            PermissionPreset::Media
+        } else {
+            PermissionPreset::Minimal
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PermissionPreset::Browser => "browser",
+            PermissionPreset::Editor => "editor",
+            PermissionPreset::Game => "game",
+            PermissionPreset::Media => "media",
+            PermissionPreset::Minimal => "minimal",
+        }
+    }
+
+    /// Permissions granted by the preset
+    pub fn permissions(&self) -> PermissionConfig {
+        let base = PermissionConfig {
+            fonts: true,
+            themes: true,
+            clipboard: ClipboardAccess::Read,
+            session_bus: true,
+            ..strict_permissions()
+        };
+        match self {
+            PermissionPreset::Browser => PermissionConfig {
+                network: true,
+                audio: true,
+                microphone: true,
+                gpu: true,
+                downloads: true,
+                clipboard: ClipboardAccess::Write,
+                ..base
+            },
+            PermissionPreset::Editor => PermissionConfig {
+                gpu: true,
+                home: true,
+                downloads: true,
+                clipboard: ClipboardAccess::Write,
+                ..base
+            },
+            PermissionPreset::Game => PermissionConfig {
+                network: true,
+                audio: true,
+                gpu: true,
+                ..base
+            },
+            PermissionPreset::Media => PermissionConfig {
+                network: true,
+                audio: true,
+                gpu: true,
+                downloads: true,
+                removable_media: true,
+                ..base
+            },
+            PermissionPreset::Minimal => base,
+        }
+    }
+}
+
+impl std::fmt::Display for PermissionPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The preset in effect for a manifest, if it declares no permissions
+pub fn permission_preset(manifest: &AppManifest) -> Option<PermissionPreset> {
+    match manifest.permissions {
+        Some(_) => None,
+        None => Some(PermissionPreset::for_categories(
+            &manifest.desktop.categories,
+        )),
+    }
+}
+
+/// Permissions a manifest grants: its own, or its category's preset
+pub fn manifest_permissions(manifest: &AppManifest) -> PermissionConfig {
+    match &manifest.permissions {
+        Some(permissions) => permissions.clone(),
+        None => PermissionPreset::for_categories(&manifest.desktop.categories).permissions(),
+    }
+}
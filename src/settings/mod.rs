@@ -1,7 +1,9 @@
 //! Settings and permission management
 
+mod config;
 mod defaults;
 mod overrides;
 
+pub use config::*;
 pub use defaults::*;
 pub use overrides::*;
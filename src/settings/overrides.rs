@@ -1,7 +1,8 @@
-//! User permission overrides
+//! User permission and environment overrides
 
 use crate::manifest::PermissionConfig;
 use crate::storage::paths;
+use std::collections::BTreeMap;
 use std::fs;
 use thiserror::Error;
 
@@ -15,6 +16,9 @@ pub enum SettingsError {
 
     #[error("Failed to save settings: {0}")]
     SaveError(String),
+
+    #[error("Invalid setting: {0}")]
+    InvalidValue(String),
 }
 
 /// Load user settings overrides for an app
@@ -47,6 +51,57 @@ pub fn save_overrides(app_name: &str, settings: &PermissionConfig) -> Result<(),
     Ok(())
 }
 
+/// Load user environment overrides for an app
+pub fn load_env_overrides(app_name: &str) -> Result<BTreeMap<String, String>, SettingsError> {
+    let env_path = paths::app_env_path(app_name);
+
+    if !env_path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let content = fs::read_to_string(env_path)?;
+    Ok(toml::from_str(&content)?)
+}
+
+/// Save user environment overrides for an app (removed when empty)
+pub fn save_env_overrides(
+    app_name: &str,
+    env: &BTreeMap<String, String>,
+) -> Result<(), SettingsError> {
+    let env_path = paths::app_env_path(app_name);
+
+    if env.is_empty() {
+        if env_path.exists() {
+            fs::remove_file(env_path)?;
+        }
+        return Ok(());
+    }
+
+    if let Some(parent) = env_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let content =
+        toml::to_string_pretty(env).map_err(|e| SettingsError::SaveError(e.to_string()))?;
+
+    fs::write(env_path, content)?;
+
+    Ok(())
+}
+
+/// Publish an app's current permissions to its running container
+pub fn write_live_permissions(
+    app_name: &str,
+    permissions: &PermissionConfig,
+) -> Result<(), SettingsError> {
+    let live_dir = paths::app_live_dir(app_name);
+    fs::create_dir_all(&live_dir)?;
+    let content =
+        serde_json::to_string(permissions).map_err(|e| SettingsError::SaveError(e.to_string()))?;
+    fs::write(live_dir.join(paths::LIVE_PERMISSIONS_FILE), content)?;
+    Ok(())
+}
+
 /// Remove settings overrides for an app
 pub fn remove_overrides(app_name: &str) -> Result<(), SettingsError> {
     let settings_path = paths::app_settings_path(app_name);
@@ -55,3 +110,8 @@ pub fn remove_overrides(app_name: &str) -> Result<(), SettingsError> {
     }
     Ok(())
 }
+
+/// Remove environment overrides for an app
+pub fn remove_env_overrides(app_name: &str) -> Result<(), SettingsError> {
+    save_env_overrides(app_name, &BTreeMap::new())
+}
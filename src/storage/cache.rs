@@ -0,0 +1,469 @@
+//! Download cache with per-entry integrity metadata
+//!
+//! Each cached download is stored as `<key>` next to a `<key>.json` journal
+//! entry recording where it came from, its SHA-256, size, fetch time and
+//! HTTP validators. Entries are revalidated with conditional requests and
+//! can be re-hashed with `voidbox cache verify`. The total size is bounded
+//! by least-recently-used eviction (`[cache] max_size` in config).
+
+use super::download::{
+    DownloadError, DownloadOutcome, Validators, download_conditional, partial_download, sha256_file,
+};
+use super::lan::fetch_from_peers;
+use super::paths;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CacheError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Invalid cache entry: {0}")]
+    InvalidEntry(String),
+
+    #[error("Config error: {0}")]
+    ConfigError(#[from] crate::settings::SettingsError),
+
+    #[error("Invalid size: {0}")]
+    InvalidSize(String),
+}
+
+/// Journal entry stored alongside each cached download
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub url: String,
+    pub sha256: String,
+    pub size: u64,
+    pub fetched: String,
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    /// Last time the entry was used by an install (defaults to `fetched`)
+    #[serde(default)]
+    pub last_used: Option<String>,
+}
+
+impl CacheEntry {
+    fn validators(&self) -> Validators {
+        Validators {
+            etag: self.etag.clone(),
+            last_modified: self.last_modified.clone(),
+        }
+    }
+
+    fn last_used_time(&self) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+        let stamp = self.last_used.as_deref().unwrap_or(&self.fetched);
+        chrono::DateTime::parse_from_rfc3339(stamp).ok()
+    }
+}
+
+/// Outcome of verifying the cache
+#[derive(Debug, Default)]
+pub struct CacheVerifyReport {
+    pub checked: usize,
+    pub ok: usize,
+    /// URLs of entries evicted because their content no longer matches
+    pub evicted: Vec<String>,
+    /// Files without a journal entry (interrupted downloads etc.)
+    pub orphans_removed: usize,
+}
+
+/// Outcome of pruning the cache
+#[derive(Debug, Default)]
+pub struct CachePruneReport {
+    /// URLs of evicted entries
+    pub evicted: Vec<String>,
+    pub freed: u64,
+    pub remaining: u64,
+}
+
+/// Parse a human-readable size such as "500M", "5G" or "1.5GiB" into bytes
+pub fn parse_size(input: &str) -> Option<u64> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let unit = unit.trim().to_ascii_uppercase();
+    let unit = unit.trim_end_matches("IB").trim_end_matches('B');
+    let multiplier: u64 = match unit {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return None,
+    };
+
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Parse an age such as "30d", "2w" or "12h" (a bare number is days)
+pub fn parse_age(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: u64 = number.parse().ok()?;
+
+    let seconds: u64 = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "" | "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    Some(Duration::from_secs(number.checked_mul(seconds)?))
+}
+
+/// Cache key for a URL
+fn cache_key(url: &str) -> String {
+    let digest = Sha256::digest(url.as_bytes());
+    hex::encode(&digest[..16])
+}
+
+fn entry_path(key: &str) -> PathBuf {
+    paths::download_cache_dir().join(format!("{}.json", key))
+}
+
+fn blob_path(key: &str) -> PathBuf {
+    paths::download_cache_dir().join(key)
+}
+
+fn read_entry(path: &Path) -> Result<CacheEntry, CacheError> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content).map_err(|e| CacheError::InvalidEntry(e.to_string()))
+}
+
+fn write_entry(path: &Path, entry: &CacheEntry) -> Result<(), CacheError> {
+    let content =
+        serde_json::to_string_pretty(entry).map_err(|e| CacheError::InvalidEntry(e.to_string()))?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// Download a URL through the cache, returning the path of the cached file
+///
+/// An existing entry is revalidated with a conditional request; if the
+/// server is unreachable the cached copy is used as-is. LAN peers are only
+/// asked when the download's SHA-256 is known from a trusted source.
+pub fn cached_download(
+    url: &str,
+    sha256: Option<&str>,
+    show_progress: bool,
+) -> Result<PathBuf, DownloadError> {
+    let dir = paths::download_cache_dir();
+    fs::create_dir_all(&dir)?;
+
+    let key = cache_key(url);
+    let blob = blob_path(&key);
+    let meta = entry_path(&key);
+    let part = dir.join(format!("{}.part", key));
+
+    // A journal entry whose file is missing or truncated is ignored
+    let existing = read_entry(&meta).ok().filter(|e| {
+        fs::metadata(&blob)
+            .map(|m| m.len() == e.size)
+            .unwrap_or(false)
+    });
+    let validators = existing
+        .as_ref()
+        .map(|e| e.validators())
+        .filter(|v| !v.is_empty());
+
+    // Cache miss: a LAN peer may have a copy with the trusted hash (unless
+    // an interrupted download can be continued)
+    if existing.is_none()
+        && let Some(sha256) = sha256
+        && partial_download(url, &part).is_none()
+        && lan_cache_enabled()
+        && let Some(mut entry) = fetch_from_peers(url, &key, sha256, &part)
+    {
+        fs::rename(&part, &blob)?;
+        entry.fetched = chrono::Local::now().to_rfc3339();
+        entry.last_used = None;
+        write_entry(&meta, &entry).map_err(|e| DownloadError::Failed(e.to_string()))?;
+        return Ok(blob);
+    }
+
+    let outcome = match download_conditional(url, &part, show_progress, validators.as_ref()) {
+        Ok(outcome) => outcome,
+        Err(e) if existing.is_some() => {
+            fs::remove_file(&part).ok();
+            say!(
+                "[voidbox] Warning: Could not revalidate cached download ({}), using cached copy",
+                e
+            );
+            touch_entry(&meta);
+            return Ok(blob);
+        }
+        // Keep what was fetched so the next attempt can resume it
+        Err(e) => return Err(e),
+    };
+
+    match outcome {
+        DownloadOutcome::NotModified => {
+            say!("[voidbox] Using cached download");
+            touch_entry(&meta);
+        }
+        DownloadOutcome::Downloaded {
+            sha256,
+            size,
+            validators,
+        } => {
+            fs::rename(&part, &blob)?;
+            let entry = CacheEntry {
+                url: url.to_string(),
+                sha256,
+                size,
+                fetched: chrono::Local::now().to_rfc3339(),
+                etag: validators.etag,
+                last_modified: validators.last_modified,
+                last_used: None,
+            };
+            write_entry(&meta, &entry).map_err(|e| DownloadError::Failed(e.to_string()))?;
+        }
+    }
+
+    Ok(blob)
+}
+
+fn lan_cache_enabled() -> bool {
+    crate::settings::load_config().is_ok_and(|c| c.lan_cache.enabled)
+}
+
+/// Record that a cache entry was just used
+fn touch_entry(meta: &Path) {
+    if let Ok(mut entry) = read_entry(meta) {
+        entry.last_used = Some(chrono::Local::now().to_rfc3339());
+        write_entry(meta, &entry).ok();
+    }
+}
+
+/// Bytes already fetched of an interrupted download of a URL, with the full
+/// size if known
+pub fn partial_cached_download(url: &str) -> Option<(u64, Option<u64>)> {
+    let part = paths::download_cache_dir().join(format!("{}.part", cache_key(url)));
+    partial_download(url, &part).map(|(partial, downloaded)| (downloaded, partial.total))
+}
+
+/// Size of the cached copy of a URL, if the cache has a complete one
+pub fn cached_size(url: &str) -> Option<u64> {
+    let key = cache_key(url);
+    let entry = read_entry(&entry_path(&key)).ok()?;
+    let len = fs::metadata(blob_path(&key)).ok()?.len();
+    (len == entry.size).then_some(len)
+}
+
+/// Drop the cached copy of a URL, if any
+pub fn remove_cached(url: &str) {
+    let key = cache_key(url);
+    fs::remove_file(blob_path(&key)).ok();
+    fs::remove_file(entry_path(&key)).ok();
+}
+
+/// List all journal entries in the cache with their file paths
+pub fn cache_entries() -> Result<Vec<(PathBuf, CacheEntry)>, CacheError> {
+    let dir = paths::download_cache_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for item in fs::read_dir(&dir)? {
+        let path = item?.path();
+        if path.extension().is_some_and(|e| e == "json")
+            && let Ok(entry) = read_entry(&path)
+        {
+            entries.push((path.with_extension(""), entry));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Re-hash every cache entry, evicting corrupt entries and orphaned files
+pub fn verify_cache() -> Result<CacheVerifyReport, CacheError> {
+    let mut report = CacheVerifyReport::default();
+    let dir = paths::download_cache_dir();
+    if !dir.exists() {
+        return Ok(report);
+    }
+
+    let mut valid_blobs = Vec::new();
+    for item in fs::read_dir(&dir)? {
+        let path = item?.path();
+        if path.extension().is_none_or(|e| e != "json") {
+            continue;
+        }
+
+        report.checked += 1;
+        let blob = path.with_extension("");
+
+        let entry = match read_entry(&path) {
+            Ok(entry) => entry,
+            Err(_) => {
+                fs::remove_file(&path)?;
+                if blob.exists() {
+                    fs::remove_file(&blob)?;
+                }
+                report.evicted.push(blob.display().to_string());
+                continue;
+            }
+        };
+
+        let intact = blob.exists() && sha256_file(&blob)? == entry.sha256;
+        if intact {
+            report.ok += 1;
+            valid_blobs.push(blob);
+        } else {
+            fs::remove_file(&path)?;
+            if blob.exists() {
+                fs::remove_file(&blob)?;
+            }
+            report.evicted.push(entry.url);
+        }
+    }
+
+    // Anything left without a journal entry is an orphan
+    for item in fs::read_dir(&dir)? {
+        let path = item?.path();
+        if path.extension().is_some_and(|e| e == "json") || valid_blobs.contains(&path) {
+            continue;
+        }
+        if path.is_file() {
+            fs::remove_file(&path)?;
+            report.orphans_removed += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Evict least-recently-used entries until the cache fits in `max_size` bytes
+pub fn prune_cache(max_size: u64) -> Result<CachePruneReport, CacheError> {
+    let mut entries = cache_entries()?;
+    let mut total: u64 = entries.iter().map(|(_, e)| e.size).sum();
+    let mut report = CachePruneReport::default();
+
+    // Oldest first; entries with unreadable timestamps go first
+    entries.sort_by_key(|(_, e)| e.last_used_time());
+
+    for (blob, entry) in entries {
+        if total <= max_size {
+            break;
+        }
+        if blob.exists() {
+            fs::remove_file(&blob)?;
+        }
+        fs::remove_file(blob.with_extension("json"))?;
+        total -= entry.size;
+        report.freed += entry.size;
+        report.evicted.push(entry.url);
+    }
+
+    report.remaining = total;
+    Ok(report)
+}
+
+/// Result of removing old cache entries
+#[derive(Debug, Default)]
+pub struct CacheCleanReport {
+    /// URLs of entries that weren't used within the age limit
+    pub evicted: Vec<String>,
+    /// Interrupted downloads that weren't resumed within the age limit
+    pub partials: usize,
+    pub freed: u64,
+}
+
+fn modified_before(path: &Path, cutoff: SystemTime) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .is_ok_and(|modified| modified < cutoff)
+}
+
+/// Remove entries not used for `max_age` and interrupted downloads not
+/// touched for as long
+pub fn clean_cache(max_age: Duration) -> Result<CacheCleanReport, CacheError> {
+    let mut report = CacheCleanReport::default();
+    let dir = paths::download_cache_dir();
+    if !dir.exists() {
+        return Ok(report);
+    }
+    let cutoff = SystemTime::now()
+        .checked_sub(max_age)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let cutoff_time: chrono::DateTime<chrono::Utc> = cutoff.into();
+
+    for (blob, entry) in cache_entries()? {
+        // Entries with unreadable timestamps count as old
+        let old = entry.last_used_time().is_none_or(|used| used < cutoff_time);
+        if !old {
+            continue;
+        }
+        if blob.exists() {
+            fs::remove_file(&blob)?;
+        }
+        fs::remove_file(blob.with_extension("json"))?;
+        report.freed += entry.size;
+        report.evicted.push(entry.url);
+    }
+
+    for item in fs::read_dir(&dir)? {
+        let path = item?.path();
+        let name = path.to_string_lossy();
+        let partial = name.ends_with(".part") || name.ends_with(".part.resume");
+        if partial && modified_before(&path, cutoff) {
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            fs::remove_file(&path)?;
+            report.freed += size;
+            if name.ends_with(".part") {
+                report.partials += 1;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Apply the configured cache size limit, warning instead of failing
+pub fn enforce_cache_limit() {
+    let config = match crate::settings::load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            say!("[voidbox] Warning: Could not load config: {}", e);
+            return;
+        }
+    };
+
+    let Some(max_size) = parse_size(&config.cache.max_size) else {
+        say!(
+            "[voidbox] Warning: Invalid cache max_size '{}'",
+            config.cache.max_size
+        );
+        return;
+    };
+
+    match prune_cache(max_size) {
+        Ok(report) if !report.evicted.is_empty() => say!(
+            "[voidbox] Evicted {} cached download(s), freed {}",
+            report.evicted.len(),
+            indicatif::HumanBytes(report.freed)
+        ),
+        Ok(_) => {}
+        Err(e) => say!("[voidbox] Warning: Could not prune cache: {}", e),
+    }
+}
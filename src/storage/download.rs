@@ -1,11 +1,62 @@
 //! File download utilities
+//!
+//! Downloads survive flaky connections and system suspend: a transfer that
+//! stops delivering data, or was running when the system was suspended, is
+//! dropped and reconnected, continuing from the bytes already written with
+//! a range request. The partial file and what is needed to continue it stay
+//! on disk, so a download interrupted by a reboot continues on the next
+//! attempt.
 
-use indicatif::{ProgressBar, ProgressStyle};
-use std::fs::File;
+use super::{http_get, http_head};
+use crate::events::{Event, emit};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{RecvTimeoutError, sync_channel};
+use std::thread;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+/// Time allowed for connecting and for the response headers
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A transfer delivering no data for this long is reconnected
+const STALL_TIMEOUT: Duration = if cfg!(test) {
+    Duration::from_millis(500)
+} else {
+    Duration::from_secs(60)
+};
+
+/// After a suspend the connection is usually dead; wait this long for data
+/// before reconnecting
+const RESUME_STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a waiting transfer checks for stalls and suspend
+const POLL_INTERVAL: Duration = if cfg!(test) {
+    Duration::from_millis(100)
+} else {
+    Duration::from_secs(2)
+};
+
+/// Waits before each reconnect; the network may take a while to come back
+/// after a resume. Progress between interruptions starts over.
+const RECONNECT_DELAYS: &[u64] = if cfg!(test) {
+    &[0; 5]
+} else {
+    &[5, 10, 20, 30, 60]
+};
+
+/// Largest file downloaded, counting the parts of a resumed download
+/// together
+const MAX_DOWNLOAD_SIZE: u64 = if cfg!(test) { 64 * 1024 } else { 1_000_000_000 };
+
+/// Chunks read ahead of the writer
+const CHUNKS_IN_FLIGHT: usize = 64;
+
 #[derive(Error, Debug)]
 pub enum DownloadError {
     #[error("HTTP request failed: {0}")]
@@ -16,62 +67,385 @@ pub enum DownloadError {
 
     #[error("Download failed: {0}")]
     Failed(String),
+
+    #[error("Download interrupted: {0}")]
+    Interrupted(String),
+}
+
+/// HTTP validators used for conditional requests
+#[derive(Debug, Clone, Default)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// Result of a conditional download
+#[derive(Debug, Clone)]
+pub enum DownloadOutcome {
+    /// Server reported the resource unchanged (304) - nothing was written
+    NotModified,
+    /// File was downloaded to the destination
+    Downloaded {
+        sha256: String,
+        size: u64,
+        validators: Validators,
+    },
+}
+
+/// What is known about a partially downloaded file, stored next to it so an
+/// interrupted transfer can continue with a range request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartialDownload {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Full size of the file, if the server reported it
+    pub total: Option<u64>,
+}
+
+impl PartialDownload {
+    /// Validator for If-Range (weak ETags can't be used there)
+    fn if_range(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|e| !e.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+}
+
+fn partial_info_path(dest: &Path) -> PathBuf {
+    let mut name = dest.as_os_str().to_owned();
+    name.push(".resume");
+    PathBuf::from(name)
+}
+
+/// A partial download of `url` at `dest` that can be resumed, with the
+/// number of bytes already on disk
+pub fn partial_download(url: &str, dest: &Path) -> Option<(PartialDownload, u64)> {
+    let downloaded = fs::metadata(dest).ok()?.len();
+    let content = fs::read_to_string(partial_info_path(dest)).ok()?;
+    let partial: PartialDownload = serde_json::from_str(&content).ok()?;
+    (partial.url == url && downloaded > 0 && partial.if_range().is_some())
+        .then_some((partial, downloaded))
 }
 
 /// Download a file with progress bar
 pub fn download_file(url: &str, dest: &Path, show_progress: bool) -> Result<(), DownloadError> {
-    let mut resp = ureq::get(url)
-        .header("User-Agent", crate::APP_NAME)
-        .call()
-        .map_err(|e| DownloadError::HttpError(e.to_string()))?;
+    download_conditional(url, dest, show_progress, None).map(|_| ())
+}
 
-    let total_size = resp
-        .headers()
-        .get("Content-Length")
-        .and_then(|v| v.to_str().ok())
+/// Download a file, skipping the transfer if the validators still match
+///
+/// If an earlier attempt left part of the file at `dest`, the rest is
+/// requested with a range request; the partial file is kept when the
+/// transfer fails so the next attempt can continue it. An interrupted
+/// transfer is reconnected and continued.
+pub fn download_conditional(
+    url: &str,
+    dest: &Path,
+    show_progress: bool,
+    validators: Option<&Validators>,
+) -> Result<DownloadOutcome, DownloadError> {
+    let mut reconnects = 0;
+    let mut written = 0;
+    loop {
+        match download_attempt(url, dest, show_progress, validators) {
+            Err(DownloadError::Interrupted(reason))
+                if reconnects < RECONNECT_DELAYS.len() && partial_download(url, dest).is_some() =>
+            {
+                let now_written = fs::metadata(dest).map(|m| m.len()).unwrap_or(0);
+                if now_written > written {
+                    reconnects = 0;
+                    written = now_written;
+                }
+                let delay = RECONNECT_DELAYS[reconnects];
+                reconnects += 1;
+                say!(
+                    "[voidbox] Download interrupted ({}); reconnecting in {}s...",
+                    reason,
+                    delay
+                );
+                thread::sleep(Duration::from_secs(delay));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Time the system has spent suspended since boot
+///
+/// CLOCK_BOOTTIME counts suspended time and CLOCK_MONOTONIC doesn't.
+fn suspended_time() -> Duration {
+    let read = |clock| {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: clock_gettime only writes into the timespec
+        unsafe { libc::clock_gettime(clock, &mut ts) };
+        Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+    };
+    read(libc::CLOCK_BOOTTIME).saturating_sub(read(libc::CLOCK_MONOTONIC))
+}
+
+/// Errors that mean the connection went away rather than that the request
+/// is wrong
+fn is_connection_error(error: &ureq::Error) -> bool {
+    matches!(
+        error,
+        ureq::Error::Io(_)
+            | ureq::Error::Timeout(_)
+            | ureq::Error::HostNotFound
+            | ureq::Error::ConnectionFailed
+    )
+}
+
+/// One request for the file, continuing a partial download if there is one
+fn download_attempt(
+    url: &str,
+    dest: &Path,
+    show_progress: bool,
+    validators: Option<&Validators>,
+) -> Result<DownloadOutcome, DownloadError> {
+    let resume = partial_download(url, dest);
+
+    let mut req = http_get(url)
+        .config()
+        .timeout_connect(Some(CONNECT_TIMEOUT))
+        .timeout_recv_response(Some(RESPONSE_TIMEOUT))
+        .build();
+    if let Some((partial, offset)) = &resume {
+        req = req
+            .header("Range", &format!("bytes={}-", offset))
+            .header("If-Range", partial.if_range().unwrap_or_default());
+    } else if let Some(v) = validators {
+        if let Some(etag) = &v.etag {
+            req = req.header("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &v.last_modified {
+            req = req.header("If-Modified-Since", last_modified);
+        }
+    }
+
+    let resp = match req.call() {
+        Ok(resp) => resp,
+        // The partial file doesn't fit what the server has; start over
+        Err(ureq::Error::StatusCode(416)) if resume.is_some() => {
+            fs::remove_file(dest).ok();
+            fs::remove_file(partial_info_path(dest)).ok();
+            return download_attempt(url, dest, show_progress, validators);
+        }
+        // Worth reconnecting while there is a partial file to continue
+        Err(e) if resume.is_some() && is_connection_error(&e) => {
+            return Err(DownloadError::Interrupted(e.to_string()));
+        }
+        Err(e) => return Err(DownloadError::HttpError(e.to_string())),
+    };
+
+    if resp.status() == 304 {
+        return Ok(DownloadOutcome::NotModified);
+    }
+
+    let header = |name: &str| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+    };
+    let new_validators = Validators {
+        etag: header("ETag"),
+        last_modified: header("Last-Modified"),
+    };
+
+    // 206 continues the partial file; a 200 means the server sent it whole
+    let offset = match &resume {
+        Some((_, offset)) if resp.status() == 206 => *offset,
+        _ => 0,
+    };
+
+    let total_size = header("Content-Length")
         .and_then(|s| s.parse::<u64>().ok())
+        .map(|len| len + offset)
         .unwrap_or(0);
+    if total_size > MAX_DOWNLOAD_SIZE {
+        return Err(too_large(dest));
+    }
 
-    let pb = if show_progress && total_size > 0 {
+    let mut hasher = Sha256::new();
+    let mut out = if offset > 0 {
+        say!(
+            "[voidbox] Resuming download at {} of {}",
+            HumanBytes(offset),
+            HumanBytes(total_size)
+        );
+        let mut existing = File::open(dest)?;
+        std::io::copy(&mut existing, &mut hasher)?;
+        OpenOptions::new().append(true).open(dest)?
+    } else {
+        File::create(dest)?
+    };
+
+    let partial = PartialDownload {
+        url: url.to_string(),
+        etag: new_validators.etag.clone(),
+        last_modified: new_validators.last_modified.clone(),
+        total: (total_size > 0).then_some(total_size),
+    };
+    if let Ok(content) = serde_json::to_string(&partial) {
+        fs::write(partial_info_path(dest), content)?;
+    }
+
+    // Progress bars redraw in place, which screen readers read as noise
+    let pb = if show_progress && total_size > 0 && !crate::is_plain() {
         let pb = ProgressBar::new(total_size);
-        pb.set_style(ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-            .unwrap()
-            .progress_chars("#>-"));
+        let template = if crate::color_enabled() {
+            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})"
+        } else {
+            "{spinner} [{elapsed_precise}] [{bar:40}] {bytes}/{total_bytes} ({eta})"
+        };
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template(template)
+                .unwrap()
+                .progress_chars("#>-"),
+        );
+        pb.set_position(offset);
         Some(pb)
     } else {
         None
     };
 
-    let mut out = File::create(dest)?;
-    let mut reader = resp.body_mut().with_config().limit(1_000_000_000).reader();
-    let mut buffer = vec![0u8; 8192];
-    let mut downloaded = 0u64;
+    // Read on a separate thread so a connection that stopped delivering
+    // (e.g. across a suspend) can be given up on; a reader still blocked
+    // then ends when its connection finally fails
+    let mut reader = resp.into_body().into_reader();
+    let (sender, receiver) = sync_channel::<std::io::Result<Vec<u8>>>(CHUNKS_IN_FLIGHT);
+    thread::spawn(move || {
+        let mut buffer = vec![0u8; 8192];
+        loop {
+            let chunk = reader.read(&mut buffer).map(|n| buffer[..n].to_vec());
+            let last = !matches!(&chunk, Ok(c) if !c.is_empty());
+            if sender.send(chunk).is_err() || last {
+                break;
+            }
+        }
+    });
+    let mut downloaded = offset;
+
+    // Progress events at most every percent (or 256 KiB without a size)
+    let total = (total_size > 0).then_some(total_size);
+    let event_step = (total_size / 100).max(256 * 1024);
+    let mut next_event = downloaded;
+    let progress = |downloaded| {
+        emit(Event::DownloadProgress {
+            url: url.to_string(),
+            downloaded,
+            total,
+        })
+    };
 
+    let mut suspended = suspended_time();
+    let mut resumed = false;
+    let mut deadline = Instant::now() + STALL_TIMEOUT;
     loop {
-        let n = reader.read(&mut buffer)?;
-        if n == 0 {
-            break;
+        let chunk = match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(chunk)) if chunk.is_empty() => break,
+            Ok(Ok(chunk)) => chunk,
+            Ok(Err(e)) => return Err(DownloadError::Interrupted(e.to_string())),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(DownloadError::Interrupted("connection closed".into()));
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                if suspended_time() > suspended + POLL_INTERVAL {
+                    suspended = suspended_time();
+                    resumed = true;
+                    deadline = deadline.min(Instant::now() + RESUME_STALL_TIMEOUT);
+                }
+                if Instant::now() >= deadline {
+                    return Err(DownloadError::Interrupted(if resumed {
+                        "connection lost during suspend".to_string()
+                    } else {
+                        format!("no data received for {}s", STALL_TIMEOUT.as_secs())
+                    }));
+                }
+                continue;
+            }
+        };
+        resumed = false;
+        deadline = Instant::now() + STALL_TIMEOUT;
+
+        downloaded += chunk.len() as u64;
+        if downloaded > MAX_DOWNLOAD_SIZE {
+            drop(out);
+            return Err(too_large(dest));
         }
-        out.write_all(&buffer[..n])?;
-        downloaded += n as u64;
+        out.write_all(&chunk)?;
+        hasher.update(&chunk);
 
         if let Some(ref pb) = pb {
             pb.set_position(downloaded);
         }
+        if downloaded >= next_event {
+            progress(downloaded);
+            next_event = downloaded + event_step;
+        }
+    }
+    progress(downloaded);
+
+    if total_size > 0 && downloaded < total_size {
+        return Err(DownloadError::Interrupted(format!(
+            "connection closed after {} of {}",
+            HumanBytes(downloaded),
+            HumanBytes(total_size)
+        )));
     }
 
     if let Some(pb) = pb {
         pb.finish_with_message("Download complete");
     }
 
-    Ok(())
+    fs::remove_file(partial_info_path(dest)).ok();
+
+    Ok(DownloadOutcome::Downloaded {
+        sha256: hex::encode(hasher.finalize()),
+        size: downloaded,
+        validators: new_validators,
+    })
+}
+
+/// Give up on a download over the size limit, without leaving anything to
+/// resume
+fn too_large(dest: &Path) -> DownloadError {
+    fs::remove_file(dest).ok();
+    fs::remove_file(partial_info_path(dest)).ok();
+    DownloadError::Failed(format!(
+        "file is larger than {}",
+        HumanBytes(MAX_DOWNLOAD_SIZE)
+    ))
+}
+
+/// Ask the server for the size of a download without fetching it
+pub fn remote_size(url: &str) -> Option<u64> {
+    http_head(url)
+        .call()
+        .ok()?
+        .headers()
+        .get("Content-Length")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
 }
 
 /// Download content to string
 pub fn download_string(url: &str) -> Result<String, DownloadError> {
-    let mut resp = ureq::get(url)
-        .header("User-Agent", crate::APP_NAME)
+    let mut resp = http_get(url)
         .call()
         .map_err(|e| DownloadError::HttpError(e.to_string()))?;
 
@@ -82,3 +456,191 @@ pub fn download_string(url: &str) -> Result<String, DownloadError> {
 
     Ok(content)
 }
+
+/// Compute the SHA-256 of a file as lowercase hex
+pub fn sha256_file(path: &Path) -> Result<String, std::io::Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 65536];
+
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// A scripted server response
+    struct Response {
+        head: String,
+        body: Vec<u8>,
+        /// Keep the connection open without sending more
+        stall: bool,
+    }
+
+    fn response(head: &str, body: &[u8], stall: bool) -> Response {
+        Response {
+            head: head.to_string(),
+            body: body.to_vec(),
+            stall,
+        }
+    }
+
+    /// Serve one response per connection, in order, and collect the
+    /// requests' headers
+    fn serve(responses: Vec<Response>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&requests);
+        thread::spawn(move || {
+            for (stream, response) in listener.incoming().zip(responses) {
+                let mut stream = stream.unwrap();
+                let mut request = String::new();
+                let mut lines = std::io::BufReader::new(stream.try_clone().unwrap());
+                while lines.read_line(&mut request).unwrap() > 2 && !request.ends_with("\r\n\r\n") {
+                }
+                seen.lock().unwrap().push(request.to_lowercase());
+                thread::spawn(move || {
+                    let head = format!("{}\r\nConnection: close\r\n\r\n", response.head);
+                    stream.write_all(head.as_bytes()).ok();
+                    stream.write_all(&response.body).ok();
+                    stream.flush().ok();
+                    if response.stall {
+                        thread::sleep(STALL_TIMEOUT * 4);
+                    }
+                });
+            }
+        });
+        (url, requests)
+    }
+
+    fn dest(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("voidbox-download-{}-{}", std::process::id(), name));
+        fs::remove_file(&path).ok();
+        fs::remove_file(partial_info_path(&path)).ok();
+        path
+    }
+
+    fn content(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn first_part(body: &[u8], sent: usize, stall: bool) -> Response {
+        response(
+            &format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nETag: \"v1\"",
+                body.len()
+            ),
+            &body[..sent],
+            stall,
+        )
+    }
+
+    fn rest(body: &[u8], from: usize) -> Response {
+        response(
+            &format!(
+                "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\n\
+                 Content-Range: bytes {}-{}/{}\r\nETag: \"v1\"",
+                body.len() - from,
+                from,
+                body.len() - 1,
+                body.len()
+            ),
+            &body[from..],
+            false,
+        )
+    }
+
+    #[test]
+    fn dropped_connection_resumes_with_range_request() {
+        let body = content(1000);
+        let (url, requests) = serve(vec![first_part(&body, 400, false), rest(&body, 400)]);
+        let path = dest("drop");
+
+        download_file(&url, &path, false).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), body);
+        assert!(!partial_info_path(&path).exists());
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(!requests[0].contains("range:"));
+        assert!(requests[1].contains("range: bytes=400-\r\n"));
+        assert!(requests[1].contains("if-range: \"v1\"\r\n"));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn stalled_connection_is_reconnected() {
+        let body = content(1000);
+        let (url, requests) = serve(vec![first_part(&body, 300, true), rest(&body, 300)]);
+        let path = dest("stall");
+
+        let started = Instant::now();
+        download_file(&url, &path, false).unwrap();
+        assert!(started.elapsed() >= STALL_TIMEOUT);
+        assert_eq!(fs::read(&path).unwrap(), body);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].contains("range: bytes=300-\r\n"));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn size_limit_counts_resumed_parts() {
+        // Each response stays under the limit, the file doesn't
+        let part = MAX_DOWNLOAD_SIZE as usize * 3 / 4;
+        let body = content(part);
+        let (url, _) = serve(vec![
+            response(
+                &format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nETag: \"v1\"",
+                    part
+                ),
+                &body[..part / 2],
+                false,
+            ),
+            response("HTTP/1.1 206 Partial Content\r\nETag: \"v1\"", &body, false),
+        ]);
+        let path = dest("limit");
+
+        assert!(matches!(
+            download_file(&url, &path, false),
+            Err(DownloadError::Failed(_))
+        ));
+        assert!(!path.exists());
+        assert!(!partial_info_path(&path).exists());
+    }
+
+    #[test]
+    fn oversized_file_is_refused_before_download() {
+        let (url, _) = serve(vec![response(
+            &format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}",
+                MAX_DOWNLOAD_SIZE + 1
+            ),
+            b"",
+            false,
+        )]);
+        let path = dest("oversized");
+
+        assert!(matches!(
+            download_file(&url, &path, false),
+            Err(DownloadError::Failed(_))
+        ));
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,50 @@
+//! HTTP request helpers shared by all network access
+
+use super::mirror::rewrite_url;
+use super::proxy::{ProxyChoice, proxy_for_url};
+use crate::settings::load_config;
+use ureq::typestate::{WithBody, WithoutBody};
+use ureq::{Agent, Proxy, RequestBuilder};
+
+/// Build an agent using the proxy appropriate for a URL
+fn agent_for(url: &str) -> Agent {
+    let config = load_config().unwrap_or_default();
+
+    let proxy = match proxy_for_url(url, &config.proxy) {
+        ProxyChoice::Default => return Agent::new_with_defaults(),
+        ProxyChoice::Direct => None,
+        ProxyChoice::Proxy(proxy) => match Proxy::new(&proxy) {
+            Ok(p) => Some(p),
+            Err(e) => {
+                eprintln!("[voidbox] Warning: Invalid proxy '{}': {}", proxy, e);
+                None
+            }
+        },
+    };
+
+    Agent::config_builder().proxy(proxy).build().into()
+}
+
+/// Start a GET request with voidbox's User-Agent, mirrors and proxy settings
+pub fn http_get(url: &str) -> RequestBuilder<WithoutBody> {
+    let url = rewrite_url(url);
+    agent_for(&url)
+        .get(&url)
+        .header("User-Agent", crate::APP_NAME)
+}
+
+/// Start a HEAD request with voidbox's User-Agent, mirrors and proxy settings
+pub fn http_head(url: &str) -> RequestBuilder<WithoutBody> {
+    let url = rewrite_url(url);
+    agent_for(&url)
+        .head(&url)
+        .header("User-Agent", crate::APP_NAME)
+}
+
+/// Start a POST request with voidbox's User-Agent, mirrors and proxy settings
+pub fn http_post(url: &str) -> RequestBuilder<WithBody> {
+    let url = rewrite_url(url);
+    agent_for(&url)
+        .post(&url)
+        .header("User-Agent", crate::APP_NAME)
+}
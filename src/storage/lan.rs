@@ -0,0 +1,312 @@
+//! LAN cache sharing between voidbox machines
+//!
+//! `voidbox cache serve` exposes the download cache over HTTP and advertises
+//! it via mDNS (`_voidbox-cache._tcp`, published with avahi). When enabled in
+//! config, downloads that miss the local cache try discovered peers first.
+//!
+//! Peers are not trusted: only downloads with a known SHA-256 (from the
+//! manifest or the checksum database) are fetched from them, and a peer copy
+//! is only used if the transferred file has that hash and the origin server
+//! confirms (with a conditional request against the peer's
+//! ETag/Last-Modified) that it is still current.
+
+use super::cache::CacheEntry;
+use super::download::Validators;
+use super::{http_get, paths};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// mDNS service type advertised by cache servers
+const SERVICE_TYPE: &str = "_voidbox-cache._tcp";
+
+static PEERS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Discover cache peers on the local network (once per process)
+fn peers() -> &'static [String] {
+    PEERS.get_or_init(discover_peers)
+}
+
+fn discover_peers() -> Vec<String> {
+    // -r resolve, -p parsable, -t terminate after the initial dump
+    let output = match Command::new("avahi-browse")
+        .args(["-rpt", SERVICE_TYPE])
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(output) => output,
+        Err(_) => return Vec::new(),
+    };
+
+    // =;iface;proto;name;type;domain;hostname;address;port;txt
+    let mut peers: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| line.starts_with('='))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(';').collect();
+            let (proto, address, port) = (fields.get(2)?, fields.get(7)?, fields.get(8)?);
+            Some(if *proto == "IPv6" {
+                format!("[{}]:{}", address, port)
+            } else {
+                format!("{}:{}", address, port)
+            })
+        })
+        .collect();
+    peers.sort();
+    peers.dedup();
+    peers
+}
+
+/// Try to fetch a URL whose SHA-256 is known from a LAN peer into `dest`
+///
+/// Returns the journal entry for the fetched file on success.
+pub(crate) fn fetch_from_peers(
+    url: &str,
+    key: &str,
+    sha256: &str,
+    dest: &Path,
+) -> Option<CacheEntry> {
+    for peer in peers() {
+        match fetch_from_peer(peer, url, key, sha256, dest) {
+            Ok(Some(entry)) => {
+                say!("[voidbox] Fetched from LAN peer {}", peer);
+                return Some(entry);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                fs::remove_file(dest).ok();
+                say!("[voidbox] Warning: LAN peer {} failed: {}", peer, e);
+            }
+        }
+    }
+    None
+}
+
+fn fetch_from_peer(
+    peer: &str,
+    url: &str,
+    key: &str,
+    sha256: &str,
+    dest: &Path,
+) -> Result<Option<CacheEntry>, String> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .proxy(None)
+        .timeout_connect(Some(Duration::from_secs(2)))
+        .build()
+        .into();
+
+    let Ok(mut resp) = agent.get(format!("http://{}/entry/{}", peer, key)).call() else {
+        return Ok(None);
+    };
+    let mut entry: CacheEntry = resp
+        .body_mut()
+        .read_json()
+        .map_err(|e| format!("bad entry: {}", e))?;
+
+    if entry.url != url || !origin_confirms(url, &entry) {
+        return Ok(None);
+    }
+
+    let mut resp = agent
+        .get(format!("http://{}/blob/{}", peer, key))
+        .call()
+        .map_err(|e| e.to_string())?;
+    let reader = resp
+        .body_mut()
+        .with_config()
+        .limit(entry.size.saturating_add(1))
+        .reader();
+
+    // The journal records what was received, not what the peer claimed
+    entry.size = receive_verified(reader, dest, sha256)?;
+    entry.sha256 = sha256.to_ascii_lowercase();
+    Ok(Some(entry))
+}
+
+/// Copy a peer's file into `dest`, failing unless it has the expected
+/// SHA-256; returns its size
+fn receive_verified(mut reader: impl Read, dest: &Path, sha256: &str) -> Result<u64, String> {
+    let mut out = File::create(dest).map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    let mut buffer = vec![0u8; 65536];
+    loop {
+        let n = reader.read(&mut buffer).map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        out.write_all(&buffer[..n]).map_err(|e| e.to_string())?;
+        hasher.update(&buffer[..n]);
+        size += n as u64;
+    }
+
+    if !hex::encode(hasher.finalize()).eq_ignore_ascii_case(sha256) {
+        fs::remove_file(dest).ok();
+        return Err("hash mismatch".to_string());
+    }
+    Ok(size)
+}
+
+/// Ask the origin whether the peer's copy is still current
+fn origin_confirms(url: &str, entry: &CacheEntry) -> bool {
+    let validators = Validators {
+        etag: entry.etag.clone(),
+        last_modified: entry.last_modified.clone(),
+    };
+    if validators.is_empty() {
+        return false;
+    }
+
+    let mut req = http_get(url);
+    if let Some(etag) = &validators.etag {
+        req = req.header("If-None-Match", etag);
+    }
+    if let Some(last_modified) = &validators.last_modified {
+        req = req.header("If-Modified-Since", last_modified);
+    }
+
+    req.call().is_ok_and(|resp| resp.status() == 304)
+}
+
+/// Serve the local download cache to LAN peers until interrupted
+pub fn serve_cache(port: u16) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    say!(
+        "[voidbox] Serving download cache on port {} (Ctrl+C to stop)",
+        port
+    );
+
+    // Advertise for as long as we're serving
+    let hostname = nix::unistd::gethostname()
+        .map(|h| h.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| crate::APP_NAME.to_string());
+    let publisher = Command::new("avahi-publish-service")
+        .args([
+            &format!("voidbox cache on {}", hostname),
+            SERVICE_TYPE,
+            &port.to_string(),
+        ])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut publisher = match publisher {
+        Ok(child) => Some(child),
+        Err(e) => {
+            say!(
+                "[voidbox] Warning: Could not advertise via avahi ({}); peers won't find this cache",
+                e
+            );
+            None
+        }
+    };
+
+    for stream in listener.incoming().flatten() {
+        std::thread::spawn(move || {
+            if let Err(e) = handle_request(stream) {
+                say!("[voidbox] Warning: LAN cache request failed: {}", e);
+            }
+        });
+    }
+
+    if let Some(child) = publisher.as_mut() {
+        child.kill().ok();
+    }
+    Ok(())
+}
+
+fn handle_request(stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let file = match target.trim_start_matches('/').split_once('/') {
+        Some(("entry", key)) if is_cache_key(key) => {
+            Some(paths::download_cache_dir().join(format!("{}.json", key)))
+        }
+        Some(("blob", key)) if is_cache_key(key) => Some(paths::download_cache_dir().join(key)),
+        _ => None,
+    };
+
+    let mut stream = &stream;
+    match file.filter(|f| method == "GET" && f.is_file()) {
+        Some(path) => {
+            let mut file = File::open(path)?;
+            let len = file.metadata()?.len();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                len
+            )?;
+            std::io::copy(&mut file, &mut stream)?;
+        }
+        None => {
+            write!(
+                stream,
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Only serve files named like cache keys (no path traversal)
+fn is_cache_key(key: &str) -> bool {
+    key.len() == 32 && key.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const CONTENT: &[u8] = b"artifact contents";
+
+    fn dest(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("voidbox-lan-{}-{}", std::process::id(), name))
+    }
+
+    fn digest(data: &[u8]) -> String {
+        hex::encode(Sha256::digest(data))
+    }
+
+    #[test]
+    fn peer_copy_with_expected_hash_is_kept() {
+        let path = dest("match");
+        let size = receive_verified(Cursor::new(CONTENT), &path, &digest(CONTENT)).unwrap();
+        assert_eq!(size, CONTENT.len() as u64);
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn expected_hash_is_case_insensitive() {
+        let path = dest("case");
+        let upper = digest(CONTENT).to_ascii_uppercase();
+        assert!(receive_verified(Cursor::new(CONTENT), &path, &upper).is_ok());
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn peer_copy_with_other_hash_is_rejected() {
+        let path = dest("mismatch");
+        let tampered = b"tampered contents";
+        assert!(receive_verified(Cursor::new(tampered), &path, &digest(CONTENT)).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn only_cache_keys_are_served() {
+        assert!(is_cache_key("0123456789abcdef0123456789abcdef"));
+        assert!(!is_cache_key("../../../../etc/passwd"));
+        assert!(!is_cache_key("0123456789abcdef0123456789abcdef.json"));
+    }
+}
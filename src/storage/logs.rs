@@ -0,0 +1,171 @@
+//! Per-app logs
+//!
+//! Each app keeps the output of its installs and updates (`install.log`)
+//! and of its launches (`launch.log`) in `logs/` in its directory. Install
+//! output is copied there while still being printed; a launch from outside
+//! a terminal writes its output only there, since nothing else would see
+//! it. A log that grows past `MAX_LOG_SIZE` is moved to `<name>.1`, so
+//! only recent history is kept.
+
+use crate::storage::paths;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, channel};
+use std::time::Duration;
+
+/// Size past which a log is rotated
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+/// How long to wait for captured output to reach the log once capture
+/// stops (a process started during it may still hold the pipe open)
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// One of an app's logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppLog {
+    /// Output of installs and updates
+    Install,
+    /// Output of the app's launches
+    Launch,
+}
+
+impl AppLog {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            AppLog::Install => "install.log",
+            AppLog::Launch => "launch.log",
+        }
+    }
+}
+
+/// Path of one of an app's logs
+pub fn app_log_path(app_name: &str, log: AppLog) -> PathBuf {
+    paths::app_logs_dir(app_name).join(log.file_name())
+}
+
+/// Open an app's log for appending, rotating it first if it is too big
+pub fn open_app_log(app_name: &str, log: AppLog) -> io::Result<File> {
+    let path = app_log_path(app_name, log);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_SIZE) {
+        fs::rename(&path, path.with_extension("log.1"))?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Append a timestamped line to an app's log
+pub fn append_app_log(app_name: &str, log: AppLog, message: &str) {
+    let line = format!(
+        "=== {} {}\n",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+        message
+    );
+    if let Err(e) = open_app_log(app_name, log).and_then(|mut f| f.write_all(line.as_bytes())) {
+        eprintln!(
+            "[voidbox] Warning: Could not write the log of {}: {}",
+            app_name, e
+        );
+    }
+}
+
+/// Copies everything printed to stdout into an app's log until dropped
+pub struct OutputCapture {
+    /// The stdout in place before capture started (None if it was closed)
+    saved: Option<File>,
+    drained: Receiver<()>,
+}
+
+/// Start copying stdout into an app's log; None if that isn't possible,
+/// in which case output is only printed as usual
+pub fn capture_stdout(app_name: &str, log: AppLog) -> Option<OutputCapture> {
+    let mut file = open_app_log(app_name, log).ok()?;
+    io::stdout().flush().ok()?;
+
+    let (done, drained) = channel();
+
+    // SAFETY: F_DUPFD_CLOEXEC duplicates stdout; File owns the new
+    // descriptor, which children don't inherit
+    let saved = unsafe { libc::fcntl(libc::STDOUT_FILENO, libc::F_DUPFD_CLOEXEC, 0) };
+    if saved < 0 {
+        // Started without a terminal, stdout may be closed: output then
+        // only goes to the log
+        // SAFETY: dup2 points stdout at the log; `file` closes its own
+        // descriptor when dropped
+        if unsafe { libc::dup2(file.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+            return None;
+        }
+        done.send(()).ok();
+        return Some(OutputCapture {
+            saved: None,
+            drained,
+        });
+    }
+    let saved = unsafe { File::from_raw_fd(saved) };
+    let mut terminal = saved.try_clone().ok()?;
+
+    let mut fds = [0; 2];
+    // SAFETY: pipe2 fills in two new descriptors, owned below
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return None;
+    }
+    let mut reader = unsafe { File::from_raw_fd(fds[0]) };
+    let writer = unsafe { File::from_raw_fd(fds[1]) };
+    // SAFETY: dup2 points stdout at the pipe (children inherit it, as they
+    // did stdout); `writer` closes its own descriptor when dropped
+    if unsafe { libc::dup2(writer.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        return None;
+    }
+    drop(writer);
+
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    terminal.write_all(&buf[..n]).ok();
+                    file.write_all(&buf[..n]).ok();
+                }
+            }
+        }
+        done.send(()).ok();
+    });
+
+    Some(OutputCapture {
+        saved: Some(saved),
+        drained,
+    })
+}
+
+impl Drop for OutputCapture {
+    fn drop(&mut self) {
+        io::stdout().flush().ok();
+        // SAFETY: dup2 points stdout back at the saved descriptor, closing
+        // the last write end of the pipe so the copying thread finishes (or
+        // close leaves stdout closed, as it was)
+        match &self.saved {
+            Some(saved) => unsafe { libc::dup2(saved.as_raw_fd(), libc::STDOUT_FILENO) },
+            None => unsafe { libc::close(libc::STDOUT_FILENO) },
+        };
+        self.drained.recv_timeout(DRAIN_TIMEOUT).ok();
+    }
+}
+
+/// Send this process's stdout and stderr, and so those of anything it
+/// starts, to an app's log
+pub fn redirect_output(app_name: &str, log: AppLog) -> io::Result<()> {
+    let file = open_app_log(app_name, log)?;
+    io::stdout().flush()?;
+    for fd in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        // SAFETY: dup2 points the descriptor at the log; `file` closes its
+        // own descriptor when dropped
+        if unsafe { libc::dup2(file.as_raw_fd(), fd) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,234 @@
+//! Cached fetching of metadata and images for GUI views
+//!
+//! Views that list many apps need small resources by URL: icons, favicons,
+//! repository metadata. A `MetadataFetcher` fetches them on a few
+//! background threads so the UI thread never waits on the network.
+//! Requests for a URL that is already pending share one fetch, each host
+//! is contacted at most once per `HOST_INTERVAL`, and responses are kept
+//! under `cache/metadata` for `MAX_AGE`, so scrolling back through a
+//! catalog costs nothing. A stale copy is used when a refetch fails.
+
+use super::http::http_get;
+use super::paths;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Background threads fetching in parallel
+const WORKERS: usize = 4;
+
+/// Minimum time between two requests to the same host
+const HOST_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long a cached response is used without refetching
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Largest response accepted (icons and metadata are small)
+const MAX_SIZE: u64 = 2 * 1024 * 1024;
+
+/// Time allowed for one fetch
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// State of a requested URL
+#[derive(Debug, Clone)]
+pub enum FetchState {
+    Pending,
+    Ready(Arc<Vec<u8>>),
+    Failed(String),
+}
+
+struct Shared {
+    states: Mutex<HashMap<String, FetchState>>,
+    /// Earliest time the next request to each host may start
+    next_slot: Mutex<HashMap<String, Instant>>,
+    /// Called after a fetch finishes (e.g. to request a repaint)
+    notify: Box<dyn Fn() + Send + Sync>,
+}
+
+/// Background fetcher with an on-disk cache, request coalescing and
+/// per-host rate limiting
+pub struct MetadataFetcher {
+    shared: Arc<Shared>,
+    sender: Sender<String>,
+}
+
+impl MetadataFetcher {
+    /// Start the worker threads; `notify` runs on a worker after each fetch
+    pub fn new(notify: impl Fn() + Send + Sync + 'static) -> Self {
+        let shared = Arc::new(Shared {
+            states: Mutex::new(HashMap::new()),
+            next_slot: Mutex::new(HashMap::new()),
+            notify: Box::new(notify),
+        });
+        let (sender, receiver) = channel::<String>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..WORKERS {
+            let shared = Arc::clone(&shared);
+            let receiver = Arc::clone(&receiver);
+            thread::spawn(move || worker(&shared, &receiver));
+        }
+        Self { shared, sender }
+    }
+
+    /// Current state of `url`, queueing a fetch the first time it is asked
+    /// for
+    ///
+    /// Never blocks on the network; call it every frame.
+    pub fn get(&self, url: &str) -> FetchState {
+        let mut states = self.shared.states.lock().unwrap();
+        if let Some(state) = states.get(url) {
+            return state.clone();
+        }
+        states.insert(url.to_string(), FetchState::Pending);
+        if self.sender.send(url.to_string()).is_err() {
+            let failed = FetchState::Failed("fetcher stopped".into());
+            states.insert(url.to_string(), failed.clone());
+            return failed;
+        }
+        FetchState::Pending
+    }
+}
+
+fn worker(shared: &Shared, receiver: &Mutex<Receiver<String>>) {
+    loop {
+        // The lock is only held while waiting for the next URL
+        let Ok(url) = receiver.lock().unwrap().recv() else {
+            return;
+        };
+        let state = match fetch_cached(shared, &url) {
+            Ok(bytes) => FetchState::Ready(Arc::new(bytes)),
+            Err(e) => FetchState::Failed(e),
+        };
+        shared.states.lock().unwrap().insert(url, state);
+        (shared.notify)();
+    }
+}
+
+fn cache_path(url: &str) -> PathBuf {
+    let digest = Sha256::digest(url.as_bytes());
+    paths::metadata_cache_dir().join(hex::encode(digest))
+}
+
+fn age(path: &std::path::Path) -> Option<Duration> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    SystemTime::now().duration_since(modified).ok()
+}
+
+/// The cached response for `url`, fetching it if missing or expired
+fn fetch_cached(shared: &Shared, url: &str) -> Result<Vec<u8>, String> {
+    let path = cache_path(url);
+    if age(&path).is_some_and(|age| age < MAX_AGE)
+        && let Ok(bytes) = fs::read(&path)
+    {
+        return Ok(bytes);
+    }
+
+    wait_for_host(shared, url);
+    match fetch(url) {
+        Ok(bytes) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).ok();
+            }
+            // Write then rename so a reader never sees a partial file
+            let partial = path.with_extension("part");
+            if fs::write(&partial, &bytes).is_ok() {
+                fs::rename(&partial, &path).ok();
+            }
+            Ok(bytes)
+        }
+        // A stale copy beats nothing
+        Err(e) => fs::read(&path).map_err(|_| e),
+    }
+}
+
+/// Sleep until this request's slot for the URL's host
+fn wait_for_host(shared: &Shared, url: &str) {
+    let host = url
+        .split("://")
+        .nth(1)
+        .and_then(|rest| rest.split('/').next())
+        .unwrap_or(url)
+        .to_string();
+    let start = {
+        let mut slots = shared.next_slot.lock().unwrap();
+        let now = Instant::now();
+        let start = slots
+            .get(&host)
+            .copied()
+            .filter(|s| *s > now)
+            .unwrap_or(now);
+        slots.insert(host, start + HOST_INTERVAL);
+        start
+    };
+    let wait = start.saturating_duration_since(Instant::now());
+    if !wait.is_zero() {
+        thread::sleep(wait);
+    }
+}
+
+fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    let mut resp = http_get(url)
+        .config()
+        .timeout_global(Some(FETCH_TIMEOUT))
+        .build()
+        .call()
+        .map_err(|e| e.to_string())?;
+    resp.body_mut()
+        .with_config()
+        .limit(MAX_SIZE)
+        .read_to_vec()
+        .map_err(|e| e.to_string())
+}
+
+/// Favicon URL for a web page's site
+pub fn favicon_url(page_url: &str) -> Option<String> {
+    let (scheme, rest) = page_url.split_once("://")?;
+    let host = rest.split('/').next().filter(|h| !h.is_empty())?;
+    Some(format!("{}://{}/favicon.ico", scheme, host))
+}
+
+/// Number of cached responses and their total size
+pub fn metadata_cache_usage() -> std::io::Result<(usize, u64)> {
+    let dir = paths::metadata_cache_dir();
+    if !dir.exists() {
+        return Ok((0, 0));
+    }
+    let mut count = 0;
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            count += 1;
+            size += metadata.len();
+        }
+    }
+    Ok((count, size))
+}
+
+/// Delete cached responses, all of them or only expired ones; returns the
+/// number deleted and the bytes freed
+pub fn prune_metadata_cache(all: bool) -> std::io::Result<(usize, u64)> {
+    let dir = paths::metadata_cache_dir();
+    if !dir.exists() {
+        return Ok((0, 0));
+    }
+    let mut count = 0;
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let expired = age(&path).is_none_or(|age| age >= MAX_AGE);
+        if all || expired {
+            let len = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            if fs::remove_file(&path).is_ok() {
+                count += 1;
+                size += len;
+            }
+        }
+    }
+    Ok((count, size))
+}
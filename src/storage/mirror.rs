@@ -0,0 +1,46 @@
+//! Mirror rewrite rules for corporate networks
+//!
+//! `[[mirrors]]` entries in config rewrite artifact and index URLs before
+//! they are requested, so downloads can be redirected transparently to an
+//! internal mirror. The first matching rule wins.
+
+use crate::settings::load_config;
+use regex::Regex;
+use std::sync::OnceLock;
+
+static RULES: OnceLock<Vec<(Regex, String)>> = OnceLock::new();
+
+/// Compile the configured rules once per process
+fn rules() -> &'static [(Regex, String)] {
+    RULES.get_or_init(|| {
+        let config = load_config().unwrap_or_default();
+        config
+            .mirrors
+            .into_iter()
+            .filter_map(|rule| match Regex::new(&rule.from) {
+                Ok(re) => Some((re, rule.to)),
+                Err(e) => {
+                    eprintln!(
+                        "[voidbox] Warning: Invalid mirror rule '{}': {}",
+                        rule.from, e
+                    );
+                    None
+                }
+            })
+            .collect()
+    })
+}
+
+/// Apply the first matching mirror rule to a URL
+pub fn rewrite_url(url: &str) -> String {
+    for (from, to) in rules() {
+        if from.is_match(url) {
+            let rewritten = from.replace(url, to.as_str()).into_owned();
+            if crate::is_verbose() {
+                say!("[voidbox] Mirror: {} -> {}", url, rewritten);
+            }
+            return rewritten;
+        }
+    }
+    url.to_string()
+}
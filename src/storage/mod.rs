@@ -1,7 +1,31 @@
 //! Storage management for Voidbox
 
+mod cache;
 mod download;
+mod http;
+mod lan;
+mod logs;
+mod metadata;
+mod mirror;
+mod network;
+mod pac;
 pub mod paths;
+mod proxy;
+mod scan;
+mod snapshot;
+mod txlog;
 
+pub use cache::*;
 pub use download::*;
+pub use http::*;
+pub use lan::*;
+pub use logs::*;
+pub use metadata::*;
+pub use mirror::*;
+pub use network::*;
+pub use pac::*;
 pub use paths::*;
+pub use proxy::*;
+pub use scan::*;
+pub use snapshot::*;
+pub use txlog::*;
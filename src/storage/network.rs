@@ -0,0 +1,59 @@
+//! Metered connection detection
+//!
+//! Background operations (maintenance runs and `--background` updates)
+//! leave large downloads for later while the connection is metered, such as
+//! a phone hotspot. `[network] metered` in config.toml decides; when it is
+//! unset, NetworkManager is asked over D-Bus. Without NetworkManager the
+//! connection is taken to be unmetered.
+
+use crate::settings::load_config;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// NetworkManager's NMMetered values meaning metered
+const NM_METERED_YES: u32 = 1;
+const NM_METERED_GUESS_YES: u32 = 3;
+
+/// Whether the connection is metered (checked once per process)
+pub fn is_metered() -> bool {
+    static METERED: OnceLock<bool> = OnceLock::new();
+    *METERED.get_or_init(
+        || match load_config().ok().and_then(|c| c.network.metered) {
+            Some(metered) => metered,
+            None => networkmanager_metered().unwrap_or(false),
+        },
+    )
+}
+
+/// NetworkManager's view of the primary connection
+fn networkmanager_metered() -> Option<bool> {
+    let output = Command::new("gdbus")
+        .args([
+            "call",
+            "--system",
+            "--dest",
+            "org.freedesktop.NetworkManager",
+            "--object-path",
+            "/org/freedesktop/NetworkManager",
+            "--method",
+            "org.freedesktop.DBus.Properties.Get",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    // Printed as "(<uint32 4>,)"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let value: u32 = stdout
+        .split_whitespace()
+        .nth(1)?
+        .trim_end_matches(['>', ',', ')'])
+        .parse()
+        .ok()?;
+    Some(matches!(value, NM_METERED_YES | NM_METERED_GUESS_YES))
+}
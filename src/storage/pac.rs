@@ -0,0 +1,423 @@
+//! Minimal PAC (proxy auto-config) evaluator
+//!
+//! Rather than embedding a JavaScript engine, this evaluates a documented
+//! subset of `FindProxyForURL` that covers the common corporate scripts:
+//!
+//! - `if (<cond>) return "<result>";` (with optional braces and `else`)
+//! - a final `return "<result>";`
+//! - conditions combining `||`, `&&`, `!` and parentheses over
+//!   `isPlainHostName(host)`, `dnsDomainIs(host, "...")`,
+//!   `localHostOrDomainIs(host, "...")` and `shExpMatch(host|url, "...")`
+//!
+//! Scripts using anything else (variables, DNS lookups, `isInNet`, ...)
+//! are rejected with `PacError::Unsupported`.
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PacError {
+    #[error("FindProxyForURL not found in PAC script")]
+    NoFunction,
+
+    #[error("Unsupported PAC construct: {0}")]
+    Unsupported(String),
+
+    #[error("PAC script did not return a result")]
+    NoResult,
+}
+
+/// Result of evaluating a PAC script for a URL - the first usable entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PacResult {
+    Direct,
+    /// HTTP proxy as host:port
+    Proxy(String),
+    /// SOCKS proxy as host:port
+    Socks(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+    Semi,
+    Not,
+    And,
+    Or,
+}
+
+enum Expr {
+    Call(String, Vec<Arg>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+enum Arg {
+    Var(String),
+    Str(String),
+}
+
+enum Stmt {
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    Return(String),
+}
+
+/// Evaluate a PAC script for the given URL and host
+pub fn evaluate_pac(script: &str, url: &str, host: &str) -> Result<PacResult, PacError> {
+    let body = function_body(script)?;
+    let tokens = tokenize(&body)?;
+    let mut parser = Parser { tokens, pos: 0 };
+
+    let mut stmts = Vec::new();
+    while parser.peek().is_some() {
+        stmts.push(parser.statement()?);
+    }
+
+    let result = exec(&stmts, url, host)?.ok_or(PacError::NoResult)?;
+    Ok(parse_result(&result))
+}
+
+/// Pick the first entry of a PAC result string like "PROXY a:1; DIRECT"
+fn parse_result(result: &str) -> PacResult {
+    for entry in result.split(';') {
+        let mut parts = entry.split_whitespace();
+        match (parts.next().map(|p| p.to_uppercase()), parts.next()) {
+            (Some(kind), Some(addr)) if kind == "PROXY" || kind == "HTTP" => {
+                return PacResult::Proxy(addr.to_string());
+            }
+            (Some(kind), Some(addr)) if kind.starts_with("SOCKS") => {
+                return PacResult::Socks(addr.to_string());
+            }
+            (Some(kind), None) if kind == "DIRECT" => return PacResult::Direct,
+            _ => continue,
+        }
+    }
+    PacResult::Direct
+}
+
+/// Extract the body of FindProxyForURL (comments removed)
+fn function_body(script: &str) -> Result<String, PacError> {
+    let script = strip_comments(script);
+    let start = script.find("FindProxyForURL").ok_or(PacError::NoFunction)?;
+    let open = script[start..].find('{').ok_or(PacError::NoFunction)? + start;
+
+    let mut depth = 0;
+    for (i, c) in script[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(script[open + 1..open + i].to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Err(PacError::NoFunction)
+}
+
+fn strip_comments(script: &str) -> String {
+    let mut out = String::with_capacity(script.len());
+    let mut chars = script.chars().peekable();
+    let mut in_str: Option<char> = None;
+
+    while let Some(c) = chars.next() {
+        if let Some(q) = in_str {
+            out.push(c);
+            if c == q {
+                in_str = None;
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"' | '\'', _) => {
+                in_str = Some(c);
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+fn tokenize(body: &str) -> Result<Vec<Token>, PacError> {
+    let mut tokens = Vec::new();
+    let mut chars = body.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | '{' | '}' | ',' | ';' | '!' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    '{' => Token::LBrace,
+                    '}' => Token::RBrace,
+                    ',' => Token::Comma,
+                    ';' => Token::Semi,
+                    _ => Token::Not,
+                });
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.next() != Some(c) {
+                    return Err(PacError::Unsupported(format!("operator '{}'", c)));
+                }
+                tokens.push(if c == '&' { Token::And } else { Token::Or });
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut s = String::new();
+                for ch in chars.by_ref() {
+                    if ch == c {
+                        break;
+                    }
+                    s.push(ch);
+                }
+                tokens.push(Token::Str(s));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_alphanumeric() || ch == '_' || ch == '.' {
+                        ident.push(ch);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            other => return Err(PacError::Unsupported(format!("character '{}'", other))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), PacError> {
+        match self.next() {
+            Some(t) if t == expected => Ok(()),
+            other => Err(PacError::Unsupported(format!(
+                "expected {:?}, found {:?}",
+                expected, other
+            ))),
+        }
+    }
+
+    fn statement(&mut self) -> Result<Stmt, PacError> {
+        match self.next() {
+            Some(Token::Ident(kw)) if kw == "return" => {
+                let result = match self.next() {
+                    Some(Token::Str(s)) => s,
+                    other => {
+                        return Err(PacError::Unsupported(format!("return of {:?}", other)));
+                    }
+                };
+                if self.peek() == Some(&Token::Semi) {
+                    self.next();
+                }
+                Ok(Stmt::Return(result))
+            }
+            Some(Token::Ident(kw)) if kw == "if" => {
+                self.expect(Token::LParen)?;
+                let cond = self.expr()?;
+                self.expect(Token::RParen)?;
+                let then = self.block()?;
+                let otherwise = match self.peek() {
+                    Some(Token::Ident(kw)) if kw == "else" => {
+                        self.next();
+                        self.block()?
+                    }
+                    _ => Vec::new(),
+                };
+                Ok(Stmt::If(cond, then, otherwise))
+            }
+            other => Err(PacError::Unsupported(format!("statement {:?}", other))),
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, PacError> {
+        if self.peek() != Some(&Token::LBrace) {
+            return Ok(vec![self.statement()?]);
+        }
+        self.next();
+        let mut stmts = Vec::new();
+        while self.peek() != Some(&Token::RBrace) {
+            if self.peek().is_none() {
+                return Err(PacError::Unsupported("unterminated block".into()));
+            }
+            stmts.push(self.statement()?);
+        }
+        self.next();
+        Ok(stmts)
+    }
+
+    fn expr(&mut self) -> Result<Expr, PacError> {
+        let mut left = self.and_expr()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            left = Expr::Or(Box::new(left), Box::new(self.and_expr()?));
+        }
+        Ok(left)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, PacError> {
+        let mut left = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            left = Expr::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, PacError> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::LParen) => {
+                let inner = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) => {
+                self.expect(Token::LParen)?;
+                let mut args = Vec::new();
+                while self.peek() != Some(&Token::RParen) {
+                    match self.next() {
+                        Some(Token::Ident(v)) => args.push(Arg::Var(v)),
+                        Some(Token::Str(s)) => args.push(Arg::Str(s)),
+                        other => {
+                            return Err(PacError::Unsupported(format!("argument {:?}", other)));
+                        }
+                    }
+                    if self.peek() == Some(&Token::Comma) {
+                        self.next();
+                    }
+                }
+                self.next();
+                Ok(Expr::Call(name, args))
+            }
+            other => Err(PacError::Unsupported(format!("expression {:?}", other))),
+        }
+    }
+}
+
+fn exec(stmts: &[Stmt], url: &str, host: &str) -> Result<Option<String>, PacError> {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Return(result) => return Ok(Some(result.clone())),
+            Stmt::If(cond, then, otherwise) => {
+                let branch = if eval(cond, url, host)? {
+                    then
+                } else {
+                    otherwise
+                };
+                if let Some(result) = exec(branch, url, host)? {
+                    return Ok(Some(result));
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn eval(expr: &Expr, url: &str, host: &str) -> Result<bool, PacError> {
+    match expr {
+        Expr::Not(e) => Ok(!eval(e, url, host)?),
+        Expr::And(a, b) => Ok(eval(a, url, host)? && eval(b, url, host)?),
+        Expr::Or(a, b) => Ok(eval(a, url, host)? || eval(b, url, host)?),
+        Expr::Call(name, args) => {
+            let values = args
+                .iter()
+                .map(|a| match a {
+                    Arg::Str(s) => Ok(s.as_str()),
+                    Arg::Var(v) if v == "host" => Ok(host),
+                    Arg::Var(v) if v == "url" => Ok(url),
+                    Arg::Var(v) => Err(PacError::Unsupported(format!("variable '{}'", v))),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            match (name.as_str(), values.as_slice()) {
+                ("isPlainHostName", [h]) => Ok(!h.contains('.')),
+                ("dnsDomainIs", [h, domain]) => Ok(h.ends_with(domain)),
+                ("localHostOrDomainIs", [h, full]) => {
+                    Ok(h == full || (!h.contains('.') && full.starts_with(&format!("{}.", h))))
+                }
+                ("shExpMatch", [s, pattern]) => Ok(glob_match(pattern, s)),
+                _ => Err(PacError::Unsupported(format!("function '{}'", name))),
+            }
+        }
+    }
+}
+
+/// Shell-style glob match supporting `*` and `?`
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+
+    p[pi..].iter().all(|&c| c == '*')
+}
@@ -1,5 +1,6 @@
 //! Directory path management for Voidbox
 
+use sha2::{Digest, Sha256};
 use std::path::PathBuf;
 
 /// Get the base data directory (~/.local/share/voidbox)
@@ -9,6 +10,79 @@ pub fn data_dir() -> PathBuf {
         .join(crate::APP_NAME)
 }
 
+/// Filesystem-safe identifier for an app name, used for paths and desktop IDs
+///
+/// Names made of lowercase ASCII letters, digits and hyphens are used as-is.
+/// Anything else is transliterated to that alphabet and suffixed with a hash
+/// of the full name, so distinct names can't collide ("Música Player" ->
+/// "musica-player-ad5a1c300e258f64"). Slugs map to themselves, so commands
+/// accept either form; manifests can't use a name shaped like a hashed slug
+/// (see [`is_hashed_slug`]), which would take over another app's slug.
+pub fn app_slug(app_name: &str) -> String {
+    let is_slug = |s: &str| {
+        !s.is_empty()
+            && !s.starts_with('-')
+            && s.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    };
+    if is_slug(app_name) {
+        return app_name.to_string();
+    }
+
+    let mut slug = String::new();
+    for c in app_name.chars().flat_map(|c| c.to_lowercase()) {
+        let mapped = match c {
+            'a'..='z' | '0'..='9' => Some(c),
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => Some('a'),
+            'ç' | 'ć' | 'č' => Some('c'),
+            'ď' | 'đ' => Some('d'),
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => Some('e'),
+            'ğ' => Some('g'),
+            'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' | 'ı' => Some('i'),
+            'ł' | 'ľ' => Some('l'),
+            'ñ' | 'ń' | 'ň' => Some('n'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => Some('o'),
+            'ř' => Some('r'),
+            'ś' | 'š' | 'ş' | 'ß' => Some('s'),
+            'ť' | 'ţ' => Some('t'),
+            'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => Some('u'),
+            'ý' | 'ÿ' => Some('y'),
+            'ź' | 'ż' | 'ž' => Some('z'),
+            _ => None,
+        };
+        match mapped {
+            Some(c) => slug.push(c),
+            None if !slug.is_empty() && !slug.ends_with('-') => slug.push('-'),
+            None => {}
+        }
+    }
+
+    let digest = Sha256::digest(app_name.as_bytes());
+    let base = slug.trim_end_matches('-');
+    let base = if base.is_empty() { "app" } else { base };
+    format!("{}-{}", base, hex::encode(&digest[..SLUG_HASH_BYTES]))
+}
+
+/// Bytes of the name's hash in a slug
+const SLUG_HASH_BYTES: usize = 8;
+
+/// Whether a name has the shape of a slug made for a name that isn't one
+/// (`<base>-<hash>`)
+pub fn is_hashed_slug(name: &str) -> bool {
+    name.rsplit_once('-').is_some_and(|(base, hash)| {
+        !base.is_empty()
+            && hash.len() == SLUG_HASH_BYTES * 2
+            && hash
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+    })
+}
+
+/// Whether two app names refer to the same app (by name or slug)
+pub fn same_app(a: &str, b: &str) -> bool {
+    a == b || app_slug(a) == app_slug(b)
+}
+
 /// Get the bases directory (shared base images)
 pub fn bases_dir() -> PathBuf {
     data_dir().join("bases")
@@ -21,7 +95,7 @@ pub fn apps_dir() -> PathBuf {
 
 /// Get a specific app's directory
 pub fn app_dir(app_name: &str) -> PathBuf {
-    apps_dir().join(app_name)
+    apps_dir().join(app_slug(app_name))
 }
 
 /// Get app's layer directory (for OverlayFS upper layer)
@@ -39,6 +113,74 @@ pub fn app_work_dir(app_name: &str) -> PathBuf {
     app_dir(app_name).join("work")
 }
 
+/// Get app's staging directory (updates waiting to be applied)
+pub fn app_staging_dir(app_name: &str) -> PathBuf {
+    app_dir(app_name).join("staged")
+}
+
+/// Get app's previous version directory (kept by updates for rollback)
+pub fn app_previous_dir(app_name: &str) -> PathBuf {
+    app_dir(app_name).join("previous")
+}
+
+/// Get app's repair directory (a rootfs being rebuilt by `voidbox repair`)
+pub fn app_repair_dir(app_name: &str) -> PathBuf {
+    app_dir(app_name).join("repair")
+}
+
+/// Get app's log directory (install and launch output)
+pub fn app_logs_dir(app_name: &str) -> PathBuf {
+    app_dir(app_name).join("logs")
+}
+
+/// Get app's directory of window-rule hints for compositors and taskbars
+pub fn app_window_rules_dir(app_name: &str) -> PathBuf {
+    app_dir(app_name).join("window-rules")
+}
+
+/// Get the file recording the app ID or class the app's windows last had
+pub fn app_window_class_path(app_name: &str) -> PathBuf {
+    app_dir(app_name).join("window-class")
+}
+
+/// Get app's instance lock file (held while the app is running)
+pub fn app_lock_path(app_name: &str) -> PathBuf {
+    app_dir(app_name).join("running.lock")
+}
+
+/// Get app's directory of running instance records
+pub fn app_instances_dir(app_name: &str) -> PathBuf {
+    app_dir(app_name).join("instances")
+}
+
+/// Get app's data directory (the container's home, kept across reinstalls)
+pub fn app_data_dir(app_name: &str) -> PathBuf {
+    app_dir(app_name).join("data")
+}
+
+/// Get app's directory of data snapshots
+pub fn app_snapshots_dir(app_name: &str) -> PathBuf {
+    app_dir(app_name).join("snapshots")
+}
+
+/// File in the live settings directory holding the current permissions
+pub const LIVE_PERMISSIONS_FILE: &str = "permissions.json";
+
+/// Get app's live settings directory (read by the running container)
+pub fn app_live_dir(app_name: &str) -> PathBuf {
+    app_dir(app_name).join("live")
+}
+
+/// Get the directory of stub programs that stand in for app updaters
+pub fn updater_stubs_dir() -> PathBuf {
+    data_dir().join("updater-stubs")
+}
+
+/// Get the marker file for an app whose first run is quarantined
+pub fn app_quarantine_path(app_name: &str) -> PathBuf {
+    app_dir(app_name).join("quarantine")
+}
+
 /// Get the manifests directory
 pub fn manifests_dir() -> PathBuf {
     data_dir().join("manifests")
@@ -46,7 +188,7 @@ pub fn manifests_dir() -> PathBuf {
 
 /// Get a specific app's manifest path
 pub fn manifest_path(app_name: &str) -> PathBuf {
-    manifests_dir().join(format!("{}.toml", app_name))
+    manifests_dir().join(format!("{}.toml", app_slug(app_name)))
 }
 
 /// Get the settings directory (user overrides)
@@ -56,7 +198,12 @@ pub fn settings_dir() -> PathBuf {
 
 /// Get a specific app's settings path
 pub fn app_settings_path(app_name: &str) -> PathBuf {
-    settings_dir().join(format!("{}.toml", app_name))
+    settings_dir().join(format!("{}.toml", app_slug(app_name)))
+}
+
+/// Get a specific app's environment overrides path
+pub fn app_env_path(app_name: &str) -> PathBuf {
+    settings_dir().join(format!("{}.env.toml", app_slug(app_name)))
 }
 
 /// Get the icons directory
@@ -66,7 +213,7 @@ pub fn icons_dir() -> PathBuf {
 
 /// Get a specific app's icon path
 pub fn app_icon_path(app_name: &str) -> PathBuf {
-    icons_dir().join(format!("{}.png", app_name))
+    icons_dir().join(format!("{}.png", app_slug(app_name)))
 }
 
 /// Get the desktop files directory
@@ -76,9 +223,19 @@ pub fn desktop_dir() -> PathBuf {
         .join("applications")
 }
 
-/// Get a specific app's desktop file path
+/// Get a specific app's desktop file path for apps without an app ID
 pub fn app_desktop_path(app_name: &str) -> PathBuf {
-    desktop_dir().join(format!("voidbox-{}.desktop", app_name))
+    desktop_entry_path(&format!("voidbox-{}", app_slug(app_name)))
+}
+
+/// Get the desktop file path for a desktop entry ID
+pub fn desktop_entry_path(desktop_id: &str) -> PathBuf {
+    desktop_dir().join(format!("{}.desktop", desktop_id))
+}
+
+/// Get the path of an app's command wrapper in the bin directory
+pub fn app_wrapper_path(app_name: &str) -> PathBuf {
+    bin_dir().join(app_slug(app_name))
 }
 
 /// Get the bin directory for symlinks
@@ -98,6 +255,73 @@ pub fn database_path() -> PathBuf {
     data_dir().join("installed.json")
 }
 
+/// Get the app list read by third-party launchers
+pub fn launcher_index_path() -> PathBuf {
+    data_dir().join("launchers.json")
+}
+
+/// Get the global config file path
+pub fn config_path() -> PathBuf {
+    data_dir().join("config.toml")
+}
+
+/// Get the directory of removed apps kept for `voidbox undo-remove`
+pub fn removed_dir() -> PathBuf {
+    data_dir().join("removed")
+}
+
+/// Get the directory of AppImages adopted from other tools
+pub fn adopted_dir() -> PathBuf {
+    data_dir().join("adopted")
+}
+
+/// Get the transaction log path
+pub fn transaction_log_path() -> PathBuf {
+    data_dir().join("transactions.jsonl")
+}
+
+/// Get the cache directory
+pub fn cache_dir() -> PathBuf {
+    data_dir().join("cache")
+}
+
+/// Get the download cache directory
+pub fn download_cache_dir() -> PathBuf {
+    cache_dir().join("downloads")
+}
+
+/// Get the cache of fetched icons and metadata
+pub fn metadata_cache_dir() -> PathBuf {
+    cache_dir().join("metadata")
+}
+
+/// Get the cache of fetched repository indexes
+pub fn index_cache_dir() -> PathBuf {
+    cache_dir().join("indexes")
+}
+
+/// Get the local copy of the checksum database
+pub fn checksum_db_path() -> PathBuf {
+    data_dir().join("checksums.toml")
+}
+
+/// Get the state directory (~/.local/state/voidbox)
+pub fn state_dir() -> PathBuf {
+    dirs::state_dir()
+        .map(|d| d.join(crate::APP_NAME))
+        .unwrap_or_else(data_dir)
+}
+
+/// Get the report written by the last `voidbox maintain` run
+pub fn maintenance_report_path() -> PathBuf {
+    state_dir().join("maintenance.json")
+}
+
+/// Get the list of background updates that were started but not finished
+pub fn pending_updates_path() -> PathBuf {
+    state_dir().join("pending-updates.json")
+}
+
 /// Ensure all required directories exist
 pub fn ensure_dirs() -> std::io::Result<()> {
     std::fs::create_dir_all(data_dir())?;
@@ -110,3 +334,50 @@ pub fn ensure_dirs() -> std::io::Result<()> {
     std::fs::create_dir_all(bin_dir())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs_map_to_themselves() {
+        assert_eq!(app_slug("firefox"), "firefox");
+        assert_eq!(app_slug("vs-code-2"), "vs-code-2");
+        let slug = app_slug("Música Player");
+        assert_eq!(slug, "musica-player-ad5a1c300e258f64");
+        assert!(is_hashed_slug(&slug));
+        assert!(!is_hashed_slug("vs-code-2"));
+        assert_eq!(app_slug(&slug), slug);
+        assert!(same_app("Música Player", &slug));
+    }
+
+    #[test]
+    fn names_that_transliterate_alike_do_not_collide() {
+        let names = [
+            "Música Player",
+            "Musica Player",
+            "musica player",
+            "MUSICA-PLAYER",
+        ];
+        let slugs: std::collections::BTreeSet<_> = names.iter().map(|n| app_slug(n)).collect();
+        assert_eq!(slugs.len(), names.len());
+        assert!(!slugs.contains("musica-player"));
+        assert!(!same_app("Música Player", "Musica Player"));
+    }
+
+    #[test]
+    fn slugs_are_single_safe_path_components() {
+        for name in ["../../etc", "a/b", "-rf", "", "日本語", ".hidden"] {
+            let slug = app_slug(name);
+            assert!(!slug.is_empty() && !slug.starts_with('-'), "{:?}", slug);
+            assert!(
+                slug.chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'),
+                "{:?} -> {:?}",
+                name,
+                slug
+            );
+        }
+        assert!(app_slug("日本語").starts_with("app-"));
+    }
+}
@@ -0,0 +1,207 @@
+//! Proxy detection beyond environment variables
+//!
+//! Resolution order for each request:
+//! 1. `mode = "none"` in config disables proxies entirely
+//! 2. `mode = "manual"` uses the configured `url` or `pac_url`
+//! 3. Environment variables (HTTPS_PROXY, ALL_PROXY, ...) - handled by ureq
+//! 4. Desktop settings (GNOME gsettings, KDE kioslaverc), including PAC URLs
+//!
+//! GUI-launched installs don't inherit proxy variables from a shell profile,
+//! so the desktop settings are the only thing that makes them work.
+
+use super::pac::{PacResult, evaluate_pac};
+use crate::settings::{ProxyConfig, ProxyMode};
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+/// Proxy setting read from the desktop environment
+#[derive(Debug, Clone)]
+enum SystemProxy {
+    Direct,
+    Manual(String),
+    Pac(String),
+}
+
+/// Decision for a single request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxyChoice {
+    /// Let ureq use its defaults (environment variables)
+    Default,
+    /// Connect directly
+    Direct,
+    /// Use this proxy URL
+    Proxy(String),
+}
+
+static SYSTEM_PROXY: OnceLock<SystemProxy> = OnceLock::new();
+static PAC_SCRIPTS: OnceLock<Mutex<HashMap<String, Option<String>>>> = OnceLock::new();
+
+/// Decide which proxy to use for a URL
+pub fn proxy_for_url(url: &str, config: &ProxyConfig) -> ProxyChoice {
+    match config.mode {
+        ProxyMode::None => return ProxyChoice::Direct,
+        ProxyMode::Manual => {
+            if let Some(proxy) = &config.url {
+                return ProxyChoice::Proxy(proxy.clone());
+            }
+            if let Some(pac_url) = &config.pac_url {
+                return proxy_from_pac(pac_url, url);
+            }
+            return ProxyChoice::Direct;
+        }
+        ProxyMode::Auto => {}
+    }
+
+    if ureq::Proxy::try_from_env().is_some() {
+        return ProxyChoice::Default;
+    }
+
+    match SYSTEM_PROXY.get_or_init(detect_system_proxy) {
+        SystemProxy::Direct => ProxyChoice::Default,
+        SystemProxy::Manual(proxy) => ProxyChoice::Proxy(proxy.clone()),
+        SystemProxy::Pac(pac_url) => proxy_from_pac(pac_url, url),
+    }
+}
+
+/// Evaluate a PAC script (fetched once per process) for a URL
+fn proxy_from_pac(pac_url: &str, url: &str) -> ProxyChoice {
+    let cache = PAC_SCRIPTS.get_or_init(|| Mutex::new(HashMap::new()));
+    let script = {
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache
+            .entry(pac_url.to_string())
+            .or_insert_with(|| fetch_pac(pac_url))
+            .clone()
+    };
+
+    let Some(script) = script else {
+        return ProxyChoice::Default;
+    };
+
+    match evaluate_pac(&script, url, url_host(url)) {
+        Ok(PacResult::Direct) => ProxyChoice::Direct,
+        Ok(PacResult::Proxy(addr)) => ProxyChoice::Proxy(format!("http://{}", addr)),
+        Ok(PacResult::Socks(addr)) => ProxyChoice::Proxy(format!("socks5://{}", addr)),
+        Err(e) => {
+            eprintln!("[voidbox] Warning: Could not evaluate PAC script: {}", e);
+            ProxyChoice::Default
+        }
+    }
+}
+
+fn fetch_pac(pac_url: &str) -> Option<String> {
+    if let Some(path) = pac_url.strip_prefix("file://") {
+        return std::fs::read_to_string(path).ok();
+    }
+
+    // The PAC script itself is always fetched directly
+    let agent: ureq::Agent = ureq::Agent::config_builder().proxy(None).build().into();
+    agent
+        .get(pac_url)
+        .header("User-Agent", crate::APP_NAME)
+        .call()
+        .ok()?
+        .body_mut()
+        .read_to_string()
+        .ok()
+}
+
+/// Extract the host part of a URL
+fn url_host(url: &str) -> &str {
+    let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let host = authority.rsplit('@').next().unwrap_or(authority);
+    host.split(':').next().unwrap_or(host)
+}
+
+fn detect_system_proxy() -> SystemProxy {
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+
+    let detected = if desktop.to_uppercase().contains("KDE") {
+        kde_proxy().or_else(gnome_proxy)
+    } else {
+        gnome_proxy().or_else(kde_proxy)
+    };
+
+    detected.unwrap_or(SystemProxy::Direct)
+}
+
+fn gsettings(schema: &str, key: &str) -> Option<String> {
+    let output = Command::new("gsettings")
+        .args(["get", schema, key])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let value = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .trim_matches('\'')
+        .to_string();
+    Some(value)
+}
+
+/// Read GNOME proxy settings (org.gnome.system.proxy)
+fn gnome_proxy() -> Option<SystemProxy> {
+    let mode = gsettings("org.gnome.system.proxy", "mode")?;
+
+    match mode.as_str() {
+        "manual" => {
+            for schema in [
+                "org.gnome.system.proxy.https",
+                "org.gnome.system.proxy.http",
+            ] {
+                let host = gsettings(schema, "host").unwrap_or_default();
+                if host.is_empty() {
+                    continue;
+                }
+                let port = gsettings(schema, "port").unwrap_or_else(|| "8080".to_string());
+                return Some(SystemProxy::Manual(format!("http://{}:{}", host, port)));
+            }
+            Some(SystemProxy::Direct)
+        }
+        "auto" => gsettings("org.gnome.system.proxy", "autoconfig-url")
+            .filter(|u| !u.is_empty())
+            .map(SystemProxy::Pac),
+        _ => None,
+    }
+}
+
+/// Read KDE proxy settings (~/.config/kioslaverc)
+fn kde_proxy() -> Option<SystemProxy> {
+    let path = dirs::config_dir()?.join("kioslaverc");
+    let content = std::fs::read_to_string(path).ok()?;
+
+    let mut in_section = false;
+    let mut values: HashMap<String, String> = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_section = line == "[Proxy Settings]";
+            continue;
+        }
+        if in_section && let Some((key, value)) = line.split_once('=') {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+
+    // ProxyType: 0 = none, 1 = manual, 2 = PAC URL, 3 = WPAD, 4 = environment
+    match values.get("ProxyType").map(String::as_str) {
+        Some("1") => {
+            let proxy = values
+                .get("httpsProxy")
+                .or_else(|| values.get("httpProxy"))
+                .filter(|p| !p.is_empty())?;
+            // KDE stores "http://host 8080"
+            Some(SystemProxy::Manual(proxy.replacen(' ', ":", 1)))
+        }
+        Some("2") => values
+            .get("Proxy Config Script")
+            .filter(|u| !u.is_empty())
+            .map(|u| SystemProxy::Pac(u.clone())),
+        Some("3") => Some(SystemProxy::Pac("http://wpad/wpad.dat".to_string())),
+        Some(_) => Some(SystemProxy::Direct),
+        None => None,
+    }
+}
@@ -0,0 +1,78 @@
+//! Malware scan hook for downloaded artifacts
+//!
+//! When `[scan] command` is set in config, every downloaded archive is passed
+//! to the scanner before it is extracted. A detection aborts the install and
+//! evicts the artifact from the download cache. Results are recorded in the
+//! transaction log.
+
+use super::{log_transaction, remove_cached};
+use crate::settings::{ScanConfig, load_config};
+use std::path::Path;
+use std::process::Command;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ScanError {
+    #[error("Malware detected in {artifact}: {report}")]
+    Detected { artifact: String, report: String },
+
+    #[error("Scanner failed: {0}")]
+    ScannerFailed(String),
+}
+
+/// Scan a downloaded artifact if a scanner is configured
+pub fn scan_artifact(app_name: &str, url: &str, path: &Path) -> Result<(), ScanError> {
+    let scan = match load_config() {
+        Ok(config) => config.scan,
+        Err(e) => {
+            return Err(ScanError::ScannerFailed(format!(
+                "could not load config: {}",
+                e
+            )));
+        }
+    };
+
+    let Some(command) = scan.command.as_deref() else {
+        return Ok(());
+    };
+
+    say!("[voidbox] Scanning {}...", url);
+    let result = run_scanner(command, &scan, path);
+
+    match &result {
+        Ok(()) => log_transaction(app_name, "scan", &format!("clean: {}", url)),
+        Err(ScanError::Detected { report, .. }) => {
+            log_transaction(app_name, "scan", &format!("detected: {} ({})", url, report));
+            // Never hand out an infected file from the cache again
+            remove_cached(url);
+        }
+        Err(e) => log_transaction(app_name, "scan", &format!("error: {} ({})", url, e)),
+    }
+
+    result
+}
+
+fn run_scanner(command: &str, scan: &ScanConfig, path: &Path) -> Result<(), ScanError> {
+    let output = Command::new(command)
+        .args(&scan.args)
+        .arg(path)
+        .output()
+        .map_err(|e| ScanError::ScannerFailed(format!("{}: {}", command, e)))?;
+
+    let report = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    match output.status.code() {
+        Some(0) => Ok(()),
+        Some(code) if scan.detected_exit_codes.contains(&code) => Err(ScanError::Detected {
+            artifact: path.display().to_string(),
+            report,
+        }),
+        code => Err(ScanError::ScannerFailed(format!(
+            "{} exited with {}: {}",
+            command,
+            code.map(|c| c.to_string())
+                .unwrap_or_else(|| "signal".to_string()),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    }
+}
@@ -0,0 +1,251 @@
+//! Snapshots of app data directories
+//!
+//! Snapshots live in the app's `snapshots/` directory, named by the time
+//! they were taken. A data directory that is a btrfs subvolume is
+//! snapshotted with `btrfs subvolume snapshot`, which is instant and shares
+//! unchanged blocks; anything else is archived to `<id>.tar.gz`.
+
+use super::paths;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use nix::sys::statfs::{BTRFS_SUPER_MAGIC, statfs};
+use std::fs::{self, File};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("No snapshot '{0}'")]
+    NotFound(String),
+
+    #[error("btrfs failed: {0}")]
+    Btrfs(String),
+}
+
+/// Inode number of the root directory of every btrfs subvolume
+const BTRFS_SUBVOLUME_INODE: u64 = 256;
+
+/// Suffix of archive snapshots
+const ARCHIVE_SUFFIX: &str = ".tar.gz";
+
+/// How a snapshot is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotKind {
+    Btrfs,
+    Archive,
+}
+
+/// A snapshot of an app's data directory
+#[derive(Debug, Clone)]
+pub struct DataSnapshot {
+    /// Creation time, e.g. "20261015-143000"
+    pub id: String,
+    pub kind: SnapshotKind,
+    pub path: PathBuf,
+}
+
+fn on_btrfs(path: &Path) -> bool {
+    statfs(path).is_ok_and(|fs| fs.filesystem_type() == BTRFS_SUPER_MAGIC)
+}
+
+fn is_subvolume(path: &Path) -> bool {
+    on_btrfs(path) && fs::metadata(path).is_ok_and(|m| m.ino() == BTRFS_SUBVOLUME_INODE)
+}
+
+fn btrfs(args: &[&Path]) -> Result<(), SnapshotError> {
+    let mut command = Command::new("btrfs");
+    command.arg("subvolume").args(args);
+    let output = command
+        .stdout(Stdio::null())
+        .output()
+        .map_err(|e| SnapshotError::Btrfs(e.to_string()))?;
+    if !output.status.success() {
+        return Err(SnapshotError::Btrfs(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Create an app's data directory, as a btrfs subvolume when possible so
+/// it can be snapshotted cheaply
+pub fn create_data_dir(data_dir: &Path) -> std::io::Result<()> {
+    if let Some(parent) = data_dir.parent() {
+        fs::create_dir_all(parent)?;
+        if on_btrfs(parent)
+            && which::which("btrfs").is_ok()
+            && btrfs(&[Path::new("create"), data_dir]).is_ok()
+        {
+            return Ok(());
+        }
+    }
+    fs::create_dir_all(data_dir)
+}
+
+/// Whether an app's data can be snapshotted instantly (and so consistently
+/// while the app is running)
+pub fn instant_snapshots(app_name: &str) -> bool {
+    is_subvolume(&paths::app_data_dir(app_name)) && which::which("btrfs").is_ok()
+}
+
+/// Write a directory's contents to a .tar.gz archive
+///
+/// Symlinks are stored as links. The archive only appears at `target` once
+/// it is complete.
+pub fn archive_dir(source: &Path, target: &Path) -> std::io::Result<()> {
+    let partial = target.with_extension("partial");
+    let encoder = GzEncoder::new(File::create(&partial)?, Compression::fast());
+    let mut archive = tar::Builder::new(encoder);
+    archive.follow_symlinks(false);
+    let result = archive
+        .append_dir_all(".", source)
+        .and_then(|()| archive.into_inner()?.finish());
+    if let Err(e) = result {
+        fs::remove_file(&partial).ok();
+        return Err(e);
+    }
+    fs::rename(&partial, target)
+}
+
+/// Snapshots of an app's data, oldest first
+pub fn list_snapshots(app_name: &str) -> Vec<DataSnapshot> {
+    let Ok(entries) = fs::read_dir(paths::app_snapshots_dir(app_name)) else {
+        return Vec::new();
+    };
+
+    let mut snapshots: Vec<DataSnapshot> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = entry.path();
+            if let Some(id) = name.strip_suffix(ARCHIVE_SUFFIX) {
+                Some(DataSnapshot {
+                    id: id.to_string(),
+                    kind: SnapshotKind::Archive,
+                    path,
+                })
+            } else if path.is_dir() {
+                Some(DataSnapshot {
+                    id: name,
+                    kind: SnapshotKind::Btrfs,
+                    path,
+                })
+            } else {
+                None
+            }
+        })
+        .collect();
+    snapshots.sort_by(|a, b| a.id.cmp(&b.id));
+    snapshots
+}
+
+/// Snapshot an app's data directory
+///
+/// Returns `None` if the app has no data yet.
+pub fn snapshot_app_data(app_name: &str) -> Result<Option<DataSnapshot>, SnapshotError> {
+    let data_dir = paths::app_data_dir(app_name);
+    if !data_dir.is_dir() {
+        return Ok(None);
+    }
+
+    let dir = paths::app_snapshots_dir(app_name);
+    fs::create_dir_all(&dir)?;
+    let mut id = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    if list_snapshots(app_name).iter().any(|s| s.id == id) {
+        id.push_str(&format!("-{}", std::process::id()));
+    }
+
+    if instant_snapshots(app_name) {
+        let path = dir.join(&id);
+        btrfs(&[Path::new("snapshot"), &data_dir, &path])?;
+        return Ok(Some(DataSnapshot {
+            id,
+            kind: SnapshotKind::Btrfs,
+            path,
+        }));
+    }
+
+    let path = dir.join(format!("{}{}", id, ARCHIVE_SUFFIX));
+    archive_dir(&data_dir, &path)?;
+
+    Ok(Some(DataSnapshot {
+        id,
+        kind: SnapshotKind::Archive,
+        path,
+    }))
+}
+
+/// Remove a directory that may be a btrfs subvolume
+///
+/// Deleting subvolumes may need privileges; emptying them doesn't.
+fn remove_tree(path: &Path) -> Result<(), SnapshotError> {
+    if is_subvolume(path) && btrfs(&[Path::new("delete"), path]).is_ok() {
+        return Ok(());
+    }
+    fs::remove_dir_all(path)?;
+    Ok(())
+}
+
+/// Delete a snapshot
+pub fn delete_snapshot(snapshot: &DataSnapshot) -> Result<(), SnapshotError> {
+    match snapshot.kind {
+        SnapshotKind::Archive => fs::remove_file(&snapshot.path)?,
+        SnapshotKind::Btrfs => remove_tree(&snapshot.path)?,
+    }
+    Ok(())
+}
+
+/// Remove all but the newest `keep` snapshots of an app's data
+pub fn prune_snapshots(app_name: &str, keep: usize) -> Result<usize, SnapshotError> {
+    let snapshots = list_snapshots(app_name);
+    let excess = snapshots.len().saturating_sub(keep);
+    for snapshot in &snapshots[..excess] {
+        delete_snapshot(snapshot)?;
+    }
+    Ok(excess)
+}
+
+/// Replace an app's data directory with a snapshot
+///
+/// The current data is only removed once the snapshot has been restored.
+pub fn restore_snapshot(app_name: &str, id: &str) -> Result<(), SnapshotError> {
+    let snapshot = list_snapshots(app_name)
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| SnapshotError::NotFound(id.to_string()))?;
+
+    let data_dir = paths::app_data_dir(app_name);
+    let restored = data_dir.with_extension("restore");
+    let previous = data_dir.with_extension("old");
+    for leftover in [&restored, &previous] {
+        if leftover.exists() {
+            remove_tree(leftover)?;
+        }
+    }
+
+    match snapshot.kind {
+        SnapshotKind::Btrfs => btrfs(&[Path::new("snapshot"), &snapshot.path, &restored])?,
+        SnapshotKind::Archive => {
+            create_data_dir(&restored)?;
+            let mut archive = tar::Archive::new(GzDecoder::new(File::open(&snapshot.path)?));
+            archive.set_preserve_permissions(true);
+            archive.unpack(&restored)?;
+        }
+    }
+
+    if data_dir.exists() {
+        fs::rename(&data_dir, &previous)?;
+    }
+    fs::rename(&restored, &data_dir)?;
+    if previous.exists() {
+        remove_tree(&previous)?;
+    }
+
+    Ok(())
+}
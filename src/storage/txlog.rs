@@ -0,0 +1,57 @@
+//! Transaction log - an append-only record of install operations
+//!
+//! Each line of `transactions.jsonl` is one JSON record. Writing is best
+//! effort: a failure to log never fails the operation being logged.
+
+use super::paths;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+/// A single transaction log record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxRecord {
+    pub timestamp: String,
+    pub app: String,
+    pub action: String,
+    pub detail: String,
+}
+
+/// Append a record to the transaction log
+pub fn log_transaction(app: &str, action: &str, detail: &str) {
+    let record = TxRecord {
+        timestamp: chrono::Local::now().to_rfc3339(),
+        app: app.to_string(),
+        action: action.to_string(),
+        detail: detail.to_string(),
+    };
+
+    if let Err(e) = append_record(&record) {
+        say!("[voidbox] Warning: Could not write transaction log: {}", e);
+    }
+}
+
+fn append_record(record: &TxRecord) -> std::io::Result<()> {
+    let path = paths::transaction_log_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let line = serde_json::to_string(record)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)
+}
+
+/// Read all records from the transaction log
+pub fn read_transactions() -> std::io::Result<Vec<TxRecord>> {
+    let path = paths::transaction_log_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}